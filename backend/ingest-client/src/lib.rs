@@ -0,0 +1,311 @@
+//! Store-and-forward ingest client for FOD Detection Backend
+//! Queues detections in a local SQLite file and delivers them to `POST /events/ingest` with idempotency keys once the server is reachable
+//!
+//! ```no_run
+//! # async fn run(detection: fod_ingest_client::Detection) -> Result<(), fod_ingest_client::Error> {
+//! use fod_ingest_client::{Client, Config};
+//! use std::sync::Arc;
+//!
+//! let client = Arc::new(Client::open("sqlite:///var/lib/fod/queue.db", Config::new("https://fod.example")).await?);
+//! client.clone().spawn();
+//! client.enqueue(&detection).await?;
+//! println!("{} waiting", client.depth().await?.pending);
+//! # Ok(()) }
+//! ```
+
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::SqliteConnectOptions, Executor, SqlitePool};
+use std::{fmt, str::FromStr, sync::Arc, time::Duration};
+use time::OffsetDateTime;
+use tokio::{sync::Notify, task::JoinHandle};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS queue (
+        seq INTEGER PRIMARY KEY AUTOINCREMENT,
+        idempotency_key TEXT NOT NULL UNIQUE,
+        body TEXT NOT NULL,
+        enqueued_at INTEGER NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        last_error TEXT,
+        -- Refused by the server; kept for inspection, never retried
+        rejected INTEGER NOT NULL DEFAULT 0
+    );
+"#;
+
+/// Statuses worth retrying even though they are client errors
+const RETRYABLE_4XX: [u16; 5] = [401, 403, 408, 409, 429];
+
+// ==================== Config ====================
+
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Backend base URL, e.g. `https://fod.example`
+    pub server: String,
+    /// Device id and shared secret when the backend requires signed requests (`DEVICE_SIGNING`)
+    pub signing: Option<(String, Vec<u8>)>,
+    /// Detections sent per flush before the queue is read again
+    pub batch: i64,
+    /// Wait after a failed flush, doubled on every further failure up to `max_backoff`
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Wait between flushes while the server is reachable
+    pub interval: Duration,
+    pub request_timeout: Duration,
+}
+
+impl Config {
+    pub fn new(server: &str) -> Self {
+        Config {
+            server: server.trim_end_matches('/').to_string(),
+            signing: None,
+            batch: 100,
+            min_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(300),
+            interval: Duration::from_secs(5),
+            request_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Sign requests as `device` with `secret`, matching the backend's `DEVICE_KEYS`
+    pub fn signed(mut self, device: &str, secret: &[u8]) -> Self {
+        self.signing = Some((device.to_string(), secret.to_vec()));
+        self
+    }
+}
+
+// ==================== Models ====================
+
+/// One detection, in the backend's ingest format
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Detection {
+    #[serde(with = "time::serde::rfc3339")]
+    pub ts: OffsetDateTime,
+    pub object_class: String,
+    pub object_count: i32,
+    pub confidence: f32,
+    pub latitude: f32,
+    pub longitude: f32,
+    pub source: String,
+    pub source_ref: String,
+    pub bbox: Option<Value>,
+    pub meta: Option<Value>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Depth {
+    /// Waiting for delivery
+    pub pending: i64,
+    /// Refused by the server
+    pub rejected: i64,
+    /// When the oldest pending detection was queued
+    pub oldest: Option<OffsetDateTime>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Flush {
+    pub delivered: usize,
+    pub rejected: usize,
+    /// Why delivery stopped early (offline, server error); the rest is retried later
+    pub stopped: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Store(sqlx::Error),
+    Encode(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Store(e) => write!(f, "queue store: {}", e),
+            Error::Encode(e) => write!(f, "encode detection: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<sqlx::Error> for Error {
+    fn from(e: sqlx::Error) -> Self {
+        Error::Store(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Encode(e)
+    }
+}
+
+// ==================== Client ====================
+
+pub struct Client {
+    pool: SqlitePool,
+    http: reqwest::Client,
+    config: Config,
+    /// Wakes the delivery loop when something is queued
+    queued: Notify,
+}
+
+impl Client {
+    /// Open (creating if needed) the queue at `url`, e.g. `sqlite:///var/lib/fod/queue.db` or `sqlite::memory:`
+    pub async fn open(url: &str, config: Config) -> Result<Self, Error> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true);
+        // An in-memory database lives and dies with its connection
+        let max = if url.contains(":memory:") { 1 } else { 4 };
+        let pool = sqlx::pool::PoolOptions::new().max_connections(max).connect_with(options).await?;
+        pool.execute(SCHEMA).await?;
+        Ok(Client { pool, http: reqwest::Client::new(), config, queued: Notify::new() })
+    }
+
+    /// Queue a detection for delivery, returns its idempotency key
+    pub async fn enqueue(&self, detection: &Detection) -> Result<String, Error> {
+        let key = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO queue (idempotency_key, body, enqueued_at) VALUES (?, ?, ?)")
+            .bind(&key)
+            .bind(serde_json::to_string(detection)?)
+            .bind(OffsetDateTime::now_utc().unix_timestamp())
+            .execute(&self.pool)
+            .await?;
+        self.queued.notify_one();
+        Ok(key)
+    }
+
+    pub async fn depth(&self) -> Result<Depth, Error> {
+        let (pending, rejected, oldest): (i64, i64, Option<i64>) = sqlx::query_as(
+            "SELECT COALESCE(SUM(rejected = 0), 0), COALESCE(SUM(rejected = 1), 0), MIN(CASE WHEN rejected = 0 THEN enqueued_at END)
+             FROM queue",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Depth { pending, rejected, oldest: oldest.and_then(|t| OffsetDateTime::from_unix_timestamp(t).ok()) })
+    }
+
+    /// Deliver queued detections oldest first until the queue is empty or the server can't
+    /// take more. Delivered detections leave the queue; a retry after a lost response reuses
+    /// the key, so the server stores it once.
+    pub async fn flush(&self) -> Result<Flush, Error> {
+        let mut report = Flush::default();
+        loop {
+            let batch: Vec<(i64, String, String)> =
+                sqlx::query_as("SELECT seq, idempotency_key, body FROM queue WHERE rejected = 0 ORDER BY seq LIMIT ?")
+                    .bind(self.config.batch)
+                    .fetch_all(&self.pool)
+                    .await?;
+            if batch.is_empty() {
+                return Ok(report);
+            }
+            for (seq, key, body) in batch {
+                let status = match self.send(&key, body).await {
+                    Ok(status) => status,
+                    Err(e) => {
+                        let reason = format!("server unreachable: {}", e);
+                        self.failed(seq, &reason, false).await?;
+                        report.stopped = Some(reason);
+                        return Ok(report);
+                    }
+                };
+                if status.is_success() {
+                    sqlx::query("DELETE FROM queue WHERE seq = ?").bind(seq).execute(&self.pool).await?;
+                    report.delivered += 1;
+                } else if status.is_client_error() && !RETRYABLE_4XX.contains(&status.as_u16()) {
+                    // The detection itself is refused; retrying won't help and would block the queue
+                    warn!(key = %key, %status, "detection rejected by server");
+                    self.failed(seq, &format!("server returned {}", status), true).await?;
+                    report.rejected += 1;
+                } else {
+                    let reason = format!("server returned {}", status);
+                    self.failed(seq, &reason, false).await?;
+                    report.stopped = Some(reason);
+                    return Ok(report);
+                }
+            }
+        }
+    }
+
+    async fn send(&self, key: &str, body: String) -> Result<reqwest::StatusCode, reqwest::Error> {
+        let mut req = self
+            .http
+            .post(format!("{}/events/ingest", self.config.server))
+            .timeout(self.config.request_timeout)
+            .header("content-type", "application/json")
+            .header("idempotency-key", key);
+        if let Some((device, secret)) = &self.config.signing {
+            for (name, value) in sign(device, secret, "/events/ingest", body.as_bytes()) {
+                req = req.header(name, value);
+            }
+        }
+        Ok(req.body(body).send().await?.status())
+    }
+
+    async fn failed(&self, seq: i64, reason: &str, rejected: bool) -> Result<(), Error> {
+        sqlx::query("UPDATE queue SET attempts = attempts + 1, last_error = ?, rejected = ? WHERE seq = ?")
+            .bind(reason)
+            .bind(rejected as i64)
+            .bind(seq)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Deliver in the background: flush every `interval` (sooner when something is queued),
+    /// backing off exponentially while the server is unreachable
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut backoff = self.config.min_backoff;
+            loop {
+                let wait = match self.flush().await {
+                    Ok(Flush { stopped: None, delivered, rejected }) => {
+                        if delivered > 0 || rejected > 0 {
+                            info!(delivered, rejected, "queued detections delivered");
+                        }
+                        backoff = self.config.min_backoff;
+                        self.config.interval
+                    }
+                    Ok(Flush { stopped: Some(reason), .. }) => {
+                        warn!(%reason, retry_in_secs = backoff.as_secs(), "detection delivery paused");
+                        let wait = backoff;
+                        backoff = (backoff * 2).min(self.config.max_backoff);
+                        wait
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "detection queue unreadable");
+                        self.config.max_backoff
+                    }
+                };
+                // While backing off only time wakes the loop, otherwise new detections do too
+                if backoff == self.config.min_backoff {
+                    let _ = tokio::time::timeout(wait, self.queued.notified()).await;
+                } else {
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        })
+    }
+}
+
+// ==================== Signing ====================
+
+/// Headers of the backend's device signature scheme: HMAC-SHA256 over
+/// METHOD \n path \n timestamp \n nonce \n body sha256 (hex)
+fn sign(device: &str, secret: &[u8], path: &str, body: &[u8]) -> Vec<(&'static str, String)> {
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp().to_string();
+    let nonce = Uuid::new_v4().simple().to_string();
+    let digest = format!("{:x}", Sha256::digest(body));
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(format!("POST\n{}\n{}\n{}\n{}", path, timestamp, nonce, digest).as_bytes());
+    let signature: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    vec![
+        ("x-device-id", device.to_string()),
+        ("x-timestamp", timestamp),
+        ("x-nonce", nonce),
+        ("x-content-sha256", digest),
+        ("x-signature", signature),
+    ]
+}
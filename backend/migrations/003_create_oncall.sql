@@ -0,0 +1,36 @@
+-- Migration 003: On-call rotations for notification routing
+-- A rotation cycles through its members every shift_hours starting at starts_at;
+-- overrides (shift swaps) take precedence inside their time window
+
+CREATE TABLE IF NOT EXISTS oncall_rotations (
+    id          SERIAL       PRIMARY KEY,
+    name        VARCHAR(100) NOT NULL UNIQUE,
+    shift_hours INTEGER      NOT NULL CHECK (shift_hours > 0),
+    starts_at   TIMESTAMP WITH TIME ZONE NOT NULL,
+    created_at  TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS oncall_members (
+    rotation_id INTEGER      NOT NULL REFERENCES oncall_rotations(id) ON DELETE CASCADE,
+    position    INTEGER      NOT NULL,
+    user_id     UUID         REFERENCES users(id) ON DELETE CASCADE,
+    channel     VARCHAR(255),
+    PRIMARY KEY (rotation_id, position),
+    CHECK (user_id IS NOT NULL OR channel IS NOT NULL)
+);
+
+CREATE TABLE IF NOT EXISTS oncall_overrides (
+    id          SERIAL       PRIMARY KEY,
+    rotation_id INTEGER      NOT NULL REFERENCES oncall_rotations(id) ON DELETE CASCADE,
+    user_id     UUID         REFERENCES users(id) ON DELETE CASCADE,
+    channel     VARCHAR(255),
+    starts_at   TIMESTAMP WITH TIME ZONE NOT NULL,
+    ends_at     TIMESTAMP WITH TIME ZONE NOT NULL,
+    reason      TEXT,
+    created_by  VARCHAR(100),
+    created_at  TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CHECK (ends_at > starts_at),
+    CHECK (user_id IS NOT NULL OR channel IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_oncall_overrides_window ON oncall_overrides (rotation_id, starts_at, ends_at);
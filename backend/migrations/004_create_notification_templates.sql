@@ -0,0 +1,23 @@
+-- Migration 004: Notification templates per channel and language
+-- Bodies are minijinja templates rendered with `event` and `site` variables
+
+CREATE TABLE IF NOT EXISTS notification_templates (
+    id         SERIAL       PRIMARY KEY,
+    name       VARCHAR(100) NOT NULL,
+    channel    VARCHAR(50)  NOT NULL,
+    locale     VARCHAR(10)  NOT NULL DEFAULT 'en',
+    subject    TEXT,
+    body       TEXT         NOT NULL,
+    updated_by VARCHAR(100),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (name, channel, locale)
+);
+
+INSERT INTO notification_templates (name, channel, locale, subject, body) VALUES
+    ('fod_detected', 'sms', 'en', NULL,
+     'FOD {{ event.class_name }} ({{ (event.confidence * 100) | round | int }}%) at {{ event.latitude }},{{ event.longitude }} via {{ event.source_ref }} - {{ site.name }}'),
+    ('fod_detected', 'sms', 'th', NULL,
+     'พบ FOD {{ event.class_name }} ({{ (event.confidence * 100) | round | int }}%) ที่ {{ event.latitude }},{{ event.longitude }} จาก {{ event.source_ref }} - {{ site.name }}'),
+    ('fod_detected', 'email', 'en', '[{{ site.name }}] FOD detected: {{ event.class_name }}',
+     E'A {{ event.class_name }} was detected at {{ event.ts }} with {{ (event.confidence * 100) | round | int }}% confidence.\nLocation: {{ event.latitude }}, {{ event.longitude }}\nSource: {{ event.source }} / {{ event.source_ref }}')
+ON CONFLICT (name, channel, locale) DO NOTHING;
@@ -0,0 +1,12 @@
+-- Migration 005: Per-user notification subscriptions
+-- An empty filter array matches everything; channels lists where the user wants delivery
+
+CREATE TABLE IF NOT EXISTS user_subscriptions (
+    user_id    UUID    PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    classes    TEXT[]  NOT NULL DEFAULT '{}',
+    zones      TEXT[]  NOT NULL DEFAULT '{}',
+    severities TEXT[]  NOT NULL DEFAULT '{}',
+    channels   TEXT[]  NOT NULL DEFAULT '{}',
+    enabled    BOOLEAN NOT NULL DEFAULT TRUE,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,12 @@
+-- Migration 007: Record of personal-data erasure requests
+-- The subject itself is not stored, only its SHA-256, so the log holds no PII
+
+CREATE TABLE IF NOT EXISTS erasures (
+    id             SERIAL       PRIMARY KEY,
+    requested_by   VARCHAR(100) NOT NULL,
+    reason         TEXT,
+    subject_sha256 VARCHAR(64),
+    criteria       JSONB        NOT NULL,
+    events_matched INTEGER      NOT NULL,
+    created_at     TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,16 @@
+-- Migration 008: Aircraft movements for FOD-per-movement KPIs
+-- FOD events are attributed to a runway through meta->>'runway'
+
+CREATE TABLE IF NOT EXISTS aircraft_movements (
+    id         BIGSERIAL    PRIMARY KEY,
+    ts         TIMESTAMP WITH TIME ZONE NOT NULL,
+    runway     VARCHAR(20)  NOT NULL,
+    kind       VARCHAR(10)  NOT NULL CHECK (kind IN ('arrival', 'departure')),
+    flight     VARCHAR(20),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Re-importing the same schedule must not double count
+CREATE UNIQUE INDEX IF NOT EXISTS idx_movements_unique ON aircraft_movements (ts, runway, kind, COALESCE(flight, ''));
+CREATE INDEX IF NOT EXISTS idx_movements_runway_ts ON aircraft_movements (runway, ts);
+CREATE INDEX IF NOT EXISTS idx_events_runway ON events ((meta->>'runway'), ts);
@@ -0,0 +1,90 @@
+-- Migration 009: Hourly and daily (UTC) event rollups
+-- Maintained by triggers on insert/update; deleting or archiving raw events leaves them intact
+
+CREATE TABLE IF NOT EXISTS event_rollups_hourly (
+    bucket         TIMESTAMP WITH TIME ZONE NOT NULL,
+    class_id       INTEGER      NOT NULL REFERENCES fod_classes(id),
+    zone           VARCHAR(255) NOT NULL DEFAULT '',
+    source_ref     VARCHAR(255) NOT NULL,
+    events         BIGINT       NOT NULL DEFAULT 0,
+    objects        BIGINT       NOT NULL DEFAULT 0,
+    confidence_sum DOUBLE PRECISION NOT NULL DEFAULT 0,
+    PRIMARY KEY (bucket, class_id, zone, source_ref)
+);
+
+CREATE TABLE IF NOT EXISTS event_rollups_daily (
+    bucket         TIMESTAMP WITH TIME ZONE NOT NULL,
+    class_id       INTEGER      NOT NULL REFERENCES fod_classes(id),
+    zone           VARCHAR(255) NOT NULL DEFAULT '',
+    source_ref     VARCHAR(255) NOT NULL,
+    events         BIGINT       NOT NULL DEFAULT 0,
+    objects        BIGINT       NOT NULL DEFAULT 0,
+    confidence_sum DOUBLE PRECISION NOT NULL DEFAULT 0,
+    PRIMARY KEY (bucket, class_id, zone, source_ref)
+);
+
+-- Zone comes from meta.zone when it is plain text (encrypted values don't roll up by zone)
+CREATE OR REPLACE FUNCTION event_rollup_zone(meta JSONB) RETURNS TEXT AS $$
+    SELECT CASE WHEN jsonb_typeof(meta->'zone') = 'string' THEN LEFT(meta->>'zone', 255) ELSE '' END
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION event_rollup_add(
+    p_ts TIMESTAMP WITH TIME ZONE, p_class_id INTEGER, p_zone TEXT, p_source_ref TEXT,
+    p_sign INTEGER, p_objects INTEGER, p_confidence REAL
+) RETURNS VOID AS $$
+BEGIN
+    INSERT INTO event_rollups_hourly AS r (bucket, class_id, zone, source_ref, events, objects, confidence_sum)
+    VALUES (date_trunc('hour', p_ts AT TIME ZONE 'UTC') AT TIME ZONE 'UTC', p_class_id, p_zone, p_source_ref,
+            p_sign, p_sign * p_objects, p_sign * p_confidence)
+    ON CONFLICT (bucket, class_id, zone, source_ref) DO UPDATE
+        SET events = r.events + EXCLUDED.events,
+            objects = r.objects + EXCLUDED.objects,
+            confidence_sum = r.confidence_sum + EXCLUDED.confidence_sum;
+
+    INSERT INTO event_rollups_daily AS r (bucket, class_id, zone, source_ref, events, objects, confidence_sum)
+    VALUES (date_trunc('day', p_ts AT TIME ZONE 'UTC') AT TIME ZONE 'UTC', p_class_id, p_zone, p_source_ref,
+            p_sign, p_sign * p_objects, p_sign * p_confidence)
+    ON CONFLICT (bucket, class_id, zone, source_ref) DO UPDATE
+        SET events = r.events + EXCLUDED.events,
+            objects = r.objects + EXCLUDED.objects,
+            confidence_sum = r.confidence_sum + EXCLUDED.confidence_sum;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION events_rollup_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        -- Erasure and key rotation rewrite rows; only move counts when a rolled-up value changed
+        IF (OLD.ts, OLD.class_id, event_rollup_zone(OLD.meta), OLD.source_ref, OLD.object_count, OLD.confidence)
+           IS NOT DISTINCT FROM
+           (NEW.ts, NEW.class_id, event_rollup_zone(NEW.meta), NEW.source_ref, NEW.object_count, NEW.confidence) THEN
+            RETURN NEW;
+        END IF;
+        PERFORM event_rollup_add(OLD.ts, OLD.class_id, event_rollup_zone(OLD.meta), OLD.source_ref,
+                                 -1, OLD.object_count, OLD.confidence);
+    END IF;
+    PERFORM event_rollup_add(NEW.ts, NEW.class_id, event_rollup_zone(NEW.meta), NEW.source_ref,
+                             1, NEW.object_count, NEW.confidence);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS events_rollup ON events;
+CREATE TRIGGER events_rollup
+    AFTER INSERT OR UPDATE ON events
+    FOR EACH ROW EXECUTE FUNCTION events_rollup_trigger();
+
+-- Backfill from existing events
+INSERT INTO event_rollups_hourly (bucket, class_id, zone, source_ref, events, objects, confidence_sum)
+SELECT date_trunc('hour', ts AT TIME ZONE 'UTC') AT TIME ZONE 'UTC', class_id, event_rollup_zone(meta), source_ref,
+       COUNT(*), SUM(object_count), SUM(confidence)
+FROM events
+GROUP BY 1, 2, 3, 4
+ON CONFLICT DO NOTHING;
+
+INSERT INTO event_rollups_daily (bucket, class_id, zone, source_ref, events, objects, confidence_sum)
+SELECT date_trunc('day', ts AT TIME ZONE 'UTC') AT TIME ZONE 'UTC', class_id, event_rollup_zone(meta), source_ref,
+       COUNT(*), SUM(object_count), SUM(confidence)
+FROM events
+GROUP BY 1, 2, 3, 4
+ON CONFLICT DO NOTHING;
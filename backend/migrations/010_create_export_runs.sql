@@ -0,0 +1,18 @@
+-- Migration 010: Daily event snapshot exports to object storage
+-- One row per attempt; the scheduler retries a day until it has a successful run
+
+CREATE TABLE IF NOT EXISTS export_runs (
+    id          SERIAL       PRIMARY KEY,
+    day         DATE         NOT NULL,
+    trigger     VARCHAR(20)  NOT NULL CHECK (trigger IN ('schedule', 'manual')),
+    status      VARCHAR(20)  NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'success', 'failed')),
+    object_key  TEXT         NOT NULL,
+    rows        BIGINT,
+    bytes       BIGINT,
+    error       TEXT,
+    started_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_export_runs_day ON export_runs (day, status);
+CREATE INDEX IF NOT EXISTS idx_export_runs_started ON export_runs (started_at DESC);
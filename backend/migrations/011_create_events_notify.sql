@@ -0,0 +1,26 @@
+-- Migration 011: Broadcast inserted events over LISTEN/NOTIFY
+-- Every backend instance listens on `fod_events` and relays to its SSE/WebSocket clients,
+-- so inserts from any writer (other instances, imports, legacy tools) reach all streams
+
+CREATE OR REPLACE FUNCTION events_notify_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    -- Payloads are capped at 8000 bytes by Postgres, so bbox/meta are left out
+    PERFORM pg_notify('fod_events', json_build_object(
+        'id', NEW.id,
+        'ts', NEW.ts,
+        'class_name', (SELECT name FROM fod_classes WHERE id = NEW.class_id),
+        'object_count', NEW.object_count,
+        'confidence', NEW.confidence,
+        'latitude', NEW.latitude,
+        'longitude', NEW.longitude,
+        'source', NEW.source,
+        'source_ref', NEW.source_ref
+    )::TEXT);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS events_notify ON events;
+CREATE TRIGGER events_notify
+    AFTER INSERT ON events
+    FOR EACH ROW EXECUTE FUNCTION events_notify_trigger();
@@ -0,0 +1,22 @@
+-- Migration 012: Operational lifecycle of events
+-- detected → confirmed → dispatched → removed → verified_clear; allowed transitions are
+-- enforced by the backend (lifecycle.rs), every change is kept in event_state_transitions
+
+ALTER TABLE events
+    ADD COLUMN IF NOT EXISTS state VARCHAR(20) NOT NULL DEFAULT 'detected'
+        CHECK (state IN ('detected', 'confirmed', 'dispatched', 'removed', 'verified_clear')),
+    ADD COLUMN IF NOT EXISTS state_changed_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_events_state_ts ON events(state, ts DESC);
+
+CREATE TABLE IF NOT EXISTS event_state_transitions (
+    id         BIGSERIAL    PRIMARY KEY,
+    event_id   UUID         NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    from_state VARCHAR(20)  NOT NULL,
+    to_state   VARCHAR(20)  NOT NULL,
+    changed_by VARCHAR(100) NOT NULL,
+    note       TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_state_transitions_event ON event_state_transitions(event_id, created_at);
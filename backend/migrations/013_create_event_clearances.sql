@@ -0,0 +1,17 @@
+-- Migration 013: Follow-up photos of the cleared spot after FOD removal
+-- Linked to the original event; the AI check result decides verified_clear vs re-dispatch
+
+CREATE TABLE IF NOT EXISTS event_clearances (
+    id              UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_id        UUID         NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    image           BYTEA        NOT NULL,
+    content_type    VARCHAR(100) NOT NULL,
+    filename        VARCHAR(255),
+    uploaded_by     VARCHAR(100) NOT NULL,
+    -- NULL when the image was not run through the AI
+    ai_detections   INTEGER,
+    cleared         BOOLEAN      NOT NULL,
+    created_at      TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_clearances_event ON event_clearances(event_id, created_at);
@@ -0,0 +1,18 @@
+-- Migration 014: Crew tasks, starting with re-inspections after high-severity events close
+-- One task per (event, kind), so the generator can re-run over the same events
+
+CREATE TABLE IF NOT EXISTS tasks (
+    id           UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind         VARCHAR(30)  NOT NULL CHECK (kind IN ('reinspection')),
+    zone         VARCHAR(255),
+    event_id     UUID         REFERENCES events(id) ON DELETE CASCADE,
+    due_at       TIMESTAMP WITH TIME ZONE NOT NULL,
+    status       VARCHAR(20)  NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'done')),
+    completed_by VARCHAR(100),
+    completed_at TIMESTAMP WITH TIME ZONE,
+    note         TEXT,
+    created_at   TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (event_id, kind)
+);
+
+CREATE INDEX IF NOT EXISTS idx_tasks_pending_due ON tasks(due_at) WHERE status = 'pending';
@@ -0,0 +1,20 @@
+-- Migration 015: Construction and maintenance works with their time range and area
+-- `points` keeps the submitted [[lat, lon], ...] ring; `area` is the same ring as a
+-- native polygon (x = longitude, y = latitude) for point-in-polygon tests
+
+CREATE TABLE IF NOT EXISTS activities (
+    id         SERIAL       PRIMARY KEY,
+    name       VARCHAR(255) NOT NULL,
+    kind       VARCHAR(30)  NOT NULL CHECK (kind IN ('construction', 'maintenance')),
+    contractor VARCHAR(255),
+    starts_at  TIMESTAMP WITH TIME ZONE NOT NULL,
+    -- NULL while the works are ongoing
+    ends_at    TIMESTAMP WITH TIME ZONE CHECK (ends_at IS NULL OR ends_at > starts_at),
+    points     JSONB        NOT NULL,
+    area       POLYGON      NOT NULL,
+    created_by VARCHAR(100) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_activities_time ON activities(starts_at, ends_at);
+CREATE INDEX IF NOT EXISTS idx_activities_area ON activities USING GIST (area);
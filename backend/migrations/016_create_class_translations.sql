@@ -0,0 +1,11 @@
+-- Migration 016: Localized display names for FOD classes
+-- fod_classes.name stays the canonical (English) key used in filters and rollups
+
+CREATE TABLE IF NOT EXISTS class_translations (
+    class_id     INTEGER      NOT NULL REFERENCES fod_classes(id) ON DELETE CASCADE,
+    -- Primary language subtag, lowercase: 'th', 'en'
+    lang         VARCHAR(8)   NOT NULL,
+    display_name VARCHAR(255) NOT NULL,
+    updated_at   TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (class_id, lang)
+);
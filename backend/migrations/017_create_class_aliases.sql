@@ -0,0 +1,18 @@
+-- Migration 017: Model label aliases for FOD classes
+-- Labels are looked up by a normalized key (lowercase, runs of space/_/- as one space),
+-- so "metal_part", "Metal-Part" and "metal part" resolve to the same class
+
+CREATE TABLE IF NOT EXISTS class_aliases (
+    alias      VARCHAR(255) PRIMARY KEY,
+    class_id   INTEGER      NOT NULL REFERENCES fod_classes(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_class_aliases_class ON class_aliases(class_id);
+
+-- Existing classes answer to their own normalized name; of two that collide the older wins
+INSERT INTO class_aliases (alias, class_id)
+SELECT LOWER(BTRIM(REGEXP_REPLACE(name, '[\s_-]+', ' ', 'g'))), id
+FROM fod_classes
+ORDER BY id
+ON CONFLICT (alias) DO NOTHING;
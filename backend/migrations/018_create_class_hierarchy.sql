@@ -0,0 +1,21 @@
+-- Migration 018: Parent/child taxonomy over FOD classes ("Metal" → "Bolt", "Wrench")
+-- Group nodes are ordinary classes; deleting a parent makes its children top-level
+
+ALTER TABLE fod_classes ADD COLUMN IF NOT EXISTS parent_id INTEGER REFERENCES fod_classes(id) ON DELETE SET NULL;
+ALTER TABLE fod_classes DROP CONSTRAINT IF EXISTS fod_classes_parent_not_self;
+ALTER TABLE fod_classes ADD CONSTRAINT fod_classes_parent_not_self CHECK (parent_id <> id);
+
+CREATE INDEX IF NOT EXISTS idx_fod_classes_parent ON fod_classes(parent_id);
+
+-- Every class with each of its ancestors, itself included at distance 0.
+-- Cycles are rejected by the API; the depth bound keeps a hand-made one from looping forever.
+CREATE OR REPLACE VIEW class_ancestors AS
+WITH RECURSIVE up AS (
+    SELECT id AS class_id, id AS ancestor_id, 0 AS distance FROM fod_classes
+    UNION ALL
+    SELECT up.class_id, fc.parent_id, up.distance + 1
+    FROM up
+    JOIN fod_classes fc ON fc.id = up.ancestor_id
+    WHERE fc.parent_id IS NOT NULL AND up.distance < 32
+)
+SELECT class_id, ancestor_id, distance FROM up;
@@ -0,0 +1,15 @@
+-- Migration 019: Per-camera calibration for estimating real-world object size
+-- A camera is identified by the source_ref it reports events under
+
+CREATE TABLE IF NOT EXISTS camera_calibrations (
+    source_ref    VARCHAR(255) PRIMARY KEY,
+    -- Ground sample distance: centimetres of ground per image pixel (nadir or near-nadir views)
+    gsd_cm_per_px DOUBLE PRECISION CHECK (gsd_cm_per_px > 0),
+    -- Row-major 3x3 matrix from image pixels to ground-plane metres; preferred over the GSD
+    homography    DOUBLE PRECISION[] CHECK (cardinality(homography) = 9),
+    -- Overrides MIN_OBJECT_SIZE_CM for this camera
+    min_size_cm   DOUBLE PRECISION CHECK (min_size_cm >= 0),
+    updated_by    VARCHAR(255) NOT NULL,
+    updated_at    TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    CHECK (gsd_cm_per_px IS NOT NULL OR homography IS NOT NULL)
+);
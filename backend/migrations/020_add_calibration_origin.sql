@@ -0,0 +1,9 @@
+-- Migration 020: Geo-reference for camera homographies
+-- With an origin, the homography's ground plane is metres east (x) and north (y) of it,
+-- and detections are placed at their projected foot point instead of the camera position
+
+ALTER TABLE camera_calibrations ADD COLUMN IF NOT EXISTS origin_lat DOUBLE PRECISION CHECK (origin_lat BETWEEN -90 AND 90);
+ALTER TABLE camera_calibrations ADD COLUMN IF NOT EXISTS origin_lon DOUBLE PRECISION CHECK (origin_lon BETWEEN -180 AND 180);
+ALTER TABLE camera_calibrations DROP CONSTRAINT IF EXISTS camera_calibrations_origin_pair;
+ALTER TABLE camera_calibrations ADD CONSTRAINT camera_calibrations_origin_pair
+    CHECK ((origin_lat IS NULL) = (origin_lon IS NULL));
@@ -0,0 +1,14 @@
+-- Migration 021: Raw AI responses behind saved events, for debugging the model
+-- One row per inference; kept for RAW_INFERENCE_RETENTION_DAYS, events outlive them
+
+CREATE TABLE IF NOT EXISTS raw_inferences (
+    id         UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- Events saved from this response, one per stored detection
+    event_ids  UUID[]       NOT NULL,
+    source_ref VARCHAR(255),
+    response   JSONB        NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_raw_inferences_event_ids ON raw_inferences USING GIN (event_ids);
+CREATE INDEX IF NOT EXISTS idx_raw_inferences_created_at ON raw_inferences (created_at);
@@ -0,0 +1,31 @@
+-- Migration 022: Re-running archived frames through another model
+-- Frames are kept with their raw response (same retention); replays compare against that response
+
+ALTER TABLE raw_inferences ADD COLUMN IF NOT EXISTS image BYTEA;
+
+CREATE TABLE IF NOT EXISTS replay_runs (
+    id           SERIAL PRIMARY KEY,
+    -- Name from AI_MODEL_URLS, NULL for the default AI service
+    model        VARCHAR(255),
+    range_from   TIMESTAMP WITH TIME ZONE NOT NULL,
+    range_to     TIMESTAMP WITH TIME ZONE NOT NULL,
+    status       VARCHAR(16)  NOT NULL DEFAULT 'running' CHECK (status IN ('running', 'done', 'failed')),
+    frames_total INTEGER      NOT NULL DEFAULT 0,
+    frames_done  INTEGER      NOT NULL DEFAULT 0,
+    error        TEXT,
+    requested_by VARCHAR(255) NOT NULL,
+    started_at   TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    finished_at  TIMESTAMP WITH TIME ZONE
+);
+
+-- Results are kept after the archived frame expires
+CREATE TABLE IF NOT EXISTS replay_results (
+    run_id           INTEGER NOT NULL REFERENCES replay_runs(id) ON DELETE CASCADE,
+    raw_inference_id UUID    NOT NULL,
+    event_ids        UUID[]  NOT NULL,
+    original         JSONB   NOT NULL,
+    replay           JSONB,
+    error            TEXT,
+    created_at       TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (run_id, raw_inference_id)
+);
@@ -0,0 +1,21 @@
+-- Migration 023: Runtime settings changed through /admin/settings, and their audit trail
+-- A key without a row uses the built-in (env) default
+
+CREATE TABLE IF NOT EXISTS settings (
+    key        VARCHAR(64) PRIMARY KEY,
+    value      JSONB        NOT NULL,
+    updated_by VARCHAR(255) NOT NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS setting_changes (
+    id         BIGSERIAL PRIMARY KEY,
+    key        VARCHAR(64)  NOT NULL,
+    -- NULL means the default was in effect
+    old_value  JSONB,
+    new_value  JSONB,
+    changed_by VARCHAR(255) NOT NULL,
+    changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_setting_changes_key ON setting_changes (key, changed_at DESC);
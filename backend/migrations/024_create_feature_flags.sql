@@ -0,0 +1,12 @@
+-- Migration 024: Feature flags toggled through /admin/flags
+-- A flag without a row uses its built-in default; `sites` limits it to those SITE_IDs
+
+CREATE TABLE IF NOT EXISTS feature_flags (
+    name        VARCHAR(64) PRIMARY KEY,
+    enabled     BOOLEAN      NOT NULL,
+    -- NULL = every site
+    sites       TEXT[],
+    description TEXT,
+    updated_by  VARCHAR(255) NOT NULL,
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,6 @@
+-- Migration 025: Reviewers can dismiss a detection as a false positive
+-- Closed like verified_clear; the ratio per source feeds /dashboard/sources
+
+ALTER TABLE events DROP CONSTRAINT IF EXISTS events_state_check;
+ALTER TABLE events ADD CONSTRAINT events_state_check
+    CHECK (state IN ('detected', 'confirmed', 'dispatched', 'removed', 'verified_clear', 'false_positive'));
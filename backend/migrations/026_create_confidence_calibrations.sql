@@ -0,0 +1,15 @@
+-- Migration 026: Per-class confidence calibration (Platt scaling or isotonic regression)
+-- Applied at save time; events keep the model's own score in raw_confidence
+
+CREATE TABLE IF NOT EXISTS confidence_calibrations (
+    class_id   INTEGER PRIMARY KEY REFERENCES fod_classes(id) ON DELETE CASCADE,
+    -- {"method": "platt", "a": .., "b": ..} or {"method": "isotonic", "points": [[x, y], ..]}
+    calibrator JSONB        NOT NULL,
+    -- Reviewed events it was fitted on; NULL when entered by hand
+    samples    INTEGER,
+    fitted_by  VARCHAR(255) NOT NULL,
+    fitted_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- NULL: no calibration applied, `confidence` is the raw score
+ALTER TABLE events ADD COLUMN IF NOT EXISTS raw_confidence REAL;
@@ -0,0 +1,13 @@
+-- Migration 027: Operator comments on events, threaded through parent_id
+
+CREATE TABLE IF NOT EXISTS event_comments (
+    id         BIGSERIAL PRIMARY KEY,
+    event_id   UUID         NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    -- NULL for a top-level comment
+    parent_id  BIGINT       REFERENCES event_comments(id) ON DELETE CASCADE,
+    author     VARCHAR(255) NOT NULL,
+    body       TEXT         NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_comments_event ON event_comments (event_id, id);
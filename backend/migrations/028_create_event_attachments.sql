@@ -0,0 +1,15 @@
+-- Migration 028: Files attached to events (ground crew photos, removal reports)
+-- The bytes live in the object store under object_key; this is the index
+
+CREATE TABLE IF NOT EXISTS event_attachments (
+    id           UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_id     UUID         NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    object_key   TEXT         NOT NULL,
+    content_type VARCHAR(100) NOT NULL,
+    filename     VARCHAR(255),
+    size_bytes   BIGINT       NOT NULL,
+    uploaded_by  VARCHAR(100) NOT NULL,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_attachments_event ON event_attachments(event_id, created_at);
@@ -0,0 +1,7 @@
+-- Migration 029: Insertion order for events
+-- `ts` is when the object was seen and devices may upload late, so /events/changes
+-- pages on this sequence instead
+
+ALTER TABLE events ADD COLUMN IF NOT EXISTS seq BIGSERIAL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_events_seq ON events (seq);
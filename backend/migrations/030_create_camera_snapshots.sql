@@ -0,0 +1,14 @@
+-- Migration 030: Frames grabbed on demand from a camera
+-- A controller double-checking a reported location; the AI result is kept with the image
+
+CREATE TABLE IF NOT EXISTS camera_snapshots (
+    id          UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    source_ref  VARCHAR(255) NOT NULL,
+    image       BYTEA        NOT NULL,
+    -- AI response; NULL when detection was not requested
+    detections  JSONB,
+    taken_by    VARCHAR(100) NOT NULL,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_camera_snapshots_source ON camera_snapshots(source_ref, created_at);
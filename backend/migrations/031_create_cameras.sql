@@ -0,0 +1,17 @@
+-- Migration 031: ONVIF cameras registered from /cameras/discover
+-- Complements CAMERA_SNAPSHOT_URLS / CAMERA_PTZ_URLS; the env wins for a source_ref in both
+
+CREATE TABLE IF NOT EXISTS cameras (
+    source_ref    VARCHAR(255) PRIMARY KEY,
+    name          TEXT,
+    hardware      TEXT,
+    -- ONVIF device service; media and PTZ addresses below come from its GetCapabilities
+    device_url    TEXT         NOT NULL,
+    snapshot_url  TEXT,
+    ptz_url       TEXT,
+    profile       TEXT,
+    -- {username, password} sealed with META_ENCRYPTION_KEYS; NULL for open cameras
+    credentials   JSONB,
+    registered_by VARCHAR(100) NOT NULL,
+    registered_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
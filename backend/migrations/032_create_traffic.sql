@@ -0,0 +1,18 @@
+-- Migration 032: Live ADS-B traffic
+-- Last known state of each aircraft from the SBS feed; rows go stale within a minute and are pruned
+
+CREATE TABLE IF NOT EXISTS traffic (
+    -- 24-bit ICAO address, hex
+    icao              VARCHAR(6)   PRIMARY KEY,
+    callsign          VARCHAR(10),
+    altitude_ft       INTEGER,
+    ground_speed_kt   DOUBLE PRECISION,
+    track             DOUBLE PRECISION,
+    lat               DOUBLE PRECISION,
+    lon               DOUBLE PRECISION,
+    vertical_rate_fpm INTEGER,
+    on_ground         BOOLEAN      NOT NULL DEFAULT FALSE,
+    updated_at        TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_traffic_updated ON traffic(updated_at);
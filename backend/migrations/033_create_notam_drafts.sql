@@ -0,0 +1,19 @@
+-- Migration 033: Draft NOTAMs for runway closures caused by debris
+-- Generated from the event for a duty officer to review and file; the fields are kept next to the text
+
+CREATE TABLE IF NOT EXISTS notam_drafts (
+    id           UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    event_id     UUID         NOT NULL REFERENCES events(id) ON DELETE CASCADE,
+    runway       VARCHAR(20)  NOT NULL,
+    -- Runway-relative position, e.g. 1200M FM THR 01L 15M R OF CL; coordinates when the runway geometry is unknown
+    location     TEXT         NOT NULL,
+    description  TEXT         NOT NULL,
+    valid_from   TIMESTAMP WITH TIME ZONE NOT NULL,
+    valid_until  TIMESTAMP WITH TIME ZONE NOT NULL,
+    text         TEXT         NOT NULL,
+    created_by   VARCHAR(100) NOT NULL,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_notam_drafts_event ON notam_drafts(event_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_notam_drafts_created ON notam_drafts(created_at);
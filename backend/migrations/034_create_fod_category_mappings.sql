@@ -0,0 +1,10 @@
+-- Migration 034: Internal FOD classes mapped to the ACI/ICAO reporting categories
+-- A class without a row takes its nearest mapped ancestor's category, 'other' when there is none
+
+CREATE TABLE IF NOT EXISTS fod_category_mappings (
+    class_id    INTEGER      PRIMARY KEY REFERENCES fod_classes(id) ON DELETE CASCADE,
+    -- Code from categories::CATEGORIES, e.g. 'hardware'
+    category    VARCHAR(50)  NOT NULL,
+    updated_by  VARCHAR(100) NOT NULL,
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
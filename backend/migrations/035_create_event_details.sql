@@ -0,0 +1,57 @@
+-- Migration 035: Move bbox/meta out of events into event_details
+-- List and aggregate queries scan only the narrow events rows; the JSON payloads are joined on
+-- the event detail, export, notification and erasure paths. The meta keys those queries filter
+-- and group on are promoted to plain columns, filled from meta when the event is written.
+
+CREATE TABLE IF NOT EXISTS event_details (
+    event_id UUID PRIMARY KEY REFERENCES events(id) ON DELETE CASCADE,
+    bbox     JSONB,
+    meta     JSONB
+);
+
+-- A plain-text meta value; sealed (encrypted) and structured values are not promoted
+CREATE OR REPLACE FUNCTION event_meta_text(meta JSONB, key TEXT) RETURNS TEXT AS $$
+    SELECT CASE WHEN jsonb_typeof(meta->key) IN ('string', 'number') THEN meta->>key END
+$$ LANGUAGE SQL IMMUTABLE;
+
+ALTER TABLE events
+    ADD COLUMN IF NOT EXISTS severity TEXT,
+    ADD COLUMN IF NOT EXISTS track_id TEXT,
+    ADD COLUMN IF NOT EXISTS runway   TEXT,
+    -- event_rollup_zone(meta), '' when unzoned
+    ADD COLUMN IF NOT EXISTS zone     TEXT NOT NULL DEFAULT '';
+
+INSERT INTO event_details (event_id, bbox, meta)
+SELECT id, bbox, meta FROM events WHERE bbox IS NOT NULL OR meta IS NOT NULL
+ON CONFLICT (event_id) DO NOTHING;
+
+-- Still under the meta-based rollup trigger, which sees no rolled-up value change
+UPDATE events
+SET severity = event_meta_text(meta, 'severity'),
+    track_id = event_meta_text(meta, 'track_id'),
+    runway   = event_meta_text(meta, 'runway'),
+    zone     = event_rollup_zone(meta)
+WHERE meta IS NOT NULL;
+
+CREATE OR REPLACE FUNCTION events_rollup_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'UPDATE' THEN
+        -- Erasure rewrites rows; only move counts when a rolled-up value changed
+        IF (OLD.ts, OLD.class_id, OLD.zone, OLD.source_ref, OLD.object_count, OLD.confidence)
+           IS NOT DISTINCT FROM
+           (NEW.ts, NEW.class_id, NEW.zone, NEW.source_ref, NEW.object_count, NEW.confidence) THEN
+            RETURN NEW;
+        END IF;
+        PERFORM event_rollup_add(OLD.ts, OLD.class_id, OLD.zone, OLD.source_ref,
+                                 -1, OLD.object_count, OLD.confidence);
+    END IF;
+    PERFORM event_rollup_add(NEW.ts, NEW.class_id, NEW.zone, NEW.source_ref,
+                             1, NEW.object_count, NEW.confidence);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Also drops idx_events_runway (migration 008), recreated on the promoted column
+ALTER TABLE events DROP COLUMN IF EXISTS bbox, DROP COLUMN IF EXISTS meta;
+
+CREATE INDEX IF NOT EXISTS idx_events_runway ON events (runway, ts);
@@ -0,0 +1,21 @@
+-- Migration 036: Indexes for the per-source, per-class, track and zone filters
+-- Check they earn their write cost with GET /admin/indexes
+
+-- Camera history and source reliability: one source over a time range
+CREATE INDEX IF NOT EXISTS idx_events_source_ref_ts ON events (source_ref, ts DESC);
+
+-- Class timelines and per-class windows (confidence fitting, distributions)
+CREATE INDEX IF NOT EXISTS idx_events_class_id_ts ON events (class_id, ts DESC);
+
+-- Duplicate-track check on every saved detection and /events/recent?collapse=track
+CREATE INDEX IF NOT EXISTS idx_events_track ON events (source_ref, track_id, ts DESC);
+
+-- `zone=` filters, on the same value the rollups use
+CREATE INDEX IF NOT EXISTS idx_events_zone_ts ON events (zone, ts DESC);
+
+-- Containment lookups on arbitrary meta keys (`meta @> '{"model": "yolov8"}'`)
+CREATE INDEX IF NOT EXISTS idx_event_details_meta ON event_details USING GIN (meta jsonb_path_ops);
+
+-- The composite indexes above lead with these columns
+DROP INDEX IF EXISTS idx_events_source_ref;
+DROP INDEX IF EXISTS idx_events_class_id;
@@ -0,0 +1,10 @@
+-- Migration 037: Responses to ingest requests by Idempotency-Key, so a device retrying after a
+-- lost response gets the original answer instead of a second event
+
+CREATE TABLE IF NOT EXISTS ingest_idempotency (
+    key         VARCHAR(255) PRIMARY KEY,
+    response    JSONB        NOT NULL,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_ingest_idempotency_created_at ON ingest_idempotency (created_at);
@@ -0,0 +1,12 @@
+-- Migration 038: Edge device registry (drones, vehicle units, cameras with an agent)
+-- Kept current by the devices' heartbeats; `id` is the device id used for request signing
+
+CREATE TABLE IF NOT EXISTS devices (
+    id                VARCHAR(100) PRIMARY KEY,
+    model             VARCHAR(100),
+    firmware_version  VARCHAR(50),
+    -- Feature names the firmware supports, e.g. ["track_id", "dedup"]
+    capabilities      JSONB        NOT NULL DEFAULT '[]'::jsonb,
+    first_seen        TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_seen         TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,10 @@
+-- Migration 039: Centrally managed device configuration
+-- Partial documents layered over the built-in defaults: '*' holds the fleet defaults, other rows a
+-- device's own overrides (set before or after the device first checks in)
+
+CREATE TABLE IF NOT EXISTS device_configs (
+    device_id   VARCHAR(100) PRIMARY KEY,
+    overrides   JSONB        NOT NULL DEFAULT '{}'::jsonb,
+    updated_by  VARCHAR(100) NOT NULL,
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
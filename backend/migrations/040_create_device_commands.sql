@@ -0,0 +1,21 @@
+-- Migration 040: Commands queued for devices (capture now, restart detection, upload logs)
+-- Devices long-poll for them and report back; delivered commands without an ack go out again
+
+CREATE TABLE IF NOT EXISTS device_commands (
+    id            UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id     VARCHAR(100) NOT NULL,
+    command       VARCHAR(50)  NOT NULL,
+    params        JSONB        NOT NULL DEFAULT '{}'::jsonb,
+    -- queued -> delivered -> done | failed
+    status        VARCHAR(20)  NOT NULL DEFAULT 'queued',
+    attempts      INTEGER      NOT NULL DEFAULT 0,
+    result        JSONB,
+    created_by    VARCHAR(100) NOT NULL,
+    created_at    TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at    TIMESTAMP WITH TIME ZONE NOT NULL,
+    delivered_at  TIMESTAMP WITH TIME ZONE,
+    acked_at      TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_commands_device ON device_commands(device_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_device_commands_pending ON device_commands(device_id, created_at) WHERE status IN ('queued', 'delivered');
@@ -0,0 +1,16 @@
+-- Migration 041: Log bundles uploaded by devices
+-- The bundles live in the object store; this indexes them per device
+
+CREATE TABLE IF NOT EXISTS device_logs (
+    id            UUID         PRIMARY KEY DEFAULT gen_random_uuid(),
+    device_id     VARCHAR(100) NOT NULL,
+    object_key    TEXT         NOT NULL,
+    content_type  VARCHAR(100) NOT NULL,
+    filename      VARCHAR(255),
+    size_bytes    BIGINT       NOT NULL,
+    -- The upload_logs command this answers, if any
+    command_id    UUID         REFERENCES device_commands(id) ON DELETE SET NULL,
+    created_at    TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_logs_device ON device_logs(device_id, created_at);
@@ -0,0 +1,11 @@
+-- Migration 042: Dependency health sampled over time, for uptime reporting
+-- Samples are aligned to the probe interval, so replicas probing the same slot store it once
+
+CREATE TABLE IF NOT EXISTS health_history (
+    sampled_at  TIMESTAMP WITH TIME ZONE NOT NULL,
+    dependency  VARCHAR(50)  NOT NULL,
+    -- nominal, degraded or down
+    status      VARCHAR(20)  NOT NULL,
+    detail      TEXT,
+    PRIMARY KEY (sampled_at, dependency)
+);
@@ -0,0 +1,19 @@
+-- Migration 043: Synthetic end-to-end probe runs
+-- A known test image sent through the detect pipeline on a schedule; nothing is saved as an event
+
+CREATE TABLE IF NOT EXISTS synthetic_probes (
+    id              BIGSERIAL    PRIMARY KEY,
+    ran_at          TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expected_class  VARCHAR(100) NOT NULL,
+    -- Expected class among the detections
+    detected        BOOLEAN      NOT NULL,
+    confidence      REAL,
+    latency_ms      DOUBLE PRECISION NOT NULL,
+    -- Answered by the fallback model
+    degraded        BOOLEAN      NOT NULL DEFAULT FALSE,
+    -- Detected, in time and without error
+    ok              BOOLEAN      NOT NULL,
+    error           TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_synthetic_probes_ran_at ON synthetic_probes(ran_at);
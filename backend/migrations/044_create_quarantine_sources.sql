@@ -0,0 +1,29 @@
+-- Migration 044: Quarantined sources (synthetic probes, test uploads, untrusted new cameras)
+-- Their events are stored as usual but left out of dashboards, alerts and exports by default
+
+CREATE TABLE IF NOT EXISTS quarantine_sources (
+    source_ref  VARCHAR(255) PRIMARY KEY,
+    reason      TEXT,
+    created_by  VARCHAR(100) NOT NULL,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Live streams drop quarantined events unless asked for them, so the payload says which they are
+CREATE OR REPLACE FUNCTION events_notify_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    -- Payloads are capped at 8000 bytes by Postgres, so bbox/meta are left out
+    PERFORM pg_notify('fod_events', json_build_object(
+        'id', NEW.id,
+        'ts', NEW.ts,
+        'class_name', (SELECT name FROM fod_classes WHERE id = NEW.class_id),
+        'object_count', NEW.object_count,
+        'confidence', NEW.confidence,
+        'latitude', NEW.latitude,
+        'longitude', NEW.longitude,
+        'source', NEW.source,
+        'source_ref', NEW.source_ref,
+        'quarantined', EXISTS (SELECT 1 FROM quarantine_sources WHERE source_ref = NEW.source_ref)
+    )::TEXT);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,11 @@
+-- Migration 045: Record of bulk event purges
+-- One row per executed purge with who ran it, why, and the criteria used
+
+CREATE TABLE IF NOT EXISTS event_purges (
+    id             SERIAL       PRIMARY KEY,
+    requested_by   VARCHAR(100) NOT NULL,
+    reason         TEXT,
+    criteria       JSONB        NOT NULL,
+    events_deleted BIGINT       NOT NULL,
+    created_at     TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
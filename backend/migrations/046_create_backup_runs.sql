@@ -0,0 +1,18 @@
+-- Migration 046: Logical database backups
+-- One row per backup attempt; the data itself lives in object storage under `prefix`
+
+CREATE TABLE IF NOT EXISTS backup_runs (
+    id          UUID         PRIMARY KEY,
+    trigger     VARCHAR(20)  NOT NULL,
+    status      VARCHAR(20)  NOT NULL DEFAULT 'running',
+    prefix      TEXT         NOT NULL,
+    tables      JSONB,
+    rows        BIGINT,
+    bytes       BIGINT,
+    error       TEXT,
+    started_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    finished_at TIMESTAMP WITH TIME ZONE,
+    restored_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX IF NOT EXISTS idx_backup_runs_started ON backup_runs (started_at DESC);
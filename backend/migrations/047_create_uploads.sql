@@ -0,0 +1,16 @@
+-- Migration 047: Direct uploads to object storage
+-- One row per presigned PUT handed to a device; only the SHA-256 of its upload token is stored
+
+CREATE TABLE IF NOT EXISTS uploads (
+    id           UUID         PRIMARY KEY,
+    object_key   TEXT         NOT NULL UNIQUE,
+    content_type VARCHAR(100) NOT NULL,
+    filename     VARCHAR(255),
+    device_id    VARCHAR(255),
+    token_hash   CHAR(64)     NOT NULL,
+    expires_at   TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at      TIMESTAMP WITH TIME ZONE,
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_uploads_expires ON uploads (expires_at);
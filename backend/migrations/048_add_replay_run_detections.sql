@@ -0,0 +1,4 @@
+-- Migration 048: Replay progress
+-- Detections returned so far, kept beside frames_done so progress needs no scan of replay_results
+
+ALTER TABLE replay_runs ADD COLUMN IF NOT EXISTS detections INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,19 @@
+-- Migration 049: Replay cancellation and retries
+-- Cancellation is a flag the worker checks between frames; transient AI failures are retried per
+-- frame under the run's policy, and a failed or cancelled run can be resumed as a new attempt
+
+ALTER TABLE replay_runs DROP CONSTRAINT IF EXISTS replay_runs_status_check;
+ALTER TABLE replay_runs ADD CONSTRAINT replay_runs_status_check CHECK (status IN ('running', 'done', 'failed', 'cancelled'));
+
+ALTER TABLE replay_runs
+    ADD COLUMN IF NOT EXISTS conf                REAL,
+    ADD COLUMN IF NOT EXISTS imgsz               INTEGER,
+    ADD COLUMN IF NOT EXISTS cancel_requested_at TIMESTAMP WITH TIME ZONE,
+    -- Times the run was started: 1 plus each retry
+    ADD COLUMN IF NOT EXISTS attempts            INTEGER NOT NULL DEFAULT 1,
+    -- Policy: AI calls per frame on transient failures, first delay doubling after each
+    ADD COLUMN IF NOT EXISTS max_attempts        INTEGER NOT NULL DEFAULT 3,
+    ADD COLUMN IF NOT EXISTS retry_backoff_ms    INTEGER NOT NULL DEFAULT 500,
+    ADD COLUMN IF NOT EXISTS ai_retries          INTEGER NOT NULL DEFAULT 0;
+
+ALTER TABLE replay_results ADD COLUMN IF NOT EXISTS attempts INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,21 @@
+-- Migration 050: Replay job queue
+-- Runs wait as `queued` until due (`run_at`) and their queue has room, highest priority first.
+-- The worker holding `lease` reports in at `heartbeat_at`; a run whose worker went quiet is queued again
+
+ALTER TABLE replay_runs DROP CONSTRAINT IF EXISTS replay_runs_status_check;
+ALTER TABLE replay_runs ADD CONSTRAINT replay_runs_status_check
+    CHECK (status IN ('queued', 'running', 'done', 'failed', 'cancelled'));
+ALTER TABLE replay_runs ALTER COLUMN status SET DEFAULT 'queued';
+ALTER TABLE replay_runs ALTER COLUMN started_at DROP DEFAULT;
+
+ALTER TABLE replay_runs
+    ADD COLUMN IF NOT EXISTS queue                VARCHAR(50) NOT NULL DEFAULT 'default',
+    ADD COLUMN IF NOT EXISTS priority             INTEGER     NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS run_at               TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN IF NOT EXISTS lease                UUID,
+    ADD COLUMN IF NOT EXISTS heartbeat_at         TIMESTAMP WITH TIME ZONE,
+    -- Set when a more urgent run needs the slot; the worker yields after its current frame
+    ADD COLUMN IF NOT EXISTS preempt_requested_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX IF NOT EXISTS idx_replay_runs_due ON replay_runs (priority DESC, run_at, id) WHERE status = 'queued';
+CREATE INDEX IF NOT EXISTS idx_replay_runs_active ON replay_runs (queue) WHERE status = 'running';
@@ -0,0 +1,7 @@
+-- Migration 051: Stored objects removed by an erasure
+-- Object storage sits outside the transaction, so keys that could not be deleted are kept for follow-up
+
+ALTER TABLE erasures
+    ADD COLUMN IF NOT EXISTS objects_deleted INTEGER NOT NULL DEFAULT 0,
+    -- [{store, key, error}]
+    ADD COLUMN IF NOT EXISTS object_failures JSONB   NOT NULL DEFAULT '[]'::jsonb;
@@ -0,0 +1,4 @@
+-- Migration 052: Rows linked to erased events that an erasure deleted
+-- {attachments, clearances, comments, snapshots}
+
+ALTER TABLE erasures ADD COLUMN IF NOT EXISTS rows_deleted JSONB NOT NULL DEFAULT '{}'::jsonb;
@@ -0,0 +1,65 @@
+-- Migration 053: Alerts raised from events, acknowledged and resolved by the tower
+-- An escalation policy pages its on-call rotations in turn until someone acknowledges
+
+CREATE TABLE IF NOT EXISTS escalation_policies (
+    id         SERIAL       PRIMARY KEY,
+    name       VARCHAR(100) NOT NULL UNIQUE,
+    -- meta.severity values that raise an alert under this policy; empty matches every event
+    severities TEXT[]       NOT NULL DEFAULT '{}',
+    updated_by VARCHAR(100),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS escalation_steps (
+    policy_id     INTEGER NOT NULL REFERENCES escalation_policies(id) ON DELETE CASCADE,
+    position      INTEGER NOT NULL,
+    rotation_id   INTEGER NOT NULL REFERENCES oncall_rotations(id) ON DELETE CASCADE,
+    -- Minutes after the alert was raised, if nobody has acknowledged it by then
+    after_minutes INTEGER NOT NULL CHECK (after_minutes >= 0),
+    PRIMARY KEY (policy_id, position)
+);
+
+CREATE TABLE IF NOT EXISTS alerts (
+    id              BIGSERIAL    PRIMARY KEY,
+    event_id        UUID         NOT NULL UNIQUE REFERENCES events(id) ON DELETE CASCADE,
+    policy_id       INTEGER      REFERENCES escalation_policies(id) ON DELETE SET NULL,
+    severity        VARCHAR(20),
+    status          VARCHAR(20)  NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'acknowledged', 'resolved')),
+    -- Escalation step to page next and when; next_step_at is NULL once nothing more is due
+    next_step       INTEGER      NOT NULL DEFAULT 0,
+    next_step_at    TIMESTAMP WITH TIME ZONE,
+    raised_at       TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    acknowledged_by VARCHAR(100),
+    acknowledged_at TIMESTAMP WITH TIME ZONE,
+    resolved_by     VARCHAR(100),
+    resolved_at     TIMESTAMP WITH TIME ZONE,
+    note            TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_alerts_due ON alerts (next_step_at) WHERE next_step_at IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_alerts_status ON alerts (status, raised_at);
+
+-- Rendered messages, one per recipient and channel; channel senders pick up the pending ones
+CREATE TABLE IF NOT EXISTS alert_notifications (
+    id         BIGSERIAL    PRIMARY KEY,
+    alert_id   BIGINT       NOT NULL REFERENCES alerts(id) ON DELETE CASCADE,
+    -- NULL for subscribers told when the alert was raised
+    step       INTEGER,
+    rotation   VARCHAR(100),
+    user_id    UUID         REFERENCES users(id) ON DELETE SET NULL,
+    username   VARCHAR(100),
+    channel    VARCHAR(255) NOT NULL,
+    subject    TEXT,
+    body       TEXT         NOT NULL,
+    status     VARCHAR(20)  NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'sent', 'failed', 'skipped')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_notifications_alert ON alert_notifications (alert_id, id);
+CREATE INDEX IF NOT EXISTS idx_alert_notifications_pending ON alert_notifications (channel, id) WHERE status = 'pending';
+
+-- Newest event seq already considered for alerts
+CREATE TABLE IF NOT EXISTS alert_cursor (
+    id       BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    last_seq BIGINT  NOT NULL
+);
@@ -0,0 +1,15 @@
+-- Migration 054: Phones registered for push notifications, and delivery results of alert messages
+-- Tokens the push service reports as gone are deleted on the next delivery
+
+CREATE TABLE IF NOT EXISTS push_devices (
+    token        TEXT         PRIMARY KEY,
+    user_id      UUID         NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    platform     VARCHAR(10)  NOT NULL CHECK (platform IN ('fcm', 'apns')),
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_push_devices_user ON push_devices (user_id);
+
+ALTER TABLE alert_notifications
+    ADD COLUMN IF NOT EXISTS error   TEXT,
+    ADD COLUMN IF NOT EXISTS sent_at TIMESTAMP WITH TIME ZONE;
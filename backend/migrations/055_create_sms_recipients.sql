@@ -0,0 +1,13 @@
+-- Migration 055: Phone numbers alert SMS go to, with each recipient's quiet hours
+-- Quiet hours are local to `timezone` (the site timezone when NULL) and may run past midnight
+
+CREATE TABLE IF NOT EXISTS sms_recipients (
+    user_id     UUID         PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- E.164, e.g. +66812345678
+    phone       VARCHAR(16)  NOT NULL,
+    quiet_from  TIME,
+    quiet_to    TIME,
+    timezone    VARCHAR(64),
+    updated_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK ((quiet_from IS NULL) = (quiet_to IS NULL))
+);
@@ -0,0 +1,14 @@
+-- Migration 056: How long a confirmed event may stay uncleared before its alert escalates
+-- '' matches any zone or severity; the most specific row for an event's zone and severity applies
+
+CREATE TABLE IF NOT EXISTS sla_thresholds (
+    zone       VARCHAR(255) NOT NULL DEFAULT '',
+    severity   VARCHAR(20)  NOT NULL DEFAULT '',
+    minutes    INTEGER      NOT NULL CHECK (minutes > 0),
+    updated_by VARCHAR(100),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (zone, severity)
+);
+
+-- Set once, when the event behind the alert overran its threshold
+ALTER TABLE alerts ADD COLUMN IF NOT EXISTS sla_breached_at TIMESTAMP WITH TIME ZONE;
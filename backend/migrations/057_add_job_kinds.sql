@@ -0,0 +1,8 @@
+-- Migration 057: Zone backfills on the replay job queue
+-- A run is a model replay or a zone backfill; the backfill walks events in `seq` order in
+-- chunks of `batch_size`, and `last_seq` is how far it got, so a requeued run picks up there
+
+ALTER TABLE replay_runs
+    ADD COLUMN IF NOT EXISTS kind       VARCHAR(20) NOT NULL DEFAULT 'replay' CHECK (kind IN ('replay', 'zone_backfill')),
+    ADD COLUMN IF NOT EXISTS batch_size INTEGER,
+    ADD COLUMN IF NOT EXISTS last_seq   BIGINT      NOT NULL DEFAULT 0;
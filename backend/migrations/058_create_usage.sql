@@ -0,0 +1,14 @@
+-- Migration 058: Per-key usage, one row per signing device and UTC day
+-- Counted by the usage layer on device routes; monthly quotas are checked against these rows
+
+CREATE TABLE IF NOT EXISTS usage (
+    api_key_id   VARCHAR(255) NOT NULL,
+    day          DATE         NOT NULL,
+    requests     BIGINT       NOT NULL DEFAULT 0,
+    bytes_in     BIGINT       NOT NULL DEFAULT 0,
+    -- Wall time of detect requests (/proxy/detect, /infer/by-ref)
+    inference_ms BIGINT       NOT NULL DEFAULT 0,
+    PRIMARY KEY (api_key_id, day)
+);
+
+CREATE INDEX IF NOT EXISTS idx_usage_day ON usage (day);
@@ -0,0 +1,12 @@
+-- Migration 059: Client certificates bound to devices
+-- A certificate identifies the device it is registered for and no other; keyed by the
+-- SHA-256 of the leaf certificate (DER, hex) as `tls::ClientCert` reports it
+
+CREATE TABLE IF NOT EXISTS device_certificates (
+    fingerprint  VARCHAR(64)  PRIMARY KEY,
+    device_id    VARCHAR(100) NOT NULL,
+    created_by   VARCHAR(255),
+    created_at   TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_device_certificates_device ON device_certificates (device_id);
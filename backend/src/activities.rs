@@ -0,0 +1,284 @@
+//! Construction and maintenance activities for FOD Detection Backend
+//! Works areas with time ranges, and FOD rates inside vs outside them
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use std::{collections::HashMap, env};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tracing::info;
+
+use crate::{auth, db::{self, internal}, perf, quarantine, AppState};
+
+pub const KINDS: [&str; 2] = ["construction", "maintenance"];
+/// Rings larger than this are almost certainly a mistake (and slow to test against)
+const MAX_POINTS: usize = 500;
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct ActivityRequest {
+    pub name: String,
+    pub kind: String,
+    pub contractor: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub starts_at: OffsetDateTime,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub ends_at: Option<OffsetDateTime>,
+    /// Polygon ring as [[lat, lon], ...], at least three points
+    pub polygon: Vec<[f64; 2]>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Activity {
+    pub id: i32,
+    pub name: String,
+    pub kind: String,
+    pub contractor: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub starts_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub ends_at: Option<OffsetDateTime>,
+    #[sqlx(rename = "points")]
+    pub polygon: Value,
+    pub created_by: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+}
+
+/// FOD inside one activity's area while it was active within the window
+#[derive(Serialize, FromRow)]
+pub struct ActivityRate {
+    pub id: i32,
+    pub name: String,
+    pub kind: String,
+    pub contractor: Option<String>,
+    pub area_km2: f64,
+    /// Days the activity was active within the window
+    pub active_days: f64,
+    pub events: i64,
+    pub events_per_day: Option<f64>,
+    pub events_per_km2_day: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct Correlation {
+    pub events_total: i64,
+    /// Events inside at least one area that was active at the time
+    pub events_inside: i64,
+    pub events_outside: i64,
+    pub inside_share: Option<f64>,
+    pub inside_per_km2_day: Option<f64>,
+    /// Needs `SITE_AREA_KM2`
+    pub outside_per_km2_day: Option<f64>,
+    /// inside / outside rate
+    pub rate_ratio: Option<f64>,
+    pub activities: Vec<ActivityRate>,
+}
+
+const ACTIVITY_COLUMNS: &str = "id, name, kind, contractor, starts_at, ends_at, points, created_by, created_at";
+
+// ==================== Validation ====================
+
+/// Ring as Postgres polygon text, x = longitude, y = latitude
+fn polygon_text(points: &[[f64; 2]]) -> Result<String, String> {
+    if points.len() < 3 || points.len() > MAX_POINTS {
+        return Err(format!("polygon needs between 3 and {} points", MAX_POINTS));
+    }
+    if points.iter().any(|[lat, lon]| !(-90.0..=90.0).contains(lat) || !(-180.0..=180.0).contains(lon)) {
+        return Err("polygon points must be [lat, lon] in degrees".to_string());
+    }
+    let pairs: Vec<String> = points.iter().map(|[lat, lon]| format!("({},{})", lon, lat)).collect();
+    Ok(format!("({})", pairs.join(",")))
+}
+
+// ==================== Queries ====================
+
+/// Per-activity FOD over [from, to) and the inside/outside split. Areas are converted
+/// from square degrees at the polygon's latitude, which is plenty at airfield scale.
+pub async fn correlation(
+    db: &PgPool,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    site_area_km2: Option<f64>,
+    include_quarantine: bool,
+) -> Result<Correlation, (StatusCode, String)> {
+    let quarantine = db::quarantine_clause("e", include_quarantine);
+    let sql = format!(
+        r#"
+        WITH a AS (
+            SELECT id, name, kind, contractor, area,
+                   GREATEST(starts_at, $1) AS active_from,
+                   LEAST(COALESCE(ends_at, $2), $2) AS active_to,
+                   (area(path(area)) * 111.32 * 111.32 * COS(RADIANS((point(area))[1])))::FLOAT8 AS area_km2
+            FROM activities
+            WHERE starts_at < $2 AND (ends_at IS NULL OR ends_at > $1)
+        )
+        SELECT a.id, a.name, a.kind, a.contractor, a.area_km2,
+               (EXTRACT(EPOCH FROM a.active_to - a.active_from) / 86400)::FLOAT8 AS active_days,
+               COUNT(e.id) AS events,
+               (COUNT(e.id) / NULLIF(EXTRACT(EPOCH FROM a.active_to - a.active_from) / 86400, 0))::FLOAT8 AS events_per_day,
+               (COUNT(e.id) / NULLIF(a.area_km2 * EXTRACT(EPOCH FROM a.active_to - a.active_from) / 86400, 0))::FLOAT8
+                   AS events_per_km2_day
+        FROM a
+        LEFT JOIN events e
+               ON e.ts >= a.active_from AND e.ts < a.active_to
+              AND a.area @> point(e.longitude, e.latitude){}
+        GROUP BY a.id, a.name, a.kind, a.contractor, a.area_km2, a.active_from, a.active_to
+        ORDER BY events DESC, a.id
+        "#,
+        quarantine
+    );
+    let q = sqlx::query_as::<_, ActivityRate>(&sql).bind(from).bind(to).fetch_all(db);
+    let activities = perf::timed("activity_rates", || format!("from={} to={}", from, to), q).await.map_err(internal)?;
+
+    // Overlapping areas must not count an event twice, so the split is its own query
+    let sql = format!(
+        r#"
+        SELECT COUNT(*),
+               COUNT(*) FILTER (WHERE EXISTS (
+                   SELECT 1 FROM activities a
+                   WHERE a.starts_at <= e.ts AND (a.ends_at IS NULL OR a.ends_at > e.ts)
+                     AND a.area @> point(e.longitude, e.latitude)
+               ))
+        FROM events e
+        WHERE e.ts >= $1 AND e.ts < $2 AND NOT (e.latitude = 0 AND e.longitude = 0){}
+        "#,
+        quarantine
+    );
+    let q = sqlx::query_as::<_, (i64, i64)>(&sql).bind(from).bind(to).fetch_one(db);
+    let (events_total, events_inside) =
+        perf::timed("activity_split", || format!("from={} to={}", from, to), q).await.map_err(internal)?;
+    let events_outside = events_total - events_inside;
+
+    let inside_km2_days: f64 = activities.iter().map(|a| a.area_km2 * a.active_days).sum();
+    let window_days = (to - from).as_seconds_f64() / 86_400.0;
+    let per = |events: i64, km2_days: f64| (km2_days > 0.0).then(|| events as f64 / km2_days);
+    let inside_per_km2_day = per(events_inside, inside_km2_days);
+    let outside_per_km2_day = site_area_km2.and_then(|site| per(events_outside, site * window_days - inside_km2_days));
+    Ok(Correlation {
+        events_total,
+        events_inside,
+        events_outside,
+        inside_share: (events_total > 0).then(|| events_inside as f64 / events_total as f64),
+        inside_per_km2_day,
+        outside_per_km2_day,
+        rate_ratio: match (inside_per_km2_day, outside_per_km2_day) {
+            (Some(i), Some(o)) if o > 0.0 => Some(i / o),
+            _ => None,
+        },
+        activities,
+    })
+}
+
+// ==================== Handlers ====================
+
+/// POST /activities — register a works area
+pub async fn create_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<ActivityRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "name is required".to_string()));
+    }
+    let kind = req.kind.trim().to_lowercase();
+    if !KINDS.contains(&kind.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("kind must be one of {}", KINDS.join(", "))));
+    }
+    if req.ends_at.is_some_and(|end| end <= req.starts_at) {
+        return Err((StatusCode::BAD_REQUEST, "ends_at must be after starts_at".to_string()));
+    }
+    let area = polygon_text(&req.polygon).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let contractor = req.contractor.as_deref().map(str::trim).filter(|s| !s.is_empty());
+
+    let activity = sqlx::query_as::<_, Activity>(&format!(
+        r#"
+        INSERT INTO activities (name, kind, contractor, starts_at, ends_at, points, area, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7::POLYGON, $8)
+        RETURNING {}
+        "#,
+        ACTIVITY_COLUMNS
+    ))
+    .bind(name)
+    .bind(&kind)
+    .bind(contractor)
+    .bind(req.starts_at)
+    .bind(req.ends_at)
+    .bind(serde_json::to_value(&req.polygon).map_err(internal)?)
+    .bind(area)
+    .bind(&claims.username)
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    info!(id = activity.id, name = %activity.name, kind = %activity.kind, "activity registered");
+    Ok((StatusCode::CREATED, Json(activity)))
+}
+
+/// GET /activities?active=true — works areas, newest first; `active` keeps ongoing ones only
+pub async fn list_handler(
+    State(st): State<AppState>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let active_only = q.get("active").is_some_and(|v| v == "true" || v == "1");
+    let rows = sqlx::query_as::<_, Activity>(&format!(
+        r#"
+        SELECT {} FROM activities
+        WHERE NOT $1 OR (starts_at <= NOW() AND (ends_at IS NULL OR ends_at > NOW()))
+        ORDER BY starts_at DESC
+        "#,
+        ACTIVITY_COLUMNS
+    ))
+    .bind(active_only)
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(rows))
+}
+
+/// DELETE /activities/:id — remove an activity entered by mistake (admin)
+pub async fn delete_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let done = sqlx::query("DELETE FROM activities WHERE id = $1").bind(id).execute(&st.db).await.map_err(internal)?;
+    if done.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Activity not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /dashboard/activity-correlation?from=&to= — FOD inside vs outside active works areas
+/// (default last 30 days)
+pub async fn correlation_handler(
+    State(st): State<AppState>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let parse = |k: &str| {
+        q.get(k)
+            .map(|s| OffsetDateTime::parse(s, &Rfc3339).map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid {} timestamp", k))))
+            .transpose()
+    };
+    let to = parse("to")?.unwrap_or_else(OffsetDateTime::now_utc);
+    let from = parse("from")?.unwrap_or(to - Duration::days(30));
+    if from >= to {
+        return Err((StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+    let site_area_km2 = env::var("SITE_AREA_KM2").ok().and_then(|s| s.parse::<f64>().ok()).filter(|&a| a > 0.0);
+    let result = correlation(&st.db, from, to, site_area_km2, quarantine::include(&q)?).await?;
+    Ok(Json(serde_json::json!({
+        "from": from.format(&Rfc3339).unwrap_or_default(),
+        "to": to.format(&Rfc3339).unwrap_or_default(),
+        "correlation": result,
+    })))
+}
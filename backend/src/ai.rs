@@ -0,0 +1,333 @@
+//! AI service adapters for FOD Detection Backend
+//! Builds detect requests and normalizes responses for each vendor's API contract
+
+use axum::http::StatusCode;
+use reqwest::{multipart, Client, RequestBuilder};
+use serde_json::{json, Value};
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use crate::db::internal;
+
+// ==================== Adapters ====================
+
+/// Tuning a caller may pass through to the model
+#[derive(Default, Clone, Copy)]
+pub struct DetectOptions {
+    pub conf: Option<f32>,
+    pub imgsz: Option<i32>,
+    /// Let the backend's fallback answer if it is unreachable
+    pub fallback: bool,
+}
+
+/// One AI vendor's detect contract. Responses are normalized to ours (see README):
+/// `model`, `img_w`, `img_h` and `detections` with `cls`, `conf`, `bbox_xywh`, `bbox_xywh_norm`.
+pub trait Adapter: Send + Sync {
+    /// Name used in `AI_ADAPTER` / `AI_MODEL_URLS`
+    fn name(&self) -> &'static str;
+    /// Path of the detect endpoint when `AI_DETECT_PATH` is not set
+    fn default_path(&self) -> &'static str;
+    /// Attach the image and options to a POST to the detect endpoint
+    fn request(&self, req: RequestBuilder, image: Vec<u8>, filename: String, opts: DetectOptions) -> RequestBuilder;
+    /// Vendor response body to our shape
+    fn parse(&self, body: Value) -> Result<Value, String>;
+}
+
+/// Our own service (`ai/app.py`): multipart `file`, `conf`/`imgsz` query, native response
+pub struct FodV1;
+
+impl Adapter for FodV1 {
+    fn name(&self) -> &'static str {
+        "fod"
+    }
+
+    fn default_path(&self) -> &'static str {
+        "v1/detect"
+    }
+
+    fn request(&self, req: RequestBuilder, image: Vec<u8>, filename: String, opts: DetectOptions) -> RequestBuilder {
+        let part = multipart::Part::bytes(image).file_name(filename).mime_str("image/jpeg").unwrap();
+        let mut query: Vec<(&str, String)> = Vec::new();
+        if let Some(c) = opts.conf {
+            query.push(("conf", c.to_string()));
+        }
+        if let Some(s) = opts.imgsz {
+            query.push(("imgsz", s.to_string()));
+        }
+        req.query(&query).multipart(multipart::Form::new().part("file", part))
+    }
+
+    fn parse(&self, body: Value) -> Result<Value, String> {
+        Ok(body)
+    }
+}
+
+/// `/predict` vendor: multipart `image`, `threshold` query, corner boxes in pixels:
+/// `{"model_version", "image": {"width", "height"}, "predictions": [{"label", "score", "box": {"x1", "y1", "x2", "y2"}}]}`
+pub struct Predict;
+
+impl Adapter for Predict {
+    fn name(&self) -> &'static str {
+        "predict"
+    }
+
+    fn default_path(&self) -> &'static str {
+        "predict"
+    }
+
+    fn request(&self, req: RequestBuilder, image: Vec<u8>, filename: String, opts: DetectOptions) -> RequestBuilder {
+        let part = multipart::Part::bytes(image).file_name(filename).mime_str("image/jpeg").unwrap();
+        let req = match opts.conf {
+            Some(c) => req.query(&[("threshold", c)]),
+            None => req,
+        };
+        req.multipart(multipart::Form::new().part("image", part))
+    }
+
+    fn parse(&self, body: Value) -> Result<Value, String> {
+        let dim = |k: &str| body.get("image").and_then(|i| i.get(k)).and_then(Value::as_f64);
+        let (w, h) = (dim("width"), dim("height"));
+        let predictions = body.get("predictions").and_then(Value::as_array).ok_or("predict response has no predictions")?;
+        let mut detections = Vec::with_capacity(predictions.len());
+        for p in predictions {
+            let label = p.get("label").and_then(Value::as_str).ok_or("prediction without label")?;
+            let score = p.get("score").and_then(Value::as_f64).ok_or("prediction without score")?;
+            let corner = |k: &str| p.get("box").and_then(|b| b.get(k)).and_then(Value::as_f64).ok_or("prediction without box");
+            let (x1, y1, x2, y2) = (corner("x1")?, corner("y1")?, corner("x2")?, corner("y2")?);
+            let xywh = [(x1 + x2) / 2.0, (y1 + y2) / 2.0, (x2 - x1).abs(), (y2 - y1).abs()];
+            let mut d = json!({ "cls": label, "conf": score, "bbox_xywh": xywh });
+            if let (Some(w), Some(h)) = (w.filter(|&w| w > 0.0), h.filter(|&h| h > 0.0)) {
+                d["bbox_xywh_norm"] = json!([xywh[0] / w, xywh[1] / h, xywh[2] / w, xywh[3] / h]);
+            }
+            detections.push(d);
+        }
+        Ok(json!({
+            "model": body.get("model_version").cloned().unwrap_or(Value::Null),
+            "img_w": w,
+            "img_h": h,
+            "detections": detections,
+        }))
+    }
+}
+
+pub fn adapter(name: &str) -> Option<Arc<dyn Adapter>> {
+    match name.trim().to_lowercase().as_str() {
+        "fod" | "" => Some(Arc::new(FodV1)),
+        "predict" => Some(Arc::new(Predict)),
+        _ => None,
+    }
+}
+
+// ==================== Backends ====================
+
+/// An AI service and the contract it speaks
+#[derive(Clone)]
+pub struct Backend {
+    pub base: String,
+    pub path: String,
+    pub adapter: Arc<dyn Adapter>,
+    /// Asked when this service can't be reached; its results are flagged `degraded`
+    pub fallback: Option<Arc<Fallback>>,
+}
+
+/// What answers while the AI service is unreachable
+pub enum Fallback {
+    /// Another service, e.g. a CPU build of the model (`AI_FALLBACK_URL`)
+    Service(Backend),
+    /// A model run inside the backend, which keeps working without any container (`AI_ONNX_MODEL`)
+    #[cfg(feature = "onnx")]
+    Embedded(crate::onnx::Model),
+}
+
+impl Fallback {
+    /// The service, for fallbacks that have to be reached over the network
+    pub fn service(&self) -> Option<&Backend> {
+        match self {
+            Fallback::Service(b) => Some(b),
+            #[cfg(feature = "onnx")]
+            Fallback::Embedded(_) => None,
+        }
+    }
+
+    fn describe(&self) -> &str {
+        match self {
+            Fallback::Service(b) => &b.base,
+            #[cfg(feature = "onnx")]
+            Fallback::Embedded(m) => &m.name,
+        }
+    }
+
+    async fn detect(&self, client: &Client, image: Vec<u8>, filename: String, opts: DetectOptions) -> Result<Value, Failure> {
+        match self {
+            Fallback::Service(b) => b.call(client, image, filename, opts).await,
+            #[cfg(feature = "onnx")]
+            Fallback::Embedded(m) => m.detect(image, opts).await.map_err(Failure::Error),
+        }
+    }
+}
+
+impl Backend {
+    pub fn new(base: &str, adapter: Arc<dyn Adapter>, path: Option<&str>) -> Self {
+        let path = path.map(|p| p.trim_matches('/').to_string()).filter(|p| !p.is_empty());
+        Backend {
+            base: base.trim_end_matches('/').to_string(),
+            path: path.unwrap_or_else(|| adapter.default_path().to_string()),
+            adapter,
+            fallback: None,
+        }
+    }
+
+    pub fn with_fallback(mut self, fallback: Backend) -> Self {
+        self.fallback = Some(Arc::new(Fallback::Service(fallback)));
+        self
+    }
+
+    #[cfg(feature = "onnx")]
+    pub fn with_embedded(mut self, model: crate::onnx::Model) -> Self {
+        self.fallback = Some(Arc::new(Fallback::Embedded(model)));
+        self
+    }
+
+    /// The default service at `base` with `AI_ADAPTER` (default `fod`) and `AI_DETECT_PATH`,
+    /// falling back to the embedded model at `AI_ONNX_MODEL` (`onnx` feature) or else to
+    /// `AI_FALLBACK_URL` (`[adapter@]url`); an unknown adapter name means `fod`
+    pub fn from_env(base: &str) -> Self {
+        let name = env::var("AI_ADAPTER").unwrap_or_default();
+        let adapter = adapter(&name).unwrap_or_else(|| {
+            tracing::warn!(adapter = %name, "unknown AI_ADAPTER, using fod");
+            Arc::new(FodV1)
+        });
+        let backend = Backend::new(base, adapter, env::var("AI_DETECT_PATH").ok().as_deref());
+        if let Some(path) = env::var("AI_ONNX_MODEL").ok().filter(|s| !s.trim().is_empty()) {
+            #[cfg(feature = "onnx")]
+            match crate::onnx::Model::load(path.trim()) {
+                Ok(model) => {
+                    tracing::info!(model = %model.name, "embedded ONNX fallback loaded");
+                    return backend.with_embedded(model);
+                }
+                Err(e) => tracing::error!(error = %e, "AI_ONNX_MODEL not loaded"),
+            }
+            #[cfg(not(feature = "onnx"))]
+            tracing::warn!(path = %path, "AI_ONNX_MODEL is set but this build lacks the onnx feature");
+        }
+        match env::var("AI_FALLBACK_URL").ok().filter(|s| !s.trim().is_empty()) {
+            None => backend,
+            Some(spec) => match Backend::parse(spec.trim()) {
+                Some(fallback) => {
+                    tracing::info!(url = %fallback.base, adapter = fallback.adapter.name(), "AI fallback configured");
+                    backend.with_fallback(fallback)
+                }
+                None => {
+                    tracing::warn!(spec = %spec, "unknown adapter in AI_FALLBACK_URL, no fallback");
+                    backend
+                }
+            },
+        }
+    }
+
+    /// `[adapter@]url`, as in `AI_MODEL_URLS`
+    pub fn parse(spec: &str) -> Option<Self> {
+        match spec.split_once('@') {
+            Some((name, url)) if !name.contains('/') => Some(Backend::new(url, adapter(name)?, None)),
+            _ => Some(Backend::new(spec, Arc::new(FodV1), None)),
+        }
+    }
+
+    /// Run detection on one image, returning the normalized response. When the service can't
+    /// be reached and `opts.fallback` is set, the fallback answers instead, with
+    /// `"degraded": true` in its response.
+    pub async fn detect(
+        &self,
+        client: &Client,
+        image: Vec<u8>,
+        filename: String,
+        opts: DetectOptions,
+    ) -> Result<Value, (StatusCode, String)> {
+        let Some(fallback) = self.fallback.as_ref().filter(|_| opts.fallback) else {
+            return self.call(client, image, filename, opts).await.map_err(Failure::into_error);
+        };
+        match self.call(client, image.clone(), filename.clone(), opts).await {
+            Ok(v) => Ok(v),
+            Err(Failure::Unreachable(e)) => {
+                STATS.unreachable.fetch_add(1, Ordering::Relaxed);
+                tracing::warn!(url = %self.base, error = %e, fallback = %fallback.describe(), "AI service unreachable, using fallback");
+                let mut v = fallback.detect(client, image, filename, opts).await.map_err(|f| {
+                    STATS.fallback_failed.fetch_add(1, Ordering::Relaxed);
+                    f.into_error()
+                })?;
+                STATS.degraded.fetch_add(1, Ordering::Relaxed);
+                if let Some(o) = v.as_object_mut() {
+                    o.insert("degraded".to_string(), Value::Bool(true));
+                }
+                Ok(v)
+            }
+            Err(f) => Err(f.into_error()),
+        }
+    }
+
+    async fn call(&self, client: &Client, image: Vec<u8>, filename: String, opts: DetectOptions) -> Result<Value, Failure> {
+        let url = format!("{}/{}", self.base, self.path);
+        let resp = self
+            .adapter
+            .request(client.post(&url), image, filename, opts)
+            .send()
+            .await
+            .map_err(|e| Failure::Unreachable(e.to_string()))?;
+        let status = resp.status();
+        let body: Value = resp.json().await.map_err(|e| Failure::Error(internal(e)))?;
+        if !status.is_success() {
+            let code = StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(Failure::Error((code, format!("ai error: {}", body))));
+        }
+        self.adapter.parse(body).map_err(|e| {
+            Failure::Error((StatusCode::BAD_GATEWAY, format!("ai error: unexpected {} response: {}", self.adapter.name(), e)))
+        })
+    }
+}
+
+/// Failures worth retrying later: the service was unreachable, overloaded or timed out
+pub fn is_transient(code: StatusCode) -> bool {
+    matches!(
+        code,
+        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT | StatusCode::TOO_MANY_REQUESTS
+    )
+}
+
+/// Why a call failed: only a service that never answered is worth the fallback
+enum Failure {
+    Unreachable(String),
+    Error((StatusCode, String)),
+}
+
+impl Failure {
+    fn into_error(self) -> (StatusCode, String) {
+        match self {
+            Failure::Unreachable(e) => (StatusCode::BAD_GATEWAY, format!("ai unreachable: {}", e)),
+            Failure::Error(e) => e,
+        }
+    }
+}
+
+// ==================== Stats ====================
+
+/// Process-wide fallback counters, reported by /admin/perf
+struct Stats {
+    unreachable: AtomicU64,
+    degraded: AtomicU64,
+    fallback_failed: AtomicU64,
+}
+
+static STATS: Stats = Stats { unreachable: AtomicU64::new(0), degraded: AtomicU64::new(0), fallback_failed: AtomicU64::new(0) };
+
+pub fn stats() -> Value {
+    json!({
+        "primary_unreachable": STATS.unreachable.load(Ordering::Relaxed),
+        "degraded_results": STATS.degraded.load(Ordering::Relaxed),
+        "fallback_failed": STATS.fallback_failed.load(Ordering::Relaxed),
+    })
+}
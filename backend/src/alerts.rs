@@ -0,0 +1,784 @@
+//! Alerts for FOD Detection Backend
+//! Raised from events that match an escalation policy, paged through on-call rotations until acknowledged
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{postgres::PgListener, FromRow, PgConnection, PgPool};
+use std::{env, sync::OnceLock, time::Duration};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{auth, crypto, db::internal, jobs, notifications, oncall, perf, push, sla, sms, subscriptions, AppState};
+
+/// Events looked at per escalator pass for new alerts
+const RAISE_BATCH: i64 = 500;
+/// Notification template rendered for every alert message
+pub const TEMPLATE: &str = "alert";
+/// Used while no `alert` template is stored for a channel
+const DEFAULT_SUBJECT: &str =
+    "FOD alert: {{ event.class_name }}{% if alert.severity %} ({{ alert.severity }}){% endif %}{% if alert.sla_breached %}, SLA breached{% endif %}";
+const DEFAULT_BODY: &str =
+    "{{ event.class_name }} x{{ event.object_count }} from {{ event.source_ref }} at {{ event.latitude }}, {{ event.longitude }}. Acknowledge alert {{ alert.id }}.";
+/// Channel for rotation members who have not picked any in their subscription
+const DEFAULT_CHANNEL: &str = "email";
+/// Alert states, in order
+pub const STATUSES: [&str; 3] = ["open", "acknowledged", "resolved"];
+/// Channel alert changes are published on, for `/alerts/stream` on every instance
+const STREAM_CHANNEL: &str = "fod_alerts";
+const STREAM_PATH: &str = "/alerts/stream";
+/// Changes buffered per slow stream client before it starts skipping
+const STREAM_BUFFER: usize = 256;
+/// Unresolved alerts replayed to a stream client on connect
+const STREAM_REPLAY: i64 = 500;
+/// Postgres caps NOTIFY payloads at 8000 bytes; larger changes go out as an id to read back
+const NOTIFY_MAX: usize = 7900;
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct Alert {
+    pub id: i64,
+    pub event_id: Uuid,
+    pub class_name: String,
+    pub source_ref: String,
+    pub policy: Option<String>,
+    pub severity: Option<String>,
+    pub status: String,
+    pub next_step: i32,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub next_step_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub raised_at: OffsetDateTime,
+    pub acknowledged_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub acknowledged_at: Option<OffsetDateTime>,
+    pub resolved_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub resolved_at: Option<OffsetDateTime>,
+    pub note: Option<String>,
+    /// When the event overran its SLA threshold (see `sla`)
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub sla_breached_at: Option<OffsetDateTime>,
+}
+
+const ALERT_COLUMNS: &str = r#"
+    a.id, a.event_id, fc.name AS class_name, e.source_ref, p.name AS policy, a.severity, a.status,
+    a.next_step, a.next_step_at, a.raised_at, a.acknowledged_by, a.acknowledged_at, a.resolved_by,
+    a.resolved_at, a.note, a.sla_breached_at
+"#;
+const ALERT_FROM: &str = r#"
+    FROM alerts a
+    JOIN events e ON e.id = a.event_id
+    JOIN fod_classes fc ON fc.id = e.class_id
+    LEFT JOIN escalation_policies p ON p.id = a.policy_id
+"#;
+
+#[derive(Serialize, FromRow)]
+pub struct Notification {
+    pub id: i64,
+    pub step: Option<i32>,
+    pub rotation: Option<String>,
+    pub username: Option<String>,
+    pub channel: String,
+    pub subject: Option<String>,
+    pub body: String,
+    pub status: String,
+    /// Why delivery failed or was skipped
+    pub error: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub sent_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Step {
+    pub rotation: String,
+    pub after_minutes: i32,
+}
+
+#[derive(Serialize)]
+pub struct Policy {
+    pub name: String,
+    pub severities: Vec<String>,
+    pub steps: Vec<Step>,
+}
+
+// ==================== Request Types ====================
+
+#[derive(Deserialize)]
+pub struct PolicyRequest {
+    pub name: String,
+    #[serde(default)]
+    pub severities: Vec<String>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+pub struct ListParams {
+    /// open, acknowledged or resolved; defaults to everything not yet resolved
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct NoteRequest {
+    pub note: Option<String>,
+}
+
+// ==================== Raising ====================
+
+/// Open an alert for every new event a policy covers, the most specific policy first, and tell
+/// the event's subscribers. The first pass only records where the event log stands.
+pub async fn raise(st: &AppState) -> Result<u64, (StatusCode, String)> {
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let last: Option<i64> = sqlx::query_scalar("SELECT last_seq FROM alert_cursor FOR UPDATE")
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(internal)?;
+    let Some(last) = last else {
+        sqlx::query("INSERT INTO alert_cursor (last_seq) SELECT COALESCE(MAX(seq), 0) FROM events ON CONFLICT DO NOTHING")
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        tx.commit().await.map_err(internal)?;
+        return Ok(0);
+    };
+
+    let q = sqlx::query_as::<_, (i64, Option<i64>)>(
+        r#"
+        WITH batch AS (
+            SELECT e.id, e.seq, e.source_ref, LOWER(e.severity) AS severity
+            FROM events e
+            WHERE e.seq > $1
+            ORDER BY e.seq
+            LIMIT $2
+        ),
+        raised AS (
+            INSERT INTO alerts (event_id, policy_id, severity, next_step_at)
+            SELECT b.id, p.id, b.severity,
+                   NOW() + make_interval(mins => (SELECT s.after_minutes FROM escalation_steps s WHERE s.policy_id = p.id AND s.position = 0))
+            FROM batch b
+            CROSS JOIN LATERAL (
+                SELECT id FROM escalation_policies
+                WHERE cardinality(severities) = 0 OR b.severity = ANY(severities)
+                ORDER BY cardinality(severities) = 0, id
+                LIMIT 1
+            ) p
+            WHERE NOT EXISTS (SELECT 1 FROM quarantine_sources qs WHERE qs.source_ref = b.source_ref)
+            ON CONFLICT (event_id) DO NOTHING
+            RETURNING id
+        )
+        SELECT (SELECT COUNT(*) FROM raised), (SELECT MAX(seq) FROM batch)
+        "#,
+    )
+    .bind(last)
+    .bind(RAISE_BATCH)
+    .fetch_one(&mut *tx);
+    let (raised, newest) = perf::timed("raise_alerts", || format!("after={}", last), q).await.map_err(internal)?;
+    let Some(newest) = newest else { return Ok(0) };
+    sqlx::query("UPDATE alert_cursor SET last_seq = $1").bind(newest).execute(&mut *tx).await.map_err(internal)?;
+    tx.commit().await.map_err(internal)?;
+
+    // Subscribers hear about it once, when it is raised
+    let fresh: Vec<(i64, Uuid)> = sqlx::query_as(
+        "SELECT a.id, a.event_id FROM alerts a JOIN events e ON e.id = a.event_id WHERE e.seq > $1 AND e.seq <= $2",
+    )
+    .bind(last)
+    .bind(newest)
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    for (alert_id, event_id) in fresh {
+        let Some((ctx, params)) = message_context(st, alert_id, event_id, None, None).await? else { continue };
+        for r in subscriptions::route(st, &params).await? {
+            for channel in &r.channels {
+                enqueue(st, alert_id, None, None, Some((r.user_id, &r.username)), channel, &ctx).await?;
+            }
+        }
+        publish(&mut *st.db.acquire().await.map_err(internal)?, "raised", alert_id).await?;
+    }
+    Ok(raised as u64)
+}
+
+/// Template variables for one alert message, and the routing parameters of its event
+async fn message_context(
+    st: &AppState,
+    alert_id: i64,
+    event_id: Uuid,
+    step: Option<i32>,
+    rotation: Option<&str>,
+) -> Result<Option<(Value, subscriptions::RecipientParams)>, (StatusCode, String)> {
+    let Some(mut event) = st.events.get(event_id).await? else { return Ok(None) };
+    if let Some(meta) = event.meta.as_mut() {
+        crypto::decrypt_meta(meta);
+    }
+    let text = |k: &str| event.meta.as_ref().and_then(|m| m.get(k)).and_then(Value::as_str).map(str::to_string);
+    let params = subscriptions::RecipientParams {
+        class: event.event.class_name.clone(),
+        zone: text("zone"),
+        severity: text("severity"),
+        source_ref: Some(event.event.source_ref.clone()),
+        runway: text("runway"),
+        include_quarantine: false,
+    };
+    let sla_breached: bool = sqlx::query_scalar("SELECT sla_breached_at IS NOT NULL FROM alerts WHERE id = $1")
+        .bind(alert_id)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?
+        .unwrap_or(false);
+    let mut ctx = notifications::context(notifications::event_vars(&event));
+    ctx["alert"] = json!({
+        "id": alert_id,
+        "severity": params.severity.as_deref().map(str::to_lowercase),
+        "step": step,
+        "rotation": rotation,
+        "sla_breached": sla_breached,
+    });
+    Ok(Some((ctx, params)))
+}
+
+/// Render the alert template for `channel` and queue the message
+async fn enqueue(
+    st: &AppState,
+    alert_id: i64,
+    step: Option<i32>,
+    rotation: Option<&str>,
+    user: Option<(Uuid, &str)>,
+    channel: &str,
+    ctx: &Value,
+) -> Result<(), (StatusCode, String)> {
+    let template = notifications::lookup(&st.db, TEMPLATE, channel, "en").await?;
+    let (subject, body) = match &template {
+        Some(t) => (t.subject.as_deref(), t.body.as_str()),
+        None => (Some(DEFAULT_SUBJECT), DEFAULT_BODY),
+    };
+    let rendered = notifications::render(subject, body, ctx).unwrap_or_else(|e| {
+        warn!(alert_id, channel, error = %e, "alert template failed, sending the default");
+        notifications::render(Some(DEFAULT_SUBJECT), DEFAULT_BODY, ctx).expect("default alert template renders")
+    });
+    sqlx::query(
+        r#"
+        INSERT INTO alert_notifications (alert_id, step, rotation, user_id, username, channel, subject, body)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(alert_id)
+    .bind(step)
+    .bind(rotation)
+    .bind(user.map(|(id, _)| id))
+    .bind(user.map(|(_, name)| name))
+    .bind(channel)
+    .bind(&rendered.subject)
+    .bind(&rendered.body)
+    .execute(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(())
+}
+
+// ==================== Escalation ====================
+
+/// Page the rotation of every open alert whose next step is due, then schedule the step after it.
+/// Returns the number of steps paged.
+pub async fn escalate(st: &AppState) -> Result<u64, (StatusCode, String)> {
+    let due = sqlx::query_as::<_, (i64, Uuid, i32, String)>(
+        r#"
+        SELECT a.id, a.event_id, a.next_step, r.name
+        FROM alerts a
+        JOIN escalation_steps s ON s.policy_id = a.policy_id AND s.position = a.next_step
+        JOIN oncall_rotations r ON r.id = s.rotation_id
+        WHERE a.status = 'open' AND a.next_step_at <= NOW()
+        ORDER BY a.next_step_at
+        "#,
+    )
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+
+    let mut paged = 0;
+    for (alert_id, event_id, step, rotation) in due {
+        // Claimed first, so an acknowledgement racing this pass wins and nobody is paged twice
+        let claimed = sqlx::query(
+            r#"
+            UPDATE alerts a SET next_step = a.next_step + 1,
+                next_step_at = a.raised_at + make_interval(mins => (
+                    SELECT s.after_minutes FROM escalation_steps s WHERE s.policy_id = a.policy_id AND s.position = a.next_step + 1))
+            WHERE a.id = $1 AND a.status = 'open' AND a.next_step = $2
+            "#,
+        )
+        .bind(alert_id)
+        .bind(step)
+        .execute(&st.db)
+        .await
+        .map_err(internal)?;
+        if claimed.rows_affected() == 0 {
+            continue;
+        }
+        let Some((ctx, _)) = message_context(st, alert_id, event_id, Some(step), Some(&rotation)).await? else { continue };
+        for on_call in oncall::on_call_now(&st.db, Some(&rotation)).await? {
+            let a = on_call.assignee;
+            let user = a.user_id.zip(a.username.as_deref());
+            let channels = match (&a.channel, a.user_id) {
+                (Some(channel), _) => vec![channel.clone()],
+                (None, Some(user_id)) => member_channels(&st.db, user_id).await?,
+                (None, None) => Vec::new(),
+            };
+            for channel in channels {
+                enqueue(st, alert_id, Some(step), Some(&rotation), user, &channel, &ctx).await?;
+            }
+        }
+        info!(alert_id, step, rotation = %rotation, "alert escalated");
+        publish(&mut *st.db.acquire().await.map_err(internal)?, "escalated", alert_id).await?;
+        paged += 1;
+    }
+    Ok(paged)
+}
+
+/// A rotation member's own channels, from their subscription
+async fn member_channels(db: &PgPool, user_id: Uuid) -> Result<Vec<String>, (StatusCode, String)> {
+    let channels: Option<Vec<String>> = sqlx::query_scalar("SELECT channels FROM user_subscriptions WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+        .map_err(internal)?;
+    Ok(channels.filter(|c| !c.is_empty()).unwrap_or_else(|| vec![DEFAULT_CHANNEL.to_string()]))
+}
+
+// ==================== Delivery ====================
+
+/// Messages a channel sender picks up per pass
+const DELIVER_BATCH: i64 = 100;
+
+/// A queued message for a channel this backend sends itself
+#[derive(FromRow)]
+pub struct Outgoing {
+    pub id: i64,
+    pub user_id: Option<Uuid>,
+    pub subject: Option<String>,
+    pub body: String,
+    pub alert_id: i64,
+    pub event_id: Uuid,
+    pub severity: Option<String>,
+}
+
+/// Oldest pending messages on `channel`
+pub async fn outgoing(db: &PgPool, channel: &str) -> Result<Vec<Outgoing>, (StatusCode, String)> {
+    sqlx::query_as::<_, Outgoing>(
+        r#"
+        SELECT n.id, n.user_id, n.subject, n.body, a.id AS alert_id, a.event_id, a.severity
+        FROM alert_notifications n JOIN alerts a ON a.id = n.alert_id
+        WHERE n.channel = $1 AND n.status = 'pending'
+        ORDER BY n.id
+        LIMIT $2
+        "#,
+    )
+    .bind(channel)
+    .bind(DELIVER_BATCH)
+    .fetch_all(db)
+    .await
+    .map_err(internal)
+}
+
+/// Record how delivering one message went: `sent`, `failed` or `skipped`, and why
+pub async fn delivered(db: &PgPool, id: i64, status: &str, error: Option<String>) -> Result<(), (StatusCode, String)> {
+    sqlx::query("UPDATE alert_notifications SET status = $2, error = $3, sent_at = CASE WHEN $2 = 'sent' THEN NOW() END WHERE id = $1")
+        .bind(id)
+        .bind(status)
+        .bind(error)
+        .execute(db)
+        .await
+        .map_err(internal)?;
+    Ok(())
+}
+
+/// Whether an alert's severity is under `min`; alerts without one are
+pub fn below(severity: Option<&str>, min: &str) -> bool {
+    let rank = |s: &str| subscriptions::SEVERITIES.iter().position(|v| *v == s);
+    severity.and_then(rank) < rank(min)
+}
+
+/// One escalator pass: raise new alerts, escalate events past their SLA, page whatever is due,
+/// then send the `push` and `sms` messages. Returns the alerts raised and the steps paged.
+pub async fn run(st: &AppState) -> Result<(u64, u64), (StatusCode, String)> {
+    let raised = raise(st).await?;
+    sla::breach(st).await?;
+    let counts = (raised, escalate(st).await?);
+    push::deliver(st).await?;
+    sms::deliver(st).await?;
+    Ok(counts)
+}
+
+/// Every `ALERT_CHECK_SECS` (default 30, `0` disables) raise and escalate alerts on one replica
+pub fn spawn_escalator(state: AppState) {
+    let secs = env::var("ALERT_CHECK_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(30);
+    if secs == 0 {
+        return;
+    }
+    info!(every_secs = secs, "alert escalator started");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_secs(secs));
+        loop {
+            tick.tick().await;
+            match jobs::run_singleton(&state.db, "alert_escalation", run(&state)).await {
+                Ok(Some(Ok((raised, paged)))) if raised + paged > 0 => info!(raised, paged, "alerts processed"),
+                Ok(Some(Ok(_))) | Ok(None) => {}
+                Ok(Some(Err((_, e)))) => warn!(error = %e, "alert escalation failed"),
+                Err(e) => warn!(error = %e, "alert escalation failed"),
+            }
+        }
+    });
+}
+
+// ==================== Queries ====================
+
+async fn get(db: &PgPool, id: i64) -> Result<Option<Alert>, (StatusCode, String)> {
+    let sql = format!("SELECT {} {} WHERE a.id = $1", ALERT_COLUMNS, ALERT_FROM);
+    sqlx::query_as::<_, Alert>(&sql).bind(id).fetch_optional(db).await.map_err(internal)
+}
+
+/// Move an alert from one of `from` to `to`, recording who did it
+async fn set_status(db: &PgPool, id: i64, from: &[&str], to: &str, by: &str, note: Option<&str>) -> Result<Alert, (StatusCode, String)> {
+    let mut tx = db.begin().await.map_err(internal)?;
+    let (by_col, at_col) = if to == "acknowledged" { ("acknowledged_by", "acknowledged_at") } else { ("resolved_by", "resolved_at") };
+    let sql = format!(
+        r#"
+        UPDATE alerts SET status = $2, {} = $3, {} = NOW(), next_step_at = NULL, note = COALESCE($4, note)
+        WHERE id = $1 AND status = ANY($5)
+        "#,
+        by_col, at_col
+    );
+    let updated = sqlx::query(&sql)
+        .bind(id)
+        .bind(to)
+        .bind(by)
+        .bind(note)
+        .bind(from)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?;
+    if updated.rows_affected() > 0 {
+        publish(&mut tx, to, id).await?;
+    }
+    tx.commit().await.map_err(internal)?;
+    let alert = get(db, id).await?.ok_or((StatusCode::NOT_FOUND, "Alert not found".to_string()))?;
+    if updated.rows_affected() == 0 {
+        return Err((StatusCode::CONFLICT, format!("Alert is already {}", alert.status)));
+    }
+    info!(alert_id = id, status = to, by, "alert updated");
+    Ok(alert)
+}
+
+async fn load_policy(db: &PgPool, id: i32, name: String, severities: Vec<String>) -> Result<Policy, (StatusCode, String)> {
+    let steps = sqlx::query_as::<_, (String, i32)>(
+        r#"
+        SELECT r.name, s.after_minutes
+        FROM escalation_steps s JOIN oncall_rotations r ON r.id = s.rotation_id
+        WHERE s.policy_id = $1
+        ORDER BY s.position
+        "#,
+    )
+    .bind(id)
+    .fetch_all(db)
+    .await
+    .map_err(internal)?;
+    let steps = steps.into_iter().map(|(rotation, after_minutes)| Step { rotation, after_minutes }).collect();
+    Ok(Policy { name, severities, steps })
+}
+
+// ==================== Stream ====================
+
+fn stream_sender() -> &'static broadcast::Sender<String> {
+    static TX: OnceLock<broadcast::Sender<String>> = OnceLock::new();
+    TX.get_or_init(|| broadcast::channel(STREAM_BUFFER).0)
+}
+
+#[derive(Deserialize)]
+struct Change {
+    #[serde(rename = "type")]
+    kind: String,
+    id: Option<i64>,
+    alert: Option<Value>,
+}
+
+/// Tell every instance's stream clients alert `id` was raised or changed, as `{"type": change,
+/// "alert": {..}}` with the alert as it stands now, so a quick ack then resolve still streams the
+/// acknowledged state; inside a transaction it goes out on commit
+pub(crate) async fn publish(conn: &mut PgConnection, change: &str, id: i64) -> Result<(), (StatusCode, String)> {
+    let sql = format!("SELECT {} {} WHERE a.id = $1", ALERT_COLUMNS, ALERT_FROM);
+    let Some(alert) = sqlx::query_as::<_, Alert>(&sql).bind(id).fetch_optional(&mut *conn).await.map_err(internal)? else {
+        return Ok(());
+    };
+    let mut payload = json!({ "type": change, "alert": alert }).to_string();
+    if payload.len() > NOTIFY_MAX {
+        payload = json!({ "type": change, "id": id }).to_string();
+    }
+    sqlx::query("SELECT pg_notify($1, $2)").bind(STREAM_CHANNEL).bind(payload).execute(&mut *conn).await.map_err(internal)?;
+    Ok(())
+}
+
+/// `{"type": change, "alert": {..}}`, read back once per instance rather than per client when
+/// the change came without the alert
+async fn relayed(db: &PgPool, payload: &str) -> Option<String> {
+    let change: Change = serde_json::from_str(payload).ok()?;
+    let alert = match change.alert {
+        Some(alert) => alert,
+        None => serde_json::to_value(get(db, change.id?).await.ok()??).ok()?,
+    };
+    Some(json!({ "type": change.kind, "alert": alert }).to_string())
+}
+
+/// Relay alert changes to local stream clients, like `live::spawn_listener` does for events
+pub fn spawn_listener(db: PgPool) {
+    tokio::spawn(async move {
+        loop {
+            let mut listener = match PgListener::connect_with(&db).await {
+                Ok(l) => l,
+                Err(e) => {
+                    error!(error = %e, "alert listener failed to connect, retrying");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            if let Err(e) = listener.listen(STREAM_CHANNEL).await {
+                error!(error = %e, "LISTEN {} failed, retrying", STREAM_CHANNEL);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+            info!(channel = STREAM_CHANNEL, "listening for alert changes");
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(n)) => {
+                        if let Some(payload) = relayed(&db, n.payload()).await {
+                            // No receivers is fine, nobody is watching
+                            drop(stream_sender().send(payload));
+                        }
+                    }
+                    Ok(None) => warn!("alert listener connection lost, reconnecting; changes in the gap are missed"),
+                    Err(e) => {
+                        error!(error = %e, "alert listener failed");
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+async fn relay(st: AppState, mut socket: WebSocket) {
+    // Subscribed before the replay is read, so nothing raised in between is lost
+    let mut rx = stream_sender().subscribe();
+    let sql = format!("SELECT {} {} WHERE a.status <> 'resolved' ORDER BY a.raised_at, a.id LIMIT $1", ALERT_COLUMNS, ALERT_FROM);
+    let current = match sqlx::query_as::<_, Alert>(&sql).bind(STREAM_REPLAY).fetch_all(&st.db).await {
+        Ok(current) => current,
+        Err(e) => {
+            warn!(error = %e, "alert stream replay failed");
+            return;
+        }
+    };
+    for alert in current {
+        let payload = json!({ "type": "current", "alert": alert }).to_string();
+        if socket.send(Message::Text(payload)).await.is_err() {
+            return;
+        }
+    }
+    loop {
+        tokio::select! {
+            payload = rx.recv() => match payload {
+                Ok(payload) => {
+                    if socket.send(Message::Text(payload)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(n)) => warn!(skipped = n, "alert stream client too slow, skipping changes"),
+                Err(RecvError::Closed) => break,
+            },
+            // Stop when the client closes or errors; inbound messages are ignored
+            msg = socket.recv() => {
+                if !matches!(msg, Some(Ok(_))) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// ==================== Handlers ====================
+
+/// GET /alerts/stream-url — signed `/alerts/stream` link for displays that can't send the bearer
+/// token on a WebSocket (login)
+pub async fn stream_url_handler(headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let (url, expires_at) = auth::sign_link(STREAM_PATH);
+    Ok(Json(json!({ "url": url, "expires_at": expires_at.format(&Rfc3339).map_err(internal)? })))
+}
+
+/// GET /alerts/stream — WebSocket of actionable alerts only, no raw events: every unresolved alert
+/// as `{"type":"current","alert":{..}}` on connect, then `raised`, `escalated`, `sla_breached`,
+/// `acknowledged` and `resolved` as they happen (login or signed link)
+pub async fn stream_handler(
+    ws: WebSocketUpgrade,
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(link): Query<auth::LinkParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user_or_link(&headers, STREAM_PATH, &link)?;
+    Ok(ws.on_upgrade(move |socket| relay(st, socket)))
+}
+
+/// GET /alerts — newest first; `?status=` open, acknowledged or resolved (default: not resolved)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<ListParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    if let Some(bad) = p.status.as_deref().filter(|s| !STATUSES.contains(s)) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown status {:?}, expected one of {}", bad, STATUSES.join(", "))));
+    }
+    let sql = format!(
+        "SELECT {} {} WHERE ($1::TEXT IS NULL AND a.status <> 'resolved' OR a.status = $1) ORDER BY a.raised_at DESC, a.id DESC LIMIT $2",
+        ALERT_COLUMNS, ALERT_FROM
+    );
+    let q = sqlx::query_as::<_, Alert>(&sql).bind(&p.status).bind(p.limit.unwrap_or(100).clamp(1, 1000)).fetch_all(&st.db);
+    Ok(Json(perf::timed("list_alerts", || format!("status={:?}", p.status), q).await.map_err(internal)?))
+}
+
+/// GET /alerts/:id — the alert with every message sent for it
+pub async fn get_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let alert = get(&st.db, id).await?.ok_or((StatusCode::NOT_FOUND, "Alert not found".to_string()))?;
+    let notifications = sqlx::query_as::<_, Notification>(
+        r#"
+        SELECT id, step, rotation, username, channel, subject, body, status, error, created_at, sent_at
+        FROM alert_notifications WHERE alert_id = $1 ORDER BY id
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    let mut body = serde_json::to_value(alert).map_err(internal)?;
+    body["notifications"] = serde_json::to_value(notifications).map_err(internal)?;
+    Ok(Json(body))
+}
+
+/// POST /alerts/:id/ack — `{note?}` take the alert; escalation stops
+pub async fn ack_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    body: Option<Json<NoteRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+    Ok(Json(set_status(&st.db, id, &["open"], "acknowledged", &claims.username, req.note.as_deref()).await?))
+}
+
+/// POST /alerts/:id/resolve — `{note?}` close the alert, acknowledged or not
+pub async fn resolve_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    body: Option<Json<NoteRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+    Ok(Json(set_status(&st.db, id, &["open", "acknowledged"], "resolved", &claims.username, req.note.as_deref()).await?))
+}
+
+/// GET /admin/escalation-policies — policies with their steps (admin)
+pub async fn list_policies_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let rows = sqlx::query_as::<_, (i32, String, Vec<String>)>("SELECT id, name, severities FROM escalation_policies ORDER BY name")
+        .fetch_all(&st.db)
+        .await
+        .map_err(internal)?;
+    let mut out = Vec::with_capacity(rows.len());
+    for (id, name, severities) in rows {
+        out.push(load_policy(&st.db, id, name, severities).await?);
+    }
+    Ok(Json(out))
+}
+
+/// PUT /admin/escalation-policies — `{name, severities, steps: [{rotation, after_minutes}]}` create
+/// or replace a policy; steps page in order, each `after_minutes` after the alert was raised (admin)
+pub async fn upsert_policy_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<PolicyRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let name = req.name.trim();
+    if name.is_empty() || req.steps.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "name and at least one step are required".to_string()));
+    }
+    let mut severities: Vec<String> = req.severities.iter().map(|s| s.trim().to_lowercase()).collect();
+    severities.sort();
+    severities.dedup();
+    if let Some(bad) = severities.iter().find(|s| !subscriptions::SEVERITIES.contains(&s.as_str())) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown severity: {} (expected one of {})", bad, subscriptions::SEVERITIES.join(", "))));
+    }
+    if req.steps.iter().any(|s| s.after_minutes < 0) || req.steps.windows(2).any(|w| w[1].after_minutes < w[0].after_minutes) {
+        return Err((StatusCode::BAD_REQUEST, "after_minutes must be zero or more and not decrease from step to step".to_string()));
+    }
+    let mut rotations = Vec::with_capacity(req.steps.len());
+    for step in &req.steps {
+        let id: i32 = sqlx::query_scalar("SELECT id FROM oncall_rotations WHERE name = $1")
+            .bind(step.rotation.trim())
+            .fetch_optional(&st.db)
+            .await
+            .map_err(internal)?
+            .ok_or((StatusCode::BAD_REQUEST, format!("Unknown rotation: {}", step.rotation)))?;
+        rotations.push(id);
+    }
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let id: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO escalation_policies (name, severities, updated_by) VALUES ($1, $2, $3)
+        ON CONFLICT (name) DO UPDATE
+            SET severities = EXCLUDED.severities, updated_by = EXCLUDED.updated_by, updated_at = CURRENT_TIMESTAMP
+        RETURNING id
+        "#,
+    )
+    .bind(name)
+    .bind(&severities)
+    .bind(&claims.username)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal)?;
+    sqlx::query("DELETE FROM escalation_steps WHERE policy_id = $1").bind(id).execute(&mut *tx).await.map_err(internal)?;
+    for (position, (step, rotation_id)) in req.steps.iter().zip(&rotations).enumerate() {
+        sqlx::query("INSERT INTO escalation_steps (policy_id, position, rotation_id, after_minutes) VALUES ($1, $2, $3, $4)")
+            .bind(id)
+            .bind(position as i32)
+            .bind(rotation_id)
+            .bind(step.after_minutes)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+    }
+    tx.commit().await.map_err(internal)?;
+    info!(policy = name, steps = rotations.len(), by = %claims.username, "escalation policy saved");
+    Ok(Json(load_policy(&st.db, id, name.to_string(), severities).await?))
+}
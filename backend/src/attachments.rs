@@ -0,0 +1,185 @@
+//! Event attachments for FOD Detection Backend
+//! Ground crew photos and removal reports, stored in the object store and indexed per event
+
+use axum::{
+    extract::{Multipart, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use time::OffsetDateTime;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{auth, db::internal, logging, perf, s3, AppState};
+
+/// Largest accepted attachment
+pub const MAX_FILE_BYTES: usize = 25 * 1024 * 1024;
+
+/// Accepted types and, where the format has one, the magic bytes the file must start with
+const ALLOWED_TYPES: &[(&str, Option<&[u8]>)] = &[
+    ("image/jpeg", Some(&[0xFF, 0xD8, 0xFF])),
+    ("image/png", Some(&[0x89, b'P', b'N', b'G'])),
+    ("image/webp", Some(b"RIFF")),
+    ("image/heic", None),
+    ("application/pdf", Some(b"%PDF")),
+    ("text/plain", None),
+    ("video/mp4", None),
+    ("video/quicktime", None),
+];
+
+/// Object store for attachments, None unless `ATTACHMENT_S3_ENDPOINT` and `ATTACHMENT_S3_BUCKET` are set
+pub fn from_env() -> Option<s3::Bucket> {
+    s3::Bucket::from_env("ATTACHMENT_S3")
+}
+
+/// The attachment store carried in `AppState::attachments`
+pub fn bucket(st: &AppState) -> Result<&s3::Bucket, (StatusCode, String)> {
+    st.attachments.as_deref().ok_or((StatusCode::SERVICE_UNAVAILABLE, "Attachment storage is not configured".to_string()))
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    #[serde(skip)]
+    pub object_key: String,
+    pub content_type: String,
+    pub filename: Option<String>,
+    pub size_bytes: i64,
+    pub uploaded_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+const COLUMNS: &str = "id, event_id, object_key, content_type, filename, size_bytes, uploaded_by, created_at";
+
+/// Attachments of an event, oldest first
+pub async fn for_event(db: &PgPool, event_id: Uuid) -> Result<Vec<Attachment>, (StatusCode, String)> {
+    let sql = format!("SELECT {} FROM event_attachments WHERE event_id = $1 ORDER BY created_at", COLUMNS);
+    let q = sqlx::query_as::<_, Attachment>(&sql).bind(event_id).fetch_all(db);
+    perf::timed("event_attachments", || format!("event_id={}", event_id), q).await.map_err(internal)
+}
+
+/// Whether `bytes` may be stored as `content_type`
+fn check_type(content_type: &str, bytes: &[u8]) -> Result<(), (StatusCode, String)> {
+    match ALLOWED_TYPES.iter().find(|(t, _)| *t == content_type) {
+        None => Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("Attachments of type {} are not accepted", content_type))),
+        Some((_, Some(magic))) if !bytes.starts_with(magic) => {
+            Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("File content is not {}", content_type)))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+// ==================== Handlers ====================
+
+/// POST /events/:id/attachments — multipart `file`, typed by its part's content type
+pub async fn upload_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    mut mp: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let bucket = bucket(&st)?;
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM events WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&st.db)
+        .await
+        .map_err(internal)?;
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, "Event not found".to_string()));
+    }
+
+    let mut file = None;
+    while let Some(field) = mp.next_field().await.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))? {
+        if field.name() == Some("file") {
+            let content_type = field.content_type().unwrap_or("application/octet-stream").to_string();
+            let filename = field.file_name().map(|s| s.to_string());
+            let bytes = field.bytes().await.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+            file = Some((bytes, content_type, filename));
+        }
+    }
+    let (bytes, content_type, filename) = file.ok_or((StatusCode::BAD_REQUEST, "No file field".to_string()))?;
+    if bytes.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Empty file".to_string()));
+    }
+    if bytes.len() > MAX_FILE_BYTES {
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, format!("Attachments are at most {} bytes", MAX_FILE_BYTES)));
+    }
+    check_type(&content_type, &bytes)?;
+
+    let attachment_id = Uuid::new_v4();
+    let key = format!("attachments/site={}/{}/{}", logging::site_id(), id, attachment_id);
+    let size = bytes.len() as i64;
+    bucket.put_object(&st.http, &key, &content_type, bytes.to_vec()).await.map_err(|e| {
+        error!(event_id = %id, key = %key, error = %e, "attachment upload failed");
+        (StatusCode::BAD_GATEWAY, "Could not store the attachment".to_string())
+    })?;
+
+    let sql = format!(
+        r#"
+        INSERT INTO event_attachments (id, event_id, object_key, content_type, filename, size_bytes, uploaded_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING {}
+        "#,
+        COLUMNS
+    );
+    let attachment = sqlx::query_as::<_, Attachment>(&sql)
+        .bind(attachment_id)
+        .bind(id)
+        .bind(&key)
+        .bind(&content_type)
+        .bind(&filename)
+        .bind(size)
+        .bind(&claims.username)
+        .fetch_one(&st.db)
+        .await
+        .map_err(internal)?;
+    info!(event_id = %id, attachment_id = %attachment.id, content_type = %content_type, bytes = size, by = %claims.username, "attachment stored");
+    Ok((StatusCode::CREATED, Json(attachment)))
+}
+
+/// GET /events/:id/attachments — attachments of an event, oldest first (without file data)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    Ok(Json(for_event(&st.db, id).await?))
+}
+
+/// GET /attachments/:id — the stored file
+pub async fn download_handler(
+    State(st): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let sql = format!("SELECT {} FROM event_attachments WHERE id = $1", COLUMNS);
+    let q = sqlx::query_as::<_, Attachment>(&sql).bind(id).fetch_optional(&st.db);
+    let attachment = perf::timed("get_attachment", || format!("id={}", id), q)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "Attachment not found".to_string()))?;
+    let body = bucket(&st)?.get_object(&st.http, &attachment.object_key).await.map_err(|e| {
+        error!(attachment_id = %id, error = %e, "attachment download failed");
+        (StatusCode::BAD_GATEWAY, "Could not fetch the attachment".to_string())
+    })?;
+    // Quotes and control characters can't break out of the header value
+    let filename: String = attachment
+        .filename
+        .unwrap_or_else(|| id.to_string())
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+        .collect();
+    Ok((
+        [
+            (header::CONTENT_TYPE, attachment.content_type),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    ))
+}
@@ -2,20 +2,24 @@
 //! Handles login, JWT creation/verification, and user setup
 
 use axum::{
-    extract::State,
+    extract::{Path, State},
     http::{HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use sqlx::PgPool;
-use std::env;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{PgExecutor, PgPool};
+use std::{env, sync::atomic::Ordering};
 use time::{Duration, OffsetDateTime};
+use tracing::warn;
 use uuid::Uuid;
 
-use crate::{db::internal, AppState};
+use crate::{db::internal, logging, secrets, AppState};
 
 // ==================== JWT Claims ====================
 
@@ -35,15 +39,32 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
 // ==================== JWT Helpers ====================
 
 fn jwt_secret() -> String {
-    env::var("JWT_SECRET")
-        .unwrap_or_else(|_| "change_this_secret_in_production_32chars!".to_string())
+    secrets::get("JWT_SECRET")
+        .unwrap_or_else(|| "change_this_secret_in_production_32chars!".to_string())
+}
+
+/// Access token lifetime (env `ACCESS_TOKEN_TTL_MINUTES`, default 15)
+fn access_ttl() -> Duration {
+    let minutes = env::var("ACCESS_TOKEN_TTL_MINUTES").ok().and_then(|s| s.parse().ok()).unwrap_or(15);
+    Duration::minutes(minutes)
+}
+
+/// Refresh token lifetime (env `REFRESH_TOKEN_TTL_DAYS`, default 7)
+fn refresh_ttl() -> Duration {
+    let days = env::var("REFRESH_TOKEN_TTL_DAYS").ok().and_then(|s| s.parse().ok()).unwrap_or(7);
+    Duration::days(days)
 }
 
 pub fn create_token(user_id: &str, username: &str, role: &str) -> Result<String, String> {
-    let exp = (OffsetDateTime::now_utc() + Duration::hours(8)).unix_timestamp();
+    let exp = (OffsetDateTime::now_utc() + access_ttl()).unix_timestamp();
     let claims = Claims {
         sub: user_id.to_string(),
         username: username.to_string(),
@@ -73,6 +94,122 @@ fn extract_bearer(headers: &HeaderMap) -> Option<String> {
     auth.strip_prefix("Bearer ").map(|s| s.to_string())
 }
 
+/// Verify the bearer token, any role
+pub fn require_user(headers: &HeaderMap) -> Result<Claims, (StatusCode, String)> {
+    let token = extract_bearer(headers)
+        .ok_or((StatusCode::UNAUTHORIZED, "No token provided".to_string()))?;
+    let claims = verify_token(&token)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid or expired token".to_string()))?;
+    logging::record_api_key_id(&claims.sub);
+    Ok(claims)
+}
+
+/// Verify the bearer token and require the admin role
+pub fn require_admin(headers: &HeaderMap) -> Result<Claims, (StatusCode, String)> {
+    let claims = require_user(headers)?;
+    if claims.role != "admin" {
+        return Err((StatusCode::FORBIDDEN, "Admin role required".to_string()));
+    }
+    Ok(claims)
+}
+
+// ==================== Signed Links ====================
+
+/// `?exp=&sig=` of a signed link, for `<img>` sources that can't send a bearer token
+#[derive(Deserialize, Default)]
+pub struct LinkParams {
+    pub exp: Option<i64>,
+    pub sig: Option<String>,
+}
+
+/// Signed link lifetime (env `SIGNED_LINK_TTL_SECS`, default 300)
+fn link_ttl() -> Duration {
+    let secs = env::var("SIGNED_LINK_TTL_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(300);
+    Duration::seconds(secs)
+}
+
+fn link_mac(path: &str, exp: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(jwt_secret().as_bytes()).expect("HMAC accepts any key length");
+    mac.update(format!("{}\n{}", path, exp).as_bytes());
+    mac
+}
+
+/// `path?exp=&sig=`, usable without a token until `SIGNED_LINK_TTL_SECS` pass; returns it and its expiry
+pub fn sign_link(path: &str) -> (String, OffsetDateTime) {
+    let expires_at = OffsetDateTime::now_utc() + link_ttl();
+    let exp = expires_at.unix_timestamp();
+    let sig = URL_SAFE_NO_PAD.encode(link_mac(path, exp).finalize().into_bytes());
+    (format!("{}?exp={}&sig={}", path, exp, sig), expires_at)
+}
+
+/// Verify the bearer token, or else a live signed link to `path`
+pub fn require_user_or_link(headers: &HeaderMap, path: &str, link: &LinkParams) -> Result<(), (StatusCode, String)> {
+    let (Some(exp), Some(sig)) = (link.exp, link.sig.as_deref()) else {
+        return require_user(headers).map(drop);
+    };
+    let valid = exp > OffsetDateTime::now_utc().unix_timestamp()
+        && URL_SAFE_NO_PAD.decode(sig).is_ok_and(|sig| link_mac(path, exp).verify_slice(&sig).is_ok());
+    if !valid {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid or expired link".to_string()));
+    }
+    Ok(())
+}
+
+// ==================== Refresh Tokens ====================
+
+/// Only the SHA-256 of a refresh token is stored
+fn hash_refresh(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Store a new refresh token in `family_id` and return its id and plaintext
+async fn issue_refresh(
+    db: impl PgExecutor<'_>,
+    user_id: Uuid,
+    family_id: Uuid,
+) -> Result<(Uuid, String), (StatusCode, String)> {
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let id: Uuid = sqlx::query_scalar(
+        "INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at) VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(user_id)
+    .bind(family_id)
+    .bind(hash_refresh(&token))
+    .bind(OffsetDateTime::now_utc() + refresh_ttl())
+    .fetch_one(db)
+    .await
+    .map_err(internal)?;
+    Ok((id, token))
+}
+
+async fn revoke_family(db: impl PgExecutor<'_>, family_id: Uuid) -> Result<u64, (StatusCode, String)> {
+    sqlx::query("UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE family_id = $1 AND revoked_at IS NULL")
+        .bind(family_id)
+        .execute(db)
+        .await
+        .map(|r| r.rows_affected())
+        .map_err(internal)
+}
+
+/// Access + refresh token pair for a fresh login
+async fn new_session(db: &PgPool, user_id: Uuid, username: &str, role: &str) -> Result<Value, (StatusCode, String)> {
+    let token = create_token(&user_id.to_string(), username, role)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    let (_, refresh_token) = issue_refresh(db, user_id, Uuid::new_v4()).await?;
+    Ok(session_json(token, refresh_token, username, role))
+}
+
+fn session_json(token: String, refresh_token: String, username: &str, role: &str) -> Value {
+    json!({
+        "token": token,
+        "refresh_token": refresh_token,
+        "expires_in": access_ttl().whole_seconds(),
+        "refresh_expires_in": refresh_ttl().whole_seconds(),
+        "username": username,
+        "role": role,
+    })
+}
+
 // ==================== Auth Models ====================
 
 #[allow(dead_code)]
@@ -109,15 +246,21 @@ pub async fn login_handler(
         return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
     }
 
-    // สร้าง JWT
-    let token = create_token(&user_id.to_string(), &username, &role)
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    // ระหว่างรอ migration (read-only) ให้แค่ access token ไว้สั่ง migration ได้ ไม่เขียน
+    // refresh_tokens เพราะ migration ที่ค้างอยู่อาจเปลี่ยนตารางนั้น
+    if st.read_only.load(Ordering::Relaxed) {
+        let token = create_token(&user_id.to_string(), &username, &role).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+        return Ok(Json(json!({
+            "token": token,
+            "refresh_token": null,
+            "expires_in": access_ttl().whole_seconds(),
+            "username": username,
+            "role": role,
+        })));
+    }
 
-    Ok(Json(json!({
-        "token": token,
-        "username": username,
-        "role": role,
-    })))
+    // สร้าง JWT (อายุสั้น) + refresh token
+    Ok(Json(new_session(&st.db, user_id, &username, &role).await?))
 }
 
 /// GET /auth/me — ตรวจ JWT ที่แนบมากับ Authorization header คืน user info
@@ -138,10 +281,91 @@ pub async fn me_handler(
     })))
 }
 
-/// POST /auth/logout — Stateless JWT ไม่มี server-side session
-/// Frontend จะลบ cookie เอง แต่ endpoint นี้ return ok เพื่อ complete the flow
-pub async fn logout_handler() -> impl IntoResponse {
-    Json(json!({ "ok": true, "message": "Logged out" }))
+/// POST /auth/refresh — แลก refresh token เป็น access token ใหม่ (rotate ทุกครั้ง)
+pub async fn refresh_handler(
+    State(st): State<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let row = sqlx::query_as::<_, (Uuid, Uuid, Uuid, OffsetDateTime, Option<OffsetDateTime>, String, String)>(
+        r#"
+        SELECT t.id, t.user_id, t.family_id, t.expires_at, t.revoked_at, u.username, u.role
+        FROM refresh_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_hash = $1
+        FOR UPDATE OF t
+        "#,
+    )
+    .bind(hash_refresh(&payload.refresh_token))
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal)?
+    .ok_or((StatusCode::UNAUTHORIZED, "Invalid refresh token".to_string()))?;
+
+    let (id, user_id, family_id, expires_at, revoked_at, username, role) = row;
+
+    // token ที่ rotate/revoke ไปแล้วถูกใช้ซ้ำ — ถือว่ารั่ว ปิดทั้ง session
+    if revoked_at.is_some() {
+        let revoked = revoke_family(&mut *tx, family_id).await?;
+        tx.commit().await.map_err(internal)?;
+        if revoked > 0 {
+            warn!(user = %username, family = %family_id, "refresh token reuse, session revoked");
+        }
+        return Err((StatusCode::UNAUTHORIZED, "Refresh token revoked".to_string()));
+    }
+    if expires_at <= OffsetDateTime::now_utc() {
+        return Err((StatusCode::UNAUTHORIZED, "Refresh token expired".to_string()));
+    }
+
+    let (new_id, refresh_token) = issue_refresh(&mut *tx, user_id, family_id).await?;
+    sqlx::query("UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP, replaced_by = $2 WHERE id = $1")
+        .bind(id)
+        .bind(new_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?;
+    tx.commit().await.map_err(internal)?;
+
+    // role อ่านจาก users ใหม่ทุกครั้ง การเปลี่ยน role จึงมีผลภายในอายุ access token
+    let token = create_token(&user_id.to_string(), &username, &role)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    Ok(Json(session_json(token, refresh_token, &username, &role)))
+}
+
+/// POST /auth/logout — revoke refresh token (ถ้าส่งมา) ทั้ง family
+/// access token ที่ออกไปแล้วจะหมดอายุเองตาม ACCESS_TOKEN_TTL_MINUTES
+pub async fn logout_handler(
+    State(st): State<AppState>,
+    payload: Option<Json<RefreshRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if let Some(Json(req)) = payload {
+        let family_id: Option<Uuid> = sqlx::query_scalar("SELECT family_id FROM refresh_tokens WHERE token_hash = $1")
+            .bind(hash_refresh(&req.refresh_token))
+            .fetch_optional(&st.db)
+            .await
+            .map_err(internal)?;
+        if let Some(family_id) = family_id {
+            revoke_family(&st.db, family_id).await?;
+        }
+    }
+    Ok(Json(json!({ "ok": true, "message": "Logged out" })))
+}
+
+/// POST /admin/users/:id/sessions/revoke — ปิดทุก session ของ user (admin)
+pub async fn revoke_sessions_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = require_admin(&headers)?;
+    let revoked = sqlx::query("UPDATE refresh_tokens SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(&st.db)
+        .await
+        .map_err(internal)?
+        .rows_affected();
+    warn!(user_id = %user_id, by = %claims.username, revoked, "sessions revoked");
+    Ok(Json(json!({ "ok": true, "revoked": revoked })))
 }
 
 /// POST /auth/register — สมัครสมาชิกแบบเปิด ทุกคน register ได้เลย
@@ -186,12 +410,5 @@ pub async fn register_handler(
     .map_err(internal)?;
 
     // สร้าง JWT แล้ว login อัตโนมัติ
-    let token = create_token(&user_id.to_string(), &payload.username, "user")
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
-
-    Ok(Json(json!({
-        "token": token,
-        "username": payload.username,
-        "role": "user",
-    })))
+    Ok(Json(new_session(&st.db, user_id, &payload.username, "user").await?))
 }
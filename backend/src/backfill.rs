@@ -0,0 +1,158 @@
+//! Backfills for FOD Detection Backend
+//! Rewrites a derived column of existing events in chunks, as a run on the replay job queue:
+//! progress on `/jobs/:id/progress`, cancel and retry on `/jobs/:id/...`, and a run queued again
+//! carries on after the last chunk it got through
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{
+    auth,
+    db::internal,
+    replay::{self, Ended, ReplayRun, RUN_COLUMNS},
+    AppState,
+};
+
+/// Events per chunk unless the run says otherwise
+const DEFAULT_BATCH: i32 = 1000;
+const MAX_BATCH: i32 = 10_000;
+
+#[derive(Deserialize)]
+pub struct BackfillParams {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    /// Events per chunk (default 1000)
+    pub batch: Option<i32>,
+    pub queue: Option<String>,
+    pub priority: Option<i32>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub run_at: Option<OffsetDateTime>,
+}
+
+// ==================== Job ====================
+
+/// Set `events.zone` from the stored meta, as ingest does, for the run's range in `seq` order.
+/// Each chunk commits with the run's progress, so a requeued run neither skips nor recounts one;
+/// between chunks the worker stops if the run was cancelled, preempted or taken from it.
+pub(crate) async fn zones(state: &AppState, job: &ReplayRun) -> Result<Ended, sqlx::Error> {
+    let run_id = job.id;
+    let batch = job.batch_size.unwrap_or(DEFAULT_BATCH) as i64;
+    let mut after = job.last_seq;
+    loop {
+        let mut tx = state.db.begin().await?;
+        // The rollup trigger moves the counts of every event whose zone changes
+        let (seen, assigned, last): (i64, i64, Option<i64>) = sqlx::query_as(
+            r#"
+            WITH chunk AS (
+                SELECT id, seq FROM events
+                WHERE ts >= $1 AND ts < $2 AND seq > $3
+                ORDER BY seq
+                LIMIT $4
+            ),
+            assigned AS (
+                UPDATE events e SET zone = event_rollup_zone(d.meta)
+                FROM chunk c LEFT JOIN event_details d ON d.event_id = c.id
+                WHERE e.id = c.id AND e.zone IS DISTINCT FROM event_rollup_zone(d.meta)
+                RETURNING e.id
+            )
+            SELECT (SELECT COUNT(*) FROM chunk), (SELECT COUNT(*) FROM assigned), (SELECT MAX(seq) FROM chunk)
+            "#,
+        )
+        .bind(job.range_from)
+        .bind(job.range_to)
+        .bind(after)
+        .bind(batch)
+        .fetch_one(&mut *tx)
+        .await?;
+        let Some(last) = last else { break };
+        after = last;
+
+        let requested: Option<(bool, bool)> = sqlx::query_as(
+            r#"
+            UPDATE replay_runs SET frames_done = frames_done + $2, detections = detections + $3, last_seq = $4, heartbeat_at = NOW()
+            WHERE id = $1 AND lease = $5
+            RETURNING cancel_requested_at IS NOT NULL, preempt_requested_at IS NOT NULL
+            "#,
+        )
+        .bind(run_id)
+        .bind(seen as i32)
+        .bind(assigned as i32)
+        .bind(last)
+        .bind(job.lease)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(requested) = requested else { return Ok(Ended::LeaseLost) };
+        tx.commit().await?;
+        drop(replay::progressed().send(run_id));
+        match requested {
+            (true, _) => return Ok(Ended::Cancelled),
+            (_, true) => return Ok(Ended::Preempted),
+            _ => {}
+        }
+    }
+    Ok(Ended::Done)
+}
+
+// ==================== Handlers ====================
+
+/// POST /admin/backfill/zones?from=&to=&batch=&queue=&priority=&run_at= — set the zone of
+/// existing events (default all of them) from their meta as a queued background job (admin)
+pub async fn zones_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<BackfillParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let to = p.to.unwrap_or_else(OffsetDateTime::now_utc);
+    let from = p.from.unwrap_or(OffsetDateTime::UNIX_EPOCH);
+    if from >= to {
+        return Err((StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+    let batch = p.batch.unwrap_or(DEFAULT_BATCH);
+    if !(1..=MAX_BATCH).contains(&batch) {
+        return Err((StatusCode::BAD_REQUEST, format!("batch must be 1 to {}", MAX_BATCH)));
+    }
+    let (queue, priority, run_at) = replay::schedule(p.queue.as_deref(), p.priority, p.run_at)?;
+
+    let events: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM events WHERE ts >= $1 AND ts < $2")
+        .bind(from)
+        .bind(to)
+        .fetch_one(&st.db)
+        .await
+        .map_err(internal)?;
+    if events == 0 {
+        return Err((StatusCode::NOT_FOUND, "No events in range".to_string()));
+    }
+
+    let run = sqlx::query_as::<_, ReplayRun>(&format!(
+        r#"
+        INSERT INTO replay_runs (kind, range_from, range_to, frames_total, requested_by, batch_size, queue, priority, run_at)
+        VALUES ('zone_backfill', $1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING {}
+        "#,
+        RUN_COLUMNS
+    ))
+    .bind(from)
+    .bind(to)
+    .bind(events as i32)
+    .bind(&claims.username)
+    .bind(batch)
+    .bind(&queue)
+    .bind(priority)
+    .bind(run_at)
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    info!(run_id = run.id, events, batch, queue = %queue, priority, by = %claims.username, "zone backfill queued");
+    replay::dispatch(&st).await;
+    Ok((StatusCode::ACCEPTED, Json(replay::get_run(&st.db, run.id).await?)))
+}
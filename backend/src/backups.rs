@@ -0,0 +1,450 @@
+//! Database backups for FOD Detection Backend
+//! Logical export of the FOD tables as gzipped CSV in object storage, on demand and on a
+//! schedule, with restore into a separate staging database
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{postgres::PgPoolOptions, FromRow, PgConnection, PgPool};
+use std::{
+    env,
+    io::{Read, Write},
+    sync::OnceLock,
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{auth, db::internal, jobs, logging, migrations::MIGRATOR, s3, AppState};
+
+/// Tables in the backup, parents before the tables referencing them. Session tokens,
+/// idempotency keys and the backup log itself are left out.
+const TABLES: &[&str] = &[
+    "fod_classes",
+    "class_translations",
+    "class_aliases",
+    "fod_category_mappings",
+    "confidence_calibrations",
+    "users",
+    "push_devices",
+    "sms_recipients",
+    "user_subscriptions",
+    "oncall_rotations",
+    "oncall_members",
+    "oncall_overrides",
+    "notification_templates",
+    "escalation_policies",
+    "sla_thresholds",
+    "escalation_steps",
+    "events",
+    "event_details",
+    "event_rollups_hourly",
+    "event_rollups_daily",
+    "event_state_transitions",
+    "event_clearances",
+    "event_comments",
+    "event_attachments",
+    "alerts",
+    "alert_notifications",
+    "alert_cursor",
+    "tasks",
+    "notam_drafts",
+    "raw_inferences",
+    "replay_runs",
+    "replay_results",
+    "erasures",
+    "event_purges",
+    "quarantine_sources",
+    "aircraft_movements",
+    "traffic",
+    "activities",
+    "cameras",
+    "camera_calibrations",
+    "camera_snapshots",
+    "devices",
+    "device_certificates",
+    "device_configs",
+    "device_commands",
+    "device_logs",
+    "usage",
+    "settings",
+    "setting_changes",
+    "feature_flags",
+    "export_runs",
+    "health_history",
+    "synthetic_probes",
+];
+
+/// How often the scheduler looks for a due backup
+const CHECK_EVERY: Duration = Duration::from_secs(900);
+
+// ==================== Config ====================
+
+/// Backup bucket, None unless `BACKUP_S3_ENDPOINT` and `BACKUP_S3_BUCKET` are set
+fn bucket() -> Option<&'static s3::Bucket> {
+    static BUCKET: OnceLock<Option<s3::Bucket>> = OnceLock::new();
+    BUCKET.get_or_init(|| s3::Bucket::from_env("BACKUP_S3")).as_ref()
+}
+
+/// Hours between scheduled backups (`BACKUP_INTERVAL_HOURS`, default 24, 0 turns them off)
+fn interval_hours() -> i64 {
+    static HOURS: OnceLock<i64> = OnceLock::new();
+    *HOURS.get_or_init(|| env::var("BACKUP_INTERVAL_HOURS").ok().and_then(|s| s.parse().ok()).filter(|&h| h >= 0).unwrap_or(24))
+}
+
+/// Database restores go to (`BACKUP_STAGING_DATABASE_URL`); never the live one
+fn staging_url() -> Option<&'static str> {
+    static URL: OnceLock<Option<String>> = OnceLock::new();
+    URL.get_or_init(|| env::var("BACKUP_STAGING_DATABASE_URL").ok().filter(|s| !s.trim().is_empty())).as_deref()
+}
+
+fn require_bucket() -> Result<&'static s3::Bucket, (StatusCode, String)> {
+    bucket().ok_or((StatusCode::SERVICE_UNAVAILABLE, "Backups are not configured".to_string()))
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct BackupRun {
+    pub id: Uuid,
+    pub trigger: String,
+    pub status: String,
+    pub prefix: String,
+    /// Per table: name, columns, rows, bytes and object key
+    pub tables: Option<Value>,
+    pub rows: Option<i64>,
+    pub bytes: Option<i64>,
+    pub error: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub restored_at: Option<OffsetDateTime>,
+}
+
+const COLUMNS: &str = "id, trigger, status, prefix, tables, rows, bytes, error, started_at, finished_at, restored_at";
+
+/// One table in `manifest.json`
+#[derive(Serialize, Deserialize)]
+pub struct TableDump {
+    pub table: String,
+    pub columns: Vec<String>,
+    pub rows: i64,
+    pub bytes: i64,
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    id: Uuid,
+    site: String,
+    #[serde(with = "time::serde::rfc3339")]
+    created_at: OffsetDateTime,
+    /// Newest migration applied when the backup was taken
+    migration_version: i64,
+    tables: Vec<TableDump>,
+}
+
+fn quote(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+fn column_list(columns: &[String]) -> String {
+    columns.iter().map(|c| quote(c)).collect::<Vec<_>>().join(", ")
+}
+
+// ==================== Backup ====================
+
+/// Stored columns of `table` in order, empty when the table doesn't exist
+async fn table_columns(conn: &mut PgConnection, table: &str) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT column_name::TEXT FROM information_schema.columns
+        WHERE table_schema = current_schema() AND table_name = $1 AND is_generated = 'NEVER'
+        ORDER BY ordinal_position
+        "#,
+    )
+    .bind(table)
+    .fetch_all(conn)
+    .await
+}
+
+/// COPY every table out of one snapshot, gzip it and upload it, then the manifest
+async fn dump(db: &PgPool, http: &reqwest::Client, bucket: &s3::Bucket, id: Uuid, prefix: &str) -> Result<Vec<TableDump>, String> {
+    let mut tx = db.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY").execute(&mut *tx).await.map_err(|e| e.to_string())?;
+    let migration_version: i64 = sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM _sqlx_migrations")
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut tables = Vec::with_capacity(TABLES.len());
+    for &table in TABLES {
+        let columns = table_columns(&mut tx, table).await.map_err(|e| e.to_string())?;
+        if columns.is_empty() {
+            continue;
+        }
+        let rows: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", quote(table)))
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| e.to_string())?;
+        let copy = format!("COPY {} ({}) TO STDOUT (FORMAT csv, HEADER true)", quote(table), column_list(&columns));
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        let mut chunks = tx.copy_out_raw(&copy).await.map_err(|e| format!("{}: {}", table, e))?;
+        while let Some(chunk) = chunks.try_next().await.map_err(|e| format!("{}: {}", table, e))? {
+            gz.write_all(&chunk).map_err(|e| e.to_string())?;
+        }
+        drop(chunks);
+        let body = gz.finish().map_err(|e| e.to_string())?;
+        let key = format!("{}/{}.csv.gz", prefix, table);
+        let bytes = body.len() as i64;
+        bucket.put_object(http, &key, "application/gzip", body).await?;
+        tables.push(TableDump { table: table.to_string(), columns, rows, bytes, key });
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    let manifest = Manifest {
+        id,
+        site: logging::site_id().to_string(),
+        created_at: OffsetDateTime::now_utc(),
+        migration_version,
+        tables,
+    };
+    let body = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    bucket.put_object(http, &format!("{}/manifest.json", prefix), "application/json", body).await?;
+    Ok(manifest.tables)
+}
+
+/// Take a backup and record the attempt; Err(409) while another replica is backing up
+async fn run_backup(state: &AppState, bucket: &s3::Bucket, trigger: &str) -> Result<BackupRun, (StatusCode, String)> {
+    jobs::run_singleton(&state.db, "database_backup", backup(state, bucket, trigger))
+        .await
+        .map_err(internal)?
+        .unwrap_or_else(|| Err((StatusCode::CONFLICT, "Another backup is running".to_string())))
+}
+
+/// Take a backup when the last successful one is older than `BACKUP_INTERVAL_HOURS`. The check
+/// runs under the backup lock, so replicas checking at once take one backup. None when none was
+/// due or another replica was backing up.
+pub async fn scheduled_backup(state: &AppState) -> Result<Option<BackupRun>, (StatusCode, String)> {
+    let Some(bucket) = bucket() else {
+        return Ok(None);
+    };
+    let job = async {
+        let due: bool = sqlx::query_scalar(
+            "SELECT NOT EXISTS (SELECT 1 FROM backup_runs WHERE status = 'success' AND started_at > NOW() - make_interval(hours => $1))",
+        )
+        .bind(interval_hours() as i32)
+        .fetch_one(&state.db)
+        .await
+        .map_err(internal)?;
+        if !due {
+            return Ok(None);
+        }
+        backup(state, bucket, "schedule").await.map(Some)
+    };
+    jobs::run_singleton(&state.db, "database_backup", job).await.map_err(internal)?.unwrap_or(Ok(None))
+}
+
+/// Back up and record the run, with the backup lock held by the caller
+async fn backup(state: &AppState, bucket: &s3::Bucket, trigger: &str) -> Result<BackupRun, (StatusCode, String)> {
+    let id = Uuid::new_v4();
+    let prefix = format!("backups/site={}/{}", logging::site_id(), id);
+    sqlx::query("INSERT INTO backup_runs (id, trigger, prefix) VALUES ($1, $2, $3)")
+        .bind(id)
+        .bind(trigger)
+        .bind(&prefix)
+        .execute(&state.db)
+        .await
+        .map_err(internal)?;
+
+    let outcome = dump(&state.db, &state.http, bucket, id, &prefix).await;
+    let (status, tables, rows, bytes, err) = match outcome {
+        Ok(tables) => {
+            let rows: i64 = tables.iter().map(|t| t.rows).sum();
+            let bytes: i64 = tables.iter().map(|t| t.bytes).sum();
+            info!(backup = %id, prefix = %prefix, tables = tables.len(), rows, bytes, "backup finished");
+            ("success", serde_json::to_value(&tables).ok(), Some(rows), Some(bytes), None)
+        }
+        Err(e) => {
+            error!(backup = %id, prefix = %prefix, error = %e, "backup failed");
+            ("failed", None, None, None, Some(e))
+        }
+    };
+    let sql = format!(
+        r#"
+        UPDATE backup_runs SET status = $2, tables = $3, rows = $4, bytes = $5, error = $6, finished_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        RETURNING {}
+        "#,
+        COLUMNS
+    );
+    sqlx::query_as::<_, BackupRun>(&sql)
+        .bind(id)
+        .bind(status)
+        .bind(tables)
+        .bind(rows)
+        .bind(bytes)
+        .bind(err)
+        .fetch_one(&state.db)
+        .await
+        .map_err(internal)
+}
+
+// ==================== Restore ====================
+
+#[derive(Serialize)]
+pub struct RestoredTable {
+    pub table: String,
+    pub rows: u64,
+}
+
+/// Database name, address and port, to tell whether two pools point at the same database
+async fn identity(db: &PgPool) -> Result<(String, String, i32), sqlx::Error> {
+    sqlx::query_as("SELECT current_database()::TEXT, COALESCE(host(inet_server_addr()), ''), COALESCE(inet_server_port(), 0)")
+        .fetch_one(db)
+        .await
+}
+
+/// Load a backup into the staging database in one transaction: migrate it, then per table
+/// (parents first) empty it and COPY the rows in. Emptying each table right before its
+/// rows go in also drops what the events trigger wrote to the rollups.
+async fn restore_into(staging: &PgPool, http: &reqwest::Client, bucket: &s3::Bucket, prefix: &str) -> Result<Vec<RestoredTable>, String> {
+    let manifest: Manifest = serde_json::from_slice(&bucket.get_object(http, &format!("{}/manifest.json", prefix)).await?)
+        .map_err(|e| format!("unreadable manifest: {}", e))?;
+    MIGRATOR.run(staging).await.map_err(|e| format!("migrating staging: {}", e))?;
+
+    let mut tx = staging.begin().await.map_err(|e| e.to_string())?;
+    let mut restored = Vec::with_capacity(manifest.tables.len());
+    for dump in &manifest.tables {
+        if !TABLES.contains(&dump.table.as_str()) {
+            return Err(format!("manifest lists unknown table {}", dump.table));
+        }
+        let mut csv = Vec::new();
+        GzDecoder::new(&bucket.get_object(http, &dump.key).await?[..])
+            .read_to_end(&mut csv)
+            .map_err(|e| format!("{}: {}", dump.key, e))?;
+
+        sqlx::query(&format!("TRUNCATE {} CASCADE", quote(&dump.table))).execute(&mut *tx).await.map_err(|e| e.to_string())?;
+        let copy = format!("COPY {} ({}) FROM STDIN (FORMAT csv, HEADER true)", quote(&dump.table), column_list(&dump.columns));
+        let mut sink = tx.copy_in_raw(&copy).await.map_err(|e| format!("{}: {}", dump.table, e))?;
+        sink.send(csv).await.map_err(|e| format!("{}: {}", dump.table, e))?;
+        let rows = sink.finish().await.map_err(|e| format!("{}: {}", dump.table, e))?;
+
+        // Serial columns continue after the restored rows
+        let serials: Vec<(String, String)> = sqlx::query_as(
+            "SELECT c, pg_get_serial_sequence($1, c) FROM UNNEST($2::TEXT[]) c WHERE pg_get_serial_sequence($1, c) IS NOT NULL",
+        )
+        .bind(&dump.table)
+        .bind(&dump.columns)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| e.to_string())?;
+        for (column, sequence) in serials {
+            let sql = format!("SELECT setval($1, COALESCE((SELECT MAX({}) FROM {}), 0) + 1, false)", quote(&column), quote(&dump.table));
+            sqlx::query(&sql).bind(sequence).execute(&mut *tx).await.map_err(|e| e.to_string())?;
+        }
+        restored.push(RestoredTable { table: dump.table.clone(), rows });
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+    Ok(restored)
+}
+
+// ==================== Scheduler ====================
+
+/// Every 15 minutes take a backup when the last successful one is older than
+/// `BACKUP_INTERVAL_HOURS`; failures are retried on the next check
+pub fn spawn_scheduler(state: AppState) {
+    let Some(bucket) = bucket() else {
+        return;
+    };
+    let hours = interval_hours();
+    if hours == 0 {
+        return;
+    }
+    info!(bucket = %bucket.name, interval_hours = hours, "backup scheduler started");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(CHECK_EVERY);
+        loop {
+            tick.tick().await;
+            // Backup errors are recorded on the run and logged in backup
+            if let Err((_, e)) = scheduled_backup(&state).await {
+                warn!(error = %e, "backup scheduler could not check the last backup");
+            }
+        }
+    });
+}
+
+// ==================== Handlers ====================
+
+/// POST /admin/backup — back up the FOD tables now (admin)
+pub async fn run_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let bucket = require_bucket()?;
+    info!(user = %claims.username, "manual backup requested");
+    Ok(Json(run_backup(&st, bucket, "manual").await?))
+}
+
+/// GET /admin/backups — backup config and recent backups, newest first (admin)
+pub async fn list_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let sql = format!("SELECT {} FROM backup_runs ORDER BY started_at DESC LIMIT 100", COLUMNS);
+    let runs = sqlx::query_as::<_, BackupRun>(&sql).fetch_all(&st.db).await.map_err(internal)?;
+    Ok(Json(json!({
+        "configured": bucket().is_some(),
+        "bucket": bucket().map(|b| &b.name),
+        "interval_hours": interval_hours(),
+        "staging_configured": staging_url().is_some(),
+        "backups": runs,
+    })))
+}
+
+/// POST /admin/backups/:id/restore — load a successful backup into the staging database
+/// (`BACKUP_STAGING_DATABASE_URL`), replacing what it holds (admin)
+pub async fn restore_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let bucket = require_bucket()?;
+    let url = staging_url().ok_or((StatusCode::SERVICE_UNAVAILABLE, "No staging database is configured".to_string()))?;
+    let sql = format!("SELECT {} FROM backup_runs WHERE id = $1", COLUMNS);
+    let run = sqlx::query_as::<_, BackupRun>(&sql)
+        .bind(id)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "Backup not found".to_string()))?;
+    if run.status != "success" {
+        return Err((StatusCode::CONFLICT, format!("Backup is {}, only successful backups can be restored", run.status)));
+    }
+
+    let staging = PgPoolOptions::new().max_connections(2).connect(url).await.map_err(|e| {
+        error!(error = %e, "staging database unreachable");
+        (StatusCode::BAD_GATEWAY, "Could not connect to the staging database".to_string())
+    })?;
+    let target = identity(&staging).await.map_err(internal)?;
+    if target == identity(&st.db).await.map_err(internal)? {
+        return Err((StatusCode::CONFLICT, "The staging database is the live database; refusing to restore".to_string()));
+    }
+    info!(backup = %id, target = %target.0, user = %claims.username, "restore to staging started");
+    let restored = restore_into(&staging, &st.http, bucket, &run.prefix).await.map_err(|e| {
+        error!(backup = %id, target = %target.0, error = %e, "restore to staging failed");
+        (StatusCode::BAD_GATEWAY, format!("Restore failed: {}", e))
+    });
+    staging.close().await;
+    let restored = restored?;
+    sqlx::query("UPDATE backup_runs SET restored_at = CURRENT_TIMESTAMP WHERE id = $1").bind(id).execute(&st.db).await.map_err(internal)?;
+    info!(backup = %id, target = %target.0, tables = restored.len(), user = %claims.username, "restore to staging finished");
+    Ok(Json(json!({ "backup_id": id, "database": target.0, "tables": restored })))
+}
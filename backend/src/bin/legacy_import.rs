@@ -0,0 +1,378 @@
+//! Legacy FOD log importer
+//! Reads the old Python system's SQLite database or CSV export, maps legacy
+//! categories onto fod_classes and bulk-loads events through db.rs
+//!
+//! Usage:
+//!   legacy-import --csv old_log.csv [--dry-run]
+//!   legacy-import --sqlite fod_log.db [--table detections] [--dry-run]
+//! Options:
+//!   --map mapping.csv       extra "legacy,class" category mappings
+//!   --create-classes        create unmapped categories instead of using "Other"
+//!   --source NAME           source for rows without one (default "legacy")
+//!   --batch N               rows per insert (default 500)
+
+#[allow(dead_code)]
+#[path = "../calendar.rs"]
+mod calendar;
+#[allow(dead_code)]
+#[path = "../crypto.rs"]
+mod crypto;
+#[allow(dead_code)]
+#[path = "../db.rs"]
+mod db;
+#[allow(dead_code)]
+#[path = "../filter.rs"]
+mod filter;
+#[allow(dead_code)]
+#[path = "../perf.rs"]
+mod perf;
+#[allow(dead_code)]
+#[path = "../secrets.rs"]
+mod secrets;
+
+use serde_json::json;
+use sqlx::{sqlite::SqliteRow, Column, PgPool, Row, SqlitePool};
+use std::{collections::HashMap, env, error::Error, process};
+use time::{
+    format_description::{well_known::Rfc3339, FormatItem},
+    macros::format_description,
+    OffsetDateTime, PrimitiveDateTime,
+};
+
+use db::NewEvent;
+
+type BoxResult<T> = Result<T, Box<dyn Error>>;
+
+/// Legacy category → fod_classes name, keys are lowercase
+const DEFAULT_MAPPING: &[(&str, &str)] = &[
+    ("bolt", "Bolt"),
+    ("bolts", "Bolt"),
+    ("nut", "Nut"),
+    ("screw", "Screw"),
+    ("wire", "Wire"),
+    ("cable", "Wire"),
+    ("metal", "Scrap Metal"),
+    ("metal_part", "Scrap Metal"),
+    ("metal fragment", "Scrap Metal"),
+    ("scrap", "Scrap Metal"),
+    ("stone", "Stone"),
+    ("rock", "Stone"),
+    ("gravel", "Stone"),
+    ("paper", "Paper"),
+    ("cardboard", "Paper"),
+    ("plastic", "Plastic"),
+    ("bottle", "Plastic"),
+    ("glass", "Glass"),
+    ("cloth", "Cloth"),
+    ("fabric", "Cloth"),
+    ("rag", "Cloth"),
+    ("tire", "Tire Pieces"),
+    ("tyre", "Tire Pieces"),
+    ("rubber", "Tire Pieces"),
+];
+
+// Column aliases used by the different legacy exports
+const TS_COLS: &[&str] = &["ts", "timestamp", "detected_at", "time", "datetime"];
+const CLASS_COLS: &[&str] = &["category", "class", "label", "object_class", "cls"];
+const CONF_COLS: &[&str] = &["confidence", "conf", "score"];
+const LAT_COLS: &[&str] = &["latitude", "lat"];
+const LON_COLS: &[&str] = &["longitude", "lon", "lng"];
+const SOURCE_COLS: &[&str] = &["source", "mode"];
+const REF_COLS: &[&str] = &["source_ref", "camera", "camera_id", "device", "device_id"];
+const ID_COLS: &[&str] = &["id", "rowid", "legacy_id"];
+
+const NAIVE_TS: &[FormatItem<'static>] = format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+const NAIVE_TS_FRAC: &[FormatItem<'static>] =
+    format_description!("[year]-[month]-[day] [hour]:[minute]:[second].[subsecond]");
+
+// ==================== Options ====================
+
+enum Input {
+    Csv(String),
+    Sqlite { path: String, table: String },
+}
+
+struct Options {
+    input: Input,
+    dry_run: bool,
+    map_file: Option<String>,
+    create_classes: bool,
+    default_source: String,
+    batch: usize,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: legacy-import (--csv FILE | --sqlite FILE [--table NAME]) [--dry-run] [--map FILE] [--create-classes] [--source NAME] [--batch N]");
+    process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut args = env::args().skip(1);
+    let (mut csv, mut sqlite, mut table) = (None, None, "detections".to_string());
+    let mut opts = Options {
+        input: Input::Csv(String::new()),
+        dry_run: false,
+        map_file: None,
+        create_classes: false,
+        default_source: "legacy".to_string(),
+        batch: 500,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--csv" => csv = Some(args.next().unwrap_or_else(|| usage())),
+            "--sqlite" => sqlite = Some(args.next().unwrap_or_else(|| usage())),
+            "--table" => table = args.next().unwrap_or_else(|| usage()),
+            "--map" => opts.map_file = Some(args.next().unwrap_or_else(|| usage())),
+            "--source" => opts.default_source = args.next().unwrap_or_else(|| usage()),
+            "--batch" => opts.batch = args.next().and_then(|s| s.parse().ok()).filter(|&n| n > 0).unwrap_or_else(|| usage()),
+            "--dry-run" => opts.dry_run = true,
+            "--create-classes" => opts.create_classes = true,
+            _ => usage(),
+        }
+    }
+    opts.input = match (csv, sqlite) {
+        (Some(path), None) => Input::Csv(path),
+        (None, Some(path)) => Input::Sqlite { path, table },
+        _ => usage(),
+    };
+    opts
+}
+
+// ==================== Legacy Records ====================
+
+/// One legacy row with its columns keyed by lowercase name
+struct LegacyRow {
+    line: usize,
+    fields: HashMap<String, String>,
+}
+
+impl LegacyRow {
+    fn get(&self, names: &[&str]) -> Option<&str> {
+        names.iter().find_map(|n| self.fields.get(*n)).map(|s| s.trim()).filter(|s| !s.is_empty())
+    }
+
+    fn get_f32(&self, names: &[&str], what: &str) -> Result<f32, String> {
+        let v = self.get(names).ok_or_else(|| format!("missing {}", what))?;
+        v.parse().map_err(|_| format!("invalid {} {:?}", what, v))
+    }
+}
+
+fn parse_ts(s: &str) -> Result<OffsetDateTime, String> {
+    if let Ok(ts) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Ok(ts);
+    }
+    // The Python logger wrote naive UTC timestamps
+    PrimitiveDateTime::parse(s, NAIVE_TS)
+        .or_else(|_| PrimitiveDateTime::parse(s, NAIVE_TS_FRAC))
+        .or_else(|_| PrimitiveDateTime::parse(&s.replace('T', " "), NAIVE_TS))
+        .map(|p| p.assume_utc())
+        .or_else(|_| s.parse::<f64>().map_err(|_| ()).and_then(|secs| {
+            OffsetDateTime::from_unix_timestamp_nanos((secs * 1e9) as i128).map_err(|_| ())
+        }))
+        .map_err(|_| format!("invalid timestamp {:?}", s))
+}
+
+fn read_csv(path: &str) -> BoxResult<Vec<LegacyRow>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers: Vec<String> = reader.headers()?.iter().map(|h| h.trim().to_lowercase()).collect();
+    let mut rows = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record?;
+        let fields = headers.iter().cloned().zip(record.iter().map(|v| v.to_string())).collect();
+        rows.push(LegacyRow { line: i + 2, fields });
+    }
+    Ok(rows)
+}
+
+fn sqlite_value(row: &SqliteRow, idx: usize) -> Option<String> {
+    if let Ok(v) = row.try_get::<Option<String>, _>(idx) {
+        return v;
+    }
+    if let Ok(v) = row.try_get::<Option<i64>, _>(idx) {
+        return v.map(|n| n.to_string());
+    }
+    if let Ok(v) = row.try_get::<Option<f64>, _>(idx) {
+        return v.map(|n| n.to_string());
+    }
+    None
+}
+
+async fn read_sqlite(path: &str, table: &str) -> BoxResult<Vec<LegacyRow>> {
+    if !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("invalid table name {:?}", table).into());
+    }
+    let pool = SqlitePool::connect(&format!("sqlite://{}?mode=ro", path)).await?;
+    let raw = sqlx::query(&format!("SELECT rowid AS rowid, * FROM {}", table)).fetch_all(&pool).await?;
+    let rows = raw
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let fields = row
+                .columns()
+                .iter()
+                .filter_map(|c| sqlite_value(row, c.ordinal()).map(|v| (c.name().to_lowercase(), v)))
+                .collect();
+            LegacyRow { line: i + 1, fields }
+        })
+        .collect();
+    Ok(rows)
+}
+
+// ==================== Category Mapping ====================
+
+fn load_mapping(extra: Option<&str>) -> BoxResult<HashMap<String, String>> {
+    let mut map: HashMap<String, String> =
+        DEFAULT_MAPPING.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    if let Some(path) = extra {
+        let mut reader = csv::ReaderBuilder::new().has_headers(false).from_path(path)?;
+        for record in reader.records() {
+            let record = record?;
+            if let (Some(from), Some(to)) = (record.get(0), record.get(1)) {
+                map.insert(from.trim().to_lowercase(), to.trim().to_string());
+            }
+        }
+    }
+    Ok(map)
+}
+
+fn map_category(map: &HashMap<String, String>, legacy: &str, create: bool) -> String {
+    let key = legacy.trim().to_lowercase();
+    match map.get(&key) {
+        Some(class) => class.clone(),
+        None if create => legacy.trim().to_string(),
+        None => "Other".to_string(),
+    }
+}
+
+// ==================== Import ====================
+
+struct Prepared {
+    ts: OffsetDateTime,
+    class_name: String,
+    confidence: f32,
+    latitude: f32,
+    longitude: f32,
+    source: String,
+    source_ref: String,
+    meta: serde_json::Value,
+}
+
+fn prepare(row: &LegacyRow, map: &HashMap<String, String>, opts: &Options) -> Result<Prepared, String> {
+    let ts = parse_ts(row.get(TS_COLS).ok_or("missing timestamp")?)?;
+    let legacy_class = row.get(CLASS_COLS).ok_or("missing category")?;
+    let confidence = row.get_f32(CONF_COLS, "confidence")?;
+    let confidence = if confidence > 1.0 { confidence / 100.0 } else { confidence };
+    let latitude = row.get_f32(LAT_COLS, "latitude")?;
+    let longitude = row.get_f32(LON_COLS, "longitude")?;
+    if !(0.0..=1.0).contains(&confidence) {
+        return Err(format!("confidence out of range {}", confidence));
+    }
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err("latitude/longitude out of range".to_string());
+    }
+    Ok(Prepared {
+        ts,
+        class_name: map_category(map, legacy_class, opts.create_classes),
+        confidence,
+        latitude,
+        longitude,
+        source: row.get(SOURCE_COLS).unwrap_or(&opts.default_source).to_string(),
+        source_ref: row.get(REF_COLS).unwrap_or("legacy_import").to_string(),
+        meta: json!({
+            "legacy_import": true,
+            "legacy_category": legacy_class,
+            "legacy_id": row.get(ID_COLS).map(|s| s.to_string()).unwrap_or_else(|| row.line.to_string()),
+        }),
+    })
+}
+
+async fn flush(db: &PgPool, batch: &mut Vec<NewEvent>) -> BoxResult<u64> {
+    let n = db::insert_events_batch(db, batch).await.map_err(|(_, e)| e)?;
+    batch.clear();
+    Ok(n)
+}
+
+#[tokio::main]
+async fn main() -> BoxResult<()> {
+    let opts = parse_args();
+    let mapping = load_mapping(opts.map_file.as_deref())?;
+
+    let rows = match &opts.input {
+        Input::Csv(path) => read_csv(path)?,
+        Input::Sqlite { path, table } => read_sqlite(path, table).await?,
+    };
+    eprintln!("read {} legacy rows", rows.len());
+
+    let db = if opts.dry_run {
+        None
+    } else {
+        secrets::init(&reqwest::Client::new()).await?;
+        crypto::init();
+        let url = secrets::get("DATABASE_URL").ok_or("DATABASE_URL must be set (or use --dry-run)")?;
+        Some(PgPool::connect(&url).await?)
+    };
+
+    let mut class_ids: HashMap<String, i32> = HashMap::new();
+    let mut per_class: HashMap<String, usize> = HashMap::new();
+    let mut batch: Vec<NewEvent> = Vec::with_capacity(opts.batch);
+    let (mut inserted, mut rejected) = (0u64, 0usize);
+    let progress_every = (rows.len() / 20).max(1000);
+
+    for (i, row) in rows.iter().enumerate() {
+        match prepare(row, &mapping, &opts) {
+            Ok(p) => {
+                *per_class.entry(p.class_name.clone()).or_default() += 1;
+                if let Some(db) = &db {
+                    let class_id = match class_ids.get(&p.class_name) {
+                        Some(id) => *id,
+                        None => {
+                            let id = db::get_or_create_class(db, &p.class_name).await.map_err(|(_, e)| e)?;
+                            class_ids.insert(p.class_name.clone(), id);
+                            id
+                        }
+                    };
+                    batch.push(NewEvent {
+                        ts: p.ts,
+                        class_id,
+                        object_count: 1,
+                        confidence: p.confidence,
+                        raw_confidence: None,
+                        latitude: p.latitude,
+                        longitude: p.longitude,
+                        source: p.source,
+                        source_ref: p.source_ref,
+                        bbox: None,
+                        meta: Some(p.meta),
+                    });
+                    if batch.len() >= opts.batch {
+                        inserted += flush(db, &mut batch).await?;
+                    }
+                }
+            }
+            Err(e) => {
+                rejected += 1;
+                eprintln!("row {}: {}", row.line, e);
+            }
+        }
+        if (i + 1) % progress_every == 0 {
+            eprintln!("progress: {}/{} rows ({} inserted, {} rejected)", i + 1, rows.len(), inserted, rejected);
+        }
+    }
+    if let Some(db) = &db {
+        inserted += flush(db, &mut batch).await?;
+    }
+
+    let mut classes: Vec<_> = per_class.into_iter().collect();
+    classes.sort_by_key(|c| std::cmp::Reverse(c.1));
+    println!("{}", if opts.dry_run { "dry run, nothing written" } else { "import finished" });
+    println!("  rows:     {}", rows.len());
+    println!("  valid:    {}", rows.len() - rejected);
+    println!("  rejected: {}", rejected);
+    if !opts.dry_run {
+        println!("  inserted: {}", inserted);
+    }
+    for (class, count) in classes {
+        println!("  {:>8}  {}", count, class);
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Site-local calendar for FOD Detection Backend aggregates
+//! Timezone (env `SITE_TIMEZONE`) and operational day start (env `SITE_DAY_START`), overridable per query
+
+use axum::http::StatusCode;
+use sqlx::PgPool;
+use std::{collections::HashMap, env, sync::OnceLock};
+
+use crate::db::internal;
+
+/// IANA zone and the local time an operational day begins; buckets are computed
+/// in SQL as `date_trunc(unit, ts AT TIME ZONE tz - day_start) + day_start`
+#[derive(Clone, Debug)]
+pub struct Calendar {
+    pub tz: String,
+    /// Minutes after local midnight, e.g. 360 for 06:00
+    pub day_start: i32,
+    /// Local buckets start on whole UTC hours, so hourly rollups can be regrouped into them
+    pub hour_aligned: bool,
+}
+
+impl Calendar {
+    /// Plain UTC days, matching the daily rollup buckets
+    pub fn is_utc_midnight(&self) -> bool {
+        self.tz == "UTC" && self.day_start == 0
+    }
+}
+
+/// `HH:MM` to minutes after midnight
+fn parse_day_start(s: &str) -> Option<i32> {
+    let (h, m) = s.trim().split_once(':')?;
+    let (h, m): (i32, i32) = (h.parse().ok()?, m.parse().ok()?);
+    ((0..24).contains(&h) && (0..60).contains(&m)).then_some(h * 60 + m)
+}
+
+/// Site default from the environment, UTC midnight when unset
+pub fn site() -> &'static Calendar {
+    static SITE: OnceLock<Calendar> = OnceLock::new();
+    SITE.get_or_init(|| Calendar {
+        tz: env::var("SITE_TIMEZONE").ok().filter(|s| !s.is_empty()).unwrap_or_else(|| "UTC".to_string()),
+        day_start: env::var("SITE_DAY_START")
+            .ok()
+            .map(|s| parse_day_start(&s).unwrap_or_else(|| panic!("SITE_DAY_START must be HH:MM, got {:?}", s)))
+            .unwrap_or(0),
+        // Needs the zone's UTC offset, resolved in from_query
+        hour_aligned: false,
+    })
+}
+
+/// Site calendar with `tz` / `day_start` query overrides; the zone is checked against Postgres
+pub async fn from_query(db: &PgPool, q: &HashMap<String, String>) -> Result<Calendar, (StatusCode, String)> {
+    let mut cal = site().clone();
+    if let Some(tz) = q.get("tz").filter(|s| !s.is_empty()) {
+        cal.tz = tz.clone();
+    }
+    if let Some(s) = q.get("day_start") {
+        cal.day_start =
+            parse_day_start(s).ok_or((StatusCode::BAD_REQUEST, "day_start must be HH:MM".to_string()))?;
+    }
+    let offset: Option<i32> = sqlx::query_scalar(
+        "SELECT (EXTRACT(EPOCH FROM utc_offset) / 60)::INT FROM pg_timezone_names WHERE name = $1",
+    )
+    .bind(&cal.tz)
+    .fetch_optional(db)
+    .await
+    .map_err(internal)?;
+    let offset = offset.ok_or((StatusCode::BAD_REQUEST, format!("Unknown timezone: {}", cal.tz)))?;
+    cal.hour_aligned = offset % 60 == 0 && cal.day_start % 60 == 0;
+    Ok(cal)
+}
@@ -0,0 +1,244 @@
+//! Camera calibration for FOD Detection Backend
+//! Estimates the physical size and ground position of detections; flags or drops ones too small
+//! to be real debris
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::{FromRow, PgPool};
+use std::{env, sync::OnceLock};
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{auth, db::internal, geometry, perf, AppState};
+
+// ==================== Config ====================
+
+struct Config {
+    /// Detections smaller than this are undersized; 0 disables the check
+    min_size_cm: f64,
+    /// Drop undersized detections instead of storing them flagged
+    drop: bool,
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| Config {
+        min_size_cm: env::var("MIN_OBJECT_SIZE_CM").ok().and_then(|s| s.parse().ok()).filter(|&n: &f64| n >= 0.0).unwrap_or(2.0),
+        drop: env::var("UNDERSIZED_ACTION").is_ok_and(|s| s.trim().eq_ignore_ascii_case("drop")),
+    })
+}
+
+/// Whether undersized detections are discarded rather than stored with `meta.undersized`
+pub fn drop_undersized() -> bool {
+    config().drop
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow, Clone)]
+pub struct Calibration {
+    pub source_ref: String,
+    pub gsd_cm_per_px: Option<f64>,
+    pub homography: Option<Vec<f64>>,
+    pub min_size_cm: Option<f64>,
+    /// Ground-plane origin of the homography; x is metres east of it, y metres north
+    pub origin_lat: Option<f64>,
+    pub origin_lon: Option<f64>,
+    pub updated_by: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct CalibrationRequest {
+    pub gsd_cm_per_px: Option<f64>,
+    /// Row-major 3x3, image pixels to ground-plane metres
+    pub homography: Option<Vec<f64>>,
+    pub min_size_cm: Option<f64>,
+    pub origin_lat: Option<f64>,
+    pub origin_lon: Option<f64>,
+}
+
+/// Estimated size of one detection
+pub struct Assessment {
+    /// Longest side of the bbox on the ground
+    pub size_cm: f64,
+    pub undersized: bool,
+}
+
+impl Assessment {
+    /// Record the estimate in an event's meta: `est_size_cm`, plus `undersized` when below threshold
+    pub fn annotate(&self, meta: &mut Map<String, Value>) {
+        meta.insert("est_size_cm".to_string(), json!((self.size_cm * 10.0).round() / 10.0));
+        if self.undersized {
+            meta.insert("undersized".to_string(), json!(true));
+        }
+    }
+}
+
+const COLUMNS: &str = "source_ref, gsd_cm_per_px, homography, min_size_cm, origin_lat, origin_lon, updated_by, updated_at";
+
+// ==================== Geometry ====================
+
+/// Pixel [cx, cy, w, h] from a YOLO-style centre bbox. Boxes with every value in [0, 1] are
+/// taken as normalized and need the image size.
+pub fn bbox_pixels(bbox: &Value, img_w: Option<f64>, img_h: Option<f64>) -> Option<[f64; 4]> {
+    let v: Vec<f64> = bbox.as_array()?.iter().map(Value::as_f64).collect::<Option<_>>()?;
+    let [x, y, w, h]: [f64; 4] = v.try_into().ok()?;
+    if [x, y, w, h].iter().all(|n| (0.0..=1.0).contains(n)) {
+        let (iw, ih) = (img_w?, img_h?);
+        Some([x * iw, y * ih, w * iw, h * ih])
+    } else {
+        Some([x, y, w, h])
+    }
+}
+
+/// Pixel to ground-plane metres; None for points on or behind the horizon
+pub fn project(h: &[f64], u: f64, v: f64) -> Option<(f64, f64)> {
+    let w = h[6] * u + h[7] * v + h[8];
+    if w.abs() < 1e-12 {
+        return None;
+    }
+    Some(((h[0] * u + h[1] * v + h[2]) / w, (h[3] * u + h[4] * v + h[5]) / w))
+}
+
+impl Calibration {
+    /// Longest side of a pixel bbox on the ground, in centimetres
+    pub fn size_cm(&self, [x, y, w, h]: [f64; 4]) -> Option<f64> {
+        if let Some(hm) = self.homography.as_deref().filter(|hm| hm.len() == 9) {
+            let corners = [(x - w / 2.0, y - h / 2.0), (x + w / 2.0, y - h / 2.0), (x + w / 2.0, y + h / 2.0), (x - w / 2.0, y + h / 2.0)];
+            let ground: Vec<(f64, f64)> = corners.iter().map(|&(u, v)| project(hm, u, v)).collect::<Option<_>>()?;
+            let longest = (0..4)
+                .map(|i| {
+                    let (a, b) = (ground[i], ground[(i + 1) % 4]);
+                    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+                })
+                .fold(0.0, f64::max);
+            return longest.is_finite().then_some(longest * 100.0);
+        }
+        self.gsd_cm_per_px.map(|gsd| w.max(h) * gsd)
+    }
+
+    /// Latitude/longitude of the bbox foot point (bottom centre, where the object meets the
+    /// ground); None unless the camera has a geo-referenced homography
+    pub fn ground_position(&self, bbox: &Value, img_w: Option<f64>, img_h: Option<f64>) -> Option<(f64, f64)> {
+        let (hm, lat0, lon0) = (self.homography.as_deref().filter(|hm| hm.len() == 9)?, self.origin_lat?, self.origin_lon?);
+        let [x, y, _, h] = bbox_pixels(bbox, img_w, img_h)?;
+        let (east, north) = project(hm, x, y + h / 2.0)?;
+        geometry::offset(lat0, lon0, east, north)
+    }
+
+    /// Size and threshold check for one detection; None without a usable bbox
+    pub fn assess(&self, bbox: &Value, img_w: Option<f64>, img_h: Option<f64>) -> Option<Assessment> {
+        let size_cm = self.size_cm(bbox_pixels(bbox, img_w, img_h)?)?;
+        let min = self.min_size_cm.unwrap_or(config().min_size_cm);
+        Some(Assessment { size_cm, undersized: min > 0.0 && size_cm < min })
+    }
+}
+
+// ==================== Queries ====================
+
+pub async fn get(db: &PgPool, source_ref: &str) -> Result<Option<Calibration>, (StatusCode, String)> {
+    let sql = format!("SELECT {} FROM camera_calibrations WHERE source_ref = $1", COLUMNS);
+    let q = sqlx::query_as::<_, Calibration>(&sql).bind(source_ref).fetch_optional(db);
+    perf::timed("get_calibration", || format!("source_ref={:?}", source_ref), q).await.map_err(internal)
+}
+
+// ==================== Handlers ====================
+
+/// GET /calibrations — every calibrated camera (login required)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let rows = sqlx::query_as::<_, Calibration>(&format!("SELECT {} FROM camera_calibrations ORDER BY source_ref", COLUMNS))
+        .fetch_all(&st.db)
+        .await
+        .map_err(internal)?;
+    Ok(Json(rows))
+}
+
+/// PUT /admin/calibrations/:source_ref — set a camera's GSD and/or homography, optional
+/// minimum size and homography origin (admin)
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(source_ref): Path<String>,
+    Json(req): Json<CalibrationRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let bad = |msg: &str| Err((StatusCode::BAD_REQUEST, msg.to_string()));
+    if req.gsd_cm_per_px.is_none() && req.homography.is_none() {
+        return bad("gsd_cm_per_px or homography is required");
+    }
+    if req.gsd_cm_per_px.is_some_and(|g| !(g.is_finite() && g > 0.0)) {
+        return bad("gsd_cm_per_px must be positive");
+    }
+    if let Some(h) = &req.homography {
+        if h.len() != 9 || h.iter().any(|n| !n.is_finite()) {
+            return bad("homography must be 9 numbers, row-major");
+        }
+    }
+    if req.min_size_cm.is_some_and(|m| !(m.is_finite() && m >= 0.0)) {
+        return bad("min_size_cm must not be negative");
+    }
+    match (req.origin_lat, req.origin_lon) {
+        (None, None) => {}
+        (Some(lat), Some(lon)) if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) => {
+            if req.homography.is_none() {
+                return bad("origin_lat/origin_lon need a homography");
+            }
+        }
+        _ => return bad("origin_lat and origin_lon must be given together and be valid"),
+    }
+
+    let row = sqlx::query_as::<_, Calibration>(&format!(
+        r#"
+        INSERT INTO camera_calibrations (source_ref, gsd_cm_per_px, homography, min_size_cm, origin_lat, origin_lon, updated_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (source_ref) DO UPDATE
+            SET gsd_cm_per_px = EXCLUDED.gsd_cm_per_px, homography = EXCLUDED.homography,
+                min_size_cm = EXCLUDED.min_size_cm, origin_lat = EXCLUDED.origin_lat, origin_lon = EXCLUDED.origin_lon,
+                updated_by = EXCLUDED.updated_by, updated_at = NOW()
+        RETURNING {}
+        "#,
+        COLUMNS
+    ))
+    .bind(&source_ref)
+    .bind(req.gsd_cm_per_px)
+    .bind(&req.homography)
+    .bind(req.min_size_cm)
+    .bind(req.origin_lat)
+    .bind(req.origin_lon)
+    .bind(&claims.username)
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    info!(source_ref = %source_ref, by = %claims.username, "camera calibration set");
+    Ok(Json(row))
+}
+
+/// DELETE /admin/calibrations/:source_ref — stop estimating sizes for a camera (admin)
+pub async fn delete_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(source_ref): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let done = sqlx::query("DELETE FROM camera_calibrations WHERE source_ref = $1")
+        .bind(&source_ref)
+        .execute(&st.db)
+        .await
+        .map_err(internal)?;
+    if done.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Calibration not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,361 @@
+//! Camera access for FOD Detection Backend
+//! Low-rate MJPEG previews and on-demand snapshots from each camera's snapshot URL, credentials kept server-side
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use bytes::Bytes;
+use futures::stream;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    env,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{ai, auth, crypto, db::internal, flags, onvif, perf, AppState};
+
+/// Multipart boundary between frames
+const BOUNDARY: &str = "frame";
+/// A stream ends after this many failed snapshots in a row
+const MAX_FAILURES: u32 = 5;
+/// Per-snapshot request timeout
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// ==================== Config ====================
+
+/// How cameras are reached, read from the env once and carried in `AppState::cameras`
+#[derive(Clone)]
+pub struct Config {
+    /// source_ref -> snapshot URL, credentials included (`CAMERA_SNAPSHOT_URLS`)
+    pub snapshot_urls: BTreeMap<String, String>,
+    /// Time between preview frames (`CAMERA_PREVIEW_INTERVAL_MS`, at least 200)
+    pub preview_interval: Duration,
+    /// source_ref -> `[profile@]url` of its ONVIF PTZ service (`CAMERA_PTZ_URLS`)
+    pub ptz_urls: BTreeMap<String, String>,
+    /// UDP port devices answer WS-Discovery probes on (`CAMERA_DISCOVERY_PORT`)
+    pub discovery_port: u16,
+    /// Open previews allowed per camera (`CAMERA_PREVIEW_MAX_STREAMS`)
+    pub max_previews: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { snapshot_urls: BTreeMap::new(), preview_interval: Duration::from_millis(1000), ptz_urls: BTreeMap::new(), discovery_port: 3702, max_previews: 2 }
+    }
+}
+
+/// `id=value,...` from `var`
+fn pairs(var: &str) -> BTreeMap<String, String> {
+    env::var(var)
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(id, value)| (id.trim().to_string(), value.trim().to_string()))
+        .filter(|(id, value)| !id.is_empty() && !value.is_empty())
+        .collect()
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let mut ptz_urls = pairs("CAMERA_PTZ_URLS");
+        ptz_urls.retain(|id, spec| {
+            let valid = onvif::valid_ptz_spec(spec);
+            if !valid {
+                warn!(camera = %id, "ignoring invalid CAMERA_PTZ_URLS entry");
+            }
+            valid
+        });
+        let default = Config::default();
+        Config {
+            snapshot_urls: pairs("CAMERA_SNAPSHOT_URLS"),
+            preview_interval: env::var("CAMERA_PREVIEW_INTERVAL_MS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .map(|ms: u64| Duration::from_millis(ms.max(200)))
+                .unwrap_or(default.preview_interval),
+            ptz_urls,
+            discovery_port: env::var("CAMERA_DISCOVERY_PORT").ok().and_then(|s| s.parse().ok()).unwrap_or(default.discovery_port),
+            max_previews: env::var("CAMERA_PREVIEW_MAX_STREAMS").ok().and_then(|s| s.parse().ok()).unwrap_or(default.max_previews),
+        }
+    }
+}
+
+/// Preview slots per camera, so a handful of viewers can't flood a camera with snapshot requests
+#[derive(Clone, Default)]
+pub struct Previews(Arc<Mutex<HashMap<String, Arc<Semaphore>>>>);
+
+impl Previews {
+    /// Hold one of `id`'s `max` slots until the permit drops with its stream
+    fn open(&self, id: &str, max: usize) -> Result<OwnedSemaphorePermit, (StatusCode, String)> {
+        let slots = self.0.lock().unwrap().entry(id.to_string()).or_insert_with(|| Arc::new(Semaphore::new(max))).clone();
+        slots.try_acquire_owned().map_err(|_| {
+            (StatusCode::TOO_MANY_REQUESTS, format!("This camera already has {} open previews", max))
+        })
+    }
+}
+
+/// One JPEG from the camera; errors leave the URL (and its credentials) out
+async fn snapshot(http: &Client, url: &str) -> Result<Bytes, String> {
+    let resp = http.get(url).timeout(SNAPSHOT_TIMEOUT).send().await.map_err(|e| e.without_url().to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("camera answered {}", resp.status()));
+    }
+    resp.bytes().await.map_err(|e| e.without_url().to_string())
+}
+
+fn part(jpeg: &[u8]) -> Bytes {
+    let mut buf = format!("--{}\r\nContent-Type: image/jpeg\r\nContent-Length: {}\r\n\r\n", BOUNDARY, jpeg.len()).into_bytes();
+    buf.extend_from_slice(jpeg);
+    buf.extend_from_slice(b"\r\n");
+    Bytes::from(buf)
+}
+
+/// Snapshot URL of a camera with its credentials, from the env or its registration
+async fn snapshot_url(st: &AppState, id: &str) -> Result<String, (StatusCode, String)> {
+    if let Some(url) = st.cameras.snapshot_urls.get(id) {
+        return Ok(url.clone());
+    }
+    let not_found = || (StatusCode::NOT_FOUND, "No snapshot URL for this camera".to_string());
+    let camera = registered(&st.db, id).await?.ok_or_else(not_found)?;
+    let mut url = camera.snapshot_url.as_deref().and_then(|u| Url::parse(u).ok()).ok_or_else(not_found)?;
+    if let Some((username, password)) = camera.login() {
+        // reqwest turns URL credentials into basic auth
+        let _ = url.set_username(&username);
+        let _ = url.set_password(Some(&password));
+    }
+    Ok(url.to_string())
+}
+
+// ==================== Registered Cameras ====================
+
+/// Associated data of sealed camera credentials
+pub const CREDENTIALS: &str = "camera_credentials";
+
+/// A camera registered through `POST /cameras`, see `onvif::register_handler`
+#[derive(Serialize, FromRow)]
+pub struct Registered {
+    pub source_ref: String,
+    pub name: Option<String>,
+    pub hardware: Option<String>,
+    pub device_url: String,
+    pub snapshot_url: Option<String>,
+    pub ptz_url: Option<String>,
+    pub profile: Option<String>,
+    #[serde(skip)]
+    pub credentials: Option<Value>,
+    pub registered_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub registered_at: OffsetDateTime,
+}
+
+pub const REGISTERED_COLUMNS: &str =
+    "source_ref, name, hardware, device_url, snapshot_url, ptz_url, profile, credentials, registered_by, registered_at";
+
+impl Registered {
+    /// Username and password, None for open cameras or when the key is gone
+    pub fn login(&self) -> Option<(String, String)> {
+        let plain = crypto::open_secret(CREDENTIALS, self.credentials.as_ref()?)?;
+        Some((plain.get("username")?.as_str()?.to_string(), plain.get("password")?.as_str()?.to_string()))
+    }
+}
+
+pub async fn registered(db: &PgPool, source_ref: &str) -> Result<Option<Registered>, (StatusCode, String)> {
+    let sql = format!("SELECT {} FROM cameras WHERE source_ref = $1", REGISTERED_COLUMNS);
+    let q = sqlx::query_as::<_, Registered>(&sql).bind(source_ref).fetch_optional(db);
+    perf::timed("get_camera", || format!("source_ref={}", source_ref), q).await.map_err(internal)
+}
+
+pub async fn all_registered(db: &PgPool) -> Result<Vec<Registered>, (StatusCode, String)> {
+    let sql = format!("SELECT {} FROM cameras ORDER BY source_ref", REGISTERED_COLUMNS);
+    let q = sqlx::query_as::<_, Registered>(&sql).fetch_all(db);
+    perf::timed("list_cameras", String::new, q).await.map_err(internal)
+}
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct SnapshotParams {
+    /// Run the frame through the AI
+    #[serde(default)]
+    pub detect: bool,
+    pub conf: Option<f32>,
+}
+
+#[derive(Serialize, Default)]
+pub struct Camera {
+    pub source_ref: String,
+    /// `/cameras/:id/preview` and `/cameras/:id/snapshot` work
+    pub preview: bool,
+    /// `/cameras/:id/ptz/*` work
+    pub ptz: bool,
+    /// Added through `POST /cameras` rather than the env
+    pub registered: bool,
+}
+
+/// Stored snapshot without the image bytes
+#[derive(Serialize, FromRow)]
+pub struct Snapshot {
+    pub id: Uuid,
+    pub source_ref: String,
+    /// AI response, absent unless `detect=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detections: Option<Value>,
+    pub taken_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// Signed link to the image for an `<img>` tag, see `auth::sign_link`
+    #[sqlx(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+}
+
+// ==================== Handlers ====================
+
+/// GET /cameras — cameras with a live preview or PTZ control, configured or registered
+pub async fn list_handler(State(st): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut cameras: BTreeMap<String, Camera> = BTreeMap::new();
+    for row in all_registered(&st.db).await? {
+        let camera = Camera { source_ref: row.source_ref.clone(), preview: row.snapshot_url.is_some(), ptz: row.ptz_url.is_some(), registered: true };
+        cameras.insert(row.source_ref, camera);
+    }
+    for id in st.cameras.snapshot_urls.keys() {
+        cameras.entry(id.clone()).or_insert_with(|| Camera { source_ref: id.clone(), ..Default::default() }).preview = true;
+    }
+    for id in st.cameras.ptz_urls.keys() {
+        cameras.entry(id.clone()).or_insert_with(|| Camera { source_ref: id.clone(), ..Default::default() }).ptz = true;
+    }
+    Ok(Json(cameras.into_values().collect::<Vec<_>>()))
+}
+
+/// GET /cameras/:id/preview-url — signed `/cameras/:id/preview` link for an `<img>` tag, which
+/// can't send the bearer token (login)
+pub async fn preview_url_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    snapshot_url(&st, &id).await?;
+    let (url, expires_at) = auth::sign_link(&format!("/cameras/{}/preview", id));
+    Ok(Json(json!({ "url": url, "expires_at": expires_at.format(&Rfc3339).map_err(internal)? })))
+}
+
+/// GET /cameras/:id/preview — `multipart/x-mixed-replace` JPEG stream for an `<img>` tag,
+/// one snapshot every `CAMERA_PREVIEW_INTERVAL_MS` until the client goes away; at most
+/// `CAMERA_PREVIEW_MAX_STREAMS` at once per camera (login or signed link)
+pub async fn preview_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(link): Query<auth::LinkParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user_or_link(&headers, &format!("/cameras/{}/preview", id), &link)?;
+    let url = snapshot_url(&st, &id).await?;
+    let permit = st.previews.open(&id, st.cameras.max_previews)?;
+    // The first frame decides the status code; later failures only end the stream
+    let first = snapshot(&st.http, &url).await.map_err(|e| {
+        warn!(camera = %id, error = %e, "camera snapshot failed");
+        (StatusCode::BAD_GATEWAY, "Camera unreachable".to_string())
+    })?;
+    let interval = st.cameras.preview_interval;
+    let frames = stream::unfold(Some(first), move |pending| {
+        let (http, url, id) = (st.http.clone(), url.clone(), id.clone());
+        // Owned by the stream, so the slot frees when the client goes away
+        let _permit = &permit;
+        async move {
+            if let Some(jpeg) = pending {
+                return Some((Ok::<_, Infallible>(part(&jpeg)), None));
+            }
+            let mut failures = 0;
+            loop {
+                tokio::time::sleep(interval).await;
+                match snapshot(&http, &url).await {
+                    Ok(jpeg) => return Some((Ok(part(&jpeg)), None)),
+                    Err(e) if failures + 1 >= MAX_FAILURES => {
+                        warn!(camera = %id, error = %e, "camera preview stopped after repeated failures");
+                        return None;
+                    }
+                    Err(e) => {
+                        warn!(camera = %id, error = %e, "camera snapshot failed, skipping frame");
+                        failures += 1;
+                    }
+                }
+            }
+        }
+    });
+    Ok((
+        [
+            (header::CONTENT_TYPE, format!("multipart/x-mixed-replace; boundary={}", BOUNDARY)),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        Body::from_stream(frames),
+    ))
+}
+
+/// POST /cameras/:id/snapshot?detect=&conf= — grab one frame now, optionally run detection,
+/// and keep it; the image is served from `/snapshots/:id/image`
+pub async fn snapshot_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(params): Query<SnapshotParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let url = snapshot_url(&st, &id).await?;
+    let jpeg = snapshot(&st.http, &url).await.map_err(|e| {
+        warn!(camera = %id, error = %e, "camera snapshot failed");
+        (StatusCode::BAD_GATEWAY, "Camera unreachable".to_string())
+    })?;
+    let detections = if params.detect {
+        let fallback = st.flags.enabled(&st.db, flags::AI_FALLBACK).await;
+        let opts = ai::DetectOptions { conf: params.conf, imgsz: None, fallback };
+        Some(st.ai.detect(&st.http, jpeg.to_vec(), format!("{}.jpg", id), opts).await?)
+    } else {
+        None
+    };
+    let q = sqlx::query_as::<_, Snapshot>(
+        r#"
+        INSERT INTO camera_snapshots (source_ref, image, detections, taken_by) VALUES ($1, $2, $3, $4)
+        RETURNING id, source_ref, detections, taken_by, created_at
+        "#,
+    )
+    .bind(&id)
+    .bind(jpeg.as_ref())
+    .bind(&detections)
+    .bind(&claims.username)
+    .fetch_one(&st.db);
+    let mut snapshot = perf::timed("insert_camera_snapshot", || format!("source_ref={}", id), q).await.map_err(internal)?;
+    snapshot.image_url = Some(auth::sign_link(&format!("/snapshots/{}/image", snapshot.id)).0);
+    info!(camera = %id, snapshot_id = %snapshot.id, detect = params.detect, by = %claims.username, "camera snapshot taken");
+    Ok((StatusCode::CREATED, Json(snapshot)))
+}
+
+/// GET /snapshots/:id/image — the JPEG of an on-demand snapshot (login or its signed `image_url`)
+pub async fn image_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Query(link): Query<auth::LinkParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user_or_link(&headers, &format!("/snapshots/{}/image", id), &link)?;
+    let q = sqlx::query_scalar::<_, Vec<u8>>("SELECT image FROM camera_snapshots WHERE id = $1").bind(id).fetch_optional(&st.db);
+    let image = perf::timed("camera_snapshot_image", || format!("id={}", id), q)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "Snapshot not found".to_string()))?;
+    Ok(([(header::CONTENT_TYPE, "image/jpeg")], image))
+}
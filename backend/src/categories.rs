@@ -0,0 +1,267 @@
+//! Standard FOD categories for FOD Detection Backend
+//! Mapping of internal classes to the ACI/ICAO reporting taxonomy and the monthly safety report per runway
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool};
+use time::{macros::format_description, Date, Month, OffsetDateTime};
+use tracing::info;
+
+use crate::{auth, calendar, db::{self, internal}, perf, AppState};
+
+/// Reporting categories: code, name in the report
+pub const CATEGORIES: &[(&str, &str)] = &[
+    ("aircraft_parts", "Aircraft parts"),
+    ("hardware", "Hardware (nuts, bolts, safety wire)"),
+    ("gse_parts", "Ground support equipment parts"),
+    ("pavement", "Pavement fragments"),
+    ("construction", "Construction and maintenance materials"),
+    ("baggage", "Baggage and cargo items"),
+    ("personal", "Personal items"),
+    ("plastic_rubber", "Plastic and rubber"),
+    ("paper", "Paper and packaging"),
+    ("natural", "Natural (stones, sand, vegetation)"),
+    ("wildlife", "Wildlife remains"),
+    ("other", "Other / unidentified"),
+];
+/// Category of classes with no mapping of their own or on an ancestor
+const FALLBACK: &str = "other";
+
+const CSV_HEADER: [&str; 6] = ["month", "runway", "category", "category_name", "events", "objects"];
+
+fn category_name(code: &str) -> &'static str {
+    CATEGORIES.iter().find(|(c, _)| *c == code).map(|(_, n)| *n).unwrap_or("Other / unidentified")
+}
+
+// ==================== Models ====================
+
+/// Effective category of a class
+#[derive(Serialize, FromRow)]
+pub struct Mapping {
+    pub class: String,
+    pub category: String,
+    /// Ancestor the category comes from; None when mapped directly or unmapped
+    pub inherited_from: Option<String>,
+    /// No mapping on the class or its ancestors, reported as `other`
+    pub unmapped: bool,
+}
+
+#[derive(Deserialize)]
+pub struct MappingRequest {
+    /// Category code; None removes the mapping so the class inherits again
+    pub category: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReportParams {
+    /// `YYYY-MM` in the site timezone, default the previous month
+    pub month: Option<String>,
+    /// `json` (default) or `csv`
+    pub format: Option<String>,
+    /// Count quarantined sources too
+    #[serde(default)]
+    pub include_quarantine: bool,
+}
+
+#[derive(FromRow)]
+struct ReportRow {
+    runway: Option<String>,
+    category: String,
+    events: i64,
+    objects: i64,
+}
+
+#[derive(Serialize)]
+pub struct CategoryCount {
+    pub category: String,
+    pub name: &'static str,
+    pub events: i64,
+    pub objects: i64,
+}
+
+#[derive(Serialize)]
+pub struct RunwayReport {
+    /// None for events without `meta.runway`
+    pub runway: Option<String>,
+    pub events: i64,
+    pub objects: i64,
+    pub categories: Vec<CategoryCount>,
+}
+
+// ==================== Queries ====================
+
+/// Nearest mapped ancestor (the class itself first) of every class
+const EFFECTIVE_CATEGORY: &str = r#"
+    SELECT DISTINCT ON (a.class_id) a.class_id, m.category, a.ancestor_id, a.distance
+    FROM class_ancestors a
+    JOIN fod_category_mappings m ON m.class_id = a.ancestor_id
+    ORDER BY a.class_id, a.distance
+"#;
+
+pub async fn mappings(db: &PgPool) -> Result<Vec<Mapping>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        WITH eff AS ({})
+        SELECT fc.name AS class, COALESCE(eff.category, '{}') AS category,
+               CASE WHEN eff.distance > 0 THEN anc.name END AS inherited_from,
+               eff.category IS NULL AS unmapped
+        FROM fod_classes fc
+        LEFT JOIN eff ON eff.class_id = fc.id
+        LEFT JOIN fod_classes anc ON anc.id = eff.ancestor_id
+        ORDER BY fc.name
+        "#,
+        EFFECTIVE_CATEGORY, FALLBACK
+    );
+    let q = sqlx::query_as::<_, Mapping>(&sql).fetch_all(db);
+    perf::timed("fod_category_mappings", String::new, q).await.map_err(internal)
+}
+
+/// Events and objects per runway and category in the local calendar month starting `month`
+async fn report(db: &PgPool, month: Date, tz: &str, include_quarantine: bool) -> Result<Vec<ReportRow>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        WITH eff AS ({})
+        SELECT NULLIF(UPPER(BTRIM(e.runway)), '') AS runway, COALESCE(eff.category, '{}') AS category,
+               COUNT(*) AS events, COALESCE(SUM(e.object_count), 0)::BIGINT AS objects
+        FROM events e
+        LEFT JOIN eff ON eff.class_id = e.class_id
+        WHERE e.ts >= ($1::DATE::TIMESTAMP AT TIME ZONE $2)
+          AND e.ts < (($1::DATE + INTERVAL '1 month')::TIMESTAMP AT TIME ZONE $2){}
+        GROUP BY 1, 2
+        ORDER BY 1 NULLS LAST, 2
+        "#,
+        EFFECTIVE_CATEGORY,
+        FALLBACK,
+        db::quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, ReportRow>(&sql).bind(month).bind(tz).fetch_all(db);
+    perf::timed("fod_category_report", || format!("month={} tz={}", month, tz), q).await.map_err(internal)
+}
+
+/// First day of `YYYY-MM`, or of the month before today
+fn parse_month(s: Option<&str>) -> Result<Date, (StatusCode, String)> {
+    let bad = || (StatusCode::BAD_REQUEST, "month must be YYYY-MM".to_string());
+    match s.map(str::trim) {
+        Some(s) => {
+            let (y, m) = s.split_once('-').ok_or_else(bad)?;
+            let month = Month::try_from(m.parse::<u8>().map_err(|_| bad())?).map_err(|_| bad())?;
+            Date::from_calendar_date(y.parse().map_err(|_| bad())?, month, 1).map_err(|_| bad())
+        }
+        None => {
+            let today = OffsetDateTime::now_utc().date();
+            let (year, month) = match today.month() {
+                Month::January => (today.year() - 1, Month::December),
+                m => (today.year(), m.previous()),
+            };
+            Date::from_calendar_date(year, month, 1).map_err(internal)
+        }
+    }
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/fod-categories — the standard categories and each class's effective one (admin)
+pub async fn list_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let categories: Vec<_> = CATEGORIES.iter().map(|(code, name)| json!({ "code": code, "name": name })).collect();
+    Ok(Json(json!({ "categories": categories, "mappings": mappings(&st.db).await? })))
+}
+
+/// PUT /admin/fod-categories/:class — `{category}` map a class (and its unmapped subclasses),
+/// `{"category": null}` to inherit again (admin)
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(class): Path<String>,
+    Json(req): Json<MappingRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let class_id: i32 = sqlx::query_scalar("SELECT id FROM fod_classes WHERE name = $1")
+        .bind(&class)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown class: {}", class)))?;
+    match req.category.as_deref().map(|c| c.trim().to_lowercase()) {
+        Some(category) => {
+            if !CATEGORIES.iter().any(|(c, _)| *c == category) {
+                let codes: Vec<&str> = CATEGORIES.iter().map(|(c, _)| *c).collect();
+                return Err((StatusCode::BAD_REQUEST, format!("Unknown category: {} (expected one of {})", category, codes.join(", "))));
+            }
+            sqlx::query(
+                r#"
+                INSERT INTO fod_category_mappings (class_id, category, updated_by) VALUES ($1, $2, $3)
+                ON CONFLICT (class_id) DO UPDATE SET category = EXCLUDED.category, updated_by = EXCLUDED.updated_by, updated_at = NOW()
+                "#,
+            )
+            .bind(class_id)
+            .bind(&category)
+            .bind(&claims.username)
+            .execute(&st.db)
+            .await
+            .map_err(internal)?;
+            info!(class = %class, category = %category, by = %claims.username, "FOD category mapped");
+        }
+        None => {
+            sqlx::query("DELETE FROM fod_category_mappings WHERE class_id = $1").bind(class_id).execute(&st.db).await.map_err(internal)?;
+            info!(class = %class, by = %claims.username, "FOD category mapping removed");
+        }
+    }
+    Ok(Json(mappings(&st.db).await?))
+}
+
+/// GET /reports/fod-categories?month=YYYY-MM&format=json|csv — events and objects per runway
+/// and standard category for one month in the site timezone (login required)
+pub async fn report_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<ReportParams>,
+) -> Result<Response, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let csv = match p.format.as_deref() {
+        None | Some("json") => false,
+        Some("csv") => true,
+        Some(_) => return Err((StatusCode::BAD_REQUEST, "format must be json or csv".to_string())),
+    };
+    let month = parse_month(p.month.as_deref())?;
+    let label = month.format(format_description!("[year]-[month]")).map_err(internal)?;
+    let tz = &calendar::site().tz;
+    let rows = report(&st.db, month, tz, p.include_quarantine).await?;
+
+    if csv {
+        let mut out = csv::Writer::from_writer(Vec::new());
+        out.write_record(CSV_HEADER).map_err(internal)?;
+        for r in &rows {
+            let (events, objects) = (r.events.to_string(), r.objects.to_string());
+            out.write_record([&label, r.runway.as_deref().unwrap_or(""), &r.category, category_name(&r.category), &events, &objects])
+                .map_err(internal)?;
+        }
+        let body = out.into_inner().map_err(internal)?;
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"fod-categories-{}.csv\"", label)),
+            ],
+            body,
+        )
+            .into_response());
+    }
+
+    let mut runways: Vec<RunwayReport> = Vec::new();
+    for r in rows {
+        if runways.last().is_none_or(|last| last.runway != r.runway) {
+            runways.push(RunwayReport { runway: r.runway.clone(), events: 0, objects: 0, categories: Vec::new() });
+        }
+        let runway = runways.last_mut().expect("pushed above");
+        runway.events += r.events;
+        runway.objects += r.objects;
+        runway.categories.push(CategoryCount { name: category_name(&r.category), category: r.category, events: r.events, objects: r.objects });
+    }
+    Ok(Json(json!({ "month": label, "timezone": tz, "runways": runways })).into_response())
+}
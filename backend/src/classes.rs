@@ -0,0 +1,377 @@
+//! FOD class taxonomy for FOD Detection Backend
+//! Label aliases, merging of duplicate classes and the parent/child hierarchy
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tracing::info;
+
+use crate::{
+    auth,
+    db::{self, class_alias_key, internal},
+    perf, AppState,
+};
+
+// ==================== Types ====================
+
+#[derive(Serialize, FromRow)]
+pub struct ClassAlias {
+    pub alias: String,
+    pub class_name: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct AliasRequest {
+    /// Model label; stored normalized
+    pub alias: String,
+    /// Existing canonical class
+    pub class: String,
+}
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    /// Duplicate classes to fold in and delete
+    pub from: Vec<String>,
+    pub into: String,
+}
+
+#[derive(Serialize)]
+pub struct MergeSummary {
+    pub into: String,
+    pub merged: Vec<String>,
+    pub events_moved: u64,
+}
+
+#[derive(FromRow)]
+struct ClassRow {
+    id: i32,
+    name: String,
+    description: Option<String>,
+    parent_id: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ClassNode {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub children: Vec<ClassNode>,
+}
+
+#[derive(Deserialize)]
+pub struct ParentRequest {
+    /// None makes the class top-level
+    pub parent: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BreakdownParams {
+    /// Depth to roll up to, 0 = top-level classes (default 0)
+    pub level: Option<i32>,
+    /// Only count classes in this subtree
+    pub under: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    /// Count quarantined sources too
+    #[serde(default)]
+    pub include_quarantine: bool,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ClassGroup {
+    /// Ancestor at the requested level, or the class itself when it sits higher up
+    pub class: String,
+    pub depth: i32,
+    pub events: i64,
+    pub objects: i64,
+}
+
+// ==================== Taxonomy ====================
+
+/// Classes as a forest, children sorted by name. Classes caught in a cycle (only possible
+/// by editing the table by hand) are left out.
+pub async fn tree(db: &PgPool) -> Result<Vec<ClassNode>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, ClassRow>("SELECT id, name, description, parent_id FROM fod_classes ORDER BY name").fetch_all(db);
+    let rows = perf::timed("class_tree", String::new, q).await.map_err(internal)?;
+    let mut children: HashMap<Option<i32>, Vec<ClassRow>> = HashMap::new();
+    for r in rows {
+        children.entry(r.parent_id).or_default().push(r);
+    }
+    fn build(parent: Option<i32>, children: &mut HashMap<Option<i32>, Vec<ClassRow>>) -> Vec<ClassNode> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|r| ClassNode { id: r.id, children: build(Some(r.id), children), name: r.name, description: r.description })
+            .collect()
+    }
+    Ok(build(None, &mut children))
+}
+
+/// Event and object counts in [from, to) rolled up to the ancestor at `level`
+pub async fn breakdown(
+    db: &PgPool,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    level: i32,
+    under: Option<&str>,
+    include_quarantine: bool,
+) -> Result<Vec<ClassGroup>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        WITH a AS (
+            SELECT class_id, ancestor_id, distance, MAX(distance) OVER (PARTITION BY class_id) AS depth
+            FROM class_ancestors
+        ),
+        grp AS (
+            SELECT class_id, ancestor_id AS group_id, depth - distance AS group_depth
+            FROM a WHERE distance = GREATEST(depth - $3, 0)
+        )
+        SELECT g.name AS class, grp.group_depth AS depth,
+               COUNT(*) AS events, SUM(e.object_count)::BIGINT AS objects
+        FROM events e
+        JOIN grp ON grp.class_id = e.class_id
+        JOIN fod_classes g ON g.id = grp.group_id
+        WHERE e.ts >= $1 AND e.ts < $2
+          AND ($4::TEXT IS NULL OR e.class_id IN (SELECT ca.class_id FROM class_ancestors ca
+                                                  JOIN fod_classes p ON p.id = ca.ancestor_id
+                                                  WHERE p.name = $4)){}
+        GROUP BY g.name, grp.group_depth
+        ORDER BY events DESC, g.name
+        "#,
+        db::quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, ClassGroup>(&sql).bind(from).bind(to).bind(level).bind(under).fetch_all(db);
+    perf::timed("class_breakdown", || format!("from={} to={} level={} under={:?}", from, to, level, under), q)
+        .await
+        .map_err(internal)
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/classes/aliases — every label and the class it resolves to (admin)
+pub async fn list_aliases_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let rows = sqlx::query_as::<_, ClassAlias>(
+        r#"
+        SELECT a.alias, fc.name AS class_name, a.created_at
+        FROM class_aliases a
+        JOIN fod_classes fc ON fc.id = a.class_id
+        ORDER BY fc.name, a.alias
+        "#,
+    )
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(rows))
+}
+
+/// PUT /admin/classes/aliases — point a label at a class, replacing any earlier mapping (admin).
+/// Events already stored under another class are not moved; merge the classes for that.
+pub async fn put_alias_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<AliasRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let alias = class_alias_key(&req.alias);
+    if alias.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "alias is empty".to_string()));
+    }
+    let row = sqlx::query_as::<_, ClassAlias>(
+        r#"
+        WITH target AS (SELECT id, name FROM fod_classes WHERE name = $2),
+        upserted AS (
+            INSERT INTO class_aliases (alias, class_id)
+            SELECT $1, id FROM target
+            ON CONFLICT (alias) DO UPDATE SET class_id = EXCLUDED.class_id, created_at = NOW()
+            RETURNING alias, created_at
+        )
+        SELECT u.alias, t.name AS class_name, u.created_at FROM upserted u, target t
+        "#,
+    )
+    .bind(&alias)
+    .bind(req.class.trim())
+    .fetch_optional(&st.db)
+    .await
+    .map_err(internal)?
+    .ok_or((StatusCode::NOT_FOUND, format!("Unknown class: {}", req.class.trim())))?;
+    info!(alias = %row.alias, class = %row.class_name, by = %claims.username, "class alias set");
+    Ok(Json(row))
+}
+
+/// POST /admin/classes/merge — fold duplicate classes into one: their events, rollups and
+/// aliases move to `into`, then the duplicates are deleted (admin)
+pub async fn merge_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<MergeRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let into = req.into.trim().to_string();
+    let mut from: Vec<String> = req.from.iter().map(|s| s.trim().to_string()).filter(|s| !s.is_empty() && *s != into).collect();
+    from.sort();
+    from.dedup();
+    if from.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "from must name at least one class other than into".to_string()));
+    }
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let into_id: i32 = sqlx::query_scalar("SELECT id FROM fod_classes WHERE name = $1 FOR UPDATE")
+        .bind(&into)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown class: {}", into)))?;
+    let from_ids: Vec<(i32, String)> = sqlx::query_as("SELECT id, name FROM fod_classes WHERE name = ANY($1) FOR UPDATE")
+        .bind(&from)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(internal)?;
+    if from_ids.len() != from.len() {
+        let found: Vec<&String> = from_ids.iter().map(|(_, n)| n).collect();
+        let missing: Vec<&String> = from.iter().filter(|n| !found.contains(n)).collect();
+        return Err((StatusCode::NOT_FOUND, format!("Unknown classes: {:?}", missing)));
+    }
+    let ids: Vec<i32> = from_ids.iter().map(|(id, _)| *id).collect();
+
+    // The rollup trigger moves counts of live events along with them
+    let events_moved = sqlx::query("UPDATE events SET class_id = $1 WHERE class_id = ANY($2)")
+        .bind(into_id)
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?
+        .rows_affected();
+    // What remains are counts of deleted events, folded in by hand
+    for table in ["event_rollups_hourly", "event_rollups_daily"] {
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO {t} AS r (bucket, class_id, zone, source_ref, events, objects, confidence_sum)
+            SELECT bucket, $1, zone, source_ref, SUM(events), SUM(objects), SUM(confidence_sum)
+            FROM {t} WHERE class_id = ANY($2)
+            GROUP BY bucket, zone, source_ref
+            ON CONFLICT (bucket, class_id, zone, source_ref) DO UPDATE
+                SET events = r.events + EXCLUDED.events,
+                    objects = r.objects + EXCLUDED.objects,
+                    confidence_sum = r.confidence_sum + EXCLUDED.confidence_sum
+            "#,
+            t = table
+        ))
+        .bind(into_id)
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?;
+        sqlx::query(&format!("DELETE FROM {} WHERE class_id = ANY($1)", table))
+            .bind(&ids)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+    }
+    // Labels that produced the duplicates now resolve to the survivor
+    sqlx::query("UPDATE class_aliases SET class_id = $1 WHERE class_id = ANY($2)")
+        .bind(into_id)
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?;
+    sqlx::query("DELETE FROM fod_classes WHERE id = ANY($1)")
+        .bind(&ids)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?;
+    tx.commit().await.map_err(internal)?;
+
+    info!(into = %into, merged = ?from, events_moved, by = %claims.username, "classes merged");
+    Ok(Json(MergeSummary { into, merged: from, events_moved }))
+}
+
+/// GET /classes/tree — the class taxonomy as nested `children`
+pub async fn tree_handler(State(st): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    Ok(Json(tree(&st.db).await?))
+}
+
+/// PUT /admin/classes/:name/parent — move a class under `parent` (created when new) or to the
+/// top with `{"parent": null}` (admin)
+pub async fn set_parent_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(req): Json<ParentRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let parent_id = match req.parent.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(parent) => Some(db::get_or_create_class(&st.db, parent).await?),
+        None => None,
+    };
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let class_id: i32 = sqlx::query_scalar("SELECT id FROM fod_classes WHERE name = $1 FOR UPDATE")
+        .bind(&name)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown class: {}", name)))?;
+    if let Some(parent_id) = parent_id {
+        let cycle: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM class_ancestors WHERE class_id = $1 AND ancestor_id = $2)",
+        )
+        .bind(parent_id)
+        .bind(class_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(internal)?;
+        if cycle {
+            return Err((StatusCode::CONFLICT, format!("{} cannot be placed under its own subclass", name)));
+        }
+    }
+    sqlx::query("UPDATE fod_classes SET parent_id = $2 WHERE id = $1")
+        .bind(class_id)
+        .bind(parent_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?;
+    tx.commit().await.map_err(internal)?;
+    info!(class = %name, parent = ?req.parent, by = %claims.username, "class parent set");
+
+    Ok(Json(tree(&st.db).await?))
+}
+
+/// GET /dashboard/class-breakdown?level=0&under=&from=&to= — counts rolled up to one taxonomy
+/// level (default last 30 days)
+pub async fn breakdown_handler(
+    State(st): State<AppState>,
+    Query(p): Query<BreakdownParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let to = p.to.unwrap_or_else(OffsetDateTime::now_utc);
+    let from = p.from.unwrap_or(to - Duration::days(30));
+    if from >= to {
+        return Err((StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+    let level = p.level.unwrap_or(0).clamp(0, 32);
+    let under = p.under.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let groups = breakdown(&st.db, from, to, level, under, p.include_quarantine).await?;
+    Ok(Json(json!({
+        "from": from.format(&Rfc3339).map_err(internal)?,
+        "to": to.format(&Rfc3339).map_err(internal)?,
+        "level": level,
+        "under": under,
+        "groups": groups,
+    })))
+}
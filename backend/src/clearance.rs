@@ -0,0 +1,186 @@
+//! Clearance photos for FOD Detection Backend
+//! Follow-up images of the cleared spot, linked to the event and driving its final state
+
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    ai, auth,
+    db::internal,
+    flags,
+    lifecycle::{self, Transition},
+    perf, AppState,
+};
+
+/// Largest accepted clearance photo; phone cameras easily exceed axum's 2 MB default
+pub const MAX_IMAGE_BYTES: usize = 15 * 1024 * 1024;
+
+// ==================== Types ====================
+
+#[derive(Deserialize)]
+pub struct ClearanceParams {
+    /// Run the photo through the AI and only clear the event when nothing is detected
+    #[serde(default)]
+    pub check: bool,
+    pub conf: Option<f32>,
+}
+
+/// Stored clearance without the image bytes
+#[derive(Serialize, FromRow)]
+pub struct Clearance {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub content_type: String,
+    pub filename: Option<String>,
+    pub uploaded_by: String,
+    pub ai_detections: Option<i32>,
+    pub cleared: bool,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize)]
+pub struct ClearanceResult {
+    pub clearance: Clearance,
+    pub transition: Transition,
+}
+
+struct Upload {
+    bytes: Bytes,
+    content_type: String,
+    filename: Option<String>,
+    note: Option<String>,
+}
+
+async fn read_upload(mp: &mut Multipart) -> Result<Upload, (StatusCode, String)> {
+    let mut file = None;
+    let mut note = None;
+    while let Some(field) = mp.next_field().await.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))? {
+        match field.name() {
+            Some("file") => {
+                let content_type = field.content_type().unwrap_or("image/jpeg").to_string();
+                if !content_type.starts_with("image/") {
+                    return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("Expected an image, got {}", content_type)));
+                }
+                let filename = field.file_name().map(|s| s.to_string());
+                let bytes = field.bytes().await.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+                file = Some((bytes, content_type, filename));
+            }
+            Some("note") => {
+                note = Some(field.text().await.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?);
+            }
+            _ => {}
+        }
+    }
+    let (bytes, content_type, filename) = file.ok_or((StatusCode::BAD_REQUEST, "No file field".to_string()))?;
+    if bytes.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Empty file".to_string()));
+    }
+    let note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    Ok(Upload { bytes, content_type, filename, note })
+}
+
+// ==================== Handlers ====================
+
+/// POST /events/:id/clearance — photo of the cleared spot (multipart `file`, optional `note`).
+/// The event must be `removed`; it moves to `verified_clear`, or back to `dispatched`
+/// when `check=true` and the AI still finds something.
+pub async fn upload_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Query(params): Query<ClearanceParams>,
+    mut mp: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    // Fail before the AI round trip; the transition re-checks under a row lock
+    match lifecycle::current_state(&st.db, id).await?.as_deref() {
+        None => return Err((StatusCode::NOT_FOUND, "Event not found".to_string())),
+        Some("removed") => {}
+        Some(state) => return Err((StatusCode::CONFLICT, format!("Event is {}, clearance needs removed", state))),
+    }
+    let upload = read_upload(&mut mp).await?;
+
+    let ai_detections = if params.check {
+        let filename = upload.filename.clone().unwrap_or_else(|| "clearance.jpg".to_string());
+        let fallback = st.flags.enabled(&st.db, flags::AI_FALLBACK).await;
+        let opts = ai::DetectOptions { conf: params.conf, imgsz: None, fallback };
+        let result = st.ai.detect(&st.http, upload.bytes.to_vec(), filename, opts).await?;
+        Some(result.get("detections").and_then(|v| v.as_array()).map(|d| d.len()).unwrap_or(0) as i32)
+    } else {
+        None
+    };
+    let cleared = ai_detections.unwrap_or(0) == 0;
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let clearance = sqlx::query_as::<_, Clearance>(
+        r#"
+        INSERT INTO event_clearances (event_id, image, content_type, filename, uploaded_by, ai_detections, cleared)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id, event_id, content_type, filename, uploaded_by, ai_detections, cleared, created_at
+        "#
+    )
+    .bind(id)
+    .bind(upload.bytes.as_ref())
+    .bind(&upload.content_type)
+    .bind(&upload.filename)
+    .bind(&claims.username)
+    .bind(ai_detections)
+    .bind(cleared)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal)?;
+    let (to, default_note) = if cleared {
+        ("verified_clear", format!("clearance {}", clearance.id))
+    } else {
+        ("dispatched", format!("clearance {}: AI still detects {} object(s)", clearance.id, ai_detections.unwrap_or(0)))
+    };
+    let note = upload.note.map(|n| format!("{} — {}", default_note, n)).unwrap_or(default_note);
+    let transition = lifecycle::transition_in(&mut tx, id, to, &claims.username, Some(&note)).await?;
+    tx.commit().await.map_err(internal)?;
+
+    Ok(Json(ClearanceResult { clearance, transition }))
+}
+
+/// GET /events/:id/clearances — clearance photos of an event, oldest first (without image data)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, Clearance>(
+        r#"
+        SELECT id, event_id, content_type, filename, uploaded_by, ai_detections, cleared, created_at
+        FROM event_clearances
+        WHERE event_id = $1
+        ORDER BY created_at
+        "#
+    )
+    .bind(id)
+    .fetch_all(&st.db);
+    let rows = perf::timed("list_clearances", || format!("event_id={}", id), q).await.map_err(internal)?;
+    Ok(Json(rows))
+}
+
+/// GET /clearances/:id/image — the stored photo
+pub async fn image_handler(
+    State(st): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, (Vec<u8>, String)>("SELECT image, content_type FROM event_clearances WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&st.db);
+    let (image, content_type) = perf::timed("clearance_image", || format!("id={}", id), q)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "Clearance not found".to_string()))?;
+    Ok(([(header::CONTENT_TYPE, content_type)], image))
+}
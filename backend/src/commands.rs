@@ -0,0 +1,226 @@
+//! Device commands for FOD Detection Backend
+//! Operator commands queued per device, picked up by the device with a long poll and acknowledged with a result
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::{FromRow, PgPool};
+use std::{collections::HashMap, sync::OnceLock, time::Duration};
+use time::OffsetDateTime;
+use tokio::{sync::broadcast, time::Instant};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{auth, db::internal, devices, live, perf, signing, tls, AppState};
+
+/// Commands devices understand
+pub const COMMANDS: [&str; 3] = ["capture_now", "restart_detection", "upload_logs"];
+/// Unacknowledged commands expire after this unless `ttl_secs` says otherwise
+const DEFAULT_TTL_SECS: i64 = 3600;
+const MAX_TTL_SECS: i64 = 86_400;
+/// A delivered command without an ack this long goes out again
+const REDELIVER_AFTER_SECS: f64 = 300.0;
+/// Longest poll; proxies tend to cut idle requests at 60s
+const MAX_WAIT: Duration = Duration::from_secs(55);
+/// Polls re-query at least this often, for commands queued through another instance
+const RECHECK: Duration = Duration::from_secs(1);
+
+/// Device ids with a command just queued on this instance, to wake their polls early
+fn queued() -> &'static broadcast::Sender<String> {
+    static TX: OnceLock<broadcast::Sender<String>> = OnceLock::new();
+    TX.get_or_init(|| broadcast::channel(256).0)
+}
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct NewCommand {
+    pub command: String,
+    #[serde(default)]
+    pub params: Map<String, Value>,
+    /// Seconds before an unacknowledged command expires (default 3600, at most a day)
+    pub ttl_secs: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct Ack {
+    /// false when the device tried and failed
+    pub ok: bool,
+    pub result: Option<Value>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Command {
+    pub id: Uuid,
+    pub device_id: String,
+    pub command: String,
+    pub params: Value,
+    /// queued, delivered, done, failed or expired
+    pub status: String,
+    pub attempts: i32,
+    pub result: Option<Value>,
+    pub created_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub expires_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub delivered_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub acked_at: Option<OffsetDateTime>,
+}
+
+/// Pending commands past their expiry read as `expired`
+const COMMAND_COLUMNS: &str = r#"
+    id, device_id, command, params,
+    CASE WHEN status IN ('queued', 'delivered') AND expires_at <= NOW() THEN 'expired' ELSE status END AS status,
+    attempts, result, created_by, created_at, expires_at, delivered_at, acked_at
+"#;
+
+// ==================== Queries ====================
+
+/// Hand out the device's due commands oldest first: queued ones, and delivered ones whose ack
+/// is overdue. SKIP LOCKED keeps two concurrent polls from taking the same command.
+async fn claim(db: &PgPool, device_id: &str) -> Result<Vec<Command>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        UPDATE device_commands SET status = 'delivered', delivered_at = NOW(), attempts = attempts + 1
+        WHERE id IN (
+            SELECT id FROM device_commands
+            WHERE device_id = $1 AND expires_at > NOW()
+              AND (status = 'queued' OR (status = 'delivered' AND delivered_at < NOW() - make_interval(secs => $2)))
+            ORDER BY created_at
+            LIMIT 20
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING {}
+        "#,
+        COMMAND_COLUMNS
+    );
+    let q = sqlx::query_as::<_, Command>(&sql).bind(device_id).bind(REDELIVER_AFTER_SECS).fetch_all(db);
+    let mut commands = perf::timed("claim_device_commands", || format!("device={}", device_id), q).await.map_err(internal)?;
+    commands.sort_by_key(|c| c.created_at);
+    Ok(commands)
+}
+
+// ==================== Handlers ====================
+
+/// POST /devices/:id/commands — queue a command for a device (login required)
+pub async fn create_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<NewCommand>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    if !COMMANDS.contains(&req.command.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("command must be one of {}", COMMANDS.join(", "))));
+    }
+    let ttl = req.ttl_secs.unwrap_or(DEFAULT_TTL_SECS);
+    if !(1..=MAX_TTL_SECS).contains(&ttl) {
+        return Err((StatusCode::BAD_REQUEST, format!("ttl_secs must be between 1 and {}", MAX_TTL_SECS)));
+    }
+    let sql = format!(
+        r#"
+        INSERT INTO device_commands (device_id, command, params, created_by, expires_at)
+        VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5))
+        RETURNING {}
+        "#,
+        COMMAND_COLUMNS
+    );
+    let q = sqlx::query_as::<_, Command>(&sql)
+        .bind(&id)
+        .bind(&req.command)
+        .bind(Value::Object(req.params))
+        .bind(&claims.username)
+        .bind(ttl as f64)
+        .fetch_one(&st.db);
+    let command = perf::timed("create_device_command", || format!("device={}", id), q).await.map_err(internal)?;
+    let _ = queued().send(id.clone());
+    info!(device = %id, command = %command.command, command_id = %command.id, by = %claims.username, "device command queued");
+    Ok((StatusCode::CREATED, Json(command)))
+}
+
+/// GET /devices/:id/commands — recent commands for a device and their outcome (login required)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let sql = format!("SELECT {} FROM device_commands WHERE device_id = $1 ORDER BY created_at DESC LIMIT 100", COMMAND_COLUMNS);
+    let q = sqlx::query_as::<_, Command>(&sql).bind(&id).fetch_all(&st.db);
+    Ok(Json(perf::timed("list_device_commands", || format!("device={}", id), q).await.map_err(internal)?))
+}
+
+/// GET /devices/:id/commands/next?wait=25s — for the device: due commands, waiting up to
+/// `wait` for one to be queued; each must be acknowledged or it is delivered again. This and
+/// the ack need the device's own signature or a client certificate registered for it
+pub async fn next_handler(
+    State(st): State<AppState>,
+    signed: Option<Extension<signing::VerifiedDevice>>,
+    cert: Option<Extension<tls::ClientCert>>,
+    Path(id): Path<String>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    devices::require_verified(&st.db, signed.as_deref(), cert.as_deref(), &id).await?;
+    let wait = q
+        .get("wait")
+        .map(|s| live::parse_wait(s))
+        .transpose()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?
+        .unwrap_or(Duration::from_secs(25))
+        .min(MAX_WAIT);
+
+    // Subscribe before the first query so a command queued in between still wakes us
+    let mut rx = queued().subscribe();
+    let deadline = Instant::now() + wait;
+    loop {
+        let commands = claim(&st.db, &id).await?;
+        let now = Instant::now();
+        if !commands.is_empty() || now >= deadline {
+            return Ok(Json(json!({ "commands": commands })));
+        }
+        let woken = async {
+            while let Ok(device) = rx.recv().await {
+                if device == id {
+                    break;
+                }
+            }
+        };
+        let _ = tokio::time::timeout((deadline - now).min(RECHECK), woken).await;
+    }
+}
+
+/// POST /devices/:id/commands/:command_id/ack — for the device: report a delivered command's outcome
+pub async fn ack_handler(
+    State(st): State<AppState>,
+    signed: Option<Extension<signing::VerifiedDevice>>,
+    cert: Option<Extension<tls::ClientCert>>,
+    Path((id, command_id)): Path<(String, Uuid)>,
+    Json(ack): Json<Ack>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    devices::require_verified(&st.db, signed.as_deref(), cert.as_deref(), &id).await?;
+    let sql = format!(
+        r#"
+        UPDATE device_commands SET status = $3, result = $4, acked_at = NOW()
+        WHERE id = $1 AND device_id = $2 AND status = 'delivered'
+        RETURNING {}
+        "#,
+        COMMAND_COLUMNS
+    );
+    let status = if ack.ok { "done" } else { "failed" };
+    let q = sqlx::query_as::<_, Command>(&sql).bind(command_id).bind(&id).bind(status).bind(&ack.result).fetch_optional(&st.db);
+    match perf::timed("ack_device_command", || format!("device={} command={}", id, command_id), q).await.map_err(internal)? {
+        Some(command) => {
+            info!(device = %id, command = %command.command, command_id = %command_id, status, "device command acknowledged");
+            Ok(Json(command))
+        }
+        None => Err((StatusCode::CONFLICT, "No delivered command with this id for this device".to_string())),
+    }
+}
@@ -0,0 +1,141 @@
+//! Event comments for FOD Detection Backend
+//! Operator notes on an event ("confirmed by ground crew, it was a fuel cap"), with replies
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{auth, db::internal, perf, AppState};
+
+/// Longest comment accepted, in characters
+const MAX_BODY_CHARS: usize = 4000;
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow, Clone)]
+pub struct Comment {
+    pub id: i64,
+    #[serde(skip)]
+    pub event_id: Uuid,
+    pub parent_id: Option<i64>,
+    pub author: String,
+    pub body: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// A comment with its replies, oldest first
+#[derive(Serialize)]
+pub struct Thread {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub replies: Vec<Thread>,
+}
+
+#[derive(Deserialize)]
+pub struct NewComment {
+    pub body: String,
+    /// Comment this one replies to
+    pub parent_id: Option<i64>,
+}
+
+// ==================== Queries ====================
+
+const COLUMNS: &str = "id, event_id, parent_id, author, body, created_at";
+
+/// Comments on an event, oldest first
+pub async fn for_event(db: &PgPool, event_id: Uuid) -> Result<Vec<Comment>, (StatusCode, String)> {
+    let sql = format!("SELECT {} FROM event_comments WHERE event_id = $1 ORDER BY id", COLUMNS);
+    let q = sqlx::query_as::<_, Comment>(&sql)
+        .bind(event_id)
+        .fetch_all(db);
+    perf::timed("event_comments", || format!("event_id={}", event_id), q).await.map_err(internal)
+}
+
+/// Nest comments (oldest first) under their parents
+pub fn thread(comments: Vec<Comment>) -> Vec<Thread> {
+    let mut children: HashMap<Option<i64>, Vec<Comment>> = HashMap::new();
+    for c in comments {
+        children.entry(c.parent_id).or_default().push(c);
+    }
+    fn build(parent: Option<i64>, children: &mut HashMap<Option<i64>, Vec<Comment>>) -> Vec<Thread> {
+        children
+            .remove(&parent)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|comment| {
+                let replies = build(Some(comment.id), children);
+                Thread { comment, replies }
+            })
+            .collect()
+    }
+    build(None, &mut children)
+}
+
+// ==================== Handlers ====================
+
+/// POST /events/:id/comments — `{body, parent_id?}` as the signed-in user
+pub async fn create_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(req): Json<NewComment>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let body = req.body.trim();
+    if body.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Comment body is empty".to_string()));
+    }
+    if body.chars().count() > MAX_BODY_CHARS {
+        return Err((StatusCode::BAD_REQUEST, format!("Comments are at most {} characters", MAX_BODY_CHARS)));
+    }
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM events WHERE id = $1)")
+        .bind(id)
+        .fetch_one(&st.db)
+        .await
+        .map_err(internal)?;
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, "Event not found".to_string()));
+    }
+    if let Some(parent) = req.parent_id {
+        let parent_event: Option<Uuid> = sqlx::query_scalar("SELECT event_id FROM event_comments WHERE id = $1")
+            .bind(parent)
+            .fetch_optional(&st.db)
+            .await
+            .map_err(internal)?;
+        if parent_event != Some(id) {
+            return Err((StatusCode::BAD_REQUEST, "parent_id is not a comment on this event".to_string()));
+        }
+    }
+
+    let comment = sqlx::query_as::<_, Comment>(&format!(
+        "INSERT INTO event_comments (event_id, parent_id, author, body) VALUES ($1, $2, $3, $4) RETURNING {}",
+        COLUMNS
+    ))
+    .bind(id)
+    .bind(req.parent_id)
+    .bind(&claims.username)
+    .bind(body)
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    info!(event_id = %id, comment_id = comment.id, by = %claims.username, "event comment added");
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+/// GET /events/:id/comments — comments as threads, oldest first
+pub async fn list_handler(
+    State(st): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    Ok(Json(thread(for_event(&st.db, id).await?)))
+}
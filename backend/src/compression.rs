@@ -0,0 +1,33 @@
+//! Response compression for FOD Detection Backend
+//! Gzip/Brotli for JSON, NDJSON and CSV bodies over slow airfield Wi-Fi
+
+use axum::http::{header, Extensions, HeaderMap, StatusCode, Version};
+use std::{env, sync::OnceLock};
+use tower_http::compression::{
+    predicate::{Predicate, SizeAbove},
+    CompressionLayer,
+};
+
+/// Text bodies worth compressing; images, attachments and websocket upgrades pass through
+const COMPRESSIBLE: &[&str] = &["application/json", "application/x-ndjson", "text/csv", "text/plain", "text/html"];
+
+/// Bodies smaller than this go out as-is (`COMPRESSION_MIN_BYTES`); streamed bodies have no
+/// length and are always compressed
+fn min_bytes() -> u16 {
+    static MIN: OnceLock<u16> = OnceLock::new();
+    *MIN.get_or_init(|| env::var("COMPRESSION_MIN_BYTES").ok().and_then(|s| s.parse().ok()).unwrap_or(1024))
+}
+
+fn compressible(status: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    let Some(content_type) = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()) else { return false };
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    status.is_success() && COMPRESSIBLE.iter().any(|t| essence.eq_ignore_ascii_case(t))
+}
+
+/// Gzip or Brotli as the client's `Accept-Encoding` prefers
+pub fn layer() -> CompressionLayer<impl Predicate> {
+    CompressionLayer::new()
+        .gzip(true)
+        .br(true)
+        .compress_when(SizeAbove::new(min_bytes()).and(compressible))
+}
@@ -0,0 +1,108 @@
+//! Conditional GETs for FOD Detection Backend
+//! ETag/Last-Modified from the newest event so idle dashboard polls are answered with 304
+
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use time::{macros::format_description, OffsetDateTime, PrimitiveDateTime, UtcOffset};
+
+use crate::{db::internal, perf};
+
+/// IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+const HTTP_DATE: &[time::format_description::FormatItem<'static>] =
+    format_description!("[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT");
+
+/// Newest event `ts`; new and merged events are what change the polled lists
+pub async fn latest_event(db: &PgPool) -> Result<OffsetDateTime, (StatusCode, String)> {
+    let q = sqlx::query_scalar("SELECT MAX(ts) FROM events").fetch_one(db);
+    let latest: Option<OffsetDateTime> = perf::timed("latest_event", String::new, q).await.map_err(internal)?;
+    Ok(latest.unwrap_or(OffsetDateTime::UNIX_EPOCH))
+}
+
+/// Newest event or aircraft movement, for counts over both
+pub async fn latest_activity(db: &PgPool) -> Result<OffsetDateTime, (StatusCode, String)> {
+    let q = sqlx::query_scalar("SELECT GREATEST((SELECT MAX(ts) FROM events), (SELECT MAX(ts) FROM aircraft_movements))")
+        .fetch_one(db);
+    let latest: Option<OffsetDateTime> = perf::timed("latest_activity", String::new, q).await.map_err(internal)?;
+    Ok(latest.unwrap_or(OffsetDateTime::UNIX_EPOCH))
+}
+
+/// Validators for one response: when its data last changed, and a tag that also covers
+/// the query string and the headers that shape the body
+pub struct Version {
+    modified: OffsetDateTime,
+    etag: String,
+}
+
+impl Version {
+    pub fn new(modified: OffsetDateTime, q: &HashMap<String, String>, headers: &HeaderMap) -> Self {
+        let mut params: Vec<_> = q.iter().collect();
+        params.sort_unstable();
+        let mut hash = Sha256::new();
+        hash.update(modified.unix_timestamp_nanos().to_be_bytes());
+        for (k, v) in params {
+            hash.update(format!("{}={}&", k, v));
+        }
+        for name in [header::ACCEPT, header::ACCEPT_LANGUAGE] {
+            hash.update(b"\n");
+            hash.update(headers.get(name).map(|v| v.as_bytes()).unwrap_or_default());
+        }
+        // Weak: the compression layer may re-encode the bytes
+        let etag = format!("W/\"{:.16x}\"", hash.finalize());
+        Version { modified, etag }
+    }
+
+    /// Validators for a document whose tag depends on its content alone
+    pub fn of_content(modified: OffsetDateTime, content: &[u8]) -> Self {
+        Version { modified, etag: format!("W/\"{:.16x}\"", Sha256::digest(content)) }
+    }
+
+    /// Whether the client's copy is current. `If-None-Match` wins over `If-Modified-Since`.
+    pub fn matches(&self, headers: &HeaderMap) -> bool {
+        current(headers, Some(&self.etag), Some(self.modified))
+    }
+
+    /// 304 with the validators and no body
+    pub fn not_modified(&self) -> Response {
+        self.tag(StatusCode::NOT_MODIFIED.into_response())
+    }
+
+    /// Attach `ETag`, `Last-Modified` and revalidation headers to a fresh response
+    pub fn tag(&self, mut resp: Response) -> Response {
+        let h = resp.headers_mut();
+        if let Ok(v) = HeaderValue::from_str(&self.etag) {
+            h.insert(header::ETAG, v);
+        }
+        if let Some(v) = self.modified.to_offset(UtcOffset::UTC).format(HTTP_DATE).ok().and_then(|s| HeaderValue::from_str(&s).ok()) {
+            h.insert(header::LAST_MODIFIED, v);
+        }
+        h.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        h.insert(header::VARY, HeaderValue::from_static("accept, accept-language"));
+        resp
+    }
+}
+
+fn http_date(value: &HeaderValue) -> Option<OffsetDateTime> {
+    value.to_str().ok().and_then(|s| PrimitiveDateTime::parse(s, HTTP_DATE).ok()).map(PrimitiveDateTime::assume_utc)
+}
+
+/// Whether the client's copy of a response sent with `etag` and `modified` is current
+fn current(headers: &HeaderMap, etag: Option<&str>, modified: Option<OffsetDateTime>) -> bool {
+    if let Some(tags) = headers.get(header::IF_NONE_MATCH).and_then(|v| v.to_str().ok()) {
+        let ours = etag.map(|e| e.trim_start_matches("W/"));
+        return tags.split(',').map(str::trim).any(|t| t == "*" || Some(t.trim_start_matches("W/")) == ours);
+    }
+    let since = headers.get(header::IF_MODIFIED_SINCE).and_then(http_date);
+    matches!((since, modified), (Some(since), Some(modified)) if modified.unix_timestamp() <= since.unix_timestamp())
+}
+
+/// As `Version::matches`, for a stored response: compares the request's validators with the
+/// `ETag` and `Last-Modified` in `stored`
+pub fn stored_matches(headers: &HeaderMap, stored: &HeaderMap) -> bool {
+    let etag = stored.get(header::ETAG).and_then(|v| v.to_str().ok());
+    current(headers, etag, stored.get(header::LAST_MODIFIED).and_then(http_date))
+}
@@ -0,0 +1,310 @@
+//! Confidence calibration for FOD Detection Backend
+//! Per-class Platt / isotonic mapping of model scores to probabilities, fitted on reviewed events
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{types::Json as SqlJson, FromRow, PgPool};
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{auth, db::internal, lifecycle, perf, AppState};
+
+/// Fewer reviewed events than this can't be fitted
+const MIN_SAMPLES: usize = 20;
+/// Newton iterations for Platt scaling
+const PLATT_ITERATIONS: usize = 100;
+
+// ==================== Calibrators ====================
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "method", rename_all = "lowercase")]
+pub enum Calibrator {
+    /// p = 1 / (1 + exp(a·x + b))
+    Platt { a: f64, b: f64 },
+    /// Piecewise linear through `(raw, calibrated)` points, flat beyond the ends
+    Isotonic { points: Vec<[f64; 2]> },
+}
+
+impl Calibrator {
+    pub fn apply(&self, x: f64) -> f64 {
+        let p = match self {
+            Calibrator::Platt { a, b } => 1.0 / (1.0 + (a * x + b).exp()),
+            Calibrator::Isotonic { points } => interpolate(points, x),
+        };
+        p.clamp(0.0, 1.0)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            Calibrator::Platt { a, b } if !a.is_finite() || !b.is_finite() => Err("a and b must be finite".to_string()),
+            Calibrator::Platt { .. } => Ok(()),
+            Calibrator::Isotonic { points } => {
+                if points.is_empty() {
+                    return Err("points is empty".to_string());
+                }
+                if points.iter().flatten().any(|v| !(0.0..=1.0).contains(v)) {
+                    return Err("points must lie in [0, 1]".to_string());
+                }
+                if points.windows(2).any(|w| w[1][0] <= w[0][0] || w[1][1] < w[0][1]) {
+                    return Err("points must increase in x and not decrease in y".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn interpolate(points: &[[f64; 2]], x: f64) -> f64 {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return x;
+    };
+    if x <= first[0] {
+        return first[1];
+    }
+    if x >= last[0] {
+        return last[1];
+    }
+    let i = points.partition_point(|p| p[0] <= x);
+    let ([x0, y0], [x1, y1]) = (points[i - 1], points[i]);
+    y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+}
+
+// ==================== Fitting ====================
+
+/// Platt scaling by Newton's method on Platt's smoothed targets
+fn fit_platt(samples: &[(f64, bool)]) -> Calibrator {
+    let pos = samples.iter().filter(|s| s.1).count() as f64;
+    let neg = samples.len() as f64 - pos;
+    let (hi, lo) = ((pos + 1.0) / (pos + 2.0), 1.0 / (neg + 2.0));
+    let (mut a, mut b) = (0.0, ((neg + 1.0) / (pos + 1.0)).ln());
+    for _ in 0..PLATT_ITERATIONS {
+        let (mut ga, mut gb, mut haa, mut hab, mut hbb) = (0.0, 0.0, 1e-12, 0.0, 1e-12);
+        for &(x, y) in samples {
+            let t = if y { hi } else { lo };
+            let p = 1.0 / (1.0 + (a * x + b).exp());
+            let w = p * (1.0 - p);
+            ga += (t - p) * x;
+            gb += t - p;
+            haa += w * x * x;
+            hab += w * x;
+            hbb += w;
+        }
+        let det = haa * hbb - hab * hab;
+        if det.abs() < 1e-18 {
+            break;
+        }
+        let (da, db) = ((hbb * ga - hab * gb) / det, (haa * gb - hab * ga) / det);
+        a -= da;
+        b -= db;
+        if da.abs() < 1e-10 && db.abs() < 1e-10 {
+            break;
+        }
+    }
+    Calibrator::Platt { a, b }
+}
+
+/// Isotonic regression by pool-adjacent-violators; each pooled block is flat between its
+/// lowest and highest score
+fn fit_isotonic(samples: &[(f64, bool)]) -> Calibrator {
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    // (lowest x, highest x, sum of y, count)
+    let mut blocks: Vec<(f64, f64, f64, f64)> = Vec::new();
+    for (x, y) in sorted {
+        blocks.push((x, x, if y { 1.0 } else { 0.0 }, 1.0));
+        while blocks.len() > 1 {
+            let (cur, prev) = (blocks[blocks.len() - 1], blocks[blocks.len() - 2]);
+            if prev.2 / prev.3 < cur.2 / cur.3 {
+                break;
+            }
+            blocks.pop();
+            *blocks.last_mut().unwrap() = (prev.0, cur.1, prev.2 + cur.2, prev.3 + cur.3);
+        }
+    }
+    let mut points: Vec<[f64; 2]> = Vec::with_capacity(blocks.len() * 2);
+    for (lo, hi, sy, n) in blocks {
+        for x in [lo, hi] {
+            // Blocks meeting at the same score keep the later (higher) value
+            match points.last_mut() {
+                Some(last) if last[0] >= x => last[1] = sy / n,
+                _ => points.push([x, sy / n]),
+            }
+        }
+    }
+    Calibrator::Isotonic { points }
+}
+
+/// Mean squared error of `f` against the review outcomes
+fn brier(samples: &[(f64, bool)], f: impl Fn(f64) -> f64) -> f64 {
+    samples.iter().map(|&(x, y)| (f(x) - if y { 1.0 } else { 0.0 }).powi(2)).sum::<f64>() / samples.len() as f64
+}
+
+// ==================== Storage ====================
+
+#[derive(Serialize, FromRow)]
+pub struct StoredCalibration {
+    pub class_name: String,
+    pub calibrator: SqlJson<Calibrator>,
+    pub samples: Option<i32>,
+    pub fitted_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub fitted_at: OffsetDateTime,
+}
+
+/// Calibrator for a class, None when its scores are used as they are
+pub async fn for_class(db: &PgPool, class_id: i32) -> Result<Option<Calibrator>, (StatusCode, String)> {
+    let q = sqlx::query_scalar::<_, SqlJson<Calibrator>>("SELECT calibrator FROM confidence_calibrations WHERE class_id = $1")
+        .bind(class_id)
+        .fetch_optional(db);
+    let found = perf::timed("confidence_calibration", || format!("class_id={}", class_id), q).await.map_err(internal)?;
+    Ok(found.map(|c| c.0))
+}
+
+/// Calibrated confidence and the raw one to keep alongside, if a calibrator applies
+pub async fn calibrate(db: &PgPool, class_id: i32, raw: f32) -> Result<(f32, Option<f32>), (StatusCode, String)> {
+    Ok(match for_class(db, class_id).await? {
+        Some(c) => (c.apply(raw as f64) as f32, Some(raw)),
+        None => (raw, None),
+    })
+}
+
+async fn class_id(db: &PgPool, name: &str) -> Result<i32, (StatusCode, String)> {
+    sqlx::query_scalar("SELECT id FROM fod_classes WHERE name = $1")
+        .bind(name)
+        .fetch_optional(db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown class: {}", name)))
+}
+
+async fn store(db: &PgPool, class_id: i32, c: &Calibrator, samples: Option<i32>, by: &str) -> Result<(), (StatusCode, String)> {
+    sqlx::query(
+        r#"
+        INSERT INTO confidence_calibrations (class_id, calibrator, samples, fitted_by) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (class_id) DO UPDATE SET calibrator = EXCLUDED.calibrator, samples = EXCLUDED.samples,
+            fitted_by = EXCLUDED.fitted_by, fitted_at = NOW()
+        "#,
+    )
+    .bind(class_id)
+    .bind(SqlJson(c))
+    .bind(samples)
+    .bind(by)
+    .execute(db)
+    .await
+    .map_err(internal)?;
+    Ok(())
+}
+
+// ==================== Handlers ====================
+
+#[derive(Deserialize)]
+pub struct FitRequest {
+    /// `platt` (default) or `isotonic`
+    pub method: Option<String>,
+    /// Reviewed events from this many days back (default 90)
+    pub days: Option<i32>,
+}
+
+/// GET /admin/confidence-calibrations — calibrated classes (admin)
+pub async fn list_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let rows = sqlx::query_as::<_, StoredCalibration>(
+        r#"
+        SELECT fc.name AS class_name, c.calibrator, c.samples, c.fitted_by, c.fitted_at
+        FROM confidence_calibrations c
+        JOIN fod_classes fc ON fc.id = c.class_id
+        ORDER BY fc.name
+        "#,
+    )
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(rows))
+}
+
+/// PUT /admin/confidence-calibrations/:class — store a calibrator fitted offline (admin)
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(class): Path<String>,
+    Json(c): Json<Calibrator>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    c.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let id = class_id(&st.db, &class).await?;
+    store(&st.db, id, &c, None, &claims.username).await?;
+    info!(class = %class, by = %claims.username, "confidence calibration set");
+    Ok(Json(c))
+}
+
+/// POST /admin/confidence-calibrations/:class/fit — fit on the class's reviewed events:
+/// confirmed ones are positives, `false_positive` negatives (admin)
+pub async fn fit_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(class): Path<String>,
+    Json(req): Json<FitRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let id = class_id(&st.db, &class).await?;
+    let rows: Vec<(f32, bool)> = sqlx::query_as(
+        r#"
+        SELECT COALESCE(raw_confidence, confidence), state <> $2
+        FROM events
+        WHERE class_id = $1 AND state <> 'detected' AND ts > NOW() - make_interval(days => $3)
+        "#,
+    )
+    .bind(id)
+    .bind(lifecycle::FALSE_POSITIVE)
+    .bind(req.days.unwrap_or(90).max(1))
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    let samples: Vec<(f64, bool)> = rows.into_iter().map(|(x, y)| (x as f64, y)).collect();
+    let positives = samples.iter().filter(|s| s.1).count();
+    if samples.len() < MIN_SAMPLES || positives == 0 || positives == samples.len() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Need at least {} reviewed events with both outcomes, have {} ({} confirmed)", MIN_SAMPLES, samples.len(), positives),
+        ));
+    }
+
+    let c = match req.method.as_deref().unwrap_or("platt") {
+        "platt" => fit_platt(&samples),
+        "isotonic" => fit_isotonic(&samples),
+        m => return Err((StatusCode::BAD_REQUEST, format!("Unknown method: {} (platt or isotonic)", m))),
+    };
+    store(&st.db, id, &c, Some(samples.len() as i32), &claims.username).await?;
+    let (raw, calibrated) = (brier(&samples, |x| x), brier(&samples, |x| c.apply(x)));
+    info!(class = %class, samples = samples.len(), brier_raw = raw, brier_calibrated = calibrated, by = %claims.username, "confidence calibration fitted");
+    Ok(Json(json!({
+        "class": class,
+        "calibrator": c,
+        "samples": samples.len(),
+        "positives": positives,
+        "brier_raw": raw,
+        "brier_calibrated": calibrated,
+    })))
+}
+
+/// DELETE /admin/confidence-calibrations/:class — use raw scores again (admin)
+pub async fn delete_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(class): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let id = class_id(&st.db, &class).await?;
+    let done = sqlx::query("DELETE FROM confidence_calibrations WHERE class_id = $1").bind(id).execute(&st.db).await.map_err(internal)?;
+    if done.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Calibration not found".to_string()));
+    }
+    info!(class = %class, by = %claims.username, "confidence calibration removed");
+    Ok(StatusCode::NO_CONTENT)
+}
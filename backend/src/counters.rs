@@ -0,0 +1,155 @@
+//! Real-time counters for FOD Detection Backend
+//! In-memory per-second ring buffer fed by the event notifications, served without touching the database
+
+use axum::{extract::State, response::IntoResponse, Json};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::{live, AppState};
+
+/// Seconds the ring buffer covers
+const WINDOW_SECS: i64 = 3600;
+
+// ==================== Aggregator ====================
+
+#[derive(Clone, Default)]
+struct Slot {
+    /// Unix second this slot holds; older contents are stale
+    second: i64,
+    events: u64,
+    objects: u64,
+    classes: Vec<(String, u64, u64)>,
+}
+
+struct Ring {
+    slots: Vec<Slot>,
+    started_at: OffsetDateTime,
+}
+
+/// Events and objects per second over the last hour, per class, as seen by this instance
+#[derive(Clone)]
+pub struct Live(Arc<Mutex<Ring>>);
+
+impl Default for Live {
+    fn default() -> Self {
+        Live(Arc::new(Mutex::new(Ring { slots: vec![Slot::default(); WINDOW_SECS as usize], started_at: OffsetDateTime::now_utc() })))
+    }
+}
+
+#[derive(Serialize)]
+pub struct ClassCount {
+    pub class: String,
+    pub events: u64,
+    pub objects: u64,
+}
+
+impl Live {
+    /// Count one inserted event at `now`
+    pub fn record(&self, class: &str, objects: u64, now: OffsetDateTime) {
+        let second = now.unix_timestamp();
+        let mut ring = self.0.lock().unwrap();
+        let slot = &mut ring.slots[second.rem_euclid(WINDOW_SECS) as usize];
+        if slot.second != second {
+            *slot = Slot { second, ..Slot::default() };
+        }
+        slot.events += 1;
+        slot.objects += objects;
+        match slot.classes.iter_mut().find(|(c, _, _)| c == class) {
+            Some((_, e, o)) => {
+                *e += 1;
+                *o += objects;
+            }
+            None => slot.classes.push((class.to_string(), 1, objects)),
+        }
+    }
+
+    /// Count a `fod_events` notification payload; rows stamped before the window (imports,
+    /// backfills) and rows from quarantined sources are left out
+    pub fn record_payload(&self, payload: &str, now: OffsetDateTime) {
+        let Ok(v) = serde_json::from_str::<Value>(payload) else {
+            return;
+        };
+        if v.get("quarantined").and_then(|q| q.as_bool()).unwrap_or(false) {
+            return;
+        }
+        let ts = v.get("ts").and_then(|t| t.as_str()).and_then(|t| OffsetDateTime::parse(t, &Rfc3339).ok());
+        if ts.is_some_and(|ts| (now - ts).whole_seconds() >= WINDOW_SECS) {
+            return;
+        }
+        let class = v.get("class_name").and_then(|c| c.as_str()).unwrap_or("unknown");
+        let objects = v.get("object_count").and_then(|n| n.as_u64()).unwrap_or(1);
+        self.record(class, objects, now);
+    }
+
+    /// Counters at `now`: last minute and hour, per-second and per-minute series (oldest
+    /// first, ending with the current second/minute) and classes over the last hour
+    pub fn snapshot(&self, now: OffsetDateTime) -> Value {
+        let now_s = now.unix_timestamp();
+        let ring = self.0.lock().unwrap();
+        let mut per_second = vec![0u64; 60];
+        let mut per_minute = vec![0u64; (WINDOW_SECS / 60) as usize];
+        let (mut minute, mut hour) = ((0u64, 0u64), (0u64, 0u64));
+        let mut classes: Vec<ClassCount> = Vec::new();
+        for slot in ring.slots.iter().filter(|s| s.events > 0) {
+            let age = now_s - slot.second;
+            if !(0..WINDOW_SECS).contains(&age) {
+                continue;
+            }
+            hour = (hour.0 + slot.events, hour.1 + slot.objects);
+            if age < 60 {
+                minute = (minute.0 + slot.events, minute.1 + slot.objects);
+                per_second[59 - age as usize] += slot.events;
+            }
+            // Calendar minutes, so the last entry is the minute in progress
+            let minutes_ago = now_s.div_euclid(60) - slot.second.div_euclid(60);
+            if let Some(i) = per_minute.len().checked_sub(1 + minutes_ago as usize) {
+                per_minute[i] += slot.events;
+            }
+            for (class, events, objects) in &slot.classes {
+                match classes.iter_mut().find(|c| &c.class == class) {
+                    Some(c) => {
+                        c.events += events;
+                        c.objects += objects;
+                    }
+                    None => classes.push(ClassCount { class: class.clone(), events: *events, objects: *objects }),
+                }
+            }
+        }
+        classes.sort_by(|a, b| b.events.cmp(&a.events).then_with(|| a.class.cmp(&b.class)));
+        json!({
+            "as_of": now.format(&Rfc3339).unwrap_or_default(),
+            // Counts only cover what this instance saw since it started
+            "started_at": ring.started_at.format(&Rfc3339).unwrap_or_default(),
+            "last_minute": { "events": minute.0, "objects": minute.1 },
+            "last_hour": { "events": hour.0, "objects": hour.1 },
+            "per_second": per_second,
+            "per_minute": per_minute,
+            "classes": classes,
+        })
+    }
+}
+
+/// Feed the counters from the event notifications relayed by `live::spawn_listener`
+pub fn spawn_aggregator(state: AppState) {
+    let mut rx = live::subscribe();
+    tokio::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(payload) => state.counters.record_payload(&payload, OffsetDateTime::now_utc()),
+                Err(RecvError::Lagged(n)) => warn!(skipped = n, "live counters fell behind, events uncounted"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+// ==================== Handlers ====================
+
+/// GET /dashboard/live — events per second/minute and per class over the last hour, from memory
+pub async fn live_handler(State(st): State<AppState>) -> impl IntoResponse {
+    Json(st.counters.snapshot(OffsetDateTime::now_utc()))
+}
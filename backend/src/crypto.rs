@@ -0,0 +1,182 @@
+//! Application-level encryption of sensitive event meta fields
+//! AES-256-GCM per field, key id stored alongside so old keys keep decrypting after rotation
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use serde_json::{json, Value};
+use std::{collections::HashMap, env, sync::OnceLock};
+use tracing::{info, warn};
+
+use crate::secrets;
+
+/// Marker and version of an encrypted field envelope
+const ENVELOPE: &str = "aes256gcm-v1";
+/// Shown to readers who may not see the plaintext
+pub const REDACTED: &str = "[encrypted]";
+/// Meta keys the backend itself queries on; never encrypted
+const QUERIED_KEYS: [&str; 1] = ["track_id"];
+
+// ==================== Config ====================
+
+struct Config {
+    /// Meta keys to encrypt (env `META_ENCRYPTED_FIELDS`, comma separated)
+    fields: Vec<String>,
+    /// Key id used for new ciphertexts, the first entry of `META_ENCRYPTION_KEYS`
+    current: Option<String>,
+    keys: HashMap<String, Aes256Gcm>,
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        let fields: Vec<String> = env::var("META_ENCRYPTED_FIELDS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|f| f.trim().to_string())
+            .filter(|f| !f.is_empty())
+            .filter(|f| {
+                let queried = QUERIED_KEYS.contains(&f.as_str());
+                if queried {
+                    warn!(field = %f, "meta field is used in queries, not encrypting it");
+                }
+                !queried
+            })
+            .collect();
+
+        // `kid:base64key,...` — 32-byte keys, first one encrypts, all decrypt
+        let mut current = None;
+        let mut keys = HashMap::new();
+        for entry in secrets::get("META_ENCRYPTION_KEYS").unwrap_or_default().split(',').filter(|e| !e.trim().is_empty()) {
+            let (kid, key) = entry.trim().split_once(':').expect("META_ENCRYPTION_KEYS entries must be kid:base64key");
+            let key = B64.decode(key).unwrap_or_else(|_| panic!("META_ENCRYPTION_KEYS: key {} is not base64", kid));
+            let cipher = Aes256Gcm::new_from_slice(&key)
+                .unwrap_or_else(|_| panic!("META_ENCRYPTION_KEYS: key {} must be 32 bytes", kid));
+            current.get_or_insert_with(|| kid.to_string());
+            keys.insert(kid.to_string(), cipher);
+        }
+        if !fields.is_empty() && current.is_none() {
+            panic!("META_ENCRYPTED_FIELDS requires META_ENCRYPTION_KEYS");
+        }
+        Config { fields, current, keys }
+    })
+}
+
+/// Validate the configuration at startup instead of on the first insert
+pub fn init() {
+    let c = config();
+    if let Some(kid) = &c.current {
+        info!(fields = ?c.fields, kid = %kid, "meta field encryption enabled");
+    }
+}
+
+pub fn current_kid() -> Option<&'static str> {
+    config().current.as_deref()
+}
+
+/// Meta keys configured as sensitive
+pub fn encrypted_fields() -> &'static [String] {
+    &config().fields
+}
+
+// ==================== Envelope ====================
+
+fn is_envelope(v: &Value) -> bool {
+    v.get("enc").and_then(|e| e.as_str()) == Some(ENVELOPE)
+}
+
+/// The field name is bound as associated data so ciphertexts can't be moved between keys
+fn seal(field: &str, plain: &Value) -> Value {
+    let c = config();
+    let kid = c.current.as_deref().expect("encryption key configured");
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ct = c.keys[kid]
+        .encrypt(&nonce, Payload { msg: plain.to_string().as_bytes(), aad: field.as_bytes() })
+        .expect("AES-GCM encryption");
+    json!({ "enc": ENVELOPE, "kid": kid, "nonce": B64.encode(nonce), "ct": B64.encode(ct) })
+}
+
+fn open(field: &str, envelope: &Value) -> Option<Value> {
+    let kid = envelope.get("kid")?.as_str()?;
+    let cipher = config().keys.get(kid)?;
+    let nonce: [u8; 12] = B64.decode(envelope.get("nonce")?.as_str()?).ok()?.try_into().ok()?;
+    let ct = B64.decode(envelope.get("ct")?.as_str()?).ok()?;
+    let plain = cipher.decrypt(&Nonce::from(nonce), Payload { msg: &ct, aad: field.as_bytes() }).ok()?;
+    serde_json::from_slice(&plain).ok()
+}
+
+/// Seal a secret kept outside event meta, bound to `name`; None without `META_ENCRYPTION_KEYS`
+pub fn seal_secret(name: &str, plain: &Value) -> Option<Value> {
+    current_kid()?;
+    Some(seal(name, plain))
+}
+
+/// Plaintext of a `seal_secret` envelope, None if its key is gone
+pub fn open_secret(name: &str, envelope: &Value) -> Option<Value> {
+    open(name, envelope)
+}
+
+// ==================== Meta ====================
+
+/// Encrypt the configured fields of an event's meta before insert
+pub fn encrypt_meta(meta: Option<Value>) -> Option<Value> {
+    let mut meta = meta?;
+    if let Some(obj) = meta.as_object_mut() {
+        for field in &config().fields {
+            if let Some(v) = obj.get_mut(field) {
+                if !v.is_null() && !is_envelope(v) {
+                    *v = seal(field, v);
+                }
+            }
+        }
+    }
+    Some(meta)
+}
+
+fn for_each_envelope(meta: &mut Value, mut f: impl FnMut(&str, &mut Value)) {
+    if let Some(obj) = meta.as_object_mut() {
+        for (field, v) in obj.iter_mut() {
+            if is_envelope(v) {
+                f(field, v);
+            }
+        }
+    }
+}
+
+/// Replace ciphertexts with plaintext for authorized readers; unknown keys stay redacted
+pub fn decrypt_meta(meta: &mut Value) {
+    for_each_envelope(meta, |field, v| {
+        *v = open(field, v).unwrap_or_else(|| {
+            warn!(field, "could not decrypt meta field");
+            Value::String(REDACTED.to_string())
+        });
+    });
+}
+
+/// Hide ciphertexts from readers without access
+pub fn redact_meta(meta: &mut Value) {
+    for_each_envelope(meta, |_, v| *v = Value::String(REDACTED.to_string()));
+}
+
+/// Re-seal fields encrypted under an older key with the current one, true if anything changed
+pub fn reencrypt_meta(meta: &mut Value) -> bool {
+    let Some(current) = current_kid() else {
+        return false;
+    };
+    let mut changed = false;
+    for_each_envelope(meta, |field, v| {
+        if v.get("kid").and_then(|k| k.as_str()) == Some(current) {
+            return;
+        }
+        match open(field, v) {
+            Some(plain) => {
+                *v = seal(field, &plain);
+                changed = true;
+            }
+            None => warn!(field, "cannot re-encrypt meta field, key missing"),
+        }
+    });
+    changed
+}
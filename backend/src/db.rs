@@ -2,13 +2,17 @@
 //! Contains all database models, queries, and helper functions
 
 use axum::http::StatusCode;
+use futures::{channel::mpsc, SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use sqlx::{FromRow, PgPool};
+use sqlx::{FromRow, PgExecutor, PgPool, Postgres, QueryBuilder};
+use std::time::Instant;
 use time::OffsetDateTime;
 use tracing::error;
 use uuid::Uuid;
 
+use crate::{calendar::Calendar, crypto, filter::Filter, perf};
+
 // ==================== Database Models ====================
 
 /// Event record from database
@@ -51,6 +55,51 @@ pub struct RecentEvent {
     pub longitude: f32,
     pub source: String,
     pub source_ref: String,
+    /// Number of frames folded into this row when collapsed by track
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frame_count: Option<i64>,
+    /// `meta.severity` when set in plain text, for list queries selecting `SEVERITY_COLUMN`
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<String>,
+}
+
+/// `severity` column for `RecentEvent` lists, promoted from meta on write; sealed values stay out
+pub const SEVERITY_COLUMN: &str = "e.severity";
+
+/// Single event with its JSON payloads
+#[derive(Serialize, FromRow)]
+pub struct EventDetail {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub event: RecentEvent,
+    pub bbox: Option<Value>,
+    pub meta: Option<Value>,
+    /// Lifecycle state, only loaded for single-event lookups
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    /// Model score before calibration, only loaded for single-event lookups
+    #[sqlx(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_confidence: Option<f32>,
+}
+
+/// Validated event ready for insert
+pub struct NewEvent {
+    pub ts: OffsetDateTime,
+    pub class_id: i32,
+    pub object_count: i32,
+    pub confidence: f32,
+    /// Model score before calibration, when a calibrator applied
+    pub raw_confidence: Option<f32>,
+    pub latitude: f32,
+    pub longitude: f32,
+    pub source: String,
+    pub source_ref: String,
+    pub bbox: Option<Value>,
+    pub meta: Option<Value>,
 }
 
 /// Dashboard summary response
@@ -59,6 +108,51 @@ pub struct DashboardSummary {
     pub total_24h: i64,
     pub avg_conf: Option<f64>,
     pub top_fod: Option<String>,
+    /// Start of the current operational day in the site (or requested) calendar
+    #[serde(with = "time::serde::rfc3339")]
+    pub day_start: OffsetDateTime,
+    /// Objects detected since `day_start`
+    pub total_today: i64,
+    /// Aircraft movements recorded in the same 24h window
+    pub movements_24h: i64,
+    /// FOD events per 1,000 movements, None without movement data
+    pub fod_per_1k_movements: Option<f64>,
+}
+
+/// Bucket whose count is well above its class baseline
+#[derive(Serialize, FromRow)]
+pub struct Anomaly {
+    pub class_name: String,
+    /// Set when grouped per source
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_ref: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub bucket: OffsetDateTime,
+    pub count: i64,
+    pub baseline_mean: f64,
+    pub baseline_stddev: f64,
+    pub z_score: f64,
+}
+
+/// Anomaly scan parameters, `bucket` is "hour" or "day"
+pub struct AnomalyParams {
+    pub bucket: &'static str,
+    /// Buckets before each scored bucket forming its baseline
+    pub baseline: i32,
+    /// Most recent buckets to score
+    pub recent: i32,
+    pub z: f64,
+    pub min_count: i64,
+    pub per_source: bool,
+    pub include_quarantine: bool,
+}
+
+/// One bucket of `/dashboard/timeseries`
+#[derive(Serialize, FromRow)]
+pub struct TimeseriesPoint {
+    #[serde(with = "time::serde::rfc3339")]
+    pub bucket: OffsetDateTime,
+    pub count: i64,
 }
 
 // ==================== Helper Functions ====================
@@ -73,31 +167,55 @@ pub fn internal<E: std::fmt::Display>(e: E) -> (StatusCode, String) {
 
 /// Check database health
 pub async fn check_health(db: &PgPool) -> Result<i32, (StatusCode, String)> {
-    sqlx::query_scalar("SELECT 1::INT")
-        .fetch_one(db)
-        .await
-        .map_err(internal)
+    let q = sqlx::query_scalar!(r#"SELECT 1::INT AS "ok!""#).fetch_one(db);
+    perf::timed("check_health", String::new, q).await.map_err(internal)
 }
 
 /// Get or create FOD class by name, returns class ID
-pub async fn get_or_create_class(db: &PgPool, name: &str) -> Result<i32, (StatusCode, String)> {
-    sqlx::query_scalar(
-        "INSERT INTO fod_classes (name, description) VALUES ($1, $2) ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name RETURNING id",
+pub async fn get_or_create_class(db: impl PgExecutor<'_>, name: &str) -> Result<i32, (StatusCode, String)> {
+    // An alias (see class_alias_key) wins; otherwise the label becomes a class and its own alias
+    let q = sqlx::query_scalar!(
+        r#"
+        WITH alias AS (
+            SELECT class_id AS id FROM class_aliases WHERE alias = $2
+        ), created AS (
+            INSERT INTO fod_classes (name, description)
+            SELECT $1, $3 WHERE NOT EXISTS (SELECT 1 FROM alias)
+            ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+            RETURNING id
+        ), registered AS (
+            INSERT INTO class_aliases (alias, class_id) SELECT $2, id FROM created
+            ON CONFLICT (alias) DO NOTHING
+        )
+        SELECT id AS "id!" FROM alias UNION ALL SELECT id FROM created
+        "#,
+        name,
+        class_alias_key(name),
+        format!("Auto-created class for: {}", name),
     )
-    .bind(name)
-    .bind(format!("Auto-created class for: {}", name))
-    .fetch_one(db)
-    .await
-    .map_err(internal)
+    .fetch_one(db);
+    perf::timed("get_or_create_class", || format!("name={:?}", name), q).await.map_err(internal)
+}
+
+/// Lookup key for class labels: lowercase, runs of whitespace, `_` and `-` as one space.
+/// Must match the normalization in migration 017.
+pub fn class_alias_key(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == '_' || c == '-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
 }
 
 /// Insert a new event, returns event ID
+#[allow(clippy::too_many_arguments)]
 pub async fn insert_event(
-    db: &PgPool,
+    db: impl PgExecutor<'_>,
     ts: OffsetDateTime,
     class_id: i32,
     object_count: i32,
     confidence: f32,
+    raw_confidence: Option<f32>,
     latitude: f32,
     longitude: f32,
     source: &str,
@@ -105,33 +223,45 @@ pub async fn insert_event(
     bbox: Option<Value>,
     meta: Option<Value>,
 ) -> Result<Uuid, (StatusCode, String)> {
-    sqlx::query_scalar(
+    // The meta keys list and aggregate queries use are promoted to events columns, the payloads
+    // go to event_details
+    let q = sqlx::query_scalar!(
         r#"
-        INSERT INTO events (ts, class_id, object_count, confidence, latitude, longitude, source, source_ref, bbox, meta)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-        RETURNING id
-        "#
+        WITH e AS (
+            INSERT INTO events (ts, class_id, object_count, confidence, raw_confidence, latitude, longitude, source, source_ref,
+                                severity, track_id, runway, zone)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9,
+                    event_meta_text($11, 'severity'), event_meta_text($11, 'track_id'), event_meta_text($11, 'runway'), event_rollup_zone($11))
+            RETURNING id
+        ), details AS (
+            INSERT INTO event_details (event_id, bbox, meta)
+            SELECT id, $10, $11 FROM e WHERE $10::JSONB IS NOT NULL OR $11::JSONB IS NOT NULL
+        )
+        SELECT id AS "id!" FROM e
+        "#,
+        ts,
+        class_id,
+        object_count,
+        confidence,
+        raw_confidence,
+        latitude,
+        longitude,
+        source,
+        source_ref,
+        bbox,
+        crypto::encrypt_meta(meta),
     )
-    .bind(ts)
-    .bind(class_id)
-    .bind(object_count)
-    .bind(confidence)
-    .bind(latitude)
-    .bind(longitude)
-    .bind(source)
-    .bind(source_ref)
-    .bind(bbox)
-    .bind(meta)
-    .fetch_one(db)
-    .await
-    .map_err(internal)
+    .fetch_one(db);
+    perf::timed("insert_event", || format!("ts={} class_id={} source={:?} source_ref={:?}", ts, class_id, source, source_ref), q).await.map_err(internal)
 }
 
 /// Insert event with current timestamp
+#[allow(clippy::too_many_arguments)]
 pub async fn insert_event_now(
-    db: &PgPool,
+    db: impl PgExecutor<'_>,
     class_id: i32,
     confidence: f32,
+    raw_confidence: Option<f32>,
     latitude: f32,
     longitude: f32,
     source: &str,
@@ -139,115 +269,746 @@ pub async fn insert_event_now(
     bbox: Option<Value>,
     meta: Value,
 ) -> Result<Uuid, (StatusCode, String)> {
-    sqlx::query_scalar(
+    let q = sqlx::query_scalar!(
         r#"
-        INSERT INTO events (ts, class_id, object_count, confidence, latitude, longitude, source, source_ref, bbox, meta)
-        VALUES (NOW(), $1, 1, $2, $3, $4, $5, $6, $7, $8)
-        RETURNING id
-        "#
+        WITH e AS (
+            INSERT INTO events (ts, class_id, object_count, confidence, raw_confidence, latitude, longitude, source, source_ref,
+                                severity, track_id, runway, zone)
+            VALUES (NOW(), $1, 1, $2, $3, $4, $5, $6, $7,
+                    event_meta_text($9, 'severity'), event_meta_text($9, 'track_id'), event_meta_text($9, 'runway'), event_rollup_zone($9))
+            RETURNING id
+        ), details AS (
+            INSERT INTO event_details (event_id, bbox, meta)
+            SELECT id, $8, $9 FROM e WHERE $8::JSONB IS NOT NULL OR $9::JSONB IS NOT NULL
+        )
+        SELECT id AS "id!" FROM e
+        "#,
+        class_id,
+        confidence,
+        raw_confidence,
+        latitude,
+        longitude,
+        source,
+        source_ref,
+        bbox,
+        crypto::encrypt_meta(Some(meta)),
     )
-    .bind(class_id)
-    .bind(confidence)
-    .bind(latitude)
-    .bind(longitude)
-    .bind(source)
-    .bind(source_ref)
-    .bind(bbox)
-    .bind(meta)
-    .fetch_one(db)
-    .await
-    .map_err(internal)
-}
-
-/// Check if event with track_id exists in last 10 seconds (for deduplication)
+    .fetch_one(db);
+    perf::timed("insert_event_now", || format!("class_id={} source={:?} source_ref={:?}", class_id, source, source_ref), q).await.map_err(internal)
+}
+
+/// Insert many events in one statement, returns number of rows inserted
+pub async fn insert_events_batch(db: &PgPool, events: &[NewEvent]) -> Result<u64, (StatusCode, String)> {
+    if events.is_empty() {
+        return Ok(0);
+    }
+    // Ids are assigned here so each row's payloads can follow it into event_details
+    let mut qb = QueryBuilder::<Postgres>::new(
+        "WITH input (id, ts, class_id, object_count, confidence, raw_confidence, latitude, longitude, source, source_ref, bbox, meta) AS (",
+    );
+    qb.push_values(events, |mut b, e| {
+        b.push_bind(Uuid::new_v4())
+            .push_bind(e.ts)
+            .push_bind(e.class_id)
+            .push_bind(e.object_count)
+            .push_bind(e.confidence)
+            .push_bind(e.raw_confidence)
+            .push_bind(e.latitude)
+            .push_bind(e.longitude)
+            .push_bind(&e.source)
+            .push_bind(&e.source_ref)
+            .push_bind(&e.bbox)
+            .push_bind(crypto::encrypt_meta(e.meta.clone()));
+    });
+    qb.push(
+        r#"
+        ), e AS (
+            INSERT INTO events (id, ts, class_id, object_count, confidence, raw_confidence, latitude, longitude, source, source_ref,
+                                severity, track_id, runway, zone)
+            SELECT id, ts, class_id, object_count, confidence, raw_confidence, latitude, longitude, source, source_ref,
+                   event_meta_text(meta, 'severity'), event_meta_text(meta, 'track_id'), event_meta_text(meta, 'runway'), event_rollup_zone(meta)
+            FROM input
+            RETURNING id
+        ), details AS (
+            INSERT INTO event_details (event_id, bbox, meta)
+            SELECT id, bbox, meta FROM input WHERE bbox IS NOT NULL OR meta IS NOT NULL
+        )
+        SELECT COUNT(*) FROM e
+        "#,
+    );
+    let q = qb.build_query_scalar::<i64>().fetch_one(db);
+    let inserted = perf::timed("insert_events_batch", || format!("rows={}", events.len()), q).await.map_err(internal)?;
+    Ok(inserted as u64)
+}
+
+/// Check if event with track_id exists in the last `window_secs` seconds (for deduplication)
 pub async fn check_duplicate_track(
-    db: &PgPool,
+    db: impl PgExecutor<'_>,
     source_ref: &str,
     track_id: &str,
+    window_secs: i64,
 ) -> Result<Option<Uuid>, (StatusCode, String)> {
-    sqlx::query_scalar(
-        r#"SELECT id FROM events WHERE ts > NOW() - INTERVAL '10 seconds' AND source_ref = $1 AND meta->>'track_id' = $2 LIMIT 1"#
+    let q = sqlx::query_scalar!(
+        r#"SELECT id FROM events WHERE ts > NOW() - make_interval(secs => $3) AND source_ref = $1 AND track_id = $2 LIMIT 1"#,
+        source_ref,
+        track_id,
+        window_secs as f64,
     )
-    .bind(source_ref)
-    .bind(track_id)
-    .fetch_optional(db)
-    .await
-    .map_err(internal)
+    .fetch_optional(db);
+    perf::timed("check_duplicate_track", || format!("source_ref={:?} track_id={:?}", source_ref, track_id), q).await.map_err(internal)
 }
 
-/// Get dashboard summary (24h stats)
-pub async fn get_summary(db: &PgPool) -> Result<DashboardSummary, (StatusCode, String)> {
-    let total_24h: i64 = sqlx::query_scalar(
-        r#"SELECT COALESCE(SUM(object_count), 0)::BIGINT FROM events WHERE ts >= NOW() - INTERVAL '24 hours'"#
+/// Fold a detection into the nearest same-class event from another source within
+/// `radius_m` metres and `window_secs` seconds of `ts`: its source joins the event's
+/// `meta.contributing_sources` and the higher confidence is kept. None when nothing overlaps.
+#[allow(clippy::too_many_arguments)]
+pub async fn merge_overlapping(
+    db: impl PgExecutor<'_>,
+    class_id: i32,
+    source_ref: &str,
+    ts: OffsetDateTime,
+    latitude: f32,
+    longitude: f32,
+    confidence: f32,
+    radius_m: f64,
+    window_secs: i64,
+) -> Result<Option<Uuid>, (StatusCode, String)> {
+    // Equirectangular distance, plenty at apron scale
+    let q = sqlx::query_scalar!(
+        r#"
+        WITH target AS (
+            SELECT id FROM (
+                SELECT id, 6371000 * SQRT(POWER(RADIANS(latitude - $4), 2)
+                           + POWER(COS(RADIANS($4)) * RADIANS(longitude - $5), 2)) AS dist
+                FROM events
+                WHERE class_id = $1 AND source_ref <> $2
+                  AND ts BETWEEN $3::TIMESTAMPTZ - make_interval(secs => $8) AND $3::TIMESTAMPTZ + make_interval(secs => $8)
+            ) c
+            WHERE dist <= $7
+            ORDER BY dist
+            LIMIT 1
+            FOR UPDATE
+        )
+        , merged AS (
+            UPDATE events e SET confidence = GREATEST(e.confidence, $6)
+            FROM target
+            WHERE e.id = target.id
+            RETURNING e.id, e.source_ref
+        ), details AS (
+            INSERT INTO event_details AS d (event_id, meta)
+            SELECT id, jsonb_build_object('contributing_sources', ARRAY(SELECT DISTINCT s FROM unnest(ARRAY[source_ref, $2]) s ORDER BY 1))
+            FROM merged
+            ON CONFLICT (event_id) DO UPDATE
+            SET meta = COALESCE(d.meta, '{}'::JSONB) || jsonb_build_object('contributing_sources', ARRAY(
+                SELECT jsonb_array_elements_text(COALESCE(d.meta->'contributing_sources', EXCLUDED.meta->'contributing_sources'))
+                UNION SELECT $2
+                ORDER BY 1
+            ))
+        )
+        SELECT id AS "id!" FROM merged
+        "#,
+        class_id,
+        source_ref,
+        ts,
+        latitude as f64,
+        longitude as f64,
+        confidence,
+        radius_m,
+        window_secs as f64,
     )
-    .fetch_one(db)
-    .await
-    .map_err(internal)?;
+    .fetch_optional(db);
+    perf::timed("merge_overlapping", || format!("class_id={} source_ref={:?}", class_id, source_ref), q).await.map_err(internal)
+}
+
+/// `AND` condition leaving out rows of quarantined sources (see `quarantine`), for a table or
+/// alias with a `source_ref` column (events and the rollups); empty when they are included
+pub fn quarantine_clause(alias: &str, include: bool) -> String {
+    if include {
+        return String::new();
+    }
+    format!(" AND NOT EXISTS (SELECT 1 FROM quarantine_sources qs WHERE qs.source_ref = {}.source_ref)", alias)
+}
 
-    let avg_conf: Option<f64> = sqlx::query_scalar(
-        r#"SELECT AVG(confidence) FROM events WHERE ts >= NOW() - INTERVAL '24 hours'"#
+/// Get dashboard summary (24h stats plus the current operational day); quarantined
+/// sources count only when `include_quarantine` is set
+pub async fn get_summary(db: &PgPool, cal: &Calendar, include_quarantine: bool) -> Result<DashboardSummary, (StatusCode, String)> {
+    let q = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(SUM(object_count), 0)::BIGINT AS "total!" FROM events e
+        WHERE ts >= NOW() - INTERVAL '24 hours'
+          AND ($1 OR NOT EXISTS (SELECT 1 FROM quarantine_sources qs WHERE qs.source_ref = e.source_ref))
+        "#,
+        include_quarantine,
     )
-    .fetch_one(db)
-    .await
-    .map_err(internal)?;
+    .fetch_one(db);
+    let total_24h: i64 = perf::timed("summary_total_24h", String::new, q).await.map_err(internal)?;
 
-    let top_fod: Option<String> = sqlx::query_scalar(
+    let q = sqlx::query_scalar!(
+        r#"
+        SELECT AVG(confidence) AS "avg" FROM events e
+        WHERE ts >= NOW() - INTERVAL '24 hours'
+          AND ($1 OR NOT EXISTS (SELECT 1 FROM quarantine_sources qs WHERE qs.source_ref = e.source_ref))
+        "#,
+        include_quarantine,
+    )
+    .fetch_one(db);
+    let avg_conf: Option<f64> = perf::timed("summary_avg_conf", String::new, q).await.map_err(internal)?;
+
+    let q = sqlx::query_scalar!(
         r#"
         SELECT fc.name FROM events e
         JOIN fod_classes fc ON e.class_id = fc.id
         WHERE e.ts >= NOW() - INTERVAL '24 hours'
+          AND ($1 OR NOT EXISTS (SELECT 1 FROM quarantine_sources qs WHERE qs.source_ref = e.source_ref))
         GROUP BY fc.name ORDER BY COUNT(*) DESC LIMIT 1
-        "#
+        "#,
+        include_quarantine,
     )
-    .fetch_optional(db)
-    .await
-    .map_err(internal)?;
+    .fetch_optional(db);
+    let top_fod: Option<String> = perf::timed("summary_top_fod", String::new, q).await.map_err(internal)?;
 
-    Ok(DashboardSummary { total_24h, avg_conf, top_fod })
+    let q = sqlx::query!(
+        r#"
+        SELECT (SELECT COUNT(*) FROM aircraft_movements WHERE ts >= NOW() - INTERVAL '24 hours') AS "movements!",
+               (SELECT COUNT(*) FROM events e WHERE ts >= NOW() - INTERVAL '24 hours'
+                  AND ($1 OR NOT EXISTS (SELECT 1 FROM quarantine_sources qs WHERE qs.source_ref = e.source_ref))) AS "events!"
+        "#,
+        include_quarantine,
+    )
+    .fetch_one(db);
+    let counts = perf::timed("summary_movements_24h", String::new, q).await.map_err(internal)?;
+    let (movements_24h, events_24h) = (counts.movements, counts.events);
+    let fod_per_1k_movements = (movements_24h > 0).then(|| events_24h as f64 * 1000.0 / movements_24h as f64);
+
+    let q = sqlx::query!(
+        r#"
+        WITH d AS (
+            SELECT (date_trunc('day', NOW() AT TIME ZONE $1 - make_interval(mins => $2)) + make_interval(mins => $2)) AT TIME ZONE $1 AS start
+        )
+        SELECT d.start AS "start!",
+               (SELECT COALESCE(SUM(object_count), 0)::BIGINT FROM events e WHERE ts >= d.start
+                  AND ($3 OR NOT EXISTS (SELECT 1 FROM quarantine_sources qs WHERE qs.source_ref = e.source_ref))) AS "total!"
+        FROM d
+        "#,
+        cal.tz,
+        cal.day_start,
+        include_quarantine,
+    )
+    .fetch_one(db);
+    let today = perf::timed("summary_total_today", || format!("tz={}", cal.tz), q).await.map_err(internal)?;
+    let (day_start, total_today) = (today.start, today.total);
+
+    Ok(DashboardSummary { total_24h, avg_conf, top_fod, day_start, total_today, movements_24h, fod_per_1k_movements })
+}
+
+/// Timeseries windows longer than this read rollups instead of scanning raw events
+const ROLLUP_MIN_WINDOW: time::Duration = time::Duration::days(7);
+
+/// Table bucketed counts are read from, with its timestamp and object-count columns and
+/// expressions (over alias `e`) for the event count and zone of a row
+struct CountsSource {
+    table: &'static str,
+    ts: &'static str,
+    objects: &'static str,
+    events: &'static str,
+    zone: &'static str,
 }
 
-/// Get recent events with optional limit
-pub async fn get_recent(db: &PgPool, limit: i64) -> Result<Vec<RecentEvent>, (StatusCode, String)> {
-    sqlx::query_as::<_, RecentEvent>(
+impl CountsSource {
+    const RAW: CountsSource =
+        CountsSource { table: "events", ts: "ts", objects: "object_count", events: "1", zone: "e.zone" };
+
+    /// Daily rollups serve plain UTC days, hourly rollups any hour-aligned calendar,
+    /// anything else (e.g. a +05:30 zone) falls back to raw events
+    fn for_calendar(bucket: &str, cal: &Calendar) -> CountsSource {
+        if bucket == "day" && cal.is_utc_midnight() {
+            CountsSource { table: "event_rollups_daily", ts: "bucket", objects: "objects", events: "e.events", zone: "e.zone" }
+        } else if cal.hour_aligned {
+            CountsSource { table: "event_rollups_hourly", ts: "bucket", objects: "objects", events: "e.events", zone: "e.zone" }
+        } else {
+            Self::RAW
+        }
+    }
+}
+
+/// Per-class (optionally per-source) counts scored against a rolling baseline of the
+/// preceding buckets; z = (count - mean) / max(stddev, 1) so flat baselines still score.
+/// Buckets follow the local calendar (`$7` zone, operational day starting `$8` minutes after midnight).
+pub async fn get_anomalies(db: &PgPool, p: &AnomalyParams, cal: &Calendar) -> Result<Vec<Anomaly>, (StatusCode, String)> {
+    let src = CountsSource::for_calendar(p.bucket, cal);
+    let sql = format!(
+        r#"
+        WITH cur AS (
+            SELECT date_trunc($1, NOW() AT TIME ZONE $7 - make_interval(mins => $8)) + make_interval(mins => $8) AS bucket
+        ),
+        buckets AS (
+            SELECT generate_series(
+                cur.bucket - ($2 + $3 - 1) * ('1 ' || $1)::INTERVAL,
+                cur.bucket,
+                ('1 ' || $1)::INTERVAL
+            ) AS bucket
+            FROM cur
+        ),
+        counts AS (
+            SELECT e.class_id, CASE WHEN $6 THEN e.source_ref END AS source_ref,
+                   date_trunc($1, e.{ts} AT TIME ZONE $7 - make_interval(mins => $8)) + make_interval(mins => $8) AS bucket,
+                   SUM(e.{objects})::BIGINT AS n
+            FROM {table} e
+            WHERE e.{ts} >= (SELECT MIN(bucket) FROM buckets) AT TIME ZONE $7{quarantine}
+            GROUP BY 1, 2, 3
+        ),
+        series AS (
+            SELECT DISTINCT class_id, source_ref FROM counts
+        ),
+        grid AS (
+            SELECT s.class_id, s.source_ref, b.bucket, COALESCE(c.n, 0) AS n
+            FROM series s
+            CROSS JOIN buckets b
+            LEFT JOIN counts c
+              ON c.class_id = s.class_id AND c.source_ref IS NOT DISTINCT FROM s.source_ref AND c.bucket = b.bucket
+        ),
+        scored AS (
+            SELECT class_id, source_ref, bucket, n,
+                   AVG(n) OVER w AS mean,
+                   COALESCE(STDDEV_SAMP(n) OVER w, 0) AS sd
+            FROM grid
+            WINDOW w AS (PARTITION BY class_id, source_ref ORDER BY bucket ROWS BETWEEN $2 PRECEDING AND 1 PRECEDING)
+        )
+        SELECT fc.name AS class_name, s.source_ref, s.bucket AT TIME ZONE $7 AS bucket, s.n AS count,
+               s.mean::FLOAT8 AS baseline_mean, s.sd::FLOAT8 AS baseline_stddev,
+               ((s.n - s.mean) / GREATEST(s.sd, 1))::FLOAT8 AS z_score
+        FROM scored s
+        JOIN fod_classes fc ON fc.id = s.class_id
+        WHERE s.bucket > (SELECT bucket FROM cur) - $3 * ('1 ' || $1)::INTERVAL
+          AND s.n >= $5
+          AND (s.n - s.mean) / GREATEST(s.sd, 1) >= $4
+        ORDER BY z_score DESC
+        "#,
+        table = src.table,
+        ts = src.ts,
+        objects = src.objects,
+        quarantine = quarantine_clause("e", p.include_quarantine),
+    );
+    let q = sqlx::query_as::<_, Anomaly>(&sql)
+        .bind(p.bucket)
+        .bind(p.baseline)
+        .bind(p.recent)
+        .bind(p.z)
+        .bind(p.min_count)
+        .bind(p.per_source)
+        .bind(&cal.tz)
+        .bind(cal.day_start)
+        .fetch_all(db);
+    perf::timed("get_anomalies", || format!("bucket={} baseline={} recent={} tz={} source={}", p.bucket, p.baseline, p.recent, cal.tz, src.table), q).await.map_err(internal)
+}
+
+/// Gap-free object counts per local hour/day bucket in [from, to), optionally for one class
+/// and everything below it in the taxonomy.
+/// Windows longer than ROLLUP_MIN_WINDOW read the rollup tables instead of raw events.
+pub async fn get_timeseries(
+    db: &PgPool,
+    bucket: &str,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    class: Option<&str>,
+    cal: &Calendar,
+    include_quarantine: bool,
+) -> Result<Vec<TimeseriesPoint>, (StatusCode, String)> {
+    let src = if to - from > ROLLUP_MIN_WINDOW { CountsSource::for_calendar(bucket, cal) } else { CountsSource::RAW };
+    let sql = format!(
+        r#"
+        WITH buckets AS (
+            SELECT generate_series(
+                date_trunc($1, $2 AT TIME ZONE $5 - make_interval(mins => $6)) + make_interval(mins => $6),
+                $3 AT TIME ZONE $5 - INTERVAL '1 microsecond',
+                ('1 ' || $1)::INTERVAL
+            ) AS bucket
+        ),
+        counts AS (
+            SELECT date_trunc($1, e.{ts} AT TIME ZONE $5 - make_interval(mins => $6)) + make_interval(mins => $6) AS bucket,
+                   SUM(e.{objects})::BIGINT AS count
+            FROM {table} e
+            WHERE e.{ts} >= $2 AND e.{ts} < $3
+              AND ($4::TEXT IS NULL OR e.class_id IN (SELECT ca.class_id FROM class_ancestors ca
+                                                      JOIN fod_classes p ON p.id = ca.ancestor_id
+                                                      WHERE p.name = $4)){quarantine}
+            GROUP BY 1
+        )
+        SELECT b.bucket AT TIME ZONE $5 AS bucket, COALESCE(c.count, 0) AS count
+        FROM buckets b
+        LEFT JOIN counts c ON c.bucket = b.bucket
+        ORDER BY b.bucket
+        "#,
+        table = src.table,
+        ts = src.ts,
+        objects = src.objects,
+        quarantine = quarantine_clause("e", include_quarantine),
+    );
+    let q = sqlx::query_as::<_, TimeseriesPoint>(&sql)
+        .bind(bucket)
+        .bind(from)
+        .bind(to)
+        .bind(class)
+        .bind(&cal.tz)
+        .bind(cal.day_start)
+        .fetch_all(db);
+    perf::timed("get_timeseries", || format!("bucket={} from={} to={} tz={} source={}", bucket, from, to, cal.tz, src.table), q).await.map_err(internal)
+}
+
+/// Events per local day of `year`, one entry per day from January 1st, optionally for one
+/// class (and everything below it) and one zone. Read from the rollups where the calendar allows.
+pub async fn get_calendar_counts(
+    db: &PgPool,
+    year: i32,
+    class: Option<&str>,
+    zone: Option<&str>,
+    cal: &Calendar,
+    include_quarantine: bool,
+) -> Result<Vec<i64>, (StatusCode, String)> {
+    let src = CountsSource::for_calendar("day", cal);
+    let sql = format!(
+        r#"
+        WITH days AS (
+            SELECT d::DATE AS day
+            FROM generate_series(make_date($1, 1, 1), make_date($1, 12, 31), INTERVAL '1 day') AS d
+        ),
+        counts AS (
+            SELECT (e.{ts} AT TIME ZONE $4 - make_interval(mins => $5))::DATE AS day,
+                   SUM({events})::BIGINT AS count
+            FROM {table} e
+            WHERE e.{ts} >= (make_date($1, 1, 1) + make_interval(mins => $5)) AT TIME ZONE $4
+              AND e.{ts} < (make_date($1 + 1, 1, 1) + make_interval(mins => $5)) AT TIME ZONE $4
+              AND ($2::TEXT IS NULL OR e.class_id IN (SELECT ca.class_id FROM class_ancestors ca
+                                                      JOIN fod_classes p ON p.id = ca.ancestor_id
+                                                      WHERE p.name = $2))
+              AND ($3::TEXT IS NULL OR {zone} = $3){quarantine}
+            GROUP BY 1
+        )
+        SELECT COALESCE(c.count, 0)
+        FROM days d
+        LEFT JOIN counts c ON c.day = d.day
+        ORDER BY d.day
+        "#,
+        table = src.table,
+        ts = src.ts,
+        events = src.events,
+        zone = src.zone,
+        quarantine = quarantine_clause("e", include_quarantine),
+    );
+    let q = sqlx::query_scalar::<_, i64>(&sql).bind(year).bind(class).bind(zone).bind(&cal.tz).bind(cal.day_start).fetch_all(db);
+    perf::timed("get_calendar_counts", || format!("year={} tz={} source={}", year, cal.tz, src.table), q).await.map_err(internal)
+}
+
+/// Row stream fed by a background task; dropping the receiver cancels the query
+pub type EventStream = mpsc::Receiver<Result<RecentEvent, sqlx::Error>>;
+
+/// Rows buffered between the DB cursor and the HTTP body
+const STREAM_BUFFER: usize = 256;
+
+/// Stream recent events newest first without buffering the result set; quarantined sources
+/// only when `include_quarantine` is set
+pub fn stream_recent(db: PgPool, limit: i64, include_quarantine: bool) -> EventStream {
+    stream_events(db, None, None, EventOrder::default(), limit, include_quarantine)
+}
+
+/// Get one event by ID with bbox and meta
+pub async fn get_event(db: &PgPool, id: Uuid) -> Result<Option<EventDetail>, (StatusCode, String)> {
+    let q = sqlx::query!(
         r#"
         SELECT e.id, e.ts, fc.name as class_name, e.object_count, e.confidence,
-               e.latitude, e.longitude, e.source, e.source_ref
+               e.latitude, e.longitude, e.source, e.source_ref, d.bbox, d.meta, e.state, e.raw_confidence
         FROM events e
         JOIN fod_classes fc ON e.class_id = fc.id
-        ORDER BY e.ts DESC
-        LIMIT $1
-        "#
+        LEFT JOIN event_details d ON d.event_id = e.id
+        WHERE e.id = $1
+        "#,
+        id,
     )
-    .bind(limit)
-    .fetch_all(db)
-    .await
-    .map_err(internal)
+    .fetch_optional(db);
+    let row = perf::timed("get_event", || format!("id={}", id), q).await.map_err(internal)?;
+    Ok(row.map(|r| EventDetail {
+        event: RecentEvent {
+            id: r.id,
+            ts: r.ts,
+            class_name: r.class_name,
+            object_count: r.object_count,
+            confidence: r.confidence,
+            latitude: r.latitude,
+            longitude: r.longitude,
+            source: r.source,
+            source_ref: r.source_ref,
+            frame_count: None,
+            severity: None,
+        },
+        bbox: r.bbox,
+        meta: r.meta,
+        state: Some(r.state),
+        raw_confidence: r.raw_confidence,
+    }))
+}
+
+/// Events holding meta fields sealed with a key other than `kid`, keyset-paginated by id
+pub async fn events_with_stale_meta(db: &PgPool, kid: &str, after: Option<Uuid>, limit: i64) -> Result<Vec<(Uuid, Value)>, (StatusCode, String)> {
+    let q = sqlx::query!(
+        r#"
+        SELECT event_id AS id, meta AS "meta!" FROM event_details
+        WHERE ($2::UUID IS NULL OR event_id > $2)
+          AND jsonb_path_exists(meta, '$.* ? (@.enc != null && @.kid != $kid)', jsonb_build_object('kid', $1::TEXT))
+        ORDER BY event_id
+        LIMIT $3
+        "#,
+        kid,
+        after,
+        limit,
+    )
+    .fetch_all(db);
+    let rows = perf::timed("events_with_stale_meta", || format!("kid={:?} after={:?}", kid, after), q).await.map_err(internal)?;
+    Ok(rows.into_iter().map(|r| (r.id, r.meta)).collect())
+}
+
+pub async fn update_event_meta(db: &PgPool, id: Uuid, meta: &Value) -> Result<(), (StatusCode, String)> {
+    let q = sqlx::query!("UPDATE event_details SET meta = $2 WHERE event_id = $1", id, meta).execute(db);
+    perf::timed("update_event_meta", || format!("id={}", id), q).await.map_err(internal)?;
+    Ok(())
+}
+
+/// Frames read per collapsed row: tracks are only gathered from the newest `limit * COLLAPSE_SCAN`
+/// events, so the feed stays an index scan on `ts` however large `events` grows
+const COLLAPSE_SCAN: i64 = 50;
+
+/// Get recent events collapsed by (source_ref, track_id), one row per track.
+/// The representative row is the latest frame; events without a track_id stay as-is.
+/// `frame_count` counts the track's frames within the scanned window.
+pub async fn get_recent_collapsed(db: &PgPool, limit: i64, include_quarantine: bool) -> Result<Vec<RecentEvent>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        SELECT * FROM (
+            SELECT DISTINCT ON (e.source_ref, COALESCE(e.track_id, e.id::text))
+                   e.id, e.ts, fc.name as class_name, e.object_count, e.confidence,
+                   e.latitude, e.longitude, e.source, e.source_ref, {},
+                   COUNT(*) OVER (PARTITION BY e.source_ref, COALESCE(e.track_id, e.id::text)) AS frame_count
+            FROM (SELECT * FROM events e WHERE TRUE{} ORDER BY ts DESC LIMIT $2) e
+            JOIN fod_classes fc ON e.class_id = fc.id
+            ORDER BY e.source_ref, COALESCE(e.track_id, e.id::text), e.ts DESC
+        ) t
+        ORDER BY t.ts DESC
+        LIMIT $1
+        "#,
+        SEVERITY_COLUMN,
+        quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, RecentEvent>(&sql).bind(limit).bind(limit * COLLAPSE_SCAN).fetch_all(db);
+    perf::timed("get_recent_collapsed", || format!("limit={} include_quarantine={}", limit, include_quarantine), q).await.map_err(internal)
+}
+
+/// Sort keys `/events/query` accepts for `order_by`
+pub const ORDER_KEYS: [&str; 4] = ["ts", "confidence", "severity", "distance"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SortKey {
+    Ts,
+    Confidence,
+    /// `meta.severity`, unknown or missing lowest
+    Severity,
+    /// From a reference point
+    Distance { lat: f64, lon: f64 },
 }
 
-/// Get events with optional filters
-pub async fn query_events(
+/// Validated ordering for event streams
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EventOrder {
+    key: SortKey,
+    descending: bool,
+}
+
+/// Newest first
+impl Default for EventOrder {
+    fn default() -> Self {
+        EventOrder { key: SortKey::Ts, descending: true }
+    }
+}
+
+impl EventOrder {
+    /// `order_by` from `ORDER_KEYS`, `dir` `asc`/`desc` (default: nearest first for distance,
+    /// highest first otherwise) and `near=lat,lon`, which distance requires
+    pub fn parse(order_by: Option<&str>, dir: Option<&str>, near: Option<&str>) -> Result<EventOrder, String> {
+        let key = match order_by.unwrap_or("ts") {
+            "ts" => SortKey::Ts,
+            "confidence" => SortKey::Confidence,
+            "severity" => SortKey::Severity,
+            "distance" => {
+                let near = near.ok_or("order_by=distance needs near=lat,lon")?;
+                let point = near.split_once(',').and_then(|(lat, lon)| Some((lat.trim().parse::<f64>().ok()?, lon.trim().parse::<f64>().ok()?)));
+                match point {
+                    Some((lat, lon)) if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) => SortKey::Distance { lat, lon },
+                    _ => return Err(format!("near must be lat,lon, got {:?}", near)),
+                }
+            }
+            other => return Err(format!("Unknown order_by: {} (expected one of {})", other, ORDER_KEYS.join(", "))),
+        };
+        let descending = match dir {
+            None => !matches!(key, SortKey::Distance { .. }),
+            Some("asc") => false,
+            Some("desc") => true,
+            Some(other) => return Err(format!("Unknown dir: {} (asc or desc)", other)),
+        };
+        Ok(EventOrder { key, descending })
+    }
+
+    /// `ORDER BY` clause over `events e`, newest first among ties
+    fn push_sql(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        qb.push(" ORDER BY ");
+        match self.key {
+            SortKey::Ts => {}
+            SortKey::Confidence => {
+                qb.push("e.confidence");
+            }
+            SortKey::Severity => {
+                qb.push(
+                    "CASE LOWER(e.severity) WHEN 'critical' THEN 4 WHEN 'high' THEN 3 \
+                     WHEN 'medium' THEN 2 WHEN 'low' THEN 1 ELSE 0 END",
+                );
+            }
+            SortKey::Distance { lat, lon } => {
+                // Equirectangular, fine for ranking within an airport
+                qb.push("POWER(e.latitude - ").push_bind(lat).push(", 2) + POWER(COS(RADIANS(").push_bind(lat);
+                qb.push(")) * (e.longitude - ").push_bind(lon).push("), 2)");
+            }
+        }
+        let dir = if self.descending { " DESC" } else { " ASC" };
+        if self.key == SortKey::Ts {
+            qb.push("e.ts").push(dir);
+        } else {
+            qb.push(dir).push(", e.ts DESC");
+        }
+        qb.push(", e.id");
+    }
+}
+
+/// ` WHERE` over `events e` for an optional class (including its subclasses) and filter expression
+pub fn push_event_where<'a>(qb: &mut QueryBuilder<'a, Postgres>, class_name: Option<&'a str>, filter: Option<&Filter>) {
+    qb.push(" WHERE (").push_bind(class_name).push(
+        r#"::TEXT IS NULL OR e.class_id IN (SELECT ca.class_id FROM class_ancestors ca
+                                            JOIN fod_classes p ON p.id = ca.ancestor_id
+                                            WHERE p.name = "#,
+    );
+    qb.push_bind(class_name).push("))");
+    if let Some(f) = filter {
+        qb.push(" AND ");
+        f.push_sql(qb);
+    }
+}
+
+/// Facets `count_events` can group by
+pub const COUNT_GROUPS: [&str; 3] = ["class", "source", "day"];
+
+#[derive(Serialize, FromRow)]
+pub struct ClassCount {
+    pub class: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct SourceCount {
+    pub source_ref: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct DayCount {
+    /// Operational day in the local calendar, YYYY-MM-DD
+    pub day: String,
+    pub count: i64,
+}
+
+/// Total and per-facet counts of the events `stream_events` would return
+#[derive(Serialize, Default)]
+pub struct EventCounts {
+    pub total: i64,
+    pub objects: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_class: Option<Vec<ClassCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_source: Option<Vec<SourceCount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by_day: Option<Vec<DayCount>>,
+}
+
+/// Count matching events, grouped by each of `groups` (from `COUNT_GROUPS`); classes and
+/// sources most frequent first, days in order. Days follow the local calendar.
+pub async fn count_events(
     db: &PgPool,
     class_name: Option<&str>,
+    filter: Option<&Filter>,
+    groups: &[&str],
+    cal: &Calendar,
+    include_quarantine: bool,
+) -> Result<EventCounts, (StatusCode, String)> {
+    let start = Instant::now();
+    let quarantine = quarantine_clause("e", include_quarantine);
+    let mut qb = QueryBuilder::<Postgres>::new("SELECT COUNT(*), COALESCE(SUM(e.object_count), 0)::BIGINT FROM events e");
+    push_event_where(&mut qb, class_name, filter);
+    qb.push(&quarantine);
+    let (total, objects): (i64, i64) = qb.build_query_as().fetch_one(db).await.map_err(internal)?;
+    let mut counts = EventCounts { total, objects, ..Default::default() };
+
+    if groups.contains(&"class") {
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT fc.name AS class, COUNT(*) AS count FROM events e JOIN fod_classes fc ON fc.id = e.class_id");
+        push_event_where(&mut qb, class_name, filter);
+        qb.push(&quarantine);
+        qb.push(" GROUP BY fc.name ORDER BY count DESC, class");
+        counts.by_class = Some(qb.build_query_as().fetch_all(db).await.map_err(internal)?);
+    }
+    if groups.contains(&"source") {
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT e.source_ref, COUNT(*) AS count FROM events e");
+        push_event_where(&mut qb, class_name, filter);
+        qb.push(&quarantine);
+        qb.push(" GROUP BY e.source_ref ORDER BY count DESC, e.source_ref");
+        counts.by_source = Some(qb.build_query_as().fetch_all(db).await.map_err(internal)?);
+    }
+    if groups.contains(&"day") {
+        let mut qb = QueryBuilder::<Postgres>::new("SELECT TO_CHAR((e.ts AT TIME ZONE ");
+        qb.push_bind(&cal.tz).push(" - make_interval(mins => ").push_bind(cal.day_start);
+        qb.push("))::DATE, 'YYYY-MM-DD') AS day, COUNT(*) AS count FROM events e");
+        push_event_where(&mut qb, class_name, filter);
+        qb.push(&quarantine);
+        qb.push(" GROUP BY day ORDER BY day");
+        counts.by_day = Some(qb.build_query_as().fetch_all(db).await.map_err(internal)?);
+    }
+    perf::record_query("count_events", start.elapsed(), || format!("class_name={:?} filter={:?} groups={:?} include_quarantine={}", class_name, filter, groups, include_quarantine));
+    Ok(counts)
+}
+
+/// Stream events with optional class (including its subclasses) and filter expression in `order`;
+/// quarantined sources only when `include_quarantine` is set
+pub fn stream_events(
+    db: PgPool,
+    class_name: Option<String>,
+    filter: Option<Filter>,
+    order: EventOrder,
     limit: i64,
-) -> Result<Vec<RecentEvent>, (StatusCode, String)> {
-    if let Some(name) = class_name {
-        sqlx::query_as::<_, RecentEvent>(
+    include_quarantine: bool,
+) -> EventStream {
+    let (mut tx, rx) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(async move {
+        let start = Instant::now();
+        let mut qb = QueryBuilder::<Postgres>::new(
             r#"
             SELECT e.id, e.ts, fc.name as class_name, e.object_count, e.confidence,
-                   e.latitude, e.longitude, e.source, e.source_ref
-            FROM events e
-            JOIN fod_classes fc ON e.class_id = fc.id
-            WHERE fc.name = $1
-            ORDER BY e.ts DESC
-            LIMIT $2
-            "#
-        )
-        .bind(name)
-        .bind(limit)
-        .fetch_all(db)
-        .await
-        .map_err(internal)
-    } else {
-        get_recent(db, limit).await
-    }
+                   e.latitude, e.longitude, e.source, e.source_ref, "#,
+        );
+        qb.push(SEVERITY_COLUMN).push(" FROM events e JOIN fod_classes fc ON e.class_id = fc.id");
+        push_event_where(&mut qb, class_name.as_deref(), filter.as_ref());
+        qb.push(quarantine_clause("e", include_quarantine));
+        order.push_sql(&mut qb);
+        qb.push(" LIMIT ").push_bind(limit);
+        let mut rows = qb.build_query_as::<RecentEvent>().fetch(&db);
+        while let Some(row) = rows.next().await {
+            if tx.send(row).await.is_err() {
+                break;
+            }
+        }
+        perf::record_query("stream_events", start.elapsed(), || format!("class_name={:?} filter={:?} order={:?} limit={} include_quarantine={}", class_name, filter, order, limit, include_quarantine));
+    });
+    rx
 }
@@ -0,0 +1,229 @@
+//! Demo data for FOD Detection Backend
+//! Plausible cameras, zones, events and aircraft movements for dashboards without hardware
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+
+use crate::{
+    auth,
+    db::{self, internal, NewEvent},
+    AppState,
+};
+
+/// `source` of every demo event, and prefix of demo camera ids / flight numbers
+pub const DEMO_SOURCE: &str = "demo";
+const DEMO_PREFIX: &str = "DEMO-";
+const BATCH_SIZE: usize = 500;
+
+/// Demo cameras: (source_ref, zone, runway, latitude, longitude) around a single airfield
+const CAMERAS: [(&str, &str, &str, f32, f32); 8] = [
+    ("DEMO-CAM-01", "RWY-01L", "01L", 13.6655, 100.7402),
+    ("DEMO-CAM-02", "RWY-01L", "01L", 13.6921, 100.7433),
+    ("DEMO-CAM-03", "RWY-19R", "19R", 13.7103, 100.7451),
+    ("DEMO-CAM-04", "RWY-01R", "01R", 13.6702, 100.7605),
+    ("DEMO-CAM-05", "RWY-01R", "01R", 13.6998, 100.7637),
+    ("DEMO-CAM-06", "TWY-B", "01R", 13.6864, 100.7529),
+    ("DEMO-CAM-07", "APRON-E", "01L", 13.6812, 100.7478),
+    ("DEMO-CAM-08", "APRON-W", "01R", 13.6950, 100.7702),
+];
+
+/// Seeded classes with relative frequency
+const CLASSES: [(&str, u32); 12] = [
+    ("Stone", 30),
+    ("Bolt", 14),
+    ("Nut", 10),
+    ("Screw", 8),
+    ("Plastic", 9),
+    ("Paper", 7),
+    ("Wire", 5),
+    ("Scrap Metal", 5),
+    ("Tire Pieces", 4),
+    ("Glass", 3),
+    ("Cloth", 3),
+    ("Other", 2),
+];
+
+// ==================== Request Types ====================
+
+#[derive(Deserialize)]
+pub struct SeedRequest {
+    /// Events to generate (default 3000, max 100,000)
+    pub events: Option<usize>,
+    /// Spread events over this many days back from now (default 30)
+    pub days: Option<i64>,
+    /// Remove earlier demo data first
+    #[serde(default)]
+    pub reset: bool,
+}
+
+#[derive(Serialize)]
+pub struct SeedSummary {
+    pub events: u64,
+    pub movements: u64,
+    pub removed_events: u64,
+}
+
+// ==================== Generation ====================
+
+/// xorshift64*; demo data only needs to look random
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Self {
+        Rng((OffsetDateTime::now_utc().unix_timestamp_nanos() as u64) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn weighted<'a, T>(&mut self, items: &'a [(T, u32)]) -> &'a T {
+        let total: u32 = items.iter().map(|(_, w)| w).sum();
+        let mut pick = self.below(total as u64) as u32;
+        for (item, w) in items {
+            if pick < *w {
+                return item;
+            }
+            pick -= w;
+        }
+        &items[items.len() - 1].0
+    }
+}
+
+/// Random instant in the last `days`, twice as likely during daytime operations
+fn timestamp(rng: &mut Rng, now: OffsetDateTime, days: i64) -> OffsetDateTime {
+    loop {
+        let ts = now - Duration::seconds((rng.unit() * (days * 86_400) as f64) as i64);
+        let daytime = (6..22).contains(&ts.hour());
+        if daytime || rng.unit() < 0.5 {
+            return ts;
+        }
+    }
+}
+
+// ==================== Seeding ====================
+
+async fn reset(db: &PgPool) -> Result<u64, (StatusCode, String)> {
+    let removed = sqlx::query("DELETE FROM events WHERE source = $1")
+        .bind(DEMO_SOURCE)
+        .execute(db)
+        .await
+        .map_err(internal)?
+        .rows_affected();
+    // Rollups keep counts of deleted events on purpose, so demo rows are cleared explicitly
+    for table in ["event_rollups_hourly", "event_rollups_daily"] {
+        sqlx::query(&format!("DELETE FROM {} WHERE source_ref LIKE $1", table))
+            .bind(format!("{}%", DEMO_PREFIX))
+            .execute(db)
+            .await
+            .map_err(internal)?;
+    }
+    sqlx::query("DELETE FROM aircraft_movements WHERE flight LIKE $1")
+        .bind(format!("{}%", DEMO_PREFIX))
+        .execute(db)
+        .await
+        .map_err(internal)?;
+    Ok(removed)
+}
+
+/// Generate demo events (and roughly 250 movements per runway per day over the same window)
+pub async fn seed(db: &PgPool, events: usize, days: i64, reset_first: bool) -> Result<SeedSummary, (StatusCode, String)> {
+    let removed_events = if reset_first { reset(db).await? } else { 0 };
+    let now = OffsetDateTime::now_utc();
+    let mut rng = Rng::new();
+
+    let mut class_ids = Vec::with_capacity(CLASSES.len());
+    for (name, weight) in CLASSES {
+        class_ids.push((db::get_or_create_class(db, name).await?, weight));
+    }
+
+    let mut inserted = 0;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    for i in 0..events {
+        let (source_ref, zone, runway, lat, lon) = CAMERAS[rng.below(CAMERAS.len() as u64) as usize];
+        // Scatter within ~150 m of the camera
+        let jitter = |rng: &mut Rng| ((rng.unit() - 0.5) * 0.0027) as f32;
+        let w = 0.01 + rng.unit() * 0.06;
+        let h = 0.01 + rng.unit() * 0.06;
+        batch.push(NewEvent {
+            ts: timestamp(&mut rng, now, days),
+            class_id: *rng.weighted(&class_ids),
+            object_count: if rng.unit() < 0.85 { 1 } else { 2 + rng.below(3) as i32 },
+            confidence: (0.45 + rng.unit() * 0.53) as f32,
+            raw_confidence: None,
+            latitude: lat + jitter(&mut rng),
+            longitude: lon + jitter(&mut rng),
+            source: DEMO_SOURCE.to_string(),
+            source_ref: source_ref.to_string(),
+            bbox: Some(json!([rng.unit() * (1.0 - w), rng.unit() * (1.0 - h), w, h])),
+            meta: Some(json!({
+                "zone": zone,
+                "runway": runway,
+                "track_id": format!("demo-{}", i),
+                "model": "demo",
+                "img_w": 1920,
+                "img_h": 1080,
+            })),
+        });
+        if batch.len() == BATCH_SIZE {
+            inserted += db::insert_events_batch(db, &batch).await?;
+            batch.clear();
+        }
+    }
+    inserted += db::insert_events_batch(db, &batch).await?;
+
+    let mut movements = 0;
+    let runways = ["01L", "01R", "19R"];
+    let total_movements = days * 250 * runways.len() as i64;
+    for chunk in (0..total_movements).collect::<Vec<_>>().chunks(BATCH_SIZE) {
+        let mut qb = sqlx::QueryBuilder::<sqlx::Postgres>::new("INSERT INTO aircraft_movements (ts, runway, kind, flight) ");
+        qb.push_values(chunk, |mut b, n| {
+            let kind = if n % 2 == 0 { "arrival" } else { "departure" };
+            b.push_bind(timestamp(&mut rng, now, days))
+                .push_bind(runways[(*n as usize) % runways.len()])
+                .push_bind(kind)
+                .push_bind(format!("{}{}", DEMO_PREFIX, n));
+        });
+        qb.push(" ON CONFLICT DO NOTHING");
+        movements += qb.build().execute(db).await.map_err(internal)?.rows_affected();
+    }
+
+    info!(events = inserted, movements, removed_events, days, "demo data seeded");
+    Ok(SeedSummary { events: inserted, movements, removed_events })
+}
+
+// ==================== Handlers ====================
+
+/// POST /admin/seed — populate demo data (admin)
+pub async fn seed_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    body: Option<Json<SeedRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let req = body.map(|Json(b)| b).unwrap_or(SeedRequest { events: None, days: None, reset: false });
+    let events = req.events.unwrap_or(3000).min(100_000);
+    let days = req.days.unwrap_or(30).clamp(1, 365);
+    Ok(Json(seed(&st.db, events, days, req.reset).await?))
+}
@@ -0,0 +1,195 @@
+//! Device logs for FOD Detection Backend
+//! Rotated log bundles uploaded by devices, stored in the object store and indexed per device
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Extension, Json,
+};
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{auth, db::internal, devices, logging, perf, s3, signing, tls, AppState};
+
+/// Largest accepted bundle; signed uploads are capped at the same size by `signing::verify`
+pub const MAX_BUNDLE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Accepted types and, where the format has one, the magic bytes the bundle must start with
+const ALLOWED_TYPES: &[(&str, Option<&[u8]>)] = &[
+    ("application/gzip", Some(&[0x1F, 0x8B])),
+    ("application/x-gzip", Some(&[0x1F, 0x8B])),
+    ("application/zstd", Some(&[0x28, 0xB5, 0x2F, 0xFD])),
+    ("application/zip", Some(b"PK\x03\x04")),
+    ("text/plain", None),
+];
+
+/// Object store for log bundles: `DEVICE_LOG_S3_*`, else the attachment store
+pub fn from_env() -> Option<s3::Bucket> {
+    s3::Bucket::from_env("DEVICE_LOG_S3").or_else(|| s3::Bucket::from_env("ATTACHMENT_S3"))
+}
+
+/// The log store carried in `AppState::device_logs`
+pub fn bucket(st: &AppState) -> Result<&s3::Bucket, (StatusCode, String)> {
+    st.device_logs.as_deref().ok_or((StatusCode::SERVICE_UNAVAILABLE, "Log storage is not configured".to_string()))
+}
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct UploadParams {
+    /// File name of the bundle on the device, e.g. `detector.log.3.gz`
+    pub name: Option<String>,
+    /// The `upload_logs` command this bundle answers
+    pub command_id: Option<Uuid>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct LogBundle {
+    pub id: Uuid,
+    pub device_id: String,
+    #[serde(skip)]
+    pub object_key: String,
+    pub content_type: String,
+    pub filename: Option<String>,
+    pub size_bytes: i64,
+    pub command_id: Option<Uuid>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+const COLUMNS: &str = "id, device_id, object_key, content_type, filename, size_bytes, command_id, created_at";
+
+/// Whether `bytes` may be stored as `content_type`
+fn check_type(content_type: &str, bytes: &[u8]) -> Result<(), (StatusCode, String)> {
+    match ALLOWED_TYPES.iter().find(|(t, _)| *t == content_type) {
+        None => Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("Log bundles of type {} are not accepted", content_type))),
+        Some((_, Some(magic))) if !bytes.starts_with(magic) => {
+            Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("Bundle content is not {}", content_type)))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+// ==================== Handlers ====================
+
+/// POST /devices/:id/logs?name=&command_id= — for the device itself (signed or by its client
+/// certificate): one log bundle as the raw body, typed by Content-Type (gzip, zstd, zip or plain text)
+pub async fn upload_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    signed: Option<Extension<signing::VerifiedDevice>>,
+    cert: Option<Extension<tls::ClientCert>>,
+    Path(id): Path<String>,
+    Query(params): Query<UploadParams>,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    devices::require_verified(&st.db, signed.as_deref(), cert.as_deref(), &id).await?;
+    let bucket = bucket(&st)?;
+    if body.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Empty log bundle".to_string()));
+    }
+    if body.len() > MAX_BUNDLE_BYTES {
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, format!("Log bundles are at most {} bytes", MAX_BUNDLE_BYTES)));
+    }
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or(v).trim().to_lowercase())
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    check_type(&content_type, &body)?;
+    let filename = params.name.filter(|n| !n.is_empty());
+    if filename.as_ref().is_some_and(|n| n.len() > 255) {
+        return Err((StatusCode::BAD_REQUEST, "name is at most 255 characters".to_string()));
+    }
+    if let Some(command_id) = params.command_id {
+        let known: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM device_commands WHERE id = $1 AND device_id = $2)")
+            .bind(command_id)
+            .bind(&id)
+            .fetch_one(&st.db)
+            .await
+            .map_err(internal)?;
+        if !known {
+            return Err((StatusCode::BAD_REQUEST, "No command with this id for this device".to_string()));
+        }
+    }
+
+    let log_id = Uuid::new_v4();
+    let key = format!("device-logs/site={}/{}/{}", logging::site_id(), id, log_id);
+    let size = body.len() as i64;
+    bucket.put_object(&st.http, &key, &content_type, body.to_vec()).await.map_err(|e| {
+        error!(device = %id, key = %key, error = %e, "log bundle upload failed");
+        (StatusCode::BAD_GATEWAY, "Could not store the log bundle".to_string())
+    })?;
+
+    let sql = format!(
+        r#"
+        INSERT INTO device_logs (id, device_id, object_key, content_type, filename, size_bytes, command_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING {}
+        "#,
+        COLUMNS
+    );
+    let bundle = sqlx::query_as::<_, LogBundle>(&sql)
+        .bind(log_id)
+        .bind(&id)
+        .bind(&key)
+        .bind(&content_type)
+        .bind(&filename)
+        .bind(size)
+        .bind(params.command_id)
+        .fetch_one(&st.db)
+        .await
+        .map_err(internal)?;
+    info!(device = %id, log_id = %bundle.id, content_type = %content_type, bytes = size, "device log bundle stored");
+    Ok((StatusCode::CREATED, Json(bundle)))
+}
+
+/// GET /devices/:id/logs — a device's log bundles, newest first (login required)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let sql = format!("SELECT {} FROM device_logs WHERE device_id = $1 ORDER BY created_at DESC LIMIT 200", COLUMNS);
+    let q = sqlx::query_as::<_, LogBundle>(&sql).bind(&id).fetch_all(&st.db);
+    Ok(Json(perf::timed("list_device_logs", || format!("device={}", id), q).await.map_err(internal)?))
+}
+
+/// GET /devices/:id/logs/:log_id — the stored bundle (login required)
+pub async fn download_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path((id, log_id)): Path<(String, Uuid)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let sql = format!("SELECT {} FROM device_logs WHERE id = $1 AND device_id = $2", COLUMNS);
+    let q = sqlx::query_as::<_, LogBundle>(&sql).bind(log_id).bind(&id).fetch_optional(&st.db);
+    let bundle = perf::timed("get_device_log", || format!("device={} id={}", id, log_id), q)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "Log bundle not found".to_string()))?;
+    let body = bucket(&st)?.get_object(&st.http, &bundle.object_key).await.map_err(|e| {
+        error!(device = %id, log_id = %log_id, error = %e, "log bundle download failed");
+        (StatusCode::BAD_GATEWAY, "Could not fetch the log bundle".to_string())
+    })?;
+    // Quotes and control characters can't break out of the header value
+    let filename: String = bundle
+        .filename
+        .unwrap_or_else(|| format!("{}-{}", id, log_id))
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"' && *c != '\\')
+        .collect();
+    Ok((
+        [
+            (header::CONTENT_TYPE, bundle.content_type),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    ))
+}
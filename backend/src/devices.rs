@@ -0,0 +1,443 @@
+//! Device registry for FOD Detection Backend
+//! Firmware, model and capabilities reported by edge device heartbeats, and centrally managed device configuration
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::{types::Json as SqlJson, FromRow, PgPool};
+use std::{cmp::Ordering, env, sync::OnceLock};
+use time::{Duration, OffsetDateTime};
+use tracing::info;
+
+use crate::{auth, conditional, db::internal, perf, signing, tls, AppState};
+
+const MAX_ID_LEN: usize = 100;
+const MAX_CAPABILITIES: usize = 50;
+/// `device_configs` row holding the fleet defaults
+const FLEET: &str = "*";
+const SAMPLING_MODES: [&str; 3] = ["all", "interval", "motion"];
+
+// ==================== Config ====================
+
+struct Config {
+    /// Minimum firmware per model, `*` for every other model (`DEVICE_MIN_FIRMWARE`,
+    /// `1.4.0` or `M30=2.1.0,*=1.4.0`)
+    min_firmware: Vec<(String, String)>,
+    /// Devices silent for longer are offline (`DEVICE_OFFLINE_SECS`, default 300)
+    offline_after: Duration,
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| Config {
+        min_firmware: env::var("DEVICE_MIN_FIRMWARE")
+            .unwrap_or_default()
+            .split(',')
+            .map(|entry| match entry.split_once('=') {
+                Some((model, version)) => (model.trim().to_string(), version.trim().to_string()),
+                None => ("*".to_string(), entry.trim().to_string()),
+            })
+            .filter(|(model, version)| !model.is_empty() && parse_version(version).is_some())
+            .collect(),
+        offline_after: Duration::seconds(
+            env::var("DEVICE_OFFLINE_SECS").ok().and_then(|s| s.parse().ok()).filter(|&s| s > 0).unwrap_or(300),
+        ),
+    })
+}
+
+/// Minimum firmware for `model`: its own entry, else the `*` one
+fn min_firmware(model: Option<&str>) -> Option<&'static str> {
+    let entries = &config().min_firmware;
+    model
+        .and_then(|m| entries.iter().find(|(k, _)| k.eq_ignore_ascii_case(m)))
+        .or_else(|| entries.iter().find(|(k, _)| k == "*"))
+        .map(|(_, v)| v.as_str())
+}
+
+// ==================== Versions ====================
+
+/// `v1.4.2-rc1` → [1, 4, 2]; pre-release and build suffixes are ignored
+fn parse_version(s: &str) -> Option<Vec<u64>> {
+    let core = s.trim().trim_start_matches(['v', 'V']).split(['-', '+']).next()?;
+    core.split('.').map(|p| p.parse().ok()).collect::<Option<Vec<u64>>>().filter(|v| !v.is_empty())
+}
+
+/// Numeric comparison with missing components as 0, so 1.4 == 1.4.0 < 1.10
+fn compare_versions(a: &[u64], b: &[u64]) -> Ordering {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0).cmp(b.get(i).unwrap_or(&0)))
+        .find(|o| o.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Below the minimum for its model; a missing or unreadable version counts as outdated
+fn outdated(model: Option<&str>, firmware: Option<&str>) -> bool {
+    let Some(min) = min_firmware(model).and_then(parse_version) else {
+        return false;
+    };
+    firmware.and_then(parse_version).is_none_or(|v| compare_versions(&v, &min).is_lt())
+}
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct Heartbeat {
+    pub firmware_version: Option<String>,
+    pub model: Option<String>,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+#[derive(FromRow)]
+struct DeviceRow {
+    id: String,
+    model: Option<String>,
+    firmware_version: Option<String>,
+    capabilities: SqlJson<Vec<String>>,
+    first_seen: OffsetDateTime,
+    last_seen: OffsetDateTime,
+}
+
+#[derive(Serialize)]
+pub struct Device {
+    pub id: String,
+    pub model: Option<String>,
+    pub firmware_version: Option<String>,
+    pub capabilities: Vec<String>,
+    /// Minimum firmware for this model, when one is configured
+    pub min_firmware: Option<&'static str>,
+    pub outdated: bool,
+    /// Heard from within `DEVICE_OFFLINE_SECS`
+    pub online: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub first_seen: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_seen: OffsetDateTime,
+}
+
+impl From<DeviceRow> for Device {
+    fn from(r: DeviceRow) -> Self {
+        Device {
+            min_firmware: min_firmware(r.model.as_deref()),
+            outdated: outdated(r.model.as_deref(), r.firmware_version.as_deref()),
+            online: OffsetDateTime::now_utc() - r.last_seen <= config().offline_after,
+            id: r.id,
+            model: r.model,
+            firmware_version: r.firmware_version,
+            capabilities: r.capabilities.0,
+            first_seen: r.first_seen,
+            last_seen: r.last_seen,
+        }
+    }
+}
+
+const DEVICE_COLUMNS: &str = "id, model, firmware_version, capabilities, first_seen, last_seen";
+
+// ==================== Queries ====================
+
+/// Device-facing routes for `id`: the caller must be identified, by a signature
+/// `signing::verify` accepted from `id` itself or by a verified client certificate registered
+/// for `id` (see `put_certificate_handler`)
+pub async fn require_verified(
+    db: &PgPool,
+    signed: Option<&signing::VerifiedDevice>,
+    cert: Option<&tls::ClientCert>,
+    id: &str,
+) -> Result<(), (StatusCode, String)> {
+    match (signed, cert) {
+        (Some(signing::VerifiedDevice(device)), _) if device == id => Ok(()),
+        (Some(_), _) => Err((StatusCode::FORBIDDEN, "Signed by another device".to_string())),
+        (None, Some(cert)) => match certificate_owner(db, &cert.fingerprint).await? {
+            Some(owner) if owner == id => Ok(()),
+            _ => Err((StatusCode::FORBIDDEN, "Client certificate is not registered for this device".to_string())),
+        },
+        (None, None) => Err((StatusCode::UNAUTHORIZED, "Device signature or client certificate required".to_string())),
+    }
+}
+
+/// Device a client certificate is registered for
+async fn certificate_owner(db: &PgPool, fingerprint: &str) -> Result<Option<String>, (StatusCode, String)> {
+    let q = sqlx::query_scalar::<_, String>("SELECT device_id FROM device_certificates WHERE fingerprint = $1")
+        .bind(fingerprint)
+        .fetch_optional(db);
+    perf::timed("device_certificate_owner", String::new, q).await.map_err(internal)
+}
+
+pub async fn get(db: &PgPool, id: &str) -> Result<Option<Device>, (StatusCode, String)> {
+    let sql = format!("SELECT {} FROM devices WHERE id = $1", DEVICE_COLUMNS);
+    let q = sqlx::query_as::<_, DeviceRow>(&sql).bind(id).fetch_optional(db);
+    Ok(perf::timed("get_device", || format!("id={}", id), q).await.map_err(internal)?.map(Device::from))
+}
+
+async fn all(db: &PgPool) -> Result<Vec<Device>, (StatusCode, String)> {
+    let sql = format!("SELECT {} FROM devices ORDER BY id", DEVICE_COLUMNS);
+    let q = sqlx::query_as::<_, DeviceRow>(&sql).fetch_all(db);
+    Ok(perf::timed("list_devices", String::new, q).await.map_err(internal)?.into_iter().map(Device::from).collect())
+}
+
+// ==================== Configuration ====================
+
+/// Built-in configuration, under the fleet defaults and each device's overrides. A null
+/// `upload_endpoint` keeps the endpoint the device was provisioned with.
+fn default_config() -> Map<String, Value> {
+    Map::from_iter([
+        ("capture_fps".to_string(), json!(2.0)),
+        ("conf_threshold".to_string(), json!(0.25)),
+        ("upload_endpoint".to_string(), Value::Null),
+        ("sampling_mode".to_string(), json!("all")),
+    ])
+}
+
+/// Check a partial document against the known keys; null removes an override
+fn validate_overrides(doc: &Map<String, Value>) -> Result<(), String> {
+    for (key, v) in doc.iter().filter(|(_, v)| !v.is_null()) {
+        let problem = match key.as_str() {
+            "capture_fps" if !v.as_f64().is_some_and(|f| f > 0.0 && f <= 60.0) => "must be above 0 and at most 60",
+            "conf_threshold" if !v.as_f64().is_some_and(|c| (0.0..=1.0).contains(&c)) => "must be between 0 and 1",
+            "upload_endpoint" if !v.as_str().is_some_and(|u| u.starts_with("http://") || u.starts_with("https://")) => {
+                "must be an http(s) URL"
+            }
+            "sampling_mode" if !v.as_str().is_some_and(|m| SAMPLING_MODES.contains(&m)) => "must be all, interval or motion",
+            "capture_fps" | "conf_threshold" | "upload_endpoint" | "sampling_mode" => continue,
+            _ => return Err(format!("unknown config key {}", key)),
+        };
+        return Err(format!("{} {}", key, problem));
+    }
+    Ok(())
+}
+
+#[derive(FromRow)]
+struct OverridesRow {
+    device_id: String,
+    overrides: Value,
+    updated_at: OffsetDateTime,
+}
+
+/// Effective configuration of `id` and when any layer of it last changed
+async fn effective_config(db: &PgPool, id: &str) -> Result<(Map<String, Value>, OffsetDateTime), (StatusCode, String)> {
+    let q = sqlx::query_as::<_, OverridesRow>(
+        "SELECT device_id, overrides, updated_at FROM device_configs WHERE device_id IN ($1, $2)",
+    )
+    .bind(FLEET)
+    .bind(id)
+    .fetch_all(db);
+    let mut rows = perf::timed("device_config", || format!("id={}", id), q).await.map_err(internal)?;
+    // Fleet first so the device's own overrides win
+    rows.sort_by_key(|r| r.device_id != FLEET);
+    let mut doc = default_config();
+    let mut modified = OffsetDateTime::UNIX_EPOCH;
+    for row in rows {
+        if let Value::Object(overrides) = row.overrides {
+            doc.extend(overrides);
+        }
+        modified = modified.max(row.updated_at);
+    }
+    Ok((doc, modified))
+}
+
+/// Merge `changes` into the stored overrides of `id` (or the fleet), returns the new overrides
+async fn update_overrides(db: &PgPool, id: &str, changes: Map<String, Value>, by: &str) -> Result<Value, (StatusCode, String)> {
+    validate_overrides(&changes).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let (set, cleared): (Map<String, Value>, Vec<String>) = changes.into_iter().fold(
+        (Map::new(), Vec::new()),
+        |(mut set, mut cleared), (k, v)| {
+            if v.is_null() {
+                cleared.push(k);
+            } else {
+                set.insert(k, v);
+            }
+            (set, cleared)
+        },
+    );
+    let q = sqlx::query_scalar::<_, Value>(
+        r#"
+        INSERT INTO device_configs (device_id, overrides, updated_by) VALUES ($1, $2 - $3::TEXT[], $4)
+        ON CONFLICT (device_id) DO UPDATE
+        SET overrides = (device_configs.overrides || $2) - $3::TEXT[], updated_by = $4, updated_at = NOW()
+        RETURNING overrides
+        "#,
+    )
+    .bind(id)
+    .bind(Value::Object(set))
+    .bind(&cleared)
+    .bind(by)
+    .fetch_one(db);
+    perf::timed("update_device_config", || format!("id={}", id), q).await.map_err(internal)
+}
+
+async fn config_response(db: &PgPool, id: &str, headers: &HeaderMap) -> Result<Response, (StatusCode, String)> {
+    let (doc, modified) = effective_config(db, id).await?;
+    let body = Value::Object(doc).to_string();
+    let version = conditional::Version::of_content(modified, body.as_bytes());
+    if version.matches(headers) {
+        return Ok(version.not_modified());
+    }
+    Ok(version.tag(([(axum::http::header::CONTENT_TYPE, "application/json")], body).into_response()))
+}
+
+// ==================== Handlers ====================
+
+/// POST /devices/:id/heartbeat — device check-in with firmware, model and capabilities;
+/// registers unknown devices. Only the device itself may report, see `require_verified`.
+pub async fn heartbeat_handler(
+    State(st): State<AppState>,
+    signed: Option<Extension<signing::VerifiedDevice>>,
+    cert: Option<Extension<tls::ClientCert>>,
+    Path(id): Path<String>,
+    Json(hb): Json<Heartbeat>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if id.is_empty() || id.len() > MAX_ID_LEN {
+        return Err((StatusCode::BAD_REQUEST, format!("device id must be 1-{} characters", MAX_ID_LEN)));
+    }
+    require_verified(&st.db, signed.as_deref(), cert.as_deref(), &id).await?;
+    if hb.capabilities.len() > MAX_CAPABILITIES {
+        return Err((StatusCode::BAD_REQUEST, format!("at most {} capabilities", MAX_CAPABILITIES)));
+    }
+    let clip = |s: Option<String>, max: usize| s.map(|s| s.trim().chars().take(max).collect::<String>()).filter(|s| !s.is_empty());
+    let (model, firmware) = (clip(hb.model, 100), clip(hb.firmware_version, 50));
+    let mut capabilities: Vec<String> = hb.capabilities.iter().map(|c| c.trim().to_lowercase()).filter(|c| !c.is_empty()).collect();
+    capabilities.sort();
+    capabilities.dedup();
+
+    let q = sqlx::query_scalar::<_, Option<String>>(
+        r#"
+        WITH previous AS (SELECT firmware_version FROM devices WHERE id = $1)
+        INSERT INTO devices (id, model, firmware_version, capabilities) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (id) DO UPDATE SET model = EXCLUDED.model, firmware_version = EXCLUDED.firmware_version,
+                                       capabilities = EXCLUDED.capabilities, last_seen = NOW()
+        RETURNING (SELECT firmware_version FROM previous)
+        "#,
+    )
+    .bind(&id)
+    .bind(&model)
+    .bind(&firmware)
+    .bind(SqlJson(&capabilities))
+    .fetch_one(&st.db);
+    let previous = perf::timed("device_heartbeat", || format!("id={}", id), q).await.map_err(internal)?;
+    if previous.is_some() && previous != firmware {
+        info!(device = %id, from = ?previous, to = ?firmware, "device firmware changed");
+    }
+    Ok(Json(json!({
+        "id": id,
+        "min_firmware": min_firmware(model.as_deref()),
+        "outdated": outdated(model.as_deref(), firmware.as_deref()),
+    })))
+}
+
+/// GET /devices — registered devices with firmware status (login required)
+pub async fn list_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    Ok(Json(all(&st.db).await?))
+}
+
+/// GET /devices/outdated — devices below the configured minimum firmware (login required)
+pub async fn outdated_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let devices: Vec<Device> = all(&st.db).await?.into_iter().filter(|d| d.outdated).collect();
+    Ok(Json(json!({ "configured": !config().min_firmware.is_empty(), "devices": devices })))
+}
+
+/// GET /devices/:id/config — the device's effective configuration (built-in defaults, fleet
+/// defaults, its own overrides) with an ETag; devices poll with `If-None-Match`. Overrides
+/// may carry credentials, so only the device itself may read it, see `require_verified`.
+pub async fn config_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    signed: Option<Extension<signing::VerifiedDevice>>,
+    cert: Option<Extension<tls::ClientCert>>,
+    Path(id): Path<String>,
+) -> Result<Response, (StatusCode, String)> {
+    require_verified(&st.db, signed.as_deref(), cert.as_deref(), &id).await?;
+    config_response(&st.db, &id, &headers).await
+}
+
+/// PUT /devices/:id/config — set (or with null, clear) configuration overrides for one device (admin)
+pub async fn put_config_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(changes): Json<Map<String, Value>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    if id.is_empty() || id.len() > MAX_ID_LEN || id == FLEET {
+        return Err((StatusCode::BAD_REQUEST, format!("device id must be 1-{} characters", MAX_ID_LEN)));
+    }
+    let overrides = update_overrides(&st.db, &id, changes, &claims.username).await?;
+    info!(device = %id, overrides = %overrides, by = %claims.username, "device config updated");
+    Ok(Json(json!({ "id": id, "overrides": overrides })))
+}
+
+/// GET /admin/device-config — fleet-wide defaults over the built-in configuration (admin)
+pub async fn fleet_config_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<Response, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    config_response(&st.db, FLEET, &headers).await
+}
+
+/// PUT /admin/device-config — set (or with null, clear) fleet defaults (admin)
+pub async fn put_fleet_config_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(changes): Json<Map<String, Value>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let overrides = update_overrides(&st.db, FLEET, changes, &claims.username).await?;
+    info!(overrides = %overrides, by = %claims.username, "fleet device config updated");
+    Ok(Json(json!({ "overrides": overrides })))
+}
+
+// ==================== Certificates ====================
+
+/// PUT /devices/:id/certificates/:fingerprint — bind a client certificate (SHA-256 of the DER
+/// leaf, hex) to the device (admin); a certificate belongs to one device at a time
+pub async fn put_certificate_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path((id, fingerprint)): Path<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    if id.is_empty() || id.len() > MAX_ID_LEN || id == FLEET {
+        return Err((StatusCode::BAD_REQUEST, format!("device id must be 1-{} characters", MAX_ID_LEN)));
+    }
+    let fingerprint = fingerprint.to_ascii_lowercase().replace(':', "");
+    if fingerprint.len() != 64 || !fingerprint.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err((StatusCode::BAD_REQUEST, "fingerprint must be a SHA-256 in hex".to_string()));
+    }
+    let q = sqlx::query(
+        r#"
+        INSERT INTO device_certificates (fingerprint, device_id, created_by) VALUES ($1, $2, $3)
+        ON CONFLICT (fingerprint) DO UPDATE SET device_id = EXCLUDED.device_id, created_by = EXCLUDED.created_by, created_at = NOW()
+        "#,
+    )
+    .bind(&fingerprint)
+    .bind(&id)
+    .bind(&claims.username)
+    .execute(&st.db);
+    perf::timed("put_device_certificate", || format!("device={}", id), q).await.map_err(internal)?;
+    info!(device = %id, fingerprint = %fingerprint, by = %claims.username, "device certificate registered");
+    Ok(Json(json!({ "id": id, "fingerprint": fingerprint })))
+}
+
+/// DELETE /devices/:id/certificates/:fingerprint — unbind a client certificate (admin)
+pub async fn delete_certificate_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path((id, fingerprint)): Path<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let fingerprint = fingerprint.to_ascii_lowercase().replace(':', "");
+    let q = sqlx::query("DELETE FROM device_certificates WHERE fingerprint = $1 AND device_id = $2")
+        .bind(&fingerprint)
+        .bind(&id)
+        .execute(&st.db);
+    let deleted = perf::timed("delete_device_certificate", || format!("device={}", id), q).await.map_err(internal)?.rows_affected();
+    if deleted == 0 {
+        return Err((StatusCode::NOT_FOUND, "No such certificate for this device".to_string()));
+    }
+    info!(device = %id, fingerprint = %fingerprint, by = %claims.username, "device certificate removed");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,244 @@
+//! Distribution statistics for FOD Detection Backend
+//! Per-class confidence, bbox size and objects-per-frame histograms and percentiles for threshold tuning
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool};
+use std::collections::BTreeMap;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+
+use crate::{db::{self, internal}, perf, AppState};
+
+/// Percentiles reported for every metric, as fractions
+const PERCENTILES: [f64; 8] = [0.05, 0.1, 0.25, 0.5, 0.75, 0.9, 0.95, 0.99];
+/// Histogram bins over [0, 1] for confidence and bbox size unless `bins=` says otherwise
+const DEFAULT_BINS: i32 = 10;
+const MAX_BINS: i32 = 100;
+/// Objects-per-frame histogram has one bin per count up to this, the last one is "this or more"
+const MAX_OBJECT_BIN: i32 = 10;
+
+/// Metric keys, in response order
+const CONFIDENCE: &str = "confidence";
+const BBOX_SIZE: &str = "bbox_size";
+const OBJECTS: &str = "objects_per_frame";
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct DistributionParams {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    /// Only this class and everything below it in the taxonomy
+    pub class: Option<String>,
+    /// Bins of the confidence and bbox size histograms (default 10)
+    pub bins: Option<i32>,
+    /// Count quarantined sources too
+    #[serde(default)]
+    pub include_quarantine: bool,
+}
+
+#[derive(FromRow)]
+struct StatsRow {
+    class: String,
+    metric: String,
+    samples: i64,
+    mean: f64,
+    min: f64,
+    max: f64,
+    percentiles: Vec<f64>,
+}
+
+#[derive(FromRow)]
+struct BinRow {
+    class: String,
+    metric: String,
+    bin: i32,
+    count: i64,
+}
+
+#[derive(Serialize)]
+pub struct Distribution {
+    pub samples: i64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// `p5` … `p99`
+    pub percentiles: BTreeMap<String, f64>,
+    /// Counts per bin: equal-width over [0, 1], or objects 1, 2, … `MAX_OBJECT_BIN`+ per frame
+    pub histogram: Vec<i64>,
+}
+
+#[derive(Serialize, Default)]
+pub struct ClassDistributions {
+    pub class: String,
+    pub events: i64,
+    pub confidence: Option<Distribution>,
+    /// Square root of the bbox area as a fraction of the image, for events with a usable bbox
+    pub bbox_size: Option<Distribution>,
+    pub objects_per_frame: Option<Distribution>,
+}
+
+// ==================== Queries ====================
+
+/// One `(class_id, metric, value)` row per event and metric in [$1, $2), class subtree `$3`.
+/// Normalized boxes (every value in [0, 1]) are used as they are, pixel boxes need `meta.img_w/img_h`.
+fn samples(include_quarantine: bool) -> String {
+    format!(
+        r#"
+    WITH scoped AS (
+        SELECT e.*
+        FROM events e
+        WHERE e.ts >= $1 AND e.ts < $2
+          AND ($3::TEXT IS NULL OR e.class_id IN (SELECT ca.class_id FROM class_ancestors ca
+                                                  JOIN fod_classes p ON p.id = ca.ancestor_id
+                                                  WHERE p.name = $3)){}
+    ),
+    boxes AS (
+        SELECT s.class_id, b.x, b.y, b.w, b.h,
+               CASE WHEN jsonb_typeof(d.meta->'img_w') = 'number' THEN (d.meta->>'img_w')::FLOAT8 END AS img_w,
+               CASE WHEN jsonb_typeof(d.meta->'img_h') = 'number' THEN (d.meta->>'img_h')::FLOAT8 END AS img_h
+        FROM scoped s
+        JOIN event_details d ON d.event_id = s.id
+        CROSS JOIN LATERAL (
+            SELECT CASE WHEN jsonb_typeof(d.bbox->0) = 'number' THEN (d.bbox->>0)::FLOAT8 END AS x,
+                   CASE WHEN jsonb_typeof(d.bbox->1) = 'number' THEN (d.bbox->>1)::FLOAT8 END AS y,
+                   CASE WHEN jsonb_typeof(d.bbox->2) = 'number' THEN (d.bbox->>2)::FLOAT8 END AS w,
+                   CASE WHEN jsonb_typeof(d.bbox->3) = 'number' THEN (d.bbox->>3)::FLOAT8 END AS h
+        ) b
+        WHERE jsonb_typeof(d.bbox) = 'array' AND jsonb_array_length(d.bbox) = 4
+    ),
+    samples AS (
+        SELECT class_id, 'confidence' AS metric, confidence::FLOAT8 AS value FROM scoped
+        UNION ALL
+        SELECT class_id, 'objects_per_frame', object_count::FLOAT8 FROM scoped
+        UNION ALL
+        SELECT class_id, 'bbox_size',
+               CASE WHEN x BETWEEN 0 AND 1 AND y BETWEEN 0 AND 1 AND w BETWEEN 0 AND 1 AND h BETWEEN 0 AND 1 THEN SQRT(w * h)
+                    WHEN img_w > 0 AND img_h > 0 THEN SQRT(GREATEST(w, 0) * GREATEST(h, 0) / (img_w * img_h))
+               END
+        FROM boxes
+    )
+"#,
+        db::quarantine_clause("e", include_quarantine)
+    )
+}
+
+async fn stats(
+    db: &PgPool,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    class: Option<&str>,
+    include_quarantine: bool,
+) -> Result<Vec<StatsRow>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        {}
+        SELECT fc.name AS class, s.metric, COUNT(*) AS samples, AVG(s.value) AS mean, MIN(s.value) AS min, MAX(s.value) AS max,
+               percentile_cont($4::FLOAT8[]) WITHIN GROUP (ORDER BY s.value) AS percentiles
+        FROM samples s
+        JOIN fod_classes fc ON fc.id = s.class_id
+        WHERE s.value IS NOT NULL
+        GROUP BY 1, 2
+        ORDER BY 1, 2
+        "#,
+        samples(include_quarantine)
+    );
+    let q = sqlx::query_as::<_, StatsRow>(&sql).bind(from).bind(to).bind(class).bind(PERCENTILES.as_slice()).fetch_all(db);
+    perf::timed("distribution_stats", || format!("from={} to={} class={:?}", from, to, class), q).await.map_err(internal)
+}
+
+/// Bins are 1-based; values of exactly 1 fall in the last bin
+async fn histograms(
+    db: &PgPool,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    class: Option<&str>,
+    bins: i32,
+    include_quarantine: bool,
+) -> Result<Vec<BinRow>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        {}
+        SELECT fc.name AS class, s.metric,
+               CASE WHEN s.metric = 'objects_per_frame' THEN LEAST(GREATEST(s.value::INT, 1), $5)
+                    ELSE LEAST(GREATEST(width_bucket(s.value, 0, 1, $4), 1), $4)
+               END AS bin,
+               COUNT(*) AS count
+        FROM samples s
+        JOIN fod_classes fc ON fc.id = s.class_id
+        WHERE s.value IS NOT NULL
+        GROUP BY 1, 2, 3
+        "#,
+        samples(include_quarantine)
+    );
+    let q = sqlx::query_as::<_, BinRow>(&sql).bind(from).bind(to).bind(class).bind(bins).bind(MAX_OBJECT_BIN).fetch_all(db);
+    perf::timed("distribution_histograms", || format!("from={} to={} class={:?} bins={}", from, to, class, bins), q).await.map_err(internal)
+}
+
+/// `0.05` → `p5`, `0.995` → `p99.5`
+fn percentile_key(p: f64) -> String {
+    format!("p{}", (p * 1000.0).round() / 10.0)
+}
+
+// ==================== Handlers ====================
+
+/// GET /dashboard/distributions?from=&to=&class=&bins= — per-class confidence, bbox size and
+/// objects-per-frame histograms and percentiles (default last 30 days)
+pub async fn distributions_handler(
+    State(st): State<AppState>,
+    Query(p): Query<DistributionParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let to = p.to.unwrap_or_else(OffsetDateTime::now_utc);
+    let from = p.from.unwrap_or(to - Duration::days(30));
+    if from >= to {
+        return Err((StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+    let bins = p.bins.unwrap_or(DEFAULT_BINS);
+    if !(2..=MAX_BINS).contains(&bins) {
+        return Err((StatusCode::BAD_REQUEST, format!("bins must be between 2 and {}", MAX_BINS)));
+    }
+    let class = p.class.as_deref().map(str::trim).filter(|s| !s.is_empty());
+
+    let mut hist: BTreeMap<(String, String), Vec<i64>> = BTreeMap::new();
+    for r in histograms(&st.db, from, to, class, bins, p.include_quarantine).await? {
+        let len = if r.metric == OBJECTS { MAX_OBJECT_BIN } else { bins };
+        let counts = hist.entry((r.class, r.metric)).or_insert_with(|| vec![0; len as usize]);
+        counts[(r.bin - 1) as usize] = r.count;
+    }
+    let mut classes: BTreeMap<String, ClassDistributions> = BTreeMap::new();
+    for r in stats(&st.db, from, to, class, p.include_quarantine).await? {
+        let entry = classes.entry(r.class.clone()).or_insert_with(|| ClassDistributions { class: r.class.clone(), ..Default::default() });
+        let histogram = hist.remove(&(r.class, r.metric.clone())).unwrap_or_default();
+        let d = Distribution {
+            samples: r.samples,
+            mean: r.mean,
+            min: r.min,
+            max: r.max,
+            percentiles: PERCENTILES.iter().zip(r.percentiles).map(|(p, v)| (percentile_key(*p), v)).collect(),
+            histogram,
+        };
+        match r.metric.as_str() {
+            CONFIDENCE => {
+                entry.events = d.samples;
+                entry.confidence = Some(d);
+            }
+            BBOX_SIZE => entry.bbox_size = Some(d),
+            _ => entry.objects_per_frame = Some(d),
+        }
+    }
+    Ok(Json(json!({
+        "from": from.format(&Rfc3339).map_err(internal)?,
+        "to": to.format(&Rfc3339).map_err(internal)?,
+        "bins": bins,
+        "max_object_bin": MAX_OBJECT_BIN,
+        "classes": classes.into_values().collect::<Vec<_>>(),
+    })))
+}
@@ -0,0 +1,533 @@
+//! Edge mode for FOD Detection Backend
+//! SQLite-backed event store for vehicle-mounted units without Postgres, with a sync job that forwards stored events to the central server
+
+use async_trait::async_trait;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use reqwest::Client;
+use serde_json::{json, Value};
+use sqlx::{sqlite::SqliteConnectOptions, Executor, FromRow, SqlitePool};
+use std::{
+    env,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tower_http::trace::TraceLayer;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    db::{self, internal, EventDetail, NewEvent, RecentEvent},
+    geometry, idempotency,
+    repository::EventRepository,
+    secrets, settings, signing, IngestEventRequest,
+};
+
+/// Events forwarded per sync round
+const SYNC_BATCH: i64 = 100;
+/// Central requests slower than this count as failed
+const SYNC_TIMEOUT: Duration = Duration::from_secs(10);
+
+const SCHEMA: &str = r#"
+    CREATE TABLE IF NOT EXISTS fod_classes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        alias_key TEXT NOT NULL UNIQUE
+    );
+    CREATE TABLE IF NOT EXISTS events (
+        id TEXT PRIMARY KEY,
+        ts_us INTEGER NOT NULL,
+        class_id INTEGER NOT NULL REFERENCES fod_classes(id),
+        object_count INTEGER NOT NULL,
+        confidence REAL NOT NULL,
+        raw_confidence REAL,
+        latitude REAL NOT NULL,
+        longitude REAL NOT NULL,
+        source TEXT NOT NULL,
+        source_ref TEXT NOT NULL,
+        track_id TEXT,
+        bbox TEXT,
+        meta TEXT,
+        synced_at INTEGER,
+        sync_error TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_events_track ON events (source_ref, track_id, ts_us);
+    CREATE INDEX IF NOT EXISTS idx_events_unsynced ON events (ts_us) WHERE synced_at IS NULL AND sync_error IS NULL;
+"#;
+
+// ==================== Config ====================
+
+/// SQLite database file (`EDGE_DB_PATH`); the backend runs in edge mode when set
+pub fn db_path() -> Option<String> {
+    env::var("EDGE_DB_PATH").ok().filter(|s| !s.trim().is_empty())
+}
+
+/// Central server events are forwarded to (`EDGE_CENTRAL_URL`); without it events stay local
+fn central_url() -> Option<&'static str> {
+    static URL: OnceLock<Option<String>> = OnceLock::new();
+    URL.get_or_init(|| env::var("EDGE_CENTRAL_URL").ok().map(|s| s.trim().trim_end_matches('/').to_string()).filter(|s| !s.is_empty()))
+        .as_deref()
+}
+
+/// Seconds between sync rounds (`EDGE_SYNC_SECS`, default 30)
+fn sync_interval() -> Duration {
+    static SECS: OnceLock<u64> = OnceLock::new();
+    Duration::from_secs(*SECS.get_or_init(|| {
+        env::var("EDGE_SYNC_SECS").ok().and_then(|s| s.parse().ok()).filter(|&s| s > 0).unwrap_or(30)
+    }))
+}
+
+/// Device id and key for signing forwarded requests (`EDGE_DEVICE_ID`, `EDGE_DEVICE_KEY`),
+/// matching an entry of the central server's `DEVICE_KEYS`
+fn device_key() -> Option<&'static (String, Vec<u8>)> {
+    static KEY: OnceLock<Option<(String, Vec<u8>)>> = OnceLock::new();
+    KEY.get_or_init(|| {
+        let id = env::var("EDGE_DEVICE_ID").ok().filter(|s| !s.is_empty())?;
+        let key = secrets::get("EDGE_DEVICE_KEY").filter(|s| !s.is_empty())?;
+        Some((id, key.into_bytes()))
+    })
+    .as_ref()
+}
+
+// ==================== SQLite Store ====================
+
+fn to_us(ts: OffsetDateTime) -> i64 {
+    (ts.unix_timestamp_nanos() / 1_000) as i64
+}
+
+fn from_us(us: i64) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp_nanos(us as i128 * 1_000).unwrap_or(OffsetDateTime::UNIX_EPOCH)
+}
+
+fn parse_json(s: Option<String>) -> Option<Value> {
+    s.and_then(|s| serde_json::from_str(&s).ok())
+}
+
+#[derive(FromRow)]
+struct EventRow {
+    id: String,
+    ts_us: i64,
+    class_name: String,
+    object_count: i32,
+    confidence: f32,
+    raw_confidence: Option<f32>,
+    latitude: f32,
+    longitude: f32,
+    source: String,
+    source_ref: String,
+    bbox: Option<String>,
+    meta: Option<String>,
+}
+
+const EVENT_COLUMNS: &str = r#"
+    e.id, e.ts_us, c.name AS class_name, e.object_count, e.confidence, e.raw_confidence,
+    e.latitude, e.longitude, e.source, e.source_ref, e.bbox, e.meta
+"#;
+
+/// Events kept on the unit itself. Meta is stored as received; the central server seals
+/// sensitive fields when the synced event reaches it. Lifecycle state is always `detected`.
+pub struct SqliteEventRepository {
+    pool: SqlitePool,
+}
+
+/// Sync backlog of the local store
+#[derive(Debug, PartialEq, Eq)]
+pub struct Backlog {
+    pub pending: i64,
+    pub synced: i64,
+    /// Refused by the central server, kept for inspection and not retried
+    pub rejected: i64,
+}
+
+impl SqliteEventRepository {
+    /// Open (creating if needed) the database at `url`, e.g. `sqlite:///data/fod.db` or `sqlite::memory:`
+    pub async fn open(url: &str) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(url)?.create_if_missing(true).foreign_keys(true);
+        // An in-memory database lives and dies with its connection
+        let max = if url.contains(":memory:") { 1 } else { 4 };
+        let pool = sqlx::pool::PoolOptions::new().max_connections(max).connect_with(options).await?;
+        pool.execute(SCHEMA).await?;
+        Ok(Self { pool })
+    }
+
+    /// Oldest events not yet forwarded, with their class names
+    async fn pending(&self, limit: i64) -> Result<Vec<EventRow>, (StatusCode, String)> {
+        let sql = format!(
+            "SELECT {} FROM events e JOIN fod_classes c ON c.id = e.class_id
+             WHERE e.synced_at IS NULL AND e.sync_error IS NULL ORDER BY e.ts_us LIMIT ?",
+            EVENT_COLUMNS
+        );
+        sqlx::query_as::<_, EventRow>(&sql).bind(limit).fetch_all(&self.pool).await.map_err(internal)
+    }
+
+    async fn mark_synced(&self, id: &str) -> Result<(), (StatusCode, String)> {
+        sqlx::query("UPDATE events SET synced_at = ? WHERE id = ?")
+            .bind(to_us(OffsetDateTime::now_utc()))
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(internal)?;
+        Ok(())
+    }
+
+    async fn mark_rejected(&self, id: &str, reason: &str) -> Result<(), (StatusCode, String)> {
+        sqlx::query("UPDATE events SET sync_error = ? WHERE id = ?").bind(reason).bind(id).execute(&self.pool).await.map_err(internal)?;
+        Ok(())
+    }
+
+    pub async fn backlog(&self) -> Result<Backlog, (StatusCode, String)> {
+        let (pending, synced, rejected): (i64, i64, i64) = sqlx::query_as(
+            "SELECT COALESCE(SUM(synced_at IS NULL AND sync_error IS NULL), 0),
+                    COALESCE(SUM(synced_at IS NOT NULL), 0),
+                    COALESCE(SUM(sync_error IS NOT NULL), 0)
+             FROM events",
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(internal)?;
+        Ok(Backlog { pending, synced, rejected })
+    }
+}
+
+#[async_trait]
+impl EventRepository for SqliteEventRepository {
+    async fn class_id(&self, name: &str) -> Result<i32, (StatusCode, String)> {
+        sqlx::query_scalar(
+            "INSERT INTO fod_classes (name, alias_key) VALUES (?, ?)
+             ON CONFLICT (alias_key) DO UPDATE SET alias_key = excluded.alias_key
+             RETURNING id",
+        )
+        .bind(name)
+        .bind(db::class_alias_key(name))
+        .fetch_one(&self.pool)
+        .await
+        .map_err(internal)
+    }
+
+    async fn find_track(&self, source_ref: &str, track_id: &str, window_secs: i64) -> Result<Option<Uuid>, (StatusCode, String)> {
+        let since = to_us(OffsetDateTime::now_utc() - time::Duration::seconds(window_secs));
+        let id: Option<String> = sqlx::query_scalar(
+            "SELECT id FROM events WHERE source_ref = ? AND track_id = ? AND ts_us > ? ORDER BY ts_us DESC LIMIT 1",
+        )
+        .bind(source_ref)
+        .bind(track_id)
+        .bind(since)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(internal)?;
+        Ok(id.and_then(|id| Uuid::parse_str(&id).ok()))
+    }
+
+    async fn merge_overlapping(&self, e: &NewEvent, radius_m: f64, window_secs: i64) -> Result<Option<Uuid>, (StatusCode, String)> {
+        let window = window_secs * 1_000_000;
+        let ts = to_us(e.ts);
+        let mut tx = self.pool.begin().await.map_err(internal)?;
+        let candidates: Vec<(String, f32, f32, String, Option<String>)> = sqlx::query_as(
+            "SELECT id, latitude, longitude, source_ref, meta FROM events
+             WHERE class_id = ? AND source_ref <> ? AND ts_us BETWEEN ? AND ?",
+        )
+        .bind(e.class_id)
+        .bind(&e.source_ref)
+        .bind(ts - window)
+        .bind(ts + window)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(internal)?;
+        let target = candidates
+            .into_iter()
+            .map(|c| (geometry::distance_m(e.latitude as f64, e.longitude as f64, c.1 as f64, c.2 as f64), c))
+            .filter(|(d, _)| *d <= radius_m)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, c)| c);
+        let Some((id, _, _, source_ref, meta)) = target else {
+            return Ok(None);
+        };
+
+        let mut meta = parse_json(meta).unwrap_or_else(|| json!({}));
+        let mut sources: Vec<String> = match meta.get("contributing_sources").and_then(Value::as_array) {
+            Some(list) => list.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            None => vec![source_ref],
+        };
+        sources.push(e.source_ref.clone());
+        sources.sort();
+        sources.dedup();
+        meta["contributing_sources"] = json!(sources);
+        sqlx::query("UPDATE events SET confidence = MAX(confidence, ?), meta = ? WHERE id = ?")
+            .bind(e.confidence)
+            .bind(meta.to_string())
+            .bind(&id)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        tx.commit().await.map_err(internal)?;
+        Ok(Uuid::parse_str(&id).ok())
+    }
+
+    async fn insert(&self, e: NewEvent) -> Result<Uuid, (StatusCode, String)> {
+        let id = Uuid::new_v4();
+        let track_id = e.meta.as_ref().and_then(|m| m.get("track_id")).and_then(Value::as_str).map(str::to_string);
+        sqlx::query(
+            "INSERT INTO events (id, ts_us, class_id, object_count, confidence, raw_confidence,
+                                 latitude, longitude, source, source_ref, track_id, bbox, meta)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(id.to_string())
+        .bind(to_us(e.ts))
+        .bind(e.class_id)
+        .bind(e.object_count)
+        .bind(e.confidence)
+        .bind(e.raw_confidence)
+        .bind(e.latitude)
+        .bind(e.longitude)
+        .bind(&e.source)
+        .bind(&e.source_ref)
+        .bind(track_id)
+        .bind(e.bbox.map(|v| v.to_string()))
+        .bind(e.meta.map(|v| v.to_string()))
+        .execute(&self.pool)
+        .await
+        .map_err(internal)?;
+        Ok(id)
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<EventDetail>, (StatusCode, String)> {
+        let sql = format!("SELECT {} FROM events e JOIN fod_classes c ON c.id = e.class_id WHERE e.id = ?", EVENT_COLUMNS);
+        let row = sqlx::query_as::<_, EventRow>(&sql).bind(id.to_string()).fetch_optional(&self.pool).await.map_err(internal)?;
+        Ok(row.map(|r| EventDetail {
+            event: RecentEvent {
+                id,
+                ts: from_us(r.ts_us),
+                class_name: r.class_name,
+                object_count: r.object_count,
+                confidence: r.confidence,
+                latitude: r.latitude,
+                longitude: r.longitude,
+                source: r.source,
+                source_ref: r.source_ref,
+                frame_count: None,
+                severity: None,
+            },
+            bbox: parse_json(r.bbox),
+            meta: parse_json(r.meta),
+            state: Some("detected".to_string()),
+            raw_confidence: r.raw_confidence,
+        }))
+    }
+}
+
+// ==================== Sync ====================
+
+/// Outcome of the last sync round, for /edge/status
+#[derive(Default)]
+struct SyncStatus {
+    last_attempt: Option<OffsetDateTime>,
+    last_success: Option<OffsetDateTime>,
+    last_error: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub sent: usize,
+    pub rejected: usize,
+    /// Why the round stopped early (offline, central error); the rest is retried next round
+    pub stopped: Option<String>,
+}
+
+/// Central `/events/ingest` body for a stored event; `meta.edge_event_id` ties the central
+/// row back to the unit's own
+fn ingest_body(r: &EventRow) -> Value {
+    let mut meta = match parse_json(r.meta.clone()) {
+        Some(Value::Object(m)) => m,
+        _ => serde_json::Map::new(),
+    };
+    meta.insert("edge_event_id".to_string(), json!(r.id));
+    json!({
+        "ts": from_us(r.ts_us).format(&Rfc3339).unwrap_or_default(),
+        "object_class": r.class_name,
+        "object_count": r.object_count,
+        "confidence": r.confidence,
+        "latitude": r.latitude,
+        "longitude": r.longitude,
+        "source": r.source,
+        "source_ref": r.source_ref,
+        "bbox": parse_json(r.bbox.clone()),
+        "meta": meta,
+    })
+}
+
+/// Forward pending events oldest first until the backlog is empty or the central server
+/// can't take more. The local id goes along as `Idempotency-Key`, so an event whose response
+/// was lost isn't stored twice when it is sent again.
+pub async fn sync_once(repo: &SqliteEventRepository, http: &Client, central: &str) -> Result<SyncReport, (StatusCode, String)> {
+    let mut report = SyncReport::default();
+    loop {
+        let batch = repo.pending(SYNC_BATCH).await?;
+        if batch.is_empty() {
+            return Ok(report);
+        }
+        for row in &batch {
+            let body = ingest_body(row).to_string();
+            let mut req = http
+                .post(format!("{}/events/ingest", central))
+                .timeout(SYNC_TIMEOUT)
+                .header("content-type", "application/json")
+                .header(idempotency::HEADER, &row.id);
+            if let Some((device, key)) = device_key() {
+                for (name, value) in signing::sign(device, key, "POST", "/events/ingest", body.as_bytes()) {
+                    req = req.header(name, value);
+                }
+            }
+            let status = match req.body(body).send().await {
+                Ok(resp) => resp.status(),
+                Err(e) => {
+                    report.stopped = Some(format!("central unreachable: {}", e));
+                    return Ok(report);
+                }
+            };
+            if status.is_success() {
+                repo.mark_synced(&row.id).await?;
+                report.sent += 1;
+            } else if status.is_client_error() && ![401, 403, 408, 429].contains(&status.as_u16()) {
+                // The event itself is refused; retrying won't help and would block the backlog
+                warn!(event_id = %row.id, %status, "central server rejected edge event");
+                repo.mark_rejected(&row.id, &format!("central server returned {}", status)).await?;
+                report.rejected += 1;
+            } else {
+                report.stopped = Some(format!("central server returned {}", status));
+                return Ok(report);
+            }
+        }
+    }
+}
+
+/// Sync every `EDGE_SYNC_SECS` while `EDGE_CENTRAL_URL` is set
+fn spawn_sync(state: EdgeState) {
+    let Some(central) = central_url() else {
+        info!("EDGE_CENTRAL_URL not set, edge events stay local");
+        return;
+    };
+    info!(central, interval_secs = sync_interval().as_secs(), "edge sync started");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(sync_interval());
+        loop {
+            tick.tick().await;
+            let result = sync_once(&state.events, &state.http, central).await;
+            let now = OffsetDateTime::now_utc();
+            let mut status = state.sync.lock().unwrap();
+            status.last_attempt = Some(now);
+            match result {
+                Ok(report) => {
+                    if report.sent > 0 || report.rejected > 0 {
+                        info!(sent = report.sent, rejected = report.rejected, "edge events synced");
+                    }
+                    if report.stopped.is_none() {
+                        status.last_success = Some(now);
+                    }
+                    status.last_error = report.stopped;
+                }
+                Err((_, e)) => status.last_error = Some(e),
+            }
+        }
+    });
+}
+
+// ==================== Handlers ====================
+
+#[derive(Clone)]
+pub struct EdgeState {
+    pub http: Client,
+    pub events: Arc<SqliteEventRepository>,
+    sync: Arc<Mutex<SyncStatus>>,
+}
+
+impl EdgeState {
+    pub fn new(http: Client, events: SqliteEventRepository) -> Self {
+        Self { http, events: Arc::new(events), sync: Arc::default() }
+    }
+}
+
+/// POST /events/ingest — store a detection locally; dedup and threshold use the default settings
+async fn ingest_handler(
+    State(st): State<EdgeState>,
+    Json(payload): Json<IngestEventRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let cfg = settings::Settings::default();
+    if let Some(track_id) = payload.meta.as_ref().and_then(|m| m.get("track_id")).and_then(|v| v.as_str()) {
+        if st.events.find_track(&payload.source_ref, track_id, cfg.dedup_window_secs).await?.is_some() {
+            return Ok(Json(json!({"status": "skipped", "reason": "duplicate track_id"})));
+        }
+    }
+    let ts = OffsetDateTime::parse(&payload.ts, &Rfc3339).map_err(|_| (StatusCode::BAD_REQUEST, "Invalid timestamp".to_string()))?;
+    if (payload.confidence as f64) < cfg.min_confidence {
+        return Ok(Json(json!({"status": "skipped", "reason": "low confidence"})));
+    }
+    let class_id = st.events.class_id(&payload.object_class).await?;
+    let event = NewEvent {
+        ts, class_id, object_count: payload.object_count, confidence: payload.confidence, raw_confidence: None,
+        latitude: payload.latitude, longitude: payload.longitude, source: payload.source, source_ref: payload.source_ref,
+        bbox: payload.bbox, meta: payload.meta,
+    };
+    let id = st.events.insert(event).await?;
+    Ok(Json(json!({"id": id, "status": "success"})))
+}
+
+/// GET /events/:id — one locally stored event
+async fn get_event_handler(State(st): State<EdgeState>, Path(id): Path<Uuid>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let event = st.events.get(id).await?.ok_or((StatusCode::NOT_FOUND, "Event not found".to_string()))?;
+    Ok(Json(event))
+}
+
+/// GET /edge/status — sync backlog and the outcome of the last sync round
+async fn status_handler(State(st): State<EdgeState>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let backlog = st.events.backlog().await?;
+    let fmt = |t: Option<OffsetDateTime>| t.and_then(|t| t.format(&Rfc3339).ok());
+    let sync = st.sync.lock().unwrap();
+    Ok(Json(json!({
+        "central": central_url(),
+        "pending": backlog.pending,
+        "synced": backlog.synced,
+        "rejected": backlog.rejected,
+        "last_attempt": fmt(sync.last_attempt),
+        "last_success": fmt(sync.last_success),
+        "last_error": sync.last_error,
+    })))
+}
+
+async fn health() -> Json<Value> {
+    Json(json!({"ok": true, "mode": "edge"}))
+}
+
+// ==================== Server ====================
+
+/// Router for edge mode: the device-facing ingest path and local lookups only
+pub fn build_edge_app(state: EdgeState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/events/ingest", post(ingest_handler))
+        .route("/events/:id", get(get_event_handler))
+        .route("/edge/status", get(status_handler))
+        .layer(TraceLayer::new_for_http())
+        .with_state(state)
+}
+
+/// Run the edge backend on `PORT` with the SQLite database at `path`
+pub async fn serve(http: Client, path: String) {
+    let events = SqliteEventRepository::open(&format!("sqlite://{}", path)).await.expect("Failed to open edge database");
+    info!(%path, "edge mode, events stored in SQLite");
+    let state = EdgeState::new(http, events);
+    spawn_sync(state.clone());
+
+    let port: u16 = env::var("PORT").ok().and_then(|s| s.parse().ok()).unwrap_or(8000);
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let listener = tokio::net::TcpListener::bind(addr).await.expect("Failed to bind");
+    info!(%addr, "edge backend listening");
+    axum::serve(listener, build_edge_app(state)).await.unwrap();
+}
@@ -0,0 +1,301 @@
+//! Personal-data erasure for FOD Detection Backend
+//! Scrubs source_ref and sensitive meta keys by subject or time range, deletes the frames, files,
+//! photos and comments linked to those events, and logs the action
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use time::OffsetDateTime;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{attachments, auth, crypto, db::internal, s3, uploads, AppState};
+
+/// Replacement for scrubbed source_ref values
+const ERASED: &str = "[erased]";
+/// Rows fetched per scan step
+const SCAN_BATCH: i64 = 500;
+
+// ==================== Request Types ====================
+
+/// At least one of `subject`, `source_ref_pattern`, `from`/`to` is required
+#[derive(Deserialize)]
+pub struct ErasureRequest {
+    /// Value to find in source_ref or the meta keys (compared after decryption)
+    pub subject: Option<String>,
+    /// Regex over source_ref; matching values are replaced
+    pub source_ref_pattern: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    /// Meta keys to remove, defaults to META_ENCRYPTED_FIELDS
+    pub meta_keys: Option<Vec<String>>,
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// ==================== Scrubbing ====================
+
+/// Scrub one event, returns the new (source_ref, meta) and its stored frame's key if it belongs
+/// to the request
+fn scrub(
+    req: &ErasureRequest,
+    keys: &[String],
+    source_ref: &str,
+    meta: Option<Value>,
+) -> Option<(String, Option<Value>, Option<String>)> {
+    let subject_hit = |v: &Value| matches!((&req.subject, v), (Some(s), Value::String(v)) if v == s);
+
+    // Compare against plaintext, but never write decrypted values back
+    let mut plain = meta.clone();
+    if let Some(m) = plain.as_mut() {
+        crypto::decrypt_meta(m);
+    }
+    let meta_hit = plain
+        .as_ref()
+        .and_then(|m| m.as_object())
+        .map(|m| keys.iter().any(|k| m.get(k).map(subject_hit).unwrap_or(false)))
+        .unwrap_or(false);
+    let ref_hit = req.subject.as_deref() == Some(source_ref);
+    if req.subject.is_some() && !meta_hit && !ref_hit {
+        return None;
+    }
+
+    let new_ref = if ref_hit || req.source_ref_pattern.is_some() { ERASED.to_string() } else { source_ref.to_string() };
+    let image = plain.as_ref().and_then(|m| m.get(uploads::META_KEY)).and_then(Value::as_str).map(str::to_string);
+    let new_meta = meta.map(|mut m| {
+        if let Some(obj) = m.as_object_mut() {
+            for k in keys.iter().map(String::as_str).chain([uploads::META_KEY]) {
+                obj.remove(k);
+            }
+        }
+        m
+    });
+    Some((new_ref, new_meta, image))
+}
+
+/// Count the rows `FROM table WHERE ...` selects, deleting them unless this is a dry run
+fn count_or_delete(dry_run: bool, from_where: &str) -> String {
+    if dry_run {
+        format!("SELECT COUNT(*) FROM {}", from_where)
+    } else {
+        format!("WITH gone AS (DELETE FROM {} RETURNING 1) SELECT COUNT(*) FROM gone", from_where)
+    }
+}
+
+// ==================== Stored Objects ====================
+
+/// Objects an erasure removed from storage, and the ones it could not
+#[derive(Default)]
+struct Objects {
+    deleted: i32,
+    /// `{store, key, error}` kept in the audit for follow-up
+    failures: Vec<Value>,
+}
+
+impl Objects {
+    /// Delete `key` (or an `s3://` / store URL) from `store`; failures are recorded, not returned,
+    /// so one unreachable bucket doesn't keep the database rows alive
+    async fn delete(&mut self, st: &AppState, store: &str, bucket: Result<&s3::Bucket, (StatusCode, String)>, key: &str) {
+        let result = match bucket {
+            Ok(bucket) => match bucket.key_from_url(key).or_else(|| (!key.contains("://")).then(|| key.trim_start_matches('/').to_string())) {
+                Some(key) => bucket.delete_object(&st.http, &key).await,
+                None => Err("not in this store".to_string()),
+            },
+            Err((_, e)) => Err(e),
+        };
+        match result {
+            Ok(()) => self.deleted += 1,
+            Err(e) => {
+                warn!(store, key, error = %e, "erased object could not be deleted");
+                self.failures.push(json!({ "store": store, "key": key, "error": e }));
+            }
+        }
+    }
+}
+
+// ==================== Handler ====================
+
+/// POST /admin/erasure — scrub personal data from matching events (admin)
+pub async fn erasure_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<ErasureRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    if req.subject.as_deref().map(str::is_empty).unwrap_or(true) && req.source_ref_pattern.is_none() && req.from.is_none() && req.to.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "subject, source_ref_pattern or a time range is required".to_string()));
+    }
+    if let Some(p) = &req.source_ref_pattern {
+        Regex::new(p).map_err(|e| (StatusCode::BAD_REQUEST, format!("source_ref_pattern: {}", e)))?;
+    }
+    let keys: Vec<String> = req.meta_keys.clone().unwrap_or_else(|| crypto::encrypted_fields().to_vec());
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let (mut matched, mut after): (i32, Option<Uuid>) = (0, None);
+    let mut images = BTreeSet::new();
+    let mut erased = Vec::new();
+    loop {
+        let rows = sqlx::query_as::<_, (Uuid, String, Option<Value>)>(
+            r#"
+            SELECT e.id, e.source_ref, d.meta FROM events e
+            LEFT JOIN event_details d ON d.event_id = e.id
+            WHERE ($1::TIMESTAMPTZ IS NULL OR e.ts >= $1)
+              AND ($2::TIMESTAMPTZ IS NULL OR e.ts < $2)
+              AND ($3::TEXT IS NULL OR e.source_ref ~ $3)
+              AND ($4::UUID IS NULL OR e.id > $4)
+            ORDER BY e.id
+            LIMIT $5
+            "#,
+        )
+        .bind(req.from)
+        .bind(req.to)
+        .bind(&req.source_ref_pattern)
+        .bind(after)
+        .bind(SCAN_BATCH)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(internal)?;
+        let Some((last, _, _)) = rows.last() else { break };
+        after = Some(*last);
+
+        for (id, source_ref, meta) in rows {
+            let Some((new_ref, new_meta, image)) = scrub(&req, &keys, &source_ref, meta) else { continue };
+            matched += 1;
+            images.extend(image);
+            erased.push(id);
+            if !req.dry_run {
+                // The promoted columns follow the scrubbed meta, so an erased runway or track goes too
+                sqlx::query(
+                    r#"
+                    UPDATE events
+                    SET source_ref = $2, severity = event_meta_text($3, 'severity'), track_id = event_meta_text($3, 'track_id'),
+                        runway = event_meta_text($3, 'runway'), zone = event_rollup_zone($3)
+                    WHERE id = $1
+                    "#,
+                )
+                .bind(id)
+                .bind(new_ref)
+                .bind(&new_meta)
+                .execute(&mut *tx)
+                .await
+                .map_err(internal)?;
+                sqlx::query("UPDATE event_details SET meta = $2 WHERE event_id = $1")
+                    .bind(id)
+                    .bind(new_meta)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(internal)?;
+                // Archived AI responses carry the source too; they are only debug data, so drop them
+                sqlx::query("DELETE FROM raw_inferences WHERE event_ids @> ARRAY[$1::UUID]")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(internal)?;
+            }
+        }
+    }
+
+    // Files, clearance photos and comments about the erased events go with them, and so do
+    // frames grabbed from the erased cameras or by the subject
+    let attachment_keys: Vec<String> = sqlx::query_scalar(if req.dry_run {
+        "SELECT object_key FROM event_attachments WHERE event_id = ANY($1)"
+    } else {
+        "DELETE FROM event_attachments WHERE event_id = ANY($1) RETURNING object_key"
+    })
+    .bind(&erased)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(internal)?;
+    let mut rows = serde_json::Map::new();
+    rows.insert("attachments".to_string(), json!(attachment_keys.len()));
+    for table in ["event_clearances", "event_comments"] {
+        let n: i64 = sqlx::query_scalar(&count_or_delete(req.dry_run, &format!("{} WHERE event_id = ANY($1)", table)))
+            .bind(&erased)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(internal)?;
+        rows.insert(table.trim_start_matches("event_").to_string(), json!(n));
+    }
+    let snapshots = count_or_delete(
+        req.dry_run,
+        r#"camera_snapshots
+        WHERE ($1::TIMESTAMPTZ IS NULL OR created_at >= $1)
+          AND ($2::TIMESTAMPTZ IS NULL OR created_at < $2)
+          AND ($3::TEXT IS NULL OR source_ref ~ $3)
+          AND ($4::TEXT IS NULL OR source_ref = $4 OR taken_by = $4)"#,
+    );
+    let n: i64 = sqlx::query_scalar(&snapshots)
+        .bind(req.from)
+        .bind(req.to)
+        .bind(&req.source_ref_pattern)
+        .bind(&req.subject)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(internal)?;
+    rows.insert("snapshots".to_string(), json!(n));
+
+    if req.dry_run {
+        return Ok(Json(json!({
+            "dry_run": true,
+            "events_matched": matched,
+            "rows_matched": rows,
+            "objects_matched": images.len() + attachment_keys.len(),
+        })));
+    }
+
+    // Before the commit, so a crash leaves the rows to erase again rather than orphaned objects
+    let mut objects = Objects::default();
+    for key in &images {
+        objects.delete(&st, "uploads", uploads::bucket(&st), key).await;
+    }
+    for key in &attachment_keys {
+        objects.delete(&st, "attachments", attachments::bucket(&st), key).await;
+    }
+
+    let subject_sha256 = req.subject.as_ref().map(|s| format!("{:x}", Sha256::digest(s.as_bytes())));
+    let criteria = json!({
+        "source_ref_pattern": req.source_ref_pattern,
+        "from": req.from.map(|t| t.unix_timestamp()),
+        "to": req.to.map(|t| t.unix_timestamp()),
+        "meta_keys": keys,
+    });
+    let erasure_id: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO erasures (requested_by, reason, subject_sha256, criteria, events_matched, rows_deleted, objects_deleted, object_failures)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id
+        "#,
+    )
+    .bind(&claims.username)
+    .bind(&req.reason)
+    .bind(&subject_sha256)
+    .bind(&criteria)
+    .bind(matched)
+    .bind(Value::Object(rows.clone()))
+    .bind(objects.deleted)
+    .bind(Value::Array(objects.failures.clone()))
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal)?;
+    tx.commit().await.map_err(internal)?;
+
+    info!(erasure_id, user = %claims.username, events = matched, objects = objects.deleted, failed = objects.failures.len(), "personal data erased");
+    Ok(Json(json!({
+        "erasure_id": erasure_id,
+        "events_matched": matched,
+        "rows_deleted": rows,
+        "objects_deleted": objects.deleted,
+        "object_failures": objects.failures,
+    })))
+}
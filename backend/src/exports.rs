@@ -0,0 +1,343 @@
+//! Daily event snapshots to S3-compatible storage for FOD Detection Backend
+//! Scheduled CSV export per operational day, run history and manual trigger under /admin/exports
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use std::{collections::HashMap, env, sync::OnceLock, time::Duration};
+use time::{format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{auth, calendar, comments::Comment, crypto, db::{internal, quarantine_clause, EventDetail}, filter::Filter, jobs, logging, s3, AppState};
+
+const CSV_HEADER: [&str; 12] = [
+    "id", "ts", "class_name", "object_count", "confidence", "latitude", "longitude", "source", "source_ref", "bbox", "meta",
+    "comments",
+];
+
+// ==================== Config ====================
+
+struct Config {
+    bucket: s3::Bucket,
+    /// Object key with `{site}`, `{date}`, `{year}`, `{month}`, `{day}` placeholders
+    path_template: String,
+    /// Only events matching this expression are exported (`EXPORT_FILTER`, see `filter`)
+    filter: Option<Filter>,
+}
+
+/// Exporter settings, None unless `EXPORT_S3_ENDPOINT` and `EXPORT_S3_BUCKET` are set
+fn config() -> Option<&'static Config> {
+    static CONFIG: OnceLock<Option<Config>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            Some(Config {
+                bucket: s3::Bucket::from_env("EXPORT_S3")?,
+                path_template: env::var("EXPORT_PATH_TEMPLATE")
+                    .unwrap_or_else(|_| "events/site={site}/date={date}/events.csv".to_string()),
+                filter: env::var("EXPORT_FILTER")
+                    .ok()
+                    .filter(|s| !s.trim().is_empty())
+                    .map(|s| Filter::parse(&s).unwrap_or_else(|e| panic!("EXPORT_FILTER is invalid: {}", e))),
+            })
+        })
+        .as_ref()
+}
+
+fn object_key(template: &str, day: Date) -> String {
+    let date = day.format(format_description!("[year]-[month]-[day]")).unwrap_or_default();
+    template
+        .replace("{site}", logging::site_id())
+        .replace("{date}", &date)
+        .replace("{year}", &day.year().to_string())
+        .replace("{month}", &format!("{:02}", day.month() as u8))
+        .replace("{day}", &format!("{:02}", day.day()))
+}
+
+// ==================== Models ====================
+
+time::serde::format_description!(iso_date, Date, "[year]-[month]-[day]");
+
+#[derive(Serialize, FromRow)]
+pub struct ExportRun {
+    pub id: i32,
+    #[serde(with = "iso_date")]
+    pub day: Date,
+    pub trigger: String,
+    pub status: String,
+    pub object_key: String,
+    pub rows: Option<i64>,
+    pub bytes: Option<i64>,
+    pub error: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct RunRequest {
+    /// Operational day to export (YYYY-MM-DD), defaults to the last completed one
+    pub day: Option<String>,
+    /// Export quarantined sources' events too
+    #[serde(default)]
+    pub include_quarantine: bool,
+}
+
+// ==================== Export ====================
+
+/// Last fully elapsed operational day in the site calendar
+async fn previous_day(db: &PgPool) -> Result<Date, sqlx::Error> {
+    let cal = calendar::site();
+    sqlx::query_scalar("SELECT ((NOW() AT TIME ZONE $1 - make_interval(mins => $2))::DATE - 1)")
+        .bind(&cal.tz)
+        .bind(cal.day_start)
+        .fetch_one(db)
+        .await
+}
+
+/// Events of one operational day as CSV; encrypted meta fields stay redacted
+async fn snapshot_csv(db: &PgPool, day: Date, filter: Option<&Filter>, include_quarantine: bool) -> Result<(i64, Vec<u8>), String> {
+    let cal = calendar::site();
+    let notes = sqlx::query_as::<_, Comment>(
+        r#"
+        WITH d AS (
+            SELECT ($1::DATE + make_interval(mins => $3)) AT TIME ZONE $2 AS start
+        )
+        SELECT c.id, c.event_id, c.parent_id, c.author, c.body, c.created_at
+        FROM event_comments c
+        JOIN events e ON e.id = c.event_id, d
+        WHERE e.ts >= d.start AND e.ts < d.start + INTERVAL '1 day'
+        ORDER BY c.id
+        "#,
+    )
+    .bind(day)
+    .bind(&cal.tz)
+    .bind(cal.day_start)
+    .fetch_all(db)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut comments: HashMap<Uuid, Vec<Comment>> = HashMap::new();
+    for c in notes {
+        comments.entry(c.event_id).or_default().push(c);
+    }
+
+    let mut qb = QueryBuilder::<Postgres>::new("WITH d AS (SELECT (");
+    qb.push_bind(day).push("::DATE + make_interval(mins => ").push_bind(cal.day_start);
+    qb.push(")) AT TIME ZONE ").push_bind(&cal.tz).push(
+        r#" AS start)
+        SELECT e.id, e.ts, fc.name as class_name, e.object_count, e.confidence,
+               e.latitude, e.longitude, e.source, e.source_ref, ed.bbox, ed.meta
+        FROM events e
+        JOIN fod_classes fc ON e.class_id = fc.id
+        LEFT JOIN event_details ed ON ed.event_id = e.id, d
+        WHERE e.ts >= d.start AND e.ts < d.start + INTERVAL '1 day'"#,
+    );
+    qb.push(quarantine_clause("e", include_quarantine));
+    if let Some(f) = filter {
+        qb.push(" AND ");
+        f.push_sql(&mut qb);
+    }
+    qb.push(" ORDER BY e.ts");
+    let mut rows = qb.build_query_as::<EventDetail>().fetch(db);
+
+    let mut out = csv::Writer::from_writer(Vec::new());
+    out.write_record(CSV_HEADER).map_err(|e| e.to_string())?;
+    let mut count = 0;
+    while let Some(mut e) = rows.try_next().await.map_err(|e| e.to_string())? {
+        if let Some(meta) = e.meta.as_mut() {
+            crypto::redact_meta(meta);
+        }
+        let json_col = |v: &Option<serde_json::Value>| v.as_ref().map(|v| v.to_string()).unwrap_or_default();
+        out.write_record([
+            e.event.id.to_string(),
+            e.event.ts.format(&Rfc3339).unwrap_or_default(),
+            e.event.class_name,
+            e.event.object_count.to_string(),
+            e.event.confidence.to_string(),
+            e.event.latitude.to_string(),
+            e.event.longitude.to_string(),
+            e.event.source,
+            e.event.source_ref,
+            json_col(&e.bbox),
+            json_col(&e.meta),
+            comments.remove(&e.event.id).map(|c| json!(c).to_string()).unwrap_or_default(),
+        ])
+        .map_err(|e| e.to_string())?;
+        count += 1;
+    }
+    Ok((count, out.into_inner().map_err(|e| e.to_string())?))
+}
+
+/// Export one day and record the attempt; Err(409) while another replica is exporting
+async fn run_export(
+    state: &AppState,
+    cfg: &Config,
+    day: Date,
+    trigger: &str,
+    include_quarantine: bool,
+) -> Result<ExportRun, (StatusCode, String)> {
+    jobs::run_singleton(&state.db, "daily_export", export_day(state, cfg, day, trigger, include_quarantine))
+        .await
+        .map_err(internal)?
+        .unwrap_or_else(|| Err((StatusCode::CONFLICT, "Another export is running".to_string())))
+}
+
+/// Export the previous operational day unless it already has a successful run. The check runs
+/// under the export lock, so replicas checking at once export the day once. None when there was
+/// nothing to do or another replica was exporting.
+pub async fn scheduled_export(state: &AppState) -> Result<Option<ExportRun>, (StatusCode, String)> {
+    let Some(cfg) = config() else {
+        return Ok(None);
+    };
+    let day = previous_day(&state.db).await.map_err(internal)?;
+    let job = async {
+        let done: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM export_runs WHERE day = $1 AND status = 'success')")
+            .bind(day)
+            .fetch_one(&state.db)
+            .await
+            .map_err(internal)?;
+        if done {
+            return Ok(None);
+        }
+        export_day(state, cfg, day, "schedule", false).await.map(Some)
+    };
+    jobs::run_singleton(&state.db, "daily_export", job).await.map_err(internal)?.unwrap_or(Ok(None))
+}
+
+/// Export one day and record the attempt, with the export lock held by the caller
+async fn export_day(
+    state: &AppState,
+    cfg: &Config,
+    day: Date,
+    trigger: &str,
+    include_quarantine: bool,
+) -> Result<ExportRun, (StatusCode, String)> {
+    let key = object_key(&cfg.path_template, day);
+    let run_id: i32 = sqlx::query_scalar("INSERT INTO export_runs (day, trigger, object_key) VALUES ($1, $2, $3) RETURNING id")
+        .bind(day)
+        .bind(trigger)
+        .bind(&key)
+        .fetch_one(&state.db)
+        .await
+        .map_err(internal)?;
+
+    let outcome = match snapshot_csv(&state.db, day, cfg.filter.as_ref(), include_quarantine).await {
+        Ok((rows, body)) => {
+            let bytes = body.len() as i64;
+            cfg.bucket.put_object(&state.http, &key, "text/csv", body).await.map(|_| (rows, bytes))
+        }
+        Err(e) => Err(e),
+    };
+    let (status, rows, bytes, err) = match &outcome {
+        Ok((rows, bytes)) => {
+            info!(%day, key = %key, rows, bytes, "export finished");
+            ("success", Some(*rows), Some(*bytes), None)
+        }
+        Err(e) => {
+            error!(%day, key = %key, error = %e, "export failed");
+            ("failed", None, None, Some(e.clone()))
+        }
+    };
+    sqlx::query_as::<_, ExportRun>(
+        r#"
+        UPDATE export_runs SET status = $2, rows = $3, bytes = $4, error = $5, finished_at = CURRENT_TIMESTAMP
+        WHERE id = $1
+        RETURNING id, day, trigger, status, object_key, rows, bytes, error, started_at, finished_at
+        "#,
+    )
+    .bind(run_id)
+    .bind(status)
+    .bind(rows)
+    .bind(bytes)
+    .bind(err)
+    .fetch_one(&state.db)
+    .await
+    .map_err(internal)
+}
+
+// ==================== Scheduler ====================
+
+/// Every `EXPORT_CHECK_SECS` (default 900) export the previous operational day
+/// unless it already has a successful run; failed days are retried on the next check
+pub fn spawn_scheduler(state: AppState) {
+    let Some(cfg) = config() else {
+        return;
+    };
+    let secs = env::var("EXPORT_CHECK_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(900);
+    if secs == 0 {
+        return;
+    }
+    info!(bucket = %cfg.bucket.name, template = %cfg.path_template, "daily export scheduler started");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_secs(secs));
+        loop {
+            tick.tick().await;
+            // Export errors are recorded on the run and logged in export_day
+            if let Err((_, e)) = scheduled_export(&state).await {
+                warn!(error = %e, "export scheduler could not check the previous day");
+            }
+        }
+    });
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/exports — exporter config, last success and recent runs (admin)
+pub async fn status_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let runs = sqlx::query_as::<_, ExportRun>(
+        r#"
+        SELECT id, day, trigger, status, object_key, rows, bytes, error, started_at, finished_at
+        FROM export_runs ORDER BY started_at DESC LIMIT 50
+        "#,
+    )
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    let last_success = sqlx::query_as::<_, ExportRun>(
+        r#"
+        SELECT id, day, trigger, status, object_key, rows, bytes, error, started_at, finished_at
+        FROM export_runs WHERE status = 'success' ORDER BY finished_at DESC LIMIT 1
+        "#,
+    )
+    .fetch_optional(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(json!({
+        "configured": config().is_some(),
+        "bucket": config().map(|c| &c.bucket.name),
+        "path_template": config().map(|c| &c.path_template),
+        "last_success": last_success,
+        "runs": runs,
+    })))
+}
+
+/// POST /admin/exports/run — `{day?, include_quarantine?}` export a day now, defaults to the
+/// previous operational day (admin)
+pub async fn run_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    body: Option<Json<RunRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let cfg = config().ok_or((StatusCode::SERVICE_UNAVAILABLE, "Exports are not configured".to_string()))?;
+    let (day, include_quarantine) = body.map_or((None, false), |Json(b)| (b.day, b.include_quarantine));
+    let day = match day {
+        Some(d) => Date::parse(&d, format_description!("[year]-[month]-[day]"))
+            .map_err(|_| (StatusCode::BAD_REQUEST, "day must be YYYY-MM-DD".to_string()))?,
+        None => previous_day(&st.db).await.map_err(internal)?,
+    };
+    info!(%day, include_quarantine, user = %claims.username, "manual export requested");
+    Ok(Json(run_export(&st, cfg, day, "manual", include_quarantine).await?))
+}
@@ -0,0 +1,49 @@
+//! Sparse event lists for FOD Detection Backend
+//! `fields=id,lat,lon,class,severity` keeps only those keys in each row, for polling map clients
+
+use axum::http::StatusCode;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Keys of an event list row, including the lifecycle list's state columns
+const EVENT_FIELDS: &[&str] = &[
+    "id", "ts", "class_name", "object_count", "confidence", "latitude", "longitude", "source", "source_ref",
+    "severity", "frame_count", "state", "state_changed_at",
+];
+
+/// Short names map clients use
+const ALIASES: &[(&str, &str)] = &[("class", "class_name"), ("lat", "latitude"), ("lon", "longitude"), ("count", "object_count")];
+
+/// Requested row keys
+#[derive(Clone, Debug)]
+pub struct Fields(Vec<&'static str>);
+
+impl Fields {
+    /// `fields` from the query string; None when absent (every field)
+    pub fn from_query(q: &HashMap<String, String>) -> Result<Option<Fields>, (StatusCode, String)> {
+        let Some(list) = q.get("fields") else { return Ok(None) };
+        let mut fields = Vec::new();
+        for name in list.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let name = ALIASES.iter().find(|a| a.0 == name).map_or(name, |a| a.1);
+            let field = EVENT_FIELDS.iter().find(|f| **f == name).ok_or_else(|| {
+                (StatusCode::BAD_REQUEST, format!("Unknown field: {} (expected some of {})", name, EVENT_FIELDS.join(", ")))
+            })?;
+            if !fields.contains(field) {
+                fields.push(*field);
+            }
+        }
+        if fields.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "fields is empty".to_string()));
+        }
+        Ok(Some(Fields(fields)))
+    }
+
+    /// Drop every other key from a row object, or from each row of an array
+    pub fn retain(&self, value: &mut Value) {
+        match value {
+            Value::Array(rows) => rows.iter_mut().for_each(|r| self.retain(r)),
+            Value::Object(row) => row.retain(|k, _| self.0.contains(&k.as_str())),
+            _ => {}
+        }
+    }
+}
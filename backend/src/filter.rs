@@ -0,0 +1,381 @@
+//! Filter expressions for FOD Detection Backend
+//! `class in (metal,plastic) and confidence>0.7 and zone=apron_2` parsed against a whitelist
+//! of event fields and compiled to parameterized SQL over `events e`
+
+use sqlx::{Postgres, QueryBuilder};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Longest expression accepted, in characters
+const MAX_LENGTH: usize = 2000;
+/// Most conditions in one expression
+const MAX_CONDITIONS: usize = 32;
+/// Most values in one `in (...)` list
+const MAX_LIST: usize = 100;
+/// Deepest nesting of parentheses and `not`
+const MAX_DEPTH: usize = 16;
+
+// ==================== Fields ====================
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Kind {
+    Text,
+    /// Class name in any case, matching the class and its subclasses
+    Class,
+    Real,
+    Integer,
+    Time,
+}
+
+struct Field {
+    name: &'static str,
+    sql: &'static str,
+    kind: Kind,
+}
+
+const FIELDS: &[Field] = &[
+    Field { name: "class", sql: "e.class_id", kind: Kind::Class },
+    Field { name: "confidence", sql: "e.confidence", kind: Kind::Real },
+    Field { name: "object_count", sql: "e.object_count", kind: Kind::Integer },
+    Field { name: "latitude", sql: "e.latitude", kind: Kind::Real },
+    Field { name: "longitude", sql: "e.longitude", kind: Kind::Real },
+    Field { name: "source", sql: "e.source", kind: Kind::Text },
+    Field { name: "source_ref", sql: "e.source_ref", kind: Kind::Text },
+    Field { name: "state", sql: "e.state", kind: Kind::Text },
+    Field { name: "zone", sql: "e.zone", kind: Kind::Text },
+    Field { name: "ts", sql: "e.ts", kind: Kind::Time },
+];
+
+// ==================== Syntax ====================
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    In,
+    NotIn,
+}
+
+impl Op {
+    fn sql(self) -> &'static str {
+        match self {
+            Op::Eq => " = ",
+            Op::Ne => " IS DISTINCT FROM ",
+            Op::Gt => " > ",
+            Op::Ge => " >= ",
+            Op::Lt => " < ",
+            Op::Le => " <= ",
+            Op::In | Op::NotIn => " = ANY(",
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(Op),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' | ')' | ',' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::Open,
+                    ')' => Token::Close,
+                    _ => Token::Comma,
+                });
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let eq = chars.next_if_eq(&'=').is_some();
+                tokens.push(Token::Op(match (c, eq) {
+                    ('=', _) => Op::Eq,
+                    ('!', true) => Op::Ne,
+                    ('<', false) if chars.next_if_eq(&'>').is_some() => Op::Ne,
+                    ('<', false) => Op::Lt,
+                    ('<', true) => Op::Le,
+                    ('>', false) => Op::Gt,
+                    ('>', true) => Op::Ge,
+                    _ => return Err("expected != after !".to_string()),
+                }));
+            }
+            '\'' | '"' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err("unterminated string".to_string()),
+                        // A doubled quote stands for itself
+                        Some(q) if q == c && chars.next_if_eq(&c).is_some() => value.push(c),
+                        Some(q) if q == c => break,
+                        Some(ch) => value.push(ch),
+                    }
+                }
+                tokens.push(Token::Quoted(value));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace() && !"(),=!<>'\"".contains(*ch)) {
+                    word.push(ch);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// ==================== Expressions ====================
+
+#[derive(Clone, Debug)]
+enum Value {
+    Text(String),
+    Real(f32),
+    Integer(i64),
+    Time(OffsetDateTime),
+}
+
+#[derive(Clone, Debug)]
+enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Cond { sql: &'static str, kind: Kind, op: Op, values: Vec<Value> },
+}
+
+/// A parsed filter expression
+#[derive(Clone, Debug)]
+pub struct Filter(Expr);
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    conditions: usize,
+}
+
+fn is_keyword(t: Option<&Token>, kw: &str) -> bool {
+    matches!(t, Some(Token::Word(w)) if w.eq_ignore_ascii_case(kw))
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let t = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn expect(&mut self, want: Token, what: &str) -> Result<(), String> {
+        match self.next() {
+            Some(t) if t == want => Ok(()),
+            _ => Err(format!("expected {}", what)),
+        }
+    }
+
+    fn or(&mut self, depth: usize) -> Result<Expr, String> {
+        let mut terms = vec![self.and(depth)?];
+        while is_keyword(self.peek(), "or") {
+            self.pos += 1;
+            terms.push(self.and(depth)?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Expr::Or(terms) })
+    }
+
+    fn and(&mut self, depth: usize) -> Result<Expr, String> {
+        let mut terms = vec![self.unary(depth)?];
+        while is_keyword(self.peek(), "and") {
+            self.pos += 1;
+            terms.push(self.unary(depth)?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Expr::And(terms) })
+    }
+
+    fn unary(&mut self, depth: usize) -> Result<Expr, String> {
+        if depth > MAX_DEPTH {
+            return Err(format!("nested deeper than {}", MAX_DEPTH));
+        }
+        if is_keyword(self.peek(), "not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary(depth + 1)?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.pos += 1;
+            let inner = self.or(depth + 1)?;
+            self.expect(Token::Close, ")")?;
+            return Ok(inner);
+        }
+        self.condition()
+    }
+
+    fn condition(&mut self) -> Result<Expr, String> {
+        let name = match self.next() {
+            Some(Token::Word(w)) => w.to_lowercase(),
+            _ => return Err("expected a field name".to_string()),
+        };
+        let field = FIELDS.iter().find(|f| f.name == name).ok_or_else(|| {
+            let names: Vec<&str> = FIELDS.iter().map(|f| f.name).collect();
+            format!("unknown field {:?} (one of {})", name, names.join(", "))
+        })?;
+        self.conditions += 1;
+        if self.conditions > MAX_CONDITIONS {
+            return Err(format!("at most {} conditions", MAX_CONDITIONS));
+        }
+
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("in") => Op::In,
+            Some(Token::Word(w)) if w.eq_ignore_ascii_case("not") && is_keyword(self.peek(), "in") => {
+                self.pos += 1;
+                Op::NotIn
+            }
+            _ => return Err(format!("expected an operator after {}", field.name)),
+        };
+        if matches!(field.kind, Kind::Text | Kind::Class) && !matches!(op, Op::Eq | Op::Ne | Op::In | Op::NotIn) {
+            return Err(format!("{} only supports =, !=, in and not in", field.name));
+        }
+
+        let values = if matches!(op, Op::In | Op::NotIn) {
+            self.expect(Token::Open, "( after in")?;
+            let mut values = vec![self.value(field)?];
+            while self.peek() == Some(&Token::Comma) {
+                self.pos += 1;
+                values.push(self.value(field)?);
+            }
+            self.expect(Token::Close, ") closing the in list")?;
+            if values.len() > MAX_LIST {
+                return Err(format!("at most {} values in a list", MAX_LIST));
+            }
+            values
+        } else {
+            vec![self.value(field)?]
+        };
+        Ok(Expr::Cond { sql: field.sql, kind: field.kind, op, values })
+    }
+
+    fn value(&mut self, field: &Field) -> Result<Value, String> {
+        let raw = match self.next() {
+            Some(Token::Word(w)) | Some(Token::Quoted(w)) => w,
+            _ => return Err(format!("expected a value for {}", field.name)),
+        };
+        let invalid = |what: &str| format!("{} needs {}, got {:?}", field.name, what, raw);
+        Ok(match field.kind {
+            Kind::Text => Value::Text(raw),
+            Kind::Class => Value::Text(raw.to_lowercase()),
+            Kind::Real => Value::Real(raw.parse().ok().filter(|n: &f32| n.is_finite()).ok_or_else(|| invalid("a number"))?),
+            Kind::Integer => Value::Integer(raw.parse().map_err(|_| invalid("a whole number"))?),
+            Kind::Time => Value::Time(OffsetDateTime::parse(&raw, &Rfc3339).map_err(|_| invalid("an RFC 3339 timestamp"))?),
+        })
+    }
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Filter, String> {
+        if s.chars().count() > MAX_LENGTH {
+            return Err(format!("filter is longer than {} characters", MAX_LENGTH));
+        }
+        let mut p = Parser { tokens: tokenize(s)?, pos: 0, conditions: 0 };
+        if p.tokens.is_empty() {
+            return Err("filter is empty".to_string());
+        }
+        let expr = p.or(0)?;
+        if p.pos < p.tokens.len() {
+            return Err(format!("unexpected {:?}", p.tokens[p.pos]));
+        }
+        Ok(Filter(expr))
+    }
+
+    /// Append the condition to `qb`, binding every value
+    pub fn push_sql(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        self.0.push_sql(qb)
+    }
+}
+
+impl Expr {
+    fn push_sql(&self, qb: &mut QueryBuilder<'_, Postgres>) {
+        match self {
+            Expr::And(terms) | Expr::Or(terms) => {
+                let joiner = if matches!(self, Expr::And(_)) { " AND " } else { " OR " };
+                qb.push("(");
+                for (i, t) in terms.iter().enumerate() {
+                    if i > 0 {
+                        qb.push(joiner);
+                    }
+                    t.push_sql(qb);
+                }
+                qb.push(")");
+            }
+            Expr::Not(inner) => {
+                qb.push("NOT COALESCE(");
+                inner.push_sql(qb);
+                qb.push(", FALSE)");
+            }
+            Expr::Cond { sql, kind, op, values } => {
+                let negated = matches!((kind, op), (Kind::Class, Op::Ne) | (_, Op::NotIn));
+                if negated {
+                    qb.push("NOT COALESCE(");
+                }
+                if *kind == Kind::Class {
+                    // The class or any of its subclasses
+                    qb.push(
+                        "e.class_id IN (SELECT ca.class_id FROM class_ancestors ca \
+                         JOIN fod_classes p ON p.id = ca.ancestor_id WHERE LOWER(p.name) = ANY(",
+                    );
+                    push_list(qb, values);
+                    qb.push("))");
+                } else {
+                    qb.push(*sql);
+                    qb.push(op.sql());
+                    if matches!(op, Op::In | Op::NotIn) {
+                        push_list(qb, values);
+                        qb.push(")");
+                    } else {
+                        push_value(qb, &values[0]);
+                    }
+                }
+                if negated {
+                    qb.push(", FALSE)");
+                }
+            }
+        }
+    }
+}
+
+fn push_value(qb: &mut QueryBuilder<'_, Postgres>, v: &Value) {
+    match v.clone() {
+        Value::Text(s) => qb.push_bind(s),
+        Value::Real(n) => qb.push_bind(n),
+        Value::Integer(n) => qb.push_bind(n),
+        Value::Time(t) => qb.push_bind(t),
+    };
+}
+
+/// Bind `values` (all of one kind) as an array
+fn push_list(qb: &mut QueryBuilder<'_, Postgres>, values: &[Value]) {
+    match values.first() {
+        Some(Value::Real(_)) => qb.push_bind(values.iter().filter_map(|v| if let Value::Real(n) = v { Some(*n) } else { None }).collect::<Vec<_>>()),
+        Some(Value::Integer(_)) => {
+            qb.push_bind(values.iter().filter_map(|v| if let Value::Integer(n) = v { Some(*n) } else { None }).collect::<Vec<_>>())
+        }
+        Some(Value::Time(_)) => {
+            qb.push_bind(values.iter().filter_map(|v| if let Value::Time(t) = v { Some(*t) } else { None }).collect::<Vec<_>>())
+        }
+        _ => qb.push_bind(values.iter().filter_map(|v| if let Value::Text(s) = v { Some(s.clone()) } else { None }).collect::<Vec<_>>()),
+    };
+}
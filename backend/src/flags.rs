@@ -0,0 +1,219 @@
+//! Feature flags for FOD Detection Backend
+//! DB-backed switches for experimental behavior, optionally limited to some sites (`SITE_ID`)
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+use crate::{auth, db::internal, logging, AppState};
+
+/// Flags are reloaded at most this often; writes through the API reload at once
+const CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Use `AI_FALLBACK_URL` when the AI service is unreachable
+pub const AI_FALLBACK: &str = "ai_fallback";
+/// Lower alert severity for sources with many false positives
+pub const RELIABILITY_WEIGHTING: &str = "reliability_weighting";
+/// Weigh runway alerts by ADS-B traffic on approach
+pub const TRAFFIC_WEIGHTING: &str = "traffic_weighting";
+
+/// Flags the code checks: name, default without a row, description. Other names may be
+/// stored too, e.g. for the frontend, and default to off.
+const KNOWN: &[(&str, bool, &str)] = &[
+    (AI_FALLBACK, true, "Answer detections from AI_FALLBACK_URL while the AI service is unreachable"),
+    (RELIABILITY_WEIGHTING, false, "Alert one severity level lower for sources /dashboard/sources marks unreliable"),
+    (TRAFFIC_WEIGHTING, false, "Alert one severity level higher for runways with an aircraft on approach, hold low alerts while the runway is quiet"),
+];
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow, Clone)]
+pub struct Flag {
+    pub name: String,
+    pub enabled: bool,
+    /// Sites the flag applies to; None = every site
+    pub sites: Option<Vec<String>>,
+    pub description: Option<String>,
+    pub updated_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+impl Flag {
+    /// Whether the flag is on for this deployment's site
+    fn active(&self) -> bool {
+        self.enabled && self.sites.as_ref().is_none_or(|s| s.iter().any(|s| s == logging::site_id()))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct PutFlag {
+    pub enabled: bool,
+    pub sites: Option<Vec<String>>,
+    pub description: Option<String>,
+}
+
+/// A flag as listed: stored row if any, and its effect here
+#[derive(Serialize)]
+pub struct FlagStatus {
+    pub name: String,
+    /// On for this site
+    pub active: bool,
+    /// Whether the code checks this flag
+    pub known: bool,
+    pub default: bool,
+    pub description: Option<String>,
+    pub stored: Option<Flag>,
+}
+
+// ==================== Cache ====================
+
+#[derive(Default)]
+struct Cache {
+    loaded_at: Option<Instant>,
+    flags: Arc<HashMap<String, Flag>>,
+}
+
+/// This replica's flags, reloaded every `CACHE_TTL`
+#[derive(Clone, Default)]
+pub struct Flags(Arc<RwLock<Cache>>);
+
+async fn load(db: &PgPool) -> Result<HashMap<String, Flag>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, Flag>("SELECT name, enabled, sites, description, updated_by, updated_at FROM feature_flags")
+        .fetch_all(db)
+        .await?;
+    Ok(rows.into_iter().map(|f| (f.name.clone(), f)).collect())
+}
+
+impl Flags {
+    /// Current flags, reloading when stale. A failed reload keeps the old set.
+    async fn current(&self, db: &PgPool, force: bool) -> Arc<HashMap<String, Flag>> {
+        {
+            let c = self.0.read().unwrap();
+            if !force && c.loaded_at.is_some_and(|at| at.elapsed() < CACHE_TTL) {
+                return c.flags.clone();
+            }
+        }
+        let loaded = load(db).await;
+        let mut c = self.0.write().unwrap();
+        match loaded {
+            Ok(flags) => c.flags = Arc::new(flags),
+            Err(e) => warn!(error = %e, "failed to load feature flags"),
+        }
+        c.loaded_at = Some(Instant::now());
+        c.flags.clone()
+    }
+
+    /// Whether `name` is on for this site
+    pub async fn enabled(&self, db: &PgPool, name: &str) -> bool {
+        match self.current(db, false).await.get(name) {
+            Some(f) => f.active(),
+            None => default_of(name),
+        }
+    }
+
+    /// Names of every flag on for this site, sorted
+    pub async fn active(&self, db: &PgPool) -> Vec<String> {
+        let flags = self.current(db, false).await;
+        let mut names: Vec<String> = flags.values().filter(|f| f.active()).map(|f| f.name.clone()).collect();
+        names.extend(KNOWN.iter().filter(|k| k.1 && !flags.contains_key(k.0)).map(|k| k.0.to_string()));
+        names.sort_unstable();
+        names
+    }
+}
+
+fn default_of(name: &str) -> bool {
+    KNOWN.iter().find(|k| k.0 == name).is_some_and(|k| k.1)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= 64 && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+async fn statuses(st: &AppState, force: bool) -> Vec<FlagStatus> {
+    let flags = st.flags.current(&st.db, force).await;
+    let mut names: Vec<&str> = KNOWN.iter().map(|k| k.0).chain(flags.keys().map(String::as_str)).collect();
+    names.sort_unstable();
+    names.dedup();
+    names
+        .into_iter()
+        .map(|name| {
+            let known = KNOWN.iter().find(|k| k.0 == name);
+            let stored = flags.get(name).cloned();
+            FlagStatus {
+                name: name.to_string(),
+                active: stored.as_ref().map_or(default_of(name), Flag::active),
+                known: known.is_some(),
+                default: default_of(name),
+                description: stored.as_ref().and_then(|f| f.description.clone()).or_else(|| known.map(|k| k.2.to_string())),
+                stored,
+            }
+        })
+        .collect()
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/flags — every known or stored flag and whether it is on for this site (admin)
+pub async fn list_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    Ok(Json(statuses(&st, false).await))
+}
+
+/// PUT /admin/flags/:name — `{enabled, sites?, description?}` (admin)
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(body): Json<PutFlag>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    if !valid_name(&name) {
+        return Err((StatusCode::BAD_REQUEST, "Flag names are lowercase letters, digits and _".to_string()));
+    }
+    let sites = body.sites.map(|s| s.into_iter().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<_>>());
+    sqlx::query(
+        r#"
+        INSERT INTO feature_flags (name, enabled, sites, description, updated_by) VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (name) DO UPDATE SET enabled = EXCLUDED.enabled, sites = EXCLUDED.sites,
+            description = EXCLUDED.description, updated_by = EXCLUDED.updated_by, updated_at = NOW()
+        "#,
+    )
+    .bind(&name)
+    .bind(body.enabled)
+    .bind(&sites)
+    .bind(&body.description)
+    .bind(&claims.username)
+    .execute(&st.db)
+    .await
+    .map_err(internal)?;
+    info!(flag = %name, enabled = body.enabled, sites = ?sites, by = %claims.username, "feature flag set");
+    Ok(Json(statuses(&st, true).await))
+}
+
+/// DELETE /admin/flags/:name — drop the stored flag, back to its default (admin)
+pub async fn delete_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let done = sqlx::query("DELETE FROM feature_flags WHERE name = $1").bind(&name).execute(&st.db).await.map_err(internal)?;
+    if done.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Flag not found".to_string()));
+    }
+    info!(flag = %name, by = %claims.username, "feature flag reset to default");
+    Ok(Json(statuses(&st, true).await))
+}
@@ -0,0 +1,94 @@
+//! Ground geometry for FOD Detection Backend
+//! Local metre offsets and distances on the WGS84 surface and drone camera ray casting
+
+use std::{env, sync::OnceLock};
+
+/// Metres per degree of latitude
+pub const METRES_PER_DEGREE: f64 = 111_320.0;
+/// Mean earth radius
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+/// Rays flatter than this never meet the ground close enough to be useful
+const MIN_DEPRESSION_DEG: f64 = 2.0;
+
+// ==================== Config ====================
+
+/// Horizontal field of view of drone cameras, degrees (`DRONE_HFOV_DEG`, default 82)
+fn drone_hfov_deg() -> f64 {
+    static HFOV: OnceLock<f64> = OnceLock::new();
+    *HFOV.get_or_init(|| {
+        env::var("DRONE_HFOV_DEG").ok().and_then(|s| s.parse().ok()).filter(|&d: &f64| d > 0.0 && d < 180.0).unwrap_or(82.0)
+    })
+}
+
+// ==================== Offsets and distances ====================
+
+/// Point `east_m` / `north_m` metres from (lat, lon); flat-earth, fine for the few hundred
+/// metres a camera sees
+pub fn offset(lat: f64, lon: f64, east_m: f64, north_m: f64) -> Option<(f64, f64)> {
+    let lat2 = lat + north_m / METRES_PER_DEGREE;
+    let lon2 = lon + east_m / (METRES_PER_DEGREE * lat.to_radians().cos());
+    ((-90.0..=90.0).contains(&lat2) && (-180.0..=180.0).contains(&lon2)).then_some((lat2, lon2))
+}
+
+/// Great-circle distance in metres
+pub fn distance_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (p1, p2) = (lat1.to_radians(), lat2.to_radians());
+    let (dp, dl) = ((lat2 - lat1).to_radians(), (lon2 - lon1).to_radians());
+    let a = (dp / 2.0).sin().powi(2) + p1.cos() * p2.cos() * (dl / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Initial bearing from the first point to the second, degrees clockwise from north
+pub fn bearing_deg(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (p1, p2, dl) = (lat1.to_radians(), lat2.to_radians(), (lon2 - lon1).to_radians());
+    let y = dl.sin() * p2.cos();
+    let x = p1.cos() * p2.sin() - p1.sin() * p2.cos() * dl.cos();
+    y.atan2(x).to_degrees().rem_euclid(360.0)
+}
+
+/// Smallest angle between two headings, degrees
+pub fn heading_diff(a: f64, b: f64) -> f64 {
+    let d = (a - b).rem_euclid(360.0);
+    d.min(360.0 - d)
+}
+
+// ==================== Drone cameras ====================
+
+/// Where a drone camera is pointing when it took the frame
+pub struct DronePose {
+    pub lat: f64,
+    pub lon: f64,
+    /// Height above the ground, metres
+    pub altitude_m: f64,
+    /// Heading of the camera axis, degrees clockwise from true north
+    pub yaw_deg: f64,
+    /// Gimbal angle below the horizon, degrees; 90 looks straight down
+    pub pitch_deg: f64,
+}
+
+/// Ground position of the image point (`nx`, `ny`), normalized with the origin top left, seen by
+/// a drone at `pose` through an image of `aspect` = height / width. None when the ray runs
+/// (nearly) parallel to the ground.
+pub fn drone_target(pose: &DronePose, nx: f64, ny: f64, aspect: f64) -> Option<(f64, f64)> {
+    if !pose.altitude_m.is_finite() || pose.altitude_m <= 0.0 {
+        return None;
+    }
+    let half_h = (drone_hfov_deg() / 2.0).to_radians().tan();
+    let half_v = half_h * aspect;
+    // Angles off the optical axis: right of it, and below it
+    let right = ((nx - 0.5) * 2.0 * half_h).atan();
+    let down = ((ny - 0.5) * 2.0 * half_v).atan();
+
+    let depression = pose.pitch_deg.to_radians() + down;
+    if depression < MIN_DEPRESSION_DEG.to_radians() || depression > (180.0 - MIN_DEPRESSION_DEG).to_radians() {
+        return None;
+    }
+    // Past 90° the ray points behind the drone and `forward` goes negative
+    let forward = pose.altitude_m / depression.tan();
+    let lateral = pose.altitude_m / depression.sin() * right.tan();
+
+    let yaw = pose.yaw_deg.to_radians();
+    let east = forward * yaw.sin() + lateral * yaw.cos();
+    let north = forward * yaw.cos() - lateral * yaw.sin();
+    offset(pose.lat, pose.lon, east, north)
+}
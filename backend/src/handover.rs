@@ -0,0 +1,334 @@
+//! Shift handover for FOD Detection Backend
+//! Open events, alerts raised and resolved, silent sources and trends of one shift, as JSON or a printable page
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use minijinja::Environment;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool};
+use std::{collections::BTreeMap, env, sync::OnceLock};
+use time::{format_description::well_known::Rfc3339, macros::format_description, Date, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    auth, calendar,
+    db::{internal, quarantine_clause, SEVERITY_COLUMN},
+    lifecycle::CLOSED_STATES,
+    perf, site,
+    status::Level,
+    AppState,
+};
+
+/// Open events listed in full; the count covers the rest
+const MAX_OPEN_EVENTS: i64 = 200;
+/// Sources heard from within this many days before the shift but not during it are silent
+const SILENT_LOOKBACK_DAYS: i32 = 7;
+/// Classes listed under trends
+const MAX_TRENDS: usize = 10;
+
+const HTML: &str = r#"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>{{ site }} handover {{ shift.name }} {{ shift.from }}</title>
+<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:1.5em}td,th{border:1px solid #999;padding:4px 8px;text-align:left}</style></head>
+<body>
+<h1>{{ site }} — {{ shift.name }} shift handover</h1>
+<p>{{ shift.from }} to {{ shift.to }} ({{ shift.timezone }}){% if shift.in_progress %}, in progress{% endif %}</p>
+<h2>Alerts</h2>
+<p>Raised: {{ alerts.raised }}. Resolved: {{ alerts.verified_clear }} cleared, {{ alerts.false_positive }} false positives.</p>
+{% if alerts.by_class %}<table><tr><th>Class</th><th>Raised</th></tr>{% for c in alerts.by_class %}<tr><td>{{ c.class }}</td><td>{{ c.events }}</td></tr>{% endfor %}</table>{% endif %}
+<h2>Open events ({{ open_events.total }})</h2>
+{% if open_events.events %}<table><tr><th>Detected</th><th>Class</th><th>State</th><th>Runway</th><th>Severity</th><th>Source</th></tr>
+{% for e in open_events.events %}<tr><td>{{ e.ts }}</td><td>{{ e.class_name }}</td><td>{{ e.state }}</td><td>{{ e.runway or "" }}</td><td>{{ e.severity or "" }}</td><td>{{ e.source_ref }}</td></tr>
+{% endfor %}</table>{% else %}<p>None.</p>{% endif %}
+<h2>Silent sources</h2>
+{% if silent_sources %}<table><tr><th>Source</th><th>Last event</th></tr>{% for s in silent_sources %}<tr><td>{{ s.source_ref }}</td><td>{{ s.last_seen }}</td></tr>{% endfor %}</table>{% else %}<p>None.</p>{% endif %}
+{% if dependencies %}<h2>Services</h2><table><tr><th>Service</th><th>Status</th><th>Detail</th></tr>{% for name, d in dependencies|items %}<tr><td>{{ name }}</td><td>{{ d.status }}</td><td>{{ d.detail or "" }}</td></tr>{% endfor %}</table>{% endif %}
+<h2>Trends against the previous {{ shift.name }} shift</h2>
+{% if trends %}<table><tr><th>Class</th><th>This shift</th><th>Previous</th><th>Change</th></tr>{% for t in trends %}<tr><td>{{ t.class }}</td><td>{{ t.events }}</td><td>{{ t.previous }}</td><td>{{ "%+d"|format(t.change) }}</td></tr>{% endfor %}</table>{% else %}<p>No change.</p>{% endif %}
+</body></html>
+"#;
+
+// ==================== Config ====================
+
+/// A named shift in the site timezone; ends before it starts when it runs past midnight
+struct Shift {
+    name: String,
+    /// Minutes after local midnight
+    start: i32,
+    /// Length in minutes
+    minutes: i32,
+}
+
+fn parse_time(s: &str) -> Option<i32> {
+    let (h, m) = s.trim().split_once(':')?;
+    let (h, m): (i32, i32) = (h.parse().ok()?, m.parse().ok()?);
+    ((0..24).contains(&h) && (0..60).contains(&m)).then_some(h * 60 + m)
+}
+
+fn parse_shift(entry: &str) -> Option<Shift> {
+    let (name, times) = entry.split_once('=')?;
+    let (start, end) = times.split_once('-')?;
+    let (start, end) = (parse_time(start)?, parse_time(end)?);
+    let minutes = match (end - start).rem_euclid(1440) {
+        0 => 1440,
+        m => m,
+    };
+    Some(Shift { name: name.trim().to_lowercase(), start, minutes })
+}
+
+/// `SITE_SHIFTS=day=06:00-18:00,night=18:00-06:00` (the default)
+fn shifts() -> &'static [Shift] {
+    static SHIFTS: OnceLock<Vec<Shift>> = OnceLock::new();
+    SHIFTS.get_or_init(|| {
+        let spec = env::var("SITE_SHIFTS").ok().filter(|s| !s.trim().is_empty()).unwrap_or_else(|| "day=06:00-18:00,night=18:00-06:00".to_string());
+        spec.split(',')
+            .map(|e| parse_shift(e).unwrap_or_else(|| panic!("SITE_SHIFTS entries must be name=HH:MM-HH:MM, got {:?}", e)))
+            .collect()
+    })
+}
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct HandoverParams {
+    pub shift: String,
+    /// Local date the shift started, default its latest start
+    pub date: Option<String>,
+    /// `json` (default, or per `Accept`) or `html`
+    pub format: Option<String>,
+    /// Count quarantined sources too
+    #[serde(default)]
+    pub include_quarantine: bool,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct OpenEvent {
+    pub id: Uuid,
+    #[serde(with = "time::serde::rfc3339")]
+    pub ts: OffsetDateTime,
+    pub class_name: String,
+    pub state: String,
+    pub source_ref: String,
+    pub severity: Option<String>,
+    pub runway: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ClassCount {
+    pub class: String,
+    pub events: i64,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct SilentSource {
+    pub source_ref: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_seen: OffsetDateTime,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Trend {
+    pub class: String,
+    pub events: i64,
+    pub previous: i64,
+    pub change: i64,
+}
+
+#[derive(FromRow)]
+struct Window {
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+}
+
+// ==================== Queries ====================
+
+/// Start and end of the shift starting on local `date`, or of its latest start at or before now
+async fn window(db: &PgPool, shift: &Shift, date: Option<Date>, tz: &str) -> Result<Window, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, Window>(
+        r#"
+        WITH s AS (
+            SELECT COALESCE(
+                $1::DATE::TIMESTAMP,
+                date_trunc('day', NOW() AT TIME ZONE $4 - make_interval(mins => $2))
+            ) + make_interval(mins => $2) AS local_start
+        )
+        SELECT local_start AT TIME ZONE $4 AS from, (local_start + make_interval(mins => $3)) AT TIME ZONE $4 AS to
+        FROM s
+        "#,
+    )
+    .bind(date)
+    .bind(shift.start)
+    .bind(shift.minutes)
+    .bind(tz)
+    .fetch_one(db);
+    perf::timed("handover_window", || format!("shift={} date={:?}", shift.name, date), q).await.map_err(internal)
+}
+
+async fn open_events(db: &PgPool, include_quarantine: bool) -> Result<(i64, Vec<OpenEvent>), (StatusCode, String)> {
+    let quarantine = quarantine_clause("e", include_quarantine);
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM events e WHERE state <> ALL($1){}", quarantine))
+        .bind(&CLOSED_STATES[..])
+        .fetch_one(db)
+        .await
+        .map_err(internal)?;
+    let sql = format!(
+        r#"
+        SELECT e.id, e.ts, fc.name AS class_name, e.state, e.source_ref, {},
+               UPPER(e.runway) AS runway
+        FROM events e
+        JOIN fod_classes fc ON fc.id = e.class_id
+        WHERE e.state <> ALL($1){}
+        ORDER BY e.ts
+        LIMIT $2
+        "#,
+        SEVERITY_COLUMN,
+        quarantine
+    );
+    let q = sqlx::query_as::<_, OpenEvent>(&sql).bind(&CLOSED_STATES[..]).bind(MAX_OPEN_EVENTS).fetch_all(db);
+    Ok((total, perf::timed("handover_open_events", String::new, q).await.map_err(internal)?))
+}
+
+async fn raised_by_class(db: &PgPool, w: &Window, include_quarantine: bool) -> Result<Vec<ClassCount>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        SELECT fc.name AS class, COUNT(*) AS events
+        FROM events e JOIN fod_classes fc ON fc.id = e.class_id
+        WHERE e.ts >= $1 AND e.ts < $2{}
+        GROUP BY fc.name
+        ORDER BY events DESC, fc.name
+        "#,
+        quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, ClassCount>(&sql).bind(w.from).bind(w.to).fetch_all(db);
+    perf::timed("handover_raised", || format!("from={} to={}", w.from, w.to), q).await.map_err(internal)
+}
+
+/// Events closed during the window per closing state
+async fn resolved(db: &PgPool, w: &Window, include_quarantine: bool) -> Result<Vec<(String, i64)>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        SELECT t.to_state, COUNT(DISTINCT t.event_id)
+        FROM event_state_transitions t
+        JOIN events e ON e.id = t.event_id
+        WHERE t.to_state = ANY($3) AND t.created_at >= $1 AND t.created_at < $2{}
+        GROUP BY t.to_state
+        "#,
+        quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, (String, i64)>(&sql).bind(w.from).bind(w.to).bind(&CLOSED_STATES[..]).fetch_all(db);
+    perf::timed("handover_resolved", || format!("from={} to={}", w.from, w.to), q).await.map_err(internal)
+}
+
+async fn silent_sources(db: &PgPool, w: &Window, include_quarantine: bool) -> Result<Vec<SilentSource>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        SELECT e.source_ref, MAX(e.ts) AS last_seen
+        FROM events e
+        WHERE e.ts >= $1 - make_interval(days => $3) AND e.ts < $2{}
+        GROUP BY e.source_ref
+        HAVING MAX(e.ts) < $1
+        ORDER BY last_seen
+        "#,
+        quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, SilentSource>(&sql).bind(w.from).bind(w.to).bind(SILENT_LOOKBACK_DAYS).fetch_all(db);
+    perf::timed("handover_silent_sources", || format!("from={} to={}", w.from, w.to), q).await.map_err(internal)
+}
+
+/// Per-class change against the same shift a day earlier, largest first
+async fn trends(db: &PgPool, w: &Window, include_quarantine: bool) -> Result<Vec<Trend>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        WITH c AS (
+            SELECT fc.name AS class, COUNT(*) FILTER (WHERE e.ts >= $1) AS events, COUNT(*) FILTER (WHERE e.ts < $1) AS previous
+            FROM events e JOIN fod_classes fc ON fc.id = e.class_id
+            WHERE ((e.ts >= $1 AND e.ts < $2) OR (e.ts >= $1 - INTERVAL '1 day' AND e.ts < LEAST($2 - INTERVAL '1 day', $1))){}
+            GROUP BY fc.name
+        )
+        SELECT class, events, previous, events - previous AS change
+        FROM c
+        WHERE events <> previous
+        ORDER BY ABS(events - previous) DESC, class
+        LIMIT $3
+        "#,
+        quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, Trend>(&sql).bind(w.from).bind(w.to).bind(MAX_TRENDS as i64).fetch_all(db);
+    perf::timed("handover_trends", || format!("from={} to={}", w.from, w.to), q).await.map_err(internal)
+}
+
+// ==================== Handlers ====================
+
+/// HTML via `format=html` or an `Accept` preferring `text/html`, JSON otherwise
+fn wants_html(format: Option<&str>, headers: &HeaderMap) -> Result<bool, (StatusCode, String)> {
+    match format {
+        Some("html") => Ok(true),
+        Some("json") => Ok(false),
+        Some(_) => Err((StatusCode::BAD_REQUEST, "format must be json or html".to_string())),
+        None => Ok(headers.get(header::ACCEPT).and_then(|v| v.to_str().ok()).is_some_and(|a| a.starts_with("text/html"))),
+    }
+}
+
+/// GET /reports/handover?shift=night&date=&format=json|html — open events, alerts raised and
+/// resolved, silent sources, degraded services and trends of one shift (login required)
+pub async fn handover_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<HandoverParams>,
+) -> Result<Response, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let html = wants_html(p.format.as_deref(), &headers)?;
+    let name = p.shift.trim().to_lowercase();
+    let shift = shifts().iter().find(|s| s.name == name).ok_or_else(|| {
+        let names: Vec<&str> = shifts().iter().map(|s| s.name.as_str()).collect();
+        (StatusCode::BAD_REQUEST, format!("Unknown shift: {} (expected one of {})", name, names.join(", ")))
+    })?;
+    let date = p
+        .date
+        .as_deref()
+        .map(|d| Date::parse(d.trim(), format_description!("[year]-[month]-[day]")))
+        .transpose()
+        .map_err(|_| (StatusCode::BAD_REQUEST, "date must be YYYY-MM-DD".to_string()))?;
+    let tz = &calendar::site().tz;
+    let mut w = window(&st.db, shift, date, tz).await?;
+    let now = OffsetDateTime::now_utc();
+    if w.from > now {
+        return Err((StatusCode::BAD_REQUEST, "That shift has not started yet".to_string()));
+    }
+    let in_progress = w.to > now;
+    w.to = w.to.min(now);
+
+    let include = p.include_quarantine;
+    let (open_total, open) = open_events(&st.db, include).await?;
+    let by_class = raised_by_class(&st.db, &w, include).await?;
+    let closed = resolved(&st.db, &w, include).await?;
+    let count_of = |state: &str| closed.iter().find(|(s, _)| s == state).map(|(_, n)| *n).unwrap_or(0);
+    let dependencies: BTreeMap<_, _> = st.status.snapshot().into_iter().filter(|(_, d)| d.status != Level::Nominal).collect();
+    let rfc3339 = |t: OffsetDateTime| t.format(&Rfc3339).unwrap_or_default();
+
+    let summary = json!({
+        "site": site::name(),
+        "shift": { "name": shift.name, "from": rfc3339(w.from), "to": rfc3339(w.to), "timezone": tz, "in_progress": in_progress },
+        "open_events": { "total": open_total, "events": open },
+        "alerts": {
+            "raised": by_class.iter().map(|c| c.events).sum::<i64>(),
+            "by_class": by_class,
+            "verified_clear": count_of("verified_clear"),
+            "false_positive": count_of("false_positive"),
+        },
+        "silent_sources": silent_sources(&st.db, &w, include).await?,
+        "dependencies": dependencies,
+        "trends": trends(&st.db, &w, include).await?,
+    });
+    if !html {
+        return Ok(Json(summary).into_response());
+    }
+    let mut env = Environment::new();
+    env.add_template("handover.html", HTML).map_err(internal)?;
+    let page = env.get_template("handover.html").and_then(|t| t.render(&summary)).map_err(internal)?;
+    Ok(Html(page).into_response())
+}
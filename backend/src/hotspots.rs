@@ -0,0 +1,178 @@
+//! Hotspot analysis for FOD Detection Backend
+//! Grid clustering of event locations with dominant classes and trend per cell
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{FromRow, PgPool};
+use time::{Duration, OffsetDateTime};
+
+use crate::{db::{internal, quarantine_clause}, geometry::METRES_PER_DEGREE, perf, AppState};
+
+/// Second half of the window must differ from the first by this factor to count as a trend
+const TREND_RATIO: f64 = 1.25;
+
+// ==================== Types ====================
+
+#[derive(Deserialize)]
+pub struct HotspotParams {
+    /// `<n>h`, `<n>d` or `<n>w`, default 30d
+    pub window: Option<String>,
+    /// Grid cell edge in metres (default 50)
+    pub cell_m: Option<f64>,
+    /// Cells with fewer events are not hotspots (default 3)
+    pub min_events: Option<i64>,
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub include_quarantine: bool,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Hotspot {
+    /// Centroid of the cell's events
+    pub latitude: f64,
+    pub longitude: f64,
+    pub events: i64,
+    pub objects: i64,
+    /// Up to three most frequent classes: [{"class", "count"}]
+    pub dominant_classes: Value,
+    /// Events in the first and second half of the window
+    pub first_half: i64,
+    pub second_half: i64,
+    /// `rising`, `falling` or `stable`
+    #[sqlx(skip)]
+    pub trend: &'static str,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_seen: OffsetDateTime,
+}
+
+/// `30d` / `12h` / `8w` to a duration
+fn parse_window(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let unit = s.chars().last()?;
+    let n: i64 = s[..s.len() - unit.len_utf8()].parse().ok().filter(|&n| n > 0 && n <= 10_000)?;
+    match unit {
+        'h' => Some(Duration::hours(n)),
+        'd' => Some(Duration::days(n)),
+        'w' => Some(Duration::weeks(n)),
+        _ => None,
+    }
+}
+
+fn trend(first: i64, second: i64) -> &'static str {
+    let (first, second) = (first as f64, second as f64);
+    if second > first * TREND_RATIO && second - first >= 2.0 {
+        "rising"
+    } else if first > second * TREND_RATIO && first - second >= 2.0 {
+        "falling"
+    } else {
+        "stable"
+    }
+}
+
+// ==================== Query ====================
+
+/// Events bucketed into roughly square cells of `cell_m` (longitude scaled by cos(latitude));
+/// events without a position (0,0) are left out
+pub async fn hotspots(
+    db: &PgPool,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    cell_m: f64,
+    min_events: i64,
+    limit: i64,
+    include_quarantine: bool,
+) -> Result<Vec<Hotspot>, (StatusCode, String)> {
+    let mid = from + (to - from) / 2;
+    let sql = format!(
+        r#"
+        WITH ev AS (
+            SELECT e.ts, e.class_id, e.object_count, e.latitude, e.longitude,
+                   FLOOR(e.latitude / $1)::BIGINT AS gy,
+                   FLOOR(e.longitude * COS(RADIANS(e.latitude)) / $1)::BIGINT AS gx
+            FROM events e
+            WHERE e.ts >= $2 AND e.ts < $3
+              AND NOT (e.latitude = 0 AND e.longitude = 0){}
+        ),
+        cells AS (
+            SELECT gy, gx,
+                   AVG(latitude)::FLOAT8 AS latitude, AVG(longitude)::FLOAT8 AS longitude,
+                   COUNT(*) AS events, SUM(object_count)::BIGINT AS objects,
+                   COUNT(*) FILTER (WHERE ts < $4) AS first_half,
+                   COUNT(*) FILTER (WHERE ts >= $4) AS second_half,
+                   MAX(ts) AS last_seen
+            FROM ev
+            GROUP BY gy, gx
+            HAVING COUNT(*) >= $5
+            ORDER BY events DESC
+            LIMIT $6
+        ),
+        classes AS (
+            SELECT ev.gy, ev.gx, fc.name, COUNT(*) AS n,
+                   ROW_NUMBER() OVER (PARTITION BY ev.gy, ev.gx ORDER BY COUNT(*) DESC, fc.name) AS rank
+            FROM ev
+            JOIN cells c ON c.gy = ev.gy AND c.gx = ev.gx
+            JOIN fod_classes fc ON fc.id = ev.class_id
+            GROUP BY ev.gy, ev.gx, fc.name
+        )
+        SELECT c.latitude, c.longitude, c.events, c.objects, c.first_half, c.second_half, c.last_seen,
+               (SELECT json_agg(json_build_object('class', k.name, 'count', k.n) ORDER BY k.rank)
+                FROM classes k WHERE k.gy = c.gy AND k.gx = c.gx AND k.rank <= 3) AS dominant_classes
+        FROM cells c
+        ORDER BY c.events DESC, c.last_seen DESC
+        "#,
+        quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, Hotspot>(&sql)
+        .bind(cell_m / METRES_PER_DEGREE)
+        .bind(from)
+        .bind(to)
+        .bind(mid)
+        .bind(min_events)
+        .bind(limit)
+        .fetch_all(db);
+    let mut rows = perf::timed("hotspots", || format!("from={} to={} cell_m={}", from, to, cell_m), q)
+        .await
+        .map_err(internal)?;
+    for h in &mut rows {
+        h.trend = trend(h.first_half, h.second_half);
+    }
+    Ok(rows)
+}
+
+// ==================== Handlers ====================
+
+/// GET /dashboard/hotspots?window=30d&cell_m=50&min_events=3&limit=50 — recurring FOD locations
+pub async fn hotspots_handler(
+    State(st): State<AppState>,
+    Query(p): Query<HotspotParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let window = match p.window.as_deref() {
+        None => Duration::days(30),
+        Some(s) => parse_window(s).ok_or((StatusCode::BAD_REQUEST, "window must look like 12h, 30d or 8w".to_string()))?,
+    };
+    if window > Duration::days(366) {
+        return Err((StatusCode::BAD_REQUEST, "window must be at most a year".to_string()));
+    }
+    let cell_m = p.cell_m.unwrap_or(50.0);
+    if !(5.0..=5000.0).contains(&cell_m) {
+        return Err((StatusCode::BAD_REQUEST, "cell_m must be between 5 and 5000".to_string()));
+    }
+    let min_events = p.min_events.unwrap_or(3).max(1);
+    let limit = p.limit.unwrap_or(50).clamp(1, 500);
+
+    let to = OffsetDateTime::now_utc();
+    let from = to - window;
+    let spots = hotspots(&st.db, from, to, cell_m, min_events, limit, p.include_quarantine).await?;
+    Ok(Json(json!({
+        "from": from.format(&time::format_description::well_known::Rfc3339).map_err(internal)?,
+        "to": to.format(&time::format_description::well_known::Rfc3339).map_err(internal)?,
+        "cell_m": cell_m,
+        "hotspots": spots,
+    })))
+}
@@ -0,0 +1,295 @@
+//! Class display names for FOD Detection Backend
+//! `class_translations` lookups and an Accept-Language aware layer over JSON responses
+
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::HashMap,
+    sync::{Arc, OnceLock, RwLock},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+use crate::{auth, db::internal, AppState};
+
+/// Translations are reloaded at most this often; writes through the API reload at once
+const CACHE_TTL: Duration = Duration::from_secs(60);
+/// Buffered bodies larger than this are passed through untranslated
+const MAX_LOCALIZED_BODY: usize = 16 * 1024 * 1024;
+/// Language of fod_classes.name
+const CANONICAL_LANG: &str = "en";
+/// JSON keys holding a canonical class name
+const CLASS_KEYS: [&str; 3] = ["class_name", "top_fod", "class"];
+
+// ==================== Cache ====================
+
+/// canonical name → lang → display name
+type Translations = HashMap<String, HashMap<String, String>>;
+
+struct Cache {
+    loaded_at: Option<Instant>,
+    names: Arc<Translations>,
+}
+
+fn cache() -> &'static RwLock<Cache> {
+    static CACHE: OnceLock<RwLock<Cache>> = OnceLock::new();
+    CACHE.get_or_init(|| RwLock::new(Cache { loaded_at: None, names: Default::default() }))
+}
+
+#[derive(Serialize, FromRow)]
+pub struct ClassTranslation {
+    /// Not `class_name`, which the localization layer would rewrite
+    pub canonical_name: String,
+    pub lang: String,
+    pub display_name: String,
+}
+
+async fn load(db: &PgPool) -> Result<Translations, sqlx::Error> {
+    let rows = sqlx::query_as::<_, ClassTranslation>(
+        r#"
+        SELECT fc.name AS canonical_name, t.lang, t.display_name
+        FROM class_translations t
+        JOIN fod_classes fc ON fc.id = t.class_id
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+    let mut names: Translations = HashMap::new();
+    for r in rows {
+        names.entry(r.canonical_name).or_default().insert(r.lang, r.display_name);
+    }
+    Ok(names)
+}
+
+/// Current translations, reloading when stale. A failed reload keeps serving the old set.
+async fn translations(db: &PgPool, force: bool) -> Arc<Translations> {
+    {
+        let c = cache().read().unwrap();
+        if !force && c.loaded_at.is_some_and(|at| at.elapsed() < CACHE_TTL) {
+            return c.names.clone();
+        }
+    }
+    match load(db).await {
+        Ok(names) => {
+            let mut c = cache().write().unwrap();
+            c.names = Arc::new(names);
+            c.loaded_at = Some(Instant::now());
+            c.names.clone()
+        }
+        Err(e) => {
+            warn!(error = %e, "failed to load class translations");
+            let mut c = cache().write().unwrap();
+            // Don't retry on every request while the database is unhappy
+            c.loaded_at = Some(Instant::now());
+            c.names.clone()
+        }
+    }
+}
+
+// ==================== Localization ====================
+
+/// Primary language subtags from Accept-Language, most preferred first; `*` and q=0 are dropped
+pub fn preferred_languages(headers: &HeaderMap) -> Vec<String> {
+    let Some(raw) = headers.get(header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok()) else {
+        return Vec::new();
+    };
+    let mut langs: Vec<(f32, usize, String)> = raw
+        .split(',')
+        .enumerate()
+        .filter_map(|(i, part)| {
+            let mut it = part.split(';');
+            let tag = it.next()?.trim();
+            let q = it
+                .find_map(|p| p.trim().strip_prefix("q="))
+                .map(|q| q.trim().parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            let primary = tag.split('-').next()?.trim().to_lowercase();
+            (q > 0.0 && !primary.is_empty() && primary != "*").then_some((q, i, primary))
+        })
+        .collect();
+    langs.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    let mut out: Vec<String> = Vec::new();
+    for (_, _, l) in langs {
+        if !out.contains(&l) {
+            out.push(l);
+        }
+    }
+    out
+}
+
+/// Display name in the first preferred language that has one; None means keep the canonical
+/// name, also when the canonical language comes first
+pub fn display_name<'a>(names: &'a Translations, class: &str, langs: &[String]) -> Option<&'a String> {
+    let by_lang = names.get(class)?;
+    for l in langs {
+        if let Some(name) = by_lang.get(l) {
+            return Some(name);
+        }
+        if l == CANONICAL_LANG {
+            return None;
+        }
+    }
+    None
+}
+
+fn localize_value(v: &mut Value, names: &Translations, langs: &[String]) {
+    match v {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                match v {
+                    Value::String(s) if CLASS_KEYS.contains(&k.as_str()) => {
+                        if let Some(shown) = display_name(names, s, langs) {
+                            *s = shown.clone();
+                        }
+                    }
+                    _ => localize_value(v, names, langs),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|v| localize_value(v, names, langs)),
+        _ => {}
+    }
+}
+
+/// Translations narrowed to one request's preferred languages
+#[derive(Clone)]
+pub struct Locale {
+    names: Arc<Translations>,
+    langs: Vec<String>,
+}
+
+impl Locale {
+    pub fn apply(&self, v: &mut Value) {
+        localize_value(v, &self.names, &self.langs);
+    }
+}
+
+tokio::task_local! {
+    static LOCALE: Option<Locale>;
+}
+
+/// Locale of the request being handled, for bodies serialized after the handler
+/// returns (see `stream_rows`); None outside `localize` or without a usable language
+pub fn current() -> Option<Locale> {
+    LOCALE.try_with(|l| l.clone()).ok().flatten()
+}
+
+/// Replace class names in JSON responses when the client asks for a language we have.
+/// Sized bodies are rewritten here; streamed rows pick up `current()` when the stream is built.
+pub async fn localize(State(st): State<AppState>, req: Request, next: Next) -> Response {
+    let langs = preferred_languages(req.headers());
+    let locale = if langs.is_empty() {
+        None
+    } else {
+        let names = translations(&st.db, false).await;
+        names
+            .values()
+            .any(|by_lang| langs.iter().any(|l| by_lang.contains_key(l)))
+            .then_some(Locale { names, langs })
+    };
+    let resp = LOCALE.scope(locale.clone(), next.run(req)).await;
+    let Some(locale) = locale else {
+        return resp;
+    };
+    let is_json = resp
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    let sized = resp.body().size_hint().exact().is_some_and(|n| n as usize <= MAX_LOCALIZED_BODY);
+    if !is_json || !sized {
+        return resp;
+    }
+
+    let (mut parts, body) = resp.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_LOCALIZED_BODY).await else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read response body".to_string()).into_response();
+    };
+    let Ok(mut value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    locale.apply(&mut value);
+    let out = serde_json::to_vec(&value).unwrap_or_else(|_| bytes.to_vec());
+    parts.headers.insert(header::CONTENT_LENGTH, HeaderValue::from(out.len()));
+    parts.headers.append(header::VARY, HeaderValue::from_static("accept-language"));
+    Response::from_parts(parts, Body::from(out))
+}
+
+// ==================== Handlers ====================
+
+/// GET /classes/translations — every class display name by language
+pub async fn list_handler(State(st): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let rows = sqlx::query_as::<_, ClassTranslation>(
+        r#"
+        SELECT fc.name AS canonical_name, t.lang, t.display_name
+        FROM class_translations t
+        JOIN fod_classes fc ON fc.id = t.class_id
+        ORDER BY fc.name, t.lang
+        "#,
+    )
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(rows))
+}
+
+/// PUT /admin/classes/:name/translations — set display names, e.g. {"th": "น็อต"};
+/// an empty string removes that language (admin)
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(body): Json<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let class_id: i32 = sqlx::query_scalar("SELECT id FROM fod_classes WHERE name = $1")
+        .bind(&name)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown class: {}", name)))?;
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    for (lang, display) in &body {
+        let lang = lang.trim().to_lowercase();
+        if lang.is_empty() || lang.len() > 8 || !lang.chars().all(|c| c.is_ascii_lowercase()) {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid language tag: {:?}", lang)));
+        }
+        let display = display.trim();
+        if display.is_empty() {
+            sqlx::query("DELETE FROM class_translations WHERE class_id = $1 AND lang = $2")
+                .bind(class_id)
+                .bind(&lang)
+                .execute(&mut *tx)
+                .await
+                .map_err(internal)?;
+        } else {
+            sqlx::query(
+                r#"
+                INSERT INTO class_translations (class_id, lang, display_name) VALUES ($1, $2, $3)
+                ON CONFLICT (class_id, lang) DO UPDATE SET display_name = EXCLUDED.display_name, updated_at = NOW()
+                "#,
+            )
+            .bind(class_id)
+            .bind(&lang)
+            .bind(display)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        }
+    }
+    tx.commit().await.map_err(internal)?;
+    info!(class = %name, langs = ?body.keys().collect::<Vec<_>>(), by = %claims.username, "class translations updated");
+
+    let names = translations(&st.db, true).await;
+    Ok(Json(names.get(&name).cloned().unwrap_or_default()))
+}
@@ -0,0 +1,98 @@
+//! Idempotent ingest for FOD Detection Backend
+//! Remembers the response to each `Idempotency-Key` so devices can retry an ingest safely,
+//! in Redis when one is configured (see `shared`), otherwise in Postgres
+
+use axum::http::{HeaderMap, StatusCode};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use crate::{db::internal, jobs, perf, AppState};
+
+pub const HEADER: &str = "idempotency-key";
+/// Retries later than this are treated as new requests
+const TTL_HOURS: i32 = 24;
+/// Expired keys are deleted this often
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+const MAX_KEY_LEN: usize = 255;
+
+// ==================== Keys ====================
+
+/// The request's key, None without the header; 400 when it is empty or too long
+pub fn key(headers: &HeaderMap) -> Result<Option<String>, (StatusCode, String)> {
+    let Some(v) = headers.get(HEADER) else {
+        return Ok(None);
+    };
+    match v.to_str().map(str::trim) {
+        Ok(k) if !k.is_empty() && k.len() <= MAX_KEY_LEN => Ok(Some(k.to_string())),
+        _ => Err((StatusCode::BAD_REQUEST, format!("Idempotency-Key must be 1-{} visible characters", MAX_KEY_LEN))),
+    }
+}
+
+fn redis_key(key: &str) -> String {
+    format!("idempotency:{}", key)
+}
+
+/// Response already given for `key`, if it is still remembered
+pub async fn replay(st: &AppState, key: &str) -> Result<Option<Value>, (StatusCode, String)> {
+    if let Some(redis) = st.shared.redis() {
+        let stored = redis.get(&redis_key(key)).await.map_err(internal)?;
+        return stored.map(|v| serde_json::from_slice(&v)).transpose().map_err(internal);
+    }
+    let q = sqlx::query_scalar::<_, Value>(
+        "SELECT response FROM ingest_idempotency WHERE key = $1 AND created_at > NOW() - make_interval(hours => $2)",
+    )
+    .bind(key)
+    .bind(TTL_HOURS)
+    .fetch_optional(&st.db);
+    perf::timed("idempotency_replay", || format!("key={:?}", key), q).await.map_err(internal)
+}
+
+/// Remember the response for `key`; an expired entry under the same key is replaced
+pub async fn remember(st: &AppState, key: &str, response: &Value) -> Result<(), (StatusCode, String)> {
+    if let Some(redis) = st.shared.redis() {
+        let ttl = Duration::from_secs(TTL_HOURS as u64 * 3600);
+        redis.set_new(&redis_key(key), &serde_json::to_vec(response).map_err(internal)?, ttl).await.map_err(internal)?;
+        return Ok(());
+    }
+    let q = sqlx::query(
+        r#"
+        INSERT INTO ingest_idempotency (key, response) VALUES ($1, $2)
+        ON CONFLICT (key) DO UPDATE SET response = EXCLUDED.response, created_at = NOW()
+        WHERE ingest_idempotency.created_at <= NOW() - make_interval(hours => $3)
+        "#,
+    )
+    .bind(key)
+    .bind(response)
+    .bind(TTL_HOURS)
+    .execute(&st.db);
+    perf::timed("idempotency_remember", || format!("key={:?}", key), q).await.map_err(internal)?;
+    Ok(())
+}
+
+// ==================== Pruning ====================
+
+/// Delete expired keys, returns the number deleted
+pub async fn prune(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let done = sqlx::query("DELETE FROM ingest_idempotency WHERE created_at <= NOW() - make_interval(hours => $1)")
+        .bind(TTL_HOURS)
+        .execute(db)
+        .await?;
+    Ok(done.rows_affected())
+}
+
+/// Hourly prune of expired keys on one replica
+pub fn spawn_pruner(state: AppState) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            tick.tick().await;
+            match jobs::run_singleton(&state.db, "idempotency_prune", prune(&state.db)).await {
+                Ok(Some(Ok(n))) if n > 0 => info!(deleted = n, "expired idempotency keys deleted"),
+                Ok(Some(Ok(_))) | Ok(None) => {}
+                Ok(Some(Err(e))) | Err(e) => warn!(error = %e, "idempotency key prune failed"),
+            }
+        }
+    });
+}
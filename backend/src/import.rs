@@ -0,0 +1,181 @@
+//! Bulk NDJSON event import for FOD Detection Backend
+//! Streams the request body line by line, validates and inserts in batches
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::info;
+
+use crate::{
+    admit, auth,
+    db::{self, internal, NewEvent},
+    track_id, Admission, AppState, IngestEventRequest,
+};
+
+/// Rows per INSERT statement
+const BATCH_SIZE: usize = 500;
+/// Longest accepted line in bytes
+const MAX_LINE_BYTES: usize = 64 * 1024;
+/// Rejected lines reported back individually, the rest are only counted
+const MAX_REPORTED_ERRORS: usize = 100;
+
+#[derive(Serialize)]
+pub struct LineError {
+    pub line: usize,
+    pub error: String,
+}
+
+#[derive(Serialize, Default)]
+pub struct ImportSummary {
+    pub lines: usize,
+    pub accepted: u64,
+    pub rejected: usize,
+    /// Valid lines the ingest rules left out (duplicate track, low confidence, sampled, ...)
+    pub skipped: usize,
+    pub errors: Vec<LineError>,
+}
+
+impl ImportSummary {
+    pub(crate) fn reject(&mut self, line: usize, error: String) {
+        self.rejected += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(LineError { line, error });
+        }
+    }
+}
+
+/// Parse and validate one NDJSON line
+fn parse_line(raw: &[u8]) -> Result<(OffsetDateTime, IngestEventRequest), String> {
+    let req: IngestEventRequest = serde_json::from_slice(raw).map_err(|e| format!("invalid json: {}", e))?;
+    let ts = OffsetDateTime::parse(&req.ts, &Rfc3339).map_err(|_| "invalid timestamp".to_string())?;
+    if req.object_class.trim().is_empty() {
+        return Err("object_class is empty".to_string());
+    }
+    if req.object_count < 1 {
+        return Err("object_count must be >= 1".to_string());
+    }
+    if !(0.0..=1.0).contains(&req.confidence) {
+        return Err("confidence must be within 0..1".to_string());
+    }
+    if !(-90.0..=90.0).contains(&req.latitude) || !(-180.0..=180.0).contains(&req.longitude) {
+        return Err("latitude/longitude out of range".to_string());
+    }
+    if req.source.is_empty() || req.source_ref.is_empty() {
+        return Err("source and source_ref are required".to_string());
+    }
+    Ok((ts, req))
+}
+
+struct Importer<'a> {
+    state: &'a AppState,
+    classes: HashMap<String, i32>,
+    batch: Vec<NewEvent>,
+    /// (source_ref, track_id) of batched rows, which track dedup can't see in the database yet
+    tracks: HashSet<(String, String)>,
+    summary: ImportSummary,
+}
+
+impl Importer<'_> {
+    async fn line(&mut self, raw: &[u8]) -> Result<(), (StatusCode, String)> {
+        self.summary.lines += 1;
+        let line_no = self.summary.lines;
+        let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+        if raw.iter().all(|b| b.is_ascii_whitespace()) {
+            return Ok(());
+        }
+        let (ts, req) = match parse_line(raw) {
+            Ok(v) => v,
+            Err(e) => {
+                self.summary.reject(line_no, e);
+                return Ok(());
+            }
+        };
+        let track = track_id(&req).map(|t| (req.source_ref.clone(), t.to_string()));
+        if track.as_ref().is_some_and(|t| self.tracks.contains(t)) && self.recent(ts) {
+            self.summary.skipped += 1;
+            return Ok(());
+        }
+        match admit(self.state, req, ts, &mut self.classes).await? {
+            Admission::Store(event) => {
+                if let Some(track) = track.filter(|_| self.recent(ts)) {
+                    self.tracks.insert(track);
+                }
+                self.batch.push(event);
+            }
+            Admission::Skip(_) => self.summary.skipped += 1,
+        }
+        if self.batch.len() >= BATCH_SIZE {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Inside the dedup window, as `find_track` would see the row once inserted
+    fn recent(&self, ts: OffsetDateTime) -> bool {
+        ts > OffsetDateTime::now_utc() - time::Duration::seconds(self.state.settings.current().dedup_window_secs)
+    }
+
+    async fn flush(&mut self) -> Result<(), (StatusCode, String)> {
+        self.summary.accepted += db::insert_events_batch(&self.state.db, &self.batch).await?;
+        self.batch.clear();
+        self.tracks.clear();
+        Ok(())
+    }
+}
+
+/// POST /events/import (admin) — newline-delimited IngestEventRequest objects, each through the
+/// same rules as /events/ingest (see `admit`). Cross-source fusion is left out on purpose: rows
+/// are inserted in batches, and a bulk load of history should keep every source's events
+pub async fn import_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let mut importer = Importer {
+        state: &state,
+        classes: HashMap::new(),
+        batch: Vec::new(),
+        tracks: HashSet::new(),
+        summary: ImportSummary::default(),
+    };
+    let mut stream = body.into_data_stream();
+    let mut buf: Vec<u8> = Vec::new();
+
+    while let Some(chunk) = stream.next().await {
+        buf.extend_from_slice(&chunk.map_err(internal)?);
+        let mut start = 0;
+        while let Some(pos) = buf[start..].iter().position(|&b| b == b'\n') {
+            let end = start + pos;
+            importer.line(&buf[start..end]).await?;
+            start = end + 1;
+        }
+        buf.drain(..start);
+        if buf.len() > MAX_LINE_BYTES {
+            return Err((StatusCode::PAYLOAD_TOO_LARGE, format!("Line {} exceeds {} bytes", importer.summary.lines + 1, MAX_LINE_BYTES)));
+        }
+    }
+    if !buf.is_empty() {
+        importer.line(&buf).await?;
+    }
+    importer.flush().await?;
+
+    let summary = importer.summary;
+    info!(
+        lines = summary.lines,
+        accepted = summary.accepted,
+        rejected = summary.rejected,
+        skipped = summary.skipped,
+        by = %claims.username,
+        "ndjson import finished"
+    );
+    Ok(Json(summary))
+}
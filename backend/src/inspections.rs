@@ -0,0 +1,256 @@
+//! Inspection routes for FOD Detection Backend
+//! Orders open events and stale zones into a short patrol route
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use uuid::Uuid;
+
+use crate::{auth, db::internal, lifecycle, perf, AppState};
+
+/// Upper bound on waypoints; beyond this the route is split across patrols anyway
+const MAX_WAYPOINTS: i64 = 200;
+/// Window for zone centroids and visit history
+const ZONE_HISTORY_DAYS: i32 = 30;
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+// ==================== Types ====================
+
+#[derive(Deserialize)]
+pub struct RouteParams {
+    pub vehicle: Option<String>,
+    /// Current vehicle position; without it the route starts at the newest open event
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    /// Open events detected within this many days (default 7)
+    pub days: Option<i32>,
+    /// Zones not visited for this many hours are added (default 24)
+    pub stale_hours: Option<i32>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Waypoint {
+    pub order: usize,
+    /// `event` (open FOD event) or `zone` (zone centroid due for a sweep)
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    pub zone: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Distance from the previous waypoint (or the start)
+    pub leg_m: f64,
+}
+
+#[derive(Serialize)]
+pub struct Route {
+    pub vehicle: Option<String>,
+    pub start: Option<[f64; 2]>,
+    pub waypoints: Vec<Waypoint>,
+    pub total_distance_m: f64,
+}
+
+#[derive(FromRow)]
+struct OpenEvent {
+    id: Uuid,
+    class_name: String,
+    state: String,
+    zone: Option<String>,
+    latitude: f32,
+    longitude: f32,
+}
+
+#[derive(FromRow)]
+struct StaleZone {
+    zone: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+// ==================== Candidates ====================
+
+/// Open events with a real position (0,0 means the source sent none), newest first
+async fn open_events(db: &PgPool, days: i32) -> Result<Vec<OpenEvent>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, OpenEvent>(
+        r#"
+        SELECT e.id, fc.name AS class_name, e.state, NULLIF(e.zone, '') AS zone,
+               e.latitude, e.longitude
+        FROM events e
+        JOIN fod_classes fc ON e.class_id = fc.id
+        WHERE e.state <> ALL($1)
+          AND e.ts >= NOW() - make_interval(days => $2)
+          AND NOT (e.latitude = 0 AND e.longitude = 0)
+        ORDER BY e.ts DESC
+        LIMIT $3
+        "#,
+    )
+    .bind(&lifecycle::CLOSED_STATES[..])
+    .bind(days)
+    .bind(MAX_WAYPOINTS)
+    .fetch_all(db);
+    perf::timed("route_open_events", || format!("days={}", days), q).await.map_err(internal)
+}
+
+/// Zones (centroid of their recent events) without a completed task or a removal there
+/// in the last `stale_hours`
+async fn stale_zones(db: &PgPool, stale_hours: i32) -> Result<Vec<StaleZone>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, StaleZone>(
+        r#"
+        WITH zones AS (
+            SELECT zone,
+                   AVG(latitude)::FLOAT8 AS latitude, AVG(longitude)::FLOAT8 AS longitude
+            FROM events
+            WHERE ts >= NOW() - make_interval(days => $2)
+              AND zone <> ''
+              AND NOT (latitude = 0 AND longitude = 0)
+            GROUP BY 1
+        ),
+        visits AS (
+            SELECT zone, completed_at AS at FROM tasks
+            WHERE status = 'done' AND completed_at >= NOW() - make_interval(hours => $1)
+            UNION ALL
+            SELECT e.zone, t.created_at
+            FROM event_state_transitions t
+            JOIN events e ON e.id = t.event_id
+            WHERE t.to_state IN ('removed', 'verified_clear')
+              AND t.created_at >= NOW() - make_interval(hours => $1)
+        )
+        SELECT z.zone, z.latitude, z.longitude
+        FROM zones z
+        WHERE NOT EXISTS (SELECT 1 FROM visits v WHERE v.zone = z.zone)
+        ORDER BY z.zone
+        LIMIT $3
+        "#,
+    )
+    .bind(stale_hours)
+    .bind(ZONE_HISTORY_DAYS)
+    .bind(MAX_WAYPOINTS)
+    .fetch_all(db);
+    perf::timed("route_stale_zones", || format!("stale_hours={}", stale_hours), q).await.map_err(internal)
+}
+
+// ==================== Ordering ====================
+
+fn distance_m(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.1 - a.1).to_radians();
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// Visiting order of `points` from `start`: nearest neighbour, then 2-opt until no
+/// reversal shortens the (open) path. Index 0 of the path is the start.
+fn plan(start: (f64, f64), points: &[(f64, f64)]) -> Vec<usize> {
+    let mut path = vec![usize::MAX];
+    let mut left: Vec<usize> = (0..points.len()).collect();
+    let pos = |i: usize| if i == usize::MAX { start } else { points[i] };
+    while !left.is_empty() {
+        let here = pos(*path.last().unwrap());
+        let (k, _) = left
+            .iter()
+            .enumerate()
+            .min_by(|(_, &a), (_, &b)| distance_m(here, points[a]).total_cmp(&distance_m(here, points[b])))
+            .unwrap();
+        path.push(left.swap_remove(k));
+    }
+
+    let mut improved = true;
+    while improved {
+        improved = false;
+        for i in 1..path.len().saturating_sub(1) {
+            for j in i + 1..path.len() {
+                let (a, b, c) = (pos(path[i - 1]), pos(path[i]), pos(path[j]));
+                let before = distance_m(a, b) + path.get(j + 1).map(|&d| distance_m(c, pos(d))).unwrap_or(0.0);
+                let after = distance_m(a, c) + path.get(j + 1).map(|&d| distance_m(b, pos(d))).unwrap_or(0.0);
+                if after + 1e-6 < before {
+                    path[i..=j].reverse();
+                    improved = true;
+                }
+            }
+        }
+    }
+    path.remove(0);
+    path
+}
+
+// ==================== Handlers ====================
+
+/// GET /inspections/suggested-route?vehicle=&lat=&lon=&days=&stale_hours= — ordered waypoints
+/// over open events and zones not visited recently
+pub async fn suggested_route_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<RouteParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let start = match (p.lat, p.lon) {
+        (Some(lat), Some(lon)) if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) => Some((lat, lon)),
+        (None, None) => None,
+        _ => return Err((StatusCode::BAD_REQUEST, "lat and lon must be given together and be valid".to_string())),
+    };
+    let days = p.days.unwrap_or(7).clamp(1, 90);
+    let stale_hours = p.stale_hours.unwrap_or(24).clamp(1, 24 * 30);
+
+    let mut candidates: Vec<Waypoint> = open_events(&st.db, days)
+        .await?
+        .into_iter()
+        .map(|e| Waypoint {
+            order: 0,
+            kind: "event",
+            event_id: Some(e.id),
+            class_name: Some(e.class_name),
+            state: Some(e.state),
+            zone: e.zone,
+            latitude: e.latitude as f64,
+            longitude: e.longitude as f64,
+            leg_m: 0.0,
+        })
+        .collect();
+    for z in stale_zones(&st.db, stale_hours).await? {
+        candidates.push(Waypoint {
+            order: 0,
+            kind: "zone",
+            event_id: None,
+            class_name: None,
+            state: None,
+            zone: Some(z.zone),
+            latitude: z.latitude,
+            longitude: z.longitude,
+            leg_m: 0.0,
+        });
+    }
+    candidates.truncate(MAX_WAYPOINTS as usize);
+
+    let points: Vec<(f64, f64)> = candidates.iter().map(|w| (w.latitude, w.longitude)).collect();
+    let origin = start.or_else(|| points.first().copied());
+    let mut waypoints = Vec::with_capacity(candidates.len());
+    let mut total_distance_m = 0.0;
+    if let Some(origin) = origin {
+        let mut prev = origin;
+        for (order, i) in plan(origin, &points).into_iter().enumerate() {
+            let mut w = candidates[i].clone();
+            w.order = order + 1;
+            w.leg_m = distance_m(prev, points[i]).round();
+            total_distance_m += w.leg_m;
+            prev = points[i];
+            waypoints.push(w);
+        }
+    }
+
+    Ok(Json(Route {
+        vehicle: p.vehicle,
+        start: start.map(|(lat, lon)| [lat, lon]),
+        waypoints,
+        total_distance_m,
+    }))
+}
@@ -0,0 +1,47 @@
+//! Background job coordination for FOD Detection Backend
+//! Postgres advisory locks so singleton jobs run on one replica at a time
+
+use futures::FutureExt;
+use sqlx::PgPool;
+use std::{future::Future, panic::AssertUnwindSafe};
+use tracing::{debug, error, warn};
+
+/// First key of every job lock, keeps them apart from other advisory lock users
+const LOCK_NAMESPACE: i32 = 0x464f44; // "FOD"
+
+/// Run `job` unless another replica is already running the job called `name`.
+/// Returns None when skipped. The lock is session-level, so it is taken and released
+/// on one pooled connection held for the duration of the job.
+pub async fn run_singleton<T>(db: &PgPool, name: &str, job: impl Future<Output = T>) -> Result<Option<T>, sqlx::Error> {
+    let mut conn = db.acquire().await?;
+    let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1, hashtext($2))")
+        .bind(LOCK_NAMESPACE)
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await?;
+    if !locked {
+        debug!(job = name, "job is running on another replica, skipping");
+        return Ok(None);
+    }
+
+    let outcome = AssertUnwindSafe(job).catch_unwind().await;
+
+    let released = sqlx::query_scalar::<_, bool>("SELECT pg_advisory_unlock($1, hashtext($2))")
+        .bind(LOCK_NAMESPACE)
+        .bind(name)
+        .fetch_one(&mut *conn)
+        .await;
+    if !matches!(released, Ok(true)) {
+        // Closing the session is the only other way to drop the lock
+        warn!(job = name, "failed to release job lock, closing its connection");
+        let _ = conn.close().await;
+    }
+
+    match outcome {
+        Ok(out) => Ok(Some(out)),
+        Err(panic) => {
+            error!(job = name, "job panicked");
+            std::panic::resume_unwind(panic)
+        }
+    }
+}
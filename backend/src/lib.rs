@@ -0,0 +1,1119 @@
+//! FOD Detection Backend - REST API
+//! Shared state, router construction and core handlers; the server binary lives in main.rs
+
+pub mod activities;
+pub mod ai;
+pub mod alerts;
+pub mod attachments;
+pub mod auth;
+pub mod backfill;
+pub mod backups;
+pub mod calendar;
+pub mod calibration;
+pub mod cameras;
+pub mod categories;
+pub mod classes;
+pub mod commands;
+pub mod clearance;
+pub mod comments;
+pub mod compression;
+pub mod conditional;
+pub mod confidence;
+pub mod counters;
+pub mod crypto;
+pub mod db;
+pub mod demo;
+pub mod device_logs;
+pub mod devices;
+pub mod distributions;
+#[cfg(feature = "edge")]
+pub mod edge;
+pub mod erasure;
+pub mod hotspots;
+pub mod exports;
+pub mod fields;
+pub mod filter;
+pub mod flags;
+pub mod geometry;
+pub mod handover;
+pub mod i18n;
+pub mod idempotency;
+pub mod import;
+pub mod inspections;
+pub mod jobs;
+pub mod lifecycle;
+pub mod live;
+pub mod logging;
+pub mod migrations;
+pub mod movements;
+pub mod notam;
+pub mod notifications;
+pub mod oncall;
+#[cfg(feature = "onnx")]
+pub mod onnx;
+pub mod onvif;
+pub mod perf;
+pub mod purge;
+pub mod push;
+pub mod quarantine;
+pub mod raw_inferences;
+pub mod reliability;
+pub mod replay;
+pub mod repository;
+pub mod s3;
+pub mod sampling;
+pub mod secrets;
+pub mod settings;
+pub mod shared;
+pub mod signing;
+pub mod site;
+pub mod sla;
+pub mod sms;
+pub mod status;
+pub mod subscriptions;
+pub mod synthetic;
+pub mod tasks;
+pub mod tls;
+pub mod traffic;
+pub mod uploads;
+pub mod usage;
+
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Multipart, Path, State, Query},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    middleware,
+    routing::{delete, get, post, put},
+    BoxError, Json, Router,
+};
+use bytes::Bytes;
+use futures::{future::ready, stream, Stream, StreamExt};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_json::json;
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    sync::{atomic::AtomicBool, Arc},
+};
+use tower_http::{
+    cors::CorsLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
+};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use db::{internal, DashboardSummary, NewEvent, RecentEvent};
+use fields::Fields;
+use filter::Filter;
+
+/// Largest series `/dashboard/timeseries` returns in one response
+const MAX_TIMESERIES_BUCKETS: i64 = 2000;
+/// Years `/dashboard/calendar` accepts
+const CALENDAR_YEARS: std::ops::RangeInclusive<i32> = 2000..=2100;
+
+// ==================== App State ====================
+
+#[derive(Clone)]
+pub struct AppState {
+    pub http: Client,
+    pub ai_base: String,
+    /// Detect endpoint at `ai_base` and the contract it speaks (`AI_ADAPTER`)
+    pub ai: ai::Backend,
+    pub db: PgPool,
+    /// Set while migrations are pending; write endpoints answer 503
+    pub read_only: Arc<AtomicBool>,
+    /// Dependency health from the background checker
+    pub status: status::Board,
+    /// Runtime tunables, see `/admin/settings`
+    pub settings: settings::Runtime,
+    /// Feature flags, see `/admin/flags`
+    pub flags: flags::Flags,
+    /// In-memory event counters behind `/dashboard/live`
+    pub counters: counters::Live,
+    /// Event store behind ingest and event lookups, Postgres unless replaced with `with_events`
+    pub events: Arc<dyn repository::EventRepository>,
+    /// Camera snapshot, PTZ and discovery settings, from the env unless replaced with `with_cameras`
+    pub cameras: Arc<cameras::Config>,
+    /// Open camera previews, capped per camera
+    pub previews: cameras::Previews,
+    /// ADS-B feed and runway thresholds, from the env unless replaced with `with_traffic`
+    pub traffic: Arc<traffic::Traffic>,
+    /// Object store for device uploads, see `uploads::bucket`
+    pub uploads: Option<Arc<s3::Bucket>>,
+    /// Object store for event attachments, see `attachments::bucket`
+    pub attachments: Option<Arc<s3::Bucket>>,
+    /// Object store for device log bundles, see `device_logs::bucket`
+    pub device_logs: Option<Arc<s3::Bucket>>,
+    /// FCM/APNs credentials for alert pushes, from the env unless replaced with `with_push`
+    pub push: Arc<push::Config>,
+    /// SMS gateway and quota for alert texts, from the env unless replaced with `with_sms`
+    pub sms: Arc<sms::Config>,
+    /// Device request signing mode and keys, from the env unless replaced with `with_signing`
+    pub signing: Arc<signing::Config>,
+    /// Response cache, rate limits, idempotency keys and event feed, in Redis or this process,
+    /// from the env unless replaced with `with_shared`
+    pub shared: Arc<shared::Shared>,
+    /// Monthly per-key quotas for device routes, from the env unless replaced with `with_usage`,
+    /// and the usage counted since the last flush
+    pub usage: Arc<usage::Meter>,
+}
+
+impl AppState {
+    pub fn new(http: Client, ai_base: String, db: PgPool, read_only: bool) -> Self {
+        let ai = ai::Backend::from_env(&ai_base);
+        let events = Arc::new(repository::PgEventRepository(db.clone()));
+        AppState { http, ai_base, ai, db, read_only: Arc::new(AtomicBool::new(read_only)), status: status::Board::default(), settings: settings::Runtime::default(), flags: flags::Flags::default(), counters: counters::Live::default(), events, cameras: Arc::new(cameras::Config::from_env()), previews: cameras::Previews::default(), traffic: Arc::new(traffic::Traffic::new(traffic::Config::from_env())), uploads: uploads::from_env().map(Arc::new), attachments: attachments::from_env().map(Arc::new), device_logs: device_logs::from_env().map(Arc::new), push: Arc::new(push::Config::from_env()), sms: Arc::new(sms::Config::from_env()), signing: Arc::new(signing::Config::from_env()), shared: Arc::new(shared::Shared::new(shared::Config::from_env())), usage: Arc::new(usage::Meter::new(usage::Config::from_env())) }
+    }
+
+    /// Replace the detect backend picked from the environment
+    pub fn with_ai(mut self, ai: ai::Backend) -> Self {
+        self.ai = ai;
+        self
+    }
+
+    /// Replace the camera settings read from the environment
+    pub fn with_cameras(mut self, cameras: cameras::Config) -> Self {
+        self.cameras = Arc::new(cameras);
+        self
+    }
+
+    /// Replace the ADS-B settings read from the environment
+    pub fn with_traffic(mut self, config: traffic::Config) -> Self {
+        self.traffic = Arc::new(traffic::Traffic::new(config));
+        self
+    }
+
+    /// Replace the upload store read from the environment
+    pub fn with_upload_store(mut self, bucket: s3::Bucket) -> Self {
+        self.uploads = Some(Arc::new(bucket));
+        self
+    }
+
+    /// Replace the attachment store read from the environment
+    pub fn with_attachment_store(mut self, bucket: s3::Bucket) -> Self {
+        self.attachments = Some(Arc::new(bucket));
+        self
+    }
+
+    /// Replace the device log store read from the environment
+    pub fn with_device_log_store(mut self, bucket: s3::Bucket) -> Self {
+        self.device_logs = Some(Arc::new(bucket));
+        self
+    }
+
+    /// Replace the push credentials read from the environment
+    pub fn with_push(mut self, config: push::Config) -> Self {
+        self.push = Arc::new(config);
+        self
+    }
+
+    /// Replace the SMS settings read from the environment
+    pub fn with_sms(mut self, config: sms::Config) -> Self {
+        self.sms = Arc::new(config);
+        self
+    }
+
+    /// Replace the device signing settings read from the environment
+    pub fn with_signing(mut self, config: signing::Config) -> Self {
+        self.signing = Arc::new(config);
+        self
+    }
+
+    /// Replace the Redis, cache and rate limit settings read from the environment
+    pub fn with_shared(mut self, config: shared::Config) -> Self {
+        self.shared = Arc::new(shared::Shared::new(config));
+        self
+    }
+
+    /// Replace the usage quotas read from the environment
+    pub fn with_usage(mut self, config: usage::Config) -> Self {
+        self.usage = Arc::new(usage::Meter::new(config));
+        self
+    }
+
+    /// Replace the Postgres event store, e.g. with `repository::MemoryEventRepository`
+    pub fn with_events(mut self, events: Arc<dyn repository::EventRepository>) -> Self {
+        self.events = events;
+        self
+    }
+}
+
+// ==================== Request Types ====================
+
+#[derive(Deserialize)]
+struct SaveParams {
+    save: Option<bool>,
+    latitude: Option<f32>,
+    longitude: Option<f32>,
+    source: Option<String>,
+    source_ref: Option<String>,
+    yaw: Option<f32>,
+    /// Drone height above ground (m); with `yaw` the detection is offset from the drone's position
+    altitude: Option<f32>,
+    /// Drone gimbal angle below the horizon in degrees (default 90, straight down)
+    pitch: Option<f32>,
+    /// Archive the full AI response with the saved events (default `RAW_INFERENCES`)
+    raw: Option<bool>,
+    conf: Option<f32>,
+    imgsz: Option<i32>,
+}
+
+/// Stored frame for /infer/by-ref: `key` (or an `s3://` / store `url`) of an object in the upload
+/// store; devices send the `upload_token` from /uploads/presign, admins may reprocess any key
+#[derive(Deserialize)]
+struct InferByRefRequest {
+    key: Option<String>,
+    url: Option<String>,
+    upload_token: Option<String>,
+    filename: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct IngestEventRequest {
+    ts: String,
+    object_class: String,
+    object_count: i32,
+    confidence: f32,
+    latitude: f32,
+    longitude: f32,
+    source: String,
+    source_ref: String,
+    bbox: Option<serde_json::Value>,
+    meta: Option<serde_json::Value>,
+}
+
+// ==================== Background Jobs ====================
+
+/// Schedulers, pruners and probers that write to or read migrated tables; see
+/// `migrations::when_writable`. `status::spawn_checker` is not one: it only pings the
+/// database, and is what reports a read-only instance as degraded.
+pub fn spawn_writers(state: AppState) {
+    exports::spawn_scheduler(state.clone());
+    backups::spawn_scheduler(state.clone());
+    tasks::spawn_scheduler(state.clone());
+    replay::spawn_dispatcher(state.clone());
+    raw_inferences::spawn_pruner(state.clone());
+    idempotency::spawn_pruner(state.clone());
+    status::spawn_prober(state.clone());
+    synthetic::spawn_prober(state.clone());
+    alerts::spawn_escalator(state.clone());
+    usage::spawn_flusher(state.clone());
+    traffic::spawn_ingester(state);
+}
+
+// ==================== Router ====================
+
+/// Full application router with middleware; background tasks are started by the caller
+pub fn build_app(state: AppState) -> Router {
+    let cors = CorsLayer::new()
+        .allow_origin("http://localhost:3000".parse::<HeaderValue>().unwrap())
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE, Method::OPTIONS])
+        .allow_headers([
+            axum::http::header::CONTENT_TYPE,
+            axum::http::header::AUTHORIZATION,
+            axum::http::header::ACCEPT,
+            axum::http::header::IF_NONE_MATCH,
+            axum::http::header::IF_MODIFIED_SINCE,
+            header::HeaderName::from_static(idempotency::HEADER),
+        ])
+        .expose_headers([
+            header::ETAG,
+            header::RETRY_AFTER,
+            header::HeaderName::from_static(status::HEADER),
+            header::HeaderName::from_static(shared::CACHE_HEADER),
+        ])
+        .allow_credentials(true);
+
+    Router::new()
+        // Health
+        .route("/health", get(health))
+        .route("/health/ai", get(ai_health))
+        .route("/health/ai-ready", get(ai_ready))
+        .route("/health/db", get(db_health))
+        .route("/meta", get(site::meta_handler))
+        // Auth
+        .route("/auth/login", post(auth::login_handler))
+        .route("/auth/register", post(auth::register_handler))
+        .route("/auth/me", get(auth::me_handler))
+        .route("/auth/refresh", post(auth::refresh_handler))
+        .route("/auth/logout", post(auth::logout_handler))
+        // Dashboard & Events; live counters and traffic are not worth caching
+        .merge(
+            Router::new()
+                .route("/dashboard/summary", get(dashboard_summary))
+                .route("/dashboard/anomalies", get(dashboard_anomalies))
+                .route("/dashboard/timeseries", get(dashboard_timeseries))
+                .route("/dashboard/calendar", get(dashboard_calendar))
+                .route("/dashboard/fod-density", get(movements::density_handler))
+                .route("/dashboard/sources", get(reliability::sources_handler))
+                .route("/dashboard/hotspots", get(hotspots::hotspots_handler))
+                .route("/dashboard/activity-correlation", get(activities::correlation_handler))
+                .route("/dashboard/class-breakdown", get(classes::breakdown_handler))
+                .route("/dashboard/distributions", get(distributions::distributions_handler))
+                .route_layer(middleware::from_fn_with_state(state.clone(), shared::cache)),
+        )
+        .route("/dashboard/live", get(counters::live_handler))
+        .route("/dashboard/traffic", get(traffic::traffic_handler))
+        .route("/reports/fod-categories", get(categories::report_handler))
+        .route("/reports/handover", get(handover::handover_handler))
+        .route("/movements", post(movements::ingest_handler))
+        .route("/classes/translations", get(i18n::list_handler))
+        .route("/classes/tree", get(classes::tree_handler))
+        .route("/calibrations", get(calibration::list_handler))
+        .route("/cameras", get(cameras::list_handler).post(onvif::register_handler))
+        .route("/cameras/discover", post(onvif::discover_handler))
+        .route("/cameras/:id", delete(onvif::unregister_handler))
+        .route("/cameras/:id/preview", get(cameras::preview_handler))
+        .route("/cameras/:id/preview-url", get(cameras::preview_url_handler))
+        .route("/cameras/:id/snapshot", post(cameras::snapshot_handler))
+        .route("/cameras/:id/ptz/move", post(onvif::move_handler))
+        .route("/cameras/:id/ptz/stop", post(onvif::stop_handler))
+        .route("/cameras/:id/ptz/presets", get(onvif::presets_handler))
+        .route("/cameras/:id/ptz/presets/:token", post(onvif::goto_preset_handler))
+        .route("/snapshots/:id/image", get(cameras::image_handler))
+        .route("/activities", get(activities::list_handler).post(activities::create_handler))
+        .route("/activities/:id", delete(activities::delete_handler))
+        .route("/events", get(lifecycle::list_handler))
+        .route("/events/recent", get(recent_events))
+        .route("/events/query", get(query_events))
+        .route("/events/count", get(count_events))
+        .route("/events/changes", get(live::changes_handler))
+        .route("/events/stream", get(live::sse_handler))
+        .route("/events/ws", get(live::ws_handler))
+        .route("/events/:id", get(get_event))
+        .route("/events/:id/state", post(lifecycle::transition_handler))
+        .route("/events/:id/history", get(lifecycle::history_handler))
+        .route("/events/:id/comments", get(comments::list_handler).post(comments::create_handler))
+        .route("/events/:id/notam-draft", post(notam::create_handler))
+        .route("/events/:id/notam-drafts", get(notam::event_list_handler))
+        .route("/notam-drafts", get(notam::list_handler))
+        .route("/notam-drafts/:id/text", get(notam::text_handler))
+        .route(
+            "/events/:id/attachments",
+            get(attachments::list_handler)
+                .post(attachments::upload_handler)
+                .layer(DefaultBodyLimit::max(attachments::MAX_FILE_BYTES + 64 * 1024)),
+        )
+        .route("/attachments/:id", get(attachments::download_handler))
+        .route("/events/:id/raw", get(raw_inferences::get_handler))
+        .route(
+            "/events/:id/clearance",
+            post(clearance::upload_handler).layer(DefaultBodyLimit::max(clearance::MAX_IMAGE_BYTES)),
+        )
+        .route("/events/:id/clearances", get(clearance::list_handler))
+        .route("/clearances/:id/image", get(clearance::image_handler))
+        // Devices
+        .route("/devices", get(devices::list_handler))
+        .route("/devices/outdated", get(devices::outdated_handler))
+        .route("/devices/:id/config", put(devices::put_config_handler))
+        .route(
+            "/devices/:id/certificates/:fingerprint",
+            put(devices::put_certificate_handler).delete(devices::delete_certificate_handler),
+        )
+        .route("/devices/:id/commands", get(commands::list_handler).post(commands::create_handler))
+        .route("/devices/:id/logs", get(device_logs::list_handler))
+        .route("/devices/:id/logs/:log_id", get(device_logs::download_handler))
+        // Crew tasks & patrols
+        .route("/tasks/today", get(tasks::today_handler))
+        .route("/tasks/:id/complete", post(tasks::complete_handler))
+        .route("/inspections/suggested-route", get(inspections::suggested_route_handler))
+        // On-call
+        .route("/oncall/now", get(oncall::now_handler))
+        .route("/oncall/rotations", get(oncall::list_rotations_handler).post(oncall::upsert_rotation_handler))
+        .route("/oncall/overrides", post(oncall::override_handler))
+        .route("/alerts", get(alerts::list_handler))
+        .route("/alerts/stream", get(alerts::stream_handler))
+        .route("/alerts/stream-url", get(alerts::stream_url_handler))
+        .route("/alerts/:id", get(alerts::get_handler))
+        .route("/alerts/:id/ack", post(alerts::ack_handler))
+        .route("/alerts/:id/resolve", post(alerts::resolve_handler))
+        .route("/admin/escalation-policies", get(alerts::list_policies_handler).put(alerts::upsert_policy_handler))
+        .route("/admin/sla-thresholds", get(sla::list_handler).put(sla::put_handler).delete(sla::delete_handler))
+        .route("/users/:id/subscriptions", get(subscriptions::get_handler).put(subscriptions::put_handler))
+        .route("/users/:id/push-devices", get(push::list_handler).post(push::register_handler))
+        .route("/users/:id/push-devices/:token", delete(push::delete_handler))
+        .route("/users/:id/sms", get(sms::get_handler).put(sms::put_handler).delete(sms::delete_handler))
+        // Admin
+        .route("/admin/migrations", get(migrations::status_handler))
+        .route("/admin/migrations/run", post(migrations::run_handler))
+        .route("/admin/indexes", get(migrations::indexes_handler))
+        .route("/admin/perf", get(admin_perf))
+        .route("/admin/usage", get(usage::list_handler))
+        .route("/admin/settings", get(settings::list_handler).put(settings::put_handler))
+        .route("/admin/settings/history", get(settings::history_handler))
+        .route("/admin/uptime", get(status::uptime_handler))
+        .route("/admin/synthetic", get(synthetic::list_handler))
+        .route("/admin/quarantine", get(quarantine::list_handler))
+        .route("/admin/quarantine/:source_ref", put(quarantine::put_handler).delete(quarantine::delete_handler))
+        .route("/admin/device-config", get(devices::fleet_config_handler).put(devices::put_fleet_config_handler))
+        .route("/admin/flags", get(flags::list_handler))
+        .route("/admin/flags/:name", put(flags::put_handler).delete(flags::delete_handler))
+        .route("/admin/confidence-calibrations", get(confidence::list_handler))
+        .route("/admin/confidence-calibrations/:class", put(confidence::put_handler).delete(confidence::delete_handler))
+        .route("/admin/confidence-calibrations/:class/fit", post(confidence::fit_handler))
+        .route("/admin/meta-keys/rotate", post(rotate_meta_keys))
+        .route("/admin/erasure", post(erasure::erasure_handler))
+        .route("/admin/events/purge", post(purge::purge_handler))
+        .route("/events/import", post(import::import_handler))
+        .route("/admin/seed", post(demo::seed_handler))
+        .route("/admin/classes/:name/translations", put(i18n::put_handler))
+        .route("/admin/classes/aliases", get(classes::list_aliases_handler).put(classes::put_alias_handler))
+        .route("/admin/classes/merge", post(classes::merge_handler))
+        .route("/admin/classes/:name/parent", put(classes::set_parent_handler))
+        .route("/admin/fod-categories", get(categories::list_handler))
+        .route("/admin/fod-categories/:class", put(categories::put_handler))
+        .route("/admin/calibrations/:source_ref", put(calibration::put_handler).delete(calibration::delete_handler))
+        .route("/admin/replay", get(replay::list_handler).post(replay::start_handler))
+        .route("/admin/replay/:id", get(replay::get_handler))
+        .route("/admin/replay/:id/results", get(replay::results_handler))
+        .route("/admin/backfill/zones", post(backfill::zones_handler))
+        .route("/jobs/:id/progress", get(replay::progress_handler))
+        .route("/jobs/:id/cancel", post(replay::cancel_handler))
+        .route("/jobs/:id/retry", post(replay::retry_handler))
+        .route("/admin/exports", get(exports::status_handler))
+        .route("/admin/exports/run", post(exports::run_handler))
+        .route("/admin/backup", post(backups::run_handler))
+        .route("/admin/backups", get(backups::list_handler))
+        .route("/admin/backups/:id/restore", post(backups::restore_handler))
+        .route("/admin/users/:id/sessions/revoke", post(auth::revoke_sessions_handler))
+        .route("/admin/notifications/templates", get(notifications::list_handler).put(notifications::upsert_handler))
+        .route("/admin/notifications/preview", post(notifications::preview_handler))
+        .route("/admin/notifications/recipients", get(subscriptions::recipients_handler))
+        .route_layer(middleware::from_fn_with_state(state.clone(), shared::limit))
+        // Device-facing, merged after the limit above so signed routes are limited inside
+        // verify, per signing device; usage metering and the limit are added before verify so
+        // they run inside it and see the signed device
+        .merge(
+            Router::new()
+                .route("/proxy/detect", post(proxy_detect))
+                .route("/events/ingest", post(ingest_event))
+                .route("/uploads/presign", post(uploads::presign_handler))
+                .route("/infer/by-ref", post(infer_by_ref))
+                .route("/devices/:id/heartbeat", post(devices::heartbeat_handler))
+                .route("/devices/:id/config", get(devices::config_handler))
+                .route("/devices/:id/commands/next", get(commands::next_handler))
+                .route("/devices/:id/commands/:command_id/ack", post(commands::ack_handler))
+                .route(
+                    "/devices/:id/logs",
+                    post(device_logs::upload_handler).layer(DefaultBodyLimit::max(device_logs::MAX_BUNDLE_BYTES + 1)),
+                )
+                .route_layer(middleware::from_fn_with_state(state.clone(), usage::meter))
+                .route_layer(middleware::from_fn_with_state(state.clone(), shared::limit))
+                .route_layer(middleware::from_fn_with_state(state.clone(), signing::verify))
+                .route_layer(middleware::from_fn(tls::require_client_cert)),
+        )
+        .route_layer(middleware::from_fn(perf::track_route))
+        .layer(middleware::from_fn_with_state(state.clone(), i18n::localize))
+        .layer(middleware::from_fn_with_state(state.clone(), migrations::read_only_guard))
+        .layer(middleware::from_fn_with_state(state.clone(), status::header))
+        .with_state(state)
+        .layer(compression::layer())
+        .layer(TraceLayer::new_for_http().make_span_with(logging::request_span))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(cors)
+}
+
+// ==================== Health Endpoints ====================
+
+/// GET /health — liveness plus the last checked status of each dependency
+async fn health(State(st): State<AppState>) -> Json<Value> {
+    Json(json!({"ok": true, "status": st.status.overall(), "dependencies": st.status.snapshot()}))
+}
+
+async fn db_health(State(st): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let v = db::check_health(&st.db).await?;
+    Ok(Json(json!({"ok": true, "db": v})))
+}
+
+async fn ai_health(State(st): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let url = format!("{}/health", st.ai_base.trim_end_matches('/'));
+    let resp = st.http.get(&url).send().await.map_err(internal)?;
+    let v: Value = resp.json().await.map_err(internal)?;
+    Ok(Json(v))
+}
+
+async fn ai_ready(State(st): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let url = format!("{}/ready", st.ai_base.trim_end_matches('/'));
+    let resp = st.http.get(&url).send().await.map_err(internal)?;
+    let v: Value = resp.json().await.map_err(internal)?;
+    Ok(Json(v))
+}
+
+// ==================== AI Proxy Helpers ====================
+
+async fn extract_file(mp: &mut Multipart, default: &str) -> Result<(bytes::Bytes, String), (StatusCode, String)> {
+    while let Some(field) = mp.next_field().await.map_err(internal)? {
+        if field.name() == Some("file") {
+            let filename = field.file_name().map(|s| s.to_string()).unwrap_or_else(|| default.to_string());
+            let bytes = field.bytes().await.map_err(internal)?;
+            return Ok((bytes, filename));
+        }
+    }
+    Err((StatusCode::BAD_REQUEST, "No file field".to_string()))
+}
+
+// ==================== AI Inference Endpoints ====================
+
+async fn proxy_detect(
+    State(state): State<AppState>,
+    Query(params): Query<SaveParams>,
+    mut mp: Multipart,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let (bytes, filename) = extract_file(&mut mp, "upload.jpg").await?;
+    let fallback = state.flags.enabled(&state.db, flags::AI_FALLBACK).await;
+    let opts = ai::DetectOptions { conf: params.conf, imgsz: params.imgsz, fallback };
+    let result = state.ai.detect(&state.http, bytes.to_vec(), filename, opts).await?;
+    maybe_save(&state, &result, &params, &bytes, None).await?;
+    Ok(Json(result))
+}
+
+/// POST /infer/by-ref — `{key | url, upload_token?, filename?}` detect on a frame already in
+/// object storage, then save as /proxy/detect does (same query parameters)
+async fn infer_by_ref(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SaveParams>,
+    Json(req): Json<InferByRefRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let bucket = uploads::bucket(&state)?;
+    let key = match (req.key.as_deref().map(str::trim).filter(|k| !k.is_empty()), req.url.as_deref()) {
+        (Some(key), None) => key.trim_start_matches('/').to_string(),
+        (None, Some(url)) => bucket
+            .key_from_url(url.trim())
+            .ok_or((StatusCode::BAD_REQUEST, "url must point into the upload store".to_string()))?,
+        _ => return Err((StatusCode::BAD_REQUEST, "Exactly one of key or url is required".to_string())),
+    };
+
+    // A device's own upload, once; anything else in the store is an admin reprocessing it
+    let (claimed, stored_name) = match req.upload_token.as_deref() {
+        Some(token) => (true, uploads::claim(&state.db, &key, token).await?),
+        None => {
+            let claims = auth::require_admin(&headers)?;
+            info!(key = %key, by = %claims.username, "stored frame reprocessed");
+            (false, None)
+        }
+    };
+    let filename = req
+        .filename
+        .or(stored_name)
+        .unwrap_or_else(|| key.rsplit('/').next().unwrap_or("upload.jpg").to_string());
+
+    // Failing before anything is saved hands the upload back, so the device can retry
+    let detected = async {
+        let bytes = bucket
+            .find_object(&state.http, &key)
+            .await
+            .map_err(|e| {
+                error!(key = %key, error = %e, "stored frame download failed");
+                (StatusCode::BAD_GATEWAY, "Could not fetch the stored frame".to_string())
+            })?
+            .ok_or((StatusCode::NOT_FOUND, "No stored frame at that key".to_string()))?;
+        uploads::check_frame(&bytes)?;
+        let fallback = state.flags.enabled(&state.db, flags::AI_FALLBACK).await;
+        let opts = ai::DetectOptions { conf: params.conf, imgsz: params.imgsz, fallback };
+        let result = state.ai.detect(&state.http, bytes.clone(), filename, opts).await?;
+        Ok::<_, (StatusCode, String)>((result, bytes))
+    }
+    .await;
+    if detected.is_err() && claimed {
+        uploads::release(&state.db, &key).await;
+    }
+    let (result, bytes) = detected?;
+    maybe_save(&state, &result, &params, &bytes, Some(&key)).await?;
+    Ok(Json(result))
+}
+
+/// `image_key` is where the frame is kept in the upload store, so erasure can remove it
+async fn maybe_save(state: &AppState, result: &Value, params: &SaveParams, frame: &[u8], image_key: Option<&str>) -> Result<(), (StatusCode, String)> {
+    if !params.save.unwrap_or(false) { return Ok(()); }
+    
+    let lat = params.latitude.unwrap_or(0.0);
+    let lon = params.longitude.unwrap_or(0.0);
+    let source = params.source.clone().unwrap_or_else(|| "monitoring".to_string());
+    let source_ref = params.source_ref.clone().unwrap_or_else(|| "live_feed".to_string());
+    
+    if let Some(detections) = result.get("detections").and_then(|v| v.as_array()) {
+        let calibration = calibration::get(&state.db, &source_ref).await?;
+        let img_w = result.get("img_w").and_then(|v| v.as_f64());
+        let img_h = result.get("img_h").and_then(|v| v.as_f64());
+        let cfg = state.settings.current();
+        let mut saved = Vec::new();
+        // One sampling decision per class per frame
+        let mut decisions: HashMap<&str, sampling::Decision> = HashMap::new();
+        // A frame is saved whole or not at all
+        let mut tx = state.db.begin().await.map_err(internal)?;
+        for det in detections {
+            if let (Some(cls), Some(conf)) = (det.get("cls").and_then(|v| v.as_str()), det.get("conf").and_then(|v| v.as_f64())) {
+                let class_id = db::get_or_create_class(&mut *tx, cls).await?;
+                let (conf, raw_conf) = confidence::calibrate(&state.db, class_id, conf as f32).await?;
+                if (conf as f64) < cfg.min_confidence { continue; }
+                let bbox = det.get("bbox_xywh_norm").cloned().or_else(|| det.get("bbox_xywh").cloned());
+                let size = calibration.as_ref().zip(bbox.as_ref()).and_then(|(c, b)| c.assess(b, img_w, img_h));
+                if size.as_ref().is_some_and(|s| s.undersized) && calibration::drop_undersized() { continue; }
+                // Fixed cameras with a geo-referenced homography place the object, not themselves;
+                // drones cast a ray from their pose through the bbox centre
+                let ground = calibration
+                    .as_ref()
+                    .zip(bbox.as_ref())
+                    .and_then(|(c, b)| c.ground_position(b, img_w, img_h))
+                    .or_else(|| drone_target(params, bbox.as_ref()?, img_w?, img_h?));
+
+                let factor = match decisions.entry(cls).or_insert_with(|| sampling::admit(&cfg, &source_ref, cls)) {
+                    sampling::Decision::Store { factor } => *factor,
+                    sampling::Decision::Skip => continue,
+                };
+
+                // Check for duplicate by track_id
+                if let Some(tid) = det.get("track_id").and_then(|v| v.as_str()) {
+                    if db::check_duplicate_track(&mut *tx, &source_ref, tid, cfg.dedup_window_secs).await?.is_some() { continue; }
+                }
+                
+                let mut meta = serde_json::Map::new();
+                if let Some(m) = result.get("model").cloned() { meta.insert("model".to_string(), m); }
+                if let Some(w) = result.get("img_w").cloned() { meta.insert("img_w".to_string(), w); }
+                if let Some(h) = result.get("img_h").cloned() { meta.insert("img_h".to_string(), h); }
+                if result.get("degraded").and_then(|v| v.as_bool()) == Some(true) { meta.insert("degraded".to_string(), json!(true)); }
+                if let Some(y) = params.yaw { meta.insert("yaw".to_string(), json!(y)); }
+                if let Some(a) = params.altitude { meta.insert("altitude".to_string(), json!(a)); }
+                if let Some(tid) = det.get("track_id").and_then(|v| v.as_str()) { meta.insert("track_id".to_string(), json!(tid)); }
+                if factor > 1 { meta.insert("sample_factor".to_string(), json!(factor)); }
+                if let Some(key) = image_key { meta.insert(uploads::META_KEY.to_string(), json!(key)); }
+                if let Some(size) = &size { size.annotate(&mut meta); }
+                let (lat, lon) = match ground {
+                    Some((glat, glon)) => {
+                        meta.insert("camera_position".to_string(), json!([lat, lon]));
+                        (glat as f32, glon as f32)
+                    }
+                    None => (lat, lon),
+                };
+                // Another camera covering the same spot may already have reported it
+                if cfg.fusion_radius_m > 0.0
+                    && db::merge_overlapping(&mut *tx, class_id, &source_ref, time::OffsetDateTime::now_utc(), lat, lon, conf, cfg.fusion_radius_m, cfg.fusion_window_secs).await?.is_some()
+                {
+                    continue;
+                }
+                
+                saved.push(db::insert_event_now(&mut *tx, class_id, conf, raw_conf, lat, lon, &source, &source_ref, bbox, Value::Object(meta)).await?);
+            }
+        }
+        if !saved.is_empty() && raw_inferences::wanted(params.raw) {
+            raw_inferences::store(&mut *tx, &saved, &source_ref, result, frame).await?;
+        }
+        tx.commit().await.map_err(internal)?;
+    }
+    Ok(())
+}
+
+/// Ground position of a drone detection from the frame's pose parameters
+fn drone_target(params: &SaveParams, bbox: &Value, img_w: f64, img_h: f64) -> Option<(f64, f64)> {
+    let pose = geometry::DronePose {
+        lat: params.latitude? as f64,
+        lon: params.longitude? as f64,
+        altitude_m: params.altitude? as f64,
+        yaw_deg: params.yaw? as f64,
+        pitch_deg: params.pitch.unwrap_or(90.0) as f64,
+    };
+    let [x, y, _, _] = calibration::bbox_pixels(bbox, Some(img_w), Some(img_h))?;
+    geometry::drone_target(&pose, x / img_w, y / img_h, img_h / img_w)
+}
+
+// ==================== Event Endpoints ====================
+
+/// POST /events/ingest — store one detection; a retry with the same `Idempotency-Key` gets
+/// the first response back
+async fn ingest_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<IngestEventRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let Some(key) = idempotency::key(&headers)? else {
+        return Ok(Json(ingest(&state, payload).await?));
+    };
+    if let Some(response) = idempotency::replay(&state, &key).await? {
+        return Ok(Json(response));
+    }
+    let response = ingest(&state, payload).await?;
+    idempotency::remember(&state, &key, &response).await?;
+    Ok(Json(response))
+}
+
+async fn ingest(state: &AppState, payload: IngestEventRequest) -> Result<Value, (StatusCode, String)> {
+    let ts = time::OffsetDateTime::parse(&payload.ts, &time::format_description::well_known::Rfc3339)
+        .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid timestamp".to_string()))?;
+    let event = match admit(state, payload, ts, &mut HashMap::new()).await? {
+        Admission::Store(event) => event,
+        Admission::Skip(reason) => return Ok(json!({"status": "skipped", "reason": reason})),
+    };
+    // Cross-source fusion: an overlapping camera's event absorbs this one
+    let cfg = state.settings.current();
+    if cfg.fusion_radius_m > 0.0 {
+        if let Some(id) = state.events.merge_overlapping(&event, cfg.fusion_radius_m, cfg.fusion_window_secs).await? {
+            return Ok(json!({"id": id, "status": "merged"}));
+        }
+    }
+    
+    let event_id = state.events.insert(event).await?;
+    
+    Ok(json!({"id": event_id, "status": "success"}))
+}
+
+/// What the ingest rules make of one event
+pub(crate) enum Admission {
+    Store(NewEvent),
+    /// Not stored, with the reason reported back
+    Skip(&'static str),
+}
+
+/// Track dedup, camera calibration, confidence threshold and sampling for one event, shared by
+/// /events/ingest and /events/import; `classes` caches label lookups across calls
+pub(crate) async fn admit(
+    state: &AppState,
+    payload: IngestEventRequest,
+    ts: time::OffsetDateTime,
+    classes: &mut HashMap<String, i32>,
+) -> Result<Admission, (StatusCode, String)> {
+    let cfg = state.settings.current();
+    // Dedup by track_id: skip if same track seen in this source_ref within the dedup window
+    if let Some(track_id) = track_id(&payload) {
+        if state.events.find_track(&payload.source_ref, track_id, cfg.dedup_window_secs).await?.is_some() {
+            return Ok(Admission::Skip("duplicate track_id"));
+        }
+    }
+
+    // Physical size and ground position from the camera's calibration; the image size comes
+    // with the event's meta
+    let mut extra = serde_json::Map::new();
+    let (mut latitude, mut longitude) = (payload.latitude, payload.longitude);
+    if let (Some(cal), Some(bbox)) = (calibration::get(&state.db, &payload.source_ref).await?, payload.bbox.as_ref()) {
+        let dim = |k: &str| payload.meta.as_ref().and_then(|m| m.get(k)).and_then(|v| v.as_f64());
+        if let Some(size) = cal.assess(bbox, dim("img_w"), dim("img_h")) {
+            if size.undersized && calibration::drop_undersized() {
+                return Ok(Admission::Skip("undersized"));
+            }
+            size.annotate(&mut extra);
+        }
+        if let Some((glat, glon)) = cal.ground_position(bbox, dim("img_w"), dim("img_h")) {
+            extra.insert("camera_position".to_string(), json!([latitude, longitude]));
+            (latitude, longitude) = (glat as f32, glon as f32);
+        }
+    }
+    let class_id = match classes.get(&payload.object_class) {
+        Some(id) => *id,
+        None => {
+            let id = state.events.class_id(&payload.object_class).await?;
+            classes.insert(payload.object_class.clone(), id);
+            id
+        }
+    };
+    // The threshold applies to the calibrated score
+    let (confidence, raw_confidence) = confidence::calibrate(&state.db, class_id, payload.confidence).await?;
+    if (confidence as f64) < cfg.min_confidence {
+        return Ok(Admission::Skip("low confidence"));
+    }
+
+    // Sampling: a source repeating the same class stores only every K-th event
+    match sampling::admit(&cfg, &payload.source_ref, &payload.object_class) {
+        sampling::Decision::Skip => return Ok(Admission::Skip("sampled")),
+        sampling::Decision::Store { factor } if factor > 1 => {
+            extra.insert("sample_factor".to_string(), json!(factor));
+        }
+        sampling::Decision::Store { .. } => {}
+    }
+    let meta = if extra.is_empty() {
+        payload.meta
+    } else {
+        let mut m = match payload.meta { Some(Value::Object(m)) => m, _ => serde_json::Map::new() };
+        m.extend(extra);
+        Some(Value::Object(m))
+    };
+    Ok(Admission::Store(NewEvent {
+        ts, class_id, object_count: payload.object_count, confidence, raw_confidence,
+        latitude, longitude, source: payload.source, source_ref: payload.source_ref,
+        bbox: payload.bbox, meta,
+    }))
+}
+
+/// `meta.track_id` of an ingested event, the key of track dedup
+pub(crate) fn track_id(payload: &IngestEventRequest) -> Option<&str> {
+    payload.meta.as_ref()?.get("track_id")?.as_str()
+}
+
+async fn dashboard_summary(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<Response, (StatusCode, String)> {
+    // The 24-hour and today windows move on their own, so the summary is stale after a minute at most
+    let now = time::OffsetDateTime::now_utc();
+    let minute = now - time::Duration::nanoseconds((now.unix_timestamp_nanos() % 60_000_000_000) as i64);
+    let version = conditional::Version::new(conditional::latest_activity(&state.db).await?.max(minute), &q, &headers);
+    if version.matches(&headers) {
+        return Ok(version.not_modified());
+    }
+    let cal = calendar::from_query(&state.db, &q).await?;
+    let summary: DashboardSummary = db::get_summary(&state.db, &cal, quarantine::include(&q)?).await?;
+    Ok(version.tag(Json(summary).into_response()))
+}
+
+/// GET /dashboard/anomalies — classes whose recent hourly/daily counts spike above their baseline
+async fn dashboard_anomalies(
+    State(state): State<AppState>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let num = |k: &str| q.get(k).and_then(|s| s.parse::<f64>().ok());
+    let (bucket, baseline, recent) = match q.get("bucket").map(|s| s.as_str()) {
+        None | Some("day") => ("day", 28.0, 1.0),
+        Some("hour") => ("hour", 168.0, 24.0),
+        Some(_) => return Err((StatusCode::BAD_REQUEST, "bucket must be hour or day".to_string())),
+    };
+    let params = db::AnomalyParams {
+        bucket,
+        baseline: num("baseline").unwrap_or(baseline).clamp(2.0, 1000.0) as i32,
+        recent: num("recent").unwrap_or(recent).clamp(1.0, 168.0) as i32,
+        z: num("z").unwrap_or(3.0),
+        min_count: num("min_count").unwrap_or(5.0) as i64,
+        per_source: q.get("group").map(|g| g == "source").unwrap_or(false),
+        include_quarantine: quarantine::include(&q)?,
+    };
+    let cal = calendar::from_query(&state.db, &q).await?;
+    Ok(Json(db::get_anomalies(&state.db, &params, &cal).await?))
+}
+
+/// GET /dashboard/timeseries — object counts per local hour/day, gaps filled with zero
+async fn dashboard_timeseries(
+    State(state): State<AppState>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let (bucket, default_span) = match q.get("bucket").map(|s| s.as_str()) {
+        None | Some("hour") => ("hour", time::Duration::hours(24)),
+        Some("day") => ("day", time::Duration::days(30)),
+        Some(_) => return Err((StatusCode::BAD_REQUEST, "bucket must be hour or day".to_string())),
+    };
+    let parse = |k: &str| {
+        q.get(k)
+            .map(|s| time::OffsetDateTime::parse(s, &time::format_description::well_known::Rfc3339).map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid {} timestamp", k))))
+            .transpose()
+    };
+    let to = parse("to")?.unwrap_or_else(time::OffsetDateTime::now_utc);
+    let from = parse("from")?.unwrap_or(to - default_span);
+    if from >= to {
+        return Err((StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+    let span = if bucket == "hour" { time::Duration::hours(1) } else { time::Duration::days(1) };
+    if (to - from) / span > MAX_TIMESERIES_BUCKETS as f64 {
+        return Err((StatusCode::BAD_REQUEST, format!("At most {} buckets per request", MAX_TIMESERIES_BUCKETS)));
+    }
+    let cal = calendar::from_query(&state.db, &q).await?;
+    let points = db::get_timeseries(&state.db, bucket, from, to, q.get("class").map(|s| s.as_str()), &cal, quarantine::include(&q)?).await?;
+    Ok(Json(json!({ "bucket": bucket, "tz": cal.tz, "points": points })))
+}
+
+/// GET /dashboard/calendar?year=&class=&zone= — events per local day for a whole year (default the
+/// current one), as a plain array from January 1st for calendar heatmaps
+async fn dashboard_calendar(
+    State(state): State<AppState>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let year = match q.get("year") {
+        Some(y) => y.trim().parse::<i32>().ok().filter(|y| CALENDAR_YEARS.contains(y)).ok_or((
+            StatusCode::BAD_REQUEST,
+            format!("year must be between {} and {}", CALENDAR_YEARS.start(), CALENDAR_YEARS.end()),
+        ))?,
+        None => time::OffsetDateTime::now_utc().year(),
+    };
+    let cal = calendar::from_query(&state.db, &q).await?;
+    let counts = db::get_calendar_counts(&state.db, year, q.get("class").map(|s| s.as_str()), q.get("zone").map(|s| s.as_str()), &cal, quarantine::include(&q)?)
+        .await?;
+    let total: i64 = counts.iter().sum();
+    let max = counts.iter().copied().max().unwrap_or(0);
+    Ok(Json(json!({ "year": year, "tz": cal.tz, "start": format!("{:04}-01-01", year), "total": total, "max": max, "counts": counts })))
+}
+
+async fn recent_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<Response, (StatusCode, String)> {
+    let limit = q.get("limit").and_then(|s| s.parse::<i64>().ok()).filter(|&n| n > 0 && n <= 500).unwrap_or(100);
+    let fields = Fields::from_query(&q)?;
+    let version = conditional::Version::new(conditional::latest_event(&state.db).await?, &q, &headers);
+    if version.matches(&headers) {
+        return Ok(version.not_modified());
+    }
+    let include_quarantine = quarantine::include(&q)?;
+    let resp = match q.get("collapse").map(|s| s.as_str()) {
+        None => stream_rows(db::stream_recent(state.db.clone(), limit, include_quarantine), wants_ndjson(&q, &headers), fields),
+        Some("track") => {
+            let rows: Vec<RecentEvent> = db::get_recent_collapsed(&state.db, limit, include_quarantine).await?;
+            match fields {
+                Some(fields) => {
+                    let mut value = serde_json::to_value(rows).map_err(internal)?;
+                    fields.retain(&mut value);
+                    Json(value).into_response()
+                }
+                None => Json(rows).into_response(),
+            }
+        }
+        Some(other) => return Err((StatusCode::BAD_REQUEST, format!("Unsupported collapse mode: {}", other))),
+    };
+    Ok(version.tag(resp))
+}
+
+/// GET /events/query?class=&filter=&order_by=&dir=&near=&limit=&fields=&include_quarantine= — events matching a
+/// class and/or filter expression (see `filter`), sorted by `db::ORDER_KEYS`
+async fn query_events(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<Response, (StatusCode, String)> {
+    // Streamed, so exports may go well past the /events/recent cap
+    let limit = q.get("limit").and_then(|s| s.parse::<i64>().ok()).filter(|&n| n > 0 && n <= 50_000).unwrap_or(100);
+    let class_name = q.get("class").cloned();
+    let filter = q.get("filter").map(|f| Filter::parse(f)).transpose().map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid filter: {}", e)))?;
+    let order = db::EventOrder::parse(q.get("order_by").map(String::as_str), q.get("dir").map(String::as_str), q.get("near").map(String::as_str))
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let fields = Fields::from_query(&q)?;
+    let events = db::stream_events(state.db.clone(), class_name, filter, order, limit, quarantine::include(&q)?);
+    Ok(stream_rows(events, wants_ndjson(&q, &headers), fields))
+}
+
+/// GET /events/count?class=&filter=&group_by=class,source,day&include_quarantine= — how many events /events/query
+/// would match, with per-class, per-source and per-day counts
+async fn count_events(
+    State(state): State<AppState>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let filter = q.get("filter").map(|f| Filter::parse(f)).transpose().map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid filter: {}", e)))?;
+    let groups: Vec<&str> = match q.get("group_by") {
+        Some(g) => g.split(',').map(str::trim).filter(|s| !s.is_empty()).collect(),
+        None => db::COUNT_GROUPS.to_vec(),
+    };
+    if let Some(bad) = groups.iter().find(|g| !db::COUNT_GROUPS.contains(g)) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown group_by: {} (expected {})", bad, db::COUNT_GROUPS.join(", "))));
+    }
+    let cal = calendar::from_query(&state.db, &q).await?;
+    let counts = db::count_events(&state.db, q.get("class").map(String::as_str), filter.as_ref(), &groups, &cal, quarantine::include(&q)?).await?;
+    Ok(Json(counts))
+}
+
+#[derive(Serialize)]
+struct EventView {
+    #[serde(flatten)]
+    event: db::EventDetail,
+    comments: Vec<comments::Thread>,
+    attachments: Vec<attachments::Attachment>,
+}
+
+/// GET /events/:id — single event with bbox/meta, comment threads and attachments; encrypted meta fields are decrypted for admins only
+async fn get_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut event = state.events.get(id).await?.ok_or((StatusCode::NOT_FOUND, "Event not found".to_string()))?;
+    if let Some(meta) = event.meta.as_mut() {
+        if auth::require_admin(&headers).is_ok() {
+            crypto::decrypt_meta(meta);
+        } else {
+            crypto::redact_meta(meta);
+        }
+    }
+    let comments = comments::thread(comments::for_event(&state.db, id).await?);
+    let attachments = attachments::for_event(&state.db, id).await?;
+    Ok(Json(EventView { event, comments, attachments }))
+}
+
+// ==================== Admin Endpoints ====================
+
+/// GET /admin/perf — latency percentiles per route and per DB query
+async fn admin_perf(headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let (routes, queries) = perf::snapshot();
+    Ok(Json(json!({
+        "window": perf::WINDOW,
+        "slow_threshold_ms": perf::as_ms(perf::slow_threshold()),
+        "routes": routes,
+        "queries": queries,
+        "ai": ai::stats(),
+    })))
+}
+
+/// POST /admin/meta-keys/rotate — re-encrypt meta fields sealed with older keys under the current key
+async fn rotate_meta_keys(State(state): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let kid = crypto::current_kid().ok_or((StatusCode::BAD_REQUEST, "META_ENCRYPTION_KEYS is not configured".to_string()))?;
+    let (mut scanned, mut updated, mut after) = (0usize, 0usize, None);
+    loop {
+        let rows = db::events_with_stale_meta(&state.db, kid, after, 500).await?;
+        let Some((last, _)) = rows.last() else { break };
+        after = Some(*last);
+        scanned += rows.len();
+        for (id, mut meta) in rows {
+            if crypto::reencrypt_meta(&mut meta) {
+                db::update_event_meta(&state.db, id, &meta).await?;
+                updated += 1;
+            }
+        }
+    }
+    info!(user = %claims.username, kid, scanned, updated, "meta encryption keys rotated");
+    Ok(Json(json!({ "kid": kid, "scanned": scanned, "updated": updated })))
+}
+
+// ==================== Streaming Helpers ====================
+
+/// NDJSON via `format=ndjson` or `Accept: application/x-ndjson`, JSON array otherwise
+fn wants_ndjson(q: &HashMap<String, String>, headers: &HeaderMap) -> bool {
+    q.get("format").map(|f| f == "ndjson").unwrap_or(false)
+        || headers
+            .get(header::ACCEPT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.contains("application/x-ndjson"))
+            .unwrap_or(false)
+}
+
+/// Serialize rows into a chunked body as they arrive from the database
+fn stream_rows<T, S>(rows: S, ndjson: bool, fields: Option<Fields>) -> Response
+where
+    T: Serialize + Send + 'static,
+    S: Stream<Item = Result<T, sqlx::Error>> + Send + 'static,
+{
+    let (open, close): (&'static [u8], &'static [u8]) = if ndjson { (b"", b"") } else { (b"[", b"]") };
+    let locale = i18n::current();
+    let mut first = true;
+    let rows = rows.map(move |row| -> Result<Bytes, BoxError> {
+        let row = row.map_err(|e| {
+            error!(error=%e, "row stream failed");
+            e
+        })?;
+        let mut buf = Vec::with_capacity(256);
+        if !ndjson && !std::mem::take(&mut first) {
+            buf.push(b',');
+        }
+        if locale.is_none() && fields.is_none() {
+            serde_json::to_writer(&mut buf, &row)?;
+        } else {
+            let mut value = serde_json::to_value(&row)?;
+            if let Some(fields) = &fields {
+                fields.retain(&mut value);
+            }
+            if let Some(locale) = &locale {
+                locale.apply(&mut value);
+            }
+            serde_json::to_writer(&mut buf, &value)?;
+        }
+        if ndjson {
+            buf.push(b'\n');
+        }
+        Ok(Bytes::from(buf))
+    });
+    let body = stream::once(ready(Ok(Bytes::from_static(open))))
+        .chain(rows)
+        .chain(stream::once(ready(Ok(Bytes::from_static(close)))));
+    let content_type = if ndjson { "application/x-ndjson" } else { "application/json" };
+    ([(header::CONTENT_TYPE, content_type)], Body::from_stream(body)).into_response()
+}
@@ -0,0 +1,224 @@
+//! Event lifecycle for FOD Detection Backend
+//! detected → confirmed → dispatched → removed → verified_clear (or false_positive), with transition history
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    auth,
+    db::{self, internal, RecentEvent},
+    fields::Fields,
+    perf, AppState,
+};
+
+/// Every state, in lifecycle order
+pub const STATES: [&str; 6] = ["detected", "confirmed", "dispatched", "removed", "verified_clear", "false_positive"];
+/// States after which nothing more happens to an event
+pub const CLOSED_STATES: [&str; 2] = ["verified_clear", "false_positive"];
+/// Review outcome meaning there was nothing there
+pub const FALSE_POSITIVE: &str = "false_positive";
+
+/// Allowed (from, to) pairs; a failed clear check sends the crew back out. Reviewers dismiss
+/// detections, and crews sent out can report finding nothing.
+const TRANSITIONS: [(&str, &str); 7] = [
+    ("detected", "confirmed"),
+    ("confirmed", "dispatched"),
+    ("dispatched", "removed"),
+    ("removed", "verified_clear"),
+    ("removed", "dispatched"),
+    ("detected", "false_positive"),
+    ("dispatched", "false_positive"),
+];
+
+pub fn can_transition(from: &str, to: &str) -> bool {
+    TRANSITIONS.contains(&(from, to))
+}
+
+// ==================== Types ====================
+
+#[derive(Deserialize)]
+pub struct TransitionRequest {
+    pub state: String,
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Transition {
+    pub from_state: String,
+    pub to_state: String,
+    pub changed_by: String,
+    pub note: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+}
+
+/// Event with its current lifecycle state
+#[derive(Serialize, FromRow)]
+pub struct StatefulEvent {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub event: RecentEvent,
+    pub state: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub state_changed_at: Option<OffsetDateTime>,
+}
+
+// ==================== Queries ====================
+
+/// Move an event to `to`, recording who did it. The row is locked so concurrent
+/// transitions are checked against the state they actually replace.
+pub async fn transition(
+    db: &PgPool,
+    id: Uuid,
+    to: &str,
+    changed_by: &str,
+    note: Option<&str>,
+) -> Result<Transition, (StatusCode, String)> {
+    let mut tx = db.begin().await.map_err(internal)?;
+    let recorded = transition_in(&mut tx, id, to, changed_by, note).await?;
+    tx.commit().await.map_err(internal)?;
+    Ok(recorded)
+}
+
+/// `transition` inside the caller's transaction, for changes that go with other writes
+pub async fn transition_in(
+    conn: &mut PgConnection,
+    id: Uuid,
+    to: &str,
+    changed_by: &str,
+    note: Option<&str>,
+) -> Result<Transition, (StatusCode, String)> {
+    if !STATES.contains(&to) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown state {:?}, expected one of {}", to, STATES.join(", "))));
+    }
+    let from: String = sqlx::query_scalar("SELECT state FROM events WHERE id = $1 FOR UPDATE")
+        .bind(id)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "Event not found".to_string()))?;
+    if !can_transition(&from, to) {
+        return Err((StatusCode::CONFLICT, format!("Cannot move event from {} to {}", from, to)));
+    }
+
+    sqlx::query("UPDATE events SET state = $2, state_changed_at = NOW() WHERE id = $1")
+        .bind(id)
+        .bind(to)
+        .execute(&mut *conn)
+        .await
+        .map_err(internal)?;
+    let recorded = sqlx::query_as::<_, Transition>(
+        r#"
+        INSERT INTO event_state_transitions (event_id, from_state, to_state, changed_by, note)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING from_state, to_state, changed_by, note, created_at
+        "#
+    )
+    .bind(id)
+    .bind(&from)
+    .bind(to)
+    .bind(changed_by)
+    .bind(note)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(internal)?;
+
+    info!(event_id = %id, from = %from, to = %to, changed_by, "event state changed");
+    Ok(recorded)
+}
+
+/// Current state, None if the event doesn't exist
+pub async fn current_state(db: &PgPool, id: Uuid) -> Result<Option<String>, (StatusCode, String)> {
+    let q = sqlx::query_scalar("SELECT state FROM events WHERE id = $1").bind(id).fetch_optional(db);
+    perf::timed("event_state", || format!("id={}", id), q).await.map_err(internal)
+}
+
+pub async fn history(db: &PgPool, id: Uuid) -> Result<Vec<Transition>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, Transition>(
+        r#"
+        SELECT from_state, to_state, changed_by, note, created_at
+        FROM event_state_transitions
+        WHERE event_id = $1
+        ORDER BY created_at, id
+        "#
+    )
+    .bind(id)
+    .fetch_all(db);
+    perf::timed("event_history", || format!("id={}", id), q).await.map_err(internal)
+}
+
+/// Events in any of `states`, newest first
+pub async fn events_in_states(db: &PgPool, states: &[&str], limit: i64) -> Result<Vec<StatefulEvent>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        SELECT e.id, e.ts, fc.name as class_name, e.object_count, e.confidence,
+               e.latitude, e.longitude, e.source, e.source_ref, {}, e.state, e.state_changed_at
+        FROM events e
+        JOIN fod_classes fc ON e.class_id = fc.id
+        WHERE e.state = ANY($1)
+        ORDER BY e.ts DESC
+        LIMIT $2
+        "#,
+        db::SEVERITY_COLUMN
+    );
+    let q = sqlx::query_as::<_, StatefulEvent>(&sql)
+    .bind(states)
+    .bind(limit)
+    .fetch_all(db);
+    perf::timed("events_in_states", || format!("states={:?} limit={}", states, limit), q).await.map_err(internal)
+}
+
+// ==================== Handlers ====================
+
+/// GET /events?state=open|closed|<state>[,<state>...]&limit&fields — events by lifecycle state
+pub async fn list_handler(
+    State(st): State<AppState>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let limit = q.get("limit").and_then(|s| s.parse::<i64>().ok()).filter(|&n| n > 0 && n <= 1000).unwrap_or(100);
+    let mut states: Vec<&str> = Vec::new();
+    for s in q.get("state").map(|s| s.as_str()).unwrap_or("open").split(',').map(str::trim) {
+        match s {
+            "open" => states.extend(STATES.iter().filter(|s| !CLOSED_STATES.contains(s))),
+            "closed" => states.extend(CLOSED_STATES),
+            s if STATES.contains(&s) => states.push(s),
+            s => return Err((StatusCode::BAD_REQUEST, format!("Unknown state {:?}", s))),
+        }
+    }
+    let fields = Fields::from_query(&q)?;
+    let events = events_in_states(&st.db, &states, limit).await?;
+    let Some(fields) = fields else { return Ok(Json(events).into_response()) };
+    let mut value = serde_json::to_value(events).map_err(internal)?;
+    fields.retain(&mut value);
+    Ok(Json(value).into_response())
+}
+
+/// POST /events/:id/state — move an event along its lifecycle
+pub async fn transition_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    Json(req): Json<TransitionRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let note = req.note.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    Ok(Json(transition(&st.db, id, &req.state, &claims.username, note).await?))
+}
+
+/// GET /events/:id/history — state transitions, oldest first
+pub async fn history_handler(
+    State(st): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    Ok(Json(history(&st.db, id).await?))
+}
@@ -0,0 +1,330 @@
+//! Live event fan-out for FOD Detection Backend
+//! Postgres LISTEN/NOTIFY, or Redis pub/sub when configured, relayed to SSE and WebSocket
+//! clients on every instance
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    Json,
+};
+use futures::{stream, Stream, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{postgres::PgListener, FromRow, PgPool};
+use std::{collections::HashMap, convert::Infallible, sync::OnceLock, time::Duration};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::Instant,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    db::{internal, quarantine_clause, RecentEvent, SEVERITY_COLUMN},
+    jobs, perf, quarantine,
+    shared::Redis,
+    AppState,
+};
+
+/// Channel the `events_notify` trigger publishes on, and the Redis channel it is relayed to
+pub const CHANNEL: &str = "fod_events";
+/// Events buffered per slow client before it starts skipping
+const CLIENT_BUFFER: usize = 1024;
+/// Longest `/events/changes` wait; proxies tend to cut idle requests at 60s
+const MAX_WAIT: Duration = Duration::from_secs(55);
+/// Long polls re-query at least this often, in case a notification was missed
+const RECHECK: Duration = Duration::from_secs(1);
+/// Wait before reconnecting a failed listener or subscription
+const RETRY: Duration = Duration::from_secs(5);
+
+fn sender() -> &'static broadcast::Sender<String> {
+    static TX: OnceLock<broadcast::Sender<String>> = OnceLock::new();
+    TX.get_or_init(|| broadcast::channel(CLIENT_BUFFER).0)
+}
+
+// ==================== Listener ====================
+
+/// Relay notifications to local subscribers. Each replica listens to Postgres itself unless
+/// Redis is configured; then one replica at a time republishes the notifications on Redis and
+/// every replica subscribes there, so Postgres holds one LISTEN connection rather than one per
+/// replica. Events sent while a connection is down are not replayed.
+pub fn spawn_listener(state: AppState) {
+    if state.shared.redis().is_some() {
+        spawn_redis_relay(state);
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = relay_local(&state.db).await {
+                error!(error = %e, "event listener failed, retrying");
+                tokio::time::sleep(RETRY).await;
+            }
+        }
+    });
+}
+
+fn spawn_redis_relay(state: AppState) {
+    let publisher = state.clone();
+    tokio::spawn(async move {
+        let Some(redis) = publisher.shared.redis() else { return };
+        loop {
+            match jobs::run_singleton(&publisher.db, "event_relay", relay_to_redis(&publisher.db, redis)).await {
+                // Another replica relays; take over if it stops
+                Ok(Some(Ok(()))) | Ok(None) => {}
+                Ok(Some(Err(e))) | Err(e) => error!(error = %e, "event relay to Redis failed, retrying"),
+            }
+            tokio::time::sleep(RETRY).await;
+        }
+    });
+    tokio::spawn(async move {
+        let Some(redis) = state.shared.redis() else { return };
+        loop {
+            // No receivers is fine, nobody is watching
+            match redis.subscribe(CHANNEL, |payload| drop(sender().send(payload))).await {
+                Ok(()) => warn!("event subscription on Redis dropped, resubscribing; events in the gap are missed"),
+                Err(e) => error!(error = %e, "event subscription on Redis failed, retrying"),
+            }
+            tokio::time::sleep(RETRY).await;
+        }
+    });
+}
+
+async fn listen(db: &PgPool) -> Result<PgListener, sqlx::Error> {
+    let mut listener = PgListener::connect_with(db).await?;
+    listener.listen(CHANNEL).await?;
+    info!(channel = CHANNEL, "listening for event notifications");
+    Ok(listener)
+}
+
+/// Next notification payload; PgListener reconnects on its own, None when it had to
+async fn recv(listener: &mut PgListener) -> Result<Option<String>, sqlx::Error> {
+    let n = listener.try_recv().await?;
+    if n.is_none() {
+        warn!("event listener connection lost, reconnecting; events in the gap are missed");
+    }
+    Ok(n.map(|n| n.payload().to_string()))
+}
+
+/// Notifications straight to this replica's subscribers, until the listener fails
+async fn relay_local(db: &PgPool) -> Result<(), sqlx::Error> {
+    let mut listener = listen(db).await?;
+    loop {
+        if let Some(payload) = recv(&mut listener).await? {
+            // No receivers is fine, nobody is watching
+            drop(sender().send(payload));
+        }
+    }
+}
+
+/// Notifications onto the Redis channel every replica subscribes to, until the listener fails
+async fn relay_to_redis(db: &PgPool, redis: &Redis) -> Result<(), sqlx::Error> {
+    let mut listener = listen(db).await?;
+    loop {
+        if let Some(payload) = recv(&mut listener).await? {
+            if let Err(e) = redis.publish(CHANNEL, &payload).await {
+                warn!(error = %e, "event notification not relayed to Redis");
+            }
+        }
+    }
+}
+
+// ==================== Subscribers ====================
+
+/// Optional `class` filter on the payload's class_name; events of quarantined sources only
+/// with `include_quarantine`
+fn matches(payload: &str, class: Option<&str>, include_quarantine: bool) -> bool {
+    if class.is_none() && include_quarantine {
+        return true;
+    }
+    let Ok(v) = serde_json::from_str::<Value>(payload) else {
+        return false;
+    };
+    if !include_quarantine && v.get("quarantined").and_then(|q| q.as_bool()).unwrap_or(false) {
+        return false;
+    }
+    class.is_none_or(|class| v.get("class_name").and_then(|c| c.as_str()).is_some_and(|c| c.eq_ignore_ascii_case(class)))
+}
+
+/// Next payload for this subscriber, None once the channel closes
+async fn next(rx: &mut broadcast::Receiver<String>, class: Option<&str>, include_quarantine: bool) -> Option<String> {
+    loop {
+        match rx.recv().await {
+            Ok(payload) if matches(&payload, class, include_quarantine) => return Some(payload),
+            Ok(_) => {}
+            Err(RecvError::Lagged(n)) => warn!(skipped = n, "stream client too slow, skipping events"),
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Receiver of every relayed notification payload, for in-process consumers
+pub fn subscribe() -> broadcast::Receiver<String> {
+    sender().subscribe()
+}
+
+fn payloads(class: Option<String>, include_quarantine: bool) -> impl Stream<Item = String> {
+    stream::unfold((sender().subscribe(), class), move |(mut rx, class)| async move {
+        let payload = next(&mut rx, class.as_deref(), include_quarantine).await?;
+        Some((payload, (rx, class)))
+    })
+}
+
+// ==================== Handlers ====================
+
+/// GET /events/stream — Server-Sent Events, one `event` per inserted row (`?class=` filters)
+pub async fn sse_handler(Query(q): Query<HashMap<String, String>>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let events = payloads(q.get("class").cloned(), quarantine::include(&q)?)
+        .map(|p| Ok::<_, Infallible>(Event::default().event("event").data(p)));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// GET /events/ws — the same feed over a WebSocket, one JSON text message per event
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let class = q.get("class").cloned();
+    let include_quarantine = quarantine::include(&q)?;
+    Ok(ws.on_upgrade(move |socket| relay(socket, class, include_quarantine)))
+}
+
+async fn relay(mut socket: WebSocket, class: Option<String>, include_quarantine: bool) {
+    let mut rx = sender().subscribe();
+    loop {
+        tokio::select! {
+            payload = next(&mut rx, class.as_deref(), include_quarantine) => {
+                let Some(payload) = payload else { break };
+                if socket.send(Message::Text(payload)).await.is_err() {
+                    break;
+                }
+            }
+            // Stop when the client closes or errors; inbound messages are ignored
+            msg = socket.recv() => {
+                if !matches!(msg, Some(Ok(_))) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// ==================== Long Polling ====================
+
+#[derive(FromRow)]
+struct Change {
+    #[sqlx(flatten)]
+    event: RecentEvent,
+    seq: i64,
+}
+
+/// One `/events/changes` response
+#[derive(Serialize)]
+pub struct Changes {
+    pub events: Vec<RecentEvent>,
+    /// Pass back as `since` for the next batch
+    pub cursor: String,
+    /// The batch was cut at `limit`; ask again at once
+    pub more: bool,
+}
+
+/// Where a client resumes: after a cursor, or events seen after a time (first call)
+enum Since {
+    Cursor(i64),
+    Time(OffsetDateTime),
+}
+
+fn parse_since(s: &str) -> Result<Since, String> {
+    if let Ok(cursor) = s.parse::<i64>() {
+        return Ok(Since::Cursor(cursor));
+    }
+    OffsetDateTime::parse(s, &Rfc3339).map(Since::Time).map_err(|_| format!("Invalid since {:?}: expected a cursor or RFC 3339 time", s))
+}
+
+/// `25s`, `500ms` or plain seconds
+pub fn parse_wait(s: &str) -> Result<Duration, String> {
+    let wait = if let Some(ms) = s.strip_suffix("ms") {
+        ms.parse().map(Duration::from_millis)
+    } else {
+        s.strip_suffix('s').unwrap_or(s).parse().map(Duration::from_secs)
+    };
+    wait.map_err(|_| format!("Invalid wait {:?}", s))
+}
+
+async fn latest_seq(db: &PgPool) -> Result<i64, (StatusCode, String)> {
+    let q = sqlx::query_scalar("SELECT COALESCE(MAX(seq), 0) FROM events").fetch_one(db);
+    perf::timed("latest_seq", String::new, q).await.map_err(internal)
+}
+
+/// Events after `after` (and after `ts`) up to the newest one, in insertion order. The
+/// cursor is that newest seq unless the batch was cut short, so skipped rows never reappear.
+async fn changes(
+    db: &PgPool,
+    after: i64,
+    ts: Option<OffsetDateTime>,
+    class: Option<&str>,
+    limit: i64,
+    include_quarantine: bool,
+) -> Result<Changes, (StatusCode, String)> {
+    let upto = latest_seq(db).await?;
+    let sql = format!(
+        r#"
+        SELECT e.id, e.ts, fc.name as class_name, e.object_count, e.confidence,
+               e.latitude, e.longitude, e.source, e.source_ref, {}, e.seq
+        FROM events e
+        JOIN fod_classes fc ON e.class_id = fc.id
+        WHERE e.seq > $1 AND e.seq <= $2
+          AND ($3::TIMESTAMPTZ IS NULL OR e.ts > $3)
+          AND ($4::TEXT IS NULL OR LOWER(fc.name) = LOWER($4)){}
+        ORDER BY e.seq
+        LIMIT $5
+        "#,
+        SEVERITY_COLUMN,
+        quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, Change>(&sql).bind(after).bind(upto).bind(ts).bind(class).bind(limit).fetch_all(db);
+    let rows = perf::timed("event_changes", || format!("after={} upto={} class={:?}", after, upto, class), q).await.map_err(internal)?;
+    let more = rows.len() as i64 == limit;
+    let cursor = if more { rows.last().map_or(upto, |r| r.seq) } else { upto };
+    Ok(Changes { events: rows.into_iter().map(|r| r.event).collect(), cursor: cursor.to_string(), more })
+}
+
+/// GET /events/changes?since=<ts|cursor>&wait=25s&class=&limit= — events inserted after
+/// `since`, waiting up to `wait` for the first one; for clients that cannot hold a
+/// WebSocket or SSE stream. Without `since` only events from now on are returned.
+pub async fn changes_handler(
+    State(st): State<AppState>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let bad = |e: String| (StatusCode::BAD_REQUEST, e);
+    let since = q.get("since").map(|s| parse_since(s)).transpose().map_err(bad)?;
+    let wait = q.get("wait").map(|s| parse_wait(s)).transpose().map_err(bad)?.unwrap_or(Duration::from_secs(25)).min(MAX_WAIT);
+    let limit = q.get("limit").and_then(|s| s.parse::<i64>().ok()).filter(|&n| n > 0 && n <= 1000).unwrap_or(100);
+    let class = q.get("class").map(String::as_str);
+    let include_quarantine = quarantine::include(&q)?;
+
+    // Subscribe before the first query so an insert in between still wakes us
+    let mut rx = sender().subscribe();
+    let deadline = Instant::now() + wait;
+    let (mut after, ts) = match since {
+        Some(Since::Cursor(c)) => (c, None),
+        Some(Since::Time(t)) => (0, Some(t)),
+        None => (latest_seq(&st.db).await?, None),
+    };
+    loop {
+        let batch = changes(&st.db, after, ts, class, limit, include_quarantine).await?;
+        let now = Instant::now();
+        if !batch.events.is_empty() || now >= deadline {
+            return Ok(Json(batch));
+        }
+        // Rows skipped by `class`, `ts` or quarantine need not be looked at again
+        after = batch.cursor.parse().unwrap_or(after);
+        let _ = tokio::time::timeout((deadline - now).min(RECHECK), next(&mut rx, class, include_quarantine)).await;
+    }
+}
@@ -0,0 +1,111 @@
+//! Logging setup for FOD Detection Backend
+//! Text or JSON output (LOG_FORMAT), per-request spans and credential redaction
+
+use axum::{body::Body, http::Request};
+use regex::Regex;
+use std::{
+    borrow::Cow,
+    env,
+    io::{self, Write},
+    sync::OnceLock,
+};
+use tracing::Span;
+use tracing_subscriber::{fmt, EnvFilter};
+
+// ==================== Setup ====================
+
+/// Install the global subscriber, `LOG_FORMAT=json` switches to structured output
+pub fn init() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let json = env::var("LOG_FORMAT").map(|v| v.eq_ignore_ascii_case("json")).unwrap_or(false);
+    if json {
+        fmt()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_env_filter(filter)
+            .with_writer(|| RedactingWriter)
+            .init();
+    } else {
+        fmt().with_env_filter(filter).with_writer(|| RedactingWriter).init();
+    }
+}
+
+/// Site identifier attached to every request span (env `SITE_ID`)
+pub fn site_id() -> &'static str {
+    static SITE: OnceLock<String> = OnceLock::new();
+    SITE.get_or_init(|| env::var("SITE_ID").unwrap_or_else(|_| "default".to_string()))
+}
+
+/// Root span for each HTTP request, carries request_id and site on every log line, and
+/// api_key_id once the request has authenticated
+pub fn request_span(req: &Request<Body>) -> Span {
+    let request_id = req
+        .headers()
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-");
+    tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        request_id = %request_id,
+        site = %site_id(),
+        api_key_id = tracing::field::Empty,
+    )
+}
+
+/// Fill the request span's api_key_id with the signing device id or the token subject
+pub fn record_api_key_id(id: &str) {
+    Span::current().record("api_key_id", id);
+}
+
+// ==================== Redaction ====================
+
+fn patterns() -> &'static [(Regex, &'static str)] {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        vec![
+            // user:password@ in connection strings and URLs
+            (Regex::new(r"(://)[^/\s:@]+:[^/\s@]+@").unwrap(), "${1}[REDACTED]@"),
+            // JWTs
+            (Regex::new(r"eyJ[A-Za-z0-9_-]*\.[A-Za-z0-9_-]*\.[A-Za-z0-9_-]*").unwrap(), "[REDACTED_JWT]"),
+            // Authorization: Bearer <token>
+            (Regex::new(r"(?i)(bearer\s+)[A-Za-z0-9\-._~+/]+=*").unwrap(), "${1}[REDACTED]"),
+            // key=value / "key":"value" for sensitive names, tolerating ANSI color codes
+            (
+                Regex::new(
+                    r#"(?i)((?:api[_-]?key|token|password|secret|authorization)(?:\x1b\[[0-9;]*m)*(?:=|"\s*:\s*"?)(?:\x1b\[[0-9;]*m)*)[^\s",&\x1b]+"#,
+                )
+                .unwrap(),
+                "${1}[REDACTED]",
+            ),
+        ]
+    })
+}
+
+/// Scrub credentials from a formatted log line
+pub fn redact(line: &str) -> Cow<'_, str> {
+    let mut out = Cow::Borrowed(line);
+    for (re, replacement) in patterns() {
+        if re.is_match(&out) {
+            out = Cow::Owned(re.replace_all(&out, *replacement).into_owned());
+        }
+    }
+    out
+}
+
+/// Stdout writer that redacts each formatted event before it leaves the process
+struct RedactingWriter;
+
+impl Write for RedactingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let line = String::from_utf8_lossy(buf);
+        io::stdout().write_all(redact(&line).as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()
+    }
+}
@@ -0,0 +1,283 @@
+//! Migration management for FOD Detection Backend
+//! Startup policy, status listing, runtime trigger, read-only guard and index usage
+
+use axum::{
+    extract::{MatchedPath, Query, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{migrate::Migrator, FromRow, PgPool};
+use std::{collections::HashMap, env, sync::atomic::Ordering, time::Duration};
+use time::OffsetDateTime;
+use tracing::{error, info, warn};
+
+use crate::{auth, db::internal, perf, AppState};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+// ==================== Startup Mode ====================
+
+/// How migrations are handled at boot (env `MIGRATION_MODE`)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MigrationMode {
+    /// Run at boot, abort on failure (default)
+    Auto,
+    /// Run at boot, fall back to read-only on failure
+    Lenient,
+    /// Never run at boot, serve read-only while anything is pending
+    Manual,
+}
+
+impl MigrationMode {
+    pub fn from_env() -> Self {
+        match env::var("MIGRATION_MODE").unwrap_or_default().to_lowercase().as_str() {
+            "lenient" => MigrationMode::Lenient,
+            "manual" => MigrationMode::Manual,
+            _ => MigrationMode::Auto,
+        }
+    }
+}
+
+/// Apply the startup policy, returns true if the server must start read-only
+pub async fn run_at_startup(db: &PgPool, mode: MigrationMode) -> bool {
+    match mode {
+        MigrationMode::Auto => {
+            MIGRATOR.run(db).await.expect("Failed to run migrations");
+            false
+        }
+        MigrationMode::Lenient => match MIGRATOR.run(db).await {
+            Ok(()) => false,
+            Err(e) => {
+                error!(error=%e, "migrations failed, starting in read-only mode");
+                true
+            }
+        },
+        MigrationMode::Manual => {
+            let pending = pending_count(db).await.unwrap_or(usize::MAX);
+            if pending > 0 {
+                warn!(pending, "migrations pending, starting in read-only mode");
+            }
+            pending > 0
+        }
+    }
+}
+
+/// How often a read-only instance checks whether its migrations have been applied
+const WRITABLE_RECHECK: Duration = Duration::from_secs(1);
+
+/// Start `writers` once the schema is current: right away, or for a read-only start after
+/// `POST /admin/migrations/run` on this or any other replica, so no background job touches
+/// tables that may not exist yet. Leaves read-only mode when the database has nothing pending.
+pub fn when_writable(state: AppState, writers: impl FnOnce(AppState) + Send + 'static) {
+    if !state.read_only.load(Ordering::Relaxed) {
+        writers(state);
+        return;
+    }
+    warn!("read-only mode, background jobs wait for migrations");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(WRITABLE_RECHECK);
+        while state.read_only.load(Ordering::Relaxed) {
+            tick.tick().await;
+            match pending_count(&state.db).await {
+                Ok(0) => state.read_only.store(false, Ordering::Relaxed),
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, "could not check for pending migrations"),
+            }
+        }
+        info!("migrations applied, starting background jobs");
+        writers(state);
+    });
+}
+
+// ==================== Status ====================
+
+#[derive(Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+    pub success: Option<bool>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub installed_on: Option<OffsetDateTime>,
+}
+
+/// Applied rows from _sqlx_migrations, empty if the table does not exist yet
+async fn applied_migrations(db: &PgPool) -> Result<HashMap<i64, (bool, OffsetDateTime)>, sqlx::Error> {
+    let exists: bool = sqlx::query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(db)
+        .await?;
+    if !exists {
+        return Ok(HashMap::new());
+    }
+    let rows: Vec<(i64, bool, OffsetDateTime)> =
+        sqlx::query_as("SELECT version, success, installed_on FROM _sqlx_migrations")
+            .fetch_all(db)
+            .await?;
+    Ok(rows.into_iter().map(|(v, ok, at)| (v, (ok, at))).collect())
+}
+
+async fn pending_count(db: &PgPool) -> Result<usize, sqlx::Error> {
+    let applied = applied_migrations(db).await?;
+    Ok(MIGRATOR.iter().filter(|m| !matches!(applied.get(&m.version), Some((true, _)))).count())
+}
+
+pub async fn list_status(db: &PgPool) -> Result<Vec<MigrationStatus>, (StatusCode, String)> {
+    let applied = applied_migrations(db).await.map_err(internal)?;
+    Ok(MIGRATOR
+        .iter()
+        .map(|m| {
+            let row = applied.get(&m.version);
+            MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                applied: matches!(row, Some((true, _))),
+                success: row.map(|(ok, _)| *ok),
+                installed_on: row.map(|(_, at)| *at),
+            }
+        })
+        .collect())
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/migrations — applied/pending migrations
+pub async fn status_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let migrations = list_status(&st.db).await?;
+    let pending = migrations.iter().filter(|m| !m.applied).count();
+    Ok(Json(json!({
+        "read_only": st.read_only.load(Ordering::Relaxed),
+        "pending": pending,
+        "migrations": migrations,
+    })))
+}
+
+/// POST /admin/migrations/run — apply pending migrations and leave read-only mode
+pub async fn run_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    info!(user=%claims.username, "running migrations on demand");
+    MIGRATOR.run(&st.db).await.map_err(internal)?;
+    st.read_only.store(false, Ordering::Relaxed);
+    let migrations = list_status(&st.db).await?;
+    Ok(Json(json!({ "ok": true, "read_only": false, "migrations": migrations })))
+}
+
+// ==================== Index Usage ====================
+
+#[derive(Serialize, FromRow)]
+pub struct IndexUsage {
+    pub table: String,
+    pub index: String,
+    /// Scans since the statistics were last reset
+    pub scans: i64,
+    pub tuples_read: i64,
+    pub tuples_fetched: i64,
+    pub size_bytes: i64,
+    pub unique: bool,
+    pub definition: String,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct TableScans {
+    pub table: String,
+    pub seq_scans: i64,
+    pub seq_tuples_read: i64,
+    pub index_scans: i64,
+    pub live_rows: i64,
+}
+
+#[derive(Deserialize)]
+pub struct IndexParams {
+    pub table: Option<String>,
+}
+
+pub async fn index_usage(db: &PgPool, table: Option<&str>) -> Result<Vec<IndexUsage>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, IndexUsage>(
+        r#"
+        SELECT s.relname::TEXT AS table, s.indexrelname::TEXT AS index, s.idx_scan AS scans,
+               s.idx_tup_read AS tuples_read, s.idx_tup_fetch AS tuples_fetched,
+               pg_relation_size(s.indexrelid) AS size_bytes, i.indisunique AS unique,
+               pg_get_indexdef(s.indexrelid) AS definition
+        FROM pg_stat_user_indexes s
+        JOIN pg_index i ON i.indexrelid = s.indexrelid
+        WHERE s.schemaname = current_schema() AND ($1::TEXT IS NULL OR s.relname = $1)
+        ORDER BY s.relname, s.idx_scan DESC, s.indexrelname
+        "#,
+    )
+    .bind(table)
+    .fetch_all(db);
+    perf::timed("index_usage", || format!("table={:?}", table), q).await.map_err(internal)
+}
+
+async fn table_scans(db: &PgPool, table: Option<&str>) -> Result<Vec<TableScans>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, TableScans>(
+        r#"
+        SELECT relname::TEXT AS table, seq_scan AS seq_scans, seq_tup_read AS seq_tuples_read,
+               COALESCE(idx_scan, 0) AS index_scans, n_live_tup AS live_rows
+        FROM pg_stat_user_tables
+        WHERE schemaname = current_schema() AND ($1::TEXT IS NULL OR relname = $1)
+        ORDER BY relname
+        "#,
+    )
+    .bind(table)
+    .fetch_all(db);
+    perf::timed("table_scans", || format!("table={:?}", table), q).await.map_err(internal)
+}
+
+/// GET /admin/indexes?table= — scans, size and definition of every index, and sequential vs
+/// index scans per table, since the statistics were last reset (admin)
+pub async fn indexes_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<IndexParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let table = p.table.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let stats_reset: Option<OffsetDateTime> = sqlx::query_scalar("SELECT stats_reset FROM pg_stat_database WHERE datname = current_database()")
+        .fetch_one(&st.db)
+        .await
+        .map_err(internal)?;
+    let indexes = index_usage(&st.db, table).await?;
+    // Never scanned and not enforcing uniqueness: candidates for dropping
+    let unused: Vec<&str> = indexes.iter().filter(|i| i.scans == 0 && !i.unique).map(|i| i.index.as_str()).collect();
+    Ok(Json(json!({
+        "stats_reset": stats_reset.map(|t| t.format(&time::format_description::well_known::Rfc3339)).transpose().map_err(internal)?,
+        "tables": table_scans(&st.db, table).await?,
+        "indexes": indexes,
+        "unused": unused,
+    })))
+}
+
+// ==================== Read-only Guard ====================
+
+/// GET routes that write all the same, e.g. marking commands delivered
+const WRITING_READS: [&str; 1] = ["/devices/:id/commands/next"];
+
+/// Requests the guard lets through while migrations are pending: reads that don't write, login
+/// (access token only, see `auth::login_handler`) and the migration trigger
+fn allowed_read_only(req: &Request) -> bool {
+    if matches!(req.uri().path(), "/auth/login" | "/admin/migrations/run") {
+        return true;
+    }
+    matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        && !req.extensions().get::<MatchedPath>().is_some_and(|p| WRITING_READS.contains(&p.as_str()))
+}
+
+/// Reject writes while migrations are pending
+pub async fn read_only_guard(State(st): State<AppState>, req: Request, next: Next) -> Response {
+    if st.read_only.load(Ordering::Relaxed) && !allowed_read_only(&req) {
+        return (StatusCode::SERVICE_UNAVAILABLE, "Read-only mode: database migrations pending".to_string())
+            .into_response();
+    }
+    next.run(req).await
+}
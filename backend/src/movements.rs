@@ -0,0 +1,210 @@
+//! Aircraft movements for FOD Detection Backend
+//! Arrival/departure ingestion (JSON or CSV) and FOD density per 1,000 movements per runway
+
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use std::collections::HashMap;
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use tracing::info;
+
+use crate::{
+    auth,
+    db::{internal, quarantine_clause},
+    import::ImportSummary,
+    perf, quarantine, AppState,
+};
+
+/// Rows per INSERT statement
+const BATCH_SIZE: usize = 500;
+pub const KINDS: [&str; 2] = ["arrival", "departure"];
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct MovementRequest {
+    pub ts: String,
+    pub runway: String,
+    pub kind: String,
+    pub flight: Option<String>,
+}
+
+struct NewMovement {
+    ts: OffsetDateTime,
+    runway: String,
+    kind: String,
+    flight: Option<String>,
+}
+
+/// FOD density for one runway over the requested window
+#[derive(Serialize, FromRow)]
+pub struct RunwayDensity {
+    pub runway: String,
+    pub movements: i64,
+    pub fod_events: i64,
+    /// None when the runway had no movements in the window
+    pub fod_per_1k_movements: Option<f64>,
+}
+
+// ==================== Validation ====================
+
+fn validate(req: MovementRequest) -> Result<NewMovement, String> {
+    let ts = OffsetDateTime::parse(req.ts.trim(), &Rfc3339).map_err(|_| "invalid timestamp".to_string())?;
+    let runway = req.runway.trim().to_uppercase();
+    if runway.is_empty() {
+        return Err("runway is empty".to_string());
+    }
+    let kind = req.kind.trim().to_lowercase();
+    if !KINDS.contains(&kind.as_str()) {
+        return Err(format!("kind must be one of {:?}", KINDS));
+    }
+    let flight = req.flight.map(|f| f.trim().to_uppercase()).filter(|f| !f.is_empty());
+    Ok(NewMovement { ts, runway, kind, flight })
+}
+
+/// CSV with a header row: ts, runway, kind[, flight]
+fn parse_csv(body: &[u8]) -> Result<Vec<Result<MovementRequest, String>>, (StatusCode, String)> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(body);
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid CSV header: {}", e)))?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect();
+    Ok(reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| format!("invalid csv: {}", e))?;
+            let fields: HashMap<&str, &str> = headers.iter().map(|h| h.as_str()).zip(record.iter()).collect();
+            let field = |k: &str| fields.get(k).map(|v| v.to_string());
+            Ok(MovementRequest {
+                ts: field("ts").ok_or("missing ts")?,
+                runway: field("runway").ok_or("missing runway")?,
+                kind: field("kind").ok_or("missing kind")?,
+                flight: field("flight"),
+            })
+        })
+        .collect())
+}
+
+// ==================== Queries ====================
+
+/// Insert movements, skipping ones already recorded; returns rows inserted
+async fn insert_batch(db: &PgPool, rows: &[NewMovement]) -> Result<u64, (StatusCode, String)> {
+    if rows.is_empty() {
+        return Ok(0);
+    }
+    let mut qb = QueryBuilder::<Postgres>::new("INSERT INTO aircraft_movements (ts, runway, kind, flight) ");
+    qb.push_values(rows, |mut b, m| {
+        b.push_bind(m.ts).push_bind(&m.runway).push_bind(&m.kind).push_bind(&m.flight);
+    });
+    qb.push(" ON CONFLICT DO NOTHING");
+    let q = qb.build().execute(db);
+    let done = perf::timed("insert_movements_batch", || format!("rows={}", rows.len()), q).await.map_err(internal)?;
+    Ok(done.rows_affected())
+}
+
+/// Movements and FOD events per runway in [from, to); events count through `meta.runway`
+pub async fn runway_density(
+    db: &PgPool,
+    from: OffsetDateTime,
+    to: OffsetDateTime,
+    include_quarantine: bool,
+) -> Result<Vec<RunwayDensity>, (StatusCode, String)> {
+    let sql = format!(
+        r#"
+        WITH m AS (
+            SELECT runway, COUNT(*) AS movements
+            FROM aircraft_movements
+            WHERE ts >= $1 AND ts < $2
+            GROUP BY runway
+        ), f AS (
+            SELECT UPPER(runway) AS runway, COUNT(*) AS fod_events
+            FROM events e
+            WHERE ts >= $1 AND ts < $2 AND runway IS NOT NULL{}
+            GROUP BY 1
+        )
+        SELECT COALESCE(m.runway, f.runway) AS runway,
+               COALESCE(m.movements, 0) AS movements,
+               COALESCE(f.fod_events, 0) AS fod_events,
+               (COALESCE(f.fod_events, 0) * 1000.0 / NULLIF(m.movements, 0))::FLOAT8 AS fod_per_1k_movements
+        FROM m FULL JOIN f ON m.runway = f.runway
+        ORDER BY runway
+        "#,
+        quarantine_clause("e", include_quarantine)
+    );
+    let q = sqlx::query_as::<_, RunwayDensity>(&sql).bind(from).bind(to).fetch_all(db);
+    perf::timed("runway_density", || format!("from={} to={}", from, to), q).await.map_err(internal)
+}
+
+// ==================== Handlers ====================
+
+/// POST /movements — record arrivals/departures as a JSON array or `text/csv`
+pub async fn ingest_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let is_csv = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/csv"));
+    let requests: Vec<Result<MovementRequest, String>> = if is_csv {
+        parse_csv(&body)?
+    } else {
+        serde_json::from_slice::<Vec<MovementRequest>>(&body)
+            .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid JSON: {}", e)))?
+            .into_iter()
+            .map(Ok)
+            .collect()
+    };
+
+    let mut summary = ImportSummary::default();
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    for (i, req) in requests.into_iter().enumerate() {
+        summary.lines += 1;
+        // CSV line numbers count the header row
+        let line = if is_csv { i + 2 } else { i + 1 };
+        match req.and_then(validate) {
+            Ok(m) => batch.push(m),
+            Err(e) => summary.reject(line, e),
+        }
+        if batch.len() >= BATCH_SIZE {
+            summary.accepted += insert_batch(&st.db, &batch).await?;
+            batch.clear();
+        }
+    }
+    summary.accepted += insert_batch(&st.db, &batch).await?;
+    info!(lines = summary.lines, accepted = summary.accepted, rejected = summary.rejected, "movements ingested");
+    Ok(Json(summary))
+}
+
+/// GET /dashboard/fod-density — FOD events per 1,000 movements per runway (default last 30 days)
+pub async fn density_handler(
+    State(st): State<AppState>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let parse = |k: &str| {
+        q.get(k)
+            .map(|s| OffsetDateTime::parse(s, &Rfc3339).map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid {} timestamp", k))))
+            .transpose()
+    };
+    let to = parse("to")?.unwrap_or_else(OffsetDateTime::now_utc);
+    let from = parse("from")?.unwrap_or(to - Duration::days(30));
+    if from >= to {
+        return Err((StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+    let runways = runway_density(&st.db, from, to, quarantine::include(&q)?).await?;
+    Ok(Json(serde_json::json!({
+        "from": from.format(&Rfc3339).unwrap_or_default(),
+        "to": to.format(&Rfc3339).unwrap_or_default(),
+        "runways": runways,
+    })))
+}
@@ -0,0 +1,252 @@
+//! NOTAM drafts for FOD Detection Backend
+//! ICAO-format runway closure text generated from an event, kept for the duty officer to review and file
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::{env, sync::OnceLock};
+use time::{format_description::well_known::Rfc3339, macros::format_description, Duration, OffsetDateTime};
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{auth, db::internal, perf, site, traffic::Traffic, AppState};
+
+/// Closure length when the request gives no end time
+const DEFAULT_CLOSURE: Duration = Duration::hours(1);
+/// Longest description accepted, in characters
+const MAX_DESCRIPTION_CHARS: usize = 500;
+
+// ==================== Config ====================
+
+/// FIR of the Q) line (`NOTAM_FIR`, e.g. VTBB); left as `XXXX` for the officer to fill in
+fn fir() -> &'static str {
+    static FIR: OnceLock<String> = OnceLock::new();
+    FIR.get_or_init(|| env::var("NOTAM_FIR").ok().map(|s| s.trim().to_uppercase()).filter(|s| !s.is_empty()).unwrap_or_else(|| "XXXX".to_string()))
+}
+
+// ==================== Models ====================
+
+#[derive(Deserialize, Default)]
+pub struct DraftRequest {
+    /// Closed runway; defaults to the event's `meta.runway`
+    pub runway: Option<String>,
+    /// RFC 3339, default now
+    pub valid_from: Option<String>,
+    /// RFC 3339, default an hour after `valid_from`
+    pub valid_until: Option<String>,
+    /// Replaces the generated `FOD (CLASS)` description
+    pub description: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Draft {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub runway: String,
+    pub location: String,
+    pub description: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub valid_from: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub valid_until: OffsetDateTime,
+    pub text: String,
+    pub created_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+const COLUMNS: &str = "id, event_id, runway, location, description, valid_from, valid_until, text, created_by, created_at";
+
+#[derive(Deserialize)]
+pub struct ListParams {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+// ==================== Text ====================
+
+/// `YYMMDDHHMM` in UTC, as items B) and C) want it
+fn notam_time(ts: OffsetDateTime) -> String {
+    ts.to_offset(time::UtcOffset::UTC)
+        .format(format_description!("[year repr:last_two][month][day][hour][minute]"))
+        .unwrap_or_default()
+}
+
+/// `1341N10045E`, degrees and minutes
+fn notam_coordinates(lat: f64, lon: f64) -> String {
+    let dm = |v: f64| {
+        let minutes = (v.abs() * 60.0).round() as i64;
+        (minutes / 60, minutes % 60)
+    };
+    let ((lat_d, lat_m), (lon_d, lon_m)) = (dm(lat), dm(lon));
+    format!(
+        "{:02}{:02}{}{:03}{:02}{}",
+        lat_d,
+        lat_m,
+        if lat < 0.0 { 'S' } else { 'N' },
+        lon_d,
+        lon_m,
+        if lon < 0.0 { 'W' } else { 'E' }
+    )
+}
+
+/// `1200M FM THR 01L 15M R OF CL` from the runway geometry, coordinates otherwise
+fn location(traffic: &Traffic, runway: &str, lat: f64, lon: f64) -> String {
+    match traffic.runway_offset(runway, lat, lon) {
+        Some((along, right)) => {
+            let along = format!("{}M {} THR {}", along.abs().round(), if along < 0.0 { "BFR" } else { "FM" }, runway);
+            let right = right.round();
+            if right == 0.0 {
+                format!("{} ON CL", along)
+            } else {
+                format!("{} {}M {} OF CL", along, right.abs(), if right > 0.0 { "R" } else { "L" })
+            }
+        }
+        None => format!("PSN {}", notam_coordinates(lat, lon)),
+    }
+}
+
+/// Draft in the ICAO format: Q) with QMRLC (runway closed), A) aerodrome, B)/C) validity, E) text
+fn render(runway: &str, location: &str, description: &str, lat: f64, lon: f64, from: OffsetDateTime, until: OffsetDateTime) -> String {
+    let aerodrome = site::icao().unwrap_or("XXXX");
+    format!(
+        "(NOTAMN\nQ) {}/QMRLC/IV/NBO/A/000/999/{}005\nA) {} B) {} C) {}\nE) RWY {} CLSD DUE {} AT {}.)",
+        fir(),
+        notam_coordinates(lat, lon),
+        aerodrome,
+        notam_time(from),
+        notam_time(until),
+        runway,
+        description,
+        location
+    )
+}
+
+// ==================== Queries ====================
+
+pub async fn for_event(db: &PgPool, event_id: Uuid) -> Result<Vec<Draft>, (StatusCode, String)> {
+    let sql = format!("SELECT {} FROM notam_drafts WHERE event_id = $1 ORDER BY created_at DESC", COLUMNS);
+    let q = sqlx::query_as::<_, Draft>(&sql).bind(event_id).fetch_all(db);
+    perf::timed("event_notam_drafts", || format!("event_id={}", event_id), q).await.map_err(internal)
+}
+
+// ==================== Handlers ====================
+
+/// POST /events/:id/notam-draft — `{runway?, valid_from?, valid_until?, description?}` generate
+/// and keep a runway closure NOTAM draft for review (login required)
+pub async fn create_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    body: Option<Json<DraftRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+    let event = st.events.get(id).await?.ok_or((StatusCode::NOT_FOUND, "Event not found".to_string()))?;
+    let bad = |e: String| (StatusCode::BAD_REQUEST, e);
+
+    let meta_runway = event.meta.as_ref().and_then(|m| m.get("runway")).and_then(|r| r.as_str()).map(str::to_string);
+    let runway = req
+        .runway
+        .or(meta_runway)
+        .map(|r| r.trim().to_uppercase())
+        .filter(|r| !r.is_empty() && r.len() <= 20)
+        .ok_or_else(|| bad("runway is required when the event has no meta.runway".to_string()))?;
+    let parse = |k: &str, v: Option<String>| {
+        v.map(|s| OffsetDateTime::parse(s.trim(), &Rfc3339).map_err(|_| bad(format!("Invalid {} timestamp", k)))).transpose()
+    };
+    let valid_from = parse("valid_from", req.valid_from)?.unwrap_or_else(OffsetDateTime::now_utc);
+    let valid_until = parse("valid_until", req.valid_until)?.unwrap_or(valid_from + DEFAULT_CLOSURE);
+    if valid_until <= valid_from {
+        return Err(bad("valid_until must be after valid_from".to_string()));
+    }
+    let description = match req.description.map(|d| d.trim().to_uppercase()) {
+        Some(d) if d.is_empty() || d.chars().count() > MAX_DESCRIPTION_CHARS => {
+            return Err(bad(format!("description must be 1-{} characters", MAX_DESCRIPTION_CHARS)));
+        }
+        Some(d) => d,
+        None => format!("FOD ({})", event.event.class_name.to_uppercase()),
+    };
+
+    let (lat, lon) = (f64::from(event.event.latitude), f64::from(event.event.longitude));
+    let location = location(&st.traffic, &runway, lat, lon);
+    let text = render(&runway, &location, &description, lat, lon, valid_from, valid_until);
+    let sql = format!(
+        r#"
+        INSERT INTO notam_drafts (event_id, runway, location, description, valid_from, valid_until, text, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING {}
+        "#,
+        COLUMNS
+    );
+    let q = sqlx::query_as::<_, Draft>(&sql)
+        .bind(id)
+        .bind(&runway)
+        .bind(&location)
+        .bind(&description)
+        .bind(valid_from)
+        .bind(valid_until)
+        .bind(&text)
+        .bind(&claims.username)
+        .fetch_one(&st.db);
+    let draft = perf::timed("insert_notam_draft", || format!("event_id={}", id), q).await.map_err(internal)?;
+    info!(event_id = %id, draft_id = %draft.id, runway = %runway, by = %claims.username, "NOTAM draft generated");
+    Ok((StatusCode::CREATED, Json(draft)))
+}
+
+/// GET /events/:id/notam-drafts — drafts for an event, newest first (login required)
+pub async fn event_list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    Ok(Json(for_event(&st.db, id).await?))
+}
+
+/// GET /notam-drafts?from=&to= — drafts created in the window (default last 7 days), newest
+/// first, for export (login required)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<ListParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let parse = |k: &str, v: &Option<String>| {
+        v.as_deref()
+            .map(|s| OffsetDateTime::parse(s, &Rfc3339).map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid {} timestamp", k))))
+            .transpose()
+    };
+    let to = parse("to", &p.to)?.unwrap_or_else(OffsetDateTime::now_utc);
+    let from = parse("from", &p.from)?.unwrap_or(to - Duration::days(7));
+    let sql = format!("SELECT {} FROM notam_drafts WHERE created_at >= $1 AND created_at < $2 ORDER BY created_at DESC", COLUMNS);
+    let q = sqlx::query_as::<_, Draft>(&sql).bind(from).bind(to).fetch_all(&st.db);
+    Ok(Json(perf::timed("list_notam_drafts", || format!("from={} to={}", from, to), q).await.map_err(internal)?))
+}
+
+/// GET /notam-drafts/:id/text — the draft as a text file to paste into the AIS system (login required)
+pub async fn text_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let text: String = sqlx::query_scalar("SELECT text FROM notam_drafts WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "NOTAM draft not found".to_string()))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/plain; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"notam-{}.txt\"", id)),
+        ],
+        text + "\n",
+    ))
+}
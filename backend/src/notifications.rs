@@ -0,0 +1,232 @@
+//! Notification templates for FOD Detection Backend
+//! Per-channel/per-locale minijinja templates with event and site variables
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use minijinja::Environment;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{FromRow, PgPool};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{auth, crypto, db::{internal, EventDetail}, logging, site, AppState};
+
+/// Locale used when a template has no translation for the requested one
+const FALLBACK_LOCALE: &str = "en";
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct Template {
+    pub name: String,
+    pub channel: String,
+    pub locale: String,
+    pub subject: Option<String>,
+    pub body: String,
+    pub updated_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize)]
+pub struct Rendered {
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+// ==================== Request Types ====================
+
+#[derive(Deserialize)]
+pub struct TemplateRequest {
+    pub name: String,
+    pub channel: String,
+    pub locale: Option<String>,
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+/// Either a stored template (`name` + `channel`) or an inline `body`
+#[derive(Deserialize)]
+pub struct PreviewRequest {
+    pub name: Option<String>,
+    pub channel: Option<String>,
+    pub locale: Option<String>,
+    pub subject: Option<String>,
+    pub body: Option<String>,
+    /// Render against this event instead of the built-in sample
+    pub event_id: Option<Uuid>,
+}
+
+// ==================== Rendering ====================
+
+fn compile_check(source: &str) -> Result<(), String> {
+    Environment::new().template_from_str(source).map(|_| ()).map_err(|e| e.to_string())
+}
+
+fn render_str(source: &str, ctx: &Value) -> Result<String, String> {
+    Environment::new().render_str(source, ctx).map_err(|e| e.to_string())
+}
+
+/// Render subject and body with the given context
+pub fn render(subject: Option<&str>, body: &str, ctx: &Value) -> Result<Rendered, String> {
+    Ok(Rendered {
+        subject: subject.map(|s| render_str(s, ctx)).transpose()?,
+        body: render_str(body, ctx)?,
+    })
+}
+
+/// Template context: `event.*` fields plus `site.id` / `site.name` (env `SITE_NAME`)
+pub fn context(event: Value) -> Value {
+    json!({
+        "event": event,
+        "site": { "id": logging::site_id(), "name": site::name() },
+    })
+}
+
+pub fn event_vars(e: &EventDetail) -> Value {
+    json!({
+        "id": e.event.id,
+        "ts": e.event.ts.format(&Rfc3339).unwrap_or_default(),
+        "class_name": e.event.class_name,
+        "object_count": e.event.object_count,
+        "confidence": e.event.confidence,
+        "latitude": e.event.latitude,
+        "longitude": e.event.longitude,
+        "source": e.event.source,
+        "source_ref": e.event.source_ref,
+        "bbox": e.bbox,
+        "meta": e.meta,
+    })
+}
+
+/// Placeholder event for previews without `event_id`
+fn sample_event_vars() -> Value {
+    json!({
+        "id": Uuid::nil(),
+        "ts": OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default(),
+        "class_name": "bolt",
+        "object_count": 1,
+        "confidence": 0.92,
+        "latitude": 13.69,
+        "longitude": 100.75,
+        "source": "camera",
+        "source_ref": "CAM-01",
+        "bbox": { "x": 120, "y": 80, "w": 40, "h": 32 },
+        "meta": {},
+    })
+}
+
+/// Stored template for the locale, falling back to FALLBACK_LOCALE
+pub async fn lookup(db: &PgPool, name: &str, channel: &str, locale: &str) -> Result<Option<Template>, (StatusCode, String)> {
+    sqlx::query_as::<_, Template>(
+        r#"
+        SELECT name, channel, locale, subject, body, updated_by, updated_at
+        FROM notification_templates
+        WHERE name = $1 AND channel = $2 AND locale IN ($3, $4)
+        ORDER BY (locale = $3) DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(name)
+    .bind(channel)
+    .bind(locale)
+    .bind(FALLBACK_LOCALE)
+    .fetch_optional(db)
+    .await
+    .map_err(internal)
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/notifications/templates — all stored templates
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let templates = sqlx::query_as::<_, Template>(
+        "SELECT name, channel, locale, subject, body, updated_by, updated_at FROM notification_templates ORDER BY name, channel, locale",
+    )
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(templates))
+}
+
+/// PUT /admin/notifications/templates — create or replace a template (admin)
+pub async fn upsert_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<TemplateRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    if req.name.trim().is_empty() || req.channel.trim().is_empty() || req.body.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "name, channel and body are required".to_string()));
+    }
+    // Reject templates that don't compile so the notifier never hits a broken one
+    compile_check(&req.body).map_err(|e| (StatusCode::BAD_REQUEST, format!("body: {}", e)))?;
+    if let Some(subject) = &req.subject {
+        compile_check(subject).map_err(|e| (StatusCode::BAD_REQUEST, format!("subject: {}", e)))?;
+    }
+
+    let template = sqlx::query_as::<_, Template>(
+        r#"
+        INSERT INTO notification_templates (name, channel, locale, subject, body, updated_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (name, channel, locale) DO UPDATE
+            SET subject = EXCLUDED.subject, body = EXCLUDED.body,
+                updated_by = EXCLUDED.updated_by, updated_at = CURRENT_TIMESTAMP
+        RETURNING name, channel, locale, subject, body, updated_by, updated_at
+        "#,
+    )
+    .bind(req.name.trim())
+    .bind(req.channel.trim())
+    .bind(req.locale.as_deref().unwrap_or(FALLBACK_LOCALE))
+    .bind(&req.subject)
+    .bind(&req.body)
+    .bind(&claims.username)
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(template))
+}
+
+/// POST /admin/notifications/preview — render a stored or inline template
+pub async fn preview_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<PreviewRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+
+    let (subject, body) = match (&req.body, &req.name, &req.channel) {
+        (Some(body), _, _) => (req.subject.clone(), body.clone()),
+        (None, Some(name), Some(channel)) => {
+            let locale = req.locale.as_deref().unwrap_or(FALLBACK_LOCALE);
+            let t = lookup(&st.db, name, channel, locale)
+                .await?
+                .ok_or((StatusCode::NOT_FOUND, format!("No template {}/{}/{}", name, channel, locale)))?;
+            (t.subject, t.body)
+        }
+        _ => return Err((StatusCode::BAD_REQUEST, "body or name and channel are required".to_string())),
+    };
+
+    let event = match req.event_id {
+        Some(id) => {
+            let mut e = st.events.get(id).await?.ok_or((StatusCode::NOT_FOUND, format!("Event not found: {}", id)))?;
+            if let Some(meta) = e.meta.as_mut() {
+                crypto::decrypt_meta(meta);
+            }
+            event_vars(&e)
+        }
+        None => sample_event_vars(),
+    };
+
+    let rendered = render(subject.as_deref(), &body, &context(event)).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    Ok(Json(rendered))
+}
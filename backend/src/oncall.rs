@@ -0,0 +1,309 @@
+//! On-call schedules for FOD Detection Backend
+//! Time-based rotations of users/channels with shift-swap overrides
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{auth, db::internal, AppState};
+
+// ==================== Models ====================
+
+#[derive(FromRow)]
+struct RotationRow {
+    id: i32,
+    name: String,
+    shift_hours: i32,
+    starts_at: OffsetDateTime,
+}
+
+#[derive(Serialize, FromRow, Clone)]
+pub struct Assignee {
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub channel: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct OnCallNow {
+    pub rotation: String,
+    /// "rotation" or "override"
+    pub source: &'static str,
+    #[serde(flatten)]
+    pub assignee: Assignee,
+    #[serde(with = "time::serde::rfc3339")]
+    pub until: OffsetDateTime,
+}
+
+#[derive(Serialize)]
+pub struct RotationView {
+    pub name: String,
+    pub shift_hours: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub starts_at: OffsetDateTime,
+    pub members: Vec<Assignee>,
+}
+
+// ==================== Request Types ====================
+
+#[derive(Deserialize)]
+pub struct MemberInput {
+    pub username: Option<String>,
+    pub channel: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RotationRequest {
+    pub name: String,
+    pub shift_hours: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub starts_at: OffsetDateTime,
+    pub members: Vec<MemberInput>,
+}
+
+#[derive(Deserialize)]
+pub struct OverrideRequest {
+    pub rotation: String,
+    pub username: Option<String>,
+    pub channel: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub starts_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub ends_at: OffsetDateTime,
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct NowParams {
+    pub rotation: Option<String>,
+}
+
+// ==================== Schedule Logic ====================
+
+/// Index of the member on duty at `now` and the end of that shift
+fn current_slot(
+    now: OffsetDateTime,
+    starts_at: OffsetDateTime,
+    shift_hours: i32,
+    members: usize,
+) -> Option<(usize, OffsetDateTime)> {
+    if members == 0 || now < starts_at {
+        return None;
+    }
+    let shift = Duration::hours(shift_hours as i64);
+    let k = (now - starts_at).whole_seconds() / shift.whole_seconds();
+    Some(((k as usize) % members, starts_at + shift * (k as i32 + 1)))
+}
+
+async fn members_of(db: &PgPool, rotation_id: i32) -> Result<Vec<Assignee>, (StatusCode, String)> {
+    sqlx::query_as::<_, Assignee>(
+        r#"
+        SELECT m.user_id, u.username, m.channel
+        FROM oncall_members m
+        LEFT JOIN users u ON u.id = m.user_id
+        WHERE m.rotation_id = $1
+        ORDER BY m.position
+        "#,
+    )
+    .bind(rotation_id)
+    .fetch_all(db)
+    .await
+    .map_err(internal)
+}
+
+async fn active_override(
+    db: &PgPool,
+    rotation_id: i32,
+    now: OffsetDateTime,
+) -> Result<Option<(Assignee, OffsetDateTime)>, (StatusCode, String)> {
+    let row = sqlx::query_as::<_, (Option<Uuid>, Option<String>, Option<String>, OffsetDateTime)>(
+        r#"
+        SELECT o.user_id, u.username, o.channel, o.ends_at
+        FROM oncall_overrides o
+        LEFT JOIN users u ON u.id = o.user_id
+        WHERE o.rotation_id = $1 AND o.starts_at <= $2 AND o.ends_at > $2
+        ORDER BY o.created_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(rotation_id)
+    .bind(now)
+    .fetch_optional(db)
+    .await
+    .map_err(internal)?;
+    Ok(row.map(|(user_id, username, channel, ends_at)| (Assignee { user_id, username, channel }, ends_at)))
+}
+
+/// Who is on duty right now for each rotation (or the named one)
+pub async fn on_call_now(db: &PgPool, rotation: Option<&str>) -> Result<Vec<OnCallNow>, (StatusCode, String)> {
+    let rotations = sqlx::query_as::<_, RotationRow>(
+        "SELECT id, name, shift_hours, starts_at FROM oncall_rotations WHERE ($1::TEXT IS NULL OR name = $1) ORDER BY name",
+    )
+    .bind(rotation)
+    .fetch_all(db)
+    .await
+    .map_err(internal)?;
+
+    let now = OffsetDateTime::now_utc();
+    let mut out = Vec::with_capacity(rotations.len());
+    for r in rotations {
+        if let Some((assignee, until)) = active_override(db, r.id, now).await? {
+            out.push(OnCallNow { rotation: r.name, source: "override", assignee, until });
+            continue;
+        }
+        let members = members_of(db, r.id).await?;
+        if let Some((idx, until)) = current_slot(now, r.starts_at, r.shift_hours, members.len()) {
+            out.push(OnCallNow { rotation: r.name, source: "rotation", assignee: members[idx].clone(), until });
+        }
+    }
+    Ok(out)
+}
+
+async fn resolve_user(db: &PgPool, username: &str) -> Result<Uuid, (StatusCode, String)> {
+    sqlx::query_scalar("SELECT id FROM users WHERE username = $1")
+        .bind(username)
+        .fetch_optional(db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::BAD_REQUEST, format!("Unknown user: {}", username)))
+}
+
+// ==================== Handlers ====================
+
+/// GET /oncall/now — current on-call assignee per rotation
+pub async fn now_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<NowParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    Ok(Json(on_call_now(&st.db, p.rotation.as_deref()).await?))
+}
+
+/// GET /oncall/rotations — rotations with their ordered members
+pub async fn list_rotations_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let rotations = sqlx::query_as::<_, RotationRow>("SELECT id, name, shift_hours, starts_at FROM oncall_rotations ORDER BY name")
+        .fetch_all(&st.db)
+        .await
+        .map_err(internal)?;
+    let mut out = Vec::with_capacity(rotations.len());
+    for r in rotations {
+        let members = members_of(&st.db, r.id).await?;
+        out.push(RotationView { name: r.name, shift_hours: r.shift_hours, starts_at: r.starts_at, members });
+    }
+    Ok(Json(out))
+}
+
+/// POST /oncall/rotations — create or replace a rotation (admin)
+pub async fn upsert_rotation_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<RotationRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    if req.name.trim().is_empty() || req.shift_hours <= 0 || req.members.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "name, positive shift_hours and at least one member are required".to_string()));
+    }
+
+    // Resolve usernames up front so a typo doesn't leave a half-written rotation
+    let mut users: HashMap<&str, Uuid> = HashMap::new();
+    for m in &req.members {
+        match (&m.username, &m.channel) {
+            (None, None) => return Err((StatusCode::BAD_REQUEST, "each member needs a username or channel".to_string())),
+            (Some(u), _) if !users.contains_key(u.as_str()) => {
+                users.insert(u, resolve_user(&st.db, u).await?);
+            }
+            _ => {}
+        }
+    }
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let rotation_id: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO oncall_rotations (name, shift_hours, starts_at) VALUES ($1, $2, $3)
+        ON CONFLICT (name) DO UPDATE SET shift_hours = EXCLUDED.shift_hours, starts_at = EXCLUDED.starts_at
+        RETURNING id
+        "#,
+    )
+    .bind(req.name.trim())
+    .bind(req.shift_hours)
+    .bind(req.starts_at)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal)?;
+
+    sqlx::query("DELETE FROM oncall_members WHERE rotation_id = $1")
+        .bind(rotation_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?;
+    for (position, m) in req.members.iter().enumerate() {
+        sqlx::query("INSERT INTO oncall_members (rotation_id, position, user_id, channel) VALUES ($1, $2, $3, $4)")
+            .bind(rotation_id)
+            .bind(position as i32)
+            .bind(m.username.as_deref().map(|u| users[u]))
+            .bind(&m.channel)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+    }
+    tx.commit().await.map_err(internal)?;
+
+    let members = members_of(&st.db, rotation_id).await?;
+    Ok(Json(RotationView { name: req.name.trim().to_string(), shift_hours: req.shift_hours, starts_at: req.starts_at, members }))
+}
+
+/// POST /oncall/overrides — temporary assignee for a shift swap
+pub async fn override_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<OverrideRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    if req.ends_at <= req.starts_at {
+        return Err((StatusCode::BAD_REQUEST, "ends_at must be after starts_at".to_string()));
+    }
+    let user_id = match (&req.username, &req.channel) {
+        (None, None) => return Err((StatusCode::BAD_REQUEST, "username or channel is required".to_string())),
+        (Some(u), _) => Some(resolve_user(&st.db, u).await?),
+        (None, Some(_)) => None,
+    };
+    let rotation_id: i32 = sqlx::query_scalar("SELECT id FROM oncall_rotations WHERE name = $1")
+        .bind(&req.rotation)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown rotation: {}", req.rotation)))?;
+
+    let id: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO oncall_overrides (rotation_id, user_id, channel, starts_at, ends_at, reason, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+        "#,
+    )
+    .bind(rotation_id)
+    .bind(user_id)
+    .bind(&req.channel)
+    .bind(req.starts_at)
+    .bind(req.ends_at)
+    .bind(&req.reason)
+    .bind(&claims.username)
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+
+    Ok((StatusCode::CREATED, Json(serde_json::json!({ "id": id, "status": "success" }))))
+}
@@ -0,0 +1,194 @@
+//! Embedded ONNX Runtime model for FOD Detection Backend
+//! A YOLO export run inside the backend when the AI service is unreachable, so detection keeps
+//! going without it. Needs the `onnx` feature and the ONNX Runtime library at `ORT_DYLIB_PATH`.
+
+use axum::http::StatusCode;
+use image::{imageops::FilterType, RgbImage};
+use ort::{session::Session, value::Tensor};
+use regex::Regex;
+use serde_json::{json, Value};
+use std::{
+    env,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use crate::{ai::DetectOptions, db::internal};
+
+/// Same defaults as the AI service's `/v1/detect`
+const DEFAULT_CONF: f32 = 0.70;
+const DEFAULT_IMGSZ: usize = 832;
+/// Boxes of one class overlapping more than this are the same object
+const NMS_IOU: f32 = 0.45;
+/// Letterbox padding, as in the Ultralytics preprocessing
+const PAD: u8 = 114;
+
+/// One loaded model; sessions run one image at a time, off the async workers
+pub struct Model {
+    session: Arc<Mutex<Session>>,
+    input: String,
+    /// Square input side when the export fixes it
+    fixed_size: Option<usize>,
+    /// Class names in output order
+    names: Vec<String>,
+    /// `onnx:<file stem>`, reported as the response's `model`
+    pub name: String,
+}
+
+impl Model {
+    /// The model at `path`; class names from `AI_ONNX_CLASSES` (comma separated, in model order)
+    /// or the `names` metadata Ultralytics writes into its exports
+    pub fn load(path: &str) -> Result<Model, String> {
+        // Loaded up front: ort panics on first use when the library is missing
+        let lib = env::var("ORT_DYLIB_PATH").ok().filter(|s| !s.is_empty()).unwrap_or_else(|| "libonnxruntime.so".to_string());
+        ort::init_from(&lib).map_err(|e| format!("loading ONNX Runtime from {}: {}", lib, e))?.commit();
+        let session = Session::builder()
+            .and_then(|mut b| b.commit_from_file(path))
+            .map_err(|e| format!("loading {}: {}", path, e))?;
+        let input = session.inputs().first().ok_or("model has no inputs")?;
+        let fixed_size = input.dtype().tensor_shape().and_then(|s| s.get(2).copied()).filter(|&d| d > 0).map(|d| d as usize);
+        let input = input.name().to_string();
+        let names = match env::var("AI_ONNX_CLASSES").ok().filter(|s| !s.trim().is_empty()) {
+            Some(list) => list.split(',').map(|s| s.trim().to_string()).collect(),
+            None => session.metadata().ok().and_then(|m| m.custom("names")).map(|n| parse_names(&n)).unwrap_or_default(),
+        };
+        if names.is_empty() {
+            return Err("model has no class names; set AI_ONNX_CLASSES".to_string());
+        }
+        let stem = Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or("model");
+        Ok(Model { session: Arc::new(Mutex::new(session)), input, fixed_size, names, name: format!("onnx:{}", stem) })
+    }
+
+    /// Detect on one encoded image, answering in the shape the AI service does
+    pub async fn detect(&self, image: Vec<u8>, opts: DetectOptions) -> Result<Value, (StatusCode, String)> {
+        let img = image::load_from_memory(&image).map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid image file: {}", e)))?.to_rgb8();
+        let size = self.fixed_size.or(opts.imgsz.map(|s| s as usize)).unwrap_or(DEFAULT_IMGSZ);
+        let conf = opts.conf.unwrap_or(DEFAULT_CONF);
+        let (session, input, classes) = (self.session.clone(), self.input.clone(), self.names.len());
+        let (w, h) = (img.width() as f32, img.height() as f32);
+        let boxes = tokio::task::spawn_blocking(move || {
+            let (pixels, letterbox) = letterbox(&img, size);
+            let tensor = Tensor::from_array(([1usize, 3, size, size], pixels)).map_err(internal)?;
+            let mut session = session.lock().map_err(|_| internal("onnx session poisoned"))?;
+            let outputs = session.run(ort::inputs![input.as_str() => tensor]).map_err(|e| (StatusCode::BAD_GATEWAY, format!("onnx error: {}", e)))?;
+            let (shape, data) = outputs[0].try_extract_tensor::<f32>().map_err(internal)?;
+            let boxes = decode(shape, data, classes, conf).map_err(|e| (StatusCode::BAD_GATEWAY, format!("onnx error: {}", e)))?;
+            Ok::<_, (StatusCode, String)>(boxes.into_iter().map(|b| b.unletterbox(&letterbox, w, h)).collect::<Vec<_>>())
+        })
+        .await
+        .map_err(internal)??;
+        let detections: Vec<Value> = nms(boxes)
+            .into_iter()
+            .map(|b| {
+                json!({
+                    "cls": self.names[b.class],
+                    "conf": b.conf,
+                    "bbox_xywh": [b.x, b.y, b.w, b.h],
+                    "bbox_xywh_norm": [b.x / w, b.y / h, b.w / w, b.h / h],
+                })
+            })
+            .collect();
+        Ok(json!({ "model": self.name, "img_w": w, "img_h": h, "detections": detections }))
+    }
+}
+
+/// `{0: 'Bolt', 1: 'Nut'}` from the export metadata, in index order
+fn parse_names(names: &str) -> Vec<String> {
+    let re = Regex::new(r#"(\d+)\s*:\s*['"]([^'"]*)['"]"#).unwrap();
+    let mut pairs: Vec<(usize, String)> =
+        re.captures_iter(names).filter_map(|c| Some((c[1].parse().ok()?, c[2].to_string()))).collect();
+    pairs.sort();
+    pairs.into_iter().map(|(_, n)| n).collect()
+}
+
+// ==================== Pre/post-processing ====================
+
+/// How the image was fitted into the square input
+struct Letterbox {
+    scale: f32,
+    pad_x: f32,
+    pad_y: f32,
+}
+
+/// Scale to fit `size`, centre on grey padding, planar RGB in 0..1
+fn letterbox(img: &RgbImage, size: usize) -> (Vec<f32>, Letterbox) {
+    let scale = (size as f32 / img.width() as f32).min(size as f32 / img.height() as f32);
+    let (nw, nh) = (((img.width() as f32 * scale).round() as u32).max(1), ((img.height() as f32 * scale).round() as u32).max(1));
+    let resized = image::imageops::resize(img, nw, nh, FilterType::Triangle);
+    let (pad_x, pad_y) = ((size as u32 - nw) / 2, (size as u32 - nh) / 2);
+    let plane = size * size;
+    let mut pixels = vec![PAD as f32 / 255.0; 3 * plane];
+    for (x, y, p) in resized.enumerate_pixels() {
+        let at = (y + pad_y) as usize * size + (x + pad_x) as usize;
+        for c in 0..3 {
+            pixels[c * plane + at] = p[c] as f32 / 255.0;
+        }
+    }
+    (pixels, Letterbox { scale, pad_x: pad_x as f32, pad_y: pad_y as f32 })
+}
+
+/// Top-left box in pixels
+struct Detection {
+    class: usize,
+    conf: f32,
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+impl Detection {
+    /// From input coordinates back to the original image, clipped to it
+    fn unletterbox(self, l: &Letterbox, img_w: f32, img_h: f32) -> Detection {
+        let x1 = ((self.x - l.pad_x) / l.scale).clamp(0.0, img_w);
+        let y1 = ((self.y - l.pad_y) / l.scale).clamp(0.0, img_h);
+        let x2 = ((self.x + self.w - l.pad_x) / l.scale).clamp(0.0, img_w);
+        let y2 = ((self.y + self.h - l.pad_y) / l.scale).clamp(0.0, img_h);
+        Detection { x: x1, y: y1, w: x2 - x1, h: y2 - y1, ..self }
+    }
+
+    fn iou(&self, o: &Detection) -> f32 {
+        let w = ((self.x + self.w).min(o.x + o.w) - self.x.max(o.x)).max(0.0);
+        let h = ((self.y + self.h).min(o.y + o.h) - self.y.max(o.y)).max(0.0);
+        let inter = w * h;
+        inter / (self.w * self.h + o.w * o.h - inter).max(f32::EPSILON)
+    }
+}
+
+/// YOLOv8 head output, `[1, 4 + classes, anchors]` (or transposed) of centre boxes and class
+/// scores, to the boxes scoring at least `conf`
+fn decode(shape: &[i64], data: &[f32], classes: usize, conf: f32) -> Result<Vec<Detection>, String> {
+    let [1, a, b] = shape else { return Err(format!("unexpected output shape {:?}", shape)) };
+    let (a, b) = (*a as usize, *b as usize);
+    let channels = 4 + classes;
+    // value `c` of anchor `i`
+    let (anchors, at): (usize, Box<dyn Fn(usize, usize) -> f32>) = if a == channels {
+        (b, Box::new(move |i, c| data[c * b + i]))
+    } else if b == channels {
+        (a, Box::new(move |i, c| data[i * b + c]))
+    } else {
+        return Err(format!("output shape {:?} does not fit {} classes", shape, classes));
+    };
+    let mut found = Vec::new();
+    for i in 0..anchors {
+        let (class, score) = (0..classes).map(|c| (c, at(i, 4 + c))).fold((0, f32::MIN), |m, s| if s.1 > m.1 { s } else { m });
+        if score < conf {
+            continue;
+        }
+        let (cx, cy, w, h) = (at(i, 0), at(i, 1), at(i, 2), at(i, 3));
+        found.push(Detection { class, conf: score, x: cx - w / 2.0, y: cy - h / 2.0, w, h });
+    }
+    Ok(found)
+}
+
+/// Highest scoring boxes first, dropping any that overlap a kept box of the same class
+fn nms(mut boxes: Vec<Detection>) -> Vec<Detection> {
+    boxes.sort_by(|a, b| b.conf.total_cmp(&a.conf));
+    let mut kept: Vec<Detection> = Vec::new();
+    for b in boxes {
+        if !kept.iter().any(|k| k.class == b.class && k.iou(&b) > NMS_IOU) {
+            kept.push(b);
+        }
+    }
+    kept
+}
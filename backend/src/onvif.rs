@@ -0,0 +1,566 @@
+//! ONVIF camera control for FOD Detection Backend
+//! Discovery, registration and PTZ proxied as SOAP calls, so operators re-aim cameras without their credentials
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use futures::future::join_all;
+use regex::Regex;
+use reqwest::{Client, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha1::{Digest, Sha1};
+use std::{
+    collections::BTreeMap,
+    env,
+    net::{Ipv4Addr, SocketAddr},
+    sync::OnceLock,
+    time::Duration,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::{net::UdpSocket, time::Instant};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    auth,
+    cameras::{self, Registered},
+    crypto,
+    db::internal,
+    perf, AppState,
+};
+
+/// Profile used when `CAMERA_PTZ_URLS` names none
+const DEFAULT_PROFILE: &str = "Profile_1";
+const SOAP_TIMEOUT: Duration = Duration::from_secs(5);
+/// WS-Discovery multicast group
+const DISCOVERY_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+/// Largest subnet probed host by host (a /22)
+const MAX_PROBED_HOSTS: u32 = 1024;
+
+// ==================== Config ====================
+
+/// ONVIF user, from a URL or a registered camera's sealed credentials
+#[derive(Clone, Default)]
+struct Login {
+    username: String,
+    password: String,
+}
+
+impl Login {
+    /// Move the URL's user and password out of it
+    fn take_from(url: &mut Url) -> Login {
+        let login = Login { username: url.username().to_string(), password: url.password().unwrap_or_default().to_string() };
+        let _ = url.set_username("");
+        let _ = url.set_password(None);
+        login
+    }
+}
+
+/// PTZ service of one camera
+#[derive(Clone)]
+struct PtzService {
+    url: Url,
+    profile: String,
+    login: Login,
+}
+
+impl PtzService {
+    /// `[profile@]url`, as in `AI_MODEL_URLS`; user and password come from the URL
+    fn parse(spec: &str) -> Option<Self> {
+        let (profile, url) = match spec.split_once('@') {
+            Some((profile, url)) if !profile.contains('/') => (profile, url),
+            _ => (DEFAULT_PROFILE, spec),
+        };
+        let mut url = Url::parse(url).ok()?;
+        let login = Login::take_from(&mut url);
+        Some(PtzService { url, profile: profile.to_string(), login })
+    }
+
+    fn from_registered(camera: &Registered) -> Option<Self> {
+        let url = Url::parse(camera.ptz_url.as_deref()?).ok()?;
+        let login = camera.login().map(|(username, password)| Login { username, password }).unwrap_or_default();
+        Some(PtzService { url, profile: camera.profile.clone().unwrap_or_else(|| DEFAULT_PROFILE.to_string()), login })
+    }
+}
+
+/// Whether a `CAMERA_PTZ_URLS` entry parses
+pub fn valid_ptz_spec(spec: &str) -> bool {
+    PtzService::parse(spec).is_some()
+}
+
+/// PTZ service from the camera config, else from the camera's registration
+async fn service(st: &AppState, id: &str) -> Result<PtzService, (StatusCode, String)> {
+    if let Some(service) = st.cameras.ptz_urls.get(id).and_then(|spec| PtzService::parse(spec)) {
+        return Ok(service);
+    }
+    cameras::registered(&st.db, id)
+        .await?
+        .as_ref()
+        .and_then(PtzService::from_registered)
+        .ok_or((StatusCode::NOT_FOUND, "No PTZ control for this camera".to_string()))
+}
+
+// ==================== SOAP ====================
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&apos;")
+}
+
+/// WS-Security UsernameToken with a password digest, which every ONVIF device accepts
+fn security_header(login: &Login) -> String {
+    if login.username.is_empty() {
+        return String::new();
+    }
+    let nonce = Uuid::new_v4();
+    let created = OffsetDateTime::now_utc().format(&Rfc3339).unwrap_or_default();
+    let mut hash = Sha1::new();
+    hash.update(nonce.as_bytes());
+    hash.update(created.as_bytes());
+    hash.update(login.password.as_bytes());
+    format!(
+        r#"<s:Header><wsse:Security xmlns:wsse="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-secext-1.0.xsd" xmlns:wsu="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-wssecurity-utility-1.0.xsd"><wsse:UsernameToken><wsse:Username>{}</wsse:Username><wsse:Password Type="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-username-token-profile-1.0#PasswordDigest">{}</wsse:Password><wsse:Nonce EncodingType="http://docs.oasis-open.org/wss/2004/01/oasis-200401-wss-soap-message-security-1.0#Base64Binary">{}</wsse:Nonce><wsu:Created>{}</wsu:Created></wsse:UsernameToken></wsse:Security></s:Header>"#,
+        escape(&login.username),
+        B64.encode(hash.finalize()),
+        B64.encode(nonce.as_bytes()),
+        created
+    )
+}
+
+/// ONVIF services we call, by prefix and WSDL namespace
+#[derive(Clone, Copy)]
+enum Wsdl {
+    Device,
+    Media,
+    Ptz,
+}
+
+impl Wsdl {
+    fn prefix(self) -> &'static str {
+        match self {
+            Wsdl::Device => "tds",
+            Wsdl::Media => "trt",
+            Wsdl::Ptz => "tptz",
+        }
+    }
+
+    fn namespace(self) -> &'static str {
+        match self {
+            Wsdl::Device => "http://www.onvif.org/ver10/device/wsdl",
+            Wsdl::Media => "http://www.onvif.org/ver10/media/wsdl",
+            Wsdl::Ptz => "http://www.onvif.org/ver20/ptz/wsdl",
+        }
+    }
+}
+
+/// Call `action` with `body` inside the action element; returns the response XML
+async fn soap(http: &Client, url: &Url, login: &Login, wsdl: Wsdl, action: &str, body: &str) -> Result<String, (StatusCode, String)> {
+    let envelope = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><s:Envelope xmlns:s="http://www.w3.org/2003/05/soap-envelope" xmlns:{p}="{ns}" xmlns:tt="http://www.onvif.org/ver10/schema">{}<s:Body><{p}:{action}>{}</{p}:{action}></s:Body></s:Envelope>"#,
+        security_header(login),
+        body,
+        p = wsdl.prefix(),
+        ns = wsdl.namespace(),
+        action = action
+    );
+    let content_type = format!("application/soap+xml; charset=utf-8; action=\"{}/{}\"", wsdl.namespace(), action);
+    let unreachable = |e: reqwest::Error| {
+        warn!(action, error = %e.without_url(), "ONVIF call failed");
+        (StatusCode::BAD_GATEWAY, "Camera unreachable".to_string())
+    };
+    let resp = http
+        .post(url.clone())
+        .header(reqwest::header::CONTENT_TYPE, content_type)
+        .timeout(SOAP_TIMEOUT)
+        .body(envelope)
+        .send()
+        .await
+        .map_err(unreachable)?;
+    let status = resp.status();
+    let text = resp.text().await.map_err(unreachable)?;
+    if !status.is_success() || text.contains(":Fault>") {
+        let reason = fault_reason(&text).unwrap_or_else(|| status.to_string());
+        warn!(action, %status, reason = %reason, "camera rejected ONVIF call");
+        return Err((StatusCode::BAD_GATEWAY, format!("Camera rejected {}: {}", action, reason)));
+    }
+    Ok(text)
+}
+
+/// PTZ `action` on the service's profile
+async fn ptz(http: &Client, service: &PtzService, action: &str, body: &str) -> Result<String, (StatusCode, String)> {
+    let body = format!("<tptz:ProfileToken>{}</tptz:ProfileToken>{}", escape(&service.profile), body);
+    soap(http, &service.url, &service.login, Wsdl::Ptz, action, &body).await
+}
+
+fn fault_reason(xml: &str) -> Option<String> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"(?s)<(?:\w+:)?Reason>.*?<(?:\w+:)?Text[^>]*>([^<]*)<").unwrap());
+    re.captures(xml).map(|c| c[1].trim().to_string())
+}
+
+/// Text of the first `<tag>` (any prefix) in `xml`
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let re = Regex::new(&format!(r"<(?:\w+:)?{}\b[^>]*>([^<]*)<", regex::escape(tag))).ok()?;
+    re.captures(xml).and_then(|c| c.get(1)).map(|m| m.as_str().trim())
+}
+
+/// Service address of a capability category (`Media`, `PTZ`, ...) in a GetCapabilities response
+fn capability_address(xml: &str, category: &str) -> Option<String> {
+    let re = Regex::new(&format!(r"(?s)<(?:\w+:)?{}\b[^>]*>.*?<(?:\w+:)?XAddr>([^<]+)<", regex::escape(category))).ok()?;
+    re.captures(xml).map(|c| c[1].trim().to_string())
+}
+
+/// Capability categories a device reports, lowercased
+fn capabilities(xml: &str) -> Vec<String> {
+    ["Analytics", "Events", "Imaging", "Media", "PTZ"].iter().filter(|c| capability_address(xml, c).is_some()).map(|c| c.to_lowercase()).collect()
+}
+
+#[derive(Serialize)]
+pub struct Preset {
+    pub token: String,
+    pub name: Option<String>,
+}
+
+fn parse_presets(xml: &str) -> Vec<Preset> {
+    static RE: OnceLock<(Regex, Regex)> = OnceLock::new();
+    let (preset, name) = RE.get_or_init(|| {
+        (
+            Regex::new(r#"(?s)<(?:\w+:)?Preset\b[^>]*\btoken="([^"]*)"[^>]*>(.*?)</(?:\w+:)?Preset>"#).unwrap(),
+            Regex::new(r"<(?:\w+:)?Name>([^<]*)</").unwrap(),
+        )
+    });
+    preset
+        .captures_iter(xml)
+        .map(|c| Preset { token: c[1].to_string(), name: name.captures(&c[2]).map(|n| n[1].to_string()) })
+        .collect()
+}
+
+// ==================== Handlers ====================
+
+#[derive(Deserialize)]
+pub struct MoveRequest {
+    /// `absolute` (default) or `relative` to the current position
+    pub mode: Option<String>,
+    /// Normalized -1..1
+    pub pan: Option<f32>,
+    pub tilt: Option<f32>,
+    /// 0..1 absolute, -1..1 relative
+    pub zoom: Option<f32>,
+}
+
+/// Position or translation elements for a move, validated against ONVIF's generic spaces
+fn move_body(req: &MoveRequest) -> Result<(&'static str, String), String> {
+    let (action, wrapper, zoom_min) = match req.mode.as_deref().unwrap_or("absolute") {
+        "absolute" => ("AbsoluteMove", "Position", 0.0),
+        "relative" => ("RelativeMove", "Translation", -1.0),
+        other => return Err(format!("Unknown mode {:?} (expected absolute or relative)", other)),
+    };
+    let check = |name: &str, v: Option<f32>, min: f32| match v {
+        Some(v) if !(min..=1.0).contains(&v) => Err(format!("{} must be within {}..1", name, min)),
+        _ => Ok(()),
+    };
+    check("pan", req.pan, -1.0)?;
+    check("tilt", req.tilt, -1.0)?;
+    check("zoom", req.zoom, zoom_min)?;
+    let mut inner = String::new();
+    match (req.pan, req.tilt) {
+        (None, None) => {}
+        (Some(x), Some(y)) => inner.push_str(&format!(r#"<tt:PanTilt x="{}" y="{}"/>"#, x, y)),
+        // A relative move may leave one axis alone; an absolute one needs both
+        (x, y) if action == "RelativeMove" => inner.push_str(&format!(r#"<tt:PanTilt x="{}" y="{}"/>"#, x.unwrap_or(0.0), y.unwrap_or(0.0))),
+        _ => return Err("Absolute moves need both pan and tilt".to_string()),
+    }
+    if let Some(z) = req.zoom {
+        inner.push_str(&format!(r#"<tt:Zoom x="{}"/>"#, z));
+    }
+    if inner.is_empty() {
+        return Err("Nothing to move: give pan/tilt and/or zoom".to_string());
+    }
+    Ok((action, format!("<tptz:{wrapper}>{inner}</tptz:{wrapper}>", wrapper = wrapper, inner = inner)))
+}
+
+/// POST /cameras/:id/ptz/move — `{mode?, pan?, tilt?, zoom?}` (login required)
+pub async fn move_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(req): Json<MoveRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let (action, body) = move_body(&req).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    ptz(&st.http, &service(&st, &id).await?, action, &body).await?;
+    info!(camera = %id, action, pan = ?req.pan, tilt = ?req.tilt, zoom = ?req.zoom, by = %claims.username, "camera moved");
+    Ok(Json(json!({ "ok": true })))
+}
+
+/// POST /cameras/:id/ptz/stop — halt any pan, tilt or zoom in progress (login required)
+pub async fn stop_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    ptz(&st.http, &service(&st, &id).await?, "Stop", "<tptz:PanTilt>true</tptz:PanTilt><tptz:Zoom>true</tptz:Zoom>").await?;
+    info!(camera = %id, by = %claims.username, "camera stopped");
+    Ok(Json(json!({ "ok": true })))
+}
+
+/// GET /cameras/:id/ptz/presets — the camera's stored positions (login required)
+pub async fn presets_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let xml = ptz(&st.http, &service(&st, &id).await?, "GetPresets", "").await?;
+    Ok(Json(parse_presets(&xml)))
+}
+
+/// POST /cameras/:id/ptz/presets/:token — move to a stored position (login required)
+pub async fn goto_preset_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path((id, token)): Path<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let body = format!("<tptz:PresetToken>{}</tptz:PresetToken>", escape(&token));
+    ptz(&st.http, &service(&st, &id).await?, "GotoPreset", &body).await?;
+    info!(camera = %id, preset = %token, by = %claims.username, "camera moved to preset");
+    Ok(Json(json!({ "ok": true })))
+}
+
+// ==================== Discovery ====================
+
+#[derive(Deserialize, Default)]
+pub struct DiscoverRequest {
+    /// `10.0.4.0/24` probes each host; without it (or `CAMERA_DISCOVERY_SUBNET`) one multicast probe
+    pub subnet: Option<String>,
+    /// How long to collect answers (default 3000, at most 10000)
+    pub timeout_ms: Option<u64>,
+}
+
+/// An ONVIF device that answered the probe
+#[derive(Serialize)]
+pub struct Discovered {
+    /// Device service, pass to `POST /cameras`
+    pub address: String,
+    pub name: Option<String>,
+    pub hardware: Option<String>,
+    pub location: Option<String>,
+    pub types: Vec<String>,
+    /// From an unauthenticated GetCapabilities; None when the device wants a login first
+    pub capabilities: Option<Vec<String>>,
+    /// source_ref it is registered under
+    pub registered: Option<String>,
+}
+
+fn probe_message() -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><e:Envelope xmlns:e="http://www.w3.org/2003/05/soap-envelope" xmlns:w="http://schemas.xmlsoap.org/ws/2004/08/addressing" xmlns:d="http://schemas.xmlsoap.org/ws/2005/04/discovery" xmlns:dn="http://www.onvif.org/ver10/network/wsdl"><e:Header><w:MessageID>uuid:{}</w:MessageID><w:To e:mustUnderstand="true">urn:schemas-xmlsoap-org:ws:2005:04:discovery</w:To><w:Action e:mustUnderstand="true">http://schemas.xmlsoap.org/ws/2005/04/discovery/Probe</w:Action></e:Header><e:Body><d:Probe><d:Types>dn:NetworkVideoTransmitter</d:Types></d:Probe></e:Body></e:Envelope>"#,
+        Uuid::new_v4()
+    )
+}
+
+/// Hosts of `a.b.c.d/n`, without network and broadcast addresses below /31
+fn hosts(subnet: &str) -> Result<Vec<Ipv4Addr>, String> {
+    let invalid = || format!("Invalid subnet {:?}: expected a.b.c.d/n", subnet);
+    let (addr, bits) = subnet.split_once('/').ok_or_else(invalid)?;
+    let addr: Ipv4Addr = addr.trim().parse().map_err(|_| invalid())?;
+    let bits: u32 = bits.trim().parse().ok().filter(|&b| b <= 32).ok_or_else(invalid)?;
+    let size = 1u64 << (32 - bits);
+    if size > MAX_PROBED_HOSTS as u64 {
+        return Err(format!("Subnet too large, at most {} hosts", MAX_PROBED_HOSTS));
+    }
+    let network = u32::from(addr) & !((size - 1) as u32);
+    let range = if size > 2 { 1..size - 1 } else { 0..size };
+    Ok(range.map(|i| Ipv4Addr::from(network + i as u32)).collect())
+}
+
+/// Scope values under `onvif://www.onvif.org/<kind>/`, percent-decoded
+fn scope(scopes: &str, kind: &str) -> Option<String> {
+    let prefix = format!("onvif://www.onvif.org/{}/", kind);
+    scopes.split_whitespace().find_map(|s| s.strip_prefix(&prefix)).map(|v| {
+        let bytes = v.as_bytes();
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            match (bytes[i], v.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok())) {
+                (b'%', Some(b)) => {
+                    out.push(b);
+                    i += 3;
+                }
+                (b, _) => {
+                    out.push(b);
+                    i += 1;
+                }
+            }
+        }
+        String::from_utf8_lossy(&out).into_owned()
+    })
+}
+
+fn parse_probe_match(xml: &str) -> Option<Discovered> {
+    let address = element(xml, "XAddrs")?.split_whitespace().find(|a| a.starts_with("http"))?.to_string();
+    let scopes = element(xml, "Scopes").unwrap_or_default();
+    Some(Discovered {
+        address,
+        name: scope(scopes, "name"),
+        hardware: scope(scopes, "hardware"),
+        location: scope(scopes, "location"),
+        types: element(xml, "Types").unwrap_or_default().split_whitespace().map(str::to_string).collect(),
+        capabilities: None,
+        registered: None,
+    })
+}
+
+/// Send WS-Discovery probes and collect distinct answers until `wait` runs out
+async fn probe(targets: &[Ipv4Addr], port: u16, wait: Duration) -> Result<Vec<Discovered>, String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await.map_err(|e| e.to_string())?;
+    let message = probe_message();
+    if targets.is_empty() {
+        socket.set_multicast_ttl_v4(1).map_err(|e| e.to_string())?;
+        socket.send_to(message.as_bytes(), SocketAddr::from((DISCOVERY_GROUP, port))).await.map_err(|e| e.to_string())?;
+    }
+    for ip in targets {
+        // Unreachable hosts are expected on a sweep
+        let _ = socket.send_to(message.as_bytes(), SocketAddr::from((*ip, port))).await;
+    }
+    let deadline = Instant::now() + wait;
+    let mut found: BTreeMap<String, Discovered> = BTreeMap::new();
+    let mut buf = vec![0u8; 65_535];
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+        let Ok((n, from)) = received else { continue };
+        match parse_probe_match(&String::from_utf8_lossy(&buf[..n])) {
+            Some(device) => drop(found.entry(device.address.clone()).or_insert(device)),
+            None => warn!(%from, "ignoring unparseable WS-Discovery answer"),
+        }
+    }
+    Ok(found.into_values().collect())
+}
+
+/// POST /cameras/discover — `{subnet?, timeout_ms?}` WS-Discovery for ONVIF cameras, with
+/// their capabilities and whether they are registered yet (admin)
+pub async fn discover_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    body: Option<Json<DiscoverRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let req = body.map(|Json(r)| r).unwrap_or_default();
+    let subnet = req.subnet.or_else(|| env::var("CAMERA_DISCOVERY_SUBNET").ok()).filter(|s| !s.trim().is_empty());
+    let targets = subnet.as_deref().map(hosts).transpose().map_err(|e| (StatusCode::BAD_REQUEST, e))?.unwrap_or_default();
+    let wait = Duration::from_millis(req.timeout_ms.unwrap_or(3000).min(10_000));
+    let mut devices = probe(&targets, st.cameras.discovery_port, wait).await.map_err(|e| {
+        warn!(error = %e, "WS-Discovery probe failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("Discovery failed: {}", e))
+    })?;
+
+    // GetCapabilities is callable before authentication on ONVIF devices
+    let answers = join_all(devices.iter().map(|d| async {
+        let url = Url::parse(&d.address).ok()?;
+        soap(&st.http, &url, &Login::default(), Wsdl::Device, "GetCapabilities", "<tds:Category>All</tds:Category>").await.ok()
+    }))
+    .await;
+    let registered = cameras::all_registered(&st.db).await?;
+    for (device, xml) in devices.iter_mut().zip(answers) {
+        device.capabilities = xml.map(|x| capabilities(&x));
+        device.registered = registered.iter().find(|c| c.device_url == device.address).map(|c| c.source_ref.clone());
+    }
+    info!(subnet = ?subnet, found = devices.len(), by = %claims.username, "camera discovery");
+    Ok(Json(devices))
+}
+
+// ==================== Registration ====================
+
+#[derive(Deserialize)]
+pub struct RegisterRequest {
+    pub source_ref: String,
+    /// Device service address from `/cameras/discover`
+    pub address: String,
+    pub name: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// POST /cameras — register a discovered camera under a source_ref; media and PTZ addresses,
+/// profile and snapshot URL are read from the device (admin)
+pub async fn register_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<RegisterRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let bad = |e: &str| (StatusCode::BAD_REQUEST, e.to_string());
+    let source_ref = req.source_ref.trim();
+    if source_ref.is_empty() || source_ref.len() > 255 {
+        return Err(bad("source_ref must be 1-255 characters"));
+    }
+    let device_url = Url::parse(req.address.trim()).ok().filter(|u| u.scheme().starts_with("http")).ok_or_else(|| bad("address must be an http(s) URL"))?;
+    let login = Login { username: req.username.unwrap_or_default(), password: req.password.unwrap_or_default() };
+    let credentials = if login.username.is_empty() {
+        None
+    } else {
+        let plain = json!({ "username": login.username, "password": login.password });
+        Some(crypto::seal_secret(cameras::CREDENTIALS, &plain).ok_or_else(|| bad("Camera credentials need META_ENCRYPTION_KEYS"))?)
+    };
+
+    let caps = soap(&st.http, &device_url, &login, Wsdl::Device, "GetCapabilities", "<tds:Category>All</tds:Category>").await?;
+    let ptz_url = capability_address(&caps, "PTZ");
+    let media_url = capability_address(&caps, "Media").and_then(|u| Url::parse(&u).ok());
+    let (mut profile, mut snapshot_url) = (None, None);
+    if let Some(media_url) = &media_url {
+        static PROFILE: OnceLock<Regex> = OnceLock::new();
+        let re = PROFILE.get_or_init(|| Regex::new(r#"<(?:\w+:)?Profiles\b[^>]*\btoken="([^"]*)""#).unwrap());
+        let profiles = soap(&st.http, media_url, &login, Wsdl::Media, "GetProfiles", "").await?;
+        profile = re.captures(&profiles).map(|c| c[1].to_string());
+        if let Some(token) = &profile {
+            let body = format!("<trt:ProfileToken>{}</trt:ProfileToken>", escape(token));
+            let uri = soap(&st.http, media_url, &login, Wsdl::Media, "GetSnapshotUri", &body).await?;
+            snapshot_url = element(&uri, "Uri").map(|u| u.replace("&amp;", "&"));
+        }
+    }
+
+    let sql = format!(
+        r#"
+        INSERT INTO cameras (source_ref, name, device_url, snapshot_url, ptz_url, profile, credentials, registered_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (source_ref) DO UPDATE SET name = EXCLUDED.name, device_url = EXCLUDED.device_url,
+            snapshot_url = EXCLUDED.snapshot_url, ptz_url = EXCLUDED.ptz_url, profile = EXCLUDED.profile,
+            credentials = EXCLUDED.credentials, registered_by = EXCLUDED.registered_by, registered_at = NOW()
+        RETURNING {}
+        "#,
+        cameras::REGISTERED_COLUMNS
+    );
+    let q = sqlx::query_as::<_, Registered>(&sql)
+        .bind(source_ref)
+        .bind(&req.name)
+        .bind(device_url.as_str())
+        .bind(&snapshot_url)
+        .bind(&ptz_url)
+        .bind(&profile)
+        .bind(&credentials)
+        .bind(&claims.username)
+        .fetch_one(&st.db);
+    let camera = perf::timed("register_camera", || format!("source_ref={}", source_ref), q).await.map_err(internal)?;
+    info!(camera = %source_ref, address = %device_url, ptz = ptz_url.is_some(), snapshot = snapshot_url.is_some(), by = %claims.username, "camera registered");
+    Ok((StatusCode::CREATED, Json(camera)))
+}
+
+/// DELETE /cameras/:id — forget a registered camera (admin)
+pub async fn unregister_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let done = sqlx::query("DELETE FROM cameras WHERE source_ref = $1").bind(&id).execute(&st.db).await.map_err(internal)?;
+    if done.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Camera not registered".to_string()));
+    }
+    info!(camera = %id, by = %claims.username, "camera unregistered");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,133 @@
+//! Latency statistics for FOD Detection Backend
+//! Per-route and per-query percentiles, slow query log and /admin/perf
+
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    env,
+    future::Future,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Samples kept per key; percentiles are computed over this window
+pub const WINDOW: usize = 1024;
+
+// ==================== Collector ====================
+
+#[derive(Default)]
+struct Samples {
+    count: u64,
+    recent: VecDeque<f64>,
+}
+
+impl Samples {
+    fn record(&mut self, ms: f64) {
+        self.count += 1;
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(ms);
+    }
+
+    fn snapshot(&self) -> LatencySnapshot {
+        let mut sorted: Vec<f64> = self.recent.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let pct = |p: f64| -> f64 {
+            if sorted.is_empty() {
+                return 0.0;
+            }
+            let idx = ((p / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+            sorted[idx]
+        };
+        LatencySnapshot {
+            count: self.count,
+            p50_ms: pct(50.0),
+            p95_ms: pct(95.0),
+            p99_ms: pct(99.0),
+            max_ms: sorted.last().copied().unwrap_or(0.0),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct LatencySnapshot {
+    pub count: u64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Default)]
+struct Collector {
+    routes: Mutex<HashMap<String, Samples>>,
+    queries: Mutex<HashMap<&'static str, Samples>>,
+}
+
+fn collector() -> &'static Collector {
+    static COLLECTOR: OnceLock<Collector> = OnceLock::new();
+    COLLECTOR.get_or_init(Collector::default)
+}
+
+/// Queries slower than this are logged with their parameters (env `SLOW_QUERY_MS`, default 500)
+pub fn slow_threshold() -> Duration {
+    static THRESHOLD: OnceLock<Duration> = OnceLock::new();
+    *THRESHOLD.get_or_init(|| {
+        let ms = env::var("SLOW_QUERY_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(500);
+        Duration::from_millis(ms)
+    })
+}
+
+pub fn as_ms(d: Duration) -> f64 {
+    (d.as_secs_f64() * 1_000_000.0).round() / 1000.0
+}
+
+// ==================== Recording ====================
+
+/// Time a named DB query; `params` is only rendered when the query is slow
+pub async fn timed<T, F, P>(name: &'static str, params: P, fut: F) -> T
+where
+    F: Future<Output = T>,
+    P: FnOnce() -> String,
+{
+    let start = Instant::now();
+    let out = fut.await;
+    record_query(name, start.elapsed(), params);
+    out
+}
+
+/// Record a query duration measured by the caller (e.g. a fully drained row stream)
+pub fn record_query<P: FnOnce() -> String>(name: &'static str, elapsed: Duration, params: P) {
+    collector().queries.lock().unwrap().entry(name).or_default().record(as_ms(elapsed));
+    if elapsed >= slow_threshold() {
+        warn!(query = name, elapsed_ms = as_ms(elapsed), params = %params(), "slow query");
+    }
+}
+
+/// Route middleware recording latency keyed by method and matched path
+pub async fn track_route(req: Request, next: Next) -> Response {
+    let key = match req.extensions().get::<MatchedPath>() {
+        Some(path) => format!("{} {}", req.method(), path.as_str()),
+        None => format!("{} <unmatched>", req.method()),
+    };
+    let start = Instant::now();
+    let resp = next.run(req).await;
+    collector().routes.lock().unwrap().entry(key).or_default().record(as_ms(start.elapsed()));
+    resp
+}
+
+// ==================== Snapshot ====================
+
+/// Current percentiles keyed by route and by query name
+pub fn snapshot() -> (BTreeMap<String, LatencySnapshot>, BTreeMap<&'static str, LatencySnapshot>) {
+    let routes = collector().routes.lock().unwrap().iter().map(|(k, v)| (k.clone(), v.snapshot())).collect();
+    let queries = collector().queries.lock().unwrap().iter().map(|(k, v)| (*k, v.snapshot())).collect();
+    (routes, queries)
+}
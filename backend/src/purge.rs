@@ -0,0 +1,156 @@
+//! Bulk event purge for FOD Detection Backend
+//! Deletes events matching a class and/or filter expression after a dry run, and logs the action
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use tracing::{info, warn};
+
+use crate::{
+    auth,
+    db::{internal, push_event_where},
+    filter::Filter,
+    perf, AppState,
+};
+
+/// Sources listed in a dry run, most events first
+const TOP_SOURCES: i64 = 20;
+
+// ==================== Request Types ====================
+
+/// `class` and/or `filter` as for /events/query; at least one is required
+#[derive(Deserialize)]
+pub struct PurgeRequest {
+    pub class: Option<String>,
+    /// Filter expression (see `filter`)
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+    /// `confirm_token` from a dry run over the same criteria
+    pub confirm: Option<String>,
+    pub reason: Option<String>,
+}
+
+// ==================== Matching ====================
+
+/// Matching events and the newest seq among them; deletion stops at that seq, so rows
+/// inserted after the count are never purged unseen
+async fn matching(db: &PgPool, class: Option<&str>, filter: Option<&Filter>) -> Result<(i64, i64), (StatusCode, String)> {
+    let mut qb = QueryBuilder::<Postgres>::new("SELECT COUNT(*), COALESCE(MAX(e.seq), 0) FROM events e");
+    push_event_where(&mut qb, class, filter);
+    let q = qb.build_query_as::<(i64, i64)>().fetch_one(db);
+    perf::timed("purge_matching", || format!("class={:?} filter={:?}", class, filter), q).await.map_err(internal)
+}
+
+async fn by_source(db: &PgPool, class: Option<&str>, filter: Option<&Filter>) -> Result<Vec<(String, i64)>, (StatusCode, String)> {
+    let mut qb = QueryBuilder::<Postgres>::new("SELECT e.source_ref, COUNT(*) AS count FROM events e");
+    push_event_where(&mut qb, class, filter);
+    qb.push(" GROUP BY e.source_ref ORDER BY count DESC, e.source_ref LIMIT ").push_bind(TOP_SOURCES);
+    qb.build_query_as().fetch_all(db).await.map_err(internal)
+}
+
+/// Token a dry run hands out; it only matches while the criteria and matching rows are unchanged
+fn confirm_token(criteria: &Value, matched: i64, upto: i64) -> String {
+    format!("{:x}", Sha256::digest(format!("{}|{}|{}", criteria, matched, upto).as_bytes()))
+}
+
+/// Delete matching events up to `upto`, with their archived AI responses, and take them back
+/// out of the rollups so the dashboards no longer count them
+async fn delete(
+    tx: &mut sqlx::PgConnection,
+    class: Option<&str>,
+    filter: Option<&Filter>,
+    upto: i64,
+) -> Result<i64, (StatusCode, String)> {
+    let mut qb = QueryBuilder::<Postgres>::new("WITH gone AS (DELETE FROM events e");
+    push_event_where(&mut qb, class, filter);
+    qb.push(" AND e.seq <= ").push_bind(upto);
+    qb.push(
+        r#"
+            RETURNING e.id, e.ts, e.class_id, e.zone, e.source_ref, e.object_count, e.confidence
+        ), raw AS (
+            DELETE FROM raw_inferences WHERE event_ids && ARRAY(SELECT id FROM gone)
+        )
+        SELECT COUNT(*) FROM (
+            SELECT event_rollup_add(ts, class_id, zone, source_ref, -1, object_count, confidence)
+            FROM gone
+        ) r"#,
+    );
+    qb.build_query_scalar().fetch_one(tx).await.map_err(internal)
+}
+
+// ==================== Handler ====================
+
+/// POST /admin/events/purge — `{class?, filter?, dry_run, confirm?, reason?}` delete matching
+/// events; a dry run reports the count and a `confirm_token` the real run must send back (admin)
+pub async fn purge_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<PurgeRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let class = req.class.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    let filter = req
+        .filter
+        .as_deref()
+        .filter(|f| !f.trim().is_empty())
+        .map(Filter::parse)
+        .transpose()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid filter: {}", e)))?;
+    if class.is_none() && filter.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "class or filter is required".to_string()));
+    }
+    let criteria = json!({ "class": class, "filter": req.filter.as_deref().map(str::trim) });
+
+    let (matched, upto) = matching(&st.db, class, filter.as_ref()).await?;
+    let token = confirm_token(&criteria, matched, upto);
+    if req.dry_run {
+        info!(user = %claims.username, criteria = %criteria, events = matched, "event purge dry run");
+        return Ok(Json(json!({
+            "dry_run": true,
+            "events_matched": matched,
+            "by_source": by_source(&st.db, class, filter.as_ref())
+                .await?
+                .into_iter()
+                .map(|(source_ref, count)| json!({ "source_ref": source_ref, "count": count }))
+                .collect::<Vec<_>>(),
+            "confirm_token": token,
+        })));
+    }
+
+    let Some(confirm) = req.confirm.as_deref() else {
+        return Err((StatusCode::BAD_REQUEST, "confirm is required; run with dry_run=true for a token".to_string()));
+    };
+    if confirm != token {
+        warn!(user = %claims.username, criteria = %criteria, "event purge refused, stale or foreign confirm token");
+        return Err((StatusCode::CONFLICT, "Matching events changed since the dry run; run it again".to_string()));
+    }
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let deleted = delete(&mut tx, class, filter.as_ref(), upto).await?;
+    let purge_id: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO event_purges (requested_by, reason, criteria, events_deleted)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(&claims.username)
+    .bind(&req.reason)
+    .bind(&criteria)
+    .bind(deleted)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(internal)?;
+    tx.commit().await.map_err(internal)?;
+
+    info!(purge_id, user = %claims.username, criteria = %criteria, reason = ?req.reason, events = deleted, "events purged");
+    Ok(Json(json!({ "purge_id": purge_id, "events_deleted": deleted })))
+}
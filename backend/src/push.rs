@@ -0,0 +1,340 @@
+//! Mobile push notifications for FOD Detection Backend
+//! Device token registration and FCM/APNs delivery of `push` alert messages, with the frame as thumbnail
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::FromRow;
+use std::{env, sync::Mutex};
+use time::OffsetDateTime;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{alerts, crypto, db::internal, secrets, subscriptions, uploads, AppState};
+
+/// Channel name in subscriptions and on-call members that means "push to my phones"
+pub const CHANNEL: &str = "push";
+/// Thumbnail links stay valid this long
+const THUMBNAIL_TTL_SECS: u64 = 3600;
+/// APNs refuses provider tokens renewed more often than every 20 minutes or older than an hour
+const APNS_TOKEN_REUSE_SECS: i64 = 40 * 60;
+pub const PLATFORMS: [&str; 2] = ["fcm", "apns"];
+
+// ==================== Config ====================
+
+pub struct Apns {
+    /// `PUSH_APNS_URL`, default the production gateway
+    pub url: String,
+    /// `.p8` signing key (`PUSH_APNS_KEY` or `PUSH_APNS_KEY_FILE`)
+    pub key_pem: String,
+    pub key_id: String,
+    pub team_id: String,
+    /// App bundle id (`PUSH_APNS_TOPIC`)
+    pub topic: String,
+    /// Provider token and when it was issued
+    token: Mutex<Option<(i64, String)>>,
+}
+
+impl Apns {
+    pub fn new(url: String, key_pem: String, key_id: String, team_id: String, topic: String) -> Self {
+        Apns { url, key_pem, key_id, team_id, topic, token: Mutex::new(None) }
+    }
+
+    /// ES256 provider token, reused for APNS_TOKEN_REUSE_SECS
+    fn provider_token(&self) -> Result<String, String> {
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        let mut cached = self.token.lock().unwrap();
+        if let Some((_, token)) = cached.as_ref().filter(|(issued, _)| now - issued < APNS_TOKEN_REUSE_SECS) {
+            return Ok(token.clone());
+        }
+        let key = EncodingKey::from_ec_pem(self.key_pem.as_bytes()).map_err(|e| format!("PUSH_APNS_KEY: {}", e))?;
+        let header = Header { kid: Some(self.key_id.clone()), ..Header::new(Algorithm::ES256) };
+        let token = encode(&header, &json!({ "iss": self.team_id, "iat": now }), &key).map_err(|e| e.to_string())?;
+        *cached = Some((now, token.clone()));
+        Ok(token)
+    }
+}
+
+pub struct Config {
+    /// FCM legacy HTTP endpoint (`PUSH_FCM_URL`)
+    pub fcm_url: String,
+    /// `PUSH_FCM_SERVER_KEY`; FCM tokens are not delivered to without it
+    pub fcm_key: Option<String>,
+    /// Set when `PUSH_APNS_KEY`, `_KEY_ID`, `_TEAM_ID` and `_TOPIC` all are
+    pub apns: Option<Apns>,
+    /// Lowest alert severity pushed (`PUSH_MIN_SEVERITY`, default critical); lower ones are skipped
+    pub min_severity: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { fcm_url: "https://fcm.googleapis.com/fcm/send".to_string(), fcm_key: None, apns: None, min_severity: "critical".to_string() }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let var = |name: &str| env::var(name).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let default = Config::default();
+        let apns = match (secrets::get("PUSH_APNS_KEY"), var("PUSH_APNS_KEY_ID"), var("PUSH_APNS_TEAM_ID"), var("PUSH_APNS_TOPIC")) {
+            (Some(key), Some(key_id), Some(team_id), Some(topic)) => {
+                let url = var("PUSH_APNS_URL").unwrap_or_else(|| "https://api.push.apple.com".to_string());
+                Some(Apns::new(url, key, key_id, team_id, topic))
+            }
+            _ => None,
+        };
+        Config {
+            fcm_url: var("PUSH_FCM_URL").unwrap_or(default.fcm_url),
+            fcm_key: secrets::get("PUSH_FCM_SERVER_KEY"),
+            apns,
+            min_severity: var("PUSH_MIN_SEVERITY")
+                .map(|s| s.to_lowercase())
+                .filter(|s| subscriptions::SEVERITIES.contains(&s.as_str()))
+                .unwrap_or(default.min_severity),
+        }
+    }
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct Device {
+    pub token: String,
+    pub platform: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct DeviceRequest {
+    pub platform: String,
+    pub token: String,
+}
+
+/// What one device is sent
+struct Message {
+    title: String,
+    body: String,
+    alert_id: i64,
+    event_id: Uuid,
+    /// Presigned link to the frame the event was detected on
+    image: Option<String>,
+    /// Where on the frame, for the app to draw the box
+    bbox: Option<Value>,
+}
+
+// ==================== Delivery ====================
+
+enum Failure {
+    /// The push service no longer knows this token
+    Gone,
+    Other(String),
+}
+
+async fn send_fcm(st: &AppState, token: &str, m: &Message) -> Result<(), Failure> {
+    let key = st.push.fcm_key.as_deref().ok_or_else(|| Failure::Other("PUSH_FCM_SERVER_KEY is not set".to_string()))?;
+    // FCM data values must be strings
+    let mut data = json!({ "alert_id": m.alert_id.to_string(), "event_id": m.event_id.to_string() });
+    if let Some(bbox) = &m.bbox {
+        data["bbox"] = json!(bbox.to_string());
+    }
+    let mut notification = json!({ "title": m.title, "body": m.body });
+    if let Some(image) = &m.image {
+        notification["image"] = json!(image);
+    }
+    let resp = st
+        .http
+        .post(&st.push.fcm_url)
+        .header("authorization", format!("key={}", key))
+        .json(&json!({ "to": token, "priority": "high", "notification": notification, "data": data }))
+        .send()
+        .await
+        .map_err(|e| Failure::Other(e.to_string()))?;
+    if !resp.status().is_success() {
+        return Err(Failure::Other(format!("FCM returned {}", resp.status())));
+    }
+    let body: Value = resp.json().await.map_err(|e| Failure::Other(e.to_string()))?;
+    match body["results"][0]["error"].as_str() {
+        None => Ok(()),
+        Some("NotRegistered" | "InvalidRegistration") => Err(Failure::Gone),
+        Some(e) => Err(Failure::Other(format!("FCM: {}", e))),
+    }
+}
+
+async fn send_apns(st: &AppState, token: &str, m: &Message) -> Result<(), Failure> {
+    let apns = st.push.apns.as_ref().ok_or_else(|| Failure::Other("APNs is not configured".to_string()))?;
+    let jwt = apns.provider_token().map_err(Failure::Other)?;
+    let payload = json!({
+        "aps": { "alert": { "title": m.title, "body": m.body }, "sound": "default", "mutable-content": 1 },
+        "alert_id": m.alert_id,
+        "event_id": m.event_id,
+        "image": m.image,
+        "bbox": m.bbox,
+    });
+    let resp = st
+        .http
+        .post(format!("{}/3/device/{}", apns.url.trim_end_matches('/'), token))
+        .header("authorization", format!("bearer {}", jwt))
+        .header("apns-topic", &apns.topic)
+        .header("apns-push-type", "alert")
+        .header("apns-priority", "10")
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| Failure::Other(e.to_string()))?;
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let reason = resp.json::<Value>().await.ok().and_then(|b| b["reason"].as_str().map(str::to_string)).unwrap_or_default();
+    if status.as_u16() == 410 || reason == "BadDeviceToken" || reason == "Unregistered" {
+        return Err(Failure::Gone);
+    }
+    Err(Failure::Other(format!("APNs returned {} {}", status, reason)))
+}
+
+/// Presigned link to the event's frame in the upload store, and its box
+async fn thumbnail(st: &AppState, event_id: Uuid) -> Result<(Option<String>, Option<Value>), (StatusCode, String)> {
+    let Some(mut event) = st.events.get(event_id).await? else { return Ok((None, None)) };
+    if let Some(meta) = event.meta.as_mut() {
+        crypto::decrypt_meta(meta);
+    }
+    let key = event.meta.as_ref().and_then(|m| m.get(uploads::META_KEY)).and_then(Value::as_str);
+    let image = match (key, uploads::bucket(st)) {
+        (Some(key), Ok(bucket)) => bucket.presign_get(key, THUMBNAIL_TTL_SECS).ok(),
+        _ => None,
+    };
+    Ok((image, event.bbox))
+}
+
+/// Deliver pending `push` alert messages to every phone of their user; sent when any phone took
+/// it. Returns the number sent.
+pub async fn deliver(st: &AppState) -> Result<u64, (StatusCode, String)> {
+    let pending = alerts::outgoing(&st.db, CHANNEL).await?;
+    let mut sent = 0;
+    for n in pending {
+        if alerts::below(n.severity.as_deref(), &st.push.min_severity) {
+            alerts::delivered(&st.db, n.id, "skipped", Some(format!("below PUSH_MIN_SEVERITY ({})", st.push.min_severity))).await?;
+            continue;
+        }
+        let devices: Vec<(String, String)> = sqlx::query_as("SELECT token, platform FROM push_devices WHERE user_id = $1 ORDER BY created_at")
+            .bind(n.user_id)
+            .fetch_all(&st.db)
+            .await
+            .map_err(internal)?;
+        if devices.is_empty() {
+            alerts::delivered(&st.db, n.id, "skipped", Some("no registered devices".to_string())).await?;
+            continue;
+        }
+        let (image, bbox) = thumbnail(st, n.event_id).await?;
+        let message = Message {
+            title: n.subject.unwrap_or_else(|| "FOD alert".to_string()),
+            body: n.body,
+            alert_id: n.alert_id,
+            event_id: n.event_id,
+            image,
+            bbox,
+        };
+
+        let (mut delivered, mut errors) = (false, Vec::new());
+        for (token, platform) in devices {
+            let result = match platform.as_str() {
+                "apns" => send_apns(st, &token, &message).await,
+                _ => send_fcm(st, &token, &message).await,
+            };
+            match result {
+                Ok(()) => delivered = true,
+                Err(Failure::Gone) => {
+                    info!(platform = %platform, "push token no longer registered, removing");
+                    sqlx::query("DELETE FROM push_devices WHERE token = $1").bind(&token).execute(&st.db).await.map_err(internal)?;
+                    errors.push(format!("{}: token no longer registered", platform));
+                }
+                Err(Failure::Other(e)) => {
+                    warn!(notification_id = n.id, platform = %platform, error = %e, "push delivery failed");
+                    errors.push(format!("{}: {}", platform, e));
+                }
+            }
+        }
+        let error = (!errors.is_empty()).then(|| errors.join("; "));
+        alerts::delivered(&st.db, n.id, if delivered { "sent" } else { "failed" }, error).await?;
+        sent += u64::from(delivered);
+    }
+    Ok(sent)
+}
+
+// ==================== Handlers ====================
+
+/// GET /users/:id/push-devices — the user's registered phones
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    subscriptions::authorize(&headers, user_id)?;
+    let devices = sqlx::query_as::<_, Device>("SELECT token, platform, created_at FROM push_devices WHERE user_id = $1 ORDER BY created_at")
+        .bind(user_id)
+        .fetch_all(&st.db)
+        .await
+        .map_err(internal)?;
+    Ok(Json(devices))
+}
+
+/// POST /users/:id/push-devices — `{platform: fcm|apns, token}` register a phone; a token seen
+/// before moves to this user
+pub async fn register_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<DeviceRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    subscriptions::authorize(&headers, user_id)?;
+    let platform = req.platform.trim().to_lowercase();
+    if !PLATFORMS.contains(&platform.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown platform {:?}, expected one of {}", req.platform, PLATFORMS.join(", "))));
+    }
+    let token = req.token.trim();
+    if token.is_empty() || token.len() > 4096 {
+        return Err((StatusCode::BAD_REQUEST, "token is required".to_string()));
+    }
+    let device = sqlx::query_as::<_, Device>(
+        r#"
+        INSERT INTO push_devices (token, user_id, platform)
+        SELECT $1, id, $3 FROM users WHERE id = $2
+        ON CONFLICT (token) DO UPDATE SET user_id = EXCLUDED.user_id, platform = EXCLUDED.platform, created_at = CURRENT_TIMESTAMP
+        RETURNING token, platform, created_at
+        "#,
+    )
+    .bind(token)
+    .bind(user_id)
+    .bind(&platform)
+    .fetch_optional(&st.db)
+    .await
+    .map_err(internal)?
+    .ok_or((StatusCode::NOT_FOUND, format!("User not found: {}", user_id)))?;
+    Ok((StatusCode::CREATED, Json(device)))
+}
+
+/// DELETE /users/:id/push-devices/:token — stop pushing to a phone
+pub async fn delete_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path((user_id, token)): Path<(Uuid, String)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    subscriptions::authorize(&headers, user_id)?;
+    let done = sqlx::query("DELETE FROM push_devices WHERE user_id = $1 AND token = $2")
+        .bind(user_id)
+        .bind(&token)
+        .execute(&st.db)
+        .await
+        .map_err(internal)?;
+    if done.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Device not registered".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
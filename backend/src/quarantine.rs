@@ -0,0 +1,119 @@
+//! Quarantined sources for FOD Detection Backend
+//! Sources whose events are stored but kept out of dashboards, alerts and exports unless `include_quarantine=true`
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::collections::HashMap;
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{auth, db::internal, perf, AppState};
+
+/// Query parameter that brings quarantined sources back in
+pub const PARAM: &str = "include_quarantine";
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct PutQuarantine {
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct QuarantinedSource {
+    pub source_ref: String,
+    pub reason: Option<String>,
+    pub created_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    /// Events stored from this source so far
+    pub events: i64,
+}
+
+// ==================== Filtering ====================
+
+/// `include_quarantine=true|false` from a query string, false when absent
+pub fn include(q: &HashMap<String, String>) -> Result<bool, (StatusCode, String)> {
+    match q.get(PARAM).map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("false") | Some("0") => Ok(false),
+        Some("true") | Some("1") => Ok(true),
+        Some(_) => Err((StatusCode::BAD_REQUEST, format!("{} must be true or false", PARAM))),
+    }
+}
+
+pub async fn is_quarantined(db: &PgPool, source_ref: &str) -> Result<bool, (StatusCode, String)> {
+    let q = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM quarantine_sources WHERE source_ref = $1)").bind(source_ref).fetch_one(db);
+    perf::timed("is_quarantined", || format!("source_ref={}", source_ref), q).await.map_err(internal)
+}
+
+async fn list(db: &PgPool) -> Result<Vec<QuarantinedSource>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, QuarantinedSource>(
+        r#"
+        SELECT q.source_ref, q.reason, q.created_by, q.created_at,
+               (SELECT COUNT(*) FROM events e WHERE e.source_ref = q.source_ref) AS events
+        FROM quarantine_sources q
+        ORDER BY q.source_ref
+        "#,
+    )
+    .fetch_all(db);
+    perf::timed("list_quarantine_sources", String::new, q).await.map_err(internal)
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/quarantine — quarantined sources with how many events each has stored (admin)
+pub async fn list_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    Ok(Json(list(&st.db).await?))
+}
+
+/// PUT /admin/quarantine/:source_ref — `{reason?}` quarantine a source; its past and future
+/// events leave the dashboards, alerts and exports (admin)
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(source_ref): Path<String>,
+    Json(body): Json<PutQuarantine>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let source_ref = source_ref.trim().to_string();
+    if source_ref.is_empty() || source_ref.len() > 255 {
+        return Err((StatusCode::BAD_REQUEST, "source_ref must be 1 to 255 characters".to_string()));
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO quarantine_sources (source_ref, reason, created_by) VALUES ($1, $2, $3)
+        ON CONFLICT (source_ref) DO UPDATE SET reason = EXCLUDED.reason
+        "#,
+    )
+    .bind(&source_ref)
+    .bind(&body.reason)
+    .bind(&claims.username)
+    .execute(&st.db)
+    .await
+    .map_err(internal)?;
+    info!(source_ref = %source_ref, reason = ?body.reason, by = %claims.username, "source quarantined");
+    Ok(Json(list(&st.db).await?))
+}
+
+/// DELETE /admin/quarantine/:source_ref — release a source; its events count again, including
+/// those stored while it was quarantined (admin)
+pub async fn delete_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(source_ref): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let done = sqlx::query("DELETE FROM quarantine_sources WHERE source_ref = $1").bind(&source_ref).execute(&st.db).await.map_err(internal)?;
+    if done.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Source is not quarantined".to_string()));
+    }
+    info!(source_ref = %source_ref, by = %claims.username, "source released from quarantine");
+    Ok(Json(list(&st.db).await?))
+}
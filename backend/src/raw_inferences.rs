@@ -0,0 +1,130 @@
+//! Raw AI response archive for FOD Detection Backend
+//! Full detect responses and frames behind saved events, kept for a short time to debug the model
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::{env, sync::OnceLock, time::Duration};
+use time::OffsetDateTime;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{auth, db::internal, jobs, perf, AppState};
+
+/// Expired responses are deleted this often
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+// ==================== Config ====================
+
+struct Config {
+    /// Archive every saved response (`RAW_INFERENCES`, default off); otherwise only on request
+    enabled: bool,
+    /// Responses older than this are deleted
+    retention_days: i32,
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| Config {
+        enabled: env::var("RAW_INFERENCES").is_ok_and(|s| matches!(s.trim(), "1" | "true" | "on")),
+        retention_days: env::var("RAW_INFERENCE_RETENTION_DAYS").ok().and_then(|s| s.parse().ok()).filter(|&d| d > 0).unwrap_or(7),
+    })
+}
+
+/// Whether to archive a response, given the request's own `raw` flag
+pub fn wanted(requested: Option<bool>) -> bool {
+    requested.unwrap_or(config().enabled)
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct RawInference {
+    pub id: Uuid,
+    pub event_ids: Vec<Uuid>,
+    pub source_ref: Option<String>,
+    pub response: Value,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+// ==================== Storage ====================
+
+/// Keep `response` and the frame it came from for the events saved from it
+pub async fn store(
+    db: impl PgExecutor<'_>,
+    event_ids: &[Uuid],
+    source_ref: &str,
+    response: &Value,
+    image: &[u8],
+) -> Result<(), (StatusCode, String)> {
+    let q = sqlx::query("INSERT INTO raw_inferences (event_ids, source_ref, response, image) VALUES ($1, $2, $3, $4)")
+        .bind(event_ids)
+        .bind(source_ref)
+        .bind(response)
+        .bind(image)
+        .execute(db);
+    perf::timed("store_raw_inference", || format!("events={} source_ref={:?}", event_ids.len(), source_ref), q)
+        .await
+        .map_err(internal)?;
+    Ok(())
+}
+
+/// Delete responses past the retention period, returns the number deleted
+pub async fn prune(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let done = sqlx::query("DELETE FROM raw_inferences WHERE created_at < NOW() - make_interval(days => $1)")
+        .bind(config().retention_days)
+        .execute(db)
+        .await?;
+    Ok(done.rows_affected())
+}
+
+/// Hourly prune of expired responses on one replica. Runs with archiving off too, since
+/// single requests can still ask for it (`raw=true`).
+pub fn spawn_pruner(state: AppState) {
+    info!(enabled = config().enabled, retention_days = config().retention_days, "raw inference pruner started");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            tick.tick().await;
+            match jobs::run_singleton(&state.db, "raw_inference_prune", prune(&state.db)).await {
+                Ok(Some(Ok(n))) if n > 0 => info!(deleted = n, "expired raw inferences deleted"),
+                Ok(Some(Ok(_))) | Ok(None) => {}
+                Ok(Some(Err(e))) | Err(e) => warn!(error = %e, "raw inference prune failed"),
+            }
+        }
+    });
+}
+
+// ==================== Handlers ====================
+
+/// GET /events/:id/raw — full AI response the event was saved from, while retained (login required)
+pub async fn get_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let q = sqlx::query_as::<_, RawInference>(
+        r#"
+        SELECT id, event_ids, source_ref, response, created_at
+        FROM raw_inferences
+        WHERE event_ids @> ARRAY[$1::UUID]
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(&st.db);
+    perf::timed("get_raw_inference", || format!("event_id={}", id), q)
+        .await
+        .map_err(internal)?
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "No raw inference for this event (not archived or expired)".to_string()))
+}
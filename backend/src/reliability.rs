@@ -0,0 +1,137 @@
+//! Source reliability for FOD Detection Backend
+//! Rolling confirmed vs false-positive ratio per camera/drone from the review workflow
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool};
+use std::{env, sync::OnceLock};
+use time::OffsetDateTime;
+
+use crate::{db::internal, lifecycle, perf, subscriptions::SEVERITIES, AppState};
+
+// ==================== Config ====================
+
+struct Config {
+    /// Events newer than this many days count (`RELIABILITY_WINDOW_DAYS`)
+    window_days: i32,
+    /// Reviews needed before a score is trusted (`RELIABILITY_MIN_REVIEWS`)
+    min_reviews: i64,
+    /// Trusted scores below this mark the source unreliable (`RELIABILITY_LOW_SCORE`)
+    low_score: f64,
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| Config {
+        window_days: env::var("RELIABILITY_WINDOW_DAYS").ok().and_then(|s| s.parse().ok()).filter(|&d| d > 0).unwrap_or(30),
+        min_reviews: env::var("RELIABILITY_MIN_REVIEWS").ok().and_then(|s| s.parse().ok()).unwrap_or(10),
+        low_score: env::var("RELIABILITY_LOW_SCORE").ok().and_then(|s| s.parse().ok()).unwrap_or(0.5),
+    })
+}
+
+// ==================== Models ====================
+
+#[derive(FromRow)]
+struct Counts {
+    source_ref: String,
+    events: i64,
+    confirmed: i64,
+    false_positives: i64,
+    last_seen: OffsetDateTime,
+}
+
+#[derive(Serialize)]
+pub struct SourceScore {
+    pub source_ref: String,
+    pub events: i64,
+    /// Events a reviewer confirmed or dismissed
+    pub reviewed: i64,
+    pub confirmed: i64,
+    pub false_positives: i64,
+    /// Share of reviewed events confirmed, smoothed towards 0.5 while reviews are few
+    pub score: f64,
+    /// Enough reviews and a score below `RELIABILITY_LOW_SCORE`
+    pub unreliable: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_seen: OffsetDateTime,
+}
+
+impl From<Counts> for SourceScore {
+    fn from(c: Counts) -> Self {
+        let cfg = config();
+        let reviewed = c.confirmed + c.false_positives;
+        // Laplace smoothing: one confirmation and one false positive assumed up front
+        let score = (c.confirmed as f64 + 1.0) / (reviewed as f64 + 2.0);
+        SourceScore {
+            source_ref: c.source_ref,
+            events: c.events,
+            reviewed,
+            confirmed: c.confirmed,
+            false_positives: c.false_positives,
+            score,
+            unreliable: reviewed >= cfg.min_reviews && score < cfg.low_score,
+            last_seen: c.last_seen,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SourcesParams {
+    pub source_ref: Option<String>,
+}
+
+// ==================== Queries ====================
+
+/// Scores over the rolling window, least reliable first; one source with `source_ref`
+pub async fn scores(db: &PgPool, source_ref: Option<&str>) -> Result<Vec<SourceScore>, (StatusCode, String)> {
+    // Anything past `detected` other than a dismissal means someone saw the object
+    let q = sqlx::query_as::<_, Counts>(
+        r#"
+        SELECT source_ref,
+               COUNT(*) AS events,
+               COUNT(*) FILTER (WHERE state NOT IN ('detected', $3)) AS confirmed,
+               COUNT(*) FILTER (WHERE state = $3) AS false_positives,
+               MAX(ts) AS last_seen
+        FROM events
+        WHERE ts > NOW() - make_interval(days => $1)
+          AND ($2::TEXT IS NULL OR source_ref = $2)
+        GROUP BY source_ref
+        "#,
+    )
+    .bind(config().window_days)
+    .bind(source_ref)
+    .bind(lifecycle::FALSE_POSITIVE)
+    .fetch_all(db);
+    let rows = perf::timed("source_scores", || format!("source_ref={:?}", source_ref), q).await.map_err(internal)?;
+    let mut scores: Vec<SourceScore> = rows.into_iter().map(SourceScore::from).collect();
+    scores.sort_by(|a, b| a.score.total_cmp(&b.score).then_with(|| a.source_ref.cmp(&b.source_ref)));
+    Ok(scores)
+}
+
+/// `severity` one level lower for an unreliable source, unchanged otherwise
+pub async fn weigh_severity(db: &PgPool, source_ref: &str, severity: &str) -> Result<String, (StatusCode, String)> {
+    let unreliable = scores(db, Some(source_ref)).await?.first().is_some_and(|s| s.unreliable);
+    let lowered = match SEVERITIES.iter().position(|s| *s == severity) {
+        Some(i) if unreliable && i > 0 => SEVERITIES[i - 1],
+        _ => severity,
+    };
+    Ok(lowered.to_string())
+}
+
+// ==================== Handlers ====================
+
+/// GET /dashboard/sources?source_ref= — review-based reliability per source over the
+/// rolling window, least reliable first
+pub async fn sources_handler(
+    State(st): State<AppState>,
+    Query(p): Query<SourcesParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let sources = scores(&st.db, p.source_ref.as_deref()).await?;
+    Ok(Json(json!({ "window_days": config().window_days, "sources": sources })))
+}
@@ -0,0 +1,881 @@
+//! Inference replay for FOD Detection Backend
+//! Re-runs archived frames through another (usually newer) model and compares the detections;
+//! runs are queued jobs started by priority and due time within per-queue concurrency limits.
+//! Backfills (see `backfill`) queue on the same runs and share the `/jobs/:id/...` endpoints.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    Json,
+};
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+    env,
+    sync::OnceLock,
+};
+use time::{Duration, OffsetDateTime};
+use tokio::sync::{broadcast, Mutex};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{ai, auth, backfill, db::internal, jobs, AppState};
+
+/// Runs larger than this must be split into narrower ranges
+const MAX_FRAMES: i64 = 5000;
+/// Frames loaded from the archive at a time
+const FETCH_BATCH: i64 = 20;
+/// Upper bounds for a run's retry policy
+const MAX_ATTEMPTS: i32 = 10;
+const MAX_BACKOFF_MS: i32 = 60_000;
+/// Progress streams re-read the run at least this often, for runs working on another instance
+const PROGRESS_RECHECK: std::time::Duration = std::time::Duration::from_secs(1);
+/// Queued runs are looked at this often even when nothing on this instance changed, for due
+/// `run_at`s and slots freed on other instances
+const DISPATCH_EVERY: std::time::Duration = std::time::Duration::from_secs(5);
+/// Priorities run from -100 (background) to 100 (urgent)
+const MAX_PRIORITY: i32 = 100;
+/// Furthest ahead a run can be scheduled
+const MAX_SCHEDULE_AHEAD: Duration = Duration::days(30);
+
+/// Statuses of runs not over yet
+const ACTIVE: [&str; 2] = ["queued", "running"];
+
+/// Ids of runs that just moved on this instance, to wake their progress streams early
+pub(crate) fn progressed() -> &'static broadcast::Sender<i32> {
+    static TX: OnceLock<broadcast::Sender<i32>> = OnceLock::new();
+    TX.get_or_init(|| broadcast::channel(256).0)
+}
+
+// ==================== Config ====================
+
+/// Extra AI services by model name, from `AI_MODEL_URLS=name=url,name2=predict@url2`; the
+/// optional `adapter@` prefix picks the service's API contract (default `fod`)
+fn model_urls() -> &'static HashMap<String, ai::Backend> {
+    static URLS: OnceLock<HashMap<String, ai::Backend>> = OnceLock::new();
+    URLS.get_or_init(|| {
+        env::var("AI_MODEL_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| {
+                let (name, url) = pair.split_once('=')?;
+                let (name, url) = (name.trim(), url.trim());
+                if name.is_empty() || url.is_empty() {
+                    return None;
+                }
+                let backend = ai::Backend::parse(url);
+                if backend.is_none() {
+                    warn!(model = %name, spec = %url, "unknown adapter in AI_MODEL_URLS, model ignored");
+                }
+                Some((name.to_string(), backend?))
+            })
+            .collect()
+    })
+}
+
+/// Default per-frame retry policy: `JOB_MAX_ATTEMPTS` AI calls (default 3) starting
+/// `JOB_RETRY_BACKOFF_MS` apart (default 500, doubling)
+fn default_policy() -> (i32, i32) {
+    static POLICY: OnceLock<(i32, i32)> = OnceLock::new();
+    *POLICY.get_or_init(|| {
+        let var = |name: &str, default: i32| env::var(name).ok().and_then(|s| s.parse().ok()).unwrap_or(default);
+        (var("JOB_MAX_ATTEMPTS", 3).clamp(1, MAX_ATTEMPTS), var("JOB_RETRY_BACKOFF_MS", 500).clamp(0, MAX_BACKOFF_MS))
+    })
+}
+
+/// Runs at once per queue, from `JOB_QUEUE_CONCURRENCY=default=1,review=2`; queues not listed get 1
+fn concurrency(queue: &str) -> i64 {
+    static LIMITS: OnceLock<HashMap<String, i64>> = OnceLock::new();
+    let limits = LIMITS.get_or_init(|| {
+        env::var("JOB_QUEUE_CONCURRENCY")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| {
+                let (queue, n) = pair.split_once('=')?;
+                Some((queue.trim().to_string(), n.trim().parse::<i64>().ok().filter(|&n| n > 0)?))
+            })
+            .collect()
+    });
+    limits.get(queue).copied().unwrap_or(1)
+}
+
+/// A running run whose worker hasn't reported for this long (`JOB_STALE_SECS`, default 900) is
+/// taken to have died with its instance and is queued again
+fn stale_after() -> i64 {
+    static SECS: OnceLock<i64> = OnceLock::new();
+    *SECS.get_or_init(|| env::var("JOB_STALE_SECS").ok().and_then(|s| s.parse().ok()).filter(|&s| s > 0).unwrap_or(900))
+}
+
+// ==================== Models ====================
+
+#[derive(Deserialize)]
+pub struct ReplayParams {
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<OffsetDateTime>,
+    /// Name from `AI_MODEL_URLS`; the default AI service when absent
+    pub model: Option<String>,
+    pub conf: Option<f32>,
+    pub imgsz: Option<i32>,
+    /// AI calls per frame when the service fails transiently (default `JOB_MAX_ATTEMPTS`)
+    pub max_attempts: Option<i32>,
+    /// Delay before the first retry, doubling after each (default `JOB_RETRY_BACKOFF_MS`)
+    pub retry_backoff_ms: Option<i32>,
+    /// Queue the run waits in, sharing its concurrency limit (default `default`)
+    pub queue: Option<String>,
+    /// Higher starts first and may take a running lower-priority run's slot (default 0)
+    pub priority: Option<i32>,
+    /// Earliest start; now when absent
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub run_at: Option<OffsetDateTime>,
+}
+
+#[derive(Clone, Serialize, FromRow)]
+pub struct ReplayRun {
+    pub id: i32,
+    /// `replay`, or `zone_backfill` for runs of `POST /admin/backfill/zones`
+    pub kind: String,
+    pub model: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub range_from: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub range_to: OffsetDateTime,
+    pub status: String,
+    pub frames_total: i32,
+    pub frames_done: i32,
+    /// Detections the replayed model returned so far
+    pub detections: i32,
+    pub error: Option<String>,
+    pub requested_by: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub started_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub finished_at: Option<OffsetDateTime>,
+    pub conf: Option<f32>,
+    pub imgsz: Option<i32>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub cancel_requested_at: Option<OffsetDateTime>,
+    /// Times the run was started, 1 plus each retry
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub retry_backoff_ms: i32,
+    /// AI calls repeated after transient failures
+    pub ai_retries: i32,
+    pub queue: String,
+    pub priority: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub run_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub preempt_requested_at: Option<OffsetDateTime>,
+    /// Held by the worker running it; a worker that lost it stops
+    #[serde(skip)]
+    pub lease: Option<Uuid>,
+    /// Events per chunk of a backfill
+    pub batch_size: Option<i32>,
+    /// Last event `seq` a backfill got through
+    #[serde(skip)]
+    pub last_seq: i64,
+}
+
+impl ReplayRun {
+    /// Wait before AI call `attempt + 1` of a frame
+    fn backoff(&self, attempt: i32) -> std::time::Duration {
+        let ms = (self.retry_backoff_ms.max(0) as u64).saturating_mul(1 << (attempt - 1).clamp(0, 16));
+        std::time::Duration::from_millis(ms.min(MAX_BACKOFF_MS as u64))
+    }
+}
+
+/// Detections per class over the frames the replay got through
+#[derive(Serialize, FromRow)]
+pub struct ClassComparison {
+    pub class: String,
+    pub original: i64,
+    pub replay: i64,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct FrameResult {
+    pub raw_inference_id: Uuid,
+    pub event_ids: Vec<Uuid>,
+    /// Detected classes, sorted
+    pub original_classes: Vec<String>,
+    pub replay_classes: Option<Vec<String>>,
+    pub error: Option<String>,
+}
+
+#[derive(FromRow)]
+struct Frame {
+    id: Uuid,
+    event_ids: Vec<Uuid>,
+    response: Value,
+    image: Vec<u8>,
+    created_at: OffsetDateTime,
+}
+
+pub(crate) const RUN_COLUMNS: &str = "id, kind, model, range_from, range_to, status, frames_total, frames_done, detections, error, requested_by, \
+    started_at, finished_at, conf, imgsz, cancel_requested_at, attempts, max_attempts, retry_backoff_ms, ai_retries, \
+    queue, priority, run_at, preempt_requested_at, lease, batch_size, last_seq";
+
+/// One progress update of a run
+#[derive(Serialize)]
+pub struct Progress {
+    pub job_id: i32,
+    pub status: String,
+    pub frames_done: i32,
+    pub frames_total: i32,
+    pub percent: f64,
+    pub detections: i32,
+    pub elapsed_secs: f64,
+    /// Remaining time at the rate so far; None before the first frame and once finished
+    pub eta_secs: Option<f64>,
+}
+
+impl Progress {
+    fn of(run: &ReplayRun) -> Progress {
+        let started = run.started_at.unwrap_or_else(OffsetDateTime::now_utc);
+        let elapsed = (run.finished_at.unwrap_or_else(OffsetDateTime::now_utc) - started).as_seconds_f64().max(0.0);
+        let (done, total) = (run.frames_done.max(0) as f64, run.frames_total.max(0) as f64);
+        Progress {
+            job_id: run.id,
+            status: run.status.clone(),
+            frames_done: run.frames_done,
+            frames_total: run.frames_total,
+            percent: if total > 0.0 { (done * 1000.0 / total).round() / 10.0 } else { 0.0 },
+            detections: run.detections,
+            elapsed_secs: (elapsed * 10.0).round() / 10.0,
+            eta_secs: (run.status == "running" && done > 0.0).then(|| ((elapsed / done * (total - done).max(0.0)) * 10.0).round() / 10.0),
+        }
+    }
+}
+
+/// SQL for the sorted class list of the detect response in `column`
+fn classes_of(column: &str) -> String {
+    format!(
+        "(SELECT COALESCE(array_agg(d->>'cls' ORDER BY d->>'cls'), '{{}}') FROM jsonb_array_elements({}->'detections') d)",
+        column
+    )
+}
+
+// ==================== Job ====================
+
+/// Why a worker stopped
+pub(crate) enum Ended {
+    Done,
+    Cancelled,
+    /// Yielded its slot to a more urgent run; queued again to resume later
+    Preempted,
+    /// The run was taken from this worker (queued again after going quiet, or cancelled while queued)
+    LeaseLost,
+}
+
+/// Replay every archived frame of the run's range not replayed yet through `backend`, recording
+/// each result; transient AI failures are retried under the run's policy. Between frames the
+/// worker reports in and stops if the run was cancelled, preempted or taken from it.
+async fn run(state: &AppState, job: &ReplayRun, backend: &ai::Backend) -> Result<Ended, sqlx::Error> {
+    let run_id = job.id;
+    // A replay measures this model; fallback answers would only muddy the comparison
+    let opts = ai::DetectOptions { conf: job.conf, imgsz: job.imgsz, fallback: false };
+    let mut after: Option<(OffsetDateTime, Uuid)> = None;
+    loop {
+        let frames = sqlx::query_as::<_, Frame>(
+            r#"
+            SELECT id, event_ids, response, image, created_at FROM raw_inferences ri
+            WHERE created_at >= $1 AND created_at < $2 AND image IS NOT NULL
+              AND ($3::TIMESTAMPTZ IS NULL OR (created_at, id) > ($3, $4))
+              AND NOT EXISTS (SELECT 1 FROM replay_results r WHERE r.run_id = $6 AND r.raw_inference_id = ri.id)
+            ORDER BY created_at, id
+            LIMIT $5
+            "#,
+        )
+        .bind(job.range_from)
+        .bind(job.range_to)
+        .bind(after.map(|a| a.0))
+        .bind(after.map(|a| a.1))
+        .bind(FETCH_BATCH)
+        .bind(run_id)
+        .fetch_all(&state.db)
+        .await?;
+        let Some(last) = frames.last() else { break };
+        after = Some((last.created_at, last.id));
+
+        for f in frames {
+            let mut attempt = 1;
+            let (replay, err) = loop {
+                match backend.detect(&state.http, f.image.clone(), "replay.jpg".to_string(), opts).await {
+                    Ok(v) => break (Some(v), None),
+                    Err((code, e)) if ai::is_transient(code) && attempt < job.max_attempts => {
+                        warn!(run_id, frame = %f.id, attempt, error = %e, "transient AI failure, retrying frame");
+                        tokio::time::sleep(job.backoff(attempt)).await;
+                        attempt += 1;
+                    }
+                    Err((_, e)) => break (None, Some(e)),
+                }
+            };
+            let detections = replay.as_ref().and_then(|v| v.get("detections")).and_then(|d| d.as_array()).map_or(0, |d| d.len() as i32);
+            sqlx::query(
+                r#"
+                INSERT INTO replay_results (run_id, raw_inference_id, event_ids, original, replay, error, attempts)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT DO NOTHING
+                "#,
+            )
+            .bind(run_id)
+            .bind(f.id)
+            .bind(&f.event_ids)
+            .bind(&f.response)
+            .bind(replay)
+            .bind(err)
+            .bind(attempt)
+            .execute(&state.db)
+            .await?;
+            // Requests are read back here, so one made on any instance stops the run
+            let requested: Option<(bool, bool)> = sqlx::query_as(
+                r#"
+                UPDATE replay_runs SET frames_done = frames_done + 1, detections = detections + $2, ai_retries = ai_retries + $3,
+                                       heartbeat_at = NOW()
+                WHERE id = $1 AND lease = $4
+                RETURNING cancel_requested_at IS NOT NULL, preempt_requested_at IS NOT NULL
+                "#,
+            )
+            .bind(run_id)
+            .bind(detections)
+            .bind(attempt - 1)
+            .bind(job.lease)
+            .fetch_optional(&state.db)
+            .await?;
+            drop(progressed().send(run_id));
+            match requested {
+                None => return Ok(Ended::LeaseLost),
+                Some((true, _)) => return Ok(Ended::Cancelled),
+                Some((_, true)) => return Ok(Ended::Preempted),
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(Ended::Done)
+}
+
+/// Run a claimed run in the background (a replay through `backend`, else a backfill), record how
+/// it ended and let the next queued run in. A restart mid-run leaves it `running` until
+/// `JOB_STALE_SECS` pass, then it is queued again.
+fn spawn_run(state: AppState, job: ReplayRun, backend: Option<ai::Backend>) {
+    tokio::spawn(async move {
+        let run_id = job.id;
+        let ended = match &backend {
+            Some(backend) => run(&state, &job, backend).await,
+            None => backfill::zones(&state, &job).await,
+        };
+        let (status, err) = match ended {
+            Ok(Ended::Done) => ("done", None),
+            Ok(Ended::Cancelled) => ("cancelled", None),
+            Ok(Ended::Preempted) => ("queued", None),
+            Ok(Ended::LeaseLost) => {
+                warn!(run_id, "replay taken from this worker, stopping");
+                dispatch(&state).await;
+                return;
+            }
+            Err(e) => ("failed", Some(e.to_string())),
+        };
+        match &err {
+            None => info!(run_id, status, "replay stopped"),
+            Some(e) => error!(run_id, error = %e, "replay failed"),
+        }
+        let _ = sqlx::query(
+            r#"
+            UPDATE replay_runs SET status = $2, error = $3, lease = NULL, preempt_requested_at = NULL,
+                                   finished_at = CASE WHEN $2 = 'queued' THEN NULL ELSE NOW() END
+            WHERE id = $1 AND lease = $4
+            "#,
+        )
+        .bind(run_id)
+        .bind(status)
+        .bind(err)
+        .bind(job.lease)
+        .execute(&state.db)
+        .await;
+        drop(progressed().send(run_id));
+        dispatch(&state).await;
+    });
+}
+
+// ==================== Queue ====================
+
+/// Start `id` under a new lease if it is still queued
+async fn claim(db: &PgPool, id: i32) -> Result<Option<ReplayRun>, sqlx::Error> {
+    sqlx::query_as::<_, ReplayRun>(&format!(
+        r#"
+        UPDATE replay_runs SET status = 'running', lease = $2, heartbeat_at = NOW(), started_at = COALESCE(started_at, NOW()),
+                               preempt_requested_at = NULL
+        WHERE id = $1 AND status = 'queued'
+        RETURNING {}
+        "#,
+        RUN_COLUMNS
+    ))
+    .bind(id)
+    .bind(Uuid::new_v4())
+    .fetch_optional(db)
+    .await
+}
+
+/// One pass over the queue: requeue runs whose worker went quiet, then start due runs by priority
+/// while their queue has room. A due run facing a full queue asks the lowest-priority run below
+/// it there to yield, one at a time per queue.
+async fn dispatch_once(st: &AppState) -> Result<(), sqlx::Error> {
+    let stale: Vec<i32> = sqlx::query_scalar(
+        r#"
+        UPDATE replay_runs SET status = CASE WHEN cancel_requested_at IS NOT NULL THEN 'cancelled' ELSE 'queued' END,
+                               finished_at = CASE WHEN cancel_requested_at IS NOT NULL THEN NOW() END,
+                               lease = NULL, preempt_requested_at = NULL
+        WHERE status = 'running' AND COALESCE(heartbeat_at, started_at) < NOW() - make_interval(secs => $1)
+        RETURNING id
+        "#,
+    )
+    .bind(stale_after() as f64)
+    .fetch_all(&st.db)
+    .await?;
+    for id in stale {
+        warn!(run_id = id, stale_secs = stale_after(), "replay worker went quiet, run queued again");
+    }
+
+    let mut running: HashMap<String, i64> = sqlx::query_as::<_, (String, i64)>(
+        "SELECT queue, COUNT(*) FROM replay_runs WHERE status = 'running' GROUP BY queue",
+    )
+    .fetch_all(&st.db)
+    .await?
+    .into_iter()
+    .collect();
+    let mut yielding: HashSet<String> = sqlx::query_scalar(
+        "SELECT DISTINCT queue FROM replay_runs WHERE status = 'running' AND preempt_requested_at IS NOT NULL",
+    )
+    .fetch_all(&st.db)
+    .await?
+    .into_iter()
+    .collect();
+    let due: Vec<(i32, String, i32, String, Option<String>)> = sqlx::query_as(
+        "SELECT id, queue, priority, kind, model FROM replay_runs WHERE status = 'queued' AND run_at <= NOW() ORDER BY priority DESC, run_at, id",
+    )
+    .fetch_all(&st.db)
+    .await?;
+
+    for (id, queue, priority, kind, model) in due {
+        let active = running.entry(queue.clone()).or_insert(0);
+        if *active < concurrency(&queue) {
+            let backend = match kind.as_str() {
+                "replay" => backend_for(st, model.as_deref()).map(Some),
+                _ => Ok(None),
+            };
+            let backend = match backend {
+                Ok(b) => b,
+                Err((_, e)) => {
+                    sqlx::query("UPDATE replay_runs SET status = 'failed', error = $2, finished_at = NOW() WHERE id = $1 AND status = 'queued'")
+                        .bind(id)
+                        .bind(&e)
+                        .execute(&st.db)
+                        .await?;
+                    warn!(run_id = id, error = %e, "queued replay can't start");
+                    continue;
+                }
+            };
+            if let Some(run) = claim(&st.db, id).await? {
+                *active += 1;
+                info!(run_id = id, kind = %kind, queue = %queue, priority, "replay started");
+                drop(progressed().send(id));
+                spawn_run(st.clone(), run, backend);
+            }
+        } else if !yielding.contains(&queue) {
+            let preempted: Option<i32> = sqlx::query_scalar(
+                r#"
+                UPDATE replay_runs SET preempt_requested_at = NOW()
+                WHERE id = (
+                    SELECT id FROM replay_runs
+                    WHERE status = 'running' AND queue = $1 AND priority < $2 AND cancel_requested_at IS NULL
+                    ORDER BY priority, started_at DESC
+                    LIMIT 1
+                )
+                RETURNING id
+                "#,
+            )
+            .bind(&queue)
+            .bind(priority)
+            .fetch_optional(&st.db)
+            .await?;
+            if let Some(preempted) = preempted {
+                info!(run_id = preempted, for_run = id, queue = %queue, "replay asked to yield to a more urgent run");
+                yielding.insert(queue);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Pass over the queue now. Callers on this instance wait their turn, so a run queued while a
+/// pass is under way is still seen; across instances the advisory lock keeps passes apart.
+pub async fn dispatch(st: &AppState) {
+    static LOCAL: Mutex<()> = Mutex::const_new(());
+    let _turn = LOCAL.lock().await;
+    match jobs::run_singleton(&st.db, "replay_dispatch", dispatch_once(st)).await {
+        Ok(Some(Err(e))) | Err(e) => warn!(error = %e, "replay queue dispatch failed"),
+        Ok(_) => {}
+    }
+}
+
+/// Look at the queue every few seconds on every instance, for due runs and freed slots
+pub fn spawn_dispatcher(state: AppState) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(DISPATCH_EVERY);
+        loop {
+            tick.tick().await;
+            dispatch(&state).await;
+        }
+    });
+}
+
+/// The run's model, which must still be configured
+fn backend_for(st: &AppState, model: Option<&str>) -> Result<ai::Backend, (StatusCode, String)> {
+    match model {
+        None => Ok(st.ai.clone()),
+        Some(m) => model_urls().get(m).cloned().ok_or((StatusCode::BAD_REQUEST, format!("Unknown model: {} (see AI_MODEL_URLS)", m))),
+    }
+}
+
+pub(crate) async fn get_run(db: &PgPool, id: i32) -> Result<ReplayRun, (StatusCode, String)> {
+    sqlx::query_as::<_, ReplayRun>(&format!("SELECT {} FROM replay_runs WHERE id = $1", RUN_COLUMNS))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "Replay run not found".to_string()))
+}
+
+/// Queue, priority and start of a new run, defaults filled in
+pub(crate) fn schedule(
+    queue: Option<&str>,
+    priority: Option<i32>,
+    run_at: Option<OffsetDateTime>,
+) -> Result<(String, i32, OffsetDateTime), (StatusCode, String)> {
+    let queue = queue.map(str::trim).unwrap_or("default").to_string();
+    if queue.is_empty() || queue.len() > 50 || !queue.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err((StatusCode::BAD_REQUEST, "queue must be 1 to 50 letters, digits, - or _".to_string()));
+    }
+    let priority = priority.unwrap_or(0);
+    if !(-MAX_PRIORITY..=MAX_PRIORITY).contains(&priority) {
+        return Err((StatusCode::BAD_REQUEST, format!("priority must be -{0} to {0}", MAX_PRIORITY)));
+    }
+    let now = OffsetDateTime::now_utc();
+    let run_at = run_at.unwrap_or(now);
+    if run_at > now + MAX_SCHEDULE_AHEAD {
+        return Err((StatusCode::BAD_REQUEST, format!("run_at is at most {} days ahead", MAX_SCHEDULE_AHEAD.whole_days())));
+    }
+    Ok((queue, priority, run_at))
+}
+
+// ==================== Handlers ====================
+
+/// POST /admin/replay?from=&to=&model=&conf=&imgsz=&queue=&priority=&run_at= — re-run archived
+/// frames (default last 24 hours) through a model as a queued background job (admin)
+pub async fn start_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<ReplayParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let to = p.to.unwrap_or_else(OffsetDateTime::now_utc);
+    let from = p.from.unwrap_or(to - Duration::days(1));
+    if from >= to {
+        return Err((StatusCode::BAD_REQUEST, "from must be before to".to_string()));
+    }
+    backend_for(&st, p.model.as_deref())?;
+    let (default_attempts, default_backoff) = default_policy();
+    let max_attempts = p.max_attempts.unwrap_or(default_attempts);
+    let retry_backoff_ms = p.retry_backoff_ms.unwrap_or(default_backoff);
+    if !(1..=MAX_ATTEMPTS).contains(&max_attempts) || !(0..=MAX_BACKOFF_MS).contains(&retry_backoff_ms) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("max_attempts must be 1 to {} and retry_backoff_ms 0 to {}", MAX_ATTEMPTS, MAX_BACKOFF_MS),
+        ));
+    }
+    let (queue, priority, run_at) = schedule(p.queue.as_deref(), p.priority, p.run_at)?;
+
+    let frames: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM raw_inferences WHERE created_at >= $1 AND created_at < $2 AND image IS NOT NULL",
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    if frames == 0 {
+        return Err((StatusCode::NOT_FOUND, "No archived frames in range (see RAW_INFERENCES)".to_string()));
+    }
+    if frames > MAX_FRAMES {
+        return Err((StatusCode::BAD_REQUEST, format!("{} frames in range, at most {} per run", frames, MAX_FRAMES)));
+    }
+
+    let run = sqlx::query_as::<_, ReplayRun>(&format!(
+        r#"
+        INSERT INTO replay_runs (model, range_from, range_to, frames_total, requested_by, conf, imgsz, max_attempts, retry_backoff_ms,
+                                 queue, priority, run_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING {}
+        "#,
+        RUN_COLUMNS
+    ))
+    .bind(&p.model)
+    .bind(from)
+    .bind(to)
+    .bind(frames as i32)
+    .bind(&claims.username)
+    .bind(p.conf)
+    .bind(p.imgsz)
+    .bind(max_attempts)
+    .bind(retry_backoff_ms)
+    .bind(&queue)
+    .bind(priority)
+    .bind(run_at)
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    info!(run_id = run.id, model = ?p.model, frames, queue = %queue, priority, run_at = %run_at, by = %claims.username, "replay queued");
+    // Starts now when due and its queue has room
+    dispatch(&st).await;
+    Ok((StatusCode::ACCEPTED, Json(get_run(&st.db, run.id).await?)))
+}
+
+/// GET /admin/replay — recent replay runs (admin)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let runs = sqlx::query_as::<_, ReplayRun>(&format!("SELECT {} FROM replay_runs WHERE kind = 'replay' ORDER BY id DESC LIMIT 50", RUN_COLUMNS))
+        .fetch_all(&st.db)
+        .await
+        .map_err(internal)?;
+    Ok(Json(runs))
+}
+
+/// GET /admin/replay/:id — progress, frames whose classes changed and per-class counts (admin)
+pub async fn get_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let run = get_run(&st.db, id).await?;
+    let (frames_changed, frames_failed): (i64, i64) = sqlx::query_as(&format!(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE replay IS NOT NULL AND {} IS DISTINCT FROM {}),
+               COUNT(*) FILTER (WHERE replay IS NULL)
+        FROM replay_results WHERE run_id = $1
+        "#,
+        classes_of("original"),
+        classes_of("replay")
+    ))
+    .bind(id)
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    let classes = sqlx::query_as::<_, ClassComparison>(
+        r#"
+        WITH o AS (
+            SELECT d->>'cls' AS class, COUNT(*) AS n
+            FROM replay_results r, jsonb_array_elements(r.original->'detections') d
+            WHERE r.run_id = $1 AND r.replay IS NOT NULL
+            GROUP BY 1
+        ),
+        p AS (
+            SELECT d->>'cls' AS class, COUNT(*) AS n
+            FROM replay_results r, jsonb_array_elements(r.replay->'detections') d
+            WHERE r.run_id = $1
+            GROUP BY 1
+        )
+        SELECT class, COALESCE(o.n, 0) AS original, COALESCE(p.n, 0) AS replay
+        FROM o FULL JOIN p USING (class)
+        ORDER BY class
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(json!({
+        "run": run,
+        "frames_changed": frames_changed,
+        "frames_failed": frames_failed,
+        "classes": classes,
+    })))
+}
+
+/// POST /jobs/:id/cancel — cancel a queued replay or backfill, or ask a running one to stop; its
+/// worker, on whichever instance, ends it as `cancelled` after the frame or chunk in hand (admin)
+pub async fn cancel_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let run = sqlx::query_as::<_, ReplayRun>(&format!(
+        r#"
+        UPDATE replay_runs SET cancel_requested_at = COALESCE(cancel_requested_at, NOW()),
+                               status = CASE WHEN status = 'queued' THEN 'cancelled' ELSE status END,
+                               finished_at = CASE WHEN status = 'queued' THEN NOW() ELSE finished_at END
+        WHERE id = $1 AND status IN ('queued', 'running')
+        RETURNING {}
+        "#,
+        RUN_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(&st.db)
+    .await
+    .map_err(internal)?;
+    let Some(run) = run else {
+        let run = get_run(&st.db, id).await?;
+        return Err((StatusCode::CONFLICT, format!("Replay run is already {}", run.status)));
+    };
+    info!(run_id = id, status = %run.status, by = %claims.username, "replay cancellation requested");
+    drop(progressed().send(id));
+    Ok((StatusCode::ACCEPTED, Json(run)))
+}
+
+/// POST /jobs/:id/retry — queue a failed or cancelled replay, or one that finished with failed
+/// frames, again as a new attempt: frames without a replayed result are run again. A failed or
+/// cancelled backfill carries on after the last chunk it got through (admin)
+pub async fn retry_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let current = get_run(&st.db, id).await?;
+    if current.kind == "replay" {
+        backend_for(&st, current.model.as_deref())?;
+    }
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let failed = sqlx::query("DELETE FROM replay_results WHERE run_id = $1 AND replay IS NULL")
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?
+        .rows_affected();
+    // The status condition makes concurrent retries start the run once
+    let run = sqlx::query_as::<_, ReplayRun>(&format!(
+        r#"
+        UPDATE replay_runs SET
+            status = 'queued', error = NULL, finished_at = NULL, cancel_requested_at = NULL, attempts = attempts + 1, run_at = NOW(),
+            frames_done = CASE WHEN kind = 'replay' THEN (SELECT COUNT(*) FROM replay_results WHERE run_id = $1) ELSE frames_done END,
+            detections = CASE WHEN kind = 'replay'
+                              THEN (SELECT COALESCE(SUM(jsonb_array_length(replay->'detections')), 0) FROM replay_results WHERE run_id = $1)
+                              ELSE detections END
+        WHERE id = $1 AND (status IN ('failed', 'cancelled') OR (status = 'done' AND $2))
+        RETURNING {}
+        "#,
+        RUN_COLUMNS
+    ))
+    .bind(id)
+    .bind(failed > 0)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(internal)?;
+    let Some(run) = run else {
+        let reason = match current.status.as_str() {
+            "queued" | "running" => format!("Replay run is still {}", current.status),
+            _ => "Replay run finished without failed frames; nothing to retry".to_string(),
+        };
+        return Err((StatusCode::CONFLICT, reason));
+    };
+    tx.commit().await.map_err(internal)?;
+
+    info!(run_id = id, attempt = run.attempts, failed_frames = failed, by = %claims.username, "replay retried");
+    dispatch(&st).await;
+    Ok((StatusCode::ACCEPTED, Json(get_run(&st.db, id).await?)))
+}
+
+/// GET /jobs/:id/progress — Server-Sent Events for a replay run: a `progress` event (frames done
+/// of total, detections so far, ETA) per processed frame, closing once the run has ended. A
+/// backfill reports per chunk, events for frames and zones changed for detections (admin)
+pub async fn progress_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let first = get_run(&st.db, id).await?;
+    // (pending run to report, last reported frames_done and status, updates receiver)
+    let updates = stream::unfold((Some(first), None::<(i32, String)>, progressed().subscribe()), move |(mut pending, last, mut rx)| {
+        let db = st.db.clone();
+        async move {
+            loop {
+                let run = match pending.take() {
+                    Some(run) => run,
+                    None => {
+                        if last.as_ref().is_some_and(|(_, status)| !ACTIVE.contains(&status.as_str())) {
+                            return None;
+                        }
+                        // Woken by this instance's worker, or re-read on the interval for others'
+                        let _ = tokio::time::timeout(PROGRESS_RECHECK, async {
+                            while rx.recv().await.is_ok_and(|run_id| run_id != id) {}
+                        })
+                        .await;
+                        match get_run(&db, id).await {
+                            Ok(run) => run,
+                            Err((_, e)) => {
+                                warn!(run_id = id, error = %e, "progress stream ended, run unreadable");
+                                return None;
+                            }
+                        }
+                    }
+                };
+                let key = (run.frames_done, run.status.clone());
+                if last.as_ref() != Some(&key) {
+                    let event = Event::default().event("progress").json_data(Progress::of(&run)).unwrap_or_default();
+                    return Some((Ok::<_, Infallible>(event), (None, Some(key), rx)));
+                }
+            }
+        }
+    });
+    Ok(Sse::new(updates).keep_alive(KeepAlive::default()))
+}
+
+/// GET /admin/replay/:id/results?changed=true&limit= — per-frame original vs replayed classes (admin)
+pub async fn results_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i32>,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    get_run(&st.db, id).await?;
+    let changed_only = q.get("changed").is_some_and(|v| v == "true" || v == "1");
+    let limit = q.get("limit").and_then(|s| s.parse::<i64>().ok()).unwrap_or(100).clamp(1, 1000);
+    let rows = sqlx::query_as::<_, FrameResult>(&format!(
+        r#"
+        SELECT raw_inference_id, event_ids, original_classes, replay_classes, error FROM (
+            SELECT raw_inference_id, event_ids, {} AS original_classes,
+                   CASE WHEN replay IS NOT NULL THEN {} END AS replay_classes, error, created_at
+            FROM replay_results WHERE run_id = $1
+        ) r
+        WHERE NOT $2 OR replay_classes IS DISTINCT FROM original_classes
+        ORDER BY created_at
+        LIMIT $3
+        "#,
+        classes_of("original"),
+        classes_of("replay")
+    ))
+    .bind(id)
+    .bind(changed_only)
+    .bind(limit)
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(rows))
+}
@@ -0,0 +1,172 @@
+//! Event repository for FOD Detection Backend
+//! Storage-agnostic interface for the event write/read path, with the Postgres store and an in-memory one for tests
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::sync::Mutex;
+use time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    crypto,
+    db::{self, EventDetail, NewEvent, RecentEvent},
+    geometry,
+};
+
+/// Events as handlers see them. Multi-statement saves that must commit together (a detected
+/// frame, see `maybe_save`) still run on a Postgres transaction through `db` directly.
+#[async_trait]
+pub trait EventRepository: Send + Sync {
+    /// Class id for a label (or one of its aliases), creating the class on first sight
+    async fn class_id(&self, name: &str) -> Result<i32, (StatusCode, String)>;
+
+    /// Event from `source_ref` carrying `track_id` within the last `window_secs`
+    async fn find_track(&self, source_ref: &str, track_id: &str, window_secs: i64) -> Result<Option<Uuid>, (StatusCode, String)>;
+
+    /// Fold `event` into the nearest same-class event from another source within `radius_m`
+    /// and `window_secs` of it; None when nothing overlaps
+    async fn merge_overlapping(&self, event: &NewEvent, radius_m: f64, window_secs: i64) -> Result<Option<Uuid>, (StatusCode, String)>;
+
+    async fn insert(&self, event: NewEvent) -> Result<Uuid, (StatusCode, String)>;
+
+    async fn get(&self, id: Uuid) -> Result<Option<EventDetail>, (StatusCode, String)>;
+}
+
+// ==================== Postgres ====================
+
+pub struct PgEventRepository(pub PgPool);
+
+#[async_trait]
+impl EventRepository for PgEventRepository {
+    async fn class_id(&self, name: &str) -> Result<i32, (StatusCode, String)> {
+        db::get_or_create_class(&self.0, name).await
+    }
+
+    async fn find_track(&self, source_ref: &str, track_id: &str, window_secs: i64) -> Result<Option<Uuid>, (StatusCode, String)> {
+        db::check_duplicate_track(&self.0, source_ref, track_id, window_secs).await
+    }
+
+    async fn merge_overlapping(&self, e: &NewEvent, radius_m: f64, window_secs: i64) -> Result<Option<Uuid>, (StatusCode, String)> {
+        db::merge_overlapping(&self.0, e.class_id, &e.source_ref, e.ts, e.latitude, e.longitude, e.confidence, radius_m, window_secs).await
+    }
+
+    async fn insert(&self, e: NewEvent) -> Result<Uuid, (StatusCode, String)> {
+        db::insert_event(
+            &self.0, e.ts, e.class_id, e.object_count, e.confidence, e.raw_confidence,
+            e.latitude, e.longitude, &e.source, &e.source_ref, e.bbox, e.meta,
+        )
+        .await
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<EventDetail>, (StatusCode, String)> {
+        db::get_event(&self.0, id).await
+    }
+}
+
+// ==================== In-memory ====================
+
+struct StoredEvent {
+    id: Uuid,
+    event: NewEvent,
+}
+
+#[derive(Default)]
+struct Store {
+    /// Class names, id = index + 1
+    classes: Vec<String>,
+    events: Vec<StoredEvent>,
+}
+
+/// Process-local store with the Postgres semantics of the trait, for tests and tools that
+/// shouldn't need a database. Lifecycle state is always `detected`.
+#[derive(Default)]
+pub struct MemoryEventRepository(Mutex<Store>);
+
+#[async_trait]
+impl EventRepository for MemoryEventRepository {
+    async fn class_id(&self, name: &str) -> Result<i32, (StatusCode, String)> {
+        let mut store = self.0.lock().unwrap();
+        let key = db::class_alias_key(name);
+        if let Some(i) = store.classes.iter().position(|c| db::class_alias_key(c) == key) {
+            return Ok(i as i32 + 1);
+        }
+        store.classes.push(name.to_string());
+        Ok(store.classes.len() as i32)
+    }
+
+    async fn find_track(&self, source_ref: &str, track_id: &str, window_secs: i64) -> Result<Option<Uuid>, (StatusCode, String)> {
+        let since = time::OffsetDateTime::now_utc() - Duration::seconds(window_secs);
+        let store = self.0.lock().unwrap();
+        Ok(store
+            .events
+            .iter()
+            .find(|s| {
+                s.event.ts > since
+                    && s.event.source_ref == source_ref
+                    && s.event.meta.as_ref().and_then(|m| m.get("track_id")).and_then(Value::as_str) == Some(track_id)
+            })
+            .map(|s| s.id))
+    }
+
+    async fn merge_overlapping(&self, e: &NewEvent, radius_m: f64, window_secs: i64) -> Result<Option<Uuid>, (StatusCode, String)> {
+        let window = Duration::seconds(window_secs);
+        let mut store = self.0.lock().unwrap();
+        let target = store
+            .events
+            .iter_mut()
+            .filter(|s| s.event.class_id == e.class_id && s.event.source_ref != e.source_ref && (s.event.ts - e.ts).abs() <= window)
+            .map(|s| {
+                let d = geometry::distance_m(e.latitude as f64, e.longitude as f64, s.event.latitude as f64, s.event.longitude as f64);
+                (d, s)
+            })
+            .filter(|(d, _)| *d <= radius_m)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, s)| s);
+        let Some(target) = target else {
+            return Ok(None);
+        };
+        target.event.confidence = target.event.confidence.max(e.confidence);
+        let meta = target.event.meta.get_or_insert_with(|| json!({}));
+        let mut sources: Vec<String> = match meta.get("contributing_sources").and_then(Value::as_array) {
+            Some(list) => list.iter().filter_map(|v| v.as_str().map(str::to_string)).collect(),
+            None => vec![target.event.source_ref.clone()],
+        };
+        sources.push(e.source_ref.clone());
+        sources.sort();
+        sources.dedup();
+        meta["contributing_sources"] = json!(sources);
+        Ok(Some(target.id))
+    }
+
+    async fn insert(&self, mut e: NewEvent) -> Result<Uuid, (StatusCode, String)> {
+        let id = Uuid::new_v4();
+        e.meta = crypto::encrypt_meta(e.meta);
+        self.0.lock().unwrap().events.push(StoredEvent { id, event: e });
+        Ok(id)
+    }
+
+    async fn get(&self, id: Uuid) -> Result<Option<EventDetail>, (StatusCode, String)> {
+        let store = self.0.lock().unwrap();
+        Ok(store.events.iter().find(|s| s.id == id).map(|s| EventDetail {
+            event: RecentEvent {
+                id: s.id,
+                ts: s.event.ts,
+                class_name: store.classes.get(s.event.class_id as usize - 1).cloned().unwrap_or_default(),
+                object_count: s.event.object_count,
+                confidence: s.event.confidence,
+                latitude: s.event.latitude,
+                longitude: s.event.longitude,
+                source: s.event.source.clone(),
+                source_ref: s.event.source_ref.clone(),
+                frame_count: None,
+                severity: None,
+            },
+            bbox: s.event.bbox.clone(),
+            meta: s.event.meta.clone(),
+            state: Some("detected".to_string()),
+            raw_confidence: s.event.raw_confidence,
+        }))
+    }
+}
@@ -0,0 +1,253 @@
+//! Minimal S3-compatible object storage client for FOD Detection Backend
+//! Path-style PUT requests signed with AWS Signature Version 4 (works with AWS, MinIO, Ceph)
+
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Url};
+use sha2::{Digest, Sha256};
+use std::env;
+use time::{format_description::FormatItem, macros::format_description, OffsetDateTime};
+
+use crate::secrets;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const AMZ_DATE: &[FormatItem<'static>] = format_description!("[year][month][day]T[hour][minute][second]Z");
+const SCOPE_DATE: &[FormatItem<'static>] = format_description!("[year][month][day]");
+
+/// Bucket location and credentials
+#[derive(Clone)]
+pub struct Bucket {
+    /// e.g. `https://s3.ap-southeast-1.amazonaws.com` or `http://minio:9000`
+    pub endpoint: String,
+    pub name: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+impl Bucket {
+    /// Bucket from `{prefix}_ENDPOINT`, `_BUCKET`, `_REGION`, `_ACCESS_KEY` and `_SECRET_KEY`;
+    /// None unless the endpoint and bucket are set
+    pub fn from_env(prefix: &str) -> Option<Bucket> {
+        let var = |name: &str| env::var(format!("{}_{}", prefix, name)).ok().filter(|s| !s.is_empty());
+        Some(Bucket {
+            endpoint: var("ENDPOINT")?,
+            name: var("BUCKET")?,
+            region: var("REGION").unwrap_or_else(|| "us-east-1".to_string()),
+            access_key: secrets::get(&format!("{}_ACCESS_KEY", prefix)).unwrap_or_default(),
+            secret_key: secrets::get(&format!("{}_SECRET_KEY", prefix)).unwrap_or_default(),
+        })
+    }
+}
+
+// ==================== Signing ====================
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// RFC 3986 encoding as S3 expects it, keeping `/` between key segments
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Reverse of `uri_encode`; None for malformed escapes or non-UTF-8 results
+fn uri_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// RFC 3986 encoding for query string values, where `/` must be escaped too
+fn query_encode(s: &str) -> String {
+    uri_encode(s).replace('/', "%2F")
+}
+
+impl Bucket {
+    /// `Authorization` header for a request without query string, signing host and the x-amz headers
+    fn authorization(&self, method: &str, path: &str, host: &str, payload_hash: &str, now: OffsetDateTime) -> (String, String) {
+        let amz_date = now.format(AMZ_DATE).expect("valid date format");
+        let date = now.format(SCOPE_DATE).expect("valid date format");
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            amz_date,
+            scope,
+            Sha256::digest(canonical_request.as_bytes())
+        );
+        let signature = self.sign(&date, &string_to_sign);
+        let auth = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+        (auth, amz_date)
+    }
+
+    /// Query-string signed URL for `method` on `key`, valid for `expires_secs`; the payload is
+    /// left unsigned so a client can send the body without our credentials
+    fn presign(&self, method: &str, key: &str, expires_secs: u64, now: OffsetDateTime) -> Result<Url, String> {
+        let (mut url, path, host) = self.locate(key)?;
+        let amz_date = now.format(AMZ_DATE).expect("valid date format");
+        let date = now.format(SCOPE_DATE).expect("valid date format");
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        // Already in canonical (sorted) order
+        let query = [
+            ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_string()),
+            ("X-Amz-Credential", format!("{}/{}", self.access_key, scope)),
+            ("X-Amz-Date", amz_date.clone()),
+            ("X-Amz-Expires", expires_secs.to_string()),
+            ("X-Amz-SignedHeaders", "host".to_string()),
+        ]
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, query_encode(v)))
+        .collect::<Vec<_>>()
+        .join("&");
+        let canonical_request = format!("{}\n{}\n{}\nhost:{}\n\nhost\nUNSIGNED-PAYLOAD", method, path, query, host);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            amz_date,
+            scope,
+            Sha256::digest(canonical_request.as_bytes())
+        );
+        let signature = self.sign(&date, &string_to_sign);
+        url.set_query(Some(&format!("{}&X-Amz-Signature={}", query, signature)));
+        Ok(url)
+    }
+
+    /// Hex signature of `string_to_sign` with the key derived for `date` (YYYYMMDD)
+    fn sign(&self, date: &str, string_to_sign: &str) -> String {
+        let key = [date, self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(format!("AWS4{}", self.secret_key).into_bytes(), |k, part| hmac(&k, part));
+        hmac(&key, string_to_sign).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Path-style URL, canonical path and host header for `key`
+    fn locate(&self, key: &str) -> Result<(Url, String, String), String> {
+        let path = uri_encode(&format!("/{}/{}", self.name, key.trim_start_matches('/')));
+        let url = Url::parse(&format!("{}{}", self.endpoint.trim_end_matches('/'), path)).map_err(|e| e.to_string())?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(h), Some(p)) => format!("{}:{}", h, p),
+            (Some(h), None) => h.to_string(),
+            _ => return Err(format!("invalid endpoint {}", self.endpoint)),
+        };
+        Ok((url, path, host))
+    }
+
+    /// Key of an object in this bucket from `s3://{bucket}/{key}` or a path-style URL under the
+    /// endpoint (query string ignored); None for anything stored elsewhere
+    pub fn key_from_url(&self, url: &str) -> Option<String> {
+        let path = match url.strip_prefix("s3://") {
+            Some(rest) => rest.to_string(),
+            None => {
+                let path = url.split(['?', '#']).next()?.strip_prefix(self.endpoint.trim_end_matches('/'))?;
+                uri_decode(path.strip_prefix('/')?)?
+            }
+        };
+        let key = path.strip_prefix(&format!("{}/", self.name))?;
+        (!key.is_empty() && !key.split('/').any(|s| s == "..")).then(|| key.to_string())
+    }
+
+    // ==================== Operations ====================
+
+    /// Upload `body` to `key`, replacing any existing object
+    pub async fn put_object(&self, http: &Client, key: &str, content_type: &str, body: Vec<u8>) -> Result<(), String> {
+        let (url, path, host) = self.locate(key)?;
+        let payload_hash = format!("{:x}", Sha256::digest(&body));
+        let (auth, amz_date) = self.authorization("PUT", &path, &host, &payload_hash, OffsetDateTime::now_utc());
+
+        let resp = http
+            .put(url)
+            .header("authorization", auth)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("content-type", content_type)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("PUT {} returned {}: {}", key, status, text.chars().take(300).collect::<String>()));
+        }
+        Ok(())
+    }
+    /// URL a client can PUT an object to at `key` without credentials, valid for `expires_secs`
+    pub fn presign_put(&self, key: &str, expires_secs: u64) -> Result<String, String> {
+        Ok(self.presign("PUT", key, expires_secs, OffsetDateTime::now_utc())?.to_string())
+    }
+
+    /// URL anyone holding it can GET `key` from, valid for `expires_secs`
+    pub fn presign_get(&self, key: &str, expires_secs: u64) -> Result<String, String> {
+        Ok(self.presign("GET", key, expires_secs, OffsetDateTime::now_utc())?.to_string())
+    }
+
+    /// Remove `key`; an object that is already gone counts as removed
+    pub async fn delete_object(&self, http: &Client, key: &str) -> Result<(), String> {
+        let (url, path, host) = self.locate(key)?;
+        let payload_hash = format!("{:x}", Sha256::digest(b""));
+        let (auth, amz_date) = self.authorization("DELETE", &path, &host, &payload_hash, OffsetDateTime::now_utc());
+
+        let resp = http
+            .delete(url)
+            .header("authorization", auth)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() && resp.status() != reqwest::StatusCode::NOT_FOUND {
+            return Err(format!("DELETE {} returned {}", key, resp.status()));
+        }
+        Ok(())
+    }
+
+    /// Download `key`
+    pub async fn get_object(&self, http: &Client, key: &str) -> Result<Vec<u8>, String> {
+        self.find_object(http, key).await?.ok_or_else(|| format!("GET {} returned 404 Not Found", key))
+    }
+
+    /// Download `key`, None when the store has no such object
+    pub async fn find_object(&self, http: &Client, key: &str) -> Result<Option<Vec<u8>>, String> {
+        let (url, path, host) = self.locate(key)?;
+        let payload_hash = format!("{:x}", Sha256::digest(b""));
+        let (auth, amz_date) = self.authorization("GET", &path, &host, &payload_hash, OffsetDateTime::now_utc());
+
+        let resp = http
+            .get(url)
+            .header("authorization", auth)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            return Err(format!("GET {} returned {}", key, resp.status()));
+        }
+        Ok(Some(resp.bytes().await.map_err(|e| e.to_string())?.to_vec()))
+    }
+}
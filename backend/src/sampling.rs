@@ -0,0 +1,63 @@
+//! Adaptive sampling for FOD Detection Backend
+//! Thins out repeated detections of the same static debris from continuous camera feeds
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::settings::Settings;
+
+/// Streak entries kept before idle ones are pruned
+const MAX_STREAKS: usize = 4096;
+
+// ==================== Streaks ====================
+
+struct Streak {
+    run: u64,
+    last_seen: Instant,
+}
+
+fn streaks() -> &'static Mutex<HashMap<(String, String), Streak>> {
+    static STREAKS: OnceLock<Mutex<HashMap<(String, String), Streak>>> = OnceLock::new();
+    STREAKS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Outcome of offering one detection to the sampler
+pub enum Decision {
+    /// Store the detection; `factor` is 1 outside a streak, otherwise the sampling interval
+    Store { factor: u64 },
+    Skip,
+}
+
+/// Count one frame's detection of `class` on `source_ref`. Call once per class per frame,
+/// so several objects of one class in a frame advance the streak by one.
+/// Streaks are per replica; with several replicas each thins its own share of the frames.
+pub fn admit(cfg: &Settings, source_ref: &str, class: &str) -> Decision {
+    let (after, every, gap) = (cfg.sampling_after, cfg.sampling_every.max(1), Duration::from_secs(cfg.sampling_gap_secs));
+    if after == 0 {
+        return Decision::Store { factor: 1 };
+    }
+    let now = Instant::now();
+    let mut map = streaks().lock().unwrap();
+    if map.len() >= MAX_STREAKS {
+        map.retain(|_, s| now.duration_since(s.last_seen) < gap);
+    }
+    let streak = map
+        .entry((source_ref.to_string(), class.to_string()))
+        .or_insert(Streak { run: 0, last_seen: now });
+    if now.duration_since(streak.last_seen) >= gap {
+        streak.run = 0;
+    }
+    streak.run += 1;
+    streak.last_seen = now;
+
+    if streak.run <= after {
+        Decision::Store { factor: 1 }
+    } else if (streak.run - after).is_multiple_of(every) {
+        Decision::Store { factor: every }
+    } else {
+        Decision::Skip
+    }
+}
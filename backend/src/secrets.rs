@@ -0,0 +1,180 @@
+//! Secret loading for FOD Detection Backend
+//! Env vars, `*_FILE` paths (docker secrets) and an optional Vault KV endpoint, cached and
+//! reloaded periodically along with rotated DB credentials
+
+use reqwest::Client;
+use serde_json::Value;
+use sqlx::{postgres::PgConnectOptions, PgPool};
+use std::{
+    collections::HashMap,
+    env, fs,
+    sync::{OnceLock, RwLock},
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+// ==================== Vault ====================
+
+/// Vault KV config: `VAULT_ADDR`, `VAULT_TOKEN` (or `VAULT_TOKEN_FILE`) and
+/// `VAULT_SECRET_PATH`, e.g. `secret/data/fod-backend` for KV v2
+struct Vault {
+    url: String,
+    token: String,
+}
+
+impl Vault {
+    fn from_env() -> Option<Self> {
+        let addr = env::var("VAULT_ADDR").ok().filter(|s| !s.is_empty())?;
+        let path = env::var("VAULT_SECRET_PATH").ok().filter(|s| !s.is_empty())?;
+        let token = from_env_or_file("VAULT_TOKEN")?;
+        Some(Vault { url: format!("{}/v1/{}", addr.trim_end_matches('/'), path.trim_start_matches('/')), token })
+    }
+
+    async fn fetch(&self, http: &Client) -> Result<HashMap<String, String>, String> {
+        let resp = http
+            .get(&self.url)
+            .header("X-Vault-Token", &self.token)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("vault returned {}", resp.status()));
+        }
+        let body: Value = resp.json().await.map_err(|e| e.to_string())?;
+        // KV v2 nests the secret under data.data, KV v1 under data
+        let data = body.pointer("/data/data").or_else(|| body.get("data")).and_then(|d| d.as_object());
+        Ok(data
+            .map(|m| m.iter().filter_map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string()))).collect())
+            .unwrap_or_default())
+    }
+}
+
+fn vault_cache() -> &'static RwLock<HashMap<String, String>> {
+    static CACHE: OnceLock<RwLock<HashMap<String, String>>> = OnceLock::new();
+    CACHE.get_or_init(RwLock::default)
+}
+
+async fn refresh_vault(http: &Client) -> Result<(), String> {
+    let Some(vault) = Vault::from_env() else {
+        return Ok(());
+    };
+    let secrets = vault.fetch(http).await?;
+    *vault_cache().write().unwrap() = secrets;
+    Ok(())
+}
+
+// ==================== Files ====================
+
+/// `*_FILE` secrets by name, read at startup and on each refresh rather than per lookup; None
+/// when the file couldn't be read
+fn file_cache() -> &'static RwLock<HashMap<String, Option<String>>> {
+    static CACHE: OnceLock<RwLock<HashMap<String, Option<String>>>> = OnceLock::new();
+    CACHE.get_or_init(RwLock::default)
+}
+
+fn read_file(path: &str) -> std::io::Result<String> {
+    fs::read_to_string(path).map(|v| v.trim().to_string())
+}
+
+/// Read every `*_FILE` secret into the cache, failing on the first unreadable one; an empty
+/// path names no file and is skipped
+fn load_files() -> Result<(), String> {
+    let mut cache = file_cache().write().unwrap();
+    for (var, path) in env::vars().filter(|(_, path)| !path.is_empty()) {
+        let Some(name) = var.strip_suffix("_FILE").filter(|n| !n.is_empty()) else {
+            continue;
+        };
+        let value = read_file(&path).map_err(|e| format!("failed to read secret {} from {}: {}", name, path, e))?;
+        cache.insert(name.to_string(), Some(value));
+    }
+    Ok(())
+}
+
+/// Re-read the cached file secrets; one that can't be read keeps its previous value
+fn refresh_files() {
+    let names: Vec<String> = file_cache().read().unwrap().keys().cloned().collect();
+    for name in names {
+        let Ok(path) = env::var(format!("{}_FILE", name)) else {
+            continue;
+        };
+        match read_file(&path) {
+            Ok(v) => {
+                file_cache().write().unwrap().insert(name, Some(v));
+            }
+            Err(e) => warn!(secret = %name, %path, error = %e, "secret file unreadable, keeping cached value"),
+        }
+    }
+}
+
+// ==================== Lookup ====================
+
+/// `NAME` from the environment, else the cached contents of the file at `NAME_FILE`; a file not
+/// seen at startup is read once here and then cached like the rest
+fn from_env_or_file(name: &str) -> Option<String> {
+    if let Ok(v) = env::var(name) {
+        return Some(v);
+    }
+    if let Some(cached) = file_cache().read().unwrap().get(name) {
+        return cached.clone();
+    }
+    let path = env::var(format!("{}_FILE", name)).ok()?;
+    let value = read_file(&path)
+        .map_err(|e| error!(secret = name, %path, error = %e, "failed to read secret file"))
+        .ok();
+    file_cache().write().unwrap().insert(name.to_string(), value.clone());
+    value
+}
+
+/// Resolve a secret: env var, then `*_FILE`, then the Vault secret's key of the same name
+pub fn get(name: &str) -> Option<String> {
+    from_env_or_file(name).or_else(|| vault_cache().read().unwrap().get(name).cloned())
+}
+
+/// Read the `*_FILE` secrets and load the Vault secret once at startup (no-op without
+/// `VAULT_ADDR`); an unreadable secret file is an error, an unreachable Vault is only logged
+pub async fn init(http: &Client) -> Result<(), String> {
+    load_files()?;
+    if let Err(e) = refresh_vault(http).await {
+        error!(error = %e, "failed to load secrets from vault");
+    }
+    Ok(())
+}
+
+/// Re-read the `*_FILE` secrets, then the Vault secret (which may use a rotated token file)
+pub async fn refresh(http: &Client) {
+    refresh_files();
+    if let Err(e) = refresh_vault(http).await {
+        warn!(error = %e, "vault refresh failed, keeping cached secrets");
+    }
+}
+
+// ==================== Rotation ====================
+
+/// Refresh the secrets every `SECRETS_REFRESH_SECS` (default 60), re-resolve DATABASE_URL and
+/// point the pool at the new credentials; existing connections are replaced as they are recycled
+pub fn watch_database_url(db: PgPool, http: Client) {
+    let secs = env::var("SECRETS_REFRESH_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(60);
+    if secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut current = get("DATABASE_URL");
+        let mut tick = tokio::time::interval(Duration::from_secs(secs));
+        tick.tick().await;
+        loop {
+            tick.tick().await;
+            refresh(&http).await;
+            let Some(latest) = get("DATABASE_URL").filter(|url| Some(url) != current.as_ref()) else {
+                continue;
+            };
+            match latest.parse::<PgConnectOptions>() {
+                Ok(opts) => {
+                    db.set_connect_options(opts);
+                    info!("database credentials rotated, new connections use the updated secret");
+                }
+                Err(e) => error!(error = %e, "rotated DATABASE_URL is invalid, keeping previous"),
+            }
+            current = Some(latest);
+        }
+    });
+}
@@ -0,0 +1,397 @@
+//! Runtime settings for FOD Detection Backend
+//! Typed tunables stored in `settings`, cached per replica and reloaded on change notification
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::{postgres::PgListener, FromRow, PgPool};
+use std::{collections::HashMap, env, sync::Arc, time::Duration};
+use time::OffsetDateTime;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::{auth, db::internal, AppState};
+
+/// Channel the settings handler notifies after a change
+const CHANNEL: &str = "fod_settings";
+
+// ==================== Settings ====================
+
+/// Current value of every tunable
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct Settings {
+    /// Same track on the same source within this many seconds is a duplicate
+    pub dedup_window_secs: i64,
+    /// Detections below this confidence are not saved
+    pub min_confidence: f64,
+    /// Detections stored in full before sampling starts; 0 disables sampling
+    pub sampling_after: u64,
+    /// Once sampling, store only every n-th detection
+    pub sampling_every: u64,
+    /// A class not seen on a source for this long ends its streak
+    pub sampling_gap_secs: u64,
+    /// Same-class detections from different sources this close merge into one event; 0 disables
+    pub fusion_radius_m: f64,
+    /// ...and at most this many seconds apart
+    pub fusion_window_secs: i64,
+}
+
+/// Built-in values, from the environment where a variable existed before runtime settings
+impl Default for Settings {
+    fn default() -> Self {
+        let var = |name: &str, default: u64| env::var(name).ok().and_then(|s| s.parse().ok()).unwrap_or(default);
+        Settings {
+            dedup_window_secs: 10,
+            min_confidence: 0.0,
+            sampling_after: var("SAMPLING_AFTER", 30),
+            sampling_every: var("SAMPLING_EVERY", 10).max(1),
+            sampling_gap_secs: var("SAMPLING_GAP_SECS", 10),
+            fusion_radius_m: 0.0,
+            fusion_window_secs: 5,
+        }
+    }
+}
+
+struct Def {
+    key: &'static str,
+    description: &'static str,
+    min: f64,
+    max: f64,
+    /// Whole numbers only
+    integer: bool,
+    get: fn(&Settings) -> Value,
+    set: fn(&mut Settings, f64),
+}
+
+const DEFS: &[Def] = &[
+    Def {
+        key: "dedup_window_secs",
+        description: "Seconds within which the same track_id on a source is a duplicate",
+        min: 0.0,
+        max: 3600.0,
+        integer: true,
+        get: |s| json!(s.dedup_window_secs),
+        set: |s, v| s.dedup_window_secs = v as i64,
+    },
+    Def {
+        key: "min_confidence",
+        description: "Detections below this confidence are not saved",
+        min: 0.0,
+        max: 1.0,
+        integer: false,
+        get: |s| json!(s.min_confidence),
+        set: |s, v| s.min_confidence = v,
+    },
+    Def {
+        key: "sampling_after",
+        description: "Detections of a class on a source stored in full before sampling; 0 disables sampling",
+        min: 0.0,
+        max: 1_000_000.0,
+        integer: true,
+        get: |s| json!(s.sampling_after),
+        set: |s, v| s.sampling_after = v as u64,
+    },
+    Def {
+        key: "sampling_every",
+        description: "Once sampling, store every n-th detection",
+        min: 1.0,
+        max: 10_000.0,
+        integer: true,
+        get: |s| json!(s.sampling_every),
+        set: |s, v| s.sampling_every = v as u64,
+    },
+    Def {
+        key: "sampling_gap_secs",
+        description: "Seconds without a detection that end a sampling streak",
+        min: 1.0,
+        max: 86_400.0,
+        integer: true,
+        get: |s| json!(s.sampling_gap_secs),
+        set: |s, v| s.sampling_gap_secs = v as u64,
+    },
+    Def {
+        key: "fusion_radius_m",
+        description: "Metres within which same-class detections from different sources merge into one event; 0 disables",
+        min: 0.0,
+        max: 1000.0,
+        integer: false,
+        get: |s| json!(s.fusion_radius_m),
+        set: |s, v| s.fusion_radius_m = v,
+    },
+    Def {
+        key: "fusion_window_secs",
+        description: "Seconds apart within which detections from different sources can merge",
+        min: 0.0,
+        max: 3600.0,
+        integer: true,
+        get: |s| json!(s.fusion_window_secs),
+        set: |s, v| s.fusion_window_secs = v as i64,
+    },
+];
+
+fn def(key: &str) -> Option<&'static Def> {
+    DEFS.iter().find(|d| d.key == key)
+}
+
+impl Def {
+    /// Validate `value` and set it on `s`
+    fn apply(&self, s: &mut Settings, value: &Value) -> Result<(), String> {
+        let n = value.as_f64().filter(|n| n.is_finite()).ok_or_else(|| format!("{} must be a number", self.key))?;
+        if self.integer && n.fract() != 0.0 {
+            return Err(format!("{} must be a whole number", self.key));
+        }
+        if n < self.min || n > self.max {
+            return Err(format!("{} must be between {} and {}", self.key, self.min, self.max));
+        }
+        (self.set)(s, n);
+        Ok(())
+    }
+}
+
+// ==================== Cache ====================
+
+/// This replica's settings; subscribers see every change
+#[derive(Clone)]
+pub struct Runtime(Arc<watch::Sender<Arc<Settings>>>);
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Runtime(Arc::new(watch::channel(Arc::new(Settings::default())).0))
+    }
+}
+
+impl Runtime {
+    pub fn current(&self) -> Arc<Settings> {
+        self.0.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<Arc<Settings>> {
+        self.0.subscribe()
+    }
+
+    fn replace(&self, s: Settings) {
+        self.0.send_if_modified(|cur| {
+            let changed = **cur != s;
+            if changed {
+                *cur = Arc::new(s);
+            }
+            changed
+        });
+    }
+}
+
+/// Rebuild the cache from the table; stored values that no longer validate are skipped
+pub async fn reload(db: &PgPool, runtime: &Runtime) -> Result<(), sqlx::Error> {
+    let rows: Vec<(String, Value)> = sqlx::query_as("SELECT key, value FROM settings").fetch_all(db).await?;
+    let mut s = Settings::default();
+    for (key, value) in rows {
+        let applied = def(&key).ok_or_else(|| "unknown setting".to_string()).and_then(|d| d.apply(&mut s, &value));
+        if let Err(e) = applied {
+            warn!(key = %key, error = %e, "ignoring stored setting");
+        }
+    }
+    runtime.replace(s);
+    Ok(())
+}
+
+/// Reload on every change notification, and on (re)connect to catch changes made meanwhile
+pub fn spawn_listener(state: AppState) {
+    tokio::spawn(async move {
+        loop {
+            let mut listener = match PgListener::connect_with(&state.db).await {
+                Ok(l) => l,
+                Err(e) => {
+                    error!(error = %e, "settings listener failed to connect, retrying");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            if let Err(e) = listener.listen(CHANNEL).await {
+                error!(error = %e, "LISTEN {} failed, retrying", CHANNEL);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+            info!(channel = CHANNEL, "listening for settings changes");
+            loop {
+                if let Err(e) = reload(&state.db, &state.settings).await {
+                    warn!(error = %e, "failed to reload settings");
+                }
+                match listener.try_recv().await {
+                    Ok(Some(_)) => {}
+                    Ok(None) => warn!("settings listener connection lost, reconnecting"),
+                    Err(e) => {
+                        error!(error = %e, "settings listener failed");
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize)]
+pub struct Entry {
+    pub key: &'static str,
+    pub description: &'static str,
+    pub value: Value,
+    pub default: Value,
+    pub min: f64,
+    pub max: f64,
+    pub integer: bool,
+    /// Who last set it; None while the default is in effect
+    pub updated_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Change {
+    pub id: i64,
+    pub key: String,
+    pub old_value: Option<Value>,
+    pub new_value: Option<Value>,
+    pub changed_by: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub changed_at: OffsetDateTime,
+}
+
+#[derive(Deserialize)]
+pub struct HistoryParams {
+    pub key: Option<String>,
+    pub limit: Option<i64>,
+}
+
+async fn entries(st: &AppState) -> Result<Vec<Entry>, (StatusCode, String)> {
+    let rows: Vec<(String, String, OffsetDateTime)> =
+        sqlx::query_as("SELECT key, updated_by, updated_at FROM settings").fetch_all(&st.db).await.map_err(internal)?;
+    let set: HashMap<String, (String, OffsetDateTime)> = rows.into_iter().map(|(k, by, at)| (k, (by, at))).collect();
+    let (current, defaults) = (st.settings.current(), Settings::default());
+    Ok(DEFS
+        .iter()
+        .map(|d| {
+            let who = set.get(d.key);
+            Entry {
+                key: d.key,
+                description: d.description,
+                value: (d.get)(&current),
+                default: (d.get)(&defaults),
+                min: d.min,
+                max: d.max,
+                integer: d.integer,
+                updated_by: who.map(|w| w.0.clone()),
+                updated_at: who.map(|w| w.1),
+            }
+        })
+        .collect())
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/settings — every runtime setting with its value, default and bounds (admin)
+pub async fn list_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    Ok(Json(entries(&st).await?))
+}
+
+/// PUT /admin/settings — `{key: value}`; null restores the default. All or nothing, audited (admin)
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<HashMap<String, Value>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let mut check = Settings::default();
+    for (key, value) in &body {
+        let d = def(key).ok_or((StatusCode::BAD_REQUEST, format!("Unknown setting: {}", key)))?;
+        if !value.is_null() {
+            d.apply(&mut check, value).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+        }
+    }
+
+    let mut tx = st.db.begin().await.map_err(internal)?;
+    let mut changed = Vec::new();
+    for (key, value) in &body {
+        let old: Option<Value> = sqlx::query_scalar("SELECT value FROM settings WHERE key = $1 FOR UPDATE")
+            .bind(key)
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(internal)?;
+        let new = (!value.is_null()).then_some(value);
+        if old.as_ref() == new {
+            continue;
+        }
+        match new {
+            None => {
+                sqlx::query("DELETE FROM settings WHERE key = $1").bind(key).execute(&mut *tx).await.map_err(internal)?;
+            }
+            Some(v) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO settings (key, value, updated_by) VALUES ($1, $2, $3)
+                    ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value, updated_by = EXCLUDED.updated_by, updated_at = NOW()
+                    "#,
+                )
+                .bind(key)
+                .bind(v)
+                .bind(&claims.username)
+                .execute(&mut *tx)
+                .await
+                .map_err(internal)?;
+            }
+        }
+        sqlx::query("INSERT INTO setting_changes (key, old_value, new_value, changed_by) VALUES ($1, $2, $3, $4)")
+            .bind(key)
+            .bind(&old)
+            .bind(new)
+            .bind(&claims.username)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        info!(key = %key, old = ?old, new = ?new, by = %claims.username, "setting changed");
+        changed.push(key.as_str());
+    }
+    if !changed.is_empty() {
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(CHANNEL)
+            .bind(changed.join(","))
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+    }
+    tx.commit().await.map_err(internal)?;
+
+    // Other replicas pick it up from the notification
+    reload(&st.db, &st.settings).await.map_err(internal)?;
+    Ok(Json(entries(&st).await?))
+}
+
+/// GET /admin/settings/history?key=&limit= — audit trail of setting changes, newest first (admin)
+pub async fn history_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<HistoryParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let changes = sqlx::query_as::<_, Change>(
+        r#"
+        SELECT id, key, old_value, new_value, changed_by, changed_at
+        FROM setting_changes
+        WHERE $1::TEXT IS NULL OR key = $1
+        ORDER BY changed_at DESC, id DESC
+        LIMIT $2
+        "#,
+    )
+    .bind(&p.key)
+    .bind(p.limit.unwrap_or(100).clamp(1, 1000))
+    .fetch_all(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(changes))
+}
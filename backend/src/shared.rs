@@ -0,0 +1,349 @@
+//! State shared between replicas for FOD Detection Backend
+//! Dashboard response cache, per-client rate limits, idempotency keys and the live event feed
+//! go through Redis when `REDIS_URL` is set; without it the cache and rate limits stay in this
+//! process, idempotency keys in Postgres and the feed on LISTEN/NOTIFY
+
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+use redis::{aio::ConnectionManager, AsyncCommands, Client, RedisResult};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use time::OffsetDateTime;
+use tokio::sync::OnceCell;
+use tracing::warn;
+
+use crate::{auth, conditional, logging, secrets, signing, AppState};
+
+/// Bodies larger than this are served but not cached
+const MAX_CACHED_BODY: usize = 4 * 1024 * 1024;
+/// Response headers stored with a cached body
+const CACHED_HEADERS: [&str; 6] = ["content-type", "etag", "last-modified", "content-disposition", "cache-control", "vary"];
+/// In-process cache size at which expired entries are swept
+const LOCAL_CACHE_SWEEP: usize = 1024;
+/// Rate limits count each client's requests per window of this length
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+pub const CACHE_HEADER: &str = "x-cache";
+
+// ==================== Config ====================
+
+#[derive(Clone, Default)]
+pub struct Config {
+    /// Redis server; None keeps everything in this process and Postgres
+    pub redis_url: Option<String>,
+    /// Start of every Redis key and channel, so deployments can share a server
+    pub prefix: String,
+    /// How long a dashboard response is reused, zero for not at all
+    pub cache_ttl: Duration,
+    /// Requests per client per minute, zero for no limit
+    pub rate_limit: u64,
+    /// Reverse proxies whose `X-Forwarded-For` is believed; from anyone else it is ignored
+    pub trusted_proxies: Vec<IpAddr>,
+}
+
+impl Config {
+    /// `REDIS_URL` (also from `_FILE`/Vault), `REDIS_PREFIX` (default `fod:<SITE_ID>`),
+    /// `RESPONSE_CACHE_SECS` and `RATE_LIMIT_PER_MINUTE` (both default 0, off), `TRUSTED_PROXIES`
+    /// (comma-separated addresses, default none)
+    pub fn from_env() -> Config {
+        let var = |name: &str| env::var(name).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let number = |name: &str| {
+            var(name).map(|s| s.parse::<u64>().unwrap_or_else(|_| panic!("{} must be a number, got {:?}", name, s))).unwrap_or(0)
+        };
+        Config {
+            redis_url: secrets::get("REDIS_URL").filter(|s| !s.trim().is_empty()),
+            prefix: var("REDIS_PREFIX").unwrap_or_else(|| format!("fod:{}", logging::site_id())),
+            cache_ttl: Duration::from_secs(number("RESPONSE_CACHE_SECS")),
+            rate_limit: number("RATE_LIMIT_PER_MINUTE"),
+            trusted_proxies: var("TRUSTED_PROXIES")
+                .map(|s| {
+                    s.split(',')
+                        .map(str::trim)
+                        .filter(|a| !a.is_empty())
+                        .map(|a| a.parse().unwrap_or_else(|_| panic!("TRUSTED_PROXIES must list IP addresses, got {:?}", a)))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// ==================== Redis ====================
+
+pub struct Redis {
+    client: Client,
+    /// Connected on first use; reconnects on its own after that
+    conn: OnceCell<ConnectionManager>,
+    prefix: String,
+}
+
+impl Redis {
+    fn key(&self, name: &str) -> String {
+        format!("{}:{}", self.prefix, name)
+    }
+
+    async fn conn(&self) -> RedisResult<ConnectionManager> {
+        self.conn.get_or_try_init(|| ConnectionManager::new(self.client.clone())).await.cloned()
+    }
+
+    pub async fn get(&self, name: &str) -> RedisResult<Option<Vec<u8>>> {
+        self.conn().await?.get(self.key(name)).await
+    }
+
+    /// Set `name` for `ttl`, replacing any value it had
+    pub async fn set(&self, name: &str, value: &[u8], ttl: Duration) -> RedisResult<()> {
+        self.conn().await?.pset_ex(self.key(name), value, ttl.as_millis() as u64).await
+    }
+
+    /// Set `name` for `ttl` unless it is already set; false when it was
+    pub async fn set_new(&self, name: &str, value: &[u8], ttl: Duration) -> RedisResult<bool> {
+        let set: Option<String> = redis::cmd("SET")
+            .arg(self.key(name))
+            .arg(value)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut self.conn().await?)
+            .await?;
+        Ok(set.is_some())
+    }
+
+    /// Add one to the counter `name`, which expires `ttl` after it was last counted
+    pub async fn incr(&self, name: &str, ttl: Duration) -> RedisResult<u64> {
+        let key = self.key(name);
+        let (n,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .pexpire(&key, ttl.as_millis() as i64)
+            .ignore()
+            .query_async(&mut self.conn().await?)
+            .await?;
+        Ok(n)
+    }
+
+    pub async fn publish(&self, channel: &str, payload: &str) -> RedisResult<()> {
+        self.conn().await?.publish(self.key(channel), payload).await
+    }
+
+    /// Hand each message on `channel` to `relay` until the subscription drops
+    pub async fn subscribe(&self, channel: &str, mut relay: impl FnMut(String)) -> RedisResult<()> {
+        let mut pubsub = self.client.get_async_pubsub().await?;
+        pubsub.subscribe(self.key(channel)).await?;
+        let mut messages = pubsub.on_message();
+        while let Some(msg) = messages.next().await {
+            relay(msg.get_payload()?);
+        }
+        Ok(())
+    }
+}
+
+// ==================== Store ====================
+
+/// In-process stand-ins for a single replica
+#[derive(Default)]
+struct Local {
+    /// Cache key → expiry and entry
+    cache: Mutex<HashMap<String, (Instant, Vec<u8>)>>,
+    /// Current rate window and the requests counted in it per client
+    hits: Mutex<(u64, HashMap<String, u64>)>,
+}
+
+pub struct Shared {
+    pub config: Config,
+    redis: Option<Redis>,
+    local: Local,
+}
+
+impl Shared {
+    pub fn new(config: Config) -> Shared {
+        let redis = config.redis_url.as_deref().map(|url| Redis {
+            client: Client::open(url).unwrap_or_else(|e| panic!("REDIS_URL is not a usable Redis URL: {}", e)),
+            conn: OnceCell::new(),
+            prefix: config.prefix.clone(),
+        });
+        Shared { config, redis, local: Local::default() }
+    }
+
+    /// The Redis server, when one is configured
+    pub fn redis(&self) -> Option<&Redis> {
+        self.redis.as_ref()
+    }
+
+    /// Unexpired cache entry; a Redis that can't be read counts as a miss
+    async fn cached(&self, key: &str) -> Option<Vec<u8>> {
+        let Some(redis) = &self.redis else {
+            let mut cache = self.local.cache.lock().unwrap();
+            return match cache.get(key) {
+                Some((expires, entry)) if *expires > Instant::now() => Some(entry.clone()),
+                Some(_) => cache.remove(key).and(None),
+                None => None,
+            };
+        };
+        redis.get(key).await.unwrap_or_else(|e| {
+            warn!(error = %e, "response cache read failed");
+            None
+        })
+    }
+
+    async fn cache(&self, key: &str, entry: Vec<u8>) {
+        let ttl = self.config.cache_ttl;
+        let Some(redis) = &self.redis else {
+            let now = Instant::now();
+            let mut cache = self.local.cache.lock().unwrap();
+            if cache.len() >= LOCAL_CACHE_SWEEP {
+                cache.retain(|_, (expires, _)| *expires > now);
+            }
+            cache.insert(key.to_string(), (now + ttl, entry));
+            return;
+        };
+        if let Err(e) = redis.set(key, &entry, ttl).await {
+            warn!(error = %e, "response cache write failed");
+        }
+    }
+
+    /// Requests `client` has made in rate `window`, this one included
+    async fn hit(&self, client: &str, window: u64) -> RedisResult<u64> {
+        let Some(redis) = &self.redis else {
+            let mut hits = self.local.hits.lock().unwrap();
+            if hits.0 != window {
+                *hits = (window, HashMap::new());
+            }
+            let n = hits.1.entry(client.to_string()).or_default();
+            *n += 1;
+            return Ok(*n);
+        };
+        redis.incr(&format!("rate:{}:{}", client, window), RATE_WINDOW).await
+    }
+}
+
+// ==================== Response Cache ====================
+
+/// Same path and query, caller, format and language
+fn cache_key(req: &Request) -> String {
+    let mut hash = Sha256::new();
+    hash.update(req.uri().to_string());
+    for name in [header::AUTHORIZATION, header::ACCEPT, header::ACCEPT_LANGUAGE] {
+        hash.update(b"\n");
+        hash.update(req.headers().get(name).map(|v| v.as_bytes()).unwrap_or_default());
+    }
+    format!("cache:{:x}", hash.finalize())
+}
+
+/// The kept headers as a JSON line, then the body
+fn encode(headers: &HeaderMap, body: &[u8]) -> Vec<u8> {
+    let kept: HashMap<&str, &str> =
+        CACHED_HEADERS.iter().filter_map(|&name| Some((name, headers.get(name)?.to_str().ok()?))).collect();
+    let mut entry = serde_json::to_vec(&kept).unwrap_or_default();
+    entry.push(b'\n');
+    entry.extend_from_slice(body);
+    entry
+}
+
+fn decode(entry: Vec<u8>) -> Option<Response> {
+    let split = entry.iter().position(|&b| b == b'\n')?;
+    let kept: HashMap<String, String> = serde_json::from_slice(&entry[..split]).ok()?;
+    let mut resp = Response::new(Body::from(entry[split + 1..].to_vec()));
+    for (name, value) in kept {
+        resp.headers_mut().insert(HeaderName::try_from(name).ok()?, HeaderValue::try_from(value).ok()?);
+    }
+    resp.headers_mut().insert(CACHE_HEADER, HeaderValue::from_static("hit"));
+    Some(resp)
+}
+
+/// Layer over the dashboard routes: a GET asked again within `RESPONSE_CACHE_SECS`, by the
+/// same caller with the same query and language, is answered with the stored response, or
+/// with 304 when the caller's `If-None-Match`/`If-Modified-Since` match its validators
+pub async fn cache(State(st): State<AppState>, req: Request, next: Next) -> Response {
+    if st.shared.config.cache_ttl.is_zero() || req.method() != Method::GET {
+        return next.run(req).await;
+    }
+    let key = cache_key(&req);
+    if let Some(resp) = st.shared.cached(&key).await.and_then(decode) {
+        if !conditional::stored_matches(req.headers(), resp.headers()) {
+            return resp;
+        }
+        let (mut parts, _) = resp.into_parts();
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        parts.headers.remove(header::CONTENT_DISPOSITION);
+        return Response::from_parts(parts, Body::empty());
+    }
+    let resp = next.run(req).await;
+    let sized = resp.body().size_hint().exact().is_some_and(|n| n as usize <= MAX_CACHED_BODY);
+    if resp.status() != StatusCode::OK || !sized {
+        return resp;
+    }
+    let (parts, body) = resp.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_CACHED_BODY).await else {
+        return (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read response body".to_string()).into_response();
+    };
+    st.shared.cache(&key, encode(&parts.headers, &bytes)).await;
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+// ==================== Rate Limits ====================
+
+/// Who the request counts against: the device `signing::verify` accepted, the token's user,
+/// else the peer address. A trusted proxy's peer address is replaced by the nearest untrusted
+/// one in its `X-Forwarded-For`. None when served without connect info
+fn client(req: &Request, trusted_proxies: &[IpAddr]) -> Option<String> {
+    if let Some(signing::VerifiedDevice(device)) = req.extensions().get() {
+        return Some(format!("device:{}", device));
+    }
+    if let Ok(claims) = auth::require_user(req.headers()) {
+        return Some(format!("user:{}", claims.sub));
+    }
+    let ConnectInfo(peer) = req.extensions().get::<ConnectInfo<SocketAddr>>()?;
+    let mut addr = peer.ip();
+    if trusted_proxies.contains(&addr) {
+        let forwarded = req.headers().get_all("x-forwarded-for").iter().filter_map(|v| v.to_str().ok());
+        let hops: Vec<IpAddr> = forwarded.flat_map(|v| v.split(',')).filter_map(|a| a.trim().parse().ok()).collect();
+        // Hops are appended, so only the tail was written by proxies we trust
+        for hop in hops.into_iter().rev() {
+            addr = hop;
+            if !trusted_proxies.contains(&hop) {
+                break;
+            }
+        }
+    }
+    Some(format!("addr:{}", addr))
+}
+
+/// Layer over every route but the health checks, inside `signing::verify` on signed device
+/// routes: a client past `RATE_LIMIT_PER_MINUTE` requests this minute gets 429 until the next one
+pub async fn limit(State(st): State<AppState>, req: Request, next: Next) -> Response {
+    let max = st.shared.config.rate_limit;
+    if max == 0 || req.uri().path().starts_with("/health") {
+        return next.run(req).await;
+    }
+    let now = OffsetDateTime::now_utc().unix_timestamp() as u64;
+    let window = RATE_WINDOW.as_secs();
+    let Some(client) = client(&req, &st.shared.config.trusted_proxies) else {
+        return next.run(req).await;
+    };
+    match st.shared.hit(&client, now / window).await {
+        Ok(n) if n > max => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, (window - now % window).to_string())],
+            format!("Rate limit of {} requests per minute exceeded", max),
+        )
+            .into_response(),
+        Ok(_) => next.run(req).await,
+        // Serving everyone beats serving no one while Redis is away
+        Err(e) => {
+            warn!(error = %e, client = %client, "rate limit check failed, letting the request through");
+            next.run(req).await
+        }
+    }
+}
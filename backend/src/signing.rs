@@ -0,0 +1,210 @@
+//! HMAC request signing for edge devices
+//! Verifies timestamp + body digest signatures with a nonce cache against replays
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hmac::{Hmac, Mac};
+use redis::RedisResult;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::{logging, secrets, AppState};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Accepted clock skew between device and server; nonces are kept for twice this
+const MAX_SKEW: Duration = Duration::from_secs(300);
+/// Largest body buffered for digest verification
+const MAX_SIGNED_BODY: usize = 16 * 1024 * 1024;
+
+pub const DEVICE_ID_HEADER: &str = "x-device-id";
+pub const TIMESTAMP_HEADER: &str = "x-timestamp";
+pub const NONCE_HEADER: &str = "x-nonce";
+pub const DIGEST_HEADER: &str = "x-content-sha256";
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+/// Device whose signature `verify` accepted, in the extensions of the requests it lets through
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifiedDevice(pub String);
+
+// ==================== Config ====================
+
+/// Enforcement level (env `DEVICE_SIGNING`)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SigningMode {
+    /// No verification (default)
+    Off,
+    /// Verify requests that carry a signature, let unsigned ones through
+    Optional,
+    /// Reject unsigned requests
+    Required,
+}
+
+pub struct Config {
+    pub mode: SigningMode,
+    /// Shared secret per device id
+    pub keys: HashMap<String, Vec<u8>>,
+}
+
+impl Config {
+    /// Mode from `DEVICE_SIGNING`, secrets from `DEVICE_KEYS` (`device_id:secret,...`)
+    pub fn from_env() -> Config {
+        let mode = match env::var("DEVICE_SIGNING").unwrap_or_default().to_lowercase().as_str() {
+            "optional" => SigningMode::Optional,
+            "required" => SigningMode::Required,
+            _ => SigningMode::Off,
+        };
+        let keys = secrets::get("DEVICE_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|pair| pair.trim().split_once(':'))
+            .map(|(id, secret)| (id.trim().to_string(), secret.trim().as_bytes().to_vec()))
+            .filter(|(id, secret)| !id.is_empty() && !secret.is_empty())
+            .collect();
+        Config { mode, keys }
+    }
+}
+
+// ==================== Nonce Cache ====================
+
+fn nonces() -> &'static Mutex<HashMap<(String, String), Instant>> {
+    static NONCES: OnceLock<Mutex<HashMap<(String, String), Instant>>> = OnceLock::new();
+    NONCES.get_or_init(Mutex::default)
+}
+
+/// Remember a nonce, false if it was already seen inside the skew window; through Redis when it
+/// is configured so a replay to another replica is caught too, otherwise in this process
+async fn claim_nonce(st: &AppState, device: &str, nonce: &str) -> RedisResult<bool> {
+    if let Some(redis) = st.shared.redis() {
+        return redis.set_new(&format!("nonce:{}:{}", device, nonce), b"1", MAX_SKEW * 2).await;
+    }
+    let now = Instant::now();
+    let mut seen = nonces().lock().unwrap();
+    seen.retain(|_, at| now.duration_since(*at) < MAX_SKEW * 2);
+    Ok(seen.insert((device.to_string(), nonce.to_string()), now).is_none())
+}
+
+// ==================== Verification ====================
+
+/// Canonical string: METHOD \n path?query \n timestamp \n nonce \n body sha256 (hex)
+pub fn string_to_sign(method: &str, path_and_query: &str, timestamp: &str, nonce: &str, digest: &str) -> String {
+    format!("{}\n{}\n{}\n{}\n{}", method, path_and_query, timestamp, nonce, digest)
+}
+
+/// Headers for a request signed as `device`, the client half of `verify`
+pub fn sign(device: &str, secret: &[u8], method: &str, path_and_query: &str, body: &[u8]) -> Vec<(&'static str, String)> {
+    let timestamp = OffsetDateTime::now_utc().unix_timestamp().to_string();
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+    let digest = format!("{:x}", Sha256::digest(body));
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(string_to_sign(method, path_and_query, &timestamp, &nonce, &digest).as_bytes());
+    let signature: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    vec![
+        (DEVICE_ID_HEADER, device.to_string()),
+        (TIMESTAMP_HEADER, timestamp),
+        (NONCE_HEADER, nonce),
+        (DIGEST_HEADER, digest),
+        (SIGNATURE_HEADER, signature),
+    ]
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn reject(msg: &str) -> Response {
+    (StatusCode::UNAUTHORIZED, msg.to_string()).into_response()
+}
+
+/// Route middleware checking the device signature before the handler sees the body
+pub async fn verify(State(st): State<AppState>, req: Request, next: Next) -> Response {
+    let mode = st.signing.mode;
+    if mode == SigningMode::Off {
+        return next.run(req).await;
+    }
+    let headers = req.headers();
+    let Some(signature) = header(headers, SIGNATURE_HEADER) else {
+        return match mode {
+            SigningMode::Required => reject("Missing request signature"),
+            _ => next.run(req).await,
+        };
+    };
+    let (Some(device), Some(timestamp), Some(nonce), Some(digest)) = (
+        header(headers, DEVICE_ID_HEADER),
+        header(headers, TIMESTAMP_HEADER),
+        header(headers, NONCE_HEADER),
+        header(headers, DIGEST_HEADER),
+    ) else {
+        return reject("Incomplete signature headers");
+    };
+    let (device, timestamp, nonce, digest) =
+        (device.to_string(), timestamp.to_string(), nonce.to_string(), digest.to_lowercase());
+    let Some(signature) = decode_hex(signature) else {
+        return reject("Malformed signature");
+    };
+    let Some(secret) = st.signing.keys.get(&device) else {
+        warn!(device = %device, "signed request from unknown device");
+        return reject("Unknown device");
+    };
+
+    let Ok(ts) = timestamp.parse::<i64>() else {
+        return reject("Malformed timestamp");
+    };
+    let skew = (OffsetDateTime::now_utc().unix_timestamp() - ts).unsigned_abs();
+    if skew > MAX_SKEW.as_secs() {
+        return reject("Request timestamp outside allowed window");
+    }
+
+    let (parts, body) = req.into_parts();
+    let bytes = match to_bytes(body, MAX_SIGNED_BODY).await {
+        Ok(b) => b,
+        Err(_) => return (StatusCode::PAYLOAD_TOO_LARGE, "Signed body too large".to_string()).into_response(),
+    };
+    if format!("{:x}", Sha256::digest(&bytes)) != digest {
+        return reject("Body digest mismatch");
+    }
+
+    let path = parts.uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(string_to_sign(parts.method.as_str(), path, &timestamp, &nonce, &digest).as_bytes());
+    if mac.verify_slice(&signature).is_err() {
+        warn!(device = %device, "invalid request signature");
+        return reject("Invalid signature");
+    }
+    // Only burn the nonce once the signature is known good
+    match claim_nonce(&st, &device, &nonce).await {
+        Ok(true) => {}
+        Ok(false) => {
+            warn!(device = %device, "replayed request nonce");
+            return reject("Replayed request");
+        }
+        Err(e) => {
+            warn!(device = %device, error = %e, "nonce check failed");
+            return (StatusCode::SERVICE_UNAVAILABLE, "Replay check unavailable".to_string()).into_response();
+        }
+    }
+    logging::record_api_key_id(&device);
+
+    let mut req = Request::from_parts(parts, Body::from(bytes));
+    req.extensions_mut().insert(VerifiedDevice(device));
+    next.run(req).await
+}
@@ -0,0 +1,99 @@
+//! Site metadata for FOD Detection Backend
+//! Airport name, map defaults and enabled features the frontend configures itself from
+
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use std::{env, sync::OnceLock};
+
+use crate::{calendar, db::internal, logging, AppState};
+
+// ==================== Config ====================
+
+struct Config {
+    /// Display name (`SITE_NAME`, default `SITE_ID`)
+    name: String,
+    /// Airport ICAO code (`SITE_ICAO`)
+    icao: Option<String>,
+    /// Map centre (`SITE_MAP_CENTER=lat,lon`, default Suvarnabhumi)
+    center: [f64; 2],
+    /// Initial map zoom (`SITE_MAP_ZOOM`)
+    zoom: u8,
+    /// Runways in use (`SITE_RUNWAYS=01L,01R`); from recent movements when unset
+    runways: Option<Vec<String>>,
+}
+
+fn parse_center(s: &str) -> Option<[f64; 2]> {
+    let (lat, lon) = s.split_once(',')?;
+    let (lat, lon): (f64, f64) = (lat.trim().parse().ok()?, lon.trim().parse().ok()?);
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)).then_some([lat, lon])
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        let var = |name: &str| env::var(name).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        Config {
+            name: var("SITE_NAME").unwrap_or_else(|| logging::site_id().to_string()),
+            icao: var("SITE_ICAO").map(|s| s.to_uppercase()),
+            center: var("SITE_MAP_CENTER")
+                .map(|s| parse_center(&s).unwrap_or_else(|| panic!("SITE_MAP_CENTER must be lat,lon, got {:?}", s)))
+                .unwrap_or([13.69, 100.7501]),
+            zoom: var("SITE_MAP_ZOOM").and_then(|s| s.parse().ok()).filter(|&z| z <= 22).unwrap_or(15),
+            runways: var("SITE_RUNWAYS")
+                .map(|s| s.split(',').map(|r| r.trim().to_uppercase()).filter(|r| !r.is_empty()).collect()),
+        }
+    })
+}
+
+/// Display name of the site
+pub fn name() -> &'static str {
+    &config().name
+}
+
+/// Airport ICAO code, when configured
+pub fn icao() -> Option<&'static str> {
+    config().icao.as_deref()
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize)]
+pub struct Map {
+    pub center: [f64; 2],
+    pub zoom: u8,
+}
+
+#[derive(Serialize)]
+pub struct SiteMeta {
+    pub site_id: &'static str,
+    pub name: &'static str,
+    pub icao: Option<&'static str>,
+    pub timezone: &'static str,
+    pub map: Map,
+    pub active_runways: Vec<String>,
+    /// Feature flags on for this site
+    pub features: Vec<String>,
+}
+
+// ==================== Handlers ====================
+
+/// GET /meta — site name, ICAO code, timezone, map defaults, runways and features (public)
+pub async fn meta_handler(State(st): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let cfg = config();
+    let active_runways = match &cfg.runways {
+        Some(r) => r.clone(),
+        None => sqlx::query_scalar("SELECT DISTINCT runway FROM aircraft_movements WHERE ts > NOW() - INTERVAL '24 hours' ORDER BY runway")
+            .fetch_all(&st.db)
+            .await
+            .map_err(internal)?,
+    };
+    Ok(Json(SiteMeta {
+        site_id: logging::site_id(),
+        name: &cfg.name,
+        icao: cfg.icao.as_deref(),
+        timezone: &calendar::site().tz,
+        map: Map { center: cfg.center, zoom: cfg.zoom },
+        active_runways,
+        features: st.flags.active(&st.db).await,
+    }))
+}
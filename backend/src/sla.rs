@@ -0,0 +1,175 @@
+//! Stale-debris SLA for FOD Detection Backend
+//! Confirmed events left uncleared past the threshold for their zone and severity escalate their alert
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use time::OffsetDateTime;
+use tracing::info;
+
+use crate::{alerts, auth, db::internal, perf, subscriptions, AppState};
+
+/// Confirmed but not yet cleared; the SLA clock runs from confirmation
+const UNCLEARED: [&str; 3] = ["confirmed", "dispatched", "removed"];
+/// Events breached per evaluator pass
+const BREACH_BATCH: i64 = 500;
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct Threshold {
+    /// `""` for any zone
+    pub zone: String,
+    /// `""` for any severity
+    pub severity: String,
+    pub minutes: i32,
+    pub updated_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+#[derive(Deserialize)]
+pub struct PutThreshold {
+    #[serde(default)]
+    pub zone: String,
+    #[serde(default)]
+    pub severity: String,
+    pub minutes: i32,
+}
+
+#[derive(Deserialize)]
+pub struct ThresholdKey {
+    #[serde(default)]
+    pub zone: String,
+    #[serde(default)]
+    pub severity: String,
+}
+
+// ==================== Evaluator ====================
+
+/// Escalate the alert of every uncleared event past its threshold, once per alert: events
+/// without an alert get one under the policy `alerts::raise` would pick, acknowledged alerts
+/// reopen, and the current escalation step (the last one if all were paged) is due now.
+/// Resolved alerts and quarantined sources are left alone. Returns the alerts breached.
+pub async fn breach(st: &AppState) -> Result<u64, (StatusCode, String)> {
+    let q = sqlx::query_scalar::<_, i64>(
+        r#"
+        WITH stale AS (
+            SELECT e.id, LOWER(e.severity) AS severity
+            FROM events e
+            CROSS JOIN LATERAL (
+                SELECT t.minutes FROM sla_thresholds t
+                WHERE t.zone IN ('', e.zone) AND t.severity IN ('', LOWER(COALESCE(e.severity, '')))
+                ORDER BY t.zone = '', t.severity = ''
+                LIMIT 1
+            ) t
+            WHERE e.state = ANY($1)
+              AND COALESCE(
+                    (SELECT MIN(s.created_at) FROM event_state_transitions s WHERE s.event_id = e.id AND s.to_state = 'confirmed'),
+                    e.state_changed_at
+                  ) <= NOW() - make_interval(mins => t.minutes)
+              AND NOT EXISTS (SELECT 1 FROM alerts a WHERE a.event_id = e.id AND (a.sla_breached_at IS NOT NULL OR a.status = 'resolved'))
+              AND NOT EXISTS (SELECT 1 FROM quarantine_sources qs WHERE qs.source_ref = e.source_ref)
+            LIMIT $2
+        )
+        INSERT INTO alerts AS a (event_id, policy_id, severity, next_step_at, sla_breached_at)
+        SELECT s.id, p.id, s.severity, CASE WHEN p.id IS NOT NULL THEN NOW() END, NOW()
+        FROM stale s
+        LEFT JOIN LATERAL (
+            SELECT id FROM escalation_policies
+            WHERE cardinality(severities) = 0 OR s.severity = ANY(severities)
+            ORDER BY cardinality(severities) = 0, id
+            LIMIT 1
+        ) p ON TRUE
+        ON CONFLICT (event_id) DO UPDATE
+            SET status = 'open', sla_breached_at = NOW(),
+                next_step = LEAST(a.next_step, COALESCE((SELECT MAX(position) FROM escalation_steps es WHERE es.policy_id = a.policy_id), 0)),
+                next_step_at = CASE WHEN a.policy_id IS NOT NULL THEN NOW() END
+        RETURNING a.id
+        "#,
+    )
+    .bind(&UNCLEARED[..])
+    .bind(BREACH_BATCH)
+    .fetch_all(&st.db);
+    let breached = perf::timed("breach_sla", String::new, q).await.map_err(internal)?;
+    for &id in &breached {
+        info!(alert_id = id, "event overran its SLA, alert escalated");
+        alerts::publish(&mut *st.db.acquire().await.map_err(internal)?, "sla_breached", id).await?;
+    }
+    Ok(breached.len() as u64)
+}
+
+// ==================== Queries ====================
+
+async fn list(db: &PgPool) -> Result<Vec<Threshold>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, Threshold>("SELECT zone, severity, minutes, updated_by, updated_at FROM sla_thresholds ORDER BY zone, severity")
+        .fetch_all(db);
+    perf::timed("list_sla_thresholds", String::new, q).await.map_err(internal)
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/sla-thresholds — minutes a confirmed event may stay uncleared, per zone and
+/// severity (admin)
+pub async fn list_handler(State(st): State<AppState>, headers: HeaderMap) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    Ok(Json(list(&st.db).await?))
+}
+
+/// PUT /admin/sla-thresholds — `{zone?, severity?, minutes}` set the threshold for a zone and
+/// severity; leaving either out covers every zone or severity (admin)
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<PutThreshold>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let (zone, severity) = (body.zone.trim(), body.severity.trim().to_lowercase());
+    if !severity.is_empty() && !subscriptions::SEVERITIES.contains(&severity.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown severity: {} (expected one of {})", severity, subscriptions::SEVERITIES.join(", "))));
+    }
+    if zone.len() > 255 || body.minutes <= 0 {
+        return Err((StatusCode::BAD_REQUEST, "zone must be at most 255 characters and minutes more than zero".to_string()));
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO sla_thresholds (zone, severity, minutes, updated_by) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (zone, severity) DO UPDATE
+            SET minutes = EXCLUDED.minutes, updated_by = EXCLUDED.updated_by, updated_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(zone)
+    .bind(&severity)
+    .bind(body.minutes)
+    .bind(&claims.username)
+    .execute(&st.db)
+    .await
+    .map_err(internal)?;
+    info!(zone, severity = %severity, minutes = body.minutes, by = %claims.username, "SLA threshold saved");
+    Ok(Json(list(&st.db).await?))
+}
+
+/// DELETE /admin/sla-thresholds?zone=&severity= — drop a threshold (admin)
+pub async fn delete_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(key): Query<ThresholdKey>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_admin(&headers)?;
+    let done = sqlx::query("DELETE FROM sla_thresholds WHERE zone = $1 AND severity = $2")
+        .bind(key.zone.trim())
+        .bind(key.severity.trim().to_lowercase())
+        .execute(&st.db)
+        .await
+        .map_err(internal)?;
+    if done.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "No such SLA threshold".to_string()));
+    }
+    info!(zone = %key.zone, severity = %key.severity, by = %claims.username, "SLA threshold removed");
+    Ok(Json(list(&st.db).await?))
+}
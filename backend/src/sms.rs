@@ -0,0 +1,292 @@
+//! SMS alert delivery for FOD Detection Backend
+//! Recipient numbers with quiet hours, sent through a Twilio-compatible gateway under a monthly quota
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::env;
+use time::OffsetDateTime;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{alerts, calendar, db::internal, secrets, subscriptions, AppState};
+
+/// Channel name in subscriptions and on-call members that means "text my phone"
+pub const CHANNEL: &str = "sms";
+/// Longest body the gateway takes; longer messages are cut
+const MAX_BODY_CHARS: usize = 1600;
+
+// ==================== Config ====================
+
+pub struct Gateway {
+    /// `SMS_API_URL`, default Twilio
+    pub url: String,
+    pub account_sid: String,
+    pub auth_token: String,
+    /// Sender number or messaging service id (`SMS_FROM`)
+    pub from: String,
+}
+
+pub struct Config {
+    /// Set when `SMS_ACCOUNT_SID`, `SMS_AUTH_TOKEN` and `SMS_FROM` all are; until then `sms`
+    /// messages stay pending
+    pub gateway: Option<Gateway>,
+    /// Most messages sent per calendar month in the site timezone (`SMS_MONTHLY_QUOTA`, unset is unlimited)
+    pub monthly_quota: Option<i64>,
+    /// Lowest alert severity texted (`SMS_MIN_SEVERITY`, default critical); lower ones are skipped
+    pub min_severity: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { gateway: None, monthly_quota: None, min_severity: "critical".to_string() }
+    }
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let var = |name: &str| env::var(name).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let gateway = match (var("SMS_ACCOUNT_SID"), secrets::get("SMS_AUTH_TOKEN"), var("SMS_FROM")) {
+            (Some(account_sid), Some(auth_token), Some(from)) => {
+                let url = var("SMS_API_URL").unwrap_or_else(|| "https://api.twilio.com".to_string());
+                Some(Gateway { url, account_sid, auth_token, from })
+            }
+            _ => None,
+        };
+        Config {
+            gateway,
+            monthly_quota: var("SMS_MONTHLY_QUOTA").map(|s| s.parse().unwrap_or_else(|_| panic!("SMS_MONTHLY_QUOTA must be a number, got {:?}", s))),
+            min_severity: var("SMS_MIN_SEVERITY")
+                .map(|s| s.to_lowercase())
+                .filter(|s| subscriptions::SEVERITIES.contains(&s.as_str()))
+                .unwrap_or_else(|| Config::default().min_severity),
+        }
+    }
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct Recipient {
+    pub user_id: Uuid,
+    pub phone: String,
+    /// `HH:MM` local time, no texts from here until `quiet_to`
+    pub quiet_from: Option<String>,
+    pub quiet_to: Option<String>,
+    /// Quiet hours are in the site timezone when unset
+    pub timezone: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+}
+
+const RECIPIENT_COLUMNS: &str =
+    "user_id, phone, to_char(quiet_from, 'HH24:MI') AS quiet_from, to_char(quiet_to, 'HH24:MI') AS quiet_to, timezone, updated_at";
+
+#[derive(Deserialize)]
+pub struct RecipientRequest {
+    pub phone: String,
+    pub quiet_from: Option<String>,
+    pub quiet_to: Option<String>,
+    pub timezone: Option<String>,
+}
+
+fn valid_phone(phone: &str) -> bool {
+    let digits = phone.strip_prefix('+').unwrap_or("");
+    (7..=15).contains(&digits.len()) && digits.bytes().all(|b| b.is_ascii_digit()) && !digits.starts_with('0')
+}
+
+fn valid_time(s: &str) -> bool {
+    let Some((h, m)) = s.split_once(':') else { return false };
+    h.len() == 2 && m.len() == 2 && h.parse::<u8>().is_ok_and(|h| h < 24) && m.parse::<u8>().is_ok_and(|m| m < 60)
+}
+
+// ==================== Delivery ====================
+
+/// Texts sent since the start of this month in the site timezone
+async fn sent_this_month(db: &PgPool) -> Result<i64, (StatusCode, String)> {
+    sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM alert_notifications
+        WHERE channel = $1 AND status = 'sent'
+          AND sent_at >= date_trunc('month', NOW() AT TIME ZONE $2) AT TIME ZONE $2
+        "#,
+    )
+    .bind(CHANNEL)
+    .bind(&calendar::site().tz)
+    .fetch_one(db)
+    .await
+    .map_err(internal)
+}
+
+/// The user's number, and whether it is inside their quiet hours now
+async fn recipient(db: &PgPool, user_id: Option<Uuid>) -> Result<Option<(String, bool)>, (StatusCode, String)> {
+    sqlx::query_as(
+        r#"
+        SELECT r.phone,
+               CASE WHEN r.quiet_from IS NULL THEN FALSE
+                    WHEN r.quiet_from <= r.quiet_to THEN l.now >= r.quiet_from AND l.now < r.quiet_to
+                    ELSE l.now >= r.quiet_from OR l.now < r.quiet_to
+               END
+        FROM sms_recipients r
+        CROSS JOIN LATERAL (SELECT (NOW() AT TIME ZONE COALESCE(r.timezone, $2))::TIME AS now) l
+        WHERE r.user_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(&calendar::site().tz)
+    .fetch_optional(db)
+    .await
+    .map_err(internal)
+}
+
+async fn send(st: &AppState, gateway: &Gateway, to: &str, body: &str) -> Result<(), String> {
+    let body: String = body.chars().take(MAX_BODY_CHARS).collect();
+    let resp = st
+        .http
+        .post(format!("{}/2010-04-01/Accounts/{}/Messages.json", gateway.url.trim_end_matches('/'), gateway.account_sid))
+        .basic_auth(&gateway.account_sid, Some(&gateway.auth_token))
+        .form(&[("To", to), ("From", gateway.from.as_str()), ("Body", body.as_str())])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if resp.status().is_success() {
+        return Ok(());
+    }
+    let status = resp.status();
+    let detail = resp.json::<serde_json::Value>().await.ok().and_then(|b| b["message"].as_str().map(str::to_string)).unwrap_or_default();
+    Err(format!("gateway returned {} {}", status, detail).trim_end().to_string())
+}
+
+/// Text pending `sms` alert messages to their user's number, skipping alerts below
+/// `SMS_MIN_SEVERITY`, recipients in quiet hours and anything past the monthly quota. Returns the
+/// number sent.
+pub async fn deliver(st: &AppState) -> Result<u64, (StatusCode, String)> {
+    let Some(gateway) = st.sms.gateway.as_ref() else { return Ok(0) };
+    let pending = alerts::outgoing(&st.db, CHANNEL).await?;
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    let mut used = sent_this_month(&st.db).await?;
+    let mut sent = 0;
+    for n in pending {
+        if alerts::below(n.severity.as_deref(), &st.sms.min_severity) {
+            alerts::delivered(&st.db, n.id, "skipped", Some(format!("below SMS_MIN_SEVERITY ({})", st.sms.min_severity))).await?;
+            continue;
+        }
+        let Some((phone, quiet)) = recipient(&st.db, n.user_id).await? else {
+            alerts::delivered(&st.db, n.id, "skipped", Some("no SMS number".to_string())).await?;
+            continue;
+        };
+        if quiet {
+            alerts::delivered(&st.db, n.id, "skipped", Some("quiet hours".to_string())).await?;
+            continue;
+        }
+        if let Some(quota) = st.sms.monthly_quota.filter(|q| used >= *q) {
+            warn!(notification_id = n.id, quota, "monthly SMS quota reached, not sending");
+            alerts::delivered(&st.db, n.id, "skipped", Some(format!("monthly SMS quota of {} reached", quota))).await?;
+            continue;
+        }
+        match send(st, gateway, &phone, &n.body).await {
+            Ok(()) => {
+                alerts::delivered(&st.db, n.id, "sent", None).await?;
+                used += 1;
+                sent += 1;
+            }
+            Err(e) => {
+                warn!(notification_id = n.id, error = %e, "SMS delivery failed");
+                alerts::delivered(&st.db, n.id, "failed", Some(e)).await?;
+            }
+        }
+    }
+    Ok(sent)
+}
+
+// ==================== Handlers ====================
+
+/// GET /users/:id/sms — the user's SMS number and quiet hours
+pub async fn get_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    subscriptions::authorize(&headers, user_id)?;
+    let sql = format!("SELECT {} FROM sms_recipients WHERE user_id = $1", RECIPIENT_COLUMNS);
+    let recipient = sqlx::query_as::<_, Recipient>(&sql)
+        .bind(user_id)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, "No SMS number set".to_string()))?;
+    Ok(Json(recipient))
+}
+
+/// PUT /users/:id/sms — `{phone, quiet_from?, quiet_to?, timezone?}` set the number (E.164) and
+/// `HH:MM` quiet hours
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<RecipientRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    subscriptions::authorize(&headers, user_id)?;
+    let phone: String = req.phone.chars().filter(|c| !matches!(c, ' ' | '-')).collect();
+    if !valid_phone(&phone) {
+        return Err((StatusCode::BAD_REQUEST, "phone must be in E.164 form, e.g. +66812345678".to_string()));
+    }
+    let (quiet_from, quiet_to) = (req.quiet_from.filter(|s| !s.is_empty()), req.quiet_to.filter(|s| !s.is_empty()));
+    if quiet_from.is_some() != quiet_to.is_some() || ![&quiet_from, &quiet_to].iter().all(|t| t.as_deref().is_none_or(valid_time)) {
+        return Err((StatusCode::BAD_REQUEST, "quiet_from and quiet_to must both be HH:MM, or both unset".to_string()));
+    }
+    let timezone = req.timezone.filter(|s| !s.is_empty());
+    if let Some(tz) = &timezone {
+        let known: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)")
+            .bind(tz)
+            .fetch_one(&st.db)
+            .await
+            .map_err(internal)?;
+        if !known {
+            return Err((StatusCode::BAD_REQUEST, format!("Unknown timezone: {}", tz)));
+        }
+    }
+    let sql = format!(
+        r#"
+        INSERT INTO sms_recipients (user_id, phone, quiet_from, quiet_to, timezone)
+        SELECT id, $2, $3::TIME, $4::TIME, $5 FROM users WHERE id = $1
+        ON CONFLICT (user_id) DO UPDATE
+            SET phone = EXCLUDED.phone, quiet_from = EXCLUDED.quiet_from, quiet_to = EXCLUDED.quiet_to,
+                timezone = EXCLUDED.timezone, updated_at = CURRENT_TIMESTAMP
+        RETURNING {}
+        "#,
+        RECIPIENT_COLUMNS
+    );
+    let recipient = sqlx::query_as::<_, Recipient>(&sql)
+        .bind(user_id)
+        .bind(&phone)
+        .bind(quiet_from)
+        .bind(quiet_to)
+        .bind(timezone)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::NOT_FOUND, format!("User not found: {}", user_id)))?;
+    Ok(Json(recipient))
+}
+
+/// DELETE /users/:id/sms — stop texting this user
+pub async fn delete_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    subscriptions::authorize(&headers, user_id)?;
+    let done = sqlx::query("DELETE FROM sms_recipients WHERE user_id = $1").bind(user_id).execute(&st.db).await.map_err(internal)?;
+    if done.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "No SMS number set".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
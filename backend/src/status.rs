@@ -0,0 +1,314 @@
+//! Service status for FOD Detection Backend
+//! Per-dependency health kept by a background checker, reported in /health and `X-Service-Status`,
+//! and sampled into `health_history` for uptime reporting
+
+use axum::{
+    extract::{Query, Request, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    env,
+    sync::{atomic::Ordering, Arc, OnceLock, RwLock},
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tracing::{info, warn};
+
+use crate::{auth, calendar, db, db::internal, perf, AppState};
+
+/// Header carrying the overall status on every response
+pub const HEADER: &str = "x-service-status";
+/// Health requests slower than this count as failed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// ==================== Config ====================
+
+/// Seconds between checks (`HEALTH_CHECK_SECS`, default 15)
+fn interval() -> Duration {
+    static SECS: OnceLock<u64> = OnceLock::new();
+    Duration::from_secs(*SECS.get_or_init(|| {
+        env::var("HEALTH_CHECK_SECS").ok().and_then(|s| s.parse().ok()).filter(|&s| s > 0).unwrap_or(15)
+    }))
+}
+
+/// Seconds between uptime samples (`HEALTH_HISTORY_SECS`, default 60)
+fn history_interval() -> Duration {
+    static SECS: OnceLock<u64> = OnceLock::new();
+    Duration::from_secs(*SECS.get_or_init(|| {
+        env::var("HEALTH_HISTORY_SECS").ok().and_then(|s| s.parse().ok()).filter(|&s| s > 0).unwrap_or(60)
+    }))
+}
+
+/// Samples older than this are deleted
+const HISTORY_RETENTION_DAYS: i32 = 400;
+/// Samples held in memory while the database is unreachable, oldest dropped first
+const MAX_BACKLOG: usize = 20_000;
+
+// ==================== Models ====================
+
+/// Ordered best to worst, so the overall status is the max
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Nominal,
+    Degraded,
+    Down,
+}
+
+impl Level {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Nominal => "nominal",
+            Level::Degraded => "degraded",
+            Level::Down => "down",
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct Dependency {
+    pub status: Level,
+    pub detail: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub checked_at: OffsetDateTime,
+}
+
+/// Latest status per dependency, shared by the checker and the request path
+#[derive(Clone, Default)]
+pub struct Board(Arc<RwLock<BTreeMap<&'static str, Dependency>>>);
+
+impl Board {
+    pub fn set(&self, name: &'static str, status: Level, detail: Option<String>) {
+        let mut deps = self.0.write().unwrap();
+        let changed = deps.get(name).map(|d| d.status) != Some(status);
+        if changed {
+            match status {
+                Level::Nominal => info!(dependency = name, "dependency nominal"),
+                _ => warn!(dependency = name, status = status.as_str(), detail = ?detail, "dependency not nominal"),
+            }
+        }
+        deps.insert(name, Dependency { status, detail, checked_at: OffsetDateTime::now_utc() });
+    }
+
+    /// Worst status of any dependency; nominal before the first check
+    pub fn overall(&self) -> Level {
+        self.0.read().unwrap().values().map(|d| d.status).max().unwrap_or(Level::Nominal)
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<&'static str, Dependency> {
+        self.0.read().unwrap().clone()
+    }
+}
+
+// ==================== Checker ====================
+
+async fn reachable(state: &AppState, base: &str) -> bool {
+    let url = format!("{}/health", base.trim_end_matches('/'));
+    state.http.get(&url).timeout(PROBE_TIMEOUT).send().await.is_ok_and(|r| r.status().is_success())
+}
+
+/// Probe every dependency once and update the board
+pub async fn check(state: &AppState) {
+    let db = match db::check_health(&state.db).await {
+        Err((_, e)) => (Level::Down, Some(e)),
+        Ok(_) if state.read_only.load(Ordering::Relaxed) => (Level::Degraded, Some("migrations pending, read-only".to_string())),
+        Ok(_) => (Level::Nominal, None),
+    };
+    state.status.set("db", db.0, db.1);
+
+    let ai = if reachable(state, &state.ai.base).await {
+        (Level::Nominal, None)
+    } else {
+        let fallback_up = match state.ai.fallback.as_deref().map(|fb| fb.service()) {
+            None => None,
+            // An embedded model is always there
+            Some(None) => Some(true),
+            Some(Some(service)) => Some(reachable(state, &service.base).await),
+        };
+        match fallback_up {
+            Some(true) => (Level::Degraded, Some("AI service unreachable, detections from fallback".to_string())),
+            Some(false) => (Level::Down, Some("AI service and fallback unreachable".to_string())),
+            None => (Level::Down, Some("AI service unreachable".to_string())),
+        }
+    };
+    state.status.set("ai", ai.0, ai.1);
+}
+
+/// Check every `HEALTH_CHECK_SECS` on every replica; each one has its own view of its dependencies
+pub fn spawn_checker(state: AppState) {
+    info!(interval_secs = interval().as_secs(), "health checker started");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(interval());
+        loop {
+            tick.tick().await;
+            check(&state).await;
+        }
+    });
+}
+
+// ==================== History ====================
+
+/// One dependency's status at one probe slot
+pub struct Sample {
+    pub sampled_at: OffsetDateTime,
+    pub dependency: &'static str,
+    pub status: Level,
+    pub detail: Option<String>,
+}
+
+/// Start of the probe slot `at` falls in; every replica maps a slot to the same instant
+fn slot(at: OffsetDateTime, interval: Duration) -> OffsetDateTime {
+    let secs = interval.as_secs().max(1) as i64;
+    let ts = at.unix_timestamp();
+    OffsetDateTime::from_unix_timestamp(ts - ts.rem_euclid(secs)).unwrap_or(at)
+}
+
+async fn write(db: &PgPool, samples: &[Sample]) -> Result<u64, sqlx::Error> {
+    let at: Vec<OffsetDateTime> = samples.iter().map(|s| s.sampled_at).collect();
+    let names: Vec<&str> = samples.iter().map(|s| s.dependency).collect();
+    let statuses: Vec<&str> = samples.iter().map(|s| s.status.as_str()).collect();
+    let details: Vec<Option<String>> = samples.iter().map(|s| s.detail.clone()).collect();
+    let done = sqlx::query(
+        r#"
+        INSERT INTO health_history (sampled_at, dependency, status, detail)
+        SELECT * FROM UNNEST($1::TIMESTAMPTZ[], $2::TEXT[], $3::TEXT[], $4::TEXT[])
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(at)
+    .bind(names)
+    .bind(statuses)
+    .bind(details)
+    .execute(db)
+    .await?;
+    sqlx::query("DELETE FROM health_history WHERE sampled_at < NOW() - make_interval(days => $1)")
+        .bind(HISTORY_RETENTION_DAYS)
+        .execute(db)
+        .await?;
+    Ok(done.rows_affected())
+}
+
+/// Probe every dependency and store the outcome for the current slot. Samples that can't be
+/// written (the database being one of the dependencies) stay in `backlog` for the next call.
+pub async fn sample(state: &AppState, backlog: &mut VecDeque<Sample>) -> Result<u64, sqlx::Error> {
+    check(state).await;
+    let sampled_at = slot(OffsetDateTime::now_utc(), history_interval());
+    for (dependency, dep) in state.status.snapshot() {
+        backlog.push_back(Sample { sampled_at, dependency, status: dep.status, detail: dep.detail });
+    }
+    while backlog.len() > MAX_BACKLOG {
+        backlog.pop_front();
+    }
+    let written = write(&state.db, backlog.make_contiguous()).await?;
+    backlog.clear();
+    Ok(written)
+}
+
+/// Sample every `HEALTH_HISTORY_SECS` on every replica; slots already stored by another are skipped
+pub fn spawn_prober(state: AppState) {
+    info!(interval_secs = history_interval().as_secs(), "health history prober started");
+    tokio::spawn(async move {
+        let mut backlog = VecDeque::new();
+        let mut tick = tokio::time::interval(history_interval());
+        loop {
+            tick.tick().await;
+            if let Err(e) = sample(&state, &mut backlog).await {
+                warn!(error = %e, held = backlog.len(), "health samples not stored, retrying next probe");
+            }
+        }
+    });
+}
+
+// ==================== Uptime ====================
+
+#[derive(Serialize, FromRow)]
+pub struct UptimeDay {
+    #[serde(skip)]
+    pub dependency: String,
+    /// Site-local date
+    pub day: String,
+    pub samples: i64,
+    pub nominal: i64,
+    pub degraded: i64,
+    pub down: i64,
+}
+
+/// Share of samples not down, in percent; degraded still serves requests
+fn availability(samples: i64, down: i64) -> Option<f64> {
+    (samples > 0).then(|| ((samples - down) as f64 * 100_000.0 / samples as f64).round() / 1000.0)
+}
+
+/// GET /admin/uptime?days=30 — availability per dependency and site-local day from the
+/// sampled health history (admin only)
+pub async fn uptime_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let days: i32 = match q.get("days") {
+        Some(s) => s.parse().ok().filter(|d| (1..=HISTORY_RETENTION_DAYS).contains(d)).ok_or((
+            StatusCode::BAD_REQUEST,
+            format!("days must be between 1 and {}", HISTORY_RETENTION_DAYS),
+        ))?,
+        None => 30,
+    };
+    let tz = &calendar::site().tz;
+    let sql = r#"
+        SELECT dependency,
+               to_char(sampled_at AT TIME ZONE $1, 'YYYY-MM-DD') AS day,
+               COUNT(*) AS samples,
+               COUNT(*) FILTER (WHERE status = 'nominal') AS nominal,
+               COUNT(*) FILTER (WHERE status = 'degraded') AS degraded,
+               COUNT(*) FILTER (WHERE status = 'down') AS down
+        FROM health_history
+        WHERE sampled_at >= (date_trunc('day', NOW() AT TIME ZONE $1) - make_interval(days => $2 - 1)) AT TIME ZONE $1
+        GROUP BY 1, 2
+        ORDER BY 1, 2
+    "#;
+    let q = sqlx::query_as::<_, UptimeDay>(sql).bind(tz).bind(days).fetch_all(&st.db);
+    let rows = perf::timed("uptime", || format!("days={}", days), q).await.map_err(internal)?;
+
+    let mut by_dependency: BTreeMap<String, Vec<UptimeDay>> = BTreeMap::new();
+    for row in rows {
+        by_dependency.entry(row.dependency.clone()).or_default().push(row);
+    }
+    let dependencies: BTreeMap<String, serde_json::Value> = by_dependency
+        .into_iter()
+        .map(|(name, days)| {
+            let samples: i64 = days.iter().map(|d| d.samples).sum();
+            let down: i64 = days.iter().map(|d| d.down).sum();
+            let days: Vec<serde_json::Value> = days
+                .iter()
+                .map(|d| {
+                    let mut v = json!(d);
+                    v["availability_pct"] = json!(availability(d.samples, d.down));
+                    v
+                })
+                .collect();
+            (name, json!({ "availability_pct": availability(samples, down), "samples": samples, "days": days }))
+        })
+        .collect();
+    Ok(Json(json!({
+        "days": days,
+        "timezone": tz,
+        "interval_secs": history_interval().as_secs(),
+        "dependencies": dependencies,
+    })))
+}
+
+// ==================== Middleware ====================
+
+/// Adds `X-Service-Status: nominal|degraded|down` to every response
+pub async fn header(State(st): State<AppState>, req: Request, next: Next) -> Response {
+    let mut resp = next.run(req).await;
+    resp.headers_mut().insert(HEADER, HeaderValue::from_static(st.status.overall().as_str()));
+    resp
+}
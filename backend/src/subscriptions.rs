@@ -0,0 +1,235 @@
+//! Notification subscriptions for FOD Detection Backend
+//! Per-user class/zone/severity filters and delivery channels
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{auth, db::internal, flags, quarantine, reliability, traffic, AppState};
+
+/// Accepted severity levels, lowest first
+pub const SEVERITIES: [&str; 4] = ["low", "medium", "high", "critical"];
+
+// ==================== Models ====================
+
+/// Empty filter lists match everything
+#[derive(Serialize, FromRow)]
+pub struct Subscription {
+    pub user_id: Uuid,
+    pub classes: Vec<String>,
+    pub zones: Vec<String>,
+    pub severities: Vec<String>,
+    pub channels: Vec<String>,
+    pub enabled: bool,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub updated_at: Option<OffsetDateTime>,
+}
+
+#[derive(Serialize, FromRow)]
+pub struct Recipient {
+    pub user_id: Uuid,
+    pub username: String,
+    pub channels: Vec<String>,
+}
+
+// ==================== Request Types ====================
+
+#[derive(Deserialize)]
+pub struct SubscriptionRequest {
+    #[serde(default)]
+    pub classes: Vec<String>,
+    #[serde(default)]
+    pub zones: Vec<String>,
+    #[serde(default)]
+    pub severities: Vec<String>,
+    #[serde(default)]
+    pub channels: Vec<String>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Deserialize)]
+pub struct RecipientParams {
+    pub class: String,
+    pub zone: Option<String>,
+    pub severity: Option<String>,
+    /// Detecting source, weighs the severity by its reliability when `reliability_weighting` is on
+    pub source_ref: Option<String>,
+    /// Runway the debris is on, weighs the severity by traffic on approach when `traffic_weighting` is on
+    pub runway: Option<String>,
+    /// Alert even when `source_ref` is quarantined
+    #[serde(default)]
+    pub include_quarantine: bool,
+}
+
+// ==================== Queries ====================
+
+/// Users whose subscription matches an event; the notifier fans out to their channels.
+/// An event without a zone/severity only reaches users who don't filter on it.
+pub async fn recipients(
+    db: &PgPool,
+    class: &str,
+    zone: Option<&str>,
+    severity: Option<&str>,
+) -> Result<Vec<Recipient>, (StatusCode, String)> {
+    sqlx::query_as::<_, Recipient>(
+        r#"
+        SELECT s.user_id, u.username, s.channels
+        FROM user_subscriptions s
+        JOIN users u ON u.id = s.user_id
+        WHERE s.enabled
+          AND cardinality(s.channels) > 0
+          AND (cardinality(s.classes) = 0 OR $1 = ANY(s.classes))
+          AND (cardinality(s.zones) = 0 OR $2 = ANY(s.zones))
+          AND (cardinality(s.severities) = 0 OR $3 = ANY(s.severities))
+        ORDER BY u.username
+        "#,
+    )
+    .bind(class)
+    .bind(zone)
+    .bind(severity)
+    .fetch_all(db)
+    .await
+    .map_err(internal)
+}
+
+/// Own settings, or anyone's for admins
+pub fn authorize(headers: &HeaderMap, user_id: Uuid) -> Result<(), (StatusCode, String)> {
+    let claims = auth::require_user(headers)?;
+    if claims.role != "admin" && claims.sub != user_id.to_string() {
+        return Err((StatusCode::FORBIDDEN, "Cannot access another user's settings".to_string()));
+    }
+    Ok(())
+}
+
+fn normalize(values: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = values.into_iter().map(|v| v.trim().to_string()).filter(|v| !v.is_empty()).collect();
+    out.sort();
+    out.dedup();
+    out
+}
+
+// ==================== Handlers ====================
+
+/// GET /users/:id/subscriptions — defaults (everything, no channels) until set
+pub async fn get_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    authorize(&headers, user_id)?;
+    let sub = sqlx::query_as::<_, Subscription>(
+        "SELECT user_id, classes, zones, severities, channels, enabled, updated_at FROM user_subscriptions WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(sub.unwrap_or(Subscription {
+        user_id,
+        classes: Vec::new(),
+        zones: Vec::new(),
+        severities: Vec::new(),
+        channels: Vec::new(),
+        enabled: true,
+        updated_at: None,
+    })))
+}
+
+/// PUT /users/:id/subscriptions — replace the user's subscription
+pub async fn put_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    Json(req): Json<SubscriptionRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    authorize(&headers, user_id)?;
+    let classes = normalize(req.classes);
+    let zones = normalize(req.zones);
+    let severities = normalize(req.severities.into_iter().map(|s| s.to_lowercase()).collect());
+    let channels = normalize(req.channels.into_iter().map(|c| c.to_lowercase()).collect());
+
+    if let Some(bad) = severities.iter().find(|s| !SEVERITIES.contains(&s.as_str())) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown severity: {} (expected one of {})", bad, SEVERITIES.join(", "))));
+    }
+    let known: Vec<String> = sqlx::query_scalar("SELECT name FROM fod_classes WHERE name = ANY($1)")
+        .bind(&classes)
+        .fetch_all(&st.db)
+        .await
+        .map_err(internal)?;
+    if let Some(bad) = classes.iter().find(|c| !known.contains(c)) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown class: {}", bad)));
+    }
+
+    let exists: Option<Uuid> = sqlx::query_scalar("SELECT id FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?;
+    if exists.is_none() {
+        return Err((StatusCode::NOT_FOUND, format!("User not found: {}", user_id)));
+    }
+
+    let sub = sqlx::query_as::<_, Subscription>(
+        r#"
+        INSERT INTO user_subscriptions (user_id, classes, zones, severities, channels, enabled)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (user_id) DO UPDATE
+            SET classes = EXCLUDED.classes, zones = EXCLUDED.zones, severities = EXCLUDED.severities,
+                channels = EXCLUDED.channels, enabled = EXCLUDED.enabled, updated_at = CURRENT_TIMESTAMP
+        RETURNING user_id, classes, zones, severities, channels, enabled, updated_at
+        "#,
+    )
+    .bind(user_id)
+    .bind(&classes)
+    .bind(&zones)
+    .bind(&severities)
+    .bind(&channels)
+    .bind(req.enabled.unwrap_or(true))
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(sub))
+}
+
+/// Who to notify about an event: the quarantine check and the reliability/traffic weighting
+/// flags applied before `recipients`; nobody for a quarantined source unless `include_quarantine`
+pub async fn route(st: &AppState, p: &RecipientParams) -> Result<Vec<Recipient>, (StatusCode, String)> {
+    if let Some(source) = p.source_ref.as_deref().filter(|_| !p.include_quarantine) {
+        if quarantine::is_quarantined(&st.db, source).await? {
+            return Ok(Vec::new());
+        }
+    }
+    let mut severity = p.severity.as_ref().map(|s| s.to_lowercase());
+    if let (Some(sev), Some(source)) = (&severity, &p.source_ref) {
+        if st.flags.enabled(&st.db, flags::RELIABILITY_WEIGHTING).await {
+            severity = Some(reliability::weigh_severity(&st.db, source, sev).await?);
+        }
+    }
+    if let (Some(sev), Some(runway)) = (&severity, &p.runway) {
+        if st.flags.enabled(&st.db, flags::TRAFFIC_WEIGHTING).await {
+            match traffic::weigh_severity(&st.db, &st.traffic, runway, sev).await? {
+                Some(weighed) => severity = Some(weighed),
+                None => return Ok(Vec::new()),
+            }
+        }
+    }
+    recipients(&st.db, &p.class, p.zone.as_deref(), severity.as_deref()).await
+}
+
+/// GET /admin/notifications/recipients — who would be notified for a class/zone/severity/source;
+/// nobody for a quarantined source unless `include_quarantine=true`
+pub async fn recipients_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<RecipientParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    Ok(Json(route(&st, &p).await?))
+}
@@ -0,0 +1,222 @@
+//! Synthetic probe for FOD Detection Backend
+//! A known test image sent through the detect pipeline on a schedule, recording latency and whether
+//! the expected class came back; repeated failures mark the `pipeline` dependency degraded
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::FromRow;
+use std::{collections::HashMap, env, sync::OnceLock, time::Duration};
+use time::OffsetDateTime;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{ai, auth, db::internal, flags, perf, status::Level, AppState};
+
+/// Status board entry the probe reports under
+pub const DEPENDENCY: &str = "pipeline";
+
+// ==================== Config ====================
+
+/// The test image and what it must yield
+#[derive(Clone)]
+pub struct Probe {
+    pub image: Vec<u8>,
+    pub filename: String,
+    /// Class the image contains; the run fails if it isn't among the detections
+    pub expected_class: String,
+    /// Runs slower than this fail even when the class is found
+    pub max_latency: Duration,
+    /// Consecutive failed runs before the pipeline reads as degraded
+    pub alert_after: i64,
+}
+
+impl Probe {
+    /// From `SYNTHETIC_IMAGE_PATH` and `SYNTHETIC_EXPECTED_CLASS`, with `SYNTHETIC_MAX_LATENCY_MS`
+    /// (default 5000) and `SYNTHETIC_ALERT_AFTER` (default 3); None unless both are set
+    pub fn from_env() -> Option<Probe> {
+        let var = |name: &str| env::var(name).ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        let path = var("SYNTHETIC_IMAGE_PATH")?;
+        let expected_class = var("SYNTHETIC_EXPECTED_CLASS")?;
+        let image = match std::fs::read(&path) {
+            Ok(image) => image,
+            Err(e) => {
+                warn!(path = %path, error = %e, "synthetic probe image unreadable, probe disabled");
+                return None;
+            }
+        };
+        let filename = std::path::Path::new(&path).file_name().map(|f| f.to_string_lossy().into_owned()).unwrap_or(path);
+        Some(Probe {
+            image,
+            filename,
+            expected_class,
+            max_latency: Duration::from_millis(var("SYNTHETIC_MAX_LATENCY_MS").and_then(|s| s.parse().ok()).unwrap_or(5000)),
+            alert_after: var("SYNTHETIC_ALERT_AFTER").and_then(|s| s.parse().ok()).filter(|&n| n > 0).unwrap_or(3),
+        })
+    }
+}
+
+fn probe() -> Option<&'static Probe> {
+    static PROBE: OnceLock<Option<Probe>> = OnceLock::new();
+    PROBE.get_or_init(Probe::from_env).as_ref()
+}
+
+/// Seconds between runs (`SYNTHETIC_PROBE_SECS`, default 300)
+fn interval() -> Duration {
+    static SECS: OnceLock<u64> = OnceLock::new();
+    Duration::from_secs(*SECS.get_or_init(|| {
+        env::var("SYNTHETIC_PROBE_SECS").ok().and_then(|s| s.parse().ok()).filter(|&s| s > 0).unwrap_or(300)
+    }))
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct Run {
+    pub id: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub ran_at: OffsetDateTime,
+    pub expected_class: String,
+    pub detected: bool,
+    pub confidence: Option<f32>,
+    pub latency_ms: f64,
+    pub degraded: bool,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+const RUN_COLUMNS: &str = "id, ran_at, expected_class, detected, confidence, latency_ms, degraded, ok, error";
+
+// ==================== Probe ====================
+
+/// Send the test image through detection as `/proxy/detect` would (nothing is saved), store
+/// the outcome and update the `pipeline` dependency from the latest runs
+pub async fn run(state: &AppState, probe: &Probe) -> Result<Run, (StatusCode, String)> {
+    let fallback = state.flags.enabled(&state.db, flags::AI_FALLBACK).await;
+    let opts = ai::DetectOptions { conf: None, imgsz: None, fallback };
+    let started = Instant::now();
+    let result = state.ai.detect(&state.http, probe.image.clone(), probe.filename.clone(), opts).await;
+    let latency = started.elapsed();
+
+    let (confidence, degraded, mut error) = match &result {
+        Ok(v) => {
+            let best = v
+                .get("detections")
+                .and_then(|d| d.as_array())
+                .into_iter()
+                .flatten()
+                .filter(|d| d.get("cls").and_then(|c| c.as_str()).is_some_and(|c| c.eq_ignore_ascii_case(&probe.expected_class)))
+                .filter_map(|d| d.get("conf").and_then(|c| c.as_f64()))
+                .fold(None, |best: Option<f64>, c| Some(best.map_or(c, |b| b.max(c))));
+            (best.map(|c| c as f32), v.get("degraded").and_then(|d| d.as_bool()).unwrap_or(false), None)
+        }
+        Err((_, e)) => (None, false, Some(e.clone())),
+    };
+    let detected = confidence.is_some();
+    if error.is_none() && !detected {
+        error = Some(format!("{} not detected", probe.expected_class));
+    } else if error.is_none() && latency > probe.max_latency {
+        error = Some(format!("took {:.0} ms, limit {} ms", perf::as_ms(latency), probe.max_latency.as_millis()));
+    }
+    let ok = error.is_none();
+
+    let sql = format!(
+        r#"
+        INSERT INTO synthetic_probes (expected_class, detected, confidence, latency_ms, degraded, ok, error)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING {}
+        "#,
+        RUN_COLUMNS
+    );
+    let q = sqlx::query_as::<_, Run>(&sql)
+        .bind(&probe.expected_class)
+        .bind(detected)
+        .bind(confidence)
+        .bind(perf::as_ms(latency))
+        .bind(degraded)
+        .bind(ok)
+        .bind(&error)
+        .fetch_one(&state.db);
+    let run = perf::timed("record_synthetic_probe", || format!("ok={}", ok), q).await.map_err(internal)?;
+    if !ok {
+        warn!(expected = %probe.expected_class, latency_ms = run.latency_ms, error = ?run.error, "synthetic probe failed");
+    }
+
+    // Degraded once the last `alert_after` runs, on any replica, all failed
+    let recent: Vec<bool> = sqlx::query_scalar("SELECT ok FROM synthetic_probes ORDER BY id DESC LIMIT $1")
+        .bind(probe.alert_after)
+        .fetch_all(&state.db)
+        .await
+        .map_err(internal)?;
+    if recent.len() as i64 >= probe.alert_after && recent.iter().all(|ok| !ok) {
+        let detail = format!("last {} synthetic probes failed: {}", recent.len(), run.error.as_deref().unwrap_or_default());
+        state.status.set(DEPENDENCY, Level::Degraded, Some(detail));
+    } else {
+        state.status.set(DEPENDENCY, Level::Nominal, None);
+    }
+    Ok(run)
+}
+
+/// Probe every `SYNTHETIC_PROBE_SECS` on every replica, when configured
+pub fn spawn_prober(state: AppState) {
+    let Some(probe) = probe() else {
+        return;
+    };
+    info!(interval_secs = interval().as_secs(), expected = %probe.expected_class, "synthetic probe started");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(interval());
+        loop {
+            tick.tick().await;
+            if let Err((_, e)) = run(&state, probe).await {
+                warn!(error = %e, "synthetic probe not recorded");
+            }
+        }
+    });
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/synthetic?limit=50 — recent probe runs and the last day's success rate and
+/// latency percentiles (admin only)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(q): Query<HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    let limit = q.get("limit").and_then(|s| s.parse::<i64>().ok()).unwrap_or(50).clamp(1, 1000);
+    let sql = format!("SELECT {} FROM synthetic_probes ORDER BY id DESC LIMIT $1", RUN_COLUMNS);
+    let q = sqlx::query_as::<_, Run>(&sql).bind(limit).fetch_all(&st.db);
+    let runs = perf::timed("list_synthetic_probes", || format!("limit={}", limit), q).await.map_err(internal)?;
+
+    let (total, ok, p50, p95): (i64, i64, Option<f64>, Option<f64>) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*), COUNT(*) FILTER (WHERE ok),
+               percentile_cont(0.5) WITHIN GROUP (ORDER BY latency_ms),
+               percentile_cont(0.95) WITHIN GROUP (ORDER BY latency_ms)
+        FROM synthetic_probes
+        WHERE ran_at > NOW() - INTERVAL '24 hours'
+        "#,
+    )
+    .fetch_one(&st.db)
+    .await
+    .map_err(internal)?;
+    Ok(Json(json!({
+        "configured": probe().is_some(),
+        "expected_class": probe().map(|p| &p.expected_class),
+        "interval_secs": interval().as_secs(),
+        "last_24h": {
+            "runs": total,
+            "ok": ok,
+            "success_pct": (total > 0).then(|| (ok as f64 * 100_000.0 / total as f64).round() / 1000.0),
+            "p50_latency_ms": p50,
+            "p95_latency_ms": p95,
+        },
+        "runs": runs,
+    })))
+}
@@ -0,0 +1,195 @@
+//! Crew tasks for FOD Detection Backend
+//! Re-inspections scheduled after high-severity events are verified clear, and the crew's daily list
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::{env, sync::OnceLock, time::Duration};
+use time::OffsetDateTime;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{auth, calendar, db::internal, jobs, perf, AppState};
+
+/// Events verified clear longer ago than this are not looked at by the generator
+const GENERATION_LOOKBACK_DAYS: i32 = 7;
+
+// ==================== Config ====================
+
+struct Config {
+    /// Delay between an event closing and its re-inspection
+    after_hours: i32,
+    /// Classes that count as high severity regardless of meta.severity
+    classes: Vec<String>,
+}
+
+fn config() -> &'static Config {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(|| Config {
+        after_hours: env::var("REINSPECT_AFTER_HOURS").ok().and_then(|s| s.parse().ok()).unwrap_or(24),
+        classes: env::var("REINSPECT_CLASSES")
+            .unwrap_or_else(|_| "Bolt,Nut,Screw,Scrap Metal,Wire,Tire Pieces".to_string())
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
+    })
+}
+
+// ==================== Models ====================
+
+#[derive(Serialize, FromRow)]
+pub struct Task {
+    pub id: Uuid,
+    pub kind: String,
+    pub zone: Option<String>,
+    pub event_id: Option<Uuid>,
+    pub class_name: Option<String>,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    pub source_ref: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub due_at: OffsetDateTime,
+    pub status: String,
+    pub completed_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub completed_at: Option<OffsetDateTime>,
+    pub note: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CompleteRequest {
+    pub note: Option<String>,
+}
+
+const TASK_COLUMNS: &str = r#"
+    t.id, t.kind, t.zone, t.event_id, fc.name AS class_name, e.latitude, e.longitude, e.source_ref,
+    t.due_at, t.status, t.completed_by, t.completed_at, t.note
+"#;
+
+// ==================== Generation ====================
+
+/// Create re-inspection tasks for recently closed high-severity events: meta.severity
+/// high/critical, or a class in `REINSPECT_CLASSES`. Returns the number of new tasks.
+pub async fn generate(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let cfg = config();
+    let done = sqlx::query(
+        r#"
+        INSERT INTO tasks (kind, zone, event_id, due_at)
+        SELECT 'reinspection', NULLIF(e.zone, ''), e.id,
+               e.state_changed_at + make_interval(hours => $1)
+        FROM events e
+        JOIN fod_classes fc ON e.class_id = fc.id
+        WHERE e.state = 'verified_clear'
+          AND e.state_changed_at >= NOW() - make_interval(days => $3)
+          AND (LOWER(e.severity) IN ('high', 'critical') OR fc.name = ANY($2))
+        ON CONFLICT (event_id, kind) DO NOTHING
+        "#,
+    )
+    .bind(cfg.after_hours)
+    .bind(&cfg.classes)
+    .bind(GENERATION_LOOKBACK_DAYS)
+    .execute(db)
+    .await?;
+    Ok(done.rows_affected())
+}
+
+/// Every `TASKS_CHECK_SECS` (default 300, `0` disables) generate re-inspection tasks on one replica
+pub fn spawn_scheduler(state: AppState) {
+    let secs = env::var("TASKS_CHECK_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(300);
+    if secs == 0 {
+        return;
+    }
+    let cfg = config();
+    info!(after_hours = cfg.after_hours, classes = ?cfg.classes, "re-inspection task scheduler started");
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(Duration::from_secs(secs));
+        loop {
+            tick.tick().await;
+            match jobs::run_singleton(&state.db, "task_generation", generate(&state.db)).await {
+                Ok(Some(Ok(n))) if n > 0 => info!(tasks = n, "re-inspection tasks created"),
+                Ok(Some(Ok(_))) | Ok(None) => {}
+                Ok(Some(Err(e))) | Err(e) => warn!(error = %e, "re-inspection task generation failed"),
+            }
+        }
+    });
+}
+
+// ==================== Handlers ====================
+
+/// GET /tasks/today — pending tasks due by the end of the site's operational day, overdue first
+pub async fn today_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_user(&headers)?;
+    let cal = calendar::site();
+    let sql = format!(
+        r#"
+        SELECT {}
+        FROM tasks t
+        LEFT JOIN events e ON e.id = t.event_id
+        LEFT JOIN fod_classes fc ON fc.id = e.class_id
+        WHERE t.status = 'pending'
+          AND t.due_at < (date_trunc('day', NOW() AT TIME ZONE $1 - make_interval(mins => $2))
+                          + make_interval(mins => $2) + INTERVAL '1 day') AT TIME ZONE $1
+        ORDER BY t.due_at
+        "#,
+        TASK_COLUMNS
+    );
+    let q = sqlx::query_as::<_, Task>(&sql).bind(&cal.tz).bind(cal.day_start).fetch_all(&st.db);
+    let tasks = perf::timed("tasks_today", String::new, q).await.map_err(internal)?;
+    Ok(Json(tasks))
+}
+
+/// POST /tasks/:id/complete — mark a task done
+pub async fn complete_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+    body: Option<Json<CompleteRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let claims = auth::require_user(&headers)?;
+    let note = body.and_then(|Json(b)| b.note).map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    let sql = format!(
+        r#"
+        WITH t AS (
+            UPDATE tasks SET status = 'done', completed_by = $2, completed_at = NOW(), note = $3
+            WHERE id = $1 AND status = 'pending'
+            RETURNING *
+        )
+        SELECT {}
+        FROM t
+        LEFT JOIN events e ON e.id = t.event_id
+        LEFT JOIN fod_classes fc ON fc.id = e.class_id
+        "#,
+        TASK_COLUMNS
+    );
+    let task = sqlx::query_as::<_, Task>(&sql)
+        .bind(id)
+        .bind(&claims.username)
+        .bind(note)
+        .fetch_optional(&st.db)
+        .await
+        .map_err(internal)?;
+    match task {
+        Some(task) => Ok(Json(task)),
+        None => {
+            let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM tasks WHERE id = $1)")
+                .bind(id)
+                .fetch_one(&st.db)
+                .await
+                .map_err(internal)?;
+            Err(if exists {
+                (StatusCode::CONFLICT, "Task is already done".to_string())
+            } else {
+                (StatusCode::NOT_FOUND, "Task not found".to_string())
+            })
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Optional TLS termination for FOD Detection Backend
+//! rustls listener with client-certificate verification for device-facing routes
+
+use axum::{
+    extract::{ConnectInfo, Request},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router,
+};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+};
+use rustls::{
+    server::AllowAnyAnonymousOrAuthenticatedClient, Certificate, PrivateKey, RootCertStore, ServerConfig,
+};
+use sha2::{Digest, Sha256};
+use std::{env, fs::File, io::BufReader, sync::{Arc, OnceLock}};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+use tracing::{debug, info, warn};
+
+/// Verified client certificate of the current connection
+#[derive(Clone, Debug)]
+pub struct ClientCert {
+    /// SHA-256 of the leaf certificate (DER), hex
+    pub fingerprint: String,
+}
+
+// ==================== Config ====================
+
+fn load_certs(path: &str) -> Result<Vec<Certificate>, String> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?);
+    let certs = rustls_pemfile::certs(&mut reader).map_err(|e| format!("Failed to read certificates from {}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(path: &str) -> Result<PrivateKey, String> {
+    let mut reader = BufReader::new(File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?);
+    while let Some(item) = rustls_pemfile::read_one(&mut reader).map_err(|e| format!("Failed to read {}: {}", path, e))? {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(k) | rustls_pemfile::Item::RSAKey(k) | rustls_pemfile::Item::ECKey(k) => {
+                return Ok(PrivateKey(k))
+            }
+            _ => {}
+        }
+    }
+    Err(format!("No private key found in {}", path))
+}
+
+/// Whether device routes demand a client certificate (env `TLS_CLIENT_CA_PATH` set)
+pub fn client_auth_enabled() -> bool {
+    static ENABLED: OnceLock<bool> = OnceLock::new();
+    *ENABLED.get_or_init(|| env::var("TLS_CLIENT_CA_PATH").map(|p| !p.is_empty()).unwrap_or(false))
+}
+
+/// Server config from `TLS_CERT_PATH` / `TLS_KEY_PATH`, None to serve plain HTTP.
+/// With `TLS_CLIENT_CA_PATH`, clients may present a certificate signed by that bundle.
+pub fn config_from_env() -> Result<Option<Arc<ServerConfig>>, String> {
+    let cert_path = env::var("TLS_CERT_PATH").ok().filter(|p| !p.is_empty());
+    let key_path = env::var("TLS_KEY_PATH").ok().filter(|p| !p.is_empty());
+    let (cert_path, key_path) = match (cert_path, key_path) {
+        (Some(c), Some(k)) => (c, k),
+        (None, None) if client_auth_enabled() => {
+            return Err("TLS_CLIENT_CA_PATH requires TLS_CERT_PATH and TLS_KEY_PATH".to_string())
+        }
+        (None, None) => return Ok(None),
+        _ => return Err("TLS_CERT_PATH and TLS_KEY_PATH must be set together".to_string()),
+    };
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match env::var("TLS_CLIENT_CA_PATH").ok().filter(|p| !p.is_empty()) {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(&ca_path)? {
+                roots.add(&cert).map_err(|e| format!("Invalid CA certificate in {}: {}", ca_path, e))?;
+            }
+            info!(ca = %ca_path, "client certificate verification enabled");
+            // Browsers reach the dashboard without a certificate; device routes check it per request
+            builder.with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed())
+        }
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder
+        .with_single_cert(load_certs(&cert_path)?, load_key(&key_path)?)
+        .map_err(|e| format!("Invalid TLS certificate/key: {}", e))?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Some(Arc::new(config)))
+}
+
+// ==================== Server ====================
+
+/// Accept TLS connections and serve `app`, tagging requests with the peer address and client certificate
+pub async fn serve(listener: TcpListener, app: Router, config: Arc<ServerConfig>) {
+    let acceptor = TlsAcceptor::from(config);
+    loop {
+        let (tcp, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!(error = %e, "accept failed");
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(tcp).await {
+                Ok(s) => s,
+                Err(e) => {
+                    debug!(%peer, error = %e, "TLS handshake failed");
+                    return;
+                }
+            };
+            let client_cert = stream.get_ref().1.peer_certificates().and_then(|certs| certs.first()).map(|c| ClientCert {
+                fingerprint: format!("{:x}", Sha256::digest(&c.0)),
+            });
+            let service = hyper::service::service_fn(move |mut req: hyper::Request<Incoming>| {
+                req.extensions_mut().insert(ConnectInfo(peer));
+                if let Some(cert) = &client_cert {
+                    req.extensions_mut().insert(cert.clone());
+                }
+                app.clone().oneshot(req)
+            });
+            if let Err(e) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!(%peer, error = %e, "connection closed with error");
+            }
+        });
+    }
+}
+
+// ==================== Middleware ====================
+
+/// Route middleware for device-facing routes: require a verified client certificate
+/// when client auth is configured, pass everything through otherwise
+pub async fn require_client_cert(req: Request, next: Next) -> Response {
+    if client_auth_enabled() {
+        let Some(cert) = req.extensions().get::<ClientCert>() else {
+            return (StatusCode::UNAUTHORIZED, "Client certificate required".to_string()).into_response();
+        };
+        debug!(client_cert = %cert.fingerprint, "device request");
+    }
+    next.run(req).await
+}
@@ -0,0 +1,369 @@
+//! ADS-B traffic for FOD Detection Backend
+//! Aircraft from the airport's SBS (BaseStation) feed, used to tell whether a runway has an aircraft on approach
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    net::TcpStream,
+};
+use tracing::{info, warn};
+
+use crate::{db::internal, geometry, perf, subscriptions::SEVERITIES, AppState};
+
+/// Buffered positions are written this often
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// Wait before reconnecting to a dropped feed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Bearing to the threshold and track must both be this close to the runway heading
+const APPROACH_CONE_DEG: f64 = 30.0;
+const METRES_PER_NM: f64 = 1852.0;
+
+// ==================== Config ====================
+
+/// Landing threshold of one runway end
+pub struct Runway {
+    name: String,
+    lat: f64,
+    lon: f64,
+    /// Magnetic heading from the designator, `01L` -> 10
+    heading: f64,
+}
+
+pub struct Config {
+    /// SBS feed, `host:port` (`ADSB_SBS_ADDR`, port 30003 on dump1090); no ingestion when unset
+    pub addr: Option<String>,
+    /// `ADSB_RUNWAYS=01L:13.6669:100.7506,19R:13.7118:100.7563`, see `parse_runways`
+    pub runways: Vec<Runway>,
+    /// Approach distance from the threshold (`ADSB_APPROACH_NM`, default 8)
+    pub approach_nm: f64,
+    /// Approach ceiling (`ADSB_APPROACH_FT`, default 3000)
+    pub approach_ft: i32,
+    /// Aircraft not heard from for this long are gone (`ADSB_STALE_SECS`, default 60)
+    pub stale_secs: i64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { addr: None, runways: Vec::new(), approach_nm: 8.0, approach_ft: 3000, stale_secs: 60 }
+    }
+}
+
+fn parse_runway(entry: &str) -> Option<Runway> {
+    let mut parts = entry.split(':').map(str::trim);
+    let name = parts.next()?.to_uppercase();
+    let (lat, lon) = (parts.next()?.parse().ok()?, parts.next()?.parse().ok()?);
+    let number: u32 = name.trim_end_matches(['L', 'R', 'C']).parse().ok().filter(|n| (1..=36).contains(n))?;
+    Some(Runway { name, lat, lon, heading: f64::from(number * 10) })
+}
+
+/// `runway:lat:lon,...` landing thresholds
+pub fn parse_runways(spec: &str) -> Result<Vec<Runway>, String> {
+    spec.split(',')
+        .filter(|e| !e.trim().is_empty())
+        .map(|e| parse_runway(e).ok_or_else(|| format!("runway entries must be runway:lat:lon, got {:?}", e)))
+        .collect()
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let default = Config::default();
+        Config {
+            addr: env::var("ADSB_SBS_ADDR").ok().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()),
+            runways: parse_runways(&env::var("ADSB_RUNWAYS").unwrap_or_default()).unwrap_or_else(|e| panic!("ADSB_RUNWAYS: {}", e)),
+            approach_nm: env::var("ADSB_APPROACH_NM").ok().and_then(|s| s.parse().ok()).filter(|&n: &f64| n > 0.0).unwrap_or(default.approach_nm),
+            approach_ft: env::var("ADSB_APPROACH_FT").ok().and_then(|s| s.parse().ok()).unwrap_or(default.approach_ft),
+            stale_secs: env::var("ADSB_STALE_SECS").ok().and_then(|s| s.parse().ok()).filter(|&s| s > 0).unwrap_or(default.stale_secs),
+        }
+    }
+}
+
+/// The feed's settings and liveness, carried in `AppState::traffic`
+pub struct Traffic {
+    pub config: Config,
+    /// Unix time of the last SBS message this process read, 0 before the first
+    last_message: AtomicI64,
+}
+
+impl Traffic {
+    pub fn new(config: Config) -> Self {
+        Traffic { config, last_message: AtomicI64::new(0) }
+    }
+
+    /// Position relative to a runway's landing threshold: metres along the centreline (negative
+    /// before the threshold) and right of it (negative left); None for runways `ADSB_RUNWAYS` doesn't know
+    pub fn runway_offset(&self, runway: &str, lat: f64, lon: f64) -> Option<(f64, f64)> {
+        let runway = runway.trim().to_uppercase();
+        let r = self.config.runways.iter().find(|r| r.name == runway)?;
+        let distance = geometry::distance_m(r.lat, r.lon, lat, lon);
+        let angle = (geometry::bearing_deg(r.lat, r.lon, lat, lon) - r.heading).to_radians();
+        Some((distance * angle.cos(), distance * angle.sin()))
+    }
+
+    /// Whether the feed is configured and has spoken recently; without it traffic says nothing
+    fn feed_live(&self) -> bool {
+        self.config.addr.is_some()
+            && OffsetDateTime::now_utc().unix_timestamp() - self.last_message.load(Ordering::Relaxed) < self.config.stale_secs
+    }
+}
+
+// ==================== Models ====================
+
+/// Last known state of an aircraft
+#[derive(Serialize, FromRow)]
+pub struct Aircraft {
+    pub icao: String,
+    pub callsign: Option<String>,
+    pub altitude_ft: Option<i32>,
+    pub ground_speed_kt: Option<f64>,
+    pub track: Option<f64>,
+    pub lat: Option<f64>,
+    pub lon: Option<f64>,
+    pub vertical_rate_fpm: Option<i32>,
+    pub on_ground: bool,
+    #[serde(with = "time::serde::rfc3339")]
+    pub updated_at: OffsetDateTime,
+    /// Runway it is on approach to
+    #[sqlx(skip)]
+    pub approaching: Option<String>,
+}
+
+impl Aircraft {
+    fn approach<'a>(&self, cfg: &'a Config) -> Option<&'a str> {
+        let (lat, lon, altitude) = (self.lat?, self.lon?, self.altitude_ft?);
+        if self.on_ground || altitude > cfg.approach_ft {
+            return None;
+        }
+        cfg.runways
+            .iter()
+            .find(|r| {
+                geometry::distance_m(lat, lon, r.lat, r.lon) <= cfg.approach_nm * METRES_PER_NM
+                    && geometry::heading_diff(geometry::bearing_deg(lat, lon, r.lat, r.lon), r.heading) <= APPROACH_CONE_DEG
+                    && self.track.is_none_or(|t| geometry::heading_diff(t, r.heading) <= APPROACH_CONE_DEG)
+            })
+            .map(|r| r.name.as_str())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TrafficParams {
+    /// Only aircraft on approach to this runway
+    pub runway: Option<String>,
+}
+
+// ==================== SBS ====================
+
+/// Fields of one SBS `MSG` line; each transmission type carries only some of them
+#[derive(Default)]
+struct Update {
+    callsign: Option<String>,
+    altitude_ft: Option<i32>,
+    ground_speed_kt: Option<f64>,
+    track: Option<f64>,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    vertical_rate_fpm: Option<i32>,
+    on_ground: Option<bool>,
+}
+
+impl Update {
+    /// Newer fields win, missing ones keep the older value
+    fn merge(&mut self, newer: Update) {
+        self.callsign = newer.callsign.or(self.callsign.take());
+        self.altitude_ft = newer.altitude_ft.or(self.altitude_ft);
+        self.ground_speed_kt = newer.ground_speed_kt.or(self.ground_speed_kt);
+        self.track = newer.track.or(self.track);
+        self.lat = newer.lat.or(self.lat);
+        self.lon = newer.lon.or(self.lon);
+        self.vertical_rate_fpm = newer.vertical_rate_fpm.or(self.vertical_rate_fpm);
+        self.on_ground = newer.on_ground.or(self.on_ground);
+    }
+}
+
+/// `MSG,3,1,1,8851B2,1,date,time,date,time,callsign,alt,gs,track,lat,lon,vrate,squawk,alert,emerg,spi,ground`
+fn parse_sbs(line: &str) -> Option<(String, Update)> {
+    let f: Vec<&str> = line.trim().split(',').map(str::trim).collect();
+    if f.len() < 22 || f[0] != "MSG" {
+        return None;
+    }
+    let icao = f[4].to_uppercase();
+    if icao.len() != 6 || !icao.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let update = Update {
+        callsign: Some(f[10].to_uppercase()).filter(|c| !c.is_empty() && c.len() <= 10),
+        altitude_ft: f[11].parse().ok(),
+        ground_speed_kt: f[12].parse().ok(),
+        track: f[13].parse().ok(),
+        lat: f[14].parse().ok().filter(|l: &f64| (-90.0..=90.0).contains(l)),
+        lon: f[15].parse().ok().filter(|l: &f64| (-180.0..=180.0).contains(l)),
+        vertical_rate_fpm: f[16].parse().ok(),
+        // BaseStation writes -1 for true
+        on_ground: match f[21] {
+            "-1" | "1" => Some(true),
+            "0" => Some(false),
+            _ => None,
+        },
+    };
+    Some((icao, update))
+}
+
+// ==================== Queries ====================
+
+/// Upsert buffered updates and drop aircraft not heard from within the stale window
+async fn flush(db: &PgPool, cfg: &Config, updates: HashMap<String, Update>) -> Result<(), (StatusCode, String)> {
+    if !updates.is_empty() {
+        let mut qb = QueryBuilder::<Postgres>::new(
+            "INSERT INTO traffic (icao, callsign, altitude_ft, ground_speed_kt, track, lat, lon, vertical_rate_fpm, on_ground) ",
+        );
+        qb.push_values(&updates, |mut b, (icao, u)| {
+            b.push_bind(icao)
+                .push_bind(&u.callsign)
+                .push_bind(u.altitude_ft)
+                .push_bind(u.ground_speed_kt)
+                .push_bind(u.track)
+                .push_bind(u.lat)
+                .push_bind(u.lon)
+                .push_bind(u.vertical_rate_fpm)
+                .push_bind(u.on_ground.unwrap_or(false));
+        });
+        qb.push(
+            r#"
+            ON CONFLICT (icao) DO UPDATE SET
+                callsign = COALESCE(EXCLUDED.callsign, traffic.callsign),
+                altitude_ft = COALESCE(EXCLUDED.altitude_ft, traffic.altitude_ft),
+                ground_speed_kt = COALESCE(EXCLUDED.ground_speed_kt, traffic.ground_speed_kt),
+                track = COALESCE(EXCLUDED.track, traffic.track),
+                lat = COALESCE(EXCLUDED.lat, traffic.lat),
+                lon = COALESCE(EXCLUDED.lon, traffic.lon),
+                vertical_rate_fpm = COALESCE(EXCLUDED.vertical_rate_fpm, traffic.vertical_rate_fpm),
+                on_ground = EXCLUDED.on_ground,
+                updated_at = NOW()
+            "#,
+        );
+        let q = qb.build().execute(db);
+        perf::timed("upsert_traffic", || format!("aircraft={}", updates.len()), q).await.map_err(internal)?;
+    }
+    let q = sqlx::query("DELETE FROM traffic WHERE updated_at < NOW() - make_interval(secs => $1)").bind(cfg.stale_secs as f64).execute(db);
+    perf::timed("prune_traffic", String::new, q).await.map_err(internal)?;
+    Ok(())
+}
+
+/// Aircraft heard from within the stale window, with the runway each is approaching
+pub async fn current(db: &PgPool, traffic: &Traffic) -> Result<Vec<Aircraft>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, Aircraft>(
+        r#"
+        SELECT icao, callsign, altitude_ft, ground_speed_kt, track, lat, lon, vertical_rate_fpm, on_ground, updated_at
+        FROM traffic
+        WHERE updated_at >= NOW() - make_interval(secs => $1)
+        ORDER BY altitude_ft NULLS LAST, icao
+        "#,
+    )
+    .bind(traffic.config.stale_secs as f64)
+    .fetch_all(db);
+    let mut aircraft = perf::timed("list_traffic", String::new, q).await.map_err(internal)?;
+    for a in &mut aircraft {
+        a.approaching = a.approach(&traffic.config).map(str::to_string);
+    }
+    Ok(aircraft)
+}
+
+/// Alert severity for debris on `runway`: one level up with an aircraft on approach, None
+/// (held) for the lowest level while the runway is quiet, unchanged without a live feed or
+/// for runways `ADSB_RUNWAYS` doesn't know
+pub async fn weigh_severity(db: &PgPool, traffic: &Traffic, runway: &str, severity: &str) -> Result<Option<String>, (StatusCode, String)> {
+    let runway = runway.trim().to_uppercase();
+    if !traffic.feed_live() || !traffic.config.runways.iter().any(|r| r.name == runway) {
+        return Ok(Some(severity.to_string()));
+    }
+    let busy = current(db, traffic).await?.iter().any(|a| a.approaching.as_deref() == Some(runway.as_str()));
+    let level = SEVERITIES.iter().position(|s| *s == severity);
+    Ok(match level {
+        Some(i) if busy => Some(SEVERITIES[(i + 1).min(SEVERITIES.len() - 1)].to_string()),
+        Some(0) => None,
+        _ => Some(severity.to_string()),
+    })
+}
+
+// ==================== Ingestion ====================
+
+/// Read the SBS feed, reconnecting when it drops
+async fn read_feed(traffic: Arc<Traffic>, addr: String, pending: Arc<Mutex<HashMap<String, Update>>>) {
+    loop {
+        match TcpStream::connect(&addr).await {
+            Ok(stream) => {
+                info!(addr = %addr, "ADS-B feed connected");
+                let mut lines = BufReader::new(stream).lines();
+                loop {
+                    match lines.next_line().await {
+                        Ok(Some(line)) => {
+                            let Some((icao, update)) = parse_sbs(&line) else { continue };
+                            traffic.last_message.store(OffsetDateTime::now_utc().unix_timestamp(), Ordering::Relaxed);
+                            pending.lock().unwrap().entry(icao).or_default().merge(update);
+                        }
+                        Ok(None) => {
+                            warn!(addr = %addr, "ADS-B feed closed");
+                            break;
+                        }
+                        Err(e) => {
+                            warn!(addr = %addr, error = %e, "ADS-B feed read failed");
+                            break;
+                        }
+                    }
+                }
+            }
+            Err(e) => warn!(addr = %addr, error = %e, "ADS-B feed unreachable"),
+        }
+        tokio::time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+/// Ingest `ADSB_SBS_ADDR` into the traffic table; does nothing when unset
+pub fn spawn_ingester(state: AppState) {
+    let Some(addr) = state.traffic.config.addr.clone() else { return };
+    info!(addr = %addr, runways = state.traffic.config.runways.len(), "ADS-B ingester started");
+    let pending: Arc<Mutex<HashMap<String, Update>>> = Arc::default();
+    tokio::spawn(read_feed(state.traffic.clone(), addr, pending.clone()));
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            tick.tick().await;
+            let updates = std::mem::take(&mut *pending.lock().unwrap());
+            if let Err((_, e)) = flush(&state.db, &state.traffic.config, updates).await {
+                warn!(error = %e, "traffic flush failed");
+            }
+        }
+    });
+}
+
+// ==================== Handlers ====================
+
+/// GET /dashboard/traffic?runway= — aircraft currently heard on ADS-B, each with the runway
+/// it is on approach to
+pub async fn traffic_handler(
+    State(st): State<AppState>,
+    Query(p): Query<TrafficParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let mut aircraft = current(&st.db, &st.traffic).await?;
+    if let Some(runway) = p.runway.map(|r| r.trim().to_uppercase()) {
+        aircraft.retain(|a| a.approaching.as_deref() == Some(runway.as_str()));
+    }
+    Ok(Json(json!({ "feed_live": st.traffic.feed_live(), "aircraft": aircraft })))
+}
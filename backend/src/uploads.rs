@@ -0,0 +1,165 @@
+//! Direct uploads for FOD Detection Backend
+//! Presigned PUT URLs so devices send frames straight to object storage and pass only the key to inference
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::{env, sync::OnceLock, time::Duration};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{db::internal, logging, perf, s3, signing, AppState};
+
+/// Frame types a device may upload
+const ALLOWED_TYPES: &[&str] = &["image/jpeg", "image/png", "image/webp"];
+
+/// Event meta key holding the upload-store key of the frame an event was detected on
+pub const META_KEY: &str = "image_key";
+
+/// Largest stored frame sent on to the detector
+pub const MAX_FRAME_BYTES: usize = 32 * 1024 * 1024;
+
+/// Object store for uploaded frames: `UPLOAD_S3_*`, else the attachment store
+pub fn from_env() -> Option<s3::Bucket> {
+    s3::Bucket::from_env("UPLOAD_S3").or_else(|| s3::Bucket::from_env("ATTACHMENT_S3"))
+}
+
+/// The upload store carried in `AppState::uploads`
+pub fn bucket(st: &AppState) -> Result<&s3::Bucket, (StatusCode, String)> {
+    st.uploads.as_deref().ok_or((StatusCode::SERVICE_UNAVAILABLE, "Upload storage is not configured".to_string()))
+}
+
+/// How long a presigned URL and its token stay valid (`UPLOAD_URL_TTL_SECS`, default 900, at most 7 days)
+fn ttl() -> Duration {
+    static SECS: OnceLock<u64> = OnceLock::new();
+    Duration::from_secs(*SECS.get_or_init(|| {
+        env::var("UPLOAD_URL_TTL_SECS").ok().and_then(|s| s.parse().ok()).unwrap_or(900).clamp(60, 7 * 24 * 3600)
+    }))
+}
+
+/// Only the SHA-256 of an upload token is stored
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Whether `bytes` start like one of the accepted image types
+pub fn check_frame(bytes: &[u8]) -> Result<(), (StatusCode, String)> {
+    let image = bytes.starts_with(&[0xFF, 0xD8])
+        || bytes.starts_with(b"\x89PNG")
+        || (bytes.len() >= 12 && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP");
+    if !image {
+        return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, "Stored object is not a JPEG, PNG or WebP image".to_string()));
+    }
+    if bytes.len() > MAX_FRAME_BYTES {
+        return Err((StatusCode::PAYLOAD_TOO_LARGE, format!("Frames are at most {} bytes", MAX_FRAME_BYTES)));
+    }
+    Ok(())
+}
+
+// ==================== Claims ====================
+
+/// Take the upload at `key` for inference if `token` is its live, unused upload token;
+/// returns the file name the device gave
+pub async fn claim(db: &PgPool, key: &str, token: &str) -> Result<Option<String>, (StatusCode, String)> {
+    let q = sqlx::query_as::<_, (Option<String>,)>(
+        r#"
+        UPDATE uploads SET used_at = NOW()
+        WHERE object_key = $1 AND token_hash = $2 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING filename
+        "#,
+    )
+    .bind(key)
+    .bind(hash_token(token))
+    .fetch_optional(db);
+    let (filename,) = perf::timed("claim_upload", || format!("key={}", key), q)
+        .await
+        .map_err(internal)?
+        .ok_or((StatusCode::FORBIDDEN, "Upload token is invalid, expired or already used".to_string()))?;
+    Ok(filename)
+}
+
+/// Hand a claimed upload back when inference failed, so the device can retry with the same token
+pub async fn release(db: &PgPool, key: &str) {
+    if let Err(e) = sqlx::query("UPDATE uploads SET used_at = NULL WHERE object_key = $1").bind(key).execute(db).await {
+        warn!(key = %key, error = %e, "upload not released after failed inference");
+    }
+}
+
+// ==================== Request Types ====================
+
+#[derive(Deserialize, Default)]
+pub struct PresignRequest {
+    /// One of image/jpeg, image/png or image/webp; default image/jpeg
+    pub content_type: Option<String>,
+    /// File name on the device, passed on to the detector
+    pub filename: Option<String>,
+}
+
+// ==================== Handlers ====================
+
+/// POST /uploads/presign — `{content_type?, filename?}` a presigned PUT URL for one frame and the
+/// `upload_token` that goes with its `key` when the device then asks for inference
+pub async fn presign_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    body: Option<Json<PresignRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let bucket = bucket(&st)?;
+    let req = body.map(|Json(b)| b).unwrap_or_default();
+    let content_type = req.content_type.as_deref().map(|c| c.trim().to_lowercase()).unwrap_or_else(|| "image/jpeg".to_string());
+    if !ALLOWED_TYPES.contains(&content_type.as_str()) {
+        return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, format!("Uploads of type {} are not accepted", content_type)));
+    }
+    let filename = req.filename.map(|f| f.trim().to_string()).filter(|f| !f.is_empty());
+    if filename.as_ref().is_some_and(|f| f.len() > 255) {
+        return Err((StatusCode::BAD_REQUEST, "filename is at most 255 characters".to_string()));
+    }
+    let device = headers.get(signing::DEVICE_ID_HEADER).and_then(|d| d.to_str().ok()).map(str::to_string);
+
+    let id = Uuid::new_v4();
+    let key = format!("uploads/site={}/{}/{}", logging::site_id(), device.as_deref().unwrap_or("unsigned"), id);
+    let url = bucket.presign_put(&key, ttl().as_secs()).map_err(|e| {
+        error!(key = %key, error = %e, "upload presign failed");
+        (StatusCode::INTERNAL_SERVER_ERROR, "Could not presign the upload".to_string())
+    })?;
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let expires_at = OffsetDateTime::now_utc() + ttl();
+
+    let q = sqlx::query(
+        r#"
+        INSERT INTO uploads (id, object_key, content_type, filename, device_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+    )
+    .bind(id)
+    .bind(&key)
+    .bind(&content_type)
+    .bind(&filename)
+    .bind(&device)
+    .bind(hash_token(&token))
+    .bind(expires_at)
+    .execute(&st.db);
+    perf::timed("create_upload", || format!("key={}", key), q).await.map_err(internal)?;
+
+    info!(upload_id = %id, device = ?device, content_type = %content_type, "upload presigned");
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "upload_id": id,
+            "key": key,
+            "url": url,
+            "method": "PUT",
+            "headers": { "content-type": content_type },
+            "upload_token": token,
+            "expires_at": expires_at.format(&Rfc3339).map_err(internal)?,
+        })),
+    ))
+}
@@ -0,0 +1,249 @@
+//! Per-key usage accounting for FOD Detection Backend
+//! Requests, uploaded bytes and inference time per signing device and UTC day, counted in
+//! memory and written in batches, with /admin/usage and optional monthly quotas
+
+use axum::{
+    body::HttpBody,
+    extract::{MatchedPath, Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use std::{
+    collections::HashMap,
+    env,
+    sync::{atomic::Ordering, Mutex},
+    time::{Duration as StdDuration, Instant},
+};
+use time::{macros::format_description, Date, Duration, OffsetDateTime};
+use tracing::warn;
+
+use crate::{auth, db::internal, perf, signing::VerifiedDevice, AppState};
+
+/// Routes whose wall time counts as inference
+const INFERENCE_ROUTES: [&str; 2] = ["/proxy/detect", "/infer/by-ref"];
+/// How often counted usage is written to the database
+const FLUSH_INTERVAL: StdDuration = StdDuration::from_secs(5);
+
+// ==================== Config ====================
+
+#[derive(Default)]
+pub struct Config {
+    /// Most requests per key per calendar month in UTC (`USAGE_MONTHLY_REQUESTS`, unset is unlimited)
+    pub monthly_requests: Option<i64>,
+    /// Most uploaded bytes per key per calendar month in UTC (`USAGE_MONTHLY_BYTES`, unset is unlimited)
+    pub monthly_bytes: Option<i64>,
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        let quota = |name: &str| {
+            env::var(name)
+                .ok()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse().unwrap_or_else(|_| panic!("{} must be a number, got {:?}", name, s)))
+        };
+        Config { monthly_requests: quota("USAGE_MONTHLY_REQUESTS"), monthly_bytes: quota("USAGE_MONTHLY_BYTES") }
+    }
+}
+
+/// Usage of one key on one day, counted but not yet written
+#[derive(Clone, Copy, Default)]
+struct Tally {
+    requests: i64,
+    bytes_in: i64,
+    inference_ms: i64,
+}
+
+/// Quotas, and the usage this process counted since the last flush
+pub struct Meter {
+    pub config: Config,
+    pending: Mutex<HashMap<(String, Date), Tally>>,
+}
+
+impl Meter {
+    pub fn new(config: Config) -> Meter {
+        Meter { config, pending: Mutex::default() }
+    }
+
+    fn add(&self, api_key_id: &str, day: Date, tally: Tally) {
+        let mut pending = self.pending.lock().unwrap();
+        let t = pending.entry((api_key_id.to_string(), day)).or_default();
+        t.requests += tally.requests;
+        t.bytes_in += tally.bytes_in;
+        t.inference_ms += tally.inference_ms;
+    }
+
+    /// Unwritten requests and bytes of `api_key_id` in the month of `today`
+    fn pending_this_month(&self, api_key_id: &str, today: Date) -> (i64, i64) {
+        let pending = self.pending.lock().unwrap();
+        pending
+            .iter()
+            .filter(|((key, day), _)| key == api_key_id && (day.year(), day.month()) == (today.year(), today.month()))
+            .fold((0, 0), |(r, b), (_, t)| (r + t.requests, b + t.bytes_in))
+    }
+}
+
+// ==================== Models ====================
+
+time::serde::format_description!(iso_date, Date, "[year]-[month]-[day]");
+
+#[derive(Serialize, FromRow)]
+pub struct DailyUsage {
+    pub api_key_id: String,
+    #[serde(with = "iso_date")]
+    pub day: Date,
+    pub requests: i64,
+    pub bytes_in: i64,
+    pub inference_ms: i64,
+}
+
+#[derive(Deserialize)]
+pub struct UsageParams {
+    pub api_key_id: Option<String>,
+    /// First and last day, `YYYY-MM-DD`; default the last 30 days
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+// ==================== Metering ====================
+
+/// Month-to-date requests and bytes for one key
+async fn this_month(db: &PgPool, api_key_id: &str) -> Result<(i64, i64), sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT COALESCE(SUM(requests), 0)::BIGINT, COALESCE(SUM(bytes_in), 0)::BIGINT
+        FROM usage
+        WHERE api_key_id = $1 AND day >= date_trunc('month', NOW() AT TIME ZONE 'UTC')::date
+        "#,
+    )
+    .bind(api_key_id)
+    .fetch_one(db)
+    .await
+}
+
+async fn record(db: &PgPool, api_key_id: &str, day: Date, tally: Tally) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO usage (api_key_id, day, requests, bytes_in, inference_ms)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (api_key_id, day) DO UPDATE SET
+            requests = usage.requests + EXCLUDED.requests,
+            bytes_in = usage.bytes_in + EXCLUDED.bytes_in,
+            inference_ms = usage.inference_ms + EXCLUDED.inference_ms
+        "#,
+    )
+    .bind(api_key_id)
+    .bind(day)
+    .bind(tally.requests)
+    .bind(tally.bytes_in)
+    .bind(tally.inference_ms)
+    .execute(db)
+    .await
+    .map(|_| ())
+}
+
+/// Write the counted usage; rows that fail are kept for the next flush. Also run on shutdown
+/// so the last interval's counts leave with the process.
+pub async fn flush(st: &AppState) {
+    let pending = std::mem::take(&mut *st.usage.pending.lock().unwrap());
+    for ((key, day), tally) in pending {
+        if let Err(e) = record(&st.db, &key, day, tally).await {
+            warn!(api_key_id = %key, error = %e, "usage not recorded, retrying on the next flush");
+            st.usage.add(&key, day, tally);
+        }
+    }
+}
+
+/// Write counted usage every `FLUSH_INTERVAL`, off the device requests' path
+pub fn spawn_flusher(state: AppState) {
+    tokio::spawn(async move {
+        let mut tick = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            tick.tick().await;
+            flush(&state).await;
+        }
+    });
+}
+
+/// Count a device request against its key, after turning it away with 429 if the key is past a
+/// monthly quota. The quota read is the only query made inline; the count itself is written by
+/// `spawn_flusher`. Must sit inside `signing::verify`: unsigned requests carry no key and pass
+/// uncounted, as does everything while the database is read-only
+pub async fn meter(State(st): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(VerifiedDevice(key)) = req.extensions().get::<VerifiedDevice>().cloned() else {
+        return next.run(req).await;
+    };
+    if st.read_only.load(Ordering::Relaxed) {
+        return next.run(req).await;
+    }
+    // verify buffered the body, so the hint is exact
+    let bytes_in = req.body().size_hint().exact().unwrap_or(0) as i64;
+    let inference = req.extensions().get::<MatchedPath>().is_some_and(|p| INFERENCE_ROUTES.contains(&p.as_str()));
+    let today = OffsetDateTime::now_utc().date();
+
+    let quota = &st.usage.config;
+    if quota.monthly_requests.is_some() || quota.monthly_bytes.is_some() {
+        match this_month(&st.db, &key).await {
+            Ok((requests, bytes)) => {
+                let (pending_requests, pending_bytes) = st.usage.pending_this_month(&key, today);
+                if quota.monthly_requests.is_some_and(|q| requests + pending_requests >= q) {
+                    return (StatusCode::TOO_MANY_REQUESTS, "Monthly request quota exceeded".to_string()).into_response();
+                }
+                if quota.monthly_bytes.is_some_and(|q| bytes + pending_bytes + bytes_in > q) {
+                    return (StatusCode::TOO_MANY_REQUESTS, "Monthly upload quota exceeded".to_string()).into_response();
+                }
+            }
+            // Fail open: a usage read must not take devices offline
+            Err(e) => warn!(api_key_id = %key, error = %e, "usage quota check failed"),
+        }
+    }
+
+    let started = Instant::now();
+    let response = next.run(req).await;
+    let inference_ms = if inference { started.elapsed().as_millis() as i64 } else { 0 };
+    st.usage.add(&key, today, Tally { requests: 1, bytes_in, inference_ms });
+    response
+}
+
+// ==================== Handlers ====================
+
+/// GET /admin/usage?api_key_id=&from=&to= — daily totals per key, newest day first (admin)
+pub async fn list_handler(
+    State(st): State<AppState>,
+    headers: HeaderMap,
+    Query(p): Query<UsageParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    auth::require_admin(&headers)?;
+    // What this instance counted so far is included; other replicas' lands on their next flush
+    flush(&st).await;
+    let parse = |v: &Option<String>, name: &str| {
+        v.as_deref()
+            .map(|d| Date::parse(d.trim(), format_description!("[year]-[month]-[day]")))
+            .transpose()
+            .map_err(|_| (StatusCode::BAD_REQUEST, format!("{} must be YYYY-MM-DD", name)))
+    };
+    let to = parse(&p.to, "to")?.unwrap_or_else(|| OffsetDateTime::now_utc().date());
+    let from = parse(&p.from, "from")?.unwrap_or(to - Duration::days(29));
+    if from > to {
+        return Err((StatusCode::BAD_REQUEST, "from must not be after to".to_string()));
+    }
+    let q = sqlx::query_as::<_, DailyUsage>(
+        r#"
+        SELECT api_key_id, day, requests, bytes_in, inference_ms
+        FROM usage
+        WHERE day BETWEEN $1 AND $2 AND ($3::TEXT IS NULL OR api_key_id = $3)
+        ORDER BY day DESC, api_key_id
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .bind(&p.api_key_id)
+    .fetch_all(&st.db);
+    let rows = perf::timed("list_usage", || format!("from={} to={} key={:?}", from, to, p.api_key_id), q).await.map_err(internal)?;
+    Ok(Json(rows))
+}
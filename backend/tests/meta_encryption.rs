@@ -0,0 +1,107 @@
+//! Encrypted event meta fields end to end: sealed at rest, opened for admins, re-sealed on rotation
+//! The encryption keys are read once per process, so these tests run in their own binary
+
+#[allow(dead_code)]
+mod support;
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use serde_json::{json, Value};
+use support::TestApp;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+const OLD_KEY: [u8; 32] = [1; 32];
+const NEW_KEY: [u8; 32] = [2; 32];
+
+/// `operator_note` sealed under `new`; `old` still opens what it sealed
+fn configure_keys() {
+    std::env::set_var("META_ENCRYPTED_FIELDS", "operator_note");
+    std::env::set_var("META_ENCRYPTION_KEYS", format!("new:{},old:{}", B64.encode(NEW_KEY), B64.encode(OLD_KEY)));
+}
+
+async fn ingest(t: &TestApp, note: &str) -> String {
+    let body = json!({
+        "ts": OffsetDateTime::now_utc().format(&Rfc3339).unwrap(),
+        "object_class": "Bolt",
+        "object_count": 1,
+        "confidence": 0.8,
+        "latitude": 13.69,
+        "longitude": 100.75,
+        "source": "camera",
+        "source_ref": "CAM-01",
+        "meta": { "operator_note": note, "zone": "RWY-01L" },
+    });
+    let (status, event) = t.post_json("/events/ingest", &body).await;
+    assert_eq!(status, StatusCode::OK, "{}", event);
+    event["id"].as_str().unwrap().to_string()
+}
+
+async fn stored_meta(t: &TestApp, id: &str) -> Value {
+    sqlx::query_scalar("SELECT meta FROM event_details WHERE event_id = $1::uuid").bind(id).fetch_one(&t.db).await.unwrap()
+}
+
+#[tokio::test]
+async fn meta_fields_are_stored_sealed_and_opened_for_admins() {
+    configure_keys();
+    let Some(t) = TestApp::spawn().await else { return };
+    let id = ingest(&t, "call the tower").await;
+
+    let stored = stored_meta(&t, &id).await;
+    assert_eq!((&stored["operator_note"]["enc"], &stored["operator_note"]["kid"]), (&json!("aes256gcm-v1"), &json!("new")), "{}", stored);
+    assert!(!stored.to_string().contains("call the tower"));
+    assert_eq!(stored["zone"], "RWY-01L");
+
+    let uri = format!("/events/{}", id);
+    let (_, event) = t.get_as(&TestApp::token("admin", "admin"), &uri).await;
+    assert_eq!(event["meta"]["operator_note"], "call the tower", "{}", event);
+    let (_, event) = t.get_as(&TestApp::token("crew1", "user"), &uri).await;
+    assert_eq!(event["meta"]["operator_note"], "[encrypted]");
+}
+
+#[tokio::test]
+async fn rotation_reseals_old_key_fields_under_the_current_key() {
+    configure_keys();
+    let Some(t) = TestApp::spawn().await else { return };
+    let admin = TestApp::token("admin", "admin");
+    let current = ingest(&t, "current").await;
+    let old = ingest(&t, "placeholder").await;
+
+    // As sealed before `new` was added in front of `old`
+    let nonce = [7u8; 12];
+    let ct = Aes256Gcm::new_from_slice(&OLD_KEY)
+        .unwrap()
+        .encrypt(&Nonce::from(nonce), Payload { msg: json!("sealed long ago").to_string().as_bytes(), aad: b"operator_note" })
+        .unwrap();
+    let envelope = json!({ "enc": "aes256gcm-v1", "kid": "old", "nonce": B64.encode(nonce), "ct": B64.encode(ct) });
+    sqlx::query("UPDATE event_details SET meta = jsonb_set(meta, '{operator_note}', $2) WHERE event_id = $1::uuid")
+        .bind(&old)
+        .bind(&envelope)
+        .execute(&t.db)
+        .await
+        .unwrap();
+    let (_, event) = t.get_as(&admin, &format!("/events/{}", old)).await;
+    assert_eq!(event["meta"]["operator_note"], "sealed long ago", "{}", event);
+
+    let (status, _) = t.post_json_as(&TestApp::token("crew1", "user"), "/admin/meta-keys/rotate", &json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, rotated) = t.post_json_as(&admin, "/admin/meta-keys/rotate", &json!({})).await;
+    assert_eq!(status, StatusCode::OK, "{}", rotated);
+    assert_eq!(rotated, json!({ "kid": "new", "scanned": 1, "updated": 1 }));
+
+    let resealed = stored_meta(&t, &old).await;
+    assert_eq!(resealed["operator_note"]["kid"], "new");
+    assert_ne!(resealed["operator_note"]["ct"], envelope["ct"]);
+    let untouched = stored_meta(&t, &current).await;
+    assert_eq!(untouched["operator_note"]["kid"], "new");
+    let (_, event) = t.get_as(&admin, &format!("/events/{}", old)).await;
+    assert_eq!(event["meta"]["operator_note"], "sealed long ago");
+    let (_, event) = t.get_as(&admin, &format!("/events/{}", current)).await;
+    assert_eq!(event["meta"]["operator_note"], "current");
+
+    let (_, again) = t.post_json_as(&admin, "/admin/meta-keys/rotate", &json!({})).await;
+    assert_eq!(again, json!({ "kid": "new", "scanned": 0, "updated": 0 }));
+}
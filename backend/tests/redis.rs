@@ -0,0 +1,179 @@
+//! Replicas sharing one Redis: response cache, rate limits, idempotency keys, signature nonces and
+//! the live feed
+//! Runs against `TEST_REDIS_URL`, or a Redis container when Docker is available
+
+#[allow(dead_code)]
+mod support;
+
+use axum::{
+    body::{to_bytes, Body},
+    http::{Request, StatusCode},
+    Router,
+};
+use backend_rust::{build_app, live, shared, signing};
+use serde_json::{json, Value};
+use std::{env, path::Path, time::Duration};
+use support::TestApp;
+use testcontainers_modules::{
+    redis::Redis,
+    testcontainers::{runners::AsyncRunner, ContainerAsync},
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+/// Redis URL, with the container to keep alive when one was started; None when neither is
+/// available
+async fn redis() -> Option<(String, Option<ContainerAsync<Redis>>)> {
+    if let Ok(url) = env::var("TEST_REDIS_URL") {
+        return Some((url, None));
+    }
+    if env::var("DOCKER_HOST").is_err() && !Path::new("/var/run/docker.sock").exists() {
+        eprintln!("skipping: set TEST_REDIS_URL or make Docker available");
+        return None;
+    }
+    let container = Redis::default().start().await.expect("start redis container");
+    let port = container.get_host_port_ipv4(6379).await.expect("redis port");
+    Some((format!("redis://127.0.0.1:{}", port), Some(container)))
+}
+
+/// One test's replicas; the prefix keeps tests sharing a server apart
+fn config(url: &str) -> shared::Config {
+    shared::Config {
+        redis_url: Some(url.to_string()),
+        prefix: format!("fod-test:{}", Uuid::new_v4().simple()),
+        cache_ttl: Duration::from_secs(60),
+        rate_limit: 4,
+        ..Default::default()
+    }
+}
+
+fn ingest_body(class: &str) -> Value {
+    json!({
+        "ts": OffsetDateTime::now_utc().format(&Rfc3339).unwrap(),
+        "object_class": class,
+        "object_count": 1,
+        "confidence": 0.8,
+        "latitude": 13.69,
+        "longitude": 100.75,
+        "source": "camera",
+        "source_ref": "CAM-01",
+    })
+}
+
+async fn json_of(app: &Router, req: Request<Body>) -> (StatusCode, Value) {
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    let bytes = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn replicas_share_cached_responses_rate_limits_and_idempotency_keys() {
+    let Some((url, _container)) = redis().await else { return };
+    let Some(t) = TestApp::spawn().await else { return };
+    let config = config(&url);
+    let (a, b) = (build_app(t.state.clone().with_shared(config.clone())), build_app(t.state.clone().with_shared(config)));
+    let minute = || OffsetDateTime::now_utc().unix_timestamp() / 60;
+    let started = minute();
+    let crew = TestApp::token("crew1", "user");
+    let get = |uri: &str| Request::get(uri).header("authorization", format!("Bearer {}", crew)).body(Body::empty()).unwrap();
+
+    let resp = a.clone().oneshot(get("/dashboard/summary")).await.unwrap();
+    assert!(resp.headers().get("x-cache").is_none());
+    let first = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    t.post_json("/events/ingest", &ingest_body("Bolt")).await;
+    let resp = b.clone().oneshot(get("/dashboard/summary")).await.unwrap();
+    assert_eq!(resp.headers()["x-cache"], "hit");
+    assert_eq!(to_bytes(resp.into_body(), usize::MAX).await.unwrap(), first);
+
+    // A retry on the other replica gets the first response back, and Postgres never saw the key
+    let key = Uuid::new_v4().to_string();
+    let ingest = || {
+        Request::post("/events/ingest")
+            .header("content-type", "application/json")
+            .header("idempotency-key", &key)
+            .body(Body::from(ingest_body("Nut").to_string()))
+            .unwrap()
+    };
+    let before = t.event_count().await;
+    let (status, created) = json_of(&a, ingest()).await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    let (_, retried) = json_of(&b, ingest()).await;
+    assert_eq!(retried["id"], created["id"]);
+    assert_eq!(t.event_count().await, before + 1);
+    let stored: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ingest_idempotency").fetch_one(&t.db).await.unwrap();
+    assert_eq!(stored, 0);
+
+    // crew1's four requests this minute, counted across both replicas
+    assert_eq!(json_of(&a, get("/events/recent")).await.0, StatusCode::OK);
+    assert_eq!(json_of(&b, get("/events/recent")).await.0, StatusCode::OK);
+    if minute() != started {
+        return;
+    }
+    assert_eq!(json_of(&a, get("/events/recent")).await.0, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(json_of(&b, get("/dashboard/summary")).await.0, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn signed_requests_replayed_to_another_replica_are_rejected() {
+    let Some((url, _container)) = redis().await else { return };
+    let Some(t) = TestApp::spawn().await else { return };
+    let config = config(&url);
+    let replica = |config: shared::Config| {
+        let keys = [("EDGE-01".to_string(), b"edge-secret".to_vec())].into();
+        build_app(t.state.clone().with_shared(config).with_signing(signing::Config { mode: signing::SigningMode::Required, keys }))
+    };
+    let (a, b) = (replica(config.clone()), replica(config));
+    let body = ingest_body("Bolt").to_string();
+    let headers = signing::sign("EDGE-01", b"edge-secret", "POST", "/events/ingest", body.as_bytes());
+    let ingest = || {
+        let mut req = Request::post("/events/ingest").header("content-type", "application/json");
+        for (name, value) in &headers {
+            req = req.header(*name, value);
+        }
+        req.body(Body::from(body.clone())).unwrap()
+    };
+
+    let before = t.event_count().await;
+    let (status, created) = json_of(&a, ingest()).await;
+    assert_eq!(status, StatusCode::OK, "{}", created);
+    assert_eq!(json_of(&b, ingest()).await.0, StatusCode::UNAUTHORIZED, "nonce replayed on the other replica");
+    assert_eq!(json_of(&a, ingest()).await.0, StatusCode::UNAUTHORIZED);
+    assert_eq!(t.event_count().await, before + 1);
+}
+
+#[tokio::test]
+async fn live_events_fan_out_through_redis() {
+    let Some((url, _container)) = redis().await else { return };
+    let Some(t) = TestApp::spawn().await else { return };
+    let state = t.state.clone().with_shared(config(&url));
+    let mut rx = live::subscribe();
+    live::spawn_listener(state.clone());
+
+    // Whatever arrives on the Redis channel reaches local clients
+    let redis = state.shared.redis().unwrap();
+    let mut relayed = None;
+    for _ in 0..50 {
+        redis.publish(live::CHANNEL, &json!({ "class_name": "Direct" }).to_string()).await.unwrap();
+        if let Ok(Ok(payload)) = tokio::time::timeout(Duration::from_millis(100), rx.recv()).await {
+            relayed = Some(payload);
+            break;
+        }
+    }
+    let relayed: Value = serde_json::from_str(&relayed.expect("Redis messages never reached local clients")).unwrap();
+    assert_eq!(relayed["class_name"], "Direct");
+
+    // Inserted events get there by way of the replica relaying Postgres notifications
+    let mut seen = false;
+    for _ in 0..50 {
+        t.post_json("/events/ingest", &ingest_body("Glass")).await;
+        while let Ok(Ok(payload)) = tokio::time::timeout(Duration::from_millis(100), rx.recv()).await {
+            seen |= serde_json::from_str::<Value>(&payload).unwrap()["class_name"] == "Glass";
+        }
+        if seen {
+            break;
+        }
+    }
+    assert!(seen, "ingested events never came back through Redis");
+}
@@ -0,0 +1,37 @@
+//! `*_FILE` secrets: read once at startup, picked up again only on refresh
+//! The secret cache is per process, so these tests run in their own binary
+
+use backend_rust::secrets;
+use std::{env, fs};
+
+#[tokio::test]
+async fn rotated_secret_file_is_read_on_refresh_only() {
+    let dir = env::temp_dir().join(format!("fod-secrets-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("jwt_secret");
+    fs::write(&path, "first-secret\n").unwrap();
+    env::set_var("ROTATED_TEST_SECRET_FILE", &path);
+    let http = reqwest::Client::new();
+
+    secrets::init(&http).await.unwrap();
+    assert_eq!(secrets::get("ROTATED_TEST_SECRET").as_deref(), Some("first-secret"));
+
+    // Lookups don't touch the file, so a rotation or a removal goes unseen until a refresh
+    fs::write(&path, "second-secret").unwrap();
+    assert_eq!(secrets::get("ROTATED_TEST_SECRET").as_deref(), Some("first-secret"));
+    secrets::refresh(&http).await;
+    assert_eq!(secrets::get("ROTATED_TEST_SECRET").as_deref(), Some("second-secret"));
+
+    // A file that can't be read on refresh keeps the last good value
+    fs::remove_file(&path).unwrap();
+    secrets::refresh(&http).await;
+    assert_eq!(secrets::get("ROTATED_TEST_SECRET").as_deref(), Some("second-secret"));
+
+    // An unreadable secret file fails startup
+    env::remove_var("ROTATED_TEST_SECRET_FILE");
+    env::set_var("MISSING_TEST_SECRET_FILE", dir.join("missing"));
+    let err = secrets::init(&http).await.unwrap_err();
+    assert!(err.contains("MISSING_TEST_SECRET"), "{}", err);
+    env::remove_var("MISSING_TEST_SECRET_FILE");
+    fs::remove_dir_all(&dir).unwrap();
+}
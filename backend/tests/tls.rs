@@ -0,0 +1,95 @@
+//! TLS termination with client certificates: config errors, and device routes behind a
+//! registered certificate while the dashboard stays open
+//! `TLS_CLIENT_CA_PATH` is read once per process, so these tests run in their own binary
+
+#[allow(dead_code)]
+mod support;
+
+use backend_rust::tls;
+use rcgen::{BasicConstraints, Certificate, CertificateParams, ExtendedKeyUsagePurpose, IsCa};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::{env, fs, path::Path};
+use support::TestApp;
+
+fn ca(name: &str) -> Certificate {
+    let mut params = CertificateParams::new(vec![]);
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.distinguished_name.push(rcgen::DnType::CommonName, name);
+    Certificate::from_params(params).unwrap()
+}
+
+/// Leaf signed by `ca`, as PEM certificate and PEM (PKCS#8) key
+fn leaf(ca: &Certificate, name: &str, usage: ExtendedKeyUsagePurpose) -> (String, String) {
+    let mut params = CertificateParams::new(vec![name.to_string()]);
+    params.extended_key_usages = vec![usage];
+    let cert = Certificate::from_params(params).unwrap();
+    (cert.serialize_pem_with_signer(ca).unwrap(), cert.serialize_private_key_pem())
+}
+
+fn write(dir: &Path, name: &str, pem: &str) -> String {
+    let path = dir.join(name);
+    fs::write(&path, pem).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn device_routes_need_a_certificate_from_the_client_ca() {
+    let dir = env::temp_dir().join(format!("fod-tls-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let (device_ca, other_ca) = (ca("FOD devices"), ca("Someone else"));
+    let (server_cert, server_key) = leaf(&device_ca, "localhost", ExtendedKeyUsagePurpose::ServerAuth);
+    let ca_path = write(&dir, "ca.pem", &device_ca.serialize_pem().unwrap());
+    let cert_path = write(&dir, "server.pem", &server_cert);
+    let key_path = write(&dir, "server.key", &server_key);
+    env::set_var("TLS_CLIENT_CA_PATH", &ca_path);
+
+    // Configuration mistakes come back as errors for main to report
+    env::remove_var("TLS_CERT_PATH");
+    env::remove_var("TLS_KEY_PATH");
+    assert!(tls::config_from_env().unwrap_err().contains("requires TLS_CERT_PATH"));
+    env::set_var("TLS_CERT_PATH", &cert_path);
+    assert!(tls::config_from_env().unwrap_err().contains("set together"));
+    env::set_var("TLS_KEY_PATH", dir.join("missing.key"));
+    assert!(tls::config_from_env().unwrap_err().contains("missing.key"));
+    env::set_var("TLS_KEY_PATH", &cert_path);
+    assert!(tls::config_from_env().unwrap_err().contains("No private key"));
+    env::set_var("TLS_KEY_PATH", &key_path);
+    let config = tls::config_from_env().unwrap().expect("TLS configured");
+
+    let Some(t) = TestApp::spawn().await else { return };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(tls::serve(listener, t.app.clone(), config));
+    let client = |identity: Option<(String, String)>| {
+        let mut builder = reqwest::Client::builder()
+            .use_rustls_tls()
+            .add_root_certificate(reqwest::Certificate::from_pem(device_ca.serialize_pem().unwrap().as_bytes()).unwrap());
+        if let Some((cert, key)) = identity {
+            builder = builder.identity(reqwest::Identity::from_pem(format!("{}{}", key, cert).as_bytes()).unwrap());
+        }
+        builder.build().unwrap()
+    };
+    let url = |path: &str| format!("https://localhost:{}{}", port, path);
+
+    // No certificate: the dashboard works, device routes don't
+    let anonymous = client(None);
+    assert_eq!(anonymous.get(url("/health")).send().await.unwrap().status(), StatusCode::OK);
+    assert_eq!(anonymous.get(url("/devices/EDGE-01/config")).send().await.unwrap().status(), StatusCode::UNAUTHORIZED);
+
+    // A certificate from the CA identifies a device once registered for it
+    let identity = leaf(&device_ca, "EDGE-01", ExtendedKeyUsagePurpose::ClientAuth);
+    let device = client(Some(identity.clone()));
+    assert_eq!(device.get(url("/devices/EDGE-01/config")).send().await.unwrap().status(), StatusCode::FORBIDDEN);
+    let der = rustls_pemfile::certs(&mut identity.0.as_bytes()).unwrap().remove(0);
+    let uri = format!("/devices/EDGE-01/certificates/{:x}", Sha256::digest(&der));
+    let admin = anonymous.put(url(&uri)).bearer_auth(TestApp::token("admin", "admin")).json(&serde_json::json!({}));
+    assert_eq!(admin.send().await.unwrap().status(), StatusCode::OK);
+    assert_eq!(device.get(url("/devices/EDGE-01/config")).send().await.unwrap().status(), StatusCode::OK);
+    assert_eq!(device.get(url("/health")).send().await.unwrap().status(), StatusCode::OK);
+
+    // A certificate from another CA fails the handshake
+    let stranger = client(Some(leaf(&other_ca, "EDGE-01", ExtendedKeyUsagePurpose::ClientAuth)));
+    assert!(stranger.get(url("/devices/EDGE-01/config")).send().await.is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,15 +1,29 @@
 export const runtime = "nodejs";
 
+import { cookies } from "next/headers";
+import { clearSessionHeaders } from "@/lib/session";
+
 export async function POST() {
+  // revoke refresh token ที่ backend (ถ้ามี) เพื่อให้ session ใช้ต่อไม่ได้จริง
+  const base = process.env.BACKEND_BASE_URL;
+  const cookieStore = await cookies();
+  const refreshToken = cookieStore.get("fod_refresh")?.value;
+
+  if (base && refreshToken) {
+    try {
+      await fetch(`${base.replace(/\/$/, "")}/auth/logout`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ refresh_token: refreshToken }),
+      });
+    } catch {
+      // backend ล่มก็ยังลบ cookie ฝั่ง browser
+    }
+  }
+
   // ลบ cookie โดย set Max-Age=0 (expire ทันที)
   return new Response(
     JSON.stringify({ ok: true, message: "Logged out" }),
-    {
-      status: 200,
-      headers: {
-        "content-type": "application/json",
-        "set-cookie": "fod_session=; HttpOnly; Path=/; SameSite=Strict; Max-Age=0",
-      },
-    }
+    { status: 200, headers: clearSessionHeaders() }
   );
 }
@@ -2,6 +2,7 @@ export const runtime = "nodejs";
 export const dynamic = "force-dynamic";
 
 import { cookies } from "next/headers";
+import { clearSessionHeaders, sessionHeaders } from "@/lib/session";
 
 export async function GET() {
   const base = process.env.BACKEND_BASE_URL;
@@ -11,12 +12,14 @@ export async function GET() {
       { status: 500, headers: { "content-type": "application/json" } }
     );
   }
+  const backend = base.replace(/\/$/, "");
 
   // อ่าน token จาก HttpOnly cookie
   const cookieStore = await cookies();
   const token = cookieStore.get("fod_session")?.value;
+  const refreshToken = cookieStore.get("fod_refresh")?.value;
 
-  if (!token) {
+  if (!token && !refreshToken) {
     return new Response(
       JSON.stringify({ error: "Not authenticated" }),
       { status: 401, headers: { "content-type": "application/json" } }
@@ -25,15 +28,39 @@ export async function GET() {
 
   try {
     // ส่ง token ไปให้ Rust verify
-    const res = await fetch(`${base.replace(/\/$/, "")}/auth/me`, {
-      headers: { Authorization: `Bearer ${token}` },
-    });
+    if (token) {
+      const res = await fetch(`${backend}/auth/me`, {
+        headers: { Authorization: `Bearer ${token}` },
+      });
+      if (res.status !== 401 || !refreshToken) {
+        const data = await res.json();
+        return new Response(JSON.stringify(data), {
+          status: res.status,
+          headers: { "content-type": "application/json" },
+        });
+      }
+    }
 
-    const data = await res.json();
-    return new Response(JSON.stringify(data), {
-      status: res.status,
+    // access token หมดอายุ — ขอใหม่ด้วย refresh token (rotate ทุกครั้ง)
+    const refreshed = await fetch(`${backend}/auth/refresh`, {
+      method: "POST",
       headers: { "content-type": "application/json" },
+      body: JSON.stringify({ refresh_token: refreshToken }),
     });
+    if (!refreshed.ok) {
+      return new Response(
+        JSON.stringify({ error: "Session expired" }),
+        { status: 401, headers: clearSessionHeaders() }
+      );
+    }
+    const session = await refreshed.json();
+
+    const res = await fetch(`${backend}/auth/me`, {
+      headers: { Authorization: `Bearer ${session.token}` },
+    });
+    const data = await res.json();
+    const headers = sessionHeaders(session);
+    return new Response(JSON.stringify(data), { status: res.status, headers });
   } catch {
     return new Response(
       JSON.stringify({ error: "Failed to connect to backend" }),
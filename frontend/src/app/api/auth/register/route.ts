@@ -1,5 +1,7 @@
 export const runtime = "nodejs";
 
+import { sessionHeaders } from "@/lib/session";
+
 export async function POST(request: Request) {
   const base = process.env.BACKEND_BASE_URL;
   if (!base) {
@@ -26,20 +28,13 @@ export async function POST(request: Request) {
       });
     }
 
-    // สมัครแล้ว login อัตโนมัติ — set HttpOnly cookie ทันที
-    const { token, username, role } = data;
-    const cookieMaxAge = 60 * 60 * 8; // 8 ชั่วโมง
+    // สมัครแล้ว login อัตโนมัติ — set cookie access + refresh token ทันที
+    const { username, role } = data;
 
-    return new Response(
-      JSON.stringify({ ok: true, username, role }),
-      {
-        status: 200,
-        headers: {
-          "content-type": "application/json",
-          "set-cookie": `fod_session=${token}; HttpOnly; Path=/; SameSite=Strict; Max-Age=${cookieMaxAge}`,
-        },
-      }
-    );
+    return new Response(JSON.stringify({ ok: true, username, role }), {
+      status: 200,
+      headers: sessionHeaders(data),
+    });
   } catch {
     return new Response(
       JSON.stringify({ error: "Failed to connect to backend" }),
@@ -0,0 +1,32 @@
+// ==================== Session Cookies ====================
+// fod_session = access JWT อายุสั้น, fod_refresh = refresh token สำหรับ /auth/refresh
+// ทั้งคู่เป็น HttpOnly เพื่อป้องกัน XSS
+
+export interface BackendSession {
+  token: string;
+  refresh_token: string;
+  expires_in: number;
+  refresh_expires_in: number;
+}
+
+// header ที่ set cookie ทั้งสองตัวจาก response ของ backend
+export function sessionHeaders(session: BackendSession): Headers {
+  const headers = new Headers({ "content-type": "application/json" });
+  headers.append(
+    "set-cookie",
+    `fod_session=${session.token}; HttpOnly; Path=/; SameSite=Strict; Max-Age=${session.expires_in}`
+  );
+  headers.append(
+    "set-cookie",
+    `fod_refresh=${session.refresh_token}; HttpOnly; Path=/; SameSite=Strict; Max-Age=${session.refresh_expires_in}`
+  );
+  return headers;
+}
+
+// header ที่ลบ cookie ทั้งสองตัว (Max-Age=0)
+export function clearSessionHeaders(): Headers {
+  const headers = new Headers({ "content-type": "application/json" });
+  headers.append("set-cookie", "fod_session=; HttpOnly; Path=/; SameSite=Strict; Max-Age=0");
+  headers.append("set-cookie", "fod_refresh=; HttpOnly; Path=/; SameSite=Strict; Max-Age=0");
+  return headers;
+}
@@ -15,10 +15,11 @@ export function middleware(request: NextRequest) {
     return NextResponse.next();
   }
 
-  // ตรวจ cookie fod_session
+  // ตรวจ cookie fod_session (หรือ fod_refresh ที่ /api/auth/me จะใช้ขอ token ใหม่)
   const token = request.cookies.get("fod_session")?.value;
+  const refreshToken = request.cookies.get("fod_refresh")?.value;
 
-  if (!token) {
+  if (!token && !refreshToken) {
     // redirect ไป /login และจำ path เดิมไว้
     const loginUrl = new URL("/login", request.url);
     loginUrl.searchParams.set("from", pathname);